@@ -5,18 +5,27 @@
 #![deny(warnings)]
 #![allow(clippy::needless_borrow)]
 
+mod replay;
 mod server;
+pub mod shutdown;
 pub mod staking;
+mod state_sync;
+mod verify;
 
 use {
     crate::api::{
-        query_server::query_api, submission_server::submission_api::SubmissionApi,
+        backup, invariants, mem_guard::MEM_GUARD, query_server::query_api,
+        submission_server::submission_api::SubmissionApi,
     },
     config::abci::{global_cfg::CFG, ABCIConfig},
     futures::executor::ThreadPool,
     lazy_static::lazy_static,
-    ledger::converter::{LOWLEVEL_DATA_MAX, LOWLEVEL_DATA_MIN},
+    ledger::{
+        converter::{LOWLEVEL_DATA_MAX, LOWLEVEL_DATA_MIN},
+        store::LedgerState,
+    },
     ruc::*,
+    state_sync::StateSyncApp,
     std::{
         env, fs, mem,
         net::SocketAddr,
@@ -26,6 +35,7 @@ use {
         },
         thread,
     },
+    tracing::info,
 };
 
 lazy_static! {
@@ -40,6 +50,17 @@ lazy_static! {
 
 /// Starting findorad
 pub fn run() -> Result<()> {
+    let imported = if let Some(snapshot) = CFG.ledger_snapshot_import.as_deref() {
+        let h = LedgerState::import_snapshot(snapshot, &CFG.ledger_dir).c(d!())?;
+        info!(
+            "ledger snapshot imported, block count: {}, tendermint height: {}",
+            h.block_commit_count, h.tendermint_height
+        );
+        Some(h)
+    } else {
+        None
+    };
+
     let basedir = {
         fs::create_dir_all(&CFG.ledger_dir).c(d!())?;
         Some(CFG.ledger_dir.as_str())
@@ -52,29 +73,100 @@ pub fn run() -> Result<()> {
 
     LOWLEVEL_DATA_MIN.swap(CFG.checkpoint.lowlevel_data_min as i64, Ordering::Relaxed);
     LOWLEVEL_DATA_MAX.swap(CFG.checkpoint.lowlevel_data_max as i64, Ordering::Relaxed);
+
+    if CFG.ledger_verify {
+        return verify::verify(&CFG.ledger_dir).c(d!());
+    }
+
+    if let Some(other) = CFG.ledger_replay_diff.as_deref() {
+        return replay::replay_diff(&CFG.ledger_dir, other).c(d!());
+    }
+
     if CFG.enable_query_service {
         env::set_var("FINDORAD_KEEP_HIST", "1");
     }
 
+    let addr_str = format!("{}:{}", config.abci_host, config.abci_port);
+    let addr = addr_str.parse::<SocketAddr>().c(d!())?;
+    let tendermint_reply =
+        format!("{}:{}", config.tendermint_host, config.tendermint_port);
+
+    if CFG.ledger_snapshot_export.is_none() && StateSyncApp::should_defer() {
+        info!("the ledger is empty, waiting for a state sync snapshot");
+        let build: state_sync::Builder = Box::new(move || {
+            let app = server::ABCISubmissionServer::new(
+                basedir,
+                tendermint_reply.clone(),
+                CFG.enable_eth_api_secondary,
+            )
+            .c(d!())?;
+            start_services(&app, &config).c(d!())?;
+            Ok(app)
+        });
+        abci::run(addr, StateSyncApp::deferred(build));
+        return Ok(());
+    }
+
     let app = server::ABCISubmissionServer::new(
         basedir,
-        format!("{}:{}", config.tendermint_host, config.tendermint_port),
+        tendermint_reply,
         CFG.enable_eth_api_secondary,
     )?;
 
+    if let Some(h) = imported.as_ref() {
+        app.la
+            .read()
+            .get_committed_state()
+            .read()
+            .verify_snapshot(h)
+            .c(d!("the imported snapshot does not match its commitment"))?;
+    }
+
+    if let Some(snapshot) = CFG.ledger_snapshot_export.as_deref() {
+        let h = app
+            .la
+            .read()
+            .get_committed_state()
+            .read()
+            .export_snapshot(snapshot, CFG.zstd_level)
+            .c(d!())?;
+        info!(
+            "ledger snapshot exported to {}, block count: {}, tendermint height: {}",
+            snapshot, h.block_commit_count, h.tendermint_height
+        );
+        return Ok(());
+    }
+
+    start_services(&app, &config).c(d!())?;
+    abci::run(addr, StateSyncApp::ready(app));
+
+    Ok(())
+}
+
+// Start the services on the ledger of `app`
+fn start_services(
+    app: &server::ABCISubmissionServer,
+    config: &ABCIConfig,
+) -> Result<()> {
+    shutdown::register_ledger(app.la.read().borrowable_ledger_state());
+    backup::start(app.la.read().borrowable_ledger_state());
+
     if CFG.enable_query_service {
         let submission_service_hdr = Arc::clone(&app.la);
 
         let query_service_hdr = submission_service_hdr.read().borrowable_ledger_state();
-        pnk!(query_api::service::start_query_server(
+        let qs = pnk!(query_api::service::start_query_server(
             Arc::clone(&query_service_hdr),
             &[
                 (&config.abci_host, config.query_port),
                 (&config.abci_host, config.ledger_port)
             ],
-        ))
-        .write()
-        .update();
+        ));
+        qs.write().update();
+        invariants::start(qs);
+
+        server::callback::sig_cache::register();
+        MEM_GUARD.start();
 
         let submission_host = config.abci_host.clone();
         let submission_port = config.submission_port;
@@ -109,10 +201,5 @@ pub fn run() -> Result<()> {
         ));
     }
 
-    let addr_str = format!("{}:{}", config.abci_host, config.abci_port);
-    let addr = addr_str.parse::<SocketAddr>().c(d!())?;
-
-    abci::run(addr, app);
-
     Ok(())
 }
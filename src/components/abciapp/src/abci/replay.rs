@@ -0,0 +1,171 @@
+//!
+//! # Replay of the ledger by two binaries in lockstep
+//!
+//! `abcid --ledger-replay-diff <binary>` dumps the blocks of the ledger, then
+//! replays them one by one in two child processes on empty ledgers, one
+//! running the current binary and one running `<binary>`, with the same
+//! arguments. At the first block after which their states differ, both
+//! children dump the entries touched by the block, and the differences are
+//! reported, the current binary being taken as the expected side.
+//!
+//! Meant to find what breaks the consensus between two releases,
+//! `<binary>` must support this flag too.
+//!
+
+use {
+    super::verify::report,
+    ledger::store::{
+        verify::{diff_dumps, diff_traces, BlockTrace, DumpScope, StateDump},
+        LedgerState,
+    },
+    ruc::*,
+    serde::de::DeserializeOwned,
+    std::{
+        env,
+        fs::File,
+        io::{self, BufRead, BufReader, Lines, Write},
+        path::{Path, PathBuf},
+        process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    },
+    tracing::info,
+};
+
+// set in the child processes
+const DUMP_VAR: &str = "LEDGER_REPLAY_DUMP";
+const DIR_VAR: &str = "LEDGER_REPLAY_DIR";
+
+// prefix of the replies of a child, its other output is passed through
+const REPLY: &str = "LEDGER_REPLAY ";
+
+/// Replay the ledger in `ledger_dir` by the current binary and `other`,
+/// an error is returned if they diverge
+pub fn replay_diff(ledger_dir: &str, other: &str) -> Result<()> {
+    if let (Ok(dump), Ok(dir)) = (env::var(DUMP_VAR), env::var(DIR_VAR)) {
+        return serve(&dump, &dir).c(d!());
+    }
+
+    if !Path::new(ledger_dir).join("ledger_status").exists() {
+        return Err(eg!(format!("no ledger found in {ledger_dir}")));
+    }
+    env::set_var("BNC_DATA_DIR", format!("{ledger_dir}/__bnc__"));
+
+    let ledger = LedgerState::load_or_init(ledger_dir).c(d!())?;
+    let tmp = globutils::fresh_tmp_dir();
+    let dump = tmp.join("blocks").to_string_lossy().into_owned();
+    let n = ledger.dump_blocks(&dump).c(d!())?;
+    drop(ledger);
+    info!("{} blocks dumped to {}, replaying", n, &dump);
+
+    let mut a =
+        Replayer::spawn(&env::current_exe().c(d!())?, &dump, tmp.join("a")).c(d!())?;
+    let mut b = Replayer::spawn(Path::new(other), &dump, tmp.join("b")).c(d!())?;
+
+    loop {
+        let ta: Option<BlockTrace> = a.call("step").c(d!())?;
+        let tb: Option<BlockTrace> = b.call("step").c(d!())?;
+        let (ta, tb) = match (ta, tb) {
+            (Some(ta), Some(tb)) => (ta, tb),
+            (None, None) => {
+                info!("{} blocks replayed, no divergence", n);
+                return Ok(());
+            }
+            (ta, tb) => {
+                return Err(eg!(format!(
+                    "the replays stop at different heights: {:?} and {:?}",
+                    ta.map(|t| t.height),
+                    tb.map(|t| t.height)
+                )));
+            }
+        };
+
+        let divergences = diff_traces(&ta, &tb);
+        if divergences.is_empty() {
+            continue;
+        }
+        report("block", &divergences);
+
+        let mut scope = ta.scope;
+        scope.extend(&tb.scope);
+        let cmd = format!("dump {}", serde_json::to_string(&scope).c(d!())?);
+        let da: StateDump = a.call(&cmd).c(d!())?;
+        let db: StateDump = b.call(&cmd).c(d!())?;
+        report("state", &diff_dumps(&da, &db));
+
+        return Err(eg!(format!("the replays diverge at height {}", ta.height)));
+    }
+}
+
+// A child process replaying the blocks on command
+struct Replayer {
+    name: String,
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Replayer {
+    fn spawn(bin: &Path, dump: &str, dir: PathBuf) -> Result<Self> {
+        let mut child = Command::new(bin)
+            .args(env::args().skip(1))
+            .env(DUMP_VAR, dump)
+            .env(DIR_VAR, &dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .c(d!(bin.display()))?;
+
+        Ok(Replayer {
+            name: bin.display().to_string(),
+            stdin: child.stdin.take().c(d!())?,
+            stdout: BufReader::new(child.stdout.take().c(d!())?).lines(),
+            _child: child,
+        })
+    }
+
+    fn call<T: DeserializeOwned>(&mut self, cmd: &str) -> Result<T> {
+        writeln!(self.stdin, "{cmd}").c(d!(&self.name))?;
+        self.stdin.flush().c(d!(&self.name))?;
+        for line in self.stdout.by_ref() {
+            let line = line.c(d!(&self.name))?;
+            match line.strip_prefix(REPLY) {
+                Some(reply) => return serde_json::from_str(reply).c(d!(&self.name)),
+                None => println!("[{}] {}", self.name, line),
+            }
+        }
+        Err(eg!(format!("{} exited", self.name)))
+    }
+}
+
+// runs in the child processes, until the stdin is closed
+fn serve(dump: &str, dir: &str) -> Result<()> {
+    env::set_var("BNC_DATA_DIR", format!("{dir}/__bnc__"));
+    let mut ledger =
+        LedgerState::new(&format!("{dir}/ledger"), Some("replay")).c(d!())?;
+    let mut blocks = BufReader::new(File::open(dump).c(d!(dump))?).lines();
+
+    let stdin = io::stdin();
+    for cmd in stdin.lock().lines() {
+        let cmd = cmd.c(d!())?;
+        let reply = if cmd == "step" {
+            match blocks.next() {
+                Some(line) => {
+                    let block = serde_json::from_str(&line.c(d!())?).c(d!())?;
+                    let trace = ledger.replay_traced(block).c(d!())?;
+                    serde_json::to_string(&Some(trace)).c(d!())?
+                }
+                None => serde_json::to_string(&None::<BlockTrace>).c(d!())?,
+            }
+        } else if let Some(scope) = cmd.strip_prefix("dump ") {
+            let scope: DumpScope = serde_json::from_str(scope).c(d!())?;
+            serde_json::to_string(&ledger.dump_state(&scope)).c(d!())?
+        } else {
+            return Err(eg!(format!("unknown command: {cmd}")));
+        };
+
+        let mut out = io::stdout();
+        writeln!(out, "{REPLY}{reply}").c(d!())?;
+        out.flush().c(d!())?;
+    }
+
+    Ok(())
+}
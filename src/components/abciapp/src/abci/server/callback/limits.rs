@@ -0,0 +1,205 @@
+//!
+//! # Resource limits of transactions and blocks
+//!
+//! Configured in the checkpoint file, so all validators of a network share them,
+//! unless they have been set on chain, see `ChainParams`.
+//!
+
+use {
+    config::abci::global_cfg::CFG,
+    ledger::{data_model::Transaction, staking::params::ChainParams},
+    std::sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The txn is larger than `max_txn_bytes`
+pub const CODE_TXN_TOO_LARGE: u32 = 3;
+
+/// The txn contains more operations than `max_txn_ops`
+pub const CODE_TOO_MANY_OPS: u32 = 4;
+
+/// The block has delivered `max_block_txns` txns
+pub const CODE_BLOCK_FULL: u32 = 5;
+
+// txns delivered in the current block
+static BLOCK_TXN_CNT: AtomicU64 = AtomicU64::new(0);
+
+/// A violated limit, `(code, log)` of the ABCI response
+pub type Violation = (u32, String);
+
+// `check_tx` always enforces the limits, as a local policy of the mem-pool,
+// while `deliver_tx` enforces them since the checkpoint
+#[inline(always)]
+fn enforced_in_deliver_tx(td_height: i64) -> bool {
+    CFG.checkpoint.txn_limits_height <= td_height
+}
+
+/// Called in `begin_block`
+#[inline(always)]
+pub fn block_begun() {
+    BLOCK_TXN_CNT.store(0, Ordering::Relaxed);
+}
+
+/// The limits of a txn in `deliver_tx` before it is decoded,
+/// it is counted in the current block once they are enforced
+pub fn check_delivered(
+    tx: &[u8],
+    td_height: i64,
+    params: &ChainParams,
+) -> Option<Violation> {
+    if enforced_in_deliver_tx(td_height) {
+        check_block_capacity(params).or_else(|| check_size(tx, params))
+    } else {
+        None
+    }
+}
+
+/// The limits of a decoded txn in `deliver_tx`
+pub fn check_delivered_ops(
+    tx: &Transaction,
+    td_height: i64,
+    params: &ChainParams,
+) -> Option<Violation> {
+    if enforced_in_deliver_tx(td_height) {
+        check_ops(tx, params)
+    } else {
+        None
+    }
+}
+
+#[allow(missing_docs)]
+pub fn check_size(tx: &[u8], params: &ChainParams) -> Option<Violation> {
+    let max = params.max_txn_bytes();
+    if max < tx.len() as u64 {
+        Some((
+            CODE_TXN_TOO_LARGE,
+            format!("txn of {} bytes exceeds the limit of {max}", tx.len()),
+        ))
+    } else {
+        None
+    }
+}
+
+#[allow(missing_docs)]
+pub fn check_ops(tx: &Transaction, params: &ChainParams) -> Option<Violation> {
+    let max = params.max_txn_ops();
+    let n = tx.body.operations.len() as u64;
+    if max < n {
+        Some((
+            CODE_TOO_MANY_OPS,
+            format!("txn of {n} operations exceeds the limit of {max}"),
+        ))
+    } else {
+        None
+    }
+}
+
+// Count a txn delivered in the current block
+fn check_block_capacity(params: &ChainParams) -> Option<Violation> {
+    let max = params.max_block_txns();
+    let n = BLOCK_TXN_CNT.fetch_add(1, Ordering::Relaxed) + 1;
+    if max < n {
+        Some((
+            CODE_BLOCK_FULL,
+            format!("block has reached the limit of {max} txns"),
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        ledger::{
+            data_model::{AssetRules, AssetTypeCode},
+            store::helpers::create_definition_transaction,
+        },
+        rand_chacha::ChaChaRng,
+        rand_core::SeedableRng,
+        zei::XfrKeyPair,
+    };
+
+    fn params(max_txn_bytes: u64, max_txn_ops: u64, max_block_txns: u64) -> ChainParams {
+        ChainParams {
+            max_txn_bytes: Some(max_txn_bytes),
+            max_txn_ops: Some(max_txn_ops),
+            max_block_txns: Some(max_block_txns),
+            ..Default::default()
+        }
+    }
+
+    // a txn of `n` asset definitions
+    fn tx(n: usize) -> Transaction {
+        let kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+        let mut tx = create_definition_transaction(
+            &AssetTypeCode::gen_random(),
+            &kp,
+            AssetRules::default(),
+            None,
+            0,
+        )
+        .unwrap();
+        let op = tx.body.operations[0].clone();
+        (1..n).for_each(|_| tx.add_operation(op.clone()));
+        tx
+    }
+
+    #[test]
+    fn size_and_ops() {
+        let params = params(10, 2, 1);
+        assert!(check_size(&[0; 10], &params).is_none());
+        assert_eq!(
+            Some(CODE_TXN_TOO_LARGE),
+            check_size(&[0; 11], &params).map(|v| v.0)
+        );
+        assert!(check_size(&[], &params).is_none());
+
+        assert!(check_ops(&tx(2), &params).is_none());
+        assert_eq!(
+            Some(CODE_TOO_MANY_OPS),
+            check_ops(&tx(3), &params).map(|v| v.0)
+        );
+        let none = ChainParams {
+            max_txn_ops: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(CODE_TOO_MANY_OPS),
+            check_ops(&tx(1), &none).map(|v| v.0)
+        );
+    }
+
+    // the only test touching the count of the block
+    #[test]
+    fn deliver_tx() {
+        let h = CFG.checkpoint.txn_limits_height;
+        let params = params(10, 2, 2);
+        let code =
+            |tx: &[u8], td_height| check_delivered(tx, td_height, &params).map(|v| v.0);
+        block_begun();
+
+        // neither enforced nor counted below the checkpoint
+        for _ in 0..3 {
+            assert_eq!(None, code(&[0; 11], h - 1));
+        }
+        assert!(check_delivered_ops(&tx(3), h - 1, &params).is_none());
+
+        // a txn refused for its size still takes its place in the block
+        assert_eq!(None, code(&[0; 10], h));
+        assert_eq!(Some(CODE_TXN_TOO_LARGE), code(&[0; 11], h));
+        assert_eq!(Some(CODE_BLOCK_FULL), code(&[0; 10], h));
+        assert_eq!(Some(CODE_BLOCK_FULL), code(&[0; 10], h + 1));
+        assert_eq!(
+            Some(CODE_TOO_MANY_OPS),
+            check_delivered_ops(&tx(3), h, &params).map(|v| v.0)
+        );
+        assert!(check_delivered_ops(&tx(2), h, &params).is_none());
+
+        // counted again from `begin_block`
+        block_begun();
+        assert_eq!(None, code(&[0; 10], h));
+        assert_eq!(None, code(&[0; 10], h));
+        assert_eq!(Some(CODE_BLOCK_FULL), code(&[0; 1], h));
+    }
+}
@@ -5,14 +5,22 @@
 use chrono::Local;
 use fp_storage::BorrowMut;
 
+mod limits;
+mod query;
+pub(crate) mod sig_cache;
 mod utils;
 
 use {
     crate::{
-        abci::{server::ABCISubmissionServer, staking, IN_SAFE_ITV, IS_EXITING, POOL},
+        abci::{
+            server::ABCISubmissionServer, staking, state_sync, IN_SAFE_ITV, IS_EXITING,
+            POOL,
+        },
         api::{
+            health, logging,
             query_server::BLOCK_CREATED,
-            submission_server::{convert_tx, try_tx_catalog, TxCatalog},
+            request_id::txn_event,
+            submission_server::{convert_tx, try_tx_catalog, TxCatalog, TxnHandle},
         },
     },
     abci::{
@@ -31,14 +39,15 @@ use {
     lazy_static::lazy_static,
     ledger::{
         converter::is_convert_account,
-        data_model::{Operation, Transaction, ASSET_TYPE_FRA},
+        data_model::{Operation, Transaction, TxnRejection, ASSET_TYPE_FRA},
         staking::{
-            evm::EVM_STAKING, FF_ADDR_EXTRA_120_0000, FF_ADDR_LIST, KEEP_HIST,
-            VALIDATOR_UPDATE_BLOCK_ITV,
+            evm::EVM_STAKING, params::ChainParams, FF_ADDR_EXTRA_120_0000, FF_ADDR_LIST,
+            KEEP_HIST, VALIDATOR_UPDATE_BLOCK_ITV,
         },
         store::{
             api_cache,
             fbnc::{new_mapx, Mapx},
+            genesis::GenesisState,
         },
         LEDGER_TENDERMINT_BLOCK_HEIGHT,
     },
@@ -85,6 +94,8 @@ lazy_static! {
 // pub const ENABLE_FRC20_HEIGHT: i64 = 150_1000;
 
 pub fn info(s: &mut ABCISubmissionServer, req: &RequestInfo) -> ResponseInfo {
+    health::abci_req_received();
+
     let mut resp = ResponseInfo::new();
 
     let mut la = s.la.write();
@@ -127,6 +138,11 @@ pub fn info(s: &mut ABCISubmissionServer, req: &RequestInfo) -> ResponseInfo {
 }
 
 pub fn query(s: &mut ABCISubmissionServer, req: &RequestQuery) -> ResponseQuery {
+    if query::is_ledger_path(&req.path) {
+        let la = s.la.read();
+        let ledger = la.get_committed_state().read();
+        return query::query(&ledger, req);
+    }
     s.account_base_app.write().query(req)
 }
 
@@ -134,11 +150,67 @@ pub fn init_chain(
     s: &mut ABCISubmissionServer,
     req: &RequestInitChain,
 ) -> ResponseInitChain {
+    // a chain restarted from the state of another one
+    if let Some(g) = pnk!(GenesisState::from_app_state(req.get_app_state_bytes())) {
+        info!(
+            "restart from {} at height {}",
+            g.checkpoint.chain_id, g.checkpoint.height
+        );
+        let la = s.la.write();
+        pnk!(la.get_committed_state().write().import_genesis(g));
+    }
+
     s.account_base_app.write().init_chain(req)
 }
 
+// the parameters set on chain by the committed blocks,
+// an update takes effect from the block after the one including it
+fn chain_params(s: &ABCISubmissionServer) -> ChainParams {
+    s.la.read()
+        .get_committed_state()
+        .read()
+        .get_staking()
+        .params()
+        .clone()
+}
+
 /// any new tx will trigger this callback before it can enter the mem-pool of tendermint
 pub fn check_tx(s: &mut ABCISubmissionServer, req: &RequestCheckTx) -> ResponseCheckTx {
+    health::abci_req_received();
+
+    let params = chain_params(s);
+    let mut resp = if let Some((code, log)) = limits::check_size(req.get_tx(), &params) {
+        let mut resp = ResponseCheckTx::new();
+        resp.code = code;
+        resp.log = log;
+        resp
+    } else {
+        do_check_tx(s, req, &params)
+    };
+
+    // report the result to those who submitted the tx through this node
+    if matches!(req.field_type, CheckTxType::New) && !s.pending_cache.read().is_empty() {
+        if let Ok(tx) = convert_tx(req.get_tx()) {
+            let handle = TxnHandle::new(&tx);
+            txn_event(
+                &handle.0,
+                0 != resp.code,
+                format_args!("checked by check_tx, code {}: {}", resp.code, resp.log),
+            );
+            s.pending_cache
+                .write()
+                .checked(&handle, resp.code, &resp.log);
+        }
+    }
+
+    resp
+}
+
+fn do_check_tx(
+    s: &mut ABCISubmissionServer,
+    req: &RequestCheckTx,
+    params: &ChainParams,
+) -> ResponseCheckTx {
     let mut resp = ResponseCheckTx::new();
 
     let tx_catalog = try_tx_catalog(req.get_tx(), false);
@@ -149,6 +221,11 @@ pub fn check_tx(s: &mut ABCISubmissionServer, req: &RequestCheckTx) -> ResponseC
         TxCatalog::FindoraTx => {
             if matches!(req.field_type, CheckTxType::New) {
                 if let Ok(tx) = convert_tx(req.get_tx()) {
+                    if let Some((code, log)) = limits::check_ops(&tx, params) {
+                        resp.code = code;
+                        resp.log = log;
+                        return resp;
+                    }
                     for op in tx.body.operations.iter() {
                         if let Operation::TransferAnonAsset(op) = op {
                             let mut inputs = op.note.body.inputs.clone();
@@ -175,18 +252,18 @@ pub fn check_tx(s: &mut ABCISubmissionServer, req: &RequestCheckTx) -> ResponseC
                         }
                         let mut signatures = tx.signatures.clone();
                         signatures.dedup();
-                        if signatures.len() > 1 {
+                        if signatures.len() > max_signatures(&tx) {
                             resp.log = "Too many signatures".to_owned();
                             resp.code = 1;
                             return resp;
                         }
 
-                        if tx.pubkey_sign_map.len() > 1 {
+                        if tx.pubkey_sign_map.len() > max_signatures(&tx) {
                             resp.log = "too many pubkey_sign_map".to_owned();
                             resp.code = 1;
                             return resp;
                         }
-                    } else if !tx.valid_in_abci() {
+                    } else if !tx.valid_in_abci_with(&params.fee_params()) {
                         resp.log = "Should not appear in ABCI".to_owned();
                         resp.code = 1;
                     } else if TX_HISTORY.read().contains_key(&tx.hash_tm_rawbytes()) {
@@ -198,6 +275,17 @@ pub fn check_tx(s: &mut ABCISubmissionServer, req: &RequestCheckTx) -> ResponseC
                         resp.code = 1;
                         resp.log = "Triple Masking is disabled".to_owned();
                     }
+                    // checked by `deliver_tx` as well, report it before the mem-pool;
+                    // last, so only the transactions entering it are cached
+                    if 0 == resp.code
+                        && CFG.checkpoint.utxo_checktx_height < td_height
+                        && !is_convert_account(&tx)
+                    {
+                        if let Err(e) = sig_cache::check_and_cache(&tx) {
+                            resp.log = e.to_string();
+                            resp.code = e.code();
+                        }
+                    }
                 } else {
                     resp.log = "Invalid format".to_owned();
                     resp.code = 1;
@@ -261,10 +349,15 @@ pub fn begin_block(
     TENDERMINT_BLOCK_HEIGHT.swap(header.height, Ordering::Relaxed);
     LEDGER_TENDERMINT_BLOCK_HEIGHT.swap(header.height, Ordering::Relaxed);
     *REQ_BEGIN_BLOCK.lock() = req.clone();
+    health::block_begun(header.get_time().get_seconds());
+    limits::block_begun();
 
+    logging::sample_block(CFG.log_sample_every);
     let start = Local::now().timestamp_millis();
     BEGIN_BLOCK_TIME.swap(start, Ordering::Relaxed);
-    info!(target: "abcitime", "begin_block  height:{}, milliseconds:{}", header.height, start);
+    if logging::block_sampled() {
+        info!(target: "abcitime", "begin_block  height:{}, milliseconds:{}", header.height, start);
+    }
 
     let mut la = s.la.write();
 
@@ -290,9 +383,47 @@ pub fn begin_block(
     }
 }
 
+// the code of a rejection by the ledger, distinct for each `TxnValidationError`
+// in `deliver_tx` since `txn_error_codes_height`, 1 before
+fn rejection_code(e: &TxnRejection, td_height: i64) -> u32 {
+    if CFG.checkpoint.txn_error_codes_height <= td_height {
+        e.code()
+    } else {
+        1
+    }
+}
+
 pub fn deliver_tx(
     s: &mut ABCISubmissionServer,
     req: &RequestDeliverTx,
+) -> ResponseDeliverTx {
+    let td_height = TENDERMINT_BLOCK_HEIGHT.load(Ordering::Relaxed);
+    let params = chain_params(s);
+    let violation = limits::check_delivered(req.get_tx(), td_height, &params);
+
+    let resp = if let Some((code, log)) = violation {
+        let mut resp = ResponseDeliverTx::new();
+        resp.code = code;
+        resp.log = log;
+        resp
+    } else {
+        do_deliver_tx(s, req, &params)
+    };
+
+    if 0 != resp.code {
+        if let Ok(tx) = convert_tx(req.get_tx()) {
+            s.la.write()
+                .txn_rejected(TxnHandle::new(&tx), resp.code, &resp.log);
+        }
+    }
+
+    resp
+}
+
+fn do_deliver_tx(
+    s: &mut ABCISubmissionServer,
+    req: &RequestDeliverTx,
+    params: &ChainParams,
 ) -> ResponseDeliverTx {
     let mut resp = ResponseDeliverTx::new();
 
@@ -302,6 +433,13 @@ pub fn deliver_tx(
     match tx_catalog {
         TxCatalog::FindoraTx => {
             if let Ok(tx) = convert_tx(req.get_tx()) {
+                if let Some((code, log)) =
+                    limits::check_delivered_ops(&tx, td_height, params)
+                {
+                    resp.code = code;
+                    resp.log = log;
+                    return resp;
+                }
                 for op in tx.body.operations.iter() {
                     if let Operation::TransferAnonAsset(op) = op {
                         let mut inputs = op.note.body.inputs.clone();
@@ -328,13 +466,13 @@ pub fn deliver_tx(
                     }
                     let mut signatures = tx.signatures.clone();
                     signatures.dedup();
-                    if signatures.len() > 1 {
+                    if signatures.len() > max_signatures(&tx) {
                         resp.log = "Too many signatures".to_owned();
                         resp.code = 1;
                         return resp;
                     }
 
-                    if tx.pubkey_sign_map.len() > 1 {
+                    if tx.pubkey_sign_map.len() > max_signatures(&tx) {
                         resp.log = "too many pubkey_sign_map".to_owned();
                         resp.code = 1;
                         return resp;
@@ -345,7 +483,7 @@ pub fn deliver_tx(
                     TX_HISTORY.write().set_value(txhash, Default::default());
                 });
 
-                if tx.valid_in_abci() {
+                if tx.valid_in_abci_with(&params.fee_params()) {
                     // Log print for monitor purpose
                     if td_height < CFG.checkpoint.evm_first_block_height {
                         info!(target: "abciapp",
@@ -356,7 +494,7 @@ pub fn deliver_tx(
 
                     if *KEEP_HIST {
                         // set attr(tags) if any, only needed on a fullnode
-                        let attr = utils::gen_tendermint_attr(&tx);
+                        let attr = utils::gen_tendermint_attr(&tx, td_height as u64);
                         if !attr.is_empty() {
                             resp.set_events(attr);
                         }
@@ -370,7 +508,7 @@ pub fn deliver_tx(
                             resp.log = "EVM is disabled".to_owned();
                             return resp;
                         } else if let Err(e) = s.la.write().cache_transaction(tx) {
-                            resp.code = 1;
+                            resp.code = rejection_code(&e, td_height);
                             resp.log = e.to_string();
                         }
                     } else if is_convert_account(&tx) {
@@ -444,20 +582,15 @@ pub fn deliver_tx(
                         resp.log = "Triple Masking is disabled".to_owned();
                         return resp;
                     } else if CFG.checkpoint.utxo_checktx_height < td_height {
-                        match tx.check_tx() {
-                            Ok(_) => {
-                                if let Err(e) = s.la.write().cache_transaction(tx) {
-                                    resp.code = 1;
-                                    resp.log = e.to_string();
-                                }
-                            }
-                            Err(e) => {
-                                resp.code = 1;
-                                resp.log = e.to_string();
-                            }
+                        let cached = sig_cache::check_cached(&tx).and_then(|_| {
+                            s.la.write().cache_transaction(tx).map(|_| ())
+                        });
+                        if let Err(e) = cached {
+                            resp.code = rejection_code(&e, td_height);
+                            resp.log = e.to_string();
                         }
                     } else if let Err(e) = s.la.write().cache_transaction(tx) {
-                        resp.code = 1;
+                        resp.code = rejection_code(&e, td_height);
                         resp.log = e.to_string();
                     }
                 } else {
@@ -638,7 +771,9 @@ pub fn end_block(
     let start = BEGIN_BLOCK_TIME.load(Ordering::Relaxed);
     let end = Local::now().timestamp_millis();
     END_BLOCK_TIME.swap(end, Ordering::Relaxed);
-    info!(target: "abcitime", "end_block  height:{}, end:{}-start:{}={}", header.height, end, start, end - start);
+    if logging::block_sampled() {
+        info!(target: "abcitime", "end_block  height:{}, end:{}-start:{}={}", header.height, end, start, end - start);
+    }
 
     resp
 }
@@ -650,13 +785,22 @@ pub fn commit(s: &mut ABCISubmissionServer, req: &RequestCommit) -> ResponseComm
     // will change `struct LedgerStatus`
     let td_height = TENDERMINT_BLOCK_HEIGHT.load(Ordering::Relaxed);
     state.set_tendermint_height(td_height as u64);
+    {
+        let req = REQ_BEGIN_BLOCK.lock();
+        let header = req.get_header();
+        state.set_tendermint_time(header.get_time().get_seconds());
+        state.set_tendermint_proposer(header.get_proposer_address());
+    }
+    health::block_committed(td_height);
 
     // cache last block for QueryServer
     pnk!(api_cache::update_api_cache(&mut state));
 
     // snapshot them finally
     let path = format!("{}/{}", &CFG.ledger_dir, &state.get_status().snapshot_file);
-    pnk!(serde_json::to_vec(&state.get_status())
+    pnk!(state
+        .get_status()
+        .encode(CFG.zstd_level)
         .c(d!())
         .and_then(|s| fs::write(&path, s).c(d!(path))));
 
@@ -666,7 +810,9 @@ pub fn commit(s: &mut ABCISubmissionServer, req: &RequestCommit) -> ResponseComm
     let begin_cs_hash = Local::now().timestamp_millis();
     let cs_hash = s.account_base_app.write().commit(req).data;
     let end_cs_hash = Local::now().timestamp_millis();
-    info!(target: "abcitime", "commit height:{}, la_hash:{} cs_hash:{}", td_height, begin_cs_hash - begin_la_hash, end_cs_hash - begin_cs_hash);
+    if logging::block_sampled() {
+        info!(target: "abcitime", "commit height:{}, la_hash:{} cs_hash:{}", td_height, begin_cs_hash - begin_la_hash, end_cs_hash - begin_cs_hash);
+    }
 
     if CFG.checkpoint.disable_evm_block_height < td_height
         && td_height < CFG.checkpoint.enable_frc20_height
@@ -680,7 +826,9 @@ pub fn commit(s: &mut ABCISubmissionServer, req: &RequestCommit) -> ResponseComm
     }
     let end = END_BLOCK_TIME.load(Ordering::Relaxed);
     let commit = Local::now().timestamp_millis();
-    info!(target: "abcitime", "commit height:{}, commit:{}-end:{}={}", td_height, commit, end, commit - end);
+    if logging::block_sampled() {
+        info!(target: "abcitime", "commit height:{}, commit:{}-end:{}={}", td_height, commit, end, commit - end);
+    }
 
     IN_SAFE_ITV.store(false, Ordering::Release);
     if let Some(eth_api_base_app) = &s.eth_api_base_app {
@@ -690,7 +838,9 @@ pub fn commit(s: &mut ABCISubmissionServer, req: &RequestCommit) -> ResponseComm
             .secondary_catch_up_primary());
     }
     let catch_up = Local::now().timestamp_millis();
-    info!(target: "abcitime", "catch_up height:{}, catch_up:{}-commit:{}={}", td_height, catch_up, commit, catch_up - commit);
+    if logging::block_sampled() {
+        info!(target: "abcitime", "catch_up height:{}, catch_up:{}-commit:{}={}", td_height, catch_up, commit, catch_up - commit);
+    }
 
     if CFG.enable_enterprise_web3 && td_height as u64 > *WEB3_SERVICE_START_HEIGHT {
         let height = td_height as u32;
@@ -805,6 +955,9 @@ pub fn commit(s: &mut ABCISubmissionServer, req: &RequestCommit) -> ResponseComm
         }
     }
 
+    // both the ledger and the contracts are on the disk by now
+    state_sync::export_if_due(&state, td_height as u64);
+
     r
 }
 
@@ -869,6 +1022,20 @@ fn app_hash_v2(
     }
 }
 
+// a swap is signed by both of its parties, other transactions by a single key
+fn max_signatures(tx: &Transaction) -> usize {
+    if tx
+        .body
+        .operations
+        .iter()
+        .any(|op| matches!(op, Operation::SwapAsset(_)))
+    {
+        2
+    } else {
+        1
+    }
+}
+
 fn is_tm_transaction(tx: &Transaction) -> bool {
     tx.body
         .operations
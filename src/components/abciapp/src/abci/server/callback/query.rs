@@ -0,0 +1,157 @@
+//!
+//! # Queries of the ledger state through the ABCI
+//!
+//! `/utxo/{sid}`, `/txn/{sid}` and `/asset/{code}` (in base64), so light
+//! clients can read the state from tendermint. The value of a response is
+//! the JSON of `UtxoProof`, `TxnProof` and `AssetValue` respectively, the
+//! proofs are against the roots in `StateCommitmentData`, whose hash is the
+//! ledger part of the app hash.
+//!
+//! Only the latest committed height is served, `height` 0 stands for it.
+//! The other paths are left to the EVM app.
+//!
+
+use {
+    crate::api::query_server::query_api::ledger_api::{txn_proof, utxo_proof},
+    abci::{RequestQuery, ResponseQuery},
+    globutils::HashOf,
+    ledger::{
+        data_model::{AssetType, AssetTypeCode, StateCommitmentData, TxnSID, TxoSID},
+        store::LedgerState,
+    },
+    serde::Serialize,
+};
+
+/// The path or the key is malformed
+pub const CODE_INVALID_QUERY: u32 = 1;
+
+/// Nothing is stored under the key
+pub const CODE_NOT_FOUND: u32 = 2;
+
+/// The height is not served, or the proof is not available yet
+pub const CODE_UNAVAILABLE: u32 = 3;
+
+/// An asset type, the asset types are not provable yet
+#[allow(missing_docs)]
+#[derive(Serialize)]
+pub struct AssetValue {
+    pub code: AssetTypeCode,
+    pub asset_type: AssetType,
+    pub issuance_num: Option<u64>,
+    /// the roots of the state, `domains.asset_types` is the one of the asset types
+    pub state_commitment_data: Option<StateCommitmentData>,
+    pub state_commitment: HashOf<Option<StateCommitmentData>>,
+    pub block_commit_count: u64,
+    pub block_height: u64,
+}
+
+/// Whether `path` is served by `query`
+pub fn is_ledger_path(path: &str) -> bool {
+    ["/utxo/", "/txn/", "/asset/"]
+        .iter()
+        .any(|p| path.starts_with(p))
+}
+
+/// Serve the query `req` of a ledger path from `ledger`
+pub fn query(ledger: &LedgerState, req: &RequestQuery) -> ResponseQuery {
+    let mut resp = ResponseQuery::new();
+    let height = ledger.get_tendermint_height();
+    resp.key = req.path.as_bytes().to_vec();
+    resp.height = height as i64;
+
+    if 0 != req.height && height as i64 != req.height {
+        resp.code = CODE_UNAVAILABLE;
+        resp.log = format!("only the latest height {height} is served");
+        return resp;
+    }
+
+    let (kind, key) = match req.path[1..].split_once('/') {
+        Some(kv) => kv,
+        None => {
+            resp.code = CODE_INVALID_QUERY;
+            resp.log = format!("invalid path: {}", req.path);
+            return resp;
+        }
+    };
+
+    let value = match kind {
+        "utxo" => match key.parse::<u64>() {
+            Ok(sid) => utxo_proof(ledger, TxoSID(sid))
+                .map_err(|e| (CODE_UNAVAILABLE, e.to_owned()))
+                .and_then(|p| to_json(&p)),
+            Err(_) => Err((CODE_INVALID_QUERY, "invalid txo sid".to_owned())),
+        },
+        "txn" => match key.parse::<usize>() {
+            Ok(sid) => txn_proof(ledger, TxnSID(sid))
+                .ok_or((CODE_NOT_FOUND, "no such transaction".to_owned()))
+                .and_then(|p| to_json(&p)),
+            Err(_) => Err((CODE_INVALID_QUERY, "invalid txn sid".to_owned())),
+        },
+        "asset" => match AssetTypeCode::new_from_base64(key) {
+            Ok(code) => asset_value(ledger, code)
+                .ok_or((CODE_NOT_FOUND, "no such asset".to_owned()))
+                .and_then(|v| to_json(&v)),
+            Err(_) => Err((CODE_INVALID_QUERY, "invalid asset code".to_owned())),
+        },
+        _ => Err((CODE_INVALID_QUERY, format!("invalid path: {}", req.path))),
+    };
+
+    match value {
+        Ok(v) => resp.value = v,
+        Err((code, log)) => {
+            resp.code = code;
+            resp.log = log;
+        }
+    }
+    resp
+}
+
+fn asset_value(ledger: &LedgerState, code: AssetTypeCode) -> Option<AssetValue> {
+    let asset_type = ledger.get_asset_type(&code)?;
+    let (state_commitment, block_commit_count) = ledger.get_state_commitment();
+    Some(AssetValue {
+        code,
+        asset_type,
+        issuance_num: ledger.get_issuance_num(&code),
+        state_commitment_data: ledger.get_state_commitment_data().cloned(),
+        state_commitment,
+        block_commit_count,
+        block_height: ledger.get_tendermint_height(),
+    })
+}
+
+fn to_json<T: Serialize>(v: &T) -> Result<Vec<u8>, (u32, String)> {
+    serde_json::to_vec(v).map_err(|e| (CODE_UNAVAILABLE, e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn req(path: &str, height: i64) -> RequestQuery {
+        let mut req = RequestQuery::new();
+        req.path = path.to_owned();
+        req.height = height;
+        req
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let ledger = LedgerState::tmp_ledger();
+
+        assert!(is_ledger_path("/utxo/1"));
+        assert!(!is_ledger_path("module/evm/code"));
+
+        assert_eq!(CODE_INVALID_QUERY, query(&ledger, &req("/utxo/x", 0)).code);
+        assert_eq!(CODE_INVALID_QUERY, query(&ledger, &req("/asset/!", 0)).code);
+        assert_eq!(CODE_NOT_FOUND, query(&ledger, &req("/txn/0", 0)).code);
+        let code = AssetTypeCode::gen_random().to_base64();
+        let resp = query(&ledger, &req(&format!("/asset/{code}"), 0));
+        assert_eq!(CODE_NOT_FOUND, resp.code);
+        assert_eq!(format!("/asset/{code}").into_bytes(), resp.key);
+
+        let resp = query(&ledger, &req("/txn/0", 100));
+        assert_eq!(CODE_UNAVAILABLE, resp.code);
+        assert_eq!(0, resp.height);
+    }
+}
@@ -0,0 +1,191 @@
+//!
+//! # Signature verification cache
+//!
+//! `check_tx` verifies the signatures of a transaction by `Transaction::check_tx`
+//! before it enters the mem-pool, and `deliver_tx` verifies them again. The hashes
+//! of the transactions accepted by `check_tx` are kept here, so `deliver_tx` does
+//! not verify them a second time.
+//!
+//! The hash covers the whole transaction, signatures included, and the check
+//! does not depend on the state of the ledger, so a hit is as good as a check.
+//! A stale entry, eg. of a transaction dropped by the mem-pool of tendermint,
+//! is never wrong, it only takes room until the LRU drops it.
+//!
+
+use {
+    crate::api::mem_guard::{Evictable, MEM_GUARD, PRIORITY_VERIFIED},
+    lazy_static::lazy_static,
+    ledger::data_model::{Transaction, TxnResult},
+    lru::LruCache,
+    parking_lot::Mutex,
+    std::sync::Arc,
+};
+
+/// The size of the mem-pool of tendermint, 5000 txns by default
+const CAP: usize = 5000;
+
+lazy_static! {
+    static ref VERIFIED: Arc<SigCache> = Arc::new(SigCache::new(CAP));
+}
+
+/// Called in `check_tx` once the transaction is going to enter the mem-pool,
+/// it is remembered if it passes.
+pub fn check_and_cache(tx: &Transaction) -> TxnResult<()> {
+    VERIFIED.check_and_cache(tx)
+}
+
+/// Called in `deliver_tx`, the check is skipped if `tx` passed `check_tx`.
+pub fn check_cached(tx: &Transaction) -> TxnResult<()> {
+    VERIFIED.check_cached(tx)
+}
+
+/// Put the cache under the control of the memory guard,
+/// an evicted transaction is verified again in `deliver_tx`.
+pub fn register() {
+    MEM_GUARD.register(VERIFIED.clone());
+}
+
+struct SigCache {
+    verified: Mutex<LruCache<Vec<u8>, ()>>,
+}
+
+impl SigCache {
+    fn new(cap: usize) -> Self {
+        SigCache {
+            verified: Mutex::new(LruCache::new(cap)),
+        }
+    }
+
+    fn check_and_cache(&self, tx: &Transaction) -> TxnResult<()> {
+        tx.check_tx()?;
+        self.verified.lock().put(tx.hash_tm_rawbytes(), ());
+        Ok(())
+    }
+
+    // A transaction is delivered once, its entry is dropped on a hit.
+    fn check_cached(&self, tx: &Transaction) -> TxnResult<()> {
+        if self.verified.lock().pop(&tx.hash_tm_rawbytes()).is_some() {
+            return Ok(());
+        }
+        tx.check_tx()
+    }
+}
+
+impl Evictable for SigCache {
+    fn name(&self) -> &'static str {
+        "verified_txns"
+    }
+
+    fn priority(&self) -> u8 {
+        PRIORITY_VERIFIED
+    }
+
+    fn entries(&self) -> usize {
+        self.verified.lock().len()
+    }
+
+    fn evict(&self) -> usize {
+        let mut verified = self.verified.lock();
+        let n = verified.len();
+        verified.clear();
+        n
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        ledger::{
+            data_model::{AssetRules, AssetTypeCode, TxnValidationError},
+            store::helpers::create_definition_transaction,
+        },
+        rand_chacha::ChaChaRng,
+        rand_core::SeedableRng,
+        zei::XfrKeyPair,
+    };
+
+    // an asset definition of `kp`, signed by `signer`
+    fn tx(kp: &XfrKeyPair, signer: &XfrKeyPair) -> Transaction {
+        let code = AssetTypeCode::gen_random();
+        let mut tx =
+            create_definition_transaction(&code, kp, AssetRules::default(), None, 0)
+                .unwrap();
+        tx.sign(signer);
+        tx
+    }
+
+    #[test]
+    fn hit_is_consumed() {
+        let mut prng = ChaChaRng::from_entropy();
+        let kp = XfrKeyPair::generate(&mut prng);
+        let other = XfrKeyPair::generate(&mut prng);
+        let cache = SigCache::new(2);
+        let txn = tx(&kp, &kp);
+
+        cache.check_and_cache(&txn).unwrap();
+        assert_eq!(1, cache.entries());
+
+        // delivered once, the entry goes with the hit
+        cache.check_cached(&txn).unwrap();
+        assert_eq!(0, cache.entries());
+
+        // a second delivery is a miss, verified in full
+        cache.check_cached(&txn).unwrap();
+        assert_eq!(0, cache.entries());
+
+        // a hit skips the check, planted here for a transaction failing it
+        let forged = tx(&kp, &other);
+        cache.verified.lock().put(forged.hash_tm_rawbytes(), ());
+        assert!(cache.check_cached(&forged).is_ok());
+        assert!(cache.check_cached(&forged).is_err());
+    }
+
+    #[test]
+    fn miss_is_verified() {
+        let mut prng = ChaChaRng::from_entropy();
+        let kp = XfrKeyPair::generate(&mut prng);
+        let other = XfrKeyPair::generate(&mut prng);
+        let cache = SigCache::new(2);
+
+        let forged = tx(&kp, &other);
+        assert!(forged.check_tx().is_err());
+
+        // never remembered, neither by `check_tx` nor by `deliver_tx`
+        let e = cache.check_and_cache(&forged).unwrap_err();
+        assert_eq!(Some(TxnValidationError::SignatureInvalid), e.kind());
+        assert_eq!(6, e.code());
+        assert_eq!(0, cache.entries());
+        assert!(cache.check_cached(&forged).is_err());
+
+        // an entry of another transaction does not cover it
+        cache.check_and_cache(&tx(&kp, &kp)).unwrap();
+        assert!(cache.check_cached(&forged).is_err());
+        assert_eq!(1, cache.entries());
+    }
+
+    #[test]
+    fn evicted_is_verified() {
+        let kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+        let cache = SigCache::new(2);
+        let txs = (0..3).map(|_| tx(&kp, &kp)).collect::<Vec<_>>();
+
+        // bounded, the oldest entry is dropped
+        for tx in txs.iter() {
+            cache.check_and_cache(tx).unwrap();
+        }
+        assert_eq!(2, cache.entries());
+        cache.check_cached(&txs[0]).unwrap();
+        assert_eq!(2, cache.entries());
+
+        // cleared by the memory guard, every transaction falls back to the check
+        let forged = tx(&kp, &XfrKeyPair::generate(&mut ChaChaRng::from_entropy()));
+        cache.verified.lock().put(forged.hash_tm_rawbytes(), ());
+        assert_eq!(2, cache.evict());
+        for tx in txs.iter() {
+            cache.check_cached(tx).unwrap();
+        }
+        assert!(cache.check_cached(&forged).is_err());
+        assert_eq!(0, cache.entries());
+    }
+}
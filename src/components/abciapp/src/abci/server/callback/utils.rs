@@ -2,11 +2,17 @@
 
 use {
     abci::{Event, Pair},
-    ledger::data_model::{Operation, Transaction, TxnSID},
+    config::abci::global_cfg::CFG,
+    ledger::data_model::{
+        AssetTypeCode, AssetTypePrefix, Operation, Transaction, TxnSID,
+    },
     protobuf::RepeatedField,
     serde::Serialize,
-    std::time::SystemTime,
-    zei::noah_api::xfr::structs::{XfrAmount, XfrAssetType},
+    std::{collections::BTreeSet, time::SystemTime},
+    zei::{
+        noah_api::xfr::structs::{XfrAmount, XfrAssetType},
+        BlindAssetRecord, XfrBody,
+    },
 };
 
 /// generate attr(tags) for index-ops of tendermint
@@ -15,7 +21,8 @@ use {
 ///   - "addr.to" => "Json<TagAttr>"
 ///   - "addr.from.<addr>" => "y"
 ///   - "addr.to.<addr>" => "y"
-pub fn gen_tendermint_attr(tx: &Transaction) -> RepeatedField<Event> {
+///   - the events of the operations, see `gen_tendermint_attr_ops`
+pub fn gen_tendermint_attr(tx: &Transaction, td_height: u64) -> RepeatedField<Event> {
     let mut res = vec![];
 
     // index txs without block info
@@ -85,9 +92,126 @@ pub fn gen_tendermint_attr(tx: &Transaction) -> RepeatedField<Event> {
         index_addr!(commitments, "commitment.created");
     }
 
+    res.extend(gen_tendermint_attr_ops(tx, td_height));
+
     RepeatedField::from_vec(res)
 }
 
+/// generate the events of the operations, addresses in bech32 and asset codes in base64
+///   - "transfer.sender" => one per input, "transfer.recipient" => the output,
+///     "transfer.asset" and "transfer.amount" if not confidential,
+///     one event per output
+///   - "define_asset.issuer", "define_asset.code"
+///   - "issue_asset.issuer", "issue_asset.code", "issue_asset.seq_num"
+///   - "asset.code" => one event per non-confidential asset involved
+fn gen_tendermint_attr_ops(tx: &Transaction, td_height: u64) -> Vec<Event> {
+    let mut res = vec![];
+    let mut codes = BTreeSet::new();
+
+    let mut transfer = |t: &XfrBody, res: &mut Vec<Event>| {
+        let senders = t
+            .inputs
+            .iter()
+            .map(|i| globutils::wallet::public_key_to_bech32(&i.public_key))
+            .collect::<BTreeSet<_>>();
+        for r in t.inputs.iter() {
+            codes.extend(asset_code(r));
+        }
+        for o in t.outputs.iter() {
+            let mut attrs = senders
+                .iter()
+                .map(|s| ("sender", s.clone()))
+                .collect::<Vec<_>>();
+            attrs.push((
+                "recipient",
+                globutils::wallet::public_key_to_bech32(&o.public_key),
+            ));
+            if let Some(code) = asset_code(o) {
+                attrs.push(("asset", code.clone()));
+                codes.insert(code);
+            }
+            if let XfrAmount::NonConfidential(am) = o.amount {
+                attrs.push(("amount", am.to_string()));
+            }
+            res.push(event("transfer", attrs));
+        }
+    };
+
+    let mut defined = vec![];
+    for op in tx.body.operations.iter() {
+        match op {
+            Operation::TransferAsset(d) => transfer(&d.body.transfer, &mut res),
+            Operation::SwapAsset(d) => transfer(&d.transfer.body.transfer, &mut res),
+            Operation::DefineAsset(d) => {
+                // the code as stored by the ledger
+                let code = AssetTypeCode::from_prefix_and_raw_asset_type_code(
+                    AssetTypePrefix::UserDefined,
+                    &d.body.asset.code,
+                    &CFG.checkpoint,
+                    td_height,
+                )
+                .to_base64();
+                res.push(event(
+                    "define_asset",
+                    vec![
+                        (
+                            "issuer",
+                            globutils::wallet::public_key_to_bech32(&d.pubkey.key),
+                        ),
+                        ("code", code.clone()),
+                    ],
+                ));
+                defined.push(code);
+            }
+            Operation::IssueAsset(d) => {
+                let code = d.body.code.to_base64();
+                res.push(event(
+                    "issue_asset",
+                    vec![
+                        (
+                            "issuer",
+                            globutils::wallet::public_key_to_bech32(&d.pubkey.key),
+                        ),
+                        ("code", code.clone()),
+                        ("seq_num", d.body.seq_num.to_string()),
+                    ],
+                ));
+                defined.push(code);
+            }
+            _ => {}
+        }
+    }
+    codes.extend(defined);
+
+    res.extend(codes.into_iter().map(|c| event("asset", vec![("code", c)])));
+    res
+}
+
+// the base64 code of a non-confidential asset type
+fn asset_code(r: &BlindAssetRecord) -> Option<String> {
+    if let XfrAssetType::NonConfidential(ty) = r.asset_type {
+        Some(AssetTypeCode { val: ty }.to_base64())
+    } else {
+        None
+    }
+}
+
+fn event(ty: &str, attrs: Vec<(&str, String)>) -> Event {
+    let mut ev = Event::new();
+    ev.set_field_type(ty.to_owned());
+    let kv = attrs
+        .into_iter()
+        .map(|(k, v)| {
+            let mut p = Pair::new();
+            p.set_key(k.as_bytes().to_vec());
+            p.set_value(v.into_bytes());
+            p
+        })
+        .collect();
+    ev.set_attributes(RepeatedField::from_vec(kv));
+    ev
+}
+
 // collect informations of inputs and outputs
 // # return: ([from ...], [to ...])
 fn gen_tendermint_attr_addr(tx: &Transaction) -> (Vec<TagAttr>, Vec<TagAttr>) {
@@ -124,6 +248,10 @@ fn gen_tendermint_attr_addr(tx: &Transaction) -> (Vec<TagAttr>, Vec<TagAttr>) {
                     append_attr!(d, inputs, 0);
                     append_attr!(d, outputs, 1);
                 }
+                Operation::SwapAsset(d) => {
+                    append_attr!(d.transfer, inputs, 0);
+                    append_attr!(d.transfer, outputs, 1);
+                }
                 Operation::DefineAsset(d) => {
                     append_attr!(d);
                 }
@@ -133,6 +261,12 @@ fn gen_tendermint_attr_addr(tx: &Transaction) -> (Vec<TagAttr>, Vec<TagAttr>) {
                 Operation::UpdateMemo(d) => {
                     append_attr!(d);
                 }
+                Operation::UpdateTransferPolicy(d) => {
+                    append_attr!(d);
+                }
+                Operation::AnchorData(d) => {
+                    append_attr!(d);
+                }
                 Operation::BarToAbar(d) => {
                     let mut attr = TagAttr::default();
                     attr.addr = globutils::wallet::public_key_to_bech32(
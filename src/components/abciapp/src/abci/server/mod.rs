@@ -5,7 +5,7 @@
 use {
     crate::{
         abci::server::callback::TENDERMINT_BLOCK_HEIGHT,
-        api::submission_server::SubmissionServer,
+        api::submission_server::{pending::PendingCache, SubmissionServer},
     },
     abci::{
         RequestBeginBlock, RequestCheckTx, RequestCommit, RequestDeliverTx,
@@ -36,6 +36,8 @@ pub mod tx_sender;
 #[derive(Clone)]
 pub struct ABCISubmissionServer {
     pub la: Arc<RwLock<SubmissionServer<ChaChaRng, TendermintForward>>>,
+    /// shared with `la`, `check_tx` updates it without locking the ledger
+    pub pending_cache: Arc<RwLock<PendingCache>>,
     pub account_base_app: Arc<RwLock<AccountBaseAPP>>,
     pub eth_api_base_app: Option<Arc<RwLock<AccountBaseAPP>>>,
 }
@@ -100,16 +102,17 @@ impl ABCISubmissionServer {
         if EVM_STAKING.set(account_base_app.clone()).is_err() {
             return Err(eg!("Invalid usage."));
         }
+        tx_sender::start_forwarding();
         let prng = rand_chacha::ChaChaRng::from_entropy();
+        let la = SubmissionServer::new_no_auto_commit(
+            prng,
+            Arc::new(RwLock::new(ledger_state)),
+            TendermintForward { tendermint_reply },
+        )
+        .c(d!())?;
         Ok(ABCISubmissionServer {
-            la: Arc::new(RwLock::new(
-                SubmissionServer::new_no_auto_commit(
-                    prng,
-                    Arc::new(RwLock::new(ledger_state)),
-                    TendermintForward { tendermint_reply },
-                )
-                .c(d!())?,
-            )),
+            pending_cache: la.pending_cache(),
+            la: Arc::new(RwLock::new(la)),
             account_base_app,
             eth_api_base_app,
         })
@@ -1,15 +1,71 @@
 //!
 //! # send the transaction to tendermint
 //!
+//! Transactions are put into a bounded queue and forwarded by a few workers,
+//! failed forwardings are retried with an exponential backoff.
+//!
+//! Transactions which can not be queued, or have exhausted their retries,
+//! are spilled to disk, and will be queued again when the workers are idle,
+//! so they survive a restart of the node.
+//!
 
 use {
-    crate::{abci::POOL, api::submission_server::TxnForward},
+    crate::api::{
+        request_id::txn_event,
+        submission_server::{ForwardQueueStat, TxnForward},
+    },
+    config::abci::global_cfg::CFG,
+    lazy_static::lazy_static,
     ledger::data_model::Transaction,
+    parking_lot::Mutex,
     ruc::*,
-    std::sync::atomic::{AtomicU16, Ordering},
+    std::{
+        fs,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+            Arc,
+        },
+        thread,
+        time::{Duration, SystemTime},
+    },
 };
 
-static TX_PENDING_CNT: AtomicU16 = AtomicU16::new(0);
+/// Capacity of the forward queue
+pub const FORWARD_QUEUE_CAP: usize = 2000;
+
+/// Attempts of a forwarding before it is spilled to disk
+pub const MAX_FORWARD_ATTEMPTS: u32 = 5;
+
+const FORWARD_WORKERS: usize = 4;
+const BACKOFF_BASE_MS: u64 = 200;
+const BACKOFF_MAX_MS: u64 = 10_000;
+
+// the workers reload spilled transactions after being idle for this period
+const RELOAD_ITV_SECS: u64 = 5;
+
+// spilled transactions older than this are dropped
+const SPILL_TTL_SECS: u64 = 3600;
+
+const SPILL_TMP_EXT: &str = "tmp";
+
+static QUEUE_DEPTH: AtomicU64 = AtomicU64::new(0);
+static FORWARDED_CNT: AtomicU64 = AtomicU64::new(0);
+static RETRIED_CNT: AtomicU64 = AtomicU64::new(0);
+static SPILLED_CNT: AtomicU64 = AtomicU64::new(0);
+
+struct ForwardTask {
+    // used as the file name when being spilled
+    handle: String,
+    url: String,
+    json_rpc: String,
+}
+
+lazy_static! {
+    static ref QUEUE: SyncSender<ForwardTask> = start_workers();
+    static ref SPILL_DIR: PathBuf = PathBuf::from(&CFG.ledger_dir).join("forward_spill");
+}
 
 pub struct TendermintForward {
     pub tendermint_reply: String,
@@ -25,6 +81,23 @@ impl TxnForward for TendermintForward {
     fn forward_txn(&self, txn: Transaction) -> Result<()> {
         forward_txn_with_mode(self.as_ref(), txn, false)
     }
+
+    fn queue_stat(&self) -> Option<ForwardQueueStat> {
+        Some(ForwardQueueStat {
+            depth: QUEUE_DEPTH.load(Ordering::Relaxed),
+            capacity: FORWARD_QUEUE_CAP as u64,
+            spilled: spilled_txns().len() as u64,
+            forwarded_cnt: FORWARDED_CNT.load(Ordering::Relaxed),
+            retried_cnt: RETRIED_CNT.load(Ordering::Relaxed),
+            spilled_cnt: SPILLED_CNT.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Start the workers, so that the transactions spilled
+/// before the last restart are forwarded without waiting for new ones
+pub fn start_forwarding() {
+    lazy_static::initialize(&QUEUE);
 }
 
 pub fn forward_txn_with_mode(
@@ -50,20 +123,144 @@ pub fn forward_txn_with_mode(
             )
     };
 
-    let tendermint_reply = format!("http://{url}");
-    if 2000 > TX_PENDING_CNT.fetch_add(1, Ordering::Relaxed) {
-        POOL.spawn_ok(async move {
-            ruc::info_omit!(attohttpc::post(&tendermint_reply)
-                .header(attohttpc::header::CONTENT_TYPE, "application/json")
-                .text(json_rpc)
-                .send()
-                .c(d!()));
-            TX_PENDING_CNT.fetch_sub(1, Ordering::Relaxed);
-        });
-    } else {
-        TX_PENDING_CNT.fetch_sub(1, Ordering::Relaxed);
-        return Err(eg!("Too many pending tasks"));
+    let task = ForwardTask {
+        handle: txn.handle(),
+        url: format!("http://{url}"),
+        json_rpc,
+    };
+
+    QUEUE_DEPTH.fetch_add(1, Ordering::Relaxed);
+    match QUEUE.try_send(task) {
+        Ok(()) => Ok(()),
+        Err(TrySendError::Full(task)) | Err(TrySendError::Disconnected(task)) => {
+            QUEUE_DEPTH.fetch_sub(1, Ordering::Relaxed);
+            spill(&task).c(d!("the forward queue is full"))
+        }
+    }
+}
+
+fn start_workers() -> SyncSender<ForwardTask> {
+    let (sender, receiver) = sync_channel(FORWARD_QUEUE_CAP);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..FORWARD_WORKERS {
+        let receiver = Arc::clone(&receiver);
+        thread::spawn(move || work(receiver));
+    }
+    sender
+}
+
+fn work(receiver: Arc<Mutex<Receiver<ForwardTask>>>) {
+    loop {
+        let rx = receiver.lock();
+        match rx.recv_timeout(Duration::from_secs(RELOAD_ITV_SECS)) {
+            Ok(task) => {
+                drop(rx);
+                QUEUE_DEPTH.fetch_sub(1, Ordering::Relaxed);
+                forward(task);
+            }
+            // keep the lock, so only one worker reloads at a time
+            Err(RecvTimeoutError::Timeout) => reload_spilled(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+fn forward(task: ForwardTask) {
+    for attempt in 0..MAX_FORWARD_ATTEMPTS {
+        if 0 < attempt {
+            RETRIED_CNT.fetch_add(1, Ordering::Relaxed);
+            let backoff = BACKOFF_BASE_MS.saturating_mul(1 << (attempt - 1));
+            thread::sleep(Duration::from_millis(backoff.min(BACKOFF_MAX_MS)));
+        }
+
+        let res = attohttpc::post(&task.url)
+            .header(attohttpc::header::CONTENT_TYPE, "application/json")
+            .text(&task.json_rpc)
+            .send()
+            .c(d!())
+            .and_then(|resp| resp.error_for_status().c(d!()));
+        match res {
+            Ok(_) => {
+                FORWARDED_CNT.fetch_add(1, Ordering::Relaxed);
+                txn_event(
+                    &task.handle,
+                    false,
+                    format!("forwarded to tendermint, attempt {}", attempt + 1),
+                );
+                return;
+            }
+            Err(e) => {
+                e.print(Some(&format!(
+                    "forwarding txn {}, attempt {}",
+                    task.handle,
+                    attempt + 1
+                )));
+            }
+        }
     }
 
+    txn_event(
+        &task.handle,
+        false,
+        format!("spilled to disk after {MAX_FORWARD_ATTEMPTS} attempts"),
+    );
+    info_omit!(spill(&task));
+}
+
+fn spill(task: &ForwardTask) -> Result<()> {
+    fs::create_dir_all(&*SPILL_DIR).c(d!())?;
+    let body = serde_json::to_vec(&(&task.url, &task.json_rpc)).c(d!())?;
+    // write and rename, a half-written file must not be seen by `reload_spilled`
+    let tmp = SPILL_DIR.join(format!("{}.{SPILL_TMP_EXT}", &task.handle));
+    fs::write(&tmp, body).c(d!())?;
+    fs::rename(&tmp, SPILL_DIR.join(&task.handle)).c(d!())?;
+    SPILLED_CNT.fetch_add(1, Ordering::Relaxed);
     Ok(())
 }
+
+fn spilled_txns() -> Vec<PathBuf> {
+    fs::read_dir(&*SPILL_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.is_file() && p.extension().map_or(true, |ext| ext != SPILL_TMP_EXT)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// queue spilled transactions again, as many as the free capacity allows
+fn reload_spilled() {
+    for path in spilled_txns() {
+        let expired = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| SystemTime::now().duration_since(t).ok())
+            .map_or(false, |age| SPILL_TTL_SECS < age.as_secs());
+        if !expired {
+            let task = fs::read(&path).c(d!()).and_then(|body| {
+                serde_json::from_slice::<(String, String)>(&body).c(d!())
+            });
+            if let Ok((url, json_rpc)) = task {
+                let handle = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                QUEUE_DEPTH.fetch_add(1, Ordering::Relaxed);
+                let task = ForwardTask {
+                    handle,
+                    url,
+                    json_rpc,
+                };
+                if QUEUE.try_send(task).is_err() {
+                    QUEUE_DEPTH.fetch_sub(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+        info_omit!(fs::remove_file(&path).c(d!()));
+    }
+}
@@ -0,0 +1,124 @@
+//!
+//! # Graceful shutdown
+//!
+//! On SIGINT/SIGTERM:
+//! 1. new transactions are rejected by the submission server
+//! 2. wait for the ledger to reach a safe point, see `IN_SAFE_ITV`
+//! 3. flush the ledger, the cached data and the status snapshot
+//! 4. stop the web servers, requests in processing are allowed to finish
+//!
+
+use {
+    super::{IN_SAFE_ITV, IS_EXITING},
+    actix_web::dev::Server,
+    config::abci::global_cfg::CFG,
+    lazy_static::lazy_static,
+    ledger::store::{LedgerState, LedgerStatus},
+    parking_lot::{Mutex, RwLock},
+    ruc::*,
+    std::{
+        fs::File,
+        io::Write,
+        sync::{atomic::Ordering, mpsc::channel, Arc},
+    },
+    tracing::info,
+};
+
+lazy_static! {
+    static ref SERVERS: Mutex<Vec<Server>> = Mutex::new(vec![]);
+    static ref LEDGER: Mutex<Option<Arc<RwLock<LedgerState>>>> = Mutex::new(None);
+}
+
+/// Register a web server to be stopped on shutdown
+pub fn register_server(server: Server) {
+    SERVERS.lock().push(server);
+}
+
+/// Register the committed ledger to be flushed on shutdown
+pub fn register_ledger(ledger: Arc<RwLock<LedgerState>>) {
+    *LEDGER.lock() = Some(ledger);
+}
+
+/// `true` once the shutdown has begun
+#[inline(always)]
+pub fn is_shutting_down() -> bool {
+    IS_EXITING.load(Ordering::Acquire)
+}
+
+/// Block the caller until SIGINT or SIGTERM is received
+pub fn wait_for_signal() -> Result<()> {
+    let (tx, rx) = channel();
+    ctrlc::set_handler(move || {
+        let _ = tx.send(());
+    })
+    .c(d!())?;
+    rx.recv().c(d!())
+}
+
+/// Stop the node, the process can exit safely after this returns
+pub fn shutdown() -> Result<()> {
+    info!("Waiting to exit, new transactions are rejected");
+    IS_EXITING.store(true, Ordering::SeqCst);
+    while !IN_SAFE_ITV.load(Ordering::SeqCst) {
+        sleep_ms!(10);
+    }
+
+    info!("Flushing the ledger");
+    flush_ledger().c(d!())?;
+
+    info!("Stopping the web servers");
+    for server in SERVERS.lock().drain(..) {
+        futures::executor::block_on(server.stop(true));
+    }
+
+    Ok(())
+}
+
+fn flush_ledger() -> Result<()> {
+    // hold the lock, so no one writes during the flushing
+    let ledger = LEDGER.lock().clone();
+    let state = ledger.as_ref().map(|l| l.read());
+
+    ledger::store::fbnc::flush_data();
+
+    if let Some(state) = state {
+        write_snapshot(state.get_status(), &CFG.ledger_dir, CFG.zstd_level).c(d!())?;
+    }
+
+    Ok(())
+}
+
+// rewrite the status snapshot in `dir`, synced to the disk
+fn write_snapshot(status: &LedgerStatus, dir: &str, zstd_level: i32) -> Result<()> {
+    let path = format!("{}/{}", dir, &status.snapshot_file);
+    let body = status.encode(zstd_level).c(d!())?;
+    let mut f = File::create(&path).c(d!(path))?;
+    f.write_all(&body).c(d!())?;
+    f.sync_all().c(d!())
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, std::fs};
+
+    #[test]
+    fn snapshot_is_rewritten() {
+        let state = LedgerState::tmp_ledger();
+        let status = state.get_status();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_str().unwrap();
+        let path = format!("{}/{}", dir, &status.snapshot_file);
+
+        // a stale snapshot, longer than the new one, is replaced as a whole,
+        // and loaded back on the next start
+        for zstd_level in [0, 3] {
+            fs::write(&path, vec![b'{'; 1 << 20]).unwrap();
+            write_snapshot(status, dir, zstd_level).unwrap();
+            let loaded = LedgerStatus::new(dir, &status.snapshot_file).unwrap();
+            assert_eq!(loaded.encode(0).unwrap(), status.encode(0).unwrap());
+        }
+
+        // reported, the node exits without a snapshot
+        assert!(write_snapshot(status, &format!("{dir}/missing"), 0).is_err());
+    }
+}
@@ -27,7 +27,8 @@ use {
                 governance::{governance_penalty_tendermint_auto, ByzantineKind},
                 mint_fra::{MintEntry, MintFraOps, MintKind},
             },
-            td_addr_to_string, Staking, VALIDATOR_UPDATE_BLOCK_ITV,
+            td_addr_to_string, BlockHeight, Staking, ValidatorBlockRecord,
+            VALIDATOR_UPDATE_BLOCK_ITV,
         },
         store::LedgerState,
     },
@@ -273,16 +274,29 @@ pub fn system_ops(
             .collect::<BTreeSet<_>>();
 
         // mark if a validator is online at last block
+        let mut signing = vec![];
         if let Ok(vd) = ruc::info!(la.get_staking_mut().validator_get_current_mut()) {
-            vd.body.values_mut().for_each(|v| {
+            vd.body.iter_mut().for_each(|(pk, v)| {
                 if online_list.contains(&v.td_addr) {
                     v.signed_last_block = true;
                     v.signed_cnt += 1;
                 } else {
                     v.signed_last_block = false;
                 }
+                signing.push((*pk, v.signed_last_block));
             });
         }
+        let last_height = (header.height as BlockHeight).saturating_sub(1);
+        signing.into_iter().for_each(|(pk, signed)| {
+            la.get_staking().record_validator_block(
+                &pk,
+                last_height,
+                ValidatorBlockRecord {
+                    signed: Some(signed),
+                    rewards: 0,
+                },
+            );
+        });
 
         if online_list.len() != lci.votes.len() {
             if let Ok(pl) = ruc::info!(gen_offline_punish_list(
@@ -405,7 +419,7 @@ pub fn system_prism_mint_pay(
     ))
 }
 
-/// Pay for freed 'Delegations' and 'FraDistributions'.
+/// Pay for freed 'Delegations', 'FraDistributions' and due 'ScheduledTransfers'.
 pub fn system_mint_pay(
     la: &LedgerState,
     account_base_app: &mut AccountBaseApp,
@@ -448,6 +462,10 @@ pub fn system_mint_pay(
                     MintEntry::new(MintKind::Claim, k, None, n, ASSET_TYPE_FRA)
                 }),
         )
+        // paid from the escrows, not from the coinbase balance
+        .chain(staking.schedule_get_due().into_iter().map(|(id, pk, n)| {
+            MintEntry::new(MintKind::Scheduled(id), pk, None, n, ASSET_TYPE_FRA)
+        }))
         .take(NUM_TO_PAY)
         .collect::<Vec<_>>();
 
@@ -7,7 +7,7 @@ use {
             Transaction, TransferType, TxnEffect, TxoRef, ASSET_TYPE_FRA,
             BLACK_HOLE_PUBKEY, TX_FEE_MIN,
         },
-        staking::{FF_PK_LIST, FRA_PRE_ISSUE_AMOUNT},
+        staking::{features::FeatureSet, FF_PK_LIST, FRA_PRE_ISSUE_AMOUNT},
         store::{utils::fra_gen_initial_tx, LedgerState},
     },
     rand::random,
@@ -38,7 +38,7 @@ fn check_block_rewards_rate() -> Result<()> {
 
     let tx = fra_gen_initial_tx(&root_kp);
 
-    let effect = TxnEffect::compute_effect(tx).c(d!())?;
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).c(d!())?;
     let mut block = ledger.start_block().c(d!())?;
     ledger.apply_transaction(&mut block, effect).c(d!())?;
     ledger.finish_block(block).c(d!())?;
@@ -59,7 +59,7 @@ fn check_block_rewards_rate() -> Result<()> {
         )
         .c(d!())?;
 
-        let effect = TxnEffect::compute_effect(tx).c(d!())?;
+        let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).c(d!())?;
         let mut block = ledger.start_block().c(d!())?;
         ledger.apply_transaction(&mut block, effect).c(d!())?;
         ledger.finish_block(block).c(d!())?;
@@ -0,0 +1,619 @@
+//!
+//! # State sync of tendermint
+//!
+//! Every `state_sync_interval` blocks, a checkpoint of the ledger directory
+//! is taken at commit, then exported by a worker thread to
+//! `<ledger_dir>_statesync/<height>.snap`, in the format of
+//! `LedgerState::export_snapshot`. The snapshots are served to the peers in
+//! chunks of `CHUNK_SIZE`, the sha256 of every chunk is carried by the
+//! metadata of the snapshot.
+//!
+//! NOTE: the databases of the contracts share the ledger directory, their
+//! files are copied as they are on disk, without a checkpoint of their own,
+//! and they are not checked by `LedgerState::verify_snapshot`.
+//!
+//! With `enable_state_sync_restore`, a node starting on an empty ledger
+//! accepts the snapshots offered by tendermint, checks every chunk against
+//! the metadata, and imports the snapshot once the last one is applied.
+//! The storage of the ledger can not be reopened within a process, so the
+//! ABCI server and the services are not built until then, or until tendermint
+//! starts from the genesis instead. Tendermint checks the restored state
+//! against the app hash of the chain by `info` afterwards.
+//!
+
+use {
+    super::server::ABCISubmissionServer,
+    abci::{
+        Application, RequestApplySnapshotChunk, RequestBeginBlock, RequestCheckTx,
+        RequestCommit, RequestDeliverTx, RequestEndBlock, RequestInfo, RequestInitChain,
+        RequestListSnapshots, RequestLoadSnapshotChunk, RequestOfferSnapshot,
+        RequestQuery, ResponseApplySnapshotChunk, ResponseApplySnapshotChunk_Result,
+        ResponseBeginBlock, ResponseCheckTx, ResponseCommit, ResponseDeliverTx,
+        ResponseEndBlock, ResponseInfo, ResponseInitChain, ResponseListSnapshots,
+        ResponseLoadSnapshotChunk, ResponseOfferSnapshot, ResponseOfferSnapshot_Result,
+        ResponseQuery, Snapshot,
+    },
+    config::abci::global_cfg::CFG,
+    ledger::store::{
+        snapshot::{SnapshotCheckpoint, SnapshotHeader, SNAPSHOT_FORMAT_VERSION},
+        LedgerState,
+    },
+    protobuf::RepeatedField,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    std::{
+        collections::BTreeSet,
+        fs::{self, File, OpenOptions},
+        io::{Read, Seek, SeekFrom, Write},
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+    },
+    tracing::{error, info, warn},
+};
+
+/// Size of the chunks served to the peers
+pub const CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+// the snapshot being restored, never listed
+const RESTORE_FILE: &str = "restore.part";
+
+// only one export at a time
+static EXPORTING: AtomicBool = AtomicBool::new(false);
+
+/// Build the ABCI server and start the services on the ledger directory
+pub type Builder = Box<dyn Fn() -> Result<ABCISubmissionServer> + Send + Sync>;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct SnapshotMeta {
+    height: u64,
+    format: u32,
+    /// hex encoded sha256 of the whole file
+    hash: String,
+    /// hex encoded sha256 of each chunk
+    chunk_hashes: Vec<String>,
+}
+
+impl SnapshotMeta {
+    fn to_snapshot(&self) -> Snapshot {
+        let mut s = Snapshot::new();
+        s.set_height(self.height);
+        s.set_format(self.format);
+        s.set_chunks(self.chunk_hashes.len() as u32);
+        s.set_hash(hex::decode(&self.hash).unwrap_or_default());
+        s.set_metadata(serde_json::to_vec(&self.chunk_hashes).unwrap_or_default());
+        s
+    }
+}
+
+fn snapshot_dir() -> PathBuf {
+    PathBuf::from(format!(
+        "{}_statesync",
+        CFG.ledger_dir.trim_end_matches('/')
+    ))
+}
+
+fn snapshot_path(dir: &Path, height: u64, ext: &str) -> PathBuf {
+    dir.join(format!("{height}.{ext}"))
+}
+
+/// Take a checkpoint at `height` if due, it is exported in the background,
+/// called at the end of `commit`
+pub fn export_if_due(ledger: &LedgerState, height: u64) {
+    let itv = CFG.state_sync_interval;
+    if 0 == itv || 0 != height % itv {
+        return;
+    }
+    if EXPORTING.swap(true, Ordering::AcqRel) {
+        warn!(
+            target: "abciapp",
+            "state sync snapshot at height {} skipped, the previous one is being exported",
+            height
+        );
+        return;
+    }
+
+    let dir = snapshot_dir();
+    let cp = fs::create_dir_all(&dir).c(d!()).and_then(|_| {
+        let staging = snapshot_path(&dir, height, "staging");
+        ledger
+            .checkpoint_snapshot(&staging.to_string_lossy())
+            .c(d!())
+    });
+    let cp = match cp {
+        Ok(cp) => cp,
+        Err(e) => {
+            EXPORTING.store(false, Ordering::Release);
+            error!(
+                target: "abciapp",
+                "state sync checkpoint at height {} failed: {}",
+                height,
+                e.generate_log(None)
+            );
+            return;
+        }
+    };
+
+    thread::spawn(move || {
+        if let Err(e) = export(&dir, &cp, height, CFG.state_sync_keep) {
+            error!(
+                target: "abciapp",
+                "state sync snapshot at height {} failed: {}",
+                height,
+                e.generate_log(None)
+            );
+        }
+        drop(cp);
+        EXPORTING.store(false, Ordering::Release);
+    });
+}
+
+fn export(dir: &Path, cp: &SnapshotCheckpoint, height: u64, keep: usize) -> Result<()> {
+    let path = snapshot_path(dir, height, "snap");
+    cp.export(&path.to_string_lossy(), CFG.zstd_level).c(d!())?;
+
+    let (hash, chunk_hashes) = hash_chunks(&path).c(d!())?;
+    let meta = SnapshotMeta {
+        height,
+        format: SNAPSHOT_FORMAT_VERSION as u32,
+        hash,
+        chunk_hashes,
+    };
+    fs::write(
+        snapshot_path(dir, height, "json"),
+        serde_json::to_vec(&meta).c(d!())?,
+    )
+    .c(d!())?;
+    info!(
+        target: "abciapp",
+        "state sync snapshot exported at height {}, {} chunks",
+        height,
+        meta.chunk_hashes.len()
+    );
+
+    let mut heights = list(dir).iter().map(|m| m.height).collect::<Vec<_>>();
+    heights.sort_unstable();
+    let stale = heights.len().saturating_sub(keep.max(1));
+    for h in heights.into_iter().take(stale) {
+        let _ = fs::remove_file(snapshot_path(dir, h, "json"));
+        let _ = fs::remove_file(snapshot_path(dir, h, "snap"));
+    }
+    Ok(())
+}
+
+// sha256 of the whole file and of each chunk, hex encoded
+fn hash_chunks(path: &Path) -> Result<(String, Vec<String>)> {
+    let mut f = File::open(path).c(d!())?;
+    let mut whole = Sha256::new();
+    let mut chunks = vec![];
+    let mut buf = vec![0u8; CHUNK_SIZE as usize];
+    loop {
+        let n = read_full(&mut f, &mut buf).c(d!())?;
+        if 0 == n {
+            break;
+        }
+        whole.update(&buf[..n]);
+        chunks.push(hex::encode(Sha256::digest(&buf[..n])));
+    }
+    Ok((hex::encode(whole.finalize()), chunks))
+}
+
+// fill `buf` unless the end of the file is reached
+fn read_full(f: &mut File, buf: &mut [u8]) -> Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match f.read(&mut buf[n..]).c(d!())? {
+            0 => break,
+            m => n += m,
+        }
+    }
+    Ok(n)
+}
+
+fn list(dir: &Path) -> Vec<SnapshotMeta> {
+    fs::read_dir(dir)
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map_or(false, |ext| "json" == ext))
+                .filter_map(|p| fs::read(p).ok())
+                .filter_map(|b| serde_json::from_slice::<SnapshotMeta>(&b).ok())
+                .filter(|m| snapshot_path(dir, m.height, "snap").exists())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn load_chunk(dir: &Path, height: u64, format: u32, chunk: u32) -> Result<Vec<u8>> {
+    let meta = list(dir)
+        .into_iter()
+        .find(|m| m.height == height && m.format == format)
+        .c(d!("snapshot not found"))?;
+    if meta.chunk_hashes.len() as u64 <= chunk as u64 {
+        return Err(eg!("chunk out of range"));
+    }
+
+    let mut f = File::open(snapshot_path(dir, height, "snap")).c(d!())?;
+    f.seek(SeekFrom::Start(chunk as u64 * CHUNK_SIZE)).c(d!())?;
+    let mut buf = vec![0u8; CHUNK_SIZE as usize];
+    let n = read_full(&mut f, &mut buf).c(d!())?;
+    buf.truncate(n);
+    Ok(buf)
+}
+
+// A snapshot accepted from the peers
+struct Restore {
+    dir: PathBuf,
+    height: u64,
+    hash: String,
+    chunk_hashes: Vec<String>,
+    file: File,
+    received: BTreeSet<u32>,
+}
+
+impl Restore {
+    fn new(dir: &Path, s: &Snapshot) -> Result<Self> {
+        let chunk_hashes = serde_json::from_slice::<Vec<String>>(&s.metadata).c(d!())?;
+        if chunk_hashes.len() != s.chunks as usize || chunk_hashes.is_empty() {
+            return Err(eg!("the metadata does not match the chunks"));
+        }
+
+        fs::create_dir_all(dir).c(d!())?;
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(dir.join(RESTORE_FILE))
+            .c(d!())?;
+        Ok(Restore {
+            dir: dir.to_owned(),
+            height: s.height,
+            hash: hex::encode(&s.hash),
+            chunk_hashes,
+            file,
+            received: BTreeSet::new(),
+        })
+    }
+
+    // `Ok(false)` if the chunk does not match its hash
+    fn apply(&mut self, index: u32, chunk: &[u8]) -> Result<bool> {
+        let expected = self
+            .chunk_hashes
+            .get(index as usize)
+            .c(d!("chunk out of range"))?;
+        if hex::encode(Sha256::digest(chunk)) != *expected {
+            return Ok(false);
+        }
+        self.file
+            .seek(SeekFrom::Start(index as u64 * CHUNK_SIZE))
+            .c(d!())?;
+        self.file.write_all(chunk).c(d!())?;
+        self.received.insert(index);
+        Ok(true)
+    }
+
+    fn is_complete(&self) -> bool {
+        self.received.len() == self.chunk_hashes.len()
+    }
+
+    // verify the whole file and extract it to the empty `basedir`
+    fn import(&mut self, basedir: &str) -> Result<SnapshotHeader> {
+        self.file.flush().c(d!())?;
+        let path = self.dir.join(RESTORE_FILE);
+        let (hash, _) = hash_chunks(&path).c(d!())?;
+        if hash != self.hash {
+            return Err(eg!("the snapshot does not match its hash"));
+        }
+
+        let header =
+            LedgerState::import_snapshot(&path.to_string_lossy(), basedir).c(d!())?;
+        if header.tendermint_height != self.height {
+            return Err(eg!(format!(
+                "the snapshot is at height {}, offered as {}",
+                header.tendermint_height, self.height
+            )));
+        }
+        let _ = fs::remove_file(&path);
+        Ok(header)
+    }
+}
+
+/// The ABCI application serving and restoring the snapshots,
+/// see the module doc
+pub struct StateSyncApp {
+    app: Option<ABCISubmissionServer>,
+    build: Option<Builder>,
+    restore: Option<Restore>,
+}
+
+impl StateSyncApp {
+    /// Wrap an app which is already running
+    pub fn ready(app: ABCISubmissionServer) -> Self {
+        StateSyncApp {
+            app: Some(app),
+            build: None,
+            restore: None,
+        }
+    }
+
+    /// Wait for a snapshot, the app is built by `build` after the restore,
+    /// or on the first request other than the state sync ones
+    pub fn deferred(build: Builder) -> Self {
+        StateSyncApp {
+            app: None,
+            build: Some(build),
+            restore: None,
+        }
+    }
+
+    /// Whether to wait for a snapshot before building the app
+    pub fn should_defer() -> bool {
+        CFG.enable_state_sync_restore
+            && fs::read_dir(&CFG.ledger_dir)
+                .map(|mut rd| rd.next().is_none())
+                .unwrap_or(true)
+    }
+
+    fn app(&mut self) -> &mut ABCISubmissionServer {
+        if self.app.is_none() {
+            if self.restore.take().is_some() {
+                warn!(target: "abciapp", "state sync abandoned, starting from the genesis");
+            }
+            let app = pnk!(self.build.as_ref().c(d!()).and_then(|b| b().c(d!())));
+            self.app = Some(app);
+        }
+        pnk!(self.app.as_mut())
+    }
+
+    fn offer(&mut self, req: &RequestOfferSnapshot) -> ResponseOfferSnapshot_Result {
+        if self.app.is_some() {
+            warn!(target: "abciapp", "a snapshot offered to a ledger in use");
+            return ResponseOfferSnapshot_Result::ABORT;
+        }
+        let s = req.get_snapshot();
+        if s.format != SNAPSHOT_FORMAT_VERSION as u32 {
+            return ResponseOfferSnapshot_Result::REJECT_FORMAT;
+        }
+        match Restore::new(&snapshot_dir(), s) {
+            Ok(r) => {
+                info!(
+                    target: "abciapp",
+                    "restoring the snapshot at height {}, {} chunks",
+                    r.height,
+                    r.chunk_hashes.len()
+                );
+                self.restore = Some(r);
+                ResponseOfferSnapshot_Result::ACCEPT
+            }
+            Err(e) => {
+                warn!(target: "abciapp", "snapshot rejected: {}", e.generate_log(None));
+                ResponseOfferSnapshot_Result::REJECT
+            }
+        }
+    }
+
+    fn apply(&mut self, req: &RequestApplySnapshotChunk) -> ResponseApplySnapshotChunk {
+        let mut resp = ResponseApplySnapshotChunk::new();
+        let r = match self.restore.as_mut() {
+            Some(r) => r,
+            None => {
+                resp.set_result(ResponseApplySnapshotChunk_Result::ABORT);
+                return resp;
+            }
+        };
+
+        match r.apply(req.index, req.get_chunk()) {
+            Ok(true) => {}
+            Ok(false) => {
+                // fetch it again from another peer
+                resp.set_result(ResponseApplySnapshotChunk_Result::RETRY);
+                resp.set_refetch_chunks(vec![req.index]);
+                resp.set_reject_senders(RepeatedField::from_vec(vec![req
+                    .sender
+                    .clone()]));
+                return resp;
+            }
+            Err(e) => {
+                error!(target: "abciapp", "{}", e.generate_log(None));
+                resp.set_result(ResponseApplySnapshotChunk_Result::REJECT_SNAPSHOT);
+                self.restore = None;
+                return resp;
+            }
+        }
+        if !r.is_complete() {
+            resp.set_result(ResponseApplySnapshotChunk_Result::ACCEPT);
+            return resp;
+        }
+
+        let height = r.height;
+        let res = r.import(&CFG.ledger_dir);
+        self.restore = None;
+        match res {
+            Ok(header) => {
+                info!(target: "abciapp", "snapshot at height {} restored", height);
+                // the files are in place, a mismatch can't be rolled back
+                let verified = self
+                    .app()
+                    .la
+                    .read()
+                    .get_committed_state()
+                    .read()
+                    .verify_snapshot(&header);
+                pnk!(verified.c(d!("the restored ledger does not match its commitment")));
+                resp.set_result(ResponseApplySnapshotChunk_Result::ACCEPT);
+            }
+            Err(e) => {
+                error!(target: "abciapp", "{}", e.generate_log(None));
+                resp.set_result(ResponseApplySnapshotChunk_Result::REJECT_SNAPSHOT);
+            }
+        }
+        resp
+    }
+}
+
+impl Application for StateSyncApp {
+    fn info(&mut self, req: &RequestInfo) -> ResponseInfo {
+        match self.app.as_mut() {
+            Some(app) => app.info(req),
+            // an empty state, tendermint then either offers the snapshots
+            // or starts from the genesis
+            None => ResponseInfo::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn query(&mut self, req: &RequestQuery) -> ResponseQuery {
+        self.app().query(req)
+    }
+
+    #[inline(always)]
+    fn check_tx(&mut self, req: &RequestCheckTx) -> ResponseCheckTx {
+        self.app().check_tx(req)
+    }
+
+    #[inline(always)]
+    fn init_chain(&mut self, req: &RequestInitChain) -> ResponseInitChain {
+        self.app().init_chain(req)
+    }
+
+    #[inline(always)]
+    fn begin_block(&mut self, req: &RequestBeginBlock) -> ResponseBeginBlock {
+        self.app().begin_block(req)
+    }
+
+    #[inline(always)]
+    fn deliver_tx(&mut self, req: &RequestDeliverTx) -> ResponseDeliverTx {
+        self.app().deliver_tx(req)
+    }
+
+    #[inline(always)]
+    fn end_block(&mut self, req: &RequestEndBlock) -> ResponseEndBlock {
+        self.app().end_block(req)
+    }
+
+    #[inline(always)]
+    fn commit(&mut self, req: &RequestCommit) -> ResponseCommit {
+        self.app().commit(req)
+    }
+
+    fn list_snapshots(&mut self, _req: &RequestListSnapshots) -> ResponseListSnapshots {
+        let mut resp = ResponseListSnapshots::new();
+        resp.set_snapshots(RepeatedField::from_vec(
+            list(&snapshot_dir())
+                .iter()
+                .map(|m| m.to_snapshot())
+                .collect(),
+        ));
+        resp
+    }
+
+    fn offer_snapshot(&mut self, req: &RequestOfferSnapshot) -> ResponseOfferSnapshot {
+        let mut resp = ResponseOfferSnapshot::new();
+        resp.set_result(self.offer(req));
+        resp
+    }
+
+    fn load_snapshot_chunk(
+        &mut self,
+        req: &RequestLoadSnapshotChunk,
+    ) -> ResponseLoadSnapshotChunk {
+        let mut resp = ResponseLoadSnapshotChunk::new();
+        match load_chunk(&snapshot_dir(), req.height, req.format, req.chunk) {
+            Ok(chunk) => resp.set_chunk(chunk),
+            Err(e) => warn!(target: "abciapp", "{}", e.generate_log(None)),
+        }
+        resp
+    }
+
+    fn apply_snapshot_chunk(
+        &mut self,
+        req: &RequestApplySnapshotChunk,
+    ) -> ResponseApplySnapshotChunk {
+        self.apply(req)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chunk_hashes() {
+        let dir = globutils::fresh_tmp_dir();
+        let path = dir.join("snap");
+        let data = vec![7u8; CHUNK_SIZE as usize + 10];
+        pnk!(fs::write(&path, &data));
+
+        let (hash, chunks) = pnk!(hash_chunks(&path));
+        assert_eq!(hash, hex::encode(Sha256::digest(&data)));
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            chunks[1],
+            hex::encode(Sha256::digest(&data[CHUNK_SIZE as usize..]))
+        );
+    }
+
+    #[test]
+    fn export_and_restore() {
+        let ledger = LedgerState::tmp_ledger();
+        let tmp = globutils::fresh_tmp_dir();
+        let dir = tmp.join("statesync");
+        pnk!(fs::create_dir_all(&dir));
+
+        for height in [10, 20, 30] {
+            let staging = snapshot_path(&dir, height, "staging");
+            let cp = pnk!(ledger.checkpoint_snapshot(&staging.to_string_lossy()));
+            pnk!(export(&dir, &cp, height, 2));
+        }
+        // the oldest one is dropped
+        let mut heights = list(&dir).iter().map(|m| m.height).collect::<Vec<_>>();
+        heights.sort_unstable();
+        assert_eq!(heights, vec![20, 30]);
+
+        let meta = list(&dir).into_iter().find(|m| 30 == m.height).unwrap();
+        let offered = meta.to_snapshot();
+        let peer = tmp.join("peer");
+        let mut r = pnk!(Restore::new(&peer, &offered));
+
+        // a chunk not matching its hash is refetched
+        let chunk = pnk!(load_chunk(&dir, 30, offered.format, 0));
+        let mut bad = chunk.clone();
+        bad[0] ^= 1;
+        assert!(!pnk!(r.apply(0, &bad)));
+        assert!(!r.is_complete());
+        assert!(load_chunk(&dir, 30, offered.format, offered.chunks).is_err());
+
+        for i in 0..offered.chunks {
+            let chunk = pnk!(load_chunk(&dir, 30, offered.format, i));
+            assert!(pnk!(r.apply(i, &chunk)));
+        }
+        assert!(r.is_complete());
+
+        let basedir = tmp.join("restored").to_string_lossy().into_owned();
+        let header = pnk!(r.import(&basedir));
+        assert_eq!(header.block_commit_count, ledger.get_block_commit_count());
+        for e in header.entries.iter() {
+            let len = pnk!(fs::metadata(format!("{basedir}/{}", e.path))).len();
+            assert_eq!(len, e.size);
+        }
+        assert!(!peer.join(RESTORE_FILE).exists());
+    }
+
+    #[test]
+    fn restore_rejects_bad_metadata() {
+        let dir = globutils::fresh_tmp_dir();
+        let mut s = Snapshot::new();
+        s.set_height(10);
+        s.set_chunks(2);
+        s.set_metadata(pnk!(serde_json::to_vec(&vec!["00".to_owned()])));
+        assert!(Restore::new(&dir, &s).is_err());
+
+        s.set_chunks(0);
+        s.set_metadata(pnk!(serde_json::to_vec(&Vec::<String>::new())));
+        assert!(Restore::new(&dir, &s).is_err());
+
+        s.set_metadata(b"not json".to_vec());
+        assert!(Restore::new(&dir, &s).is_err());
+    }
+}
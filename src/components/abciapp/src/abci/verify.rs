@@ -0,0 +1,82 @@
+//!
+//! # Offline verification of the ledger
+//!
+//! `abcid --ledger-verify` checks the ledger directory,
+//! then replays its blocks in a child process on an empty ledger,
+//! see `ledger::store::verify` for the reason of the child process.
+//!
+
+use {
+    ledger::store::{verify::Divergence, LedgerState},
+    ruc::*,
+    std::{env, path::Path, process::Command},
+    tracing::info,
+};
+
+// set in the child process, the path of the dumped blocks
+const DUMP_VAR: &str = "LEDGER_VERIFY_DUMP";
+
+/// Verify the ledger in `ledger_dir`, an error is returned if any divergence
+pub fn verify(ledger_dir: &str) -> Result<()> {
+    if let Ok(dump) = env::var(DUMP_VAR) {
+        return replay(&dump).c(d!());
+    }
+
+    if !Path::new(ledger_dir).join("ledger_status").exists() {
+        return Err(eg!(format!("no ledger found in {ledger_dir}")));
+    }
+    env::set_var("BNC_DATA_DIR", format!("{ledger_dir}/__bnc__"));
+
+    let ledger = LedgerState::load_or_init(ledger_dir).c(d!())?;
+    let divergences = ledger.check_stores();
+    report("stores", &divergences);
+
+    let tmp = globutils::fresh_tmp_dir();
+    let dump = tmp.join("blocks").to_string_lossy().into_owned();
+    let n = ledger.dump_blocks(&dump).c(d!())?;
+    info!("{} blocks dumped to {}, replaying", n, &dump);
+
+    let replayed = Command::new(env::current_exe().c(d!())?)
+        .args(env::args().skip(1))
+        .env(DUMP_VAR, &dump)
+        .status()
+        .c(d!())?;
+
+    if divergences.is_empty() && replayed.success() {
+        info!("the ledger is intact, block count: {}", n);
+        Ok(())
+    } else {
+        Err(eg!("the ledger diverges, see the reports above"))
+    }
+}
+
+// runs in the child process
+fn replay(dump: &str) -> Result<()> {
+    let dir = Path::new(dump)
+        .parent()
+        .c(d!())?
+        .to_string_lossy()
+        .into_owned();
+    env::set_var("BNC_DATA_DIR", format!("{dir}/__bnc__"));
+
+    let mut ledger =
+        LedgerState::new(&format!("{dir}/ledger"), Some("verify")).c(d!())?;
+    let (n, divergences) = ledger.replay_blocks(dump).c(d!())?;
+    info!("{} blocks replayed", n);
+    report("replay", &divergences);
+
+    if divergences.is_empty() {
+        Ok(())
+    } else {
+        Err(eg!("the replay diverges"))
+    }
+}
+
+pub(super) fn report(stage: &str, divergences: &[Divergence]) {
+    for d in divergences.iter() {
+        println!(
+            "[{}] height {}, {}: expected {}, found {}",
+            stage, d.height, d.what, d.expected, d.found
+        );
+    }
+}
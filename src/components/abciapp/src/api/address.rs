@@ -0,0 +1,61 @@
+//!
+//! # Addresses in the requests
+//!
+//! The routes take an address either in bech32, prefixed by the human
+//! readable part of the chain, or as the legacy base64 public key.
+//!
+
+use {globutils::wallet, ruc::*, serde::Serialize, zei::XfrPublicKey};
+
+/// An address in both of its encodings, as served by `/convert_address`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ConvertedAddress {
+    #[allow(missing_docs)]
+    pub bech32: String,
+    /// the legacy encoding
+    pub base64: String,
+}
+
+impl From<&XfrPublicKey> for ConvertedAddress {
+    fn from(pk: &XfrPublicKey) -> Self {
+        ConvertedAddress {
+            bech32: wallet::public_key_to_bech32(pk),
+            base64: wallet::public_key_to_base64(pk),
+        }
+    }
+}
+
+/// The public key of an address in bech32 or base64, bech32 is tried first
+/// as its checksum rejects the mistyped ones
+pub fn parse_address(addr: &str) -> Result<XfrPublicKey> {
+    let addr = addr.trim();
+    wallet::public_key_from_bech32(addr)
+        .or_else(|_| wallet::public_key_from_base64(addr))
+        .c(d!(format!(
+            "invalid address `{addr}`, expected bech32 or base64"
+        )))
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, rand_chacha::ChaChaRng, rand_core::SeedableRng, zei::XfrKeyPair};
+
+    #[test]
+    fn test_parse_address() {
+        let pk = XfrKeyPair::generate(&mut ChaChaRng::from_entropy()).get_pk();
+        let conv = ConvertedAddress::from(&pk);
+        assert_eq!(pk, pnk!(parse_address(&conv.bech32)));
+        assert_eq!(pk, pnk!(parse_address(&conv.base64)));
+        assert_eq!(
+            conv,
+            ConvertedAddress::from(&pnk!(parse_address(&conv.bech32)))
+        );
+
+        // a typo breaks the checksum
+        let mut typo = conv.bech32.clone();
+        let last = typo.pop().unwrap();
+        typo.push(if 'q' == last { 'p' } else { 'q' });
+        assert!(parse_address(&typo).is_err());
+        assert!(parse_address("").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//!
+//! # Authentication of the REST services
+//!
+//! A request carries `Authorization: Bearer <credential>`, the credential is
+//! either a static API key of the config, or a JWT signed by `jwt_secret`
+//! (HS256) with a `role` claim and an optional `exp` claim.
+//! Requests without any bearer credential are `public`, those with an
+//! unknown or invalid one are rejected.
+//!
+//! The `Auth` middleware resolves the role of every request, the handlers
+//! check it by `require_role`, the admin endpoints by `authorize`.
+//!
+
+use {
+    super::error::unauthorized,
+    actix_service::{Service, Transform},
+    actix_web::{
+        dev::{ServiceRequest, ServiceResponse},
+        error::Error as ActixError,
+        http::{header, HeaderMap},
+        HttpMessage, HttpRequest,
+    },
+    chrono::Utc,
+    config::abci::global_cfg::{ApiRole, CFG},
+    futures::future::{ready, Either, Ready},
+    hmac::{Hmac, Mac},
+    ruc::*,
+    serde::Deserialize,
+    serde_json::Value,
+    sha2::Sha256,
+    std::task::{Context, Poll},
+};
+
+/// Resolve the role of each request, see the module doc
+pub struct Auth;
+
+impl<S, B> Transform<S> for Auth
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = ActixError,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = ActixError;
+    type InitError = ();
+    type Transform = AuthMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthMiddleware { service }))
+    }
+}
+
+#[allow(missing_docs)]
+pub struct AuthMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for AuthMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = ActixError,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = ActixError;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        match role_of(req.headers()) {
+            Ok(role) => {
+                req.extensions_mut().insert(role);
+                Either::Left(self.service.call(req))
+            }
+            Err(e) => Either::Right(ready(Err(e))),
+        }
+    }
+}
+
+/// Check that the role of a request includes `role`
+pub fn require_role(req: &HttpRequest, role: ApiRole) -> Result<(), ActixError> {
+    let given = req
+        .extensions()
+        .get::<ApiRole>()
+        .copied()
+        .unwrap_or_default();
+    if role <= given {
+        Ok(())
+    } else {
+        Err(unauthorized(format!("the {role:?} role is required")))
+    }
+}
+
+/// Check that a request of an administrative endpoint has the admin role
+#[inline(always)]
+pub fn authorize(req: &HttpRequest) -> Result<(), ActixError> {
+    require_role(req, ApiRole::Admin)
+}
+
+fn role_of(headers: &HeaderMap) -> Result<ApiRole, ActixError> {
+    // other schemes are left to the proxies in front of the node
+    let token = match headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        Some(t) => t.trim(),
+        None => return Ok(ApiRole::Public),
+    };
+
+    let auth = &CFG.auth;
+    if let Some(role) = auth
+        .api_keys
+        .iter()
+        .find(|(k, _)| constant_time_eq(k.as_bytes(), token.as_bytes()))
+        .map(|(_, r)| *r)
+    {
+        return Ok(role);
+    }
+
+    match auth.jwt_secret.as_deref() {
+        Some(secret) if 2 == token.matches('.').count() => {
+            verify_jwt(token, secret, Utc::now().timestamp())
+                .map_err(|e| unauthorized(e.generate_log(None)))
+        }
+        _ => Err(unauthorized("invalid bearer token")),
+    }
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    role: ApiRole,
+    /// unix seconds
+    #[serde(default)]
+    exp: Option<i64>,
+}
+
+// the role claimed by an HS256 JWT
+fn verify_jwt(token: &str, secret: &str, now: i64) -> Result<ApiRole> {
+    let parts = token.split('.').collect::<Vec<_>>();
+    if 3 != parts.len() {
+        return Err(eg!("malformed JWT"));
+    }
+
+    let header: Value = serde_json::from_slice(&b64url(parts[0]).c(d!())?).c(d!())?;
+    if "HS256" != header["alg"] {
+        return Err(eg!("only HS256 JWTs are accepted"));
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).c(d!())?;
+    mac.update(parts[0].as_bytes());
+    mac.update(b".");
+    mac.update(parts[1].as_bytes());
+    mac.verify_slice(&b64url(parts[2]).c(d!())?)
+        .map_err(|_| eg!("invalid JWT signature"))?;
+
+    let claims: Claims = serde_json::from_slice(&b64url(parts[1]).c(d!())?).c(d!())?;
+    if matches!(claims.exp, Some(exp) if exp <= now) {
+        return Err(eg!("the JWT has expired"));
+    }
+    Ok(claims.role)
+}
+
+fn b64url(s: &str) -> Result<Vec<u8>> {
+    base64::decode_config(s, base64::URL_SAFE_NO_PAD).c(d!())
+}
+
+// do not leak the length of the matching prefix through the timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && 0 == a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn jwt(secret: &str, alg: &str, claims: &str) -> String {
+        let enc = |s: &str| base64::encode_config(s, base64::URL_SAFE_NO_PAD);
+        let signed = format!(
+            "{}.{}",
+            enc(&format!("{{\"alg\":\"{alg}\",\"typ\":\"JWT\"}}")),
+            enc(claims)
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(signed.as_bytes());
+        let sig =
+            base64::encode_config(mac.finalize().into_bytes(), base64::URL_SAFE_NO_PAD);
+        format!("{signed}.{sig}")
+    }
+
+    #[test]
+    fn jwt_roles() {
+        let t = jwt("secret", "HS256", r#"{"role":"wallet"}"#);
+        assert_eq!(pnk!(verify_jwt(&t, "secret", 0)), ApiRole::Wallet);
+        assert!(verify_jwt(&t, "other", 0).is_err());
+
+        let t = jwt("secret", "HS256", r#"{"role":"admin","exp":100}"#);
+        assert_eq!(pnk!(verify_jwt(&t, "secret", 99)), ApiRole::Admin);
+        assert!(verify_jwt(&t, "secret", 100).is_err());
+
+        let t = jwt("secret", "none", r#"{"role":"admin"}"#);
+        assert!(verify_jwt(&t, "secret", 0).is_err());
+        let t = jwt("secret", "HS256", r#"{"role":"root"}"#);
+        assert!(verify_jwt(&t, "secret", 0).is_err());
+
+        assert!(ApiRole::Public < ApiRole::Wallet && ApiRole::Wallet < ApiRole::Admin);
+    }
+}
@@ -0,0 +1,305 @@
+//!
+//! # Rotated backups of the ledger
+//!
+//! Every `backup_interval` blocks, a background thread exports the ledger
+//! to `ledger-<block_commit_count>.snap` in `backup_target`, and drops the
+//! backups beyond the `backup_keep` newest. The commits are blocked only
+//! while `LedgerState::checkpoint_snapshot` holds the read lock, the export
+//! of the checkpoint, the upload and the pruning run without it.
+//!
+//! A target starting with `http://` or `https://` is taken as the URL of a
+//! bucket of an S3-compatible store: the backups are staged next to the
+//! ledger directory, then written by a plain `PUT <target>/<name>`, and
+//! pruned by `DELETE`. Requests are not signed, so the bucket must accept
+//! the writes of the node, e.g. by a policy on its address. Only the
+//! backups uploaded by the running process are pruned from a bucket.
+//!
+//! The progress is served by `/admin/backup`.
+//!
+
+use {
+    chrono::Utc,
+    config::abci::global_cfg::CFG,
+    lazy_static::lazy_static,
+    ledger::store::LedgerState,
+    parking_lot::RwLock,
+    ruc::*,
+    serde::Serialize,
+    std::{
+        collections::VecDeque,
+        fs::{self, File},
+        path::{Path, PathBuf},
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    },
+    tracing::{error, info},
+};
+
+// how often the height of the ledger is checked
+const POLL_ITV: Duration = Duration::from_secs(5);
+
+const PREFIX: &str = "ledger-";
+const EXT: &str = ".snap";
+
+lazy_static! {
+    static ref STATUS: RwLock<BackupStatus> = RwLock::new(BackupStatus::default());
+}
+
+/// State of the backups, served by `/admin/backup`
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BackupStatus {
+    /// 0 if the backups are disabled
+    pub interval: u64,
+    #[allow(missing_docs)]
+    pub target: String,
+    /// a backup is being exported or uploaded
+    pub running: bool,
+    /// block commit count of the last successful backup
+    pub last_block_commit_count: Option<u64>,
+    /// when the last backup finished, in unix seconds
+    pub last_at: Option<i64>,
+    #[allow(missing_docs)]
+    pub last_elapsed_ms: Option<u64>,
+    /// error of the last attempt, cleared by a successful one
+    pub last_error: Option<String>,
+    /// the backups kept in the target, oldest first
+    pub backups: VecDeque<String>,
+}
+
+/// The current state of the backups
+pub fn status() -> BackupStatus {
+    STATUS.read().clone()
+}
+
+enum Target {
+    Dir(PathBuf),
+    Bucket(String),
+}
+
+impl Target {
+    fn from_cfg() -> Self {
+        match CFG.backup_target.as_deref() {
+            Some(t) if t.starts_with("http://") || t.starts_with("https://") => {
+                Target::Bucket(t.trim_end_matches('/').to_owned())
+            }
+            Some(t) => Target::Dir(PathBuf::from(t)),
+            None => Target::Dir(PathBuf::from(format!(
+                "{}_backups",
+                CFG.ledger_dir.trim_end_matches('/')
+            ))),
+        }
+    }
+
+    fn display(&self) -> String {
+        match self {
+            Target::Dir(d) => d.to_string_lossy().into_owned(),
+            Target::Bucket(url) => url.clone(),
+        }
+    }
+}
+
+/// Start the background backups, if `backup_interval` is not zero
+pub fn start(ledger: Arc<RwLock<LedgerState>>) {
+    let itv = CFG.backup_interval;
+    let target = Target::from_cfg();
+    {
+        let mut s = STATUS.write();
+        s.interval = itv;
+        s.target = target.display();
+    }
+    if 0 == itv {
+        return;
+    }
+
+    if let Target::Dir(dir) = &target {
+        STATUS.write().backups = list_dir(dir).into_iter().map(|(_, n)| n).collect();
+    }
+
+    thread::spawn(move || {
+        let mut last = ledger.read().get_block_commit_count();
+        loop {
+            thread::sleep(POLL_ITV);
+            let count = ledger.read().get_block_commit_count();
+            if count < last + itv {
+                continue;
+            }
+            last = count;
+
+            STATUS.write().running = true;
+            let start = Instant::now();
+            let res = backup(&ledger, &target);
+            let mut s = STATUS.write();
+            s.running = false;
+            match res {
+                Ok((count, backups)) => {
+                    info!(
+                        "ledger backup at {} done in {}ms",
+                        count,
+                        start.elapsed().as_millis()
+                    );
+                    s.last_block_commit_count = Some(count);
+                    s.last_at = Some(Utc::now().timestamp());
+                    s.last_elapsed_ms = Some(start.elapsed().as_millis() as u64);
+                    s.last_error = None;
+                    s.backups = backups;
+                }
+                Err(e) => {
+                    let e = e.generate_log(None);
+                    error!("ledger backup failed: {}", e);
+                    s.last_error = Some(e);
+                }
+            }
+        }
+    });
+}
+
+// export, upload and prune, returns the block commit count of the new backup
+// along with the backups kept
+fn backup(
+    ledger: &RwLock<LedgerState>,
+    target: &Target,
+) -> Result<(u64, VecDeque<String>)> {
+    match target {
+        Target::Dir(dir) => {
+            fs::create_dir_all(dir).c(d!())?;
+            let (count, tmp) = export(ledger, dir).c(d!())?;
+            let name = name_of(count);
+            fs::rename(&tmp, dir.join(&name)).c(d!())?;
+
+            let mut backups = list_dir(dir);
+            let stale = backups.len().saturating_sub(CFG.backup_keep.max(1));
+            for (_, n) in backups.drain(..stale) {
+                fs::remove_file(dir.join(&n)).c(d!(n))?;
+            }
+            Ok((count, backups.into_iter().map(|(_, n)| n).collect()))
+        }
+        Target::Bucket(url) => {
+            let staging = PathBuf::from(format!(
+                "{}_snapshots",
+                CFG.ledger_dir.trim_end_matches('/')
+            ));
+            fs::create_dir_all(&staging).c(d!())?;
+            let (count, tmp) = export(ledger, &staging).c(d!())?;
+            let name = name_of(count);
+            let res = upload(&tmp, &format!("{url}/{name}")).c(d!());
+            let _ = fs::remove_file(&tmp);
+            res?;
+
+            let mut backups = STATUS.read().backups.clone();
+            backups.push_back(name);
+            while backups.len() > CFG.backup_keep.max(1) {
+                if let Some(n) = backups.front() {
+                    delete(&format!("{url}/{n}")).c(d!())?;
+                }
+                backups.pop_front();
+            }
+            Ok((count, backups))
+        }
+    }
+}
+
+// export the ledger to a temporary file in `dir`,
+// so a partial backup is never listed
+fn export(ledger: &RwLock<LedgerState>, dir: &Path) -> Result<(u64, PathBuf)> {
+    let cp = {
+        let ledger = ledger.read();
+        let staging = dir.join(format!(
+            "{}.staging",
+            name_of(ledger.get_block_commit_count())
+        ));
+        ledger
+            .checkpoint_snapshot(&staging.to_string_lossy())
+            .c(d!())?
+    };
+    let tmp = dir.join(format!("{}.part", name_of(cp.block_commit_count())));
+    match cp.export(&tmp.to_string_lossy(), CFG.zstd_level) {
+        Ok(h) => Ok((h.block_commit_count, tmp)),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e).c(d!())
+        }
+    }
+}
+
+fn upload(path: &Path, url: &str) -> Result<()> {
+    let f = File::open(path).c(d!())?;
+    attohttpc::put(url)
+        .file(f)
+        .send()
+        .c(d!(url))?
+        .error_for_status()
+        .c(d!(url))
+        .map(|_| ())
+}
+
+fn delete(url: &str) -> Result<()> {
+    let resp = attohttpc::delete(url).send().c(d!(url))?;
+    // already gone
+    if resp.is_success() || 404 == resp.status().as_u16() {
+        Ok(())
+    } else {
+        Err(eg!(format!("{}: {}", url, resp.status())))
+    }
+}
+
+fn name_of(count: u64) -> String {
+    format!("{PREFIX}{count}{EXT}")
+}
+
+// the backups in `dir`, oldest first
+fn list_dir(dir: &Path) -> Vec<(u64, String)> {
+    let mut backups = fs::read_dir(dir)
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .filter_map(|e| e.file_name().into_string().ok())
+                .filter_map(|n| {
+                    n.strip_prefix(PREFIX)
+                        .and_then(|s| s.strip_suffix(EXT))
+                        .and_then(|s| s.parse::<u64>().ok())
+                        .map(|c| (c, n.clone()))
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    backups.sort_unstable();
+    backups
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_list_dir() {
+        let dir = globutils::fresh_tmp_dir();
+        for n in [
+            "ledger-10.snap",
+            "ledger-9.snap",
+            "ledger-11.snap.part",
+            "ledger-12.snap.staging",
+            "ledger-.snap",
+            "ledger-x.snap",
+            "ledger--1.snap",
+            "ledger-18446744073709551616.snap",
+            "other",
+        ] {
+            fs::write(dir.join(n), b"").unwrap();
+        }
+        let names = list_dir(&dir)
+            .into_iter()
+            .map(|(_, n)| n)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["ledger-9.snap", "ledger-10.snap"], names);
+
+        // ordered by the count, not by the name
+        let counts = list_dir(&dir)
+            .into_iter()
+            .map(|(c, _)| c)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![9, 10], counts);
+
+        // nothing to list in a missing target
+        assert!(list_dir(&dir.join("missing")).is_empty());
+    }
+}
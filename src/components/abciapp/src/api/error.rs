@@ -0,0 +1,155 @@
+//!
+//! # Errors of the REST services
+//!
+//! Every handler reports errors as `{"code": "...", "message": "..."}`,
+//! clients should branch on `code` instead of parsing `message`.
+//!
+
+use {
+    actix_web::{
+        error::Error as ActixError, http::StatusCode, HttpRequest, HttpResponse,
+        ResponseError,
+    },
+    serde::{Deserialize, Serialize},
+    std::fmt,
+};
+
+/// Machine-readable error codes
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// Malformed path, query or body
+    InvalidParam,
+    /// The requested item does not exist
+    NotFound,
+    /// The transaction has been rejected
+    TxnRejected,
+    /// The data is not ready yet, retry later
+    Unavailable,
+    /// The pinned height is outdated for this query, start a new read session
+    StaleRead,
+    /// Missing or wrong credentials
+    Unauthorized,
+    /// The body exceeds the limit of the route
+    PayloadTooLarge,
+    /// Unexpected failure inside the node
+    Internal,
+}
+
+impl ErrorCode {
+    #[inline(always)]
+    fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidParam | ErrorCode::TxnRejected => StatusCode::BAD_REQUEST,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::StaleRead => StatusCode::CONFLICT,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// The error body of all REST services
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    #[allow(missing_docs)]
+    pub fn new(code: ErrorCode, message: impl fmt::Display) -> Self {
+        ApiError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(self)
+    }
+}
+
+/// `ErrorCode::InvalidParam`
+#[inline(always)]
+pub fn invalid_param(msg: impl fmt::Display) -> ActixError {
+    ApiError::new(ErrorCode::InvalidParam, msg).into()
+}
+
+/// `ErrorCode::NotFound`
+#[inline(always)]
+pub fn not_found(msg: impl fmt::Display) -> ActixError {
+    ApiError::new(ErrorCode::NotFound, msg).into()
+}
+
+/// `ErrorCode::TxnRejected`
+#[inline(always)]
+pub fn txn_rejected(msg: impl fmt::Display) -> ActixError {
+    ApiError::new(ErrorCode::TxnRejected, msg).into()
+}
+
+/// `ErrorCode::Unavailable`
+#[inline(always)]
+pub fn unavailable(msg: impl fmt::Display) -> ActixError {
+    ApiError::new(ErrorCode::Unavailable, msg).into()
+}
+
+/// `ErrorCode::Unauthorized`
+#[inline(always)]
+pub fn unauthorized(msg: impl fmt::Display) -> ActixError {
+    ApiError::new(ErrorCode::Unauthorized, msg).into()
+}
+
+/// `ErrorCode::PayloadTooLarge`
+#[inline(always)]
+pub fn payload_too_large(msg: impl fmt::Display) -> ActixError {
+    ApiError::new(ErrorCode::PayloadTooLarge, msg).into()
+}
+
+/// `ErrorCode::StaleRead`
+#[inline(always)]
+pub fn stale_read(msg: impl fmt::Display) -> ActixError {
+    ApiError::new(ErrorCode::StaleRead, msg).into()
+}
+
+/// `ErrorCode::Internal`
+#[inline(always)]
+pub fn internal(msg: impl fmt::Display) -> ActixError {
+    ApiError::new(ErrorCode::Internal, msg).into()
+}
+
+/// Used by the path/query/json extractors,
+/// so that a malformed request gets the same error body
+pub fn extractor_error<E: fmt::Display>(e: E, _: &HttpRequest) -> ActixError {
+    invalid_param(e)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn error_body() {
+        let e = ApiError::new(ErrorCode::NotFound, "no such txn");
+        assert_eq!(e.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            serde_json::to_string(&e).unwrap(),
+            r#"{"code":"NOT_FOUND","message":"no such txn"}"#
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//!
+//! # Health of the node
+//!
+//! Fed by the ABCI callbacks, reported by `/healthz` and `/readyz` of the query server.
+//!
+
+use {
+    chrono::Utc,
+    serde::Serialize,
+    std::sync::atomic::{AtomicI64, Ordering},
+};
+
+/// The ABCI connection is regarded as dead
+/// if no request has been received from tendermint in this period
+pub const ABCI_TIMEOUT_SECS: i64 = 60;
+
+/// The node is regarded as syncing
+/// if the header time of the latest block is older than this
+pub const MAX_BLOCK_AGE_SECS: i64 = 120;
+
+/// The query server is regarded as caught up
+/// if it lags behind the ledger no more than this number of blocks
+pub const MAX_INDEX_LAG: u64 = 1;
+
+static LAST_ABCI_REQ_TIME: AtomicI64 = AtomicI64::new(0);
+static LAST_BLOCK_TIME: AtomicI64 = AtomicI64::new(0);
+static LAST_COMMIT_HEIGHT: AtomicI64 = AtomicI64::new(0);
+static LAST_COMMIT_TIME: AtomicI64 = AtomicI64::new(0);
+
+/// Called on every request from tendermint
+#[inline(always)]
+pub fn abci_req_received() {
+    LAST_ABCI_REQ_TIME.store(Utc::now().timestamp(), Ordering::Relaxed);
+}
+
+/// Called in `begin_block` with the header time of the block, in unix seconds
+#[inline(always)]
+pub fn block_begun(block_time: i64) {
+    abci_req_received();
+    LAST_BLOCK_TIME.store(block_time, Ordering::Relaxed);
+}
+
+/// Called in `commit`
+#[inline(always)]
+pub fn block_committed(height: i64) {
+    abci_req_received();
+    LAST_COMMIT_HEIGHT.store(height, Ordering::Relaxed);
+    LAST_COMMIT_TIME.store(Utc::now().timestamp(), Ordering::Relaxed);
+}
+
+/// Health and sync status of the node
+#[derive(Clone, Debug, Serialize)]
+pub struct HealthStat {
+    /// a request has been received from tendermint recently
+    pub abci_alive: bool,
+    /// seconds since the last request from tendermint
+    pub secs_since_last_abci_req: Option<i64>,
+    /// tendermint height of the last committed block
+    pub last_committed_height: i64,
+    /// seconds since the last block was committed locally
+    pub secs_since_last_block: Option<i64>,
+    /// seconds between the header time of the latest block and now
+    pub last_block_age_secs: Option<i64>,
+    /// the node is replaying historical blocks
+    pub syncing: bool,
+    /// block commit count of the ledger
+    pub ledger_commit_count: u64,
+    /// block commit count seen by the query server
+    pub indexed_commit_count: u64,
+    /// the query server has caught up with the ledger
+    pub indexes_caught_up: bool,
+    /// the node is ready to serve traffic
+    pub ready: bool,
+}
+
+impl HealthStat {
+    /// Evaluate the current status,
+    /// the commit counts are provided by the query server
+    pub fn collect(ledger_commit_count: u64, indexed_commit_count: u64) -> Self {
+        Self::evaluate(
+            Utc::now().timestamp(),
+            ledger_commit_count,
+            indexed_commit_count,
+        )
+    }
+
+    fn evaluate(now: i64, ledger_commit_count: u64, indexed_commit_count: u64) -> Self {
+        let since = |t: i64| if 0 < t { Some(now - t) } else { None };
+
+        let secs_since_last_abci_req = since(LAST_ABCI_REQ_TIME.load(Ordering::Relaxed));
+        let last_block_age_secs = since(LAST_BLOCK_TIME.load(Ordering::Relaxed));
+        let abci_alive =
+            matches!(secs_since_last_abci_req, Some(s) if s <= ABCI_TIMEOUT_SECS);
+        let syncing = !matches!(last_block_age_secs, Some(s) if s <= MAX_BLOCK_AGE_SECS);
+        let indexes_caught_up =
+            ledger_commit_count.saturating_sub(indexed_commit_count) <= MAX_INDEX_LAG;
+
+        HealthStat {
+            abci_alive,
+            secs_since_last_abci_req,
+            last_committed_height: LAST_COMMIT_HEIGHT.load(Ordering::Relaxed),
+            secs_since_last_block: since(LAST_COMMIT_TIME.load(Ordering::Relaxed)),
+            last_block_age_secs,
+            syncing,
+            ledger_commit_count,
+            indexed_commit_count,
+            indexes_caught_up,
+            ready: abci_alive && !syncing && indexes_caught_up,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn readiness() {
+        let now = Utc::now().timestamp();
+
+        block_begun(now - 10 * MAX_BLOCK_AGE_SECS);
+        block_committed(10);
+        let s = HealthStat::evaluate(now, 10, 10);
+        assert!(s.abci_alive);
+        assert!(s.syncing);
+        assert!(!s.ready);
+        assert_eq!(s.last_committed_height, 10);
+
+        block_begun(now - 1);
+        block_committed(11);
+        let s = HealthStat::evaluate(now, 11, 11 - MAX_INDEX_LAG);
+        assert!(!s.syncing);
+        assert!(s.ready);
+
+        let s = HealthStat::evaluate(now, 11, 10 - MAX_INDEX_LAG);
+        assert!(!s.indexes_caught_up);
+        assert!(!s.ready);
+
+        let s = HealthStat::evaluate(now + 2 * ABCI_TIMEOUT_SECS, 11, 11);
+        assert!(!s.abci_alive);
+        assert!(!s.ready);
+    }
+}
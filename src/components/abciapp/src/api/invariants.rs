@@ -0,0 +1,103 @@
+//!
+//! # Ledger invariants
+//!
+//! Checked periodically in the background, and on demand by `/admin/check_invariants`.
+//!
+//! Both run on the last clone of the query server, never on the ledger
+//! being written, so the consensus path is not blocked by the scan.
+//!
+
+use {
+    crate::api::query_server::query_api::server::QueryServer,
+    chrono::Utc,
+    config::abci::global_cfg::CFG,
+    ledger::store::{verify::Divergence, LedgerState},
+    parking_lot::RwLock,
+    serde::Serialize,
+    std::{
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    },
+    tracing::{error, info, warn},
+};
+
+/// The check stops after this many violations
+pub const MAX_VIOLATIONS: usize = 100;
+
+/// Result of a check
+#[derive(Clone, Debug, Serialize)]
+pub struct InvariantReport {
+    /// the ledger height being checked
+    pub block_commit_count: u64,
+    /// when the check started, in unix seconds
+    pub checked_at: i64,
+    #[allow(missing_docs)]
+    pub elapsed_ms: u64,
+    /// empty if the ledger is consistent
+    pub violations: Vec<Divergence>,
+    /// a block was committed during the check,
+    /// the violations may come from the half-written block then
+    pub stale: bool,
+}
+
+/// Check the invariants of `ledger`
+pub fn check(ledger: &LedgerState) -> InvariantReport {
+    let checked_at = Utc::now().timestamp();
+    let start = Instant::now();
+    let violations = ledger.check_invariants(MAX_VIOLATIONS);
+    InvariantReport {
+        block_commit_count: ledger.get_block_commit_count(),
+        checked_at,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        violations,
+        stale: false,
+    }
+}
+
+/// Check the last clone of the query server.
+///
+/// The clone shares the on-disk collections of the live ledger,
+/// so the report is marked as stale if the live ledger has moved on
+/// since the clone was taken.
+pub fn check_committed(qs: &RwLock<QueryServer>) -> InvariantReport {
+    let (cloned, live) = {
+        let qs = qs.read();
+        (Arc::clone(&qs.ledger_cloned), Arc::clone(&qs.ledger))
+    };
+    let mut report = check(&cloned);
+    report.stale = report.block_commit_count != live.read().get_block_commit_count();
+    report
+}
+
+/// Start the background check, if `invariant_check_itv` is not zero
+pub fn start(qs: Arc<RwLock<QueryServer>>) {
+    let itv = CFG.invariant_check_itv;
+    if 0 == itv {
+        return;
+    }
+
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(itv));
+        let report = check_committed(&qs);
+        if report.violations.is_empty() {
+            info!(
+                "ledger invariants hold at {}, checked in {}ms",
+                report.block_commit_count, report.elapsed_ms
+            );
+        } else if report.stale {
+            warn!(
+                "ledger invariant check at {} overlapped a commit, {} violations ignored",
+                report.block_commit_count,
+                report.violations.len()
+            );
+        } else {
+            for v in report.violations.iter() {
+                error!(
+                    "ledger invariant violated at {}, {}: expected {}, found {}",
+                    v.height, v.what, v.expected, v.found
+                );
+            }
+        }
+    });
+}
@@ -0,0 +1,118 @@
+//!
+//! # Logging of the node
+//!
+//! The logs are written as text or as JSON lines, filtered by per-module
+//! directives such as `info,abciapp=debug,abcitime=warn`. The directives
+//! can be replaced at runtime by `POST /admin/log_level`.
+//!
+//! The per-block timing logs of the ABCI callbacks are sampled, one block
+//! in every `log_sample_every` is logged.
+//!
+
+use {
+    lazy_static::lazy_static,
+    parking_lot::Mutex,
+    ruc::*,
+    std::sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    tracing_subscriber::{fmt, prelude::*, reload, EnvFilter, Registry},
+};
+
+const DEFAULT_DIRECTIVES: &str = "info";
+
+lazy_static! {
+    static ref FILTER: Mutex<Option<(reload::Handle<EnvFilter, Registry>, String)>> =
+        Mutex::new(None);
+}
+
+/// Pick one event out of every `n`
+#[derive(Default)]
+pub struct Sampler(AtomicU64);
+
+impl Sampler {
+    #[allow(missing_docs)]
+    pub const fn new() -> Self {
+        Sampler(AtomicU64::new(0))
+    }
+
+    /// Whether this event is the one picked, `n` of `0` is taken as `1`
+    pub fn hit(&self, n: u64) -> bool {
+        0 == self.0.fetch_add(1, Ordering::Relaxed) % n.max(1)
+    }
+}
+
+static BLOCK_SAMPLER: Sampler = Sampler::new();
+static BLOCK_SAMPLED: AtomicBool = AtomicBool::new(true);
+
+/// Decide whether the timings of the current block are logged,
+/// called once per block in `begin_block`
+pub fn sample_block(every: u64) {
+    BLOCK_SAMPLED.store(BLOCK_SAMPLER.hit(every), Ordering::Relaxed);
+}
+
+/// Whether the timings of the current block are logged
+#[inline(always)]
+pub fn block_sampled() -> bool {
+    BLOCK_SAMPLED.load(Ordering::Relaxed)
+}
+
+/// Install the global subscriber, `directives` falls back to `RUST_LOG`
+pub fn init(json: bool, directives: Option<&str>) -> Result<()> {
+    let directives = directives
+        .map(|d| d.to_owned())
+        .or_else(|| std::env::var(EnvFilter::DEFAULT_ENV).ok())
+        .unwrap_or_else(|| DEFAULT_DIRECTIVES.to_owned());
+    let filter = EnvFilter::try_new(&directives).c(d!())?;
+    let (filter, handle) = reload::Layer::new(filter);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(json.then(|| fmt::layer().json()))
+        .with((!json).then(fmt::layer))
+        .try_init()
+        .c(d!())?;
+
+    *FILTER.lock() = Some((handle, directives));
+    Ok(())
+}
+
+/// The directives in effect, `None` before `init`
+pub fn level() -> Option<String> {
+    FILTER.lock().as_ref().map(|(_, d)| d.clone())
+}
+
+/// Replace the directives in effect
+pub fn set_level(directives: &str) -> Result<()> {
+    let filter = EnvFilter::try_new(directives).c(d!())?;
+    let mut f = FILTER.lock();
+    let (handle, current) = f.as_mut().c(d!("logging not initialized"))?;
+    handle.reload(filter).c(d!())?;
+    *current = directives.to_owned();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sampler() {
+        let s = Sampler::new();
+        let hits = (0..10).filter(|_| s.hit(3)).count();
+        assert_eq!(hits, 4);
+
+        let s = Sampler::new();
+        assert!((0..5).all(|_| s.hit(0)));
+        assert!((0..5).all(|_| s.hit(1)));
+
+        // the first call is always a hit, then one in `n`
+        let s = Sampler::new();
+        assert!(s.hit(u64::MAX));
+        assert!(!(0..100).any(|_| s.hit(u64::MAX)));
+
+        let s = Sampler::new();
+        assert_eq!(
+            vec![true, false, true, false],
+            (0..4).map(|_| s.hit(2)).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//!
+//! # Memory pressure guard
+//!
+//! Samples the resident set size of the process and drops reconstructible caches,
+//! lowest priority first, once it exceeds the configured ceiling.
+//!
+
+use {
+    config::abci::global_cfg::CFG,
+    lazy_static::lazy_static,
+    parking_lot::{Mutex, RwLock},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::HashMap,
+        hash::Hash,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    },
+    tracing::{info, warn},
+};
+
+/// Interval between two RSS samples
+const CHECK_ITV_SECS: u64 = 5;
+
+/// Rendered responses, the cheapest to rebuild
+pub const PRIORITY_RENDERED: u8 = 0;
+/// Transactions verified by `check_tx`, verified again by `deliver_tx` on a miss
+pub const PRIORITY_VERIFIED: u8 = 1;
+/// In-memory views built from the indexes, the costliest to rebuild
+pub const PRIORITY_INDEX: u8 = 2;
+
+lazy_static! {
+    /// Process-wide memory guard
+    pub static ref MEM_GUARD: MemGuard =
+        MemGuard::new(CFG.mem_ceiling_mb.map(|mb| mb.saturating_mul(1024 * 1024)));
+}
+
+/// A cache whose content can be rebuilt from the ledger at any time
+pub trait Evictable: Send + Sync {
+    /// Name used in the statistics
+    fn name(&self) -> &'static str;
+    /// Caches with a lower priority are evicted first
+    fn priority(&self) -> u8;
+    /// Number of cached entries
+    fn entries(&self) -> usize;
+    /// Drop all entries, returns how many were dropped
+    fn evict(&self) -> usize;
+}
+
+/// A bounded in-memory map for immutable query results
+pub struct ResponseCache<K, V> {
+    name: &'static str,
+    priority: u8,
+    cap: usize,
+    inner: Mutex<HashMap<K, V>>,
+}
+
+impl<K, V> ResponseCache<K, V>
+where
+    K: Eq + Hash + Send,
+    V: Clone + Send,
+{
+    /// Create a cache holding at most `cap` entries
+    pub fn new(name: &'static str, priority: u8, cap: usize) -> Self {
+        ResponseCache {
+            name,
+            priority,
+            cap,
+            inner: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn get(&self, k: &K) -> Option<V> {
+        self.inner.lock().get(k).cloned()
+    }
+
+    /// The whole cache is reset when it is full,
+    /// entries are cheap to rebuild and there is no need to track recency.
+    pub fn insert(&self, k: K, v: V) {
+        let mut inner = self.inner.lock();
+        if inner.len() >= self.cap {
+            inner.clear();
+        }
+        inner.insert(k, v);
+    }
+
+    /// Return the cached value, or build and cache it
+    pub fn get_or_insert_with<E>(
+        &self,
+        k: K,
+        f: impl FnOnce() -> Result<V, E>,
+    ) -> Result<V, E> {
+        if let Some(v) = self.get(&k) {
+            return Ok(v);
+        }
+        let v = f()?;
+        self.insert(k, v.clone());
+        Ok(v)
+    }
+}
+
+impl<K, V> Evictable for ResponseCache<K, V>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    fn entries(&self) -> usize {
+        self.inner.lock().len()
+    }
+
+    fn evict(&self) -> usize {
+        let mut inner = self.inner.lock();
+        let n = inner.len();
+        *inner = HashMap::new();
+        n
+    }
+}
+
+/// Statistics of a registered cache
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CacheStat {
+    pub name: String,
+    pub priority: u8,
+    pub entries: usize,
+    pub eviction_cnt: u64,
+    pub evicted_entries: u64,
+}
+
+/// Statistics exposed through the query server
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MemGuardStat {
+    pub ceiling: Option<u64>,
+    pub rss: u64,
+    pub caches: Vec<CacheStat>,
+}
+
+/// Keep the process below a memory ceiling by evicting caches in priority order
+pub struct MemGuard {
+    ceiling: Option<u64>,
+    caches: RwLock<Vec<Arc<dyn Evictable>>>,
+    // name => (eviction count, evicted entries)
+    evictions: Mutex<HashMap<&'static str, (u64, u64)>>,
+    last_rss: AtomicU64,
+    started: AtomicBool,
+}
+
+impl MemGuard {
+    #[allow(missing_docs)]
+    pub fn new(ceiling: Option<u64>) -> Self {
+        MemGuard {
+            ceiling,
+            caches: RwLock::new(vec![]),
+            evictions: Mutex::new(HashMap::new()),
+            last_rss: AtomicU64::new(0),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// Put a cache under the control of the guard
+    pub fn register(&self, cache: Arc<dyn Evictable>) {
+        let mut caches = self.caches.write();
+        caches.push(cache);
+        caches.sort_by_key(|c| c.priority());
+    }
+
+    /// Sample the RSS and evict caches until it goes below the ceiling
+    #[inline(always)]
+    pub fn check(&self) {
+        self.check_with(current_rss)
+    }
+
+    // `rss` samples the resident set size, evictions are sampled again
+    fn check_with(&self, rss_of: impl Fn() -> Option<u64>) {
+        let mut rss = if let Some(rss) = rss_of() {
+            rss
+        } else {
+            return;
+        };
+        self.last_rss.store(rss, Ordering::Relaxed);
+
+        let ceiling = if let Some(c) = self.ceiling {
+            c
+        } else {
+            return;
+        };
+
+        for cache in self.caches.read().iter() {
+            if rss <= ceiling {
+                break;
+            }
+            let n = cache.evict();
+            {
+                let mut evictions = self.evictions.lock();
+                let e = evictions.entry(cache.name()).or_insert((0, 0));
+                e.0 += 1;
+                e.1 += n as u64;
+            }
+            warn!(target: "abciapp",
+                "memory pressure: rss {} > ceiling {}, {} entries evicted from `{}`",
+                rss, ceiling, n, cache.name()
+            );
+            rss = rss_of().unwrap_or(rss);
+            self.last_rss.store(rss, Ordering::Relaxed);
+        }
+    }
+
+    /// Spawn the background sampler, does nothing if no ceiling is configured
+    pub fn start(&'static self) {
+        if self.ceiling.is_none() || self.started.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        info!(target: "abciapp", "memory guard started, ceiling: {:?}", self.ceiling);
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(CHECK_ITV_SECS));
+            self.check();
+        });
+    }
+
+    #[allow(missing_docs)]
+    pub fn stat(&self) -> MemGuardStat {
+        let evictions = self.evictions.lock();
+        let caches = self
+            .caches
+            .read()
+            .iter()
+            .map(|c| {
+                let (eviction_cnt, evicted_entries) =
+                    evictions.get(c.name()).copied().unwrap_or_default();
+                CacheStat {
+                    name: c.name().to_owned(),
+                    priority: c.priority(),
+                    entries: c.entries(),
+                    eviction_cnt,
+                    evicted_entries,
+                }
+            })
+            .collect();
+        MemGuardStat {
+            ceiling: self.ceiling,
+            rss: self.last_rss.load(Ordering::Relaxed),
+            caches,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn current_rss() -> Option<u64> {
+    use nix::unistd::{sysconf, SysconfVar};
+
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages = statm.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    let page_size = sysconf(SysconfVar::PAGE_SIZE).ok().flatten()? as u64;
+    Some(pages * page_size)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current_rss() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn eviction_follows_priority() {
+        let guard = MemGuard::new(Some(250));
+        let index = Arc::new(ResponseCache::new("index", PRIORITY_INDEX, 8));
+        let verified = Arc::new(ResponseCache::new("verified", PRIORITY_VERIFIED, 8));
+        let rendered = Arc::new(ResponseCache::new("rendered", PRIORITY_RENDERED, 8));
+        index.insert(1u64, 1u64);
+        verified.insert(1u64, ());
+        verified.insert(2u64, ());
+        rendered.insert(1u64, "1".to_owned());
+        rendered.insert(2u64, "2".to_owned());
+        guard.register(index.clone());
+        guard.register(rendered.clone());
+        guard.register(verified.clone());
+
+        let names = guard
+            .stat()
+            .caches
+            .into_iter()
+            .map(|c| c.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["rendered", "verified", "index"]);
+
+        // 100 bytes per cached entry
+        let rss = || {
+            Some(
+                100 * (index.entries() + verified.entries() + rendered.entries()) as u64,
+            )
+        };
+
+        // below the ceiling, nothing is evicted
+        let idle = MemGuard::new(Some(1000));
+        idle.register(rendered.clone());
+        idle.check_with(rss);
+        assert_eq!(rendered.entries(), 2);
+
+        // 500 > 250, then 300 > 250, then 100 <= 250
+        guard.check_with(rss);
+        assert_eq!(rendered.entries(), 0);
+        assert_eq!(verified.entries(), 0);
+        assert_eq!(index.entries(), 1);
+
+        let stat = guard.stat();
+        assert_eq!(stat.rss, 100);
+        let evicted = stat
+            .caches
+            .iter()
+            .map(|c| (c.name.as_str(), c.eviction_cnt, c.evicted_entries))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            evicted,
+            vec![("rendered", 1, 2), ("verified", 1, 2), ("index", 0, 0)]
+        );
+    }
+
+    #[test]
+    fn response_cache_is_bounded() {
+        let c = ResponseCache::new("t", PRIORITY_RENDERED, 2);
+        c.insert(1u8, 1u8);
+        c.insert(2u8, 2u8);
+        c.insert(3u8, 3u8);
+        assert!(c.entries() <= 2);
+        assert_eq!(c.get(&3), Some(3));
+        assert_eq!(
+            c.get_or_insert_with::<()>(4, || Ok(4)).unwrap(),
+            c.get(&4).unwrap()
+        );
+    }
+}
@@ -2,8 +2,66 @@
 //! # Services provided by api
 //!
 
+/// Addresses in bech32 or base64 in the requests
+pub mod address;
+
+/// Roles of the clients of the REST services
+pub mod auth;
+
+/// Rotated snapshot backups of the ledger
+pub mod backup;
+
+/// Error responses of the REST services
+pub mod error;
+
+/// Health and sync status of the node
+pub mod health;
+
+/// Background and on-demand checks of the ledger invariants
+pub mod invariants;
+
+/// Log format, runtime log levels and sampling
+pub mod logging;
+
+/// Evict reconstructible caches under memory pressure
+pub mod mem_guard;
+
 /// Provide query service for ledgerState
 pub mod query_server;
 
+/// Correlation IDs of the requests and the transactions they submit
+pub mod request_id;
+
 /// Provide services for operating transactions
 pub mod submission_server;
+
+/// Sanity limits of the lists and the bodies of the requests
+pub mod validation;
+
+use {
+    actix_cors::Cors, actix_web::middleware::Condition, config::abci::global_cfg::CFG,
+};
+
+/// Build the CORS middleware of the REST services from the global config
+pub fn cors() -> Condition<Cors> {
+    let cfg = &CFG.cors;
+    if cfg.is_permissive() {
+        return Condition::new(true, Cors::permissive().supports_credentials());
+    }
+
+    let cors = Cors::default().supports_credentials();
+    let cors = match cfg.allowed_origins.as_ref() {
+        Some(origins) => origins.iter().fold(cors, |c, o| c.allowed_origin(o)),
+        None => cors.allow_any_origin(),
+    };
+    let cors = match cfg.allowed_methods.as_ref() {
+        Some(methods) => cors.allowed_methods(methods.iter().map(|m| m.as_str())),
+        None => cors.allow_any_method(),
+    };
+    let cors = match cfg.allowed_headers.as_ref() {
+        Some(headers) => cors.allowed_headers(headers.iter().map(|h| h.as_str())),
+        None => cors.allow_any_header(),
+    };
+
+    Condition::new(!cfg.disabled, cors)
+}
@@ -0,0 +1,314 @@
+//!
+//! # Indexes of the assets
+//!
+//! The assets in the order of definition, and the assets with metadata
+//! by symbol and name. Kept in memory, built from the api cache at startup,
+//! then extended with the definitions of each new block.
+//!
+
+use {
+    config::abci::global_cfg::CFG,
+    ledger::{
+        data_model::{
+            format_amount, AssetMetadata, AssetType, AssetTypeCode, AssetTypePrefix,
+            Operation, TxnSID,
+        },
+        store::LedgerState,
+    },
+    serde::Serialize,
+    std::collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    zei::XfrPublicKey,
+};
+
+/// The maximum number of assets in one page of `/assets/search` and `/assets`
+pub const MAX_SEARCH_PER_PAGE: usize = 100;
+
+/// A defined asset, as listed by `/assets`
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct AssetSummary {
+    pub code: AssetTypeCode,
+    pub issuer: XfrPublicKey,
+    pub memo: String,
+    pub decimals: u8,
+    pub max_units: Option<u64>,
+    pub transferable: bool,
+    pub metadata: Option<AssetMetadata>,
+}
+
+impl From<AssetType> for AssetSummary {
+    fn from(t: AssetType) -> Self {
+        let p = t.properties;
+        AssetSummary {
+            code: p.code,
+            issuer: p.issuer.key,
+            memo: p.memo.0,
+            decimals: p.asset_rules.decimals,
+            max_units: p.asset_rules.max_units,
+            transferable: p.asset_rules.transferable,
+            metadata: p.metadata,
+        }
+    }
+}
+
+impl AssetSummary {
+    /// `am` smallest units of the asset in its units, e.g. `12.345`
+    pub fn format_amount(&self, am: u64) -> String {
+        format_amount(am, self.decimals)
+    }
+}
+
+/// An asset found by `AssetIndex::search`
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct AssetHit {
+    pub code: AssetTypeCode,
+    pub metadata: AssetMetadata,
+}
+
+/// All the assets in the order of definition, and the assets with metadata
+/// searchable by a prefix of their symbols or names
+#[derive(Debug, Default)]
+pub struct AssetIndex {
+    defined: Vec<(AssetTypeCode, XfrPublicKey)>,
+    // the entries of `ApiCache::defined_assets` before it have been indexed
+    next_defined: u64,
+    // lowercased symbols and names
+    terms: BTreeMap<String, BTreeSet<AssetTypeCode>>,
+    metadata: HashMap<AssetTypeCode, AssetMetadata>,
+    // the transactions before it have been indexed
+    next_txn: usize,
+}
+
+impl AssetIndex {
+    /// Index all the assets created so far
+    pub fn build(ledger: &LedgerState) -> Self {
+        let mut index = AssetIndex {
+            next_txn: ledger.get_next_txn().0,
+            ..Default::default()
+        };
+        if let Some(api_cache) = ledger.api_cache.as_ref() {
+            let recorded = api_cache
+                .defined_assets
+                .iter()
+                .map(|(_, code)| code)
+                .collect::<HashSet<_>>();
+            for (issuer, assets) in api_cache.created_assets.iter() {
+                for (code, def) in assets.iter() {
+                    if let Some(metadata) = def.body.asset.metadata {
+                        index.insert(code, metadata);
+                    }
+                    // defined before the order was recorded,
+                    // listed first, in the order of the issuers
+                    if !recorded.contains(&code) {
+                        index.defined.push((code, issuer.key));
+                    }
+                }
+            }
+            index.update_defined(ledger);
+        }
+        index
+    }
+
+    // append the codes of `ApiCache::defined_assets` not indexed yet
+    fn update_defined(&mut self, ledger: &LedgerState) {
+        let api_cache = match ledger.api_cache.as_ref() {
+            Some(c) => c,
+            None => return,
+        };
+        while let Some(code) = api_cache.defined_assets.get(&self.next_defined) {
+            let issuer = if let Some(t) = ledger.get_asset_type(&code) {
+                t.properties.issuer.key
+            } else {
+                // retried on the next block
+                break;
+            };
+            self.defined.push((code, issuer));
+            self.next_defined += 1;
+        }
+    }
+
+    /// Returns the total count and a page(`[start, end)`) of the assets
+    /// in the order of definition, only those of `issuer` if it is not none
+    pub fn list(
+        &self,
+        issuer: Option<&XfrPublicKey>,
+        start: usize,
+        end: usize,
+    ) -> (u64, Vec<AssetTypeCode>) {
+        let codes = self
+            .defined
+            .iter()
+            .filter(|(_, pk)| issuer.map_or(true, |i| i == pk))
+            .map(|(code, _)| *code)
+            .collect::<Vec<_>>();
+        let page = codes
+            .iter()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .copied()
+            .collect();
+        (codes.len() as u64, page)
+    }
+
+    /// Index the assets defined since the last call
+    pub fn update(&mut self, ledger: &LedgerState) {
+        let api_cache = if let Some(c) = ledger.api_cache.as_ref() {
+            c
+        } else {
+            return;
+        };
+        self.update_defined(ledger);
+
+        let next_txn = ledger.get_next_txn().0;
+        for sid in self.next_txn..next_txn {
+            let txn = if let Ok(tx) = ledger.get_transaction_light(TxnSID(sid)) {
+                tx.txn
+            } else {
+                continue;
+            };
+            // the codes are derived at the height of the definition,
+            // the same as `ApiCache::add_created_asset`
+            let height = api_cache
+                .txn_meta
+                .get(&TxnSID(sid))
+                .map(|m| m.height)
+                .unwrap_or_else(|| ledger.get_tendermint_height());
+            for op in txn.body.operations {
+                if let Operation::DefineAsset(def) = op {
+                    if let Some(metadata) = def.body.asset.metadata {
+                        let code = AssetTypeCode::from_prefix_and_raw_asset_type_code(
+                            AssetTypePrefix::UserDefined,
+                            &def.body.asset.code,
+                            &CFG.checkpoint,
+                            height,
+                        );
+                        self.insert(code, metadata);
+                    }
+                }
+            }
+        }
+        self.next_txn = next_txn;
+    }
+
+    /// Returns the total count and a page(`[start, end)`) of the assets
+    /// whose symbol or name starts with `q`, case insensitive,
+    /// in the order of the matched terms
+    pub fn search(&self, q: &str, start: usize, end: usize) -> (u64, Vec<AssetHit>) {
+        let q = q.to_lowercase();
+        let mut seen = HashSet::new();
+        let codes = self
+            .terms
+            .range(q.clone()..)
+            .take_while(|(term, _)| term.starts_with(&q))
+            .flat_map(|(_, codes)| codes.iter().copied())
+            .filter(|code| seen.insert(*code))
+            .collect::<Vec<_>>();
+
+        let page = codes
+            .iter()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .filter_map(|code| {
+                self.metadata.get(code).map(|m| AssetHit {
+                    code: *code,
+                    metadata: m.clone(),
+                })
+            })
+            .collect();
+
+        (codes.len() as u64, page)
+    }
+
+    fn insert(&mut self, code: AssetTypeCode, metadata: AssetMetadata) {
+        for term in [&metadata.symbol, &metadata.name] {
+            if !term.is_empty() {
+                self.terms
+                    .entry(term.to_lowercase())
+                    .or_default()
+                    .insert(code);
+            }
+        }
+        self.metadata.insert(code, metadata);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, rand_chacha::ChaChaRng, rand_core::SeedableRng, zei::XfrKeyPair};
+
+    #[test]
+    fn asset_search() {
+        let meta = |symbol: &str, name: &str| AssetMetadata {
+            decimals: 6,
+            symbol: symbol.to_owned(),
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let usdc = AssetTypeCode::gen_random();
+        let usdt = AssetTypeCode::gen_random();
+        let fra = AssetTypeCode::gen_random();
+
+        let mut index = AssetIndex::default();
+        index.insert(usdc, meta("USDC", "USD Coin"));
+        index.insert(usdt, meta("USDT", "Tether USD"));
+        index.insert(fra, meta("FRA", "Findora"));
+
+        let (total, hits) = index.search("usd", 0, 10);
+        assert_eq!(total, 2);
+        // `USD Coin` and `USDC` match the same asset
+        assert_eq!(
+            hits.iter().map(|h| h.code).collect::<Vec<_>>(),
+            vec![usdc, usdt]
+        );
+
+        let (total, hits) = index.search("Tether", 0, 10);
+        assert_eq!(total, 1);
+        assert_eq!(hits[0].metadata.symbol, "USDT");
+
+        let (total, hits) = index.search("usd", 1, 2);
+        assert_eq!(total, 2);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].code, usdt);
+
+        assert_eq!(index.search("btc", 0, 10).0, 0);
+
+        // a page past the end, or an empty range, still counts the matches
+        for (start, end) in [(2, 10), (1, 1), (2, 1)] {
+            let (total, hits) = index.search("usd", start, end);
+            assert_eq!(total, 2);
+            assert!(hits.is_empty());
+        }
+    }
+
+    #[test]
+    fn asset_list() {
+        let mut prng = ChaChaRng::from_entropy();
+        let alice = XfrKeyPair::generate(&mut prng).get_pk();
+        let bob = XfrKeyPair::generate(&mut prng).get_pk();
+        let codes = (0..5)
+            .map(|_| AssetTypeCode::gen_random())
+            .collect::<Vec<_>>();
+
+        let mut index = AssetIndex::default();
+        for (i, code) in codes.iter().enumerate() {
+            index
+                .defined
+                .push((*code, if i % 2 == 0 { alice } else { bob }));
+        }
+
+        assert_eq!(index.list(None, 0, 10), (5, codes.clone()));
+        assert_eq!(index.list(None, 3, 6), (5, codes[3..].to_vec()));
+        assert_eq!(
+            index.list(Some(&alice), 1, 3),
+            (3, vec![codes[2], codes[4]])
+        );
+        assert_eq!(index.list(Some(&bob), 0, 1), (2, vec![codes[1]]));
+
+        // past the end, an empty range and an issuer without assets
+        assert_eq!(index.list(None, 5, 10), (5, vec![]));
+        assert_eq!(index.list(None, 3, 2), (5, vec![]));
+        let carol = XfrKeyPair::generate(&mut prng).get_pk();
+        assert_eq!(index.list(Some(&carol), 0, 10), (0, vec![]));
+    }
+}
@@ -0,0 +1,157 @@
+//!
+//! # Audit of traced records
+//!
+//! An auditor submits the decryption key of an asset tracer, which can read
+//! the tracing memos but can not spend anything, and gets the amounts and
+//! asset types of the records in a range of TXOs that the key can open.
+//!
+//! Only the records of the assets with a tracing policy of the auditor carry
+//! tracing memos, the others are skipped. Disabled unless `enable_audit_api`
+//! is configured, the key is sent to the node, so serve it behind TLS.
+//!
+
+use {
+    config::abci::global_cfg::CFG,
+    ledger::{
+        data_model::{AssetTypeCode, Operation, TxnSID, TxoSID, UnAuthenticatedUtxo},
+        store::LedgerState,
+    },
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    zei::{
+        noah_api::xfr::structs::{AssetTracerDecKeys, XfrAmount, XfrAssetType},
+        XfrPublicKey,
+    },
+};
+
+/// The most TXOs audited by a request
+pub const MAX_AUDIT_RANGE: u64 = 1000;
+
+/// Body of `/audit/trace`
+#[allow(missing_docs)]
+#[derive(Deserialize)]
+pub struct AuditRequest {
+    /// `dec_key` of the tracer key pair, never the spend key
+    pub dec_key: AssetTracerDecKeys,
+    pub from: TxoSID,
+    /// inclusive
+    pub to: TxoSID,
+}
+
+/// A record opened by the tracer key
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditedTxo {
+    pub txo_sid: TxoSID,
+    pub txn_sid: TxnSID,
+    pub owner: XfrPublicKey,
+    pub amount: u64,
+    /// base64 encoded asset type code
+    pub asset_type: String,
+    pub spent: bool,
+}
+
+/// Whether the audit endpoint is enabled by the config
+#[inline(always)]
+pub fn enabled() -> bool {
+    CFG.enable_audit_api
+}
+
+/// The records within `[from, to]` which `dec_key` can open
+pub fn trace(
+    ledger: &LedgerState,
+    dec_key: &AssetTracerDecKeys,
+    from: TxoSID,
+    to: TxoSID,
+) -> Result<Vec<AuditedTxo>> {
+    if from.0 > to.0 {
+        return Err(eg!("`from` is greater than `to`"));
+    }
+    if MAX_AUDIT_RANGE <= to.0 - from.0 {
+        return Err(eg!(format!(
+            "audit at most {MAX_AUDIT_RANGE} TXOs at a time"
+        )));
+    }
+
+    let to = to.0.min(ledger.get_next_txo().0.saturating_sub(1));
+    Ok((from.0..=to)
+        .map(TxoSID)
+        .filter_map(|sid| {
+            ledger
+                .get_utxo_light(sid)
+                .map(|au| (au, false))
+                .or_else(|| ledger.get_spent_utxo_light(sid).map(|au| (au, true)))
+                .and_then(|(au, spent)| open(sid, &au, spent, dec_key))
+        })
+        .collect())
+}
+
+// Decrypt the tracing memos of the output holding the record of `au`
+fn open(
+    txo_sid: TxoSID,
+    au: &UnAuthenticatedUtxo,
+    spent: bool,
+    dec_key: &AssetTracerDecKeys,
+) -> Option<AuditedTxo> {
+    let record = &au.utxo.0.record;
+    au.txn.txn.body.operations.iter().find_map(|op| {
+        let x = match op {
+            Operation::TransferAsset(x) => x,
+            _ => return None,
+        };
+        let xfr = &x.body.transfer;
+        let idx = xfr.outputs.iter().position(|o| o == record)?;
+        xfr.asset_tracing_memos
+            .get(xfr.inputs.len() + idx)?
+            .iter()
+            .find_map(|memo| memo.decrypt(dec_key).ok())
+            .map(|(amount, asset_type, _)| {
+                // the non-confidential fields are not locked in the memo
+                let amount = amount.or(match record.amount {
+                    XfrAmount::NonConfidential(n) => Some(n),
+                    _ => None,
+                });
+                let asset_type = asset_type.or(match record.asset_type {
+                    XfrAssetType::NonConfidential(ty) => Some(ty),
+                    _ => None,
+                });
+                (amount, asset_type)
+            })
+            .and_then(|(amount, asset_type)| {
+                Some(AuditedTxo {
+                    txo_sid,
+                    txn_sid: au.txn.tx_id,
+                    owner: record.public_key,
+                    amount: amount?,
+                    asset_type: AssetTypeCode { val: asset_type? }.to_base64(),
+                    spent,
+                })
+            })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*, rand_chacha::ChaChaRng, rand_core::SeedableRng,
+        zei::noah_api::xfr::structs::AssetTracerKeyPair,
+    };
+
+    #[test]
+    fn audit_range() {
+        let ledger = LedgerState::tmp_ledger();
+        let tracer = AssetTracerKeyPair::generate(&mut ChaChaRng::from_seed([0; 32]));
+
+        assert!(trace(&ledger, &tracer.dec_key, TxoSID(2), TxoSID(1)).is_err());
+        assert!(
+            trace(&ledger, &tracer.dec_key, TxoSID(0), TxoSID(MAX_AUDIT_RANGE)).is_err()
+        );
+        assert!(pnk!(trace(
+            &ledger,
+            &tracer.dec_key,
+            TxoSID(0),
+            TxoSID(MAX_AUDIT_RANGE - 1)
+        ))
+        .is_empty());
+    }
+}
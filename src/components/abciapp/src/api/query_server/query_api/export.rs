@@ -0,0 +1,240 @@
+//!
+//! # CSV export of the history endpoints
+//!
+//! `?format=csv` turns the page of a history endpoint into a CSV file,
+//! the rows are rendered while the chunked response is being sent.
+//!
+
+use {
+    super::{server::TimelineEntry, CoinbaseTxnBody},
+    actix_web::{error::Error as ActixError, http::header, web::Bytes, HttpResponse},
+    futures::stream,
+    globutils::wallet,
+    ledger::{
+        data_model::{AssetTypeCode, Operation, Transaction},
+        staking::ops::mint_fra::MintKind,
+    },
+    serde::{Deserialize, Serialize},
+    std::{borrow::Cow, iter},
+    zei::XfrPublicKey,
+};
+
+// rows rendered into one chunk of the response
+const ROWS_PER_CHUNK: usize = 500;
+
+/// Format of the response of a history endpoint
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// `?format=`, JSON if absent
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct FormatParam {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// A record which can be exported as a line of CSV
+pub trait CsvRow {
+    /// names of the columns
+    const HEADER: &'static [&'static str];
+
+    /// the fields, in the order of `HEADER`
+    fn fields(&self) -> Vec<String>;
+}
+
+/// Respond with `body` as JSON, or with `rows` as a CSV attachment named `name`
+pub fn respond<B, R, F>(
+    format: ExportFormat,
+    name: &str,
+    body: B,
+    rows: F,
+) -> HttpResponse
+where
+    B: Serialize,
+    R: CsvRow + 'static,
+    F: FnOnce(B) -> Vec<R>,
+{
+    match format {
+        ExportFormat::Json => HttpResponse::Ok().json(body),
+        ExportFormat::Csv => csv_response(name, rows(body)),
+    }
+}
+
+/// Stream `rows` as a CSV attachment named `name`
+pub fn csv_response<R: CsvRow + 'static>(name: &str, rows: Vec<R>) -> HttpResponse {
+    let header = line(R::HEADER.iter().map(|h| h.to_string()).collect());
+    let mut rows = rows.into_iter();
+    let chunks = iter::once(header).chain(iter::from_fn(move || {
+        let chunk = rows
+            .by_ref()
+            .take(ROWS_PER_CHUNK)
+            .map(|r| line(r.fields()))
+            .collect::<String>();
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(chunk)
+        }
+    }));
+
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{name}.csv\""),
+        )
+        .streaming(stream::iter(
+            chunks.map(|c| Ok::<_, ActixError>(Bytes::from(c))),
+        ))
+}
+
+/// A claim operation in the export of `claim_history`
+pub struct ClaimRow {
+    txn_hash: String,
+    claimer: XfrPublicKey,
+    amount: Option<u64>,
+}
+
+impl ClaimRow {
+    /// The claims of a page of `claim_history`
+    pub fn of_txns(txns: Vec<Option<Transaction>>) -> Vec<Self> {
+        txns.into_iter()
+            .flatten()
+            .flat_map(|tx| {
+                let txn_hash = tx.hash_tm().hex().to_uppercase();
+                tx.body
+                    .operations
+                    .iter()
+                    .filter_map(|op| match op {
+                        Operation::Claim(c) => Some(ClaimRow {
+                            txn_hash: txn_hash.clone(),
+                            claimer: c.get_claim_publickey(),
+                            amount: c.get_claim_amount(),
+                        }),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+impl CsvRow for ClaimRow {
+    const HEADER: &'static [&'static str] = &["txn_hash", "claimer", "amount"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.txn_hash.clone(),
+            wallet::public_key_to_base64(&self.claimer),
+            // empty for claiming all the rewards
+            opt(self.amount),
+        ]
+    }
+}
+
+impl CsvRow for CoinbaseTxnBody {
+    const HEADER: &'static [&'static str] = &[
+        "height",
+        "kind",
+        "target",
+        "receiver",
+        "amount",
+        "asset_type",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        let e = &self.data;
+        let kind = match e.kind {
+            MintKind::Claim => "claim".to_owned(),
+            MintKind::UnStake => "unstake".to_owned(),
+            MintKind::Other => "other".to_owned(),
+            MintKind::Scheduled(id) => format!("scheduled:{id}"),
+        };
+        vec![
+            self.height.to_string(),
+            kind,
+            wallet::public_key_to_base64(&e.target_pk),
+            wallet::public_key_to_base64(&e.utxo.record.public_key),
+            e.amount.to_string(),
+            AssetTypeCode { val: e.asset_type }.to_base64(),
+        ]
+    }
+}
+
+impl CsvRow for TimelineEntry {
+    const HEADER: &'static [&'static str] = &["txn_sid", "height", "timestamp", "ops"];
+
+    fn fields(&self) -> Vec<String> {
+        let ops = self
+            .ops
+            .iter()
+            .filter_map(|k| serde_json::to_value(k).ok())
+            .filter_map(|v| v.as_str().map(|s| s.to_owned()))
+            .collect::<Vec<_>>();
+        vec![
+            self.txn_sid.0.to_string(),
+            opt(self.height),
+            opt(self.timestamp),
+            ops.join(";"),
+        ]
+    }
+}
+
+fn opt<T: ToString>(v: Option<T>) -> String {
+    v.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn line(fields: Vec<String>) -> String {
+    let mut l = fields
+        .iter()
+        .map(|f| escape(f))
+        .collect::<Vec<_>>()
+        .join(",");
+    l.push_str("\r\n");
+    l
+}
+
+// RFC 4180, quote the fields containing a separator, a quote or a line break
+fn escape(field: &str) -> Cow<str> {
+    if field.contains(|c| matches!(c, ',' | '"' | '\r' | '\n')) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_escape() {
+        assert_eq!(
+            line(vec![
+                "plain".to_owned(),
+                "a,b".to_owned(),
+                "say \"hi\"".to_owned(),
+                String::new(),
+            ]),
+            "plain,\"a,b\",\"say \"\"hi\"\"\",\r\n"
+        );
+
+        // the line breaks are quoted too, the spaces are kept as they are
+        assert_eq!(
+            line(vec![
+                "a\nb".to_owned(),
+                "a\r\nb".to_owned(),
+                " x ".to_owned()
+            ]),
+            "\"a\nb\",\"a\r\nb\", x \r\n"
+        );
+        assert_eq!(line(vec!["\"".to_owned()]), "\"\"\"\"\r\n");
+        assert_eq!(line(vec![]), "\r\n");
+    }
+}
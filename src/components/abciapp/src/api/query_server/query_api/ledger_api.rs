@@ -3,29 +3,45 @@
 //!
 
 use {
-    super::server::QueryServer,
-    actix_web::{error, web},
+    super::{
+        server::{AbarTreeInfo, QueryServer, ReadView},
+        state::{QueryState, API_CACHE_UNAVAILABLE},
+    },
+    crate::api::{
+        address::parse_address,
+        error::{
+            internal, invalid_param, not_found, stale_read, unauthorized, unavailable,
+        },
+        validation::parse_list,
+    },
+    actix_web::web,
     config::abci::global_cfg::CFG,
+    cryptohash::{sha256::Digest as BitDigest, HashValue},
     finutils::api::{
-        DelegationInfo, DelegatorInfo, DelegatorList, NetworkRoute, Validator,
-        ValidatorDetail, ValidatorList,
+        DelegationInfo, DelegatorInfo, DelegatorList, NetworkRoute, ValidatorDetail,
+        ValidatorList,
     },
-    globutils::{wallet, HashOf},
+    globutils::{wallet, HashOf, ProofOf, SignatureOf},
     ledger::{
         data_model::{
             ABARData, ATxoSID, AssetType, AssetTypeCode, AssetTypePrefix,
-            AuthenticatedUtxo, StateCommitmentData, TxnSID, TxoSID, UnAuthenticatedUtxo,
-            Utxo,
+            AuthenticatedUtxo, CustomAssetPolicy, StateCommitmentData,
+            StateDomainHashes, Transaction, TxOutput, TxnSID, TxoSID,
+            UnAuthenticatedUtxo, Utxo,
         },
         staking::{
-            DelegationRwdDetail, DelegationState, Staking, TendermintAddr,
-            TendermintAddrRef,
+            BlockHeight, DelegationRwdDetail, ScheduledTransfer, Staking,
+            TendermintAddr, TendermintAddrRef,
+        },
+        store::{
+            api_cache::{AbarRoot, BlockMeta, OpKind, VALIDATOR_HIST_EPOCH},
+            utxo_tree::SparseMerkleProof,
+            LedgerState,
         },
     },
-    parking_lot::RwLock,
     ruc::*,
     serde::{Deserialize, Serialize},
-    std::{collections::BTreeMap, mem, sync::Arc},
+    std::collections::BTreeMap,
     zei::{OwnerMemo, XfrPublicKey},
 };
 
@@ -35,53 +51,114 @@ pub async fn ping() -> actix_web::Result<String> {
     Ok("success".into())
 }
 
+/// `?height=` of the queries, see `QueryServer::read_view`
+#[derive(Debug, Default, Deserialize)]
+pub struct HeightParam {
+    height: Option<u64>,
+}
+
+impl HeightParam {
+    /// The view pinned to the requested height
+    pub fn view(&self, qs: &QueryServer) -> actix_web::Result<ReadView> {
+        qs.read_view(self.height).map_err(unavailable)
+    }
+
+    /// Queries on the mutable state can only be served at the latest height
+    pub fn require_latest(&self, qs: &QueryServer) -> actix_web::Result<ReadView> {
+        let view = self.view(qs)?;
+        if view.latest {
+            Ok(view)
+        } else {
+            Err(stale_read(format!(
+                "this query can not be served at a previous height: {}",
+                view.height
+            )))
+        }
+    }
+}
+
+/// `?op=` of the queries on related transactions, see `OpKind::parse_list`
+#[derive(Debug, Default, Deserialize)]
+pub struct OpFilter {
+    op: Option<String>,
+}
+
+impl OpFilter {
+    /// `None` if no filter is given
+    pub fn kinds(&self) -> actix_web::Result<Option<Vec<OpKind>>> {
+        self.op
+            .as_deref()
+            .map(OpKind::parse_list)
+            .transpose()
+            .map_err(invalid_param)
+    }
+}
+
+/// `?locked=` of the queries on owned utxos,
+/// `true` for the time-locked ones only, `false` for the spendable ones only
+#[derive(Debug, Default, Deserialize)]
+pub struct LockFilter {
+    locked: Option<bool>,
+}
+
+impl LockFilter {
+    /// Whether `utxo` passes the filter at the block count `height`
+    pub fn matches(&self, utxo: &Utxo, height: u64) -> bool {
+        self.locked.map_or(true, |l| l == utxo.0.is_locked(height))
+    }
+}
+
+/// Pin a view to the latest block, pass its `height` to
+/// the following queries to read the state of the same block
+pub async fn begin_read_session(
+    data: QueryState,
+) -> actix_web::Result<web::Json<ReadView>> {
+    HeightParam::default().view(&data.read()).map(web::Json)
+}
+
 /// query utxo according to `TxoSID` return Authenticated Utxo
 pub async fn query_utxo(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
+    web::Query(h): web::Query<HeightParam>,
 ) -> actix_web::Result<web::Json<AuthenticatedUtxo>> {
     let qs = data.read();
+    h.require_latest(&qs)?;
     let ledger = &qs.ledger_cloned;
     if let Ok(txo_sid) = info.parse::<u64>() {
         if let Some(txo) = ledger.get_utxo(TxoSID(txo_sid)) {
             Ok(web::Json(txo))
         } else {
-            Err(actix_web::error::ErrorNotFound(
-                "Specified txo does not currently exist.",
-            ))
+            Err(not_found("Specified txo does not currently exist."))
         }
     } else {
-        Err(actix_web::error::ErrorBadRequest(
-            "Invalid txo sid encoding",
-        ))
+        Err(invalid_param("Invalid txo sid encoding"))
     }
 }
 
 /// query utxo according to `TxoSID` return UnAuthenticated Utxo
 pub async fn query_utxo_light(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
+    web::Query(h): web::Query<HeightParam>,
 ) -> actix_web::Result<web::Json<UnAuthenticatedUtxo>> {
     let qs = data.read();
+    h.require_latest(&qs)?;
     let ledger = &qs.ledger_cloned;
     if let Ok(txo_sid) = info.parse::<u64>() {
         if let Some(txo) = ledger.get_utxo_light(TxoSID(txo_sid)) {
             Ok(web::Json(txo))
         } else {
-            Err(actix_web::error::ErrorNotFound(
-                "Specified txo does not currently exist.",
-            ))
+            Err(not_found("Specified txo does not currently exist."))
         }
     } else {
-        Err(actix_web::error::ErrorBadRequest(
-            "Invalid txo sid encoding",
-        ))
+        Err(invalid_param("Invalid txo sid encoding"))
     }
 }
 
 /// query issuance num according to `AssetTypeCode`
 pub async fn query_asset_issuance_num(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
 ) -> actix_web::Result<web::Json<u64>> {
     let qs = data.read();
@@ -90,47 +167,36 @@ pub async fn query_asset_issuance_num(
         if let Some(iss_num) = ledger.get_issuance_num(&token_code) {
             Ok(web::Json(iss_num))
         } else {
-            Err(actix_web::error::ErrorNotFound(
+            Err(not_found(
                 "Specified asset definition does not currently exist.",
             ))
         }
     } else {
-        Err(actix_web::error::ErrorBadRequest(
-            "Invalid asset definition encoding.",
-        ))
+        Err(invalid_param("Invalid asset definition encoding."))
     }
 }
 
 /// Separate a string of `TxoSID` by ',' and query the corresponding Authenticated utxo
 pub async fn query_utxos(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
+    web::Query(h): web::Query<HeightParam>,
 ) -> actix_web::Result<web::Json<Vec<Option<AuthenticatedUtxo>>>> {
-    let sid_list = info
-        .as_ref()
-        .split(',')
-        .map(|i| {
-            i.parse::<u64>()
-                .map(TxoSID)
-                .map_err(actix_web::error::ErrorBadRequest)
-        })
-        .collect::<actix_web::Result<Vec<_>, actix_web::error::Error>>()?;
+    let sid_list = parse_list(info.as_str(), 10, |i| i.parse::<u64>().map(TxoSID))?;
 
     let qs = data.read();
+    h.require_latest(&qs)?;
     let ledger = &qs.ledger_cloned;
 
-    if sid_list.len() > 10 || sid_list.is_empty() {
-        return Err(actix_web::error::ErrorBadRequest("Invalid Query List"));
-    }
     match ledger.get_utxos(sid_list.as_slice()) {
         Ok(v) => Ok(web::Json(v)),
-        Err(e) => Err(actix_web::error::ErrorBadRequest(format!("{:?}", e))),
+        Err(e) => Err(invalid_param(format!("{:?}", e))),
     }
 }
 
 /// query asset according to `AssetType`
 pub async fn query_asset(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
 ) -> actix_web::Result<web::Json<AssetType>> {
     let qs = data.read();
@@ -139,20 +205,63 @@ pub async fn query_asset(
         if let Some(asset) = ledger.get_asset_type(&token_code) {
             Ok(web::Json(asset))
         } else {
-            Err(actix_web::error::ErrorNotFound(
+            Err(not_found(
                 "Specified asset definition does not currently exist.",
             ))
         }
     } else {
-        Err(actix_web::error::ErrorBadRequest(
-            "Invalid asset definition encoding.",
-        ))
+        Err(invalid_param("Invalid asset definition encoding."))
+    }
+}
+
+/// query the transfer policy of an asset, `null` if it has none
+pub async fn query_asset_policy(
+    data: QueryState,
+    info: web::Path<String>,
+) -> actix_web::Result<web::Json<Option<CustomAssetPolicy>>> {
+    let code = AssetTypeCode::new_from_base64(&info)
+        .map_err(|_| invalid_param("Invalid asset definition encoding."))?;
+    let qs = data.read();
+    qs.ledger_cloned
+        .get_asset_type(&code)
+        .map(|asset| web::Json(asset.properties.asset_rules.transfer_policy))
+        .ok_or_else(|| not_found("Specified asset definition does not currently exist."))
+}
+
+/// `?sig=` of the tracing queries, the signature of the asset issuer on the asset code
+#[derive(Debug, Deserialize)]
+pub struct IssuerAuth {
+    sig: SignatureOf<AssetTypeCode>,
+}
+
+/// query the traceable TXOs of an asset with tracing policies,
+/// each along with whether it has been spent,
+/// the amounts can be decrypted from the tracing memos of the carrying transactions
+pub async fn query_tracked_txos(
+    data: QueryState,
+    info: web::Path<String>,
+    web::Query(auth): web::Query<IssuerAuth>,
+) -> actix_web::Result<web::Json<BTreeMap<TxoSID, (UnAuthenticatedUtxo, bool)>>> {
+    let code = AssetTypeCode::new_from_base64(&info)
+        .map_err(|_| invalid_param("Invalid asset definition encoding."))?;
+    let qs = data.read();
+    let ledger = &qs.ledger_cloned;
+    let asset = ledger.get_asset_type(&code).ok_or_else(|| {
+        not_found("Specified asset definition does not currently exist.")
+    })?;
+    if asset.get_tracing_policies_ref().is_empty() {
+        return Err(invalid_param("Specified asset has no tracing policies."));
     }
+    auth.sig
+        .verify(&asset.properties.issuer.key, &code)
+        .map_err(|_| unauthorized("invalid issuer signature"))?;
+
+    Ok(web::Json(ledger.get_tracked_txos(&code)))
 }
 
 /// get_derived asset code according to `AssetTypeCode`
 pub async fn get_derived_asset_code(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
 ) -> actix_web::Result<String> {
     let qs = data.read();
@@ -165,62 +274,179 @@ pub async fn get_derived_asset_code(
         );
         Ok(derived_asset_code.to_base64())
     } else {
-        Err(actix_web::error::ErrorBadRequest(
-            "Invalid asset definition encoding.",
-        ))
+        Err(invalid_param("Invalid asset definition encoding."))
     }
 }
 
 /// query tx according to `TxnSID`
 pub async fn query_txn(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
+    web::Query(h): web::Query<HeightParam>,
 ) -> actix_web::Result<String> {
     let qs = data.read();
+    let view = h.view(&qs)?;
     let ledger = &qs.ledger_cloned;
     if let Ok(txn_sid) = info.parse::<usize>() {
+        if !qs.txn_visible(&view, TxnSID(txn_sid)) {
+            return Err(not_found("Specified transaction does not exist."));
+        }
         if let Ok(mut txn) = ruc::info!(ledger.get_transaction(TxnSID(txn_sid))) {
             txn.finalized_txn.set_txo_id();
-            Ok(serde_json::to_string(&txn)?)
+            Ok(serde_json::to_string(&txn).map_err(internal)?)
         } else {
-            Err(actix_web::error::ErrorNotFound(
-                "Specified transaction does not exist.",
-            ))
+            Err(not_found("Specified transaction does not exist."))
         }
     } else {
-        Err(actix_web::error::ErrorBadRequest(
-            "Invalid txn sid encoding.",
-        ))
+        Err(invalid_param("Invalid txn sid encoding."))
     }
 }
 
+/// Merkle inclusion proof of a transaction
+#[allow(missing_docs)]
+#[derive(Deserialize, Serialize)]
+pub struct TxnProof {
+    pub txn_sid: TxnSID,
+    /// leaf index in the transaction merkle tree
+    pub merkle_id: u64,
+    pub proof: ProofOf<(TxnSID, Transaction)>,
+    /// current root of the transaction merkle tree
+    pub root: HashValue,
+    /// current state commitment, which commits to `root`
+    pub state_commitment: HashOf<Option<StateCommitmentData>>,
+    pub block_commit_count: u64,
+    pub block_height: u64,
+}
+
+/// query the merkle inclusion proof of a transaction according to `TxnSID`
+pub async fn query_txn_proof(
+    data: QueryState,
+    info: web::Path<String>,
+    web::Query(h): web::Query<HeightParam>,
+) -> actix_web::Result<web::Json<TxnProof>> {
+    let qs = data.read();
+    // the proof is against the latest transaction merkle root
+    h.require_latest(&qs)?;
+    let txn_sid = info
+        .parse::<usize>()
+        .map_err(|_| invalid_param("Invalid txn sid encoding."))?;
+    txn_proof(&qs.ledger_cloned, TxnSID(txn_sid))
+        .map(web::Json)
+        .ok_or_else(|| not_found("Specified transaction does not exist."))
+}
+
+/// The inclusion proof of the transaction `txn_sid`
+/// against the latest state, `None` if it does not exist
+pub fn txn_proof(ledger: &LedgerState, txn_sid: TxnSID) -> Option<TxnProof> {
+    let txn = ruc::info!(ledger.get_transaction(txn_sid)).ok()?;
+    Some(TxnProof {
+        txn_sid,
+        merkle_id: txn.finalized_txn.merkle_id,
+        proof: txn.txn_inclusion_proof,
+        root: txn.state_commitment_data.transaction_merkle_commitment,
+        state_commitment: txn.state_commitment,
+        block_commit_count: ledger.get_block_commit_count(),
+        block_height: ledger.get_tendermint_height(),
+    })
+}
+
+/// Membership or non-membership proof of a TXO in the UTXO set
+#[allow(missing_docs)]
+#[derive(Deserialize, Serialize)]
+pub struct UtxoProof {
+    pub sid: TxoSID,
+    /// `None` if the TXO is spent or does not exist
+    pub utxo: Option<TxOutput>,
+    pub proof: SparseMerkleProof,
+    /// current root of the UTXO tree, see `StateDomainHashes::utxos`
+    pub root: BitDigest,
+    /// current state commitment, which commits to `root`
+    pub state_commitment: HashOf<Option<StateCommitmentData>>,
+    pub block_commit_count: u64,
+    pub block_height: u64,
+}
+
+/// query the proof that a TXO is unspent, or that it is not, according to `TxoSID`
+pub async fn query_utxo_proof(
+    data: QueryState,
+    info: web::Path<String>,
+    web::Query(h): web::Query<HeightParam>,
+) -> actix_web::Result<web::Json<UtxoProof>> {
+    let qs = data.read();
+    // the proof is against the latest root of the UTXO tree
+    h.require_latest(&qs)?;
+    let sid = info
+        .parse::<u64>()
+        .map(TxoSID)
+        .map_err(|_| invalid_param("Invalid txo sid encoding."))?;
+    utxo_proof(&qs.ledger_cloned, sid)
+        .map(web::Json)
+        .map_err(unavailable)
+}
+
+/// The proof that the TXO `sid` is unspent, or that it is not,
+/// against the latest state, the error tells why it is unavailable
+pub fn utxo_proof(
+    ledger: &LedgerState,
+    sid: TxoSID,
+) -> std::result::Result<UtxoProof, &'static str> {
+    let root = ledger
+        .get_state_commitment_data()
+        .and_then(|d| d.domains.as_ref().map(|d| d.utxos))
+        .ok_or("The UTXO tree is not activated yet.")?;
+    let (utxo, proof) = ledger
+        .get_utxo_proof(sid)
+        .ok_or("The UTXO tree is not activated yet.")?;
+    // the tree may have moved on with a block being committed
+    if !proof.verify(&root, utxo.as_ref()) {
+        return Err("The UTXO tree is being updated, retry later.");
+    }
+    let (state_commitment, block_commit_count) = ledger.get_state_commitment();
+
+    Ok(UtxoProof {
+        sid,
+        utxo,
+        proof,
+        root,
+        state_commitment,
+        block_commit_count,
+        block_height: ledger.get_tendermint_height(),
+    })
+}
+
 /// query tx according to `TxnSID`, lighter and faster version
 pub async fn query_txn_light(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
+    web::Query(h): web::Query<HeightParam>,
 ) -> actix_web::Result<String> {
     let qs = data.read();
+    let view = h.view(&qs)?;
     let ledger = &qs.ledger_cloned;
     if let Ok(txn_sid) = info.parse::<usize>() {
+        if !qs.txn_visible(&view, TxnSID(txn_sid)) {
+            return Err(not_found("Specified transaction does not exist."));
+        }
+        if let Some(txn) = qs.txn_light_cache.get(&txn_sid) {
+            return Ok(txn);
+        }
         if let Ok(mut txn) = ruc::info!(ledger.get_transaction_light(TxnSID(txn_sid))) {
             txn.set_txo_id();
-            Ok(serde_json::to_string(&txn)?)
+            let rendered = serde_json::to_string(&txn).map_err(internal)?;
+            qs.txn_light_cache.insert(txn_sid, rendered.clone());
+            Ok(rendered)
         } else {
-            Err(actix_web::error::ErrorNotFound(
-                "Specified transaction does not exist.",
-            ))
+            Err(not_found("Specified transaction does not exist."))
         }
     } else {
-        Err(actix_web::error::ErrorBadRequest(
-            "Invalid txn sid encoding.",
-        ))
+        Err(invalid_param("Invalid txn sid encoding."))
     }
 }
 
 /// query global state, return (apphash, block count, apphash and block count signatures)
 #[allow(clippy::type_complexity)]
 pub async fn query_global_state(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
 ) -> web::Json<(HashOf<Option<StateCommitmentData>>, u64, &'static str)> {
     let qs = data.read();
     let (hash, seq_id) = qs.get_state_commitment_from_api_cache();
@@ -230,7 +456,7 @@ pub async fn query_global_state(
 
 /// query global state version according to `block_height`
 pub async fn query_global_state_version(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     version: web::Path<u64>,
 ) -> web::Json<Option<HashOf<Option<StateCommitmentData>>>> {
     let qs = data.read();
@@ -239,49 +465,200 @@ pub async fn query_global_state_version(
     web::Json(hash)
 }
 
+/// The maximum number of blocks in one `/state_commitment_history` query
+pub const STATE_COMMITMENT_HISTORY_LIMIT: u64 = 1000;
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct StateCommitmentRange {
+    from: u64,
+    to: u64,
+}
+
+/// A compact header for light clients, `commitment` is the hash of `data`,
+/// which links to the previous one by `data.previous_state_commitment`.
+#[allow(missing_docs)]
+#[derive(Deserialize, Serialize)]
+pub struct StateCommitmentHeader {
+    pub block_commit_count: u64,
+    pub commitment: HashOf<Option<StateCommitmentData>>,
+    pub data: StateCommitmentData,
+}
+
+/// Query the chain of state commitments of blocks in `[from, to]`,
+/// at most `STATE_COMMITMENT_HISTORY_LIMIT` blocks are returned at a time.
+///
+/// NOTE: the latest commitment should be checked against the app hash
+/// of a tendermint header signed by the validators.
+pub async fn query_state_commitment_history(
+    data: QueryState,
+    web::Query(range): web::Query<StateCommitmentRange>,
+) -> actix_web::Result<web::Json<Vec<StateCommitmentHeader>>> {
+    if range.from > range.to {
+        return Err(invalid_param("`from` is greater than `to`"));
+    }
+    let to = range.to.min(
+        range
+            .from
+            .saturating_add(STATE_COMMITMENT_HISTORY_LIMIT - 1),
+    );
+
+    let qs = data.read();
+    let headers = qs
+        .ledger_cloned
+        .get_state_commitment_history(range.from, to)
+        .into_iter()
+        .map(
+            |(block_commit_count, commitment, data)| StateCommitmentHeader {
+                block_commit_count,
+                commitment,
+                data,
+            },
+        )
+        .collect();
+
+    Ok(web::Json(headers))
+}
+
+/// Query the transactions and header info of the tendermint block `height`
+pub async fn query_block(
+    data: QueryState,
+    height: web::Path<BlockHeight>,
+) -> actix_web::Result<web::Json<BlockMeta>> {
+    let qs = data.read();
+    qs.get_block_meta(*height)
+        .map(web::Json)
+        .ok_or_else(|| not_found("Specified block does not exist."))
+}
+
+/// Query the tendermint blocks in `[from, to]`,
+/// at most `STATE_COMMITMENT_HISTORY_LIMIT` blocks are returned at a time.
+pub async fn query_blocks(
+    data: QueryState,
+    web::Query(range): web::Query<StateCommitmentRange>,
+) -> actix_web::Result<web::Json<Vec<BlockMeta>>> {
+    if range.from > range.to {
+        return Err(invalid_param("`from` is greater than `to`"));
+    }
+    let to = range.to.min(
+        range
+            .from
+            .saturating_add(STATE_COMMITMENT_HISTORY_LIMIT - 1),
+    );
+
+    let qs = data.read();
+    Ok(web::Json(qs.get_block_metas(range.from, to)))
+}
+
+/// Query the committed abar merkle tree, provers pick a root from
+/// `/abar_roots` whose version is not below `min_version`
+pub async fn query_abar_tree(
+    data: QueryState,
+) -> actix_web::Result<web::Json<AbarTreeInfo>> {
+    data.read()
+        .get_abar_tree_info()
+        .map(web::Json)
+        .ok_or_else(|| unavailable("The abar merkle tree can not be read."))
+}
+
+/// Query the roots of the abar merkle tree after the tendermint blocks
+/// in `[from, to]`, at most `STATE_COMMITMENT_HISTORY_LIMIT` blocks at a time.
+pub async fn query_abar_roots(
+    data: QueryState,
+    web::Query(range): web::Query<StateCommitmentRange>,
+) -> actix_web::Result<web::Json<Vec<(BlockHeight, AbarRoot)>>> {
+    if range.from > range.to {
+        return Err(invalid_param("`from` is greater than `to`"));
+    }
+    let to = range.to.min(
+        range
+            .from
+            .saturating_add(STATE_COMMITMENT_HISTORY_LIMIT - 1),
+    );
+
+    let qs = data.read();
+    Ok(web::Json(qs.get_abar_roots(range.from, to)))
+}
+
+/// The latest state commitment with the hashes it commits to,
+/// nodes diverging from each other can be told apart by the differing ones.
+#[allow(missing_docs)]
+#[derive(Deserialize, Serialize)]
+pub struct StateHash {
+    pub block_commit_count: u64,
+    pub block_height: u64,
+    pub commitment: HashOf<Option<StateCommitmentData>>,
+    pub utxo_bitmap: BitDigest,
+    pub block_merkle: HashValue,
+    pub transaction_merkle: HashValue,
+    /// `None` before the `state_domains_height` checkpoint
+    pub domains: Option<StateDomainHashes>,
+}
+
+/// Query the sub-hashes of the latest state commitment
+pub async fn query_state_hash(
+    data: QueryState,
+) -> actix_web::Result<web::Json<StateHash>> {
+    let qs = data.read();
+    let ledger = &qs.ledger_cloned;
+    let (commitment, block_commit_count) = ledger.get_state_commitment();
+    let data = ledger
+        .get_state_commitment_data()
+        .ok_or_else(|| not_found("No block has been committed."))?;
+
+    Ok(web::Json(StateHash {
+        block_commit_count,
+        block_height: ledger.get_tendermint_height(),
+        commitment,
+        utxo_bitmap: data.bitmap,
+        block_merkle: data.block_merkle,
+        transaction_merkle: data.transaction_merkle_commitment,
+        domains: data.domains.clone(),
+    }))
+}
+
+/// Query the checksum of the UTXO bitmap after the block `version`(block commit count)
+pub async fn query_utxo_checksum(
+    data: QueryState,
+    version: web::Path<u64>,
+) -> actix_web::Result<web::Json<BitDigest>> {
+    let qs = data.read();
+    qs.ledger_cloned
+        .get_utxo_checksum(*version)
+        .map(web::Json)
+        .ok_or_else(|| not_found("Specified version does not exist."))
+}
+
+/// Query the UTXO bitmap checksums of blocks in `[from, to]`,
+/// at most `STATE_COMMITMENT_HISTORY_LIMIT` blocks are returned at a time.
+pub async fn query_utxo_checksum_range(
+    data: QueryState,
+    web::Query(range): web::Query<StateCommitmentRange>,
+) -> actix_web::Result<web::Json<Vec<(u64, BitDigest)>>> {
+    if range.from > range.to {
+        return Err(invalid_param("`from` is greater than `to`"));
+    }
+    let to = range.to.min(
+        range
+            .from
+            .saturating_add(STATE_COMMITMENT_HISTORY_LIMIT - 1),
+    );
+
+    let qs = data.read();
+    let checksums = (range.from.max(1)..=to)
+        .map_while(|v| qs.ledger_cloned.get_utxo_checksum(v).map(|c| (v, c)))
+        .collect();
+
+    Ok(web::Json(checksums))
+}
+
 /// Query current validator list,
 /// validtors who have not completed self-deletagion will be filtered out.
 #[allow(unused)]
 pub async fn query_validators(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
 ) -> actix_web::Result<web::Json<ValidatorList>> {
-    let qs = data.read();
-    let ledger = &qs.ledger_cloned;
-    let staking = ledger.get_staking();
-
-    if let Some(validator_data) = staking.validator_get_current() {
-        let validators = validator_data.get_validator_addr_map();
-        let validators_list = validators
-            .iter()
-            .flat_map(|(tendermint_addr, pk)| {
-                validator_data
-                    .get_validator_by_id(pk)
-                    .filter(|v| v.td_power != 0)
-                    .map(|v| {
-                        let mut power_list = validator_data
-                            .body
-                            .values()
-                            .map(|v| v.td_power)
-                            .collect::<Vec<_>>();
-                        power_list.sort_unstable();
-                        let rank = power_list.len()
-                            - power_list.binary_search(&v.td_power).unwrap();
-                        Validator::new(
-                            tendermint_addr.clone(),
-                            rank as u64,
-                            staking.delegation_has_addr(&pk),
-                            &v,
-                        )
-                    })
-            })
-            .collect();
-        return Ok(web::Json(ValidatorList::new(
-            staking.cur_height(),
-            validators_list,
-        )));
-    };
-
-    Ok(web::Json(ValidatorList::new(0, vec![])))
+    Ok(web::Json(data.read().query_validators()))
 }
 
 #[allow(missing_docs)]
@@ -293,25 +670,23 @@ pub struct DelegationRwdQueryParams {
 
 /// get delegation reward according to `DelegationRwdQueryParams`
 pub async fn get_delegation_reward(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     web::Query(info): web::Query<DelegationRwdQueryParams>,
 ) -> actix_web::Result<web::Json<Vec<DelegationRwdDetail>>> {
     // Convert from base64 representation
-    let key: XfrPublicKey = globutils::wallet::public_key_from_base64(&info.address)
+    let key: XfrPublicKey = parse_address(&info.address)
         .c(d!())
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+        .map_err(|e| invalid_param(e.to_string()))?;
 
     let qs = data.read();
 
     let hdr = qs
-        .ledger_cloned
-        .api_cache
-        .as_ref()
-        .unwrap()
+        .api_cache()
+        .ok_or_else(|| unavailable(API_CACHE_UNAVAILABLE))?
         .staking_delegation_rwd_hist
         .get(&key)
         .c(d!())
-        .map_err(|e| error::ErrorNotFound(e.to_string()))?;
+        .map_err(|e| not_found(e.to_string()))?;
 
     let h = qs.ledger_cloned.get_tendermint_height();
 
@@ -343,7 +718,7 @@ pub struct ValidatorDelegation {
 
 /// get history according to `ValidatorDelegationQueryParams`
 pub async fn get_validator_delegation_history(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     web::Query(info): web::Query<ValidatorDelegationQueryParams>,
 ) -> actix_web::Result<web::Json<Vec<ValidatorDelegation>>> {
     let qs = data.read();
@@ -353,35 +728,21 @@ pub async fn get_validator_delegation_history(
     let v_id = staking
         .validator_td_addr_to_app_pk(&info.address)
         .c(d!())
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
 
     let h = staking.cur_height();
 
     let start_height = staking
         .delegation_get(&v_id)
-        .ok_or_else(|| error::ErrorBadRequest("not exists"))?
+        .ok_or_else(|| invalid_param("not exists"))?
         .start_height;
 
-    let staking_global_rate_hist = &qs
-        .ledger_cloned
-        .api_cache
-        .as_ref()
-        .unwrap()
-        .staking_global_rate_hist;
-
-    let delegation_amount_hist = ledger
-        .api_cache
-        .as_ref()
-        .unwrap()
-        .staking_delegation_amount_hist
-        .get(&v_id);
-
-    let self_delegation_amount_hist = ledger
-        .api_cache
-        .as_ref()
-        .unwrap()
-        .staking_self_delegation_hist
-        .get(&v_id);
+    let api_cache = qs
+        .api_cache()
+        .ok_or_else(|| unavailable(API_CACHE_UNAVAILABLE))?;
+    let staking_global_rate_hist = &api_cache.staking_global_rate_hist;
+    let delegation_amount_hist = api_cache.staking_delegation_amount_hist.get(&v_id);
+    let self_delegation_amount_hist = api_cache.staking_self_delegation_hist.get(&v_id);
 
     let mut esiz = info.epoch_size.unwrap_or(10);
     alt!(esiz > h, esiz = h);
@@ -472,7 +833,7 @@ enum OrderOption {
 
 /// paging Query delegators according to `DelegatorQueryParams`
 pub async fn get_delegators_with_params(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     web::Query(info): web::Query<DelegatorQueryParams>,
 ) -> actix_web::Result<web::Json<DelegatorList>> {
     let qs = data.read();
@@ -486,15 +847,15 @@ pub async fn get_delegators_with_params(
     let start = (info.page - 1)
         .checked_mul(info.per_page)
         .c(d!())
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
     let end = start
         .checked_add(info.per_page)
         .c(d!())
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
 
     let list = validator_get_delegator_list(staking, info.address.as_ref(), start, end)
         .c(d!())
-        .map_err(error::ErrorNotFound)?;
+        .map_err(not_found)?;
 
     let list: Vec<DelegatorInfo> = list
         .iter()
@@ -508,7 +869,7 @@ pub async fn get_delegators_with_params(
 
 /// query delegator list according to `TendermintAddr`
 pub async fn query_delegator_list(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     addr: web::Path<TendermintAddr>,
 ) -> actix_web::Result<web::Json<DelegatorList>> {
     let qs = data.read();
@@ -517,7 +878,7 @@ pub async fn query_delegator_list(
 
     let list = validator_get_delegator_list(staking, addr.as_ref(), 0, usize::MAX)
         .c(d!())
-        .map_err(error::ErrorNotFound)?;
+        .map_err(not_found)?;
 
     let list: Vec<DelegatorInfo> = list
         .iter()
@@ -531,7 +892,7 @@ pub async fn query_delegator_list(
 
 /// query validator detail according to `TendermintAddr`
 pub async fn query_validator_detail(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     addr: web::Path<TendermintAddr>,
 ) -> actix_web::Result<web::Json<ValidatorDetail>> {
     let qs = data.read();
@@ -540,11 +901,11 @@ pub async fn query_validator_detail(
 
     // Get Pub key from from the provided tendermint address
     let v_pub_key = info!(staking.validator_td_addr_to_app_pk(addr.as_ref()))
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
     // Get Self delegation for the validator
     // v_self_delegation is the delegation details for the validator
     let v_self_delegation =
-        info!(staking.delegation_get(&v_pub_key)).map_err(error::ErrorBadRequest)?;
+        info!(staking.delegation_get(&v_pub_key)).map_err(invalid_param)?;
 
     if let Some(vd) = staking.validator_get_current() {
         if let Some(v) = vd.body.get(&v_pub_key) {
@@ -554,7 +915,8 @@ pub async fn query_validator_detail(
                 let mut power_list =
                     vd.body.values().map(|v| v.td_power).collect::<Vec<_>>();
                 power_list.sort_unstable();
-                power_list.len() - power_list.binary_search(&v.td_power).unwrap()
+                power_list.len()
+                    - power_list.binary_search(&v.td_power).unwrap_or_else(|i| i)
             };
             // Network Realtime APY
             let network_realtime_apy = ledger.staking_get_block_rewards_rate();
@@ -576,7 +938,7 @@ pub async fn query_validator_detail(
             for (delegator, _) in &v.delegators {
                 let delegation = staking
                     .delegation_get(&delegator)
-                    .ok_or_else(|| error::ErrorBadRequest("not exists"))?;
+                    .ok_or_else(|| invalid_param("not exists"))?;
                 fra_rewards += delegation.rwd_amount;
             }
             let resp = ValidatorDetail {
@@ -605,123 +967,207 @@ pub async fn query_validator_detail(
         }
     }
 
-    Err(error::ErrorNotFound("not exists"))
+    Err(not_found("not exists"))
+}
+
+/// The maximum number of epochs in one query of `/validator_history`
+pub const MAX_VALIDATOR_HIST_EPOCHS: u64 = 1024;
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct ValidatorHistoryParams {
+    from_height: Option<BlockHeight>,
+    to_height: Option<BlockHeight>,
+}
+
+/// Signing record and rewards of a validator in an epoch
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ValidatorEpoch {
+    pub start_height: BlockHeight,
+    pub end_height: BlockHeight,
+    pub signed_cnt: u64,
+    pub missed_cnt: u64,
+    /// signed blocks / recorded blocks
+    pub uptime: [u64; 2],
+    /// rewards received as the proposer, including the commissions
+    pub rewards: u64,
+}
+
+/// per-epoch signing records and rewards of a validator,
+/// `to_height` defaults to the current height,
+/// `from_height` to the start of the oldest epoch allowed
+pub async fn query_validator_history(
+    data: QueryState,
+    addr: web::Path<TendermintAddr>,
+    web::Query(info): web::Query<ValidatorHistoryParams>,
+) -> actix_web::Result<web::Json<Vec<ValidatorEpoch>>> {
+    let qs = data.read();
+    let staking = qs.ledger_cloned.get_staking();
+    let pk = info!(staking.validator_td_addr_to_app_pk(addr.as_ref()))
+        .map_err(invalid_param)?;
+
+    let to = min!(info.to_height.unwrap_or(u64::MAX), staking.cur_height());
+    let last = to / VALIDATOR_HIST_EPOCH;
+    let first = info
+        .from_height
+        .map(|h| h / VALIDATOR_HIST_EPOCH)
+        .unwrap_or_else(|| last.saturating_sub(MAX_VALIDATOR_HIST_EPOCHS - 1));
+    if first > last {
+        return Err(invalid_param("`from_height` is beyond `to_height`"));
+    }
+    if last - first >= MAX_VALIDATOR_HIST_EPOCHS {
+        return Err(invalid_param(format!(
+            "at most {MAX_VALIDATOR_HIST_EPOCHS} epochs of {VALIDATOR_HIST_EPOCH} blocks"
+        )));
+    }
+
+    let res = qs
+        .get_validator_history(&pk, first, last)
+        .into_iter()
+        .map(|(e, r)| ValidatorEpoch {
+            start_height: e * VALIDATOR_HIST_EPOCH,
+            end_height: (e + 1) * VALIDATOR_HIST_EPOCH - 1,
+            signed_cnt: r.signed_cnt,
+            missed_cnt: r.missed_cnt,
+            uptime: [r.signed_cnt, r.signed_cnt + r.missed_cnt],
+            rewards: r.rewards,
+        })
+        .collect();
+
+    Ok(web::Json(res))
 }
 
 /// query delegation info according to `public_key`
 pub async fn query_delegation_info(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     address: web::Path<String>,
 ) -> actix_web::Result<web::Json<DelegationInfo>> {
-    let pk = globutils::wallet::public_key_from_base64(address.as_str())
+    let pk = parse_address(address.as_str())
         .c(d!())
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+        .map_err(|e| invalid_param(e.to_string()))?;
 
-    let qs = data.read();
-    let ledger = &qs.ledger_cloned;
-    let staking = ledger.get_staking();
+    Ok(web::Json(data.read().query_delegation_info(&pk)))
+}
 
-    let block_rewards_rate = ledger.staking_get_block_rewards_rate();
-    let global_staking = staking.validator_global_power();
-    let global_delegation = staking.delegation_info_global_amount();
-
-    let (
-        bond_amount,
-        bond_entries,
-        unbond_amount,
-        rwd_amount,
-        start_height,
-        end_height,
-        delegation_rwd_cnt,
-        proposer_rwd_cnt,
-    ) = staking
-        .delegation_get(&pk)
-        .map(|d| {
-            let mut bond_amount = d.amount();
-            let bond_entries: Vec<(String, u64)> = d
-                .delegations
-                .iter()
-                .filter_map(|(pk, am)| {
-                    staking
-                        .validator_app_pk_to_td_addr(pk)
-                        .ok()
-                        .map(|addr| (addr, *am))
-                })
-                .collect();
-            let mut unbond_amount = 0;
-            match d.state {
-                DelegationState::Paid => {
-                    bond_amount = 0;
-                }
-                DelegationState::Free => {
-                    mem::swap(&mut bond_amount, &mut unbond_amount);
-                }
-                DelegationState::Bond => {
-                    if staking.cur_height()
-                        > d.end_height()
-                            .saturating_sub(CFG.checkpoint.unbond_block_cnt)
-                    {
-                        mem::swap(&mut bond_amount, &mut unbond_amount);
-                    }
-                }
-            }
-            // check temporary partial undelegators
-            unbond_amount += d.tmp_delegators.values().sum::<u64>();
-
-            (
-                bond_amount,
-                bond_entries,
-                unbond_amount,
-                d.rwd_amount,
-                d.start_height(),
-                d.end_height(),
-                d.delegation_rwd_cnt,
-                d.proposer_rwd_cnt,
-            )
+/// A pending unbonding, as listed by `/unbonding`
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UnbondingEntry {
+    /// the address itself, or the temporary delegator
+    /// of a partial undelegation
+    pub delegator: String,
+    pub validator: TendermintAddr,
+    pub amount: u64,
+    /// the funds are freed after this height,
+    /// then paid back by the next coinbase transaction
+    pub maturity_height: BlockHeight,
+    pub remaining_blocks: u64,
+    /// freed, waiting for the payment
+    pub matured: bool,
+}
+
+/// Pending unbondings of an address, in the order of maturity
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UnbondingList {
+    pub current_height: BlockHeight,
+    pub total: u64,
+    pub entries: Vec<UnbondingEntry>,
+    /// seconds since the staking view was built
+    pub cache_age: u64,
+}
+
+/// query the pending unbondings of `public_key`
+pub async fn query_unbonding(
+    data: QueryState,
+    address: web::Path<String>,
+) -> actix_web::Result<web::Json<UnbondingList>> {
+    let pk = parse_address(address.as_str())
+        .c(d!())
+        .map_err(|e| invalid_param(e.to_string()))?;
+
+    Ok(web::Json(data.read().query_unbonding(&pk)))
+}
+
+/// A scheduled transfer, as listed by `/scheduled_transfers`
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ScheduleEntry {
+    pub id: u64,
+    #[serde(flatten)]
+    pub schedule: ScheduledTransfer,
+    /// payments left, none once canceled
+    pub remaining_payments: u64,
+}
+
+/// Active scheduled transfers of an address, in the order of their ids
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ScheduleList {
+    pub current_height: BlockHeight,
+    pub entries: Vec<ScheduleEntry>,
+}
+
+/// query the active scheduled transfers paid from or to `public_key`,
+/// the canceled ones are listed until their escrow is refunded
+pub async fn query_scheduled_transfers(
+    data: QueryState,
+    address: web::Path<String>,
+) -> actix_web::Result<web::Json<ScheduleList>> {
+    let pk = parse_address(address.as_str())
+        .c(d!())
+        .map_err(|e| invalid_param(e.to_string()))?;
+
+    let qs = data.read();
+    let staking = qs.ledger_cloned.get_staking();
+    let entries = staking
+        .schedule_get_by_addr(&pk)
+        .into_iter()
+        .map(|(id, st)| ScheduleEntry {
+            id,
+            remaining_payments: alt!(st.canceled, 0, st.balance / st.amount),
+            schedule: st.clone(),
         })
-        .unwrap_or((0, vec![], 0, 0, 0, 0, 0, 0));
-
-    let mut resp = DelegationInfo::new(
-        bond_amount,
-        bond_entries,
-        unbond_amount,
-        rwd_amount,
-        block_rewards_rate,
-        global_delegation,
-        global_staking,
-    );
-    resp.start_height = start_height;
-    resp.current_height = staking.cur_height();
-    resp.end_height = end_height;
-    resp.delegation_rwd_cnt = delegation_rwd_cnt;
-    resp.proposer_rwd_cnt = proposer_rwd_cnt;
+        .collect();
 
-    Ok(web::Json(resp))
+    Ok(web::Json(ScheduleList {
+        current_height: staking.cur_height(),
+        entries,
+    }))
 }
 
 /// query utxos according `public_key`
 pub async fn query_owned_utxos(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     owner: web::Path<String>,
+    web::Query(h): web::Query<HeightParam>,
+    web::Query(lock): web::Query<LockFilter>,
 ) -> actix_web::Result<web::Json<BTreeMap<TxoSID, (Utxo, Option<OwnerMemo>)>>> {
     let qs = data.read();
+    h.require_latest(&qs)?;
     let ledger = &qs.ledger_cloned;
-    globutils::wallet::public_key_from_base64(owner.as_str())
+    let height = ledger.get_block_commit_count();
+    let pk = parse_address(owner.as_str())
         .c(d!())
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))
-        .map(|pk| web::Json(pnk!(ledger.get_owned_utxos(&pk))))
+        .map_err(|e| invalid_param(e.to_string()))?;
+    let mut utxos = ledger
+        .get_owned_utxos(&pk)
+        .map_err(|e| internal(e.generate_log(None)))?;
+    utxos.retain(|_, (utxo, _)| lock.matches(utxo, height));
+    Ok(web::Json(utxos))
 }
 
 // query utxos according to `commitment`
 pub(super) async fn query_owned_abar(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     com: web::Path<String>,
 ) -> actix_web::Result<web::Json<Option<(ATxoSID, ABARData)>>> {
     let qs = data.read();
     let ledger = &qs.ledger_cloned;
     globutils::wallet::commitment_from_base58(com.as_str())
         .c(d!())
-        .map_err(|e| error::ErrorBadRequest(e.generate_log(None)))
+        .map_err(|e| invalid_param(e.generate_log(None)))
         .map(|com| {
             web::Json(ledger.get_owned_abar(&com).map(|a| {
                 let c = wallet::commitment_to_base58(&com);
@@ -737,10 +1183,15 @@ pub enum ApiRoutes {
     UtxoSidList,
     AssetIssuanceNum,
     AssetToken,
+    AssetPolicy,
+    TrackedTxos,
     GetDerivedAssetCode,
     GlobalState,
     TxnSid,
     TxnSidLight,
+    TxnProof,
+    UtxoProof,
+    Block,
     GlobalStateVersion,
     OwnedUtxos,
     OwnedAbars,
@@ -748,6 +1199,9 @@ pub enum ApiRoutes {
     DelegationInfo,
     DelegatorList,
     ValidatorDetail,
+    ValidatorHistory,
+    Unbonding,
+    ScheduledTransfers,
 }
 
 impl NetworkRoute for ApiRoutes {
@@ -758,16 +1212,24 @@ impl NetworkRoute for ApiRoutes {
             ApiRoutes::UtxoSidList => "utxo_sid_list",
             ApiRoutes::AssetIssuanceNum => "asset_issuance_num",
             ApiRoutes::AssetToken => "asset_token",
+            ApiRoutes::AssetPolicy => "asset_policy",
+            ApiRoutes::TrackedTxos => "tracked_txos",
             ApiRoutes::GetDerivedAssetCode => "get_derived_asset_code",
             ApiRoutes::GlobalState => "global_state",
             ApiRoutes::TxnSid => "txn_sid",
             ApiRoutes::TxnSidLight => "txn_sid_light",
+            ApiRoutes::TxnProof => "txn_proof",
+            ApiRoutes::UtxoProof => "utxo_proof",
+            ApiRoutes::Block => "block",
             ApiRoutes::GlobalStateVersion => "global_state_version",
             ApiRoutes::OwnedUtxos => "owned_utxos",
             ApiRoutes::ValidatorList => "validator_list",
             ApiRoutes::DelegationInfo => "delegation_info",
             ApiRoutes::DelegatorList => "delegator_list",
             ApiRoutes::ValidatorDetail => "validator_detail",
+            ApiRoutes::ValidatorHistory => "validator_history",
+            ApiRoutes::Unbonding => "unbonding",
+            ApiRoutes::ScheduledTransfers => "scheduled_transfers",
             ApiRoutes::OwnedAbars => "owned_abars",
         };
         "/".to_owned() + endpoint
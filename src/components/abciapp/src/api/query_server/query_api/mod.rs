@@ -5,34 +5,72 @@
 // pub it for doc
 pub mod ledger_api;
 
+pub mod asset_index;
+pub mod audit;
+pub mod export;
+pub mod openapi;
 pub mod server;
 pub mod service;
+pub mod staking_view;
+pub mod state;
+pub mod view_key;
+pub mod webhook;
 
 use {
-    actix_cors::Cors,
-    actix_web::{error, middleware, web, App, HttpServer},
+    crate::{
+        abci::shutdown,
+        api::{
+            address::{parse_address, ConvertedAddress},
+            auth::{authorize, Auth},
+            backup::{self, BackupStatus},
+            cors,
+            error::{extractor_error, internal, invalid_param, not_found, unavailable},
+            health::HealthStat,
+            invariants::{self, InvariantReport},
+            logging,
+            mem_guard::{MemGuardStat, MEM_GUARD},
+            request_id::RequestTracing,
+            validation::{parse_list, PayloadLimit},
+        },
+    },
+    actix_web::{middleware, web, App, HttpRequest, HttpResponse, HttpServer},
+    asset_index::{AssetHit, AssetSummary, MAX_SEARCH_PER_PAGE},
+    audit::{AuditRequest, AuditedTxo},
     config::abci::{global_cfg::CFG, CheckPointConfig},
+    export::{ClaimRow, FormatParam},
     finutils::api::NetworkRoute,
-    globutils::wallet,
     ledger::{
         data_model::{
-            b64dec, ATxoSID, AssetTypeCode, DefineAsset, IssuerPublicKey, Transaction,
-            TxOutput, TxnIDHash, TxnSID, TxoSID, XfrAddress, BLACK_HOLE_PUBKEY,
+            b64dec, ATxoSID, AssetTypeCode, DefineAsset, FeeParams, IssuerPublicKey,
+            Transaction, TxOutput, TxnIDHash, TxnSID, TxoSID, ViewKeyRegistration,
+            XfrAddress, BLACK_HOLE_PUBKEY,
         },
         staking::{
-            ops::mint_fra::MintEntry, FF_PK_EXTRA_120_0000, FRA, FRA_TOTAL_AMOUNT,
+            ops::mint_fra::MintEntry, params::ChainParamValues, FF_PK_EXTRA_120_0000,
+            FRA, FRA_TOTAL_AMOUNT,
         },
+        store::api_cache::{AnchorRecord, StakingEventRecord},
     },
     ledger_api::*,
+    openapi::get_openapi,
     parking_lot::RwLock,
     ruc::*,
     serde::{Deserialize, Serialize},
-    server::QueryServer,
+    server::{
+        AbarEntry, AbarTransferMaterial, BalanceHistoryEntry, QueryServer,
+        TimelineEntry, TxnDetails,
+    },
+    state::QueryState,
     std::{
-        collections::{BTreeMap, HashMap, HashSet},
-        sync::Arc,
+        collections::{BTreeMap, BTreeSet, HashMap},
+        fs,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
     },
     tracing::info,
+    webhook::{Subscription, WebhookStat},
     zei::{
         noah_algebra::serialization::NoahFromToBytes,
         noah_api::anon_xfr::structs::{AxfrOwnerMemo, Commitment, MTLeafInfo},
@@ -53,13 +91,13 @@ pub async fn version() -> actix_web::Result<String> {
 /// Queries the status of a transaction by its handle. Returns either a not committed message or a
 /// serialized TxnStatus.
 pub async fn get_address(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<u64>,
 ) -> actix_web::Result<String, actix_web::error::Error> {
     let server = data.read();
     let address_res = server.get_address_of_sid(TxoSID(*info));
     let res = if let Some(address) = address_res {
-        serde_json::to_string(&address)?
+        serde_json::to_string(&address).map_err(internal)?
     } else {
         format!("No utxo {} found. Please retry with a new utxo.", &info)
     };
@@ -69,24 +107,20 @@ pub async fn get_address(
 /// Returns the owner memo required to decrypt the asset record stored at given index, if it exists.
 #[allow(clippy::unnecessary_wraps)]
 pub async fn get_owner_memo(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<u64>,
 ) -> actix_web::Result<web::Json<Option<OwnerMemo>>, actix_web::error::Error> {
     let server = data.read();
     Ok(web::Json(server.get_owner_memo(TxoSID(*info))))
 }
 
-/// Separate a string of `TxoSID` by ',' and query the corresponding memo
-#[allow(clippy::unnecessary_wraps)]
+/// Separate a string of `TxoSID` by ',' and query the corresponding memo,
+/// limit `max_batch_len` of the config
 pub async fn get_owner_memo_batch(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
 ) -> actix_web::Result<web::Json<Vec<Option<OwnerMemo>>>, actix_web::error::Error> {
-    let ids = info
-        .as_ref()
-        .split(',')
-        .map(|i| i.parse::<u64>().map_err(actix_web::error::ErrorBadRequest))
-        .collect::<actix_web::Result<Vec<_>, actix_web::error::Error>>()?;
+    let ids = parse_list(info.as_str(), usize::MAX, |i| i.parse::<u64>())?;
     let hdr = data.read();
     let resp = ids
         .into_iter()
@@ -98,7 +132,7 @@ pub async fn get_owner_memo_batch(
 /// Returns the owner memo required to decrypt the asset record stored at given index, if it exists.
 #[allow(clippy::unnecessary_wraps)]
 async fn get_abar_memo(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<u64>,
 ) -> actix_web::Result<web::Json<Option<AxfrOwnerMemo>>, actix_web::error::Error> {
     let server = data.read();
@@ -108,47 +142,52 @@ async fn get_abar_memo(
 /// Returns the owner memos required to decrypt the asset record stored at between start and end,
 /// include start and end, limit 100.
 async fn get_abar_memos(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     query: web::Query<HashMap<String, u64>>,
 ) -> actix_web::Result<web::Json<Vec<(u64, AxfrOwnerMemo)>>, actix_web::error::Error> {
     match (query.get("start"), query.get("end")) {
         (Some(start), Some(end)) => {
             if end < start || end - start > 100 {
                 // return limit 100 error.
-                return Err(actix_web::error::ErrorBadRequest("Limit 100"));
+                return Err(invalid_param("Limit 100"));
             }
             let server = data.read();
             Ok(web::Json(server.get_abar_memos(*start, *end)))
         }
-        _ => Err(actix_web::error::ErrorBadRequest("Missing start and end")),
+        _ => Err(invalid_param("Missing start and end")),
     }
 }
 
 /// Return the abar commitment by sid.
 async fn get_abar_commitment(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<u64>,
 ) -> actix_web::Result<web::Json<Option<Commitment>>, actix_web::error::Error> {
     let server = data.read();
     Ok(web::Json(server.get_abar_commitment(ATxoSID(*info))))
 }
 
-/// Returns an array of the utxo sids currently spendable by a given address
+/// Returns an array of the utxo sids owned by a given address, in ascending order,
+/// `?locked=` selects the time-locked or the spendable ones
 pub async fn get_owned_utxos(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     owner: web::Path<String>,
-) -> actix_web::Result<web::Json<HashSet<TxoSID>>> {
+    web::Query(h): web::Query<HeightParam>,
+    web::Query(lock): web::Query<LockFilter>,
+) -> actix_web::Result<web::Json<BTreeSet<TxoSID>>> {
     let qs = data.read();
+    h.require_latest(&qs)?;
     let ledger = &qs.ledger_cloned;
+    let height = ledger.get_block_commit_count();
 
-    let pk = wallet::public_key_from_base64(owner.as_str())
-        .map_err(actix_web::error::ErrorServiceUnavailable)?;
+    let pk = parse_address(owner.as_str()).map_err(unavailable)?;
 
     let utxos = ledger
         .get_owned_utxos(&pk)
-        .map_err(actix_web::error::ErrorServiceUnavailable)?
-        .keys()
-        .copied()
+        .map_err(unavailable)?
+        .into_iter()
+        .filter(|(_, (utxo, _))| lock.matches(utxo, height))
+        .map(|(sid, _)| sid)
         .collect();
 
     Ok(web::Json(utxos))
@@ -156,21 +195,129 @@ pub async fn get_owned_utxos(
 
 /// Returns the ATxo Sid currently spendable by a given commitment
 async fn get_owned_abar(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     com: web::Path<String>,
 ) -> actix_web::Result<web::Json<Option<ATxoSID>>> {
     let qs = data.read();
     let ledger = &qs.ledger_cloned;
-    //let read = qs.state.as_ref().unwrap().read();
     globutils::wallet::commitment_from_base58(com.as_str())
         .c(d!())
-        .map_err(|e| error::ErrorBadRequest(e.generate_log(None)))
+        .map_err(|e| invalid_param(e.generate_log(None)))
         .map(|com| web::Json(ledger.get_owned_abar(&com)))
 }
 
+/// The most commitments in one `/owned_abars` query
+pub const MAX_ABAR_COMMITMENTS: usize = 10_000;
+
+/// Returns the anonymous record of a commitment with its owner memo
+async fn get_abar_by_commitment(
+    data: QueryState,
+    com: web::Path<String>,
+) -> actix_web::Result<web::Json<AbarEntry>> {
+    let com = globutils::wallet::commitment_from_base58(com.as_str())
+        .c(d!())
+        .map_err(|e| invalid_param(e.generate_log(None)))?;
+    data.read()
+        .get_abar_entry(&com)
+        .map(web::Json)
+        .ok_or_else(|| not_found("Specified commitment does not exist."))
+}
+
+/// Returns an address in bech32 and in the legacy base64 encoding,
+/// given in either of them
+async fn convert_address(
+    addr: web::Path<String>,
+) -> actix_web::Result<web::Json<ConvertedAddress>> {
+    parse_address(addr.as_str())
+        .map(|pk| web::Json(ConvertedAddress::from(&pk)))
+        .map_err(|e| invalid_param(e.to_string()))
+}
+
+/// Returns the transactions anchoring a document hash, the oldest first,
+/// with their block time
+async fn get_anchors(
+    data: QueryState,
+    hash: web::Path<String>,
+) -> actix_web::Result<web::Json<Vec<AnchorRecord>>> {
+    let hash = hash.to_lowercase();
+    match hex::decode(&hash) {
+        Ok(bytes) if 32 == bytes.len() => {}
+        _ => return Err(invalid_param("a hash should be 32 bytes in hex")),
+    }
+    let anchors = data.read().get_anchors(&hash);
+    if anchors.is_empty() {
+        return Err(not_found("Specified hash is not anchored."));
+    }
+    Ok(web::Json(anchors))
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct OwnedAbarsQuery {
+    /// base58 encoded
+    commitments: Vec<String>,
+    page: usize,
+    per_page: usize,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Serialize)]
+pub struct OwnedAbars {
+    total_count: u64,
+    /// in the order of the commitments, `None` for the unknown ones
+    abars: Vec<Option<AbarEntry>>,
+}
+
+/// Returns a page of the anonymous records of a list of commitments,
+/// a wallet syncs all of its records in a few requests
+async fn get_abars_by_commitments(
+    data: QueryState,
+    web::Json(info): web::Json<OwnedAbarsQuery>,
+) -> actix_web::Result<web::Json<OwnedAbars>> {
+    if MAX_ABAR_COMMITMENTS < info.commitments.len() {
+        return Err(invalid_param(format!(
+            "at most {MAX_ABAR_COMMITMENTS} commitments at a time"
+        )));
+    }
+    if MAX_SEARCH_PER_PAGE < info.per_page {
+        return Err(invalid_param(format!(
+            "at most {MAX_SEARCH_PER_PAGE} abars per page"
+        )));
+    }
+
+    let total_count = info.commitments.len() as u64;
+    if info.page == 0 {
+        return Ok(web::Json(OwnedAbars {
+            total_count,
+            abars: vec![],
+        }));
+    }
+
+    let start = (info.page - 1)
+        .checked_mul(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+
+    let qs = data.read();
+    let abars = info
+        .commitments
+        .iter()
+        .skip(start)
+        .take(info.per_page)
+        .map(|c| {
+            globutils::wallet::commitment_from_base58(c)
+                .c(d!())
+                .map_err(|e| invalid_param(e.generate_log(None)))
+                .map(|com| qs.get_abar_entry(&com))
+        })
+        .collect::<actix_web::Result<Vec<_>>>()?;
+
+    Ok(web::Json(OwnedAbars { total_count, abars }))
+}
+
 /// Returns the Merkle proof for anonymous transactions
 async fn get_abar_proof(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<u64>,
 ) -> actix_web::Result<web::Json<Option<MTLeafInfo>>, actix_web::error::Error> {
     let server = data.read();
@@ -179,22 +326,52 @@ async fn get_abar_proof(
 
 /// Checks if a nullifier hash is present in nullifier set
 async fn check_nullifier_hash(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
 ) -> actix_web::Result<web::Json<Option<bool>>, actix_web::error::Error> {
     let server = data.read();
     Ok(web::Json(server.check_nullifier_hash((*info).clone())))
 }
 
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct AbarTransferParams {
+    /// `ATxoSID`s separated by ','
+    atxo_sids: String,
+    /// base58 encoded nullifiers separated by ','
+    nullifiers: Option<String>,
+}
+
+/// Returns memos, Merkle proofs, the current root and the nullifier status
+/// needed to build an anonymous transfer, limit 100 abars.
+async fn get_abar_transfer_material(
+    data: QueryState,
+    web::Query(info): web::Query<AbarTransferParams>,
+) -> actix_web::Result<web::Json<AbarTransferMaterial>, actix_web::error::Error> {
+    let sids = parse_list(&info.atxo_sids, 100, |i| i.parse::<u64>().map(ATxoSID))?;
+    let nullifiers = info
+        .nullifiers
+        .as_deref()
+        .filter(|n| !n.trim().is_empty())
+        .map(|n| parse_list(n, 100, |i| Ok::<_, String>(i.to_owned())))
+        .transpose()?
+        .unwrap_or_default();
+
+    let server = data.read();
+    Ok(web::Json(
+        server.get_abar_transfer_material(&sids, nullifiers),
+    ))
+}
+
 async fn get_max_atxo_sid(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
 ) -> actix_web::Result<web::Json<Option<usize>>, actix_web::error::Error> {
     let server = data.read();
     Ok(web::Json(server.max_atxo_sid()))
 }
 
 async fn get_max_atxo_sid_at_height(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<u64>,
 ) -> actix_web::Result<web::Json<Option<usize>>, actix_web::error::Error> {
     let server = data.read();
@@ -214,6 +391,7 @@ pub enum QueryServerRoutes {
     GetAbarMemos,
     GetAbarProof,
     CheckNullifierHash,
+    GetAbarTransferMaterial,
     GetMaxATxoSid,
     GetMaxATxoSidAtHeight,
     GetCreatedAssets,
@@ -224,6 +402,7 @@ pub enum QueryServerRoutes {
     GetAuthencatedTxnIDHash,
     GetTransactionHash,
     GetTransactionSid,
+    GetTxnDetails,
     GetCommits,
 }
 
@@ -242,6 +421,7 @@ impl NetworkRoute for QueryServerRoutes {
             QueryServerRoutes::GetAbarMemos => "get_abar_memos",
             QueryServerRoutes::GetAbarProof => "get_abar_proof",
             QueryServerRoutes::CheckNullifierHash => "check_nullifier_hash",
+            QueryServerRoutes::GetAbarTransferMaterial => "get_abar_transfer_material",
             QueryServerRoutes::GetMaxATxoSid => "get_max_atxo_sid",
             QueryServerRoutes::GetMaxATxoSidAtHeight => "get_max_atxo_sid_at_height",
             QueryServerRoutes::GetCreatedAssets => "get_created_assets",
@@ -250,6 +430,7 @@ impl NetworkRoute for QueryServerRoutes {
             QueryServerRoutes::GetAuthencatedTxnIDHash => "get_authencated_txnid_hash",
             QueryServerRoutes::GetTransactionHash => "get_transaction_hash",
             QueryServerRoutes::GetTransactionSid => "get_transaction_sid",
+            QueryServerRoutes::GetTxnDetails => "get_txn_details",
             QueryServerRoutes::GetCommits => "get_commits",
         };
         "/".to_owned() + endpoint
@@ -258,16 +439,16 @@ impl NetworkRoute for QueryServerRoutes {
 
 /// Returns the list of assets created by a public key
 pub async fn get_created_assets(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
 ) -> actix_web::Result<web::Json<Vec<DefineAsset>>> {
     // Convert from base64 representation
     let key: XfrPublicKey = XfrPublicKey::noah_from_bytes(
         &b64dec(&*info)
             .c(d!())
-            .map_err(|e| error::ErrorBadRequest(e.to_string()))?,
+            .map_err(|e| invalid_param(e.to_string()))?,
     )
-    .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+    .map_err(|e| invalid_param(e.to_string()))?;
     let server = data.read();
     let assets = server.get_created_assets(&IssuerPublicKey { key });
     Ok(web::Json(assets.unwrap_or_default()))
@@ -276,16 +457,16 @@ pub async fn get_created_assets(
 /// Returns the list of records issued by a public key
 #[allow(clippy::type_complexity)]
 pub async fn get_issued_records(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
 ) -> actix_web::Result<web::Json<Vec<(TxOutput, Option<OwnerMemo>)>>> {
     // Convert from base64 representation
     let key: XfrPublicKey = XfrPublicKey::noah_from_bytes(
         &b64dec(&*info)
             .c(d!())
-            .map_err(|e| error::ErrorBadRequest(e.to_string()))?,
+            .map_err(|e| invalid_param(e.to_string()))?,
     )
-    .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+    .map_err(|e| invalid_param(e.to_string()))?;
     let server = data.read();
     let records = server.get_issued_records(&IssuerPublicKey { key });
     Ok(web::Json(records.unwrap_or_default()))
@@ -294,7 +475,7 @@ pub async fn get_issued_records(
 /// Returns the list of records issued by a token code
 #[allow(clippy::type_complexity)]
 pub async fn get_issued_records_by_code(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
 ) -> actix_web::Result<web::Json<Vec<(TxOutput, Option<OwnerMemo>)>>> {
     let server = data.read();
@@ -304,24 +485,24 @@ pub async fn get_issued_records_by_code(
             if let Some(records) = server.get_issued_records_by_code(&token_code) {
                 Ok(web::Json(records))
             } else {
-                Err(actix_web::error::ErrorNotFound(
+                Err(not_found(
                     "Specified asset definition does not currently exist.",
                 ))
             }
         }
-        Err(e) => Err(actix_web::error::ErrorBadRequest(e.to_string())),
+        Err(e) => Err(invalid_param(e.to_string())),
     }
 }
 
 /// Returns authenticated txn sid and hash
 pub async fn get_authenticated_txnid_hash(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<u64>,
 ) -> actix_web::Result<web::Json<TxnIDHash>> {
     let server = data.read();
     match server.get_authenticated_txnid(TxoSID(*info)) {
         Some(txnid) => Ok(web::Json(txnid)),
-        None => Err(actix_web::error::ErrorNotFound(
+        None => Err(not_found(
             "No authenticated transaction found. Please retry with correct sid.",
         )),
     }
@@ -329,27 +510,45 @@ pub async fn get_authenticated_txnid_hash(
 
 /// Returns txn hash by sid
 pub async fn get_transaction_hash(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<usize>,
 ) -> actix_web::Result<web::Json<String>> {
     let server = data.read();
     match server.get_transaction_hash(TxnSID(*info)) {
         Some(hash) => Ok(web::Json(hash)),
-        None => Err(actix_web::error::ErrorNotFound(
+        None => Err(not_found(
             "No transaction found. Please retry with correct sid.",
         )),
     }
 }
 
+/// Returns a transaction with its block height and time, the txos it created
+/// and spent, and a summary of each operation, for explorers
+pub async fn get_txn_details(
+    data: QueryState,
+    info: web::Path<usize>,
+    web::Query(h): web::Query<HeightParam>,
+) -> actix_web::Result<web::Json<TxnDetails>> {
+    let server = data.read();
+    let view = h.view(&server)?;
+    if !server.txn_visible(&view, TxnSID(*info)) {
+        return Err(not_found("Specified transaction does not exist."));
+    }
+    server
+        .get_txn_details(TxnSID(*info))
+        .map(web::Json)
+        .ok_or_else(|| not_found("Specified transaction does not exist."))
+}
+
 /// Returns txn sid by hash
 pub async fn get_transaction_sid(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
 ) -> actix_web::Result<web::Json<usize>> {
     let server = data.read();
     match server.get_transaction_sid((*info).clone()) {
         Some(sid) => Ok(web::Json(sid.0)),
-        None => Err(actix_web::error::ErrorNotFound(
+        None => Err(not_found(
             "No transaction found. Please retry with correct hash.",
         )),
     }
@@ -357,9 +556,7 @@ pub async fn get_transaction_sid(
 
 /// Returns most recent commit count at server side
 /// Check this number to make sure server is in sync
-pub async fn get_commits(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
-) -> actix_web::Result<web::Json<u64>> {
+pub async fn get_commits(data: QueryState) -> actix_web::Result<web::Json<u64>> {
     let server = data.read();
     Ok(web::Json(server.get_commits()))
 }
@@ -395,20 +592,26 @@ pub struct CoinbaseOperInfo {
     txs: Vec<CoinbaseTxnBody>,
 }
 
-/// paging Query delegators according to `WalletQueryParams`
+/// paging Query delegators according to `WalletQueryParams`,
+/// `?format=csv` exports the page as CSV
 pub async fn get_coinbase_oper_list(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     web::Query(info): web::Query<WalletQueryParams>,
-) -> actix_web::Result<web::Json<CoinbaseOperInfo>> {
+    web::Query(fmt): web::Query<FormatParam>,
+) -> actix_web::Result<HttpResponse> {
     // Convert from base64 representation
-    let key: XfrPublicKey = wallet::public_key_from_base64(&info.address)
+    let key: XfrPublicKey = parse_address(&info.address)
         .c(d!())
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+        .map_err(|e| invalid_param(e.to_string()))?;
 
     let server = data.read();
 
+    let respond = |body: CoinbaseOperInfo| {
+        export::respond(fmt.format, "coinbase_history", body, |b| b.txs)
+    };
+
     if info.page == 0 {
-        return Ok(web::Json(CoinbaseOperInfo {
+        return Ok(respond(CoinbaseOperInfo {
             total_count: 0u64,
             txs: vec![],
         }));
@@ -417,11 +620,11 @@ pub async fn get_coinbase_oper_list(
     let start = (info.page - 1)
         .checked_mul(info.per_page)
         .c(d!())
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
     let end = start
         .checked_add(info.per_page)
         .c(d!())
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
 
     let resp = server
         .get_coinbase_entries(
@@ -431,9 +634,9 @@ pub async fn get_coinbase_oper_list(
             info.order == OrderOption::Desc,
         )
         .c(d!())
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
 
-    Ok(web::Json(CoinbaseOperInfo {
+    Ok(respond(CoinbaseOperInfo {
         total_count: resp.0,
         txs: resp
             .1
@@ -446,30 +649,95 @@ pub async fn get_coinbase_oper_list(
     }))
 }
 
-/// Returns the list of claim transations of a given ledger address
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct StakingEventParams {
+    address: String,
+    #[serde(default)]
+    as_delegator: bool,
+    page: usize,
+    per_page: usize,
+    order: OrderOption,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StakingEventInfo {
+    total_count: u64,
+    events: Vec<StakingEventRecord>,
+}
+
+/// paging query the staking events(slashing, jailing, commission changes)
+/// of a validator, or of a delegator if `as_delegator` is true
+pub async fn get_staking_events(
+    data: QueryState,
+    web::Query(info): web::Query<StakingEventParams>,
+) -> actix_web::Result<web::Json<StakingEventInfo>> {
+    let key: XfrPublicKey = parse_address(&info.address)
+        .c(d!())
+        .map_err(|e| invalid_param(e.to_string()))?;
+
+    if info.page == 0 {
+        return Ok(web::Json(StakingEventInfo {
+            total_count: 0u64,
+            events: vec![],
+        }));
+    }
+
+    let start = (info.page - 1)
+        .checked_mul(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+    let end = start
+        .checked_add(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+
+    let server = data.read();
+    let (total_count, events) = server.get_staking_events(
+        &key,
+        info.as_delegator,
+        start,
+        end,
+        info.order == OrderOption::Desc,
+    );
+
+    Ok(web::Json(StakingEventInfo {
+        total_count,
+        events,
+    }))
+}
+
+/// Returns the list of claim transations of a given ledger address,
+/// `?format=csv` exports the claims of the page as CSV
 pub async fn get_claim_txns(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     web::Query(info): web::Query<WalletQueryParams>,
-) -> actix_web::Result<web::Json<Vec<Option<Transaction>>>> {
+    web::Query(fmt): web::Query<FormatParam>,
+) -> actix_web::Result<HttpResponse> {
     // Convert from base64 representation
-    let key: XfrPublicKey = wallet::public_key_from_base64(&info.address)
+    let key: XfrPublicKey = parse_address(&info.address)
         .c(d!())
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+        .map_err(|e| invalid_param(e.to_string()))?;
+
+    let respond = |body: Vec<Option<Transaction>>| {
+        export::respond(fmt.format, "claim_history", body, ClaimRow::of_txns)
+    };
 
     let server = data.read();
 
     if info.page == 0 {
-        return Ok(web::Json(vec![]));
+        return Ok(respond(vec![]));
     }
 
     let start = (info.page - 1)
         .checked_mul(info.per_page)
         .c(d!())
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
     let end = start
         .checked_add(info.per_page)
         .c(d!())
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
 
     let records = server
         .get_claim_transactions(
@@ -479,47 +747,237 @@ pub async fn get_claim_txns(
             info.order == OrderOption::Desc,
         )
         .c(d!())
-        .map_err(error::ErrorBadRequest)?;
+        .map_err(invalid_param)?;
 
-    Ok(web::Json(records))
+    Ok(respond(records))
 }
 
-/// Returns the list of transations associated with a given ledger address
+/// Returns the list of transations associated with a given ledger address, in ascending order
 pub async fn get_related_txns(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
-) -> actix_web::Result<web::Json<HashSet<TxnSID>>> {
+    web::Query(h): web::Query<HeightParam>,
+    web::Query(filter): web::Query<OpFilter>,
+) -> actix_web::Result<web::Json<BTreeSet<TxnSID>>> {
+    let ops = filter.kinds()?;
     // Convert from base64 representation
     let key: XfrPublicKey = XfrPublicKey::noah_from_bytes(
         &b64dec(&*info)
             .c(d!())
-            .map_err(|e| error::ErrorBadRequest(e.to_string()))?,
+            .map_err(|e| invalid_param(e.to_string()))?,
     )
     .c(d!())
-    .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+    .map_err(|e| invalid_param(e.to_string()))?;
     let server = data.read();
-    let records = server.get_related_transactions(&XfrAddress { key });
-    Ok(web::Json(records.unwrap_or_default()))
+    let view = h.view(&server)?;
+    let records = server
+        .get_related_transactions(&XfrAddress { key })
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|sid| view.latest || server.txn_visible(&view, *sid))
+        .filter(|sid| {
+            ops.as_ref()
+                .map_or(true, |ops| server.txn_has_op(*sid, ops))
+        })
+        .collect();
+    Ok(web::Json(records))
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AddressTimeline {
+    total_count: u64,
+    txns: Vec<TimelineEntry>,
 }
 
-/// Returns the list of transfer transations associated with a given asset
+/// Returns a page of the transactions related to an address,
+/// with the block height, block time and operation kinds of each one,
+/// `?format=csv` exports the page as CSV
+pub async fn get_address_timeline(
+    data: QueryState,
+    web::Query(info): web::Query<WalletQueryParams>,
+    web::Query(filter): web::Query<OpFilter>,
+    web::Query(fmt): web::Query<FormatParam>,
+) -> actix_web::Result<HttpResponse> {
+    let key: XfrPublicKey = parse_address(&info.address)
+        .c(d!())
+        .map_err(|e| invalid_param(e.to_string()))?;
+    let ops = filter.kinds()?;
+    let respond = |body: AddressTimeline| {
+        export::respond(fmt.format, "address_timeline", body, |b| b.txns)
+    };
+
+    if info.page == 0 {
+        return Ok(respond(AddressTimeline {
+            total_count: 0u64,
+            txns: vec![],
+        }));
+    }
+
+    let start = (info.page - 1)
+        .checked_mul(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+    let end = start
+        .checked_add(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+
+    let server = data.read();
+    let (total_count, txns) = server.get_address_timeline(
+        &XfrAddress { key },
+        ops.as_deref(),
+        start,
+        end,
+        info.order == OrderOption::Desc,
+    );
+
+    Ok(respond(AddressTimeline { total_count, txns }))
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct AssetSearchParams {
+    /// prefix of the symbols or the names
+    q: String,
+    page: usize,
+    per_page: usize,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Serialize)]
+pub struct AssetSearchResult {
+    total_count: u64,
+    assets: Vec<AssetHit>,
+}
+
+/// Returns a page of the assets whose symbol or name starts with `q`,
+/// case insensitive, only the assets defined with metadata are found
+pub async fn search_assets(
+    data: QueryState,
+    web::Query(info): web::Query<AssetSearchParams>,
+) -> actix_web::Result<web::Json<AssetSearchResult>> {
+    if info.q.is_empty() {
+        return Err(invalid_param("the query should not be empty"));
+    }
+    if MAX_SEARCH_PER_PAGE < info.per_page {
+        return Err(invalid_param(format!(
+            "at most {MAX_SEARCH_PER_PAGE} assets per page"
+        )));
+    }
+
+    if info.page == 0 {
+        return Ok(web::Json(AssetSearchResult {
+            total_count: 0,
+            assets: vec![],
+        }));
+    }
+
+    let start = (info.page - 1)
+        .checked_mul(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+    let end = start
+        .checked_add(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+
+    let (total_count, assets) = data.read().search_assets(&info.q, start, end);
+
+    Ok(web::Json(AssetSearchResult {
+        total_count,
+        assets,
+    }))
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct AssetListParams {
+    page: usize,
+    per_page: usize,
+    /// base64 public key of the issuer
+    issuer: Option<String>,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Serialize)]
+pub struct AssetList {
+    total_count: u64,
+    assets: Vec<AssetSummary>,
+}
+
+/// Returns a page of all the defined assets in the order of definition,
+/// only those of `issuer` if it is given
+pub async fn list_assets(
+    data: QueryState,
+    web::Query(info): web::Query<AssetListParams>,
+) -> actix_web::Result<web::Json<AssetList>> {
+    if MAX_SEARCH_PER_PAGE < info.per_page {
+        return Err(invalid_param(format!(
+            "at most {MAX_SEARCH_PER_PAGE} assets per page"
+        )));
+    }
+    let issuer = info
+        .issuer
+        .as_ref()
+        .map(|pk| parse_address(pk))
+        .transpose()
+        .c(d!())
+        .map_err(|e| invalid_param(e.to_string()))?;
+
+    if info.page == 0 {
+        return Ok(web::Json(AssetList {
+            total_count: 0,
+            assets: vec![],
+        }));
+    }
+
+    let start = (info.page - 1)
+        .checked_mul(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+    let end = start
+        .checked_add(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+
+    let (total_count, assets) = data.read().list_assets(issuer.as_ref(), start, end);
+
+    Ok(web::Json(AssetList {
+        total_count,
+        assets,
+    }))
+}
+
+/// Returns the list of transfer transations associated with a given asset, in ascending order
 pub async fn get_related_xfrs(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
     info: web::Path<String>,
-) -> actix_web::Result<web::Json<HashSet<TxnSID>>> {
+    web::Query(h): web::Query<HeightParam>,
+    web::Query(filter): web::Query<OpFilter>,
+) -> actix_web::Result<web::Json<BTreeSet<TxnSID>>> {
+    let ops = filter.kinds()?;
     let server = data.read();
+    let view = h.view(&server)?;
     if let Ok(token_code) = AssetTypeCode::new_from_base64(&info) {
         if let Some(records) = server.get_related_transfers(&token_code) {
-            Ok(web::Json(records))
+            Ok(web::Json(
+                records
+                    .into_iter()
+                    .filter(|sid| view.latest || server.txn_visible(&view, *sid))
+                    .filter(|sid| {
+                        ops.as_ref()
+                            .map_or(true, |ops| server.txn_has_op(*sid, ops))
+                    })
+                    .collect(),
+            ))
         } else {
-            Err(actix_web::error::ErrorNotFound(
+            Err(not_found(
                 "Specified asset definition does not currently exist.",
             ))
         }
     } else {
-        Err(actix_web::error::ErrorBadRequest(
-            "Invalid asset definition encoding.",
-        ))
+        Err(invalid_param("Invalid asset definition encoding."))
     }
 }
 
@@ -527,7 +985,7 @@ pub async fn get_related_xfrs(
 #[allow(clippy::unnecessary_wraps)]
 
 pub async fn get_circulating_supply(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
 ) -> actix_web::Result<web::Json<BTreeMap<&'static str, f64>>, actix_web::error::Error> {
     let l = data.read();
     let fra = FRA as f64;
@@ -547,11 +1005,31 @@ pub async fn get_circulating_supply(
 }
 
 /// return
+/// The fee rules of the network
+pub async fn get_fee_params(
+    data: QueryState,
+) -> actix_web::Result<web::Json<FeeParams>> {
+    let qs = data.read();
+    Ok(web::Json(
+        qs.ledger_cloned.get_staking().params().fee_params(),
+    ))
+}
+
+/// return
+/// The parameters of the network in effect,
+/// those set on chain or the defaults
+pub async fn get_chain_params(
+    data: QueryState,
+) -> actix_web::Result<web::Json<ChainParamValues>> {
+    let qs = data.read();
+    Ok(web::Json(qs.ledger_cloned.get_staking().params().values()))
+}
+
 /// global_circulating_supply
 /// global_adjusted_circulating_supply
 /// global_total_supply
 pub async fn get_total_supply(
-    data: web::Data<Arc<RwLock<QueryServer>>>,
+    data: QueryState,
 ) -> actix_web::Result<web::Json<BTreeMap<&'static str, f64>>, actix_web::error::Error> {
     let l = data.read();
     let burn_pubkey = XfrPublicKey::from_noah(&BLACK_HOLE_PUBKEY);
@@ -591,6 +1069,276 @@ pub async fn get_checkpoint(
     Ok(web::Json(CFG.checkpoint.clone()))
 }
 
+/// Returns the current RSS, the configured ceiling and the eviction statistics of each cache
+#[inline(always)]
+pub async fn get_mem_pressure(
+) -> actix_web::Result<web::Json<MemGuardStat>, actix_web::error::Error> {
+    Ok(web::Json(MEM_GUARD.stat()))
+}
+
+fn health_stat(data: &web::Data<Arc<RwLock<QueryServer>>>) -> HealthStat {
+    let server = data.read();
+    let indexed = server.get_commits();
+    HealthStat::collect(server.get_ledger_commits().unwrap_or(indexed), indexed)
+}
+
+/// Liveness of the node, 503 if the ABCI connection is dead
+pub async fn healthz(data: web::Data<Arc<RwLock<QueryServer>>>) -> HttpResponse {
+    let stat = health_stat(&data);
+    if stat.abci_alive {
+        HttpResponse::Ok().json(stat)
+    } else {
+        HttpResponse::ServiceUnavailable().json(stat)
+    }
+}
+
+/// Readiness of the node, 503 if the node is syncing
+/// or the query server has not caught up with the ledger
+pub async fn readyz(data: web::Data<Arc<RwLock<QueryServer>>>) -> HttpResponse {
+    let stat = health_stat(&data);
+    if stat.ready {
+        HttpResponse::Ok().json(stat)
+    } else {
+        HttpResponse::ServiceUnavailable().json(stat)
+    }
+}
+
+/// Run the invariant check of the query server's clone of the ledger on demand
+pub async fn check_invariants(
+    req: HttpRequest,
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+) -> actix_web::Result<web::Json<InvariantReport>, actix_web::error::Error> {
+    authorize(&req)?;
+    let qs = Arc::clone(&data);
+    web::block(move || Ok::<_, ()>(invariants::check_committed(&qs)))
+        .await
+        .map(web::Json)
+        .map_err(|e| internal(e.to_string()))
+}
+
+/// Log filter directives, e.g. `info,abciapp=debug`
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogLevel {
+    directives: String,
+}
+
+/// The log filter in effect
+pub async fn get_log_level(req: HttpRequest) -> actix_web::Result<web::Json<LogLevel>> {
+    authorize(&req)?;
+    logging::level()
+        .map(|directives| web::Json(LogLevel { directives }))
+        .ok_or_else(|| unavailable("logging not initialized"))
+}
+
+/// Replace the log filter, until the next restart
+pub async fn set_log_level(
+    req: HttpRequest,
+    web::Json(body): web::Json<LogLevel>,
+) -> actix_web::Result<web::Json<LogLevel>> {
+    authorize(&req)?;
+    logging::set_level(&body.directives)
+        .map_err(|e| invalid_param(e.generate_log(None)))?;
+    info!(target: "abciapp", "log filter set to `{}`", body.directives);
+    Ok(web::Json(body))
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Serialize)]
+pub struct SnapshotResult {
+    path: String,
+    block_commit_count: u64,
+    tendermint_height: u64,
+}
+
+// only one export at a time
+static EXPORTING_SNAPSHOT: AtomicBool = AtomicBool::new(false);
+
+/// Export a ledger snapshot to the `<ledger_dir>_snapshots` directory,
+/// the ledger is only locked while its checkpoint is taken
+pub async fn trigger_snapshot(
+    req: HttpRequest,
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+) -> actix_web::Result<web::Json<SnapshotResult>> {
+    authorize(&req)?;
+    if EXPORTING_SNAPSHOT.swap(true, Ordering::AcqRel) {
+        return Err(unavailable("a snapshot is being exported"));
+    }
+
+    let ledger = Arc::clone(&data.read().ledger);
+    let res = web::block(move || {
+        let dir = format!("{}_snapshots", CFG.ledger_dir.trim_end_matches('/'));
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let cp = {
+            let ledger = ledger.read();
+            let staging =
+                format!("{}/ledger-{}.staging", dir, ledger.get_block_commit_count());
+            ledger.checkpoint_snapshot(&staging)
+        }
+        .map_err(|e| e.generate_log(None))?;
+        let path = format!("{}/ledger-{}.snap", dir, cp.block_commit_count());
+        cp.export(&path, CFG.zstd_level)
+            .map(|h| SnapshotResult {
+                path,
+                block_commit_count: h.block_commit_count,
+                tendermint_height: h.tendermint_height,
+            })
+            .map_err(|e| e.generate_log(None))
+    })
+    .await;
+    EXPORTING_SNAPSHOT.store(false, Ordering::Release);
+
+    res.map(web::Json).map_err(|e| internal(e.to_string()))
+}
+
+/// State of the background backups, see `--backup-interval`
+pub async fn get_backup_status(
+    req: HttpRequest,
+) -> actix_web::Result<web::Json<BackupStatus>> {
+    authorize(&req)?;
+    Ok(web::Json(backup::status()))
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct WebhookRequest {
+    /// http(s) URL receiving the notifications
+    url: String,
+    /// base64 encoded public keys
+    addresses: Vec<String>,
+}
+
+/// Register a watch list, the only response carrying the secret of its signatures
+async fn subscribe_webhook(
+    req: HttpRequest,
+    data: QueryState,
+    web::Json(info): web::Json<WebhookRequest>,
+) -> actix_web::Result<web::Json<Subscription>> {
+    authorize(&req)?;
+    data.read()
+        .webhooks
+        .subscribe(&info.url, &info.addresses)
+        .map(web::Json)
+        .map_err(|e| invalid_param(e.generate_log(None)))
+}
+
+/// A registered watch list, without its secret
+async fn get_webhook(
+    req: HttpRequest,
+    data: QueryState,
+    id: web::Path<String>,
+) -> actix_web::Result<web::Json<Subscription>> {
+    authorize(&req)?;
+    data.read()
+        .webhooks
+        .get(id.as_str())
+        .map(web::Json)
+        .ok_or_else(|| not_found("Specified subscription does not exist."))
+}
+
+/// Remove a watch list
+async fn delete_webhook(
+    req: HttpRequest,
+    data: QueryState,
+    id: web::Path<String>,
+) -> actix_web::Result<web::Json<bool>> {
+    authorize(&req)?;
+    match data.read().webhooks.unsubscribe(id.as_str()) {
+        Ok(true) => Ok(web::Json(true)),
+        Ok(false) => Err(not_found("Specified subscription does not exist.")),
+        Err(e) => Err(internal(e.generate_log(None))),
+    }
+}
+
+/// Number of watch lists and the delivery statistics of their notifications
+async fn get_webhooks_stat(
+    req: HttpRequest,
+    data: QueryState,
+) -> actix_web::Result<web::Json<WebhookStat>> {
+    authorize(&req)?;
+    Ok(web::Json(data.read().webhooks.stat()))
+}
+
+/// Decrypt the traced records within a range of TXOs by the tracer key
+/// of an auditor, see `audit`
+async fn audit_trace(
+    data: QueryState,
+    web::Json(info): web::Json<AuditRequest>,
+) -> actix_web::Result<web::Json<Vec<AuditedTxo>>> {
+    if !audit::enabled() {
+        return Err(unavailable("the audit endpoint is disabled"));
+    }
+    let ledger = Arc::clone(&data.read().ledger_cloned);
+    audit::trace(&ledger, &info.dec_key, info.from, info.to)
+        .map(web::Json)
+        .map_err(|e| invalid_param(e.generate_log(None)))
+}
+
+/// Set or revoke the view key of an address, signed by the address
+async fn register_view_key(
+    data: QueryState,
+    web::Json(reg): web::Json<ViewKeyRegistration>,
+) -> actix_web::Result<web::Json<bool>> {
+    data.read()
+        .view_keys
+        .register(&reg)
+        .map(|_| web::Json(true))
+        .map_err(|e| invalid_param(e.generate_log(None)))
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct ViewQueryParams {
+    address: String,
+    page: usize,
+    per_page: usize,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Serialize)]
+pub struct BalanceHistory {
+    total_count: u64,
+    txns: Vec<BalanceHistoryEntry>,
+}
+
+/// Returns a page of the balance changes of an address, the oldest first,
+/// authenticated by the view key of the address
+async fn get_balance_history(
+    req: HttpRequest,
+    data: QueryState,
+    web::Query(info): web::Query<ViewQueryParams>,
+) -> actix_web::Result<web::Json<BalanceHistory>> {
+    let key: XfrPublicKey = parse_address(&info.address)
+        .c(d!())
+        .map_err(|e| invalid_param(e.to_string()))?;
+    let server = data.read();
+    server.view_keys.authorize(&req, &key)?;
+
+    if info.page == 0 {
+        return Ok(web::Json(BalanceHistory {
+            total_count: 0,
+            txns: vec![],
+        }));
+    }
+    if MAX_SEARCH_PER_PAGE < info.per_page {
+        return Err(invalid_param(format!(
+            "at most {MAX_SEARCH_PER_PAGE} transactions per page"
+        )));
+    }
+    let start = (info.page - 1)
+        .checked_mul(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+    let end = start
+        .checked_add(info.per_page)
+        .c(d!())
+        .map_err(invalid_param)?;
+
+    let (total_count, txns) =
+        server.get_balance_history(&XfrAddress { key }, start, end);
+    Ok(web::Json(BalanceHistory { total_count, txns }))
+}
+
 /// Structures exposed to the outside world
 pub struct QueryApi;
 
@@ -602,12 +1350,32 @@ impl QueryApi {
         let _ = actix_rt::System::new("findora API");
 
         let mut hdr = HttpServer::new(move || {
+            let limit = PayloadLimit::new(CFG.limits.query_payload_limit);
             App::new()
+                .wrap(limit)
+                .wrap(Auth)
+                .wrap(RequestTracing)
                 .wrap(middleware::Logger::default())
-                .wrap(Cors::permissive().supports_credentials())
+                .wrap(cors())
                 .data(Arc::clone(&server))
+                .app_data(web::PathConfig::default().error_handler(extractor_error))
+                .app_data(web::QueryConfig::default().error_handler(extractor_error))
+                .app_data(
+                    web::JsonConfig::default()
+                        .limit(limit.largest())
+                        .error_handler(extractor_error),
+                )
                 .route("/ping", web::get().to(ping))
+                .route("/healthz", web::get().to(healthz))
+                .route("/admin/check_invariants", web::get().to(check_invariants))
+                .route("/admin/snapshot", web::post().to(trigger_snapshot))
+                .route("/admin/backup", web::get().to(get_backup_status))
+                .route("/admin/log_level", web::get().to(get_log_level))
+                .route("/admin/log_level", web::post().to(set_log_level))
+                .route("/readyz", web::get().to(readyz))
                 .route("/version", web::get().to(version))
+                .route("/openapi.json", web::get().to(get_openapi))
+                .route("/begin_read_session", web::get().to(begin_read_session))
                 .service(
                     web::resource("get_total_supply")
                         .route(web::get().to(get_total_supply)),
@@ -616,6 +1384,10 @@ impl QueryApi {
                     web::resource("circulating_supply")
                         .route(web::get().to(get_circulating_supply)),
                 )
+                .service(
+                    web::resource("fee_params").route(web::get().to(get_fee_params)),
+                )
+                .service(web::resource("params").route(web::get().to(get_chain_params)))
                 .route(
                     &QueryServerRoutes::GetAddress.with_arg_template("txo_sid"),
                     web::get().to(get_address),
@@ -628,6 +1400,29 @@ impl QueryApi {
                     &QueryServerRoutes::GetOwnedAbars.with_arg_template("commitment"),
                     web::get().to(get_owned_abar),
                 )
+                .route("/abar/{commitment}", web::get().to(get_abar_by_commitment))
+                .route("/anchors/{hash}", web::get().to(get_anchors))
+                .route("/convert_address/{addr}", web::get().to(convert_address))
+                .service(
+                    web::resource("/webhooks").route(web::post().to(subscribe_webhook)),
+                )
+                .service(
+                    web::resource("/webhooks/{id}")
+                        .route(web::get().to(get_webhook))
+                        .route(web::delete().to(delete_webhook)),
+                )
+                .route("/webhooks_stat", web::get().to(get_webhooks_stat))
+                .service(
+                    web::resource("/audit/trace").route(web::post().to(audit_trace)),
+                )
+                .service(
+                    web::resource("/view_keys").route(web::post().to(register_view_key)),
+                )
+                .route("/view/balance_history", web::get().to(get_balance_history))
+                .service(
+                    web::resource("/owned_abars")
+                        .route(web::post().to(get_abars_by_commitments)),
+                )
                 .route(
                     &QueryServerRoutes::GetOwnerMemo.with_arg_template("txo_sid"),
                     web::get().to(get_owner_memo),
@@ -658,6 +1453,16 @@ impl QueryApi {
                         .with_arg_template("null_hash"),
                     web::get().to(check_nullifier_hash),
                 )
+                .service(
+                    web::resource("/abar_tree").route(web::get().to(query_abar_tree)),
+                )
+                .service(
+                    web::resource("/abar_roots").route(web::get().to(query_abar_roots)),
+                )
+                .route(
+                    &QueryServerRoutes::GetAbarTransferMaterial.route(),
+                    web::get().to(get_abar_transfer_material),
+                )
                 .route(
                     &QueryServerRoutes::GetMaxATxoSid.route(),
                     web::get().to(get_max_atxo_sid),
@@ -708,6 +1513,10 @@ impl QueryApi {
                     &QueryServerRoutes::GetTransactionSid.with_arg_template("txn_hash"),
                     web::get().to(get_transaction_sid),
                 )
+                .route(
+                    &QueryServerRoutes::GetTxnDetails.with_arg_template("txn_sid"),
+                    web::get().to(get_txn_details),
+                )
                 .route(
                     &QueryServerRoutes::GetCommits.route(),
                     web::get().to(get_commits),
@@ -732,6 +1541,14 @@ impl QueryApi {
                     &ApiRoutes::AssetToken.with_arg_template("code"),
                     web::get().to(query_asset),
                 )
+                .route(
+                    &ApiRoutes::AssetPolicy.with_arg_template("code"),
+                    web::get().to(query_asset_policy),
+                )
+                .route(
+                    &ApiRoutes::TrackedTxos.with_arg_template("code"),
+                    web::get().to(query_tracked_txos),
+                )
                 .route(
                     &ApiRoutes::GetDerivedAssetCode.with_arg_template("code"),
                     web::get().to(get_derived_asset_code),
@@ -748,6 +1565,19 @@ impl QueryApi {
                     &ApiRoutes::TxnSidLight.with_arg_template("sid"),
                     web::get().to(query_txn_light),
                 )
+                .route(
+                    &ApiRoutes::TxnProof.with_arg_template("sid"),
+                    web::get().to(query_txn_proof),
+                )
+                .route(
+                    &ApiRoutes::UtxoProof.with_arg_template("sid"),
+                    web::get().to(query_utxo_proof),
+                )
+                .route(
+                    &ApiRoutes::Block.with_arg_template("height"),
+                    web::get().to(query_block),
+                )
+                .service(web::resource("/blocks").route(web::get().to(query_blocks)))
                 .route(
                     &ApiRoutes::GlobalStateVersion.with_arg_template("version"),
                     web::get().to(query_global_state_version),
@@ -768,6 +1598,14 @@ impl QueryApi {
                     &ApiRoutes::DelegationInfo.with_arg_template("XfrPublicKey"),
                     web::get().to(query_delegation_info),
                 )
+                .route(
+                    &ApiRoutes::Unbonding.with_arg_template("XfrPublicKey"),
+                    web::get().to(query_unbonding),
+                )
+                .route(
+                    &ApiRoutes::ScheduledTransfers.with_arg_template("XfrPublicKey"),
+                    web::get().to(query_scheduled_transfers),
+                )
                 .route(
                     &ApiRoutes::DelegatorList.with_arg_template("NodeAddress"),
                     web::get().to(query_delegator_list),
@@ -788,17 +1626,52 @@ impl QueryApi {
                     &ApiRoutes::ValidatorDetail.with_arg_template("NodeAddress"),
                     web::get().to(query_validator_detail),
                 )
+                .route(
+                    &ApiRoutes::ValidatorHistory.with_arg_template("NodeAddress"),
+                    web::get().to(query_validator_history),
+                )
                 .service(
                     web::resource("/display_checkpoint")
                         .route(web::get().to(get_checkpoint)),
                 )
+                .service(
+                    web::resource("/utxo_checksum")
+                        .route(web::get().to(query_utxo_checksum_range)),
+                )
+                .route(
+                    "/utxo_checksum/{version}",
+                    web::get().to(query_utxo_checksum),
+                )
+                .service(
+                    web::resource("/state_commitment_history")
+                        .route(web::get().to(query_state_commitment_history)),
+                )
+                .service(
+                    web::resource("/state_hash").route(web::get().to(query_state_hash)),
+                )
+                .service(
+                    web::resource("/address_timeline")
+                        .route(web::get().to(get_address_timeline)),
+                )
+                .service(
+                    web::resource("/assets/search").route(web::get().to(search_assets)),
+                )
+                .service(web::resource("/assets").route(web::get().to(list_assets)))
+                .service(
+                    web::resource("/staking_events")
+                        .route(web::get().to(get_staking_events)),
+                )
+                .service(
+                    web::resource("/mem_pressure")
+                        .route(web::get().to(get_mem_pressure)),
+                )
         });
 
         for (host, port) in addrs.iter() {
             hdr = hdr.bind(&format!("{host}:{port}")).c(d!())?
         }
 
-        hdr.run();
+        shutdown::register_server(hdr.run());
 
         info!("Query server started");
 
@@ -0,0 +1,1195 @@
+//!
+//! # OpenAPI document of the query server
+//!
+//! Served at `/openapi.json`, remember to update it when adding a route.
+//!
+
+use {
+    super::{ledger_api::ApiRoutes, view_key::VIEW_KEY_HEADER, QueryServerRoutes},
+    actix_web::HttpResponse,
+    finutils::api::{
+        openapi::{schema::*, with_staking_schemas, OpenApi, Operation},
+        NetworkRoute,
+    },
+    lazy_static::lazy_static,
+    serde_json::Value,
+};
+
+lazy_static! {
+    static ref SPEC: String = spec().build().to_string();
+}
+
+/// Returns the OpenAPI document of the query server
+pub async fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(SPEC.as_str())
+}
+
+fn pagination(op: Operation) -> Operation {
+    op.query("address", string(), true)
+        .query("page", integer(), true)
+        .query("per_page", integer(), true)
+        .query(
+            "order",
+            serde_json::json!({"type": "string", "enum": ["desc", "asc"]}),
+            true,
+        )
+}
+
+// see `OpKind::parse_list`
+fn op_filter(op: Operation) -> Operation {
+    op.query("op", string(), false)
+}
+
+// see `export::FormatParam`
+fn exportable(op: Operation) -> Operation {
+    op.query(
+        "format",
+        serde_json::json!({"type": "string", "enum": ["json", "csv"]}),
+        false,
+    )
+}
+
+// see `LockFilter`
+fn lock_filter(op: Operation) -> Operation {
+    op.query("locked", boolean(), false)
+}
+
+// see `QueryServer::read_view`
+fn pinned(op: Operation) -> Operation {
+    op.query("height", integer(), false)
+}
+
+fn range(op: Operation) -> Operation {
+    op.query("from", integer(), true)
+        .query("to", integer(), true)
+}
+
+fn op_kind() -> Value {
+    serde_json::json!({"type": "string", "enum": [
+        "transfer",
+        "issue",
+        "define_asset",
+        "update_memo",
+        "update_staker",
+        "delegation",
+        "undelegation",
+        "redelegation",
+        "claim",
+        "update_validator",
+        "governance",
+        "fra_distribution",
+        "mint",
+        "convert_account",
+        "bar_to_abar",
+        "abar_to_bar",
+        "anon_transfer",
+        "replace_staker",
+        "update_transfer_policy",
+        "update_params",
+        "swap",
+        "schedule_transfer",
+        "cancel_schedule",
+        "anchor_data",
+    ]})
+}
+
+fn hash() -> Value {
+    opaque("HashOf<Option<StateCommitmentData>>")
+}
+
+#[allow(missing_docs)]
+pub fn spec() -> OpenApi {
+    use {ApiRoutes as A, QueryServerRoutes as Q};
+
+    let owner_memo = || nullable(opaque("OwnerMemo"));
+    let issued = || array(tuple(vec![opaque("TxOutput"), owner_memo()]));
+
+    with_staking_schemas(OpenApi::new(
+        "Findora query server",
+        env!("CARGO_PKG_VERSION"),
+    ))
+    .error_schema("ApiError")
+    .schema(
+        "ApiError",
+        object(&[
+            (
+                "code",
+                serde_json::json!({"type": "string", "enum": [
+                    "INVALID_PARAM",
+                    "NOT_FOUND",
+                    "TXN_REJECTED",
+                    "UNAVAILABLE",
+                    "STALE_READ",
+                    "UNAUTHORIZED",
+                    "PAYLOAD_TOO_LARGE",
+                    "INTERNAL",
+                ]}),
+            ),
+            ("message", string()),
+        ]),
+    )
+    .schema(
+        "StakingEventRecord",
+        object(&[
+            ("height", integer()),
+            (
+                "event",
+                tagged(&[
+                    (
+                        "Slashed",
+                        object(&[
+                            ("validator", string()),
+                            ("delegator", nullable(string())),
+                            ("amount", integer()),
+                        ]),
+                    ),
+                    ("Jailed", object(&[("validator", string())])),
+                    ("Unjailed", object(&[("validator", string())])),
+                    (
+                        "CommissionChanged",
+                        object(&[
+                            ("validator", string()),
+                            ("old", fixed_array(integer(), 2)),
+                            ("new", fixed_array(integer(), 2)),
+                        ]),
+                    ),
+                ]),
+            ),
+        ]),
+    )
+    .schema("OpKind", op_kind())
+    .schema(
+        "TimelineEntry",
+        object(&[
+            ("txn_sid", integer()),
+            ("height", nullable(integer())),
+            ("timestamp", nullable(integer())),
+            ("ops", array(reference("OpKind"))),
+        ]),
+    )
+    .schema(
+        "ValidatorEpoch",
+        object(&[
+            ("start_height", integer()),
+            ("end_height", integer()),
+            ("signed_cnt", integer()),
+            ("missed_cnt", integer()),
+            ("uptime", fixed_array(integer(), 2)),
+            ("rewards", integer()),
+        ]),
+    )
+    .schema(
+        "UnbondingList",
+        object(&[
+            ("current_height", integer()),
+            ("total", integer()),
+            (
+                "entries",
+                array(object(&[
+                    ("delegator", string()),
+                    ("validator", string()),
+                    ("amount", integer()),
+                    ("maturity_height", integer()),
+                    ("remaining_blocks", integer()),
+                    ("matured", boolean()),
+                ])),
+            ),
+            ("cache_age", integer()),
+        ]),
+    )
+    .schema(
+        "ScheduleList",
+        object(&[
+            ("current_height", integer()),
+            (
+                "entries",
+                array(object(&[
+                    ("id", integer()),
+                    ("owner", string()),
+                    ("receiver", string()),
+                    ("amount", integer()),
+                    ("interval", integer()),
+                    ("next_height", integer()),
+                    ("end_height", integer()),
+                    ("balance", integer()),
+                    ("canceled", boolean()),
+                    ("remaining_payments", integer()),
+                ])),
+            ),
+        ]),
+    )
+    .schema(
+        "AssetMetadata",
+        object(&[
+            ("decimals", integer()),
+            ("symbol", string()),
+            ("name", string()),
+            ("url", string()),
+            ("content_hash", string()),
+        ]),
+    )
+    .schema(
+        "AssetHit",
+        object(&[("code", string()), ("metadata", reference("AssetMetadata"))]),
+    )
+    .schema(
+        "AssetSummary",
+        object(&[
+            ("code", string()),
+            ("issuer", string()),
+            ("memo", string()),
+            ("decimals", integer()),
+            ("max_units", nullable(integer())),
+            ("transferable", boolean()),
+            ("metadata", nullable(reference("AssetMetadata"))),
+        ]),
+    )
+    .schema(
+        "OutputSummary",
+        object(&[
+            ("txo_sid", nullable(integer())),
+            ("owner", string()),
+            ("amount", nullable(integer())),
+            ("asset_type", nullable(string())),
+        ]),
+    )
+    .schema(
+        "SwapLeg",
+        object(&[
+            ("party", string()),
+            ("asset_type", string()),
+            ("amount", integer()),
+        ]),
+    )
+    .schema(
+        "OpSummary",
+        tagged(&[
+            (
+                "transfer",
+                object(&[
+                    ("spent", array(integer())),
+                    ("outputs", array(reference("OutputSummary"))),
+                ]),
+            ),
+            (
+                "issue",
+                object(&[
+                    ("code", string()),
+                    ("seq_num", integer()),
+                    ("outputs", array(reference("OutputSummary"))),
+                ]),
+            ),
+            (
+                "define_asset",
+                object(&[("code", string()), ("issuer", string()), ("memo", string())]),
+            ),
+            (
+                "update_memo",
+                object(&[("code", string()), ("memo", string())]),
+            ),
+            (
+                "mint",
+                object(&[("outputs", array(reference("OutputSummary")))]),
+            ),
+            (
+                "swap",
+                object(&[
+                    ("legs", array(reference("SwapLeg"))),
+                    ("spent", array(integer())),
+                    ("outputs", array(reference("OutputSummary"))),
+                ]),
+            ),
+            ("other", reference("OpKind")),
+        ]),
+    )
+    .schema(
+        "TxnDetails",
+        object(&[
+            ("txn_sid", integer()),
+            ("txn_hash", nullable(string())),
+            ("block_index", integer()),
+            ("height", nullable(integer())),
+            ("timestamp", nullable(integer())),
+            ("created_txos", array(integer())),
+            ("spent_txos", array(integer())),
+            ("ops", array(reference("OpSummary"))),
+            ("txn", opaque("Transaction")),
+        ]),
+    )
+    .schema(
+        "AbarEntry",
+        object(&[
+            ("atxo_sid", integer()),
+            ("commitment", string()),
+            ("memo", nullable(opaque("AxfrOwnerMemo"))),
+        ]),
+    )
+    .schema(
+        "Subscription",
+        object(&[
+            ("id", string()),
+            ("url", string()),
+            ("addresses", array(string())),
+            // only in the response of the registration
+            ("secret", string()),
+        ]),
+    )
+    .schema(
+        "BalanceHistoryEntry",
+        object(&[
+            ("txn_sid", integer()),
+            ("height", nullable(integer())),
+            ("timestamp", nullable(integer())),
+            (
+                "changes",
+                array(object(&[
+                    ("asset_type", string()),
+                    ("received", integer()),
+                    ("sent", integer()),
+                ])),
+            ),
+            ("confidential_cnt", integer()),
+        ]),
+    )
+    .schema(
+        "AuditedTxo",
+        object(&[
+            ("txo_sid", integer()),
+            ("txn_sid", integer()),
+            ("owner", string()),
+            ("amount", integer()),
+            ("asset_type", string()),
+            ("spent", boolean()),
+        ]),
+    )
+    .schema(
+        "AnchorRecord",
+        object(&[
+            ("txn_sid", integer()),
+            ("txn_hash", string()),
+            ("signer", string()),
+            ("label", object(&[("kind", string()), ("name", string())])),
+            ("height", integer()),
+            ("timestamp", integer()),
+        ]),
+    )
+    .schema(
+        "AbarRoot",
+        object(&[
+            ("version", integer()),
+            ("root", opaque("BN254Scalar")),
+            ("size", integer()),
+        ]),
+    )
+    .schema(
+        "AbarTreeInfo",
+        object(&[
+            ("block_commit_count", integer()),
+            ("version", integer()),
+            ("root", opaque("BN254Scalar")),
+            ("size", integer()),
+            ("min_version", integer()),
+        ]),
+    )
+    .schema(
+        "BlockMeta",
+        object(&[
+            ("height", integer()),
+            ("timestamp", integer()),
+            ("proposer", string()),
+            ("block_index", nullable(integer())),
+            ("txns", array(integer())),
+        ]),
+    )
+    .schema(
+        "ReadView",
+        object(&[
+            ("height", integer()),
+            ("txo_count", integer()),
+            ("latest", boolean()),
+        ]),
+    )
+    .schema(
+        "HealthStat",
+        object(&[
+            ("abci_alive", boolean()),
+            ("secs_since_last_abci_req", nullable(integer())),
+            ("last_committed_height", integer()),
+            ("secs_since_last_block", nullable(integer())),
+            ("last_block_age_secs", nullable(integer())),
+            ("syncing", boolean()),
+            ("ledger_commit_count", integer()),
+            ("indexed_commit_count", integer()),
+            ("indexes_caught_up", boolean()),
+            ("ready", boolean()),
+        ]),
+    )
+    .schema(
+        "StateDomainHashes",
+        object(&[
+            ("version", integer()),
+            ("asset_types", opaque("BitDigest")),
+            ("issuance_nums", opaque("BitDigest")),
+            ("utxos", opaque("BitDigest")),
+            ("staking", opaque("HashOf<Staking>")),
+        ]),
+    )
+    .schema(
+        "StateHash",
+        object(&[
+            ("block_commit_count", integer()),
+            ("block_height", integer()),
+            ("commitment", hash()),
+            ("utxo_bitmap", opaque("BitDigest")),
+            ("block_merkle", string()),
+            ("transaction_merkle", string()),
+            ("domains", nullable(reference("StateDomainHashes"))),
+        ]),
+    )
+    .schema(
+        "StateCommitmentHeader",
+        object(&[
+            ("block_commit_count", integer()),
+            ("commitment", hash()),
+            ("data", opaque("StateCommitmentData")),
+        ]),
+    )
+    .schema(
+        "FeeParams",
+        object(&[
+            ("tx_fee_min", integer()),
+            ("bar_to_abar_tx_fee_min", integer()),
+            ("fee_destination", string()),
+        ]),
+    )
+    .schema(
+        "ChainParamValues",
+        object(&[
+            ("max_txn_bytes", integer()),
+            ("max_txn_ops", integer()),
+            ("max_block_txns", integer()),
+            ("tx_fee_min", integer()),
+            ("bar_to_abar_tx_fee_min", integer()),
+            ("unbond_block_cnt", integer()),
+            ("feature_heights", map(integer())),
+        ]),
+    )
+    .schema(
+        "TxnProof",
+        object(&[
+            ("txn_sid", integer()),
+            ("merkle_id", integer()),
+            ("proof", opaque("ProofOf<(TxnSID, Transaction)>")),
+            ("root", string()),
+            ("state_commitment", hash()),
+            ("block_commit_count", integer()),
+            ("block_height", integer()),
+        ]),
+    )
+    .schema(
+        "UtxoProof",
+        object(&[
+            ("sid", integer()),
+            ("utxo", nullable(opaque("TxOutput"))),
+            ("proof", opaque("SparseMerkleProof")),
+            ("root", opaque("BitDigest")),
+            ("state_commitment", hash()),
+            ("block_commit_count", integer()),
+            ("block_height", integer()),
+        ]),
+    )
+    .get("/ping", Operation::text("Liveness check"))
+    .get("/version", Operation::text("Build version of the node"))
+    .get(
+        "/begin_read_session",
+        Operation::new("Pin a view to the latest block", reference("ReadView")),
+    )
+    .get(
+        "/healthz",
+        Operation::new(
+            "Liveness, 503 if the ABCI connection is dead",
+            reference("HealthStat"),
+        ),
+    )
+    .get(
+        "/readyz",
+        Operation::new(
+            "Readiness, 503 if the node is syncing or the indexes are behind",
+            reference("HealthStat"),
+        ),
+    )
+    .get(
+        "/get_total_supply",
+        Operation::new("Total supply of FRA", map(number())),
+    )
+    .get(
+        "/circulating_supply",
+        Operation::new("Circulating supply of FRA", map(number())),
+    )
+    .get(
+        "/fee_params",
+        Operation::new("Fee rules of the network", reference("FeeParams")),
+    )
+    .get(
+        "/params",
+        Operation::new(
+            "Parameters of the network in effect",
+            reference("ChainParamValues"),
+        ),
+    )
+    .get(
+        &Q::GetAddress.with_arg_template("txo_sid"),
+        Operation::text("Owner address of a utxo").path("txo_sid", integer()),
+    )
+    .get(
+        &Q::GetOwnedUtxos.with_arg_template("address"),
+        lock_filter(pinned(Operation::new(
+            "Utxo SIDs owned by an address",
+            array(integer()),
+        ))),
+    )
+    .get(
+        &Q::GetOwnedAbars.with_arg_template("commitment"),
+        Operation::new("ATxoSID of an anonymous record", nullable(integer())),
+    )
+    .get(
+        "/abar/{commitment}",
+        Operation::new(
+            "Anonymous record of a commitment with its owner memo",
+            reference("AbarEntry"),
+        ),
+    )
+    .get(
+        "/anchors/{hash}",
+        Operation::new(
+            "Transactions anchoring a document hash, with their block time",
+            array(reference("AnchorRecord")),
+        ),
+    )
+    .get(
+        "/convert_address/{addr}",
+        Operation::new(
+            "An address in bech32 and in base64, given in either of them",
+            object(&[("bech32", string()), ("base64", string())]),
+        ),
+    )
+    .get(
+        "/abar_tree",
+        Operation::new(
+            "Version, root and size of the abar merkle tree",
+            reference("AbarTreeInfo"),
+        ),
+    )
+    .get(
+        "/abar_roots",
+        range(Operation::new(
+            "Roots of the abar merkle tree after a range of tendermint blocks",
+            array(tuple(vec![integer(), reference("AbarRoot")])),
+        )),
+    )
+    .post(
+        "/owned_abars",
+        Operation::new(
+            "A page of the anonymous records of a list of commitments",
+            object(&[
+                ("total_count", integer()),
+                ("abars", array(nullable(reference("AbarEntry")))),
+            ]),
+        )
+        .body(object(&[
+            ("commitments", array(string())),
+            ("page", integer()),
+            ("per_page", integer()),
+        ])),
+    )
+    .get(
+        &Q::GetOwnerMemo.with_arg_template("txo_sid"),
+        Operation::new("Owner memo of a utxo", owner_memo()).path("txo_sid", integer()),
+    )
+    .get(
+        &Q::GetOwnerMemoBatch.with_arg_template("txo_sid_list"),
+        Operation::new(
+            "Owner memos of comma separated utxo SIDs",
+            array(owner_memo()),
+        ),
+    )
+    .get(
+        &Q::GetAbarCommitment.with_arg_template("atxo_sid"),
+        Operation::new("Commitment of an anonymous record", nullable(string()))
+            .path("atxo_sid", integer()),
+    )
+    .get(
+        &Q::GetAbarMemo.with_arg_template("atxo_sid"),
+        Operation::new(
+            "Owner memo of an anonymous record",
+            nullable(opaque("AxfrOwnerMemo")),
+        )
+        .path("atxo_sid", integer()),
+    )
+    .get(
+        &Q::GetAbarMemos.route(),
+        Operation::new(
+            "Owner memos of anonymous records in [start, end]",
+            array(tuple(vec![integer(), opaque("AxfrOwnerMemo")])),
+        )
+        .query("start", integer(), true)
+        .query("end", integer(), true),
+    )
+    .get(
+        &Q::GetAbarProof.with_arg_template("atxo_sid"),
+        Operation::new(
+            "Merkle proof of an anonymous record",
+            nullable(opaque("MTLeafInfo")),
+        )
+        .path("atxo_sid", integer()),
+    )
+    .get(
+        &Q::CheckNullifierHash.with_arg_template("null_hash"),
+        Operation::new("Whether a nullifier has been spent", nullable(boolean())),
+    )
+    .get(
+        &Q::GetAbarTransferMaterial.route(),
+        Operation::new(
+            "Materials to build an anonymous transfer",
+            opaque("AbarTransferMaterial"),
+        )
+        .query("atxo_sids", string(), true)
+        .query("nullifiers", string(), false),
+    )
+    .get(
+        &Q::GetMaxATxoSid.route(),
+        Operation::new("The latest ATxoSID", nullable(integer())),
+    )
+    .get(
+        &Q::GetMaxATxoSidAtHeight.with_arg_template("height"),
+        Operation::new("The latest ATxoSID at a height", nullable(integer()))
+            .path("height", integer()),
+    )
+    .get(
+        &Q::GetRelatedTxns.with_arg_template("address"),
+        op_filter(pinned(Operation::new(
+            "Transactions related to an address",
+            array(integer()),
+        ))),
+    )
+    .get(
+        "/claim_history",
+        exportable(pagination(Operation::new(
+            "Claim transactions of an address, or its claims as CSV",
+            array(nullable(opaque("Transaction"))),
+        ))),
+    )
+    .get(
+        "/coinbase_history",
+        exportable(pagination(Operation::new(
+            "Coinbase operations of an address, or a CSV of them",
+            object(&[
+                ("total_count", integer()),
+                ("txs", array(opaque("CoinbaseTxnBody"))),
+            ]),
+        ))),
+    )
+    .get(
+        "/address_timeline",
+        exportable(op_filter(pagination(Operation::new(
+            "Transactions of an address in chronological order, or a CSV of them",
+            object(&[
+                ("total_count", integer()),
+                ("txns", array(reference("TimelineEntry"))),
+            ]),
+        )))),
+    )
+    .get(
+        "/assets/search",
+        Operation::new(
+            "Assets whose symbol or name starts with `q`",
+            object(&[
+                ("total_count", integer()),
+                ("assets", array(reference("AssetHit"))),
+            ]),
+        )
+        .query("q", string(), true)
+        .query("page", integer(), true)
+        .query("per_page", integer(), true),
+    )
+    .get(
+        "/assets",
+        Operation::new(
+            "All the assets in the order of definition",
+            object(&[
+                ("total_count", integer()),
+                ("assets", array(reference("AssetSummary"))),
+            ]),
+        )
+        .query("page", integer(), true)
+        .query("per_page", integer(), true)
+        .query("issuer", string(), false),
+    )
+    .get(
+        "/staking_events",
+        pagination(Operation::new(
+            "Staking events of a validator or a delegator",
+            object(&[
+                ("total_count", integer()),
+                ("events", array(reference("StakingEventRecord"))),
+            ]),
+        ))
+        .query("as_delegator", boolean(), false),
+    )
+    .get(
+        &Q::GetRelatedXfrs.with_arg_template("asset_token"),
+        op_filter(pinned(Operation::new(
+            "Transfers of an asset",
+            array(integer()),
+        ))),
+    )
+    .get(
+        &Q::GetCreatedAssets.with_arg_template("address"),
+        Operation::new("Assets created by an address", array(opaque("DefineAsset"))),
+    )
+    .get(
+        &Q::GetIssuedRecords.with_arg_template("address"),
+        Operation::new("Records issued by an address", issued()),
+    )
+    .get(
+        &Q::GetIssuedRecordsByCode.with_arg_template("asset_token"),
+        Operation::new("Records issued of an asset", issued()),
+    )
+    .get(
+        &Q::GetAuthencatedTxnIDHash.with_arg_template("txo_sid"),
+        Operation::new(
+            "Transaction containing a utxo",
+            tuple(vec![integer(), string()]),
+        )
+        .path("txo_sid", integer()),
+    )
+    .get(
+        &Q::GetTransactionHash.with_arg_template("txn_sid"),
+        Operation::new("Hash of a transaction", string()).path("txn_sid", integer()),
+    )
+    .get(
+        &Q::GetTransactionSid.with_arg_template("txn_hash"),
+        Operation::new("SID of a transaction", integer()),
+    )
+    .get(
+        &Q::GetTxnDetails.with_arg_template("txn_sid"),
+        pinned(
+            Operation::new(
+                "Transaction with its block, txos and operation summaries",
+                reference("TxnDetails"),
+            )
+            .path("txn_sid", integer()),
+        ),
+    )
+    .get(
+        &Q::GetCommits.route(),
+        Operation::new("Block commit count", integer()),
+    )
+    .get(
+        &A::UtxoSid.with_arg_template("sid"),
+        pinned(
+            Operation::new("Authenticated utxo", opaque("AuthenticatedUtxo"))
+                .path("sid", integer()),
+        ),
+    )
+    .get(
+        &A::UtxoSidLight.with_arg_template("sid"),
+        pinned(
+            Operation::new("Utxo without proofs", opaque("UnAuthenticatedUtxo"))
+                .path("sid", integer()),
+        ),
+    )
+    .get(
+        &A::UtxoSidList.with_arg_template("sid_list"),
+        pinned(Operation::new(
+            "Authenticated utxos of comma separated SIDs",
+            array(nullable(opaque("AuthenticatedUtxo"))),
+        )),
+    )
+    .get(
+        &A::AssetIssuanceNum.with_arg_template("code"),
+        Operation::new("Issuance number of an asset", integer()),
+    )
+    .get(
+        &A::AssetToken.with_arg_template("code"),
+        Operation::new("Definition of an asset", opaque("AssetType")),
+    )
+    .get(
+        &A::AssetPolicy.with_arg_template("code"),
+        Operation::new(
+            "Transfer policy of an asset",
+            nullable(object(&[("whitelist", array(string()))])),
+        ),
+    )
+    .get(
+        &A::TrackedTxos.with_arg_template("code"),
+        Operation::new(
+            "Traceable TXOs of an asset and whether they are spent, \
+             requires the signature of the issuer on the asset code",
+            map(tuple(vec![opaque("UnAuthenticatedUtxo"), boolean()])),
+        )
+        .query("sig", string(), true),
+    )
+    .get(
+        &A::GetDerivedAssetCode.with_arg_template("code"),
+        Operation::text("Derived asset code"),
+    )
+    .get(
+        &A::GlobalState.route(),
+        Operation::new(
+            "Latest state commitment, block commit count and signature",
+            tuple(vec![hash(), integer(), string()]),
+        ),
+    )
+    .get(
+        &A::TxnSid.with_arg_template("sid"),
+        pinned(
+            Operation::text("Authenticated transaction in JSON").path("sid", integer()),
+        ),
+    )
+    .get(
+        &A::TxnSidLight.with_arg_template("sid"),
+        pinned(
+            Operation::text("Transaction without proofs in JSON").path("sid", integer()),
+        ),
+    )
+    .get(
+        &A::TxnProof.with_arg_template("sid"),
+        pinned(
+            Operation::new("Inclusion proof of a transaction", reference("TxnProof"))
+                .path("sid", integer()),
+        ),
+    )
+    .get(
+        &A::UtxoProof.with_arg_template("sid"),
+        pinned(
+            Operation::new(
+                "Proof that a utxo is unspent, or that it is not",
+                reference("UtxoProof"),
+            )
+            .path("sid", integer()),
+        ),
+    )
+    .get(
+        &A::GlobalStateVersion.with_arg_template("version"),
+        Operation::new("State commitment of a version", nullable(hash()))
+            .path("version", integer()),
+    )
+    .get(
+        "/state_hash",
+        Operation::new(
+            "Sub-hashes of the latest state commitment",
+            reference("StateHash"),
+        ),
+    )
+    .get(
+        "/state_commitment_history",
+        range(Operation::new(
+            "State commitments of a range of blocks",
+            array(reference("StateCommitmentHeader")),
+        )),
+    )
+    .get(
+        &A::Block.with_arg_template("height"),
+        Operation::new(
+            "Transactions and header info of a tendermint block",
+            reference("BlockMeta"),
+        )
+        .path("height", integer()),
+    )
+    .get(
+        "/blocks",
+        range(Operation::new(
+            "Tendermint blocks in a range of heights",
+            array(reference("BlockMeta")),
+        )),
+    )
+    .get(
+        "/utxo_checksum/{version}",
+        Operation::new("Utxo bitmap checksum of a version", opaque("BitDigest"))
+            .path("version", integer()),
+    )
+    .get(
+        "/utxo_checksum",
+        range(Operation::new(
+            "Utxo bitmap checksums of a range of versions",
+            array(tuple(vec![integer(), opaque("BitDigest")])),
+        )),
+    )
+    .get(
+        &A::OwnedUtxos.with_arg_template("owner"),
+        lock_filter(pinned(Operation::new(
+            "Utxos owned by an address",
+            map(tuple(vec![opaque("Utxo"), owner_memo()])),
+        ))),
+    )
+    .get(
+        &A::OwnedAbars.with_arg_template("owner"),
+        Operation::new(
+            "Anonymous record of a commitment",
+            nullable(tuple(vec![integer(), opaque("ABARData")])),
+        ),
+    )
+    .get(
+        &A::ValidatorList.route(),
+        Operation::new("Current validators", reference("ValidatorList")),
+    )
+    .get(
+        &A::DelegationInfo.with_arg_template("XfrPublicKey"),
+        Operation::new("Delegation of an address", reference("DelegationInfo")),
+    )
+    .get(
+        &A::Unbonding.with_arg_template("XfrPublicKey"),
+        Operation::new(
+            "Pending unbondings of an address",
+            reference("UnbondingList"),
+        ),
+    )
+    .get(
+        &A::ScheduledTransfers.with_arg_template("XfrPublicKey"),
+        Operation::new(
+            "Active scheduled transfers paid from or to an address",
+            reference("ScheduleList"),
+        ),
+    )
+    .get(
+        &A::DelegatorList.with_arg_template("NodeAddress"),
+        Operation::new("Delegators of a validator", reference("DelegatorList")),
+    )
+    .get(
+        "/delegator_list",
+        pagination(Operation::new(
+            "Delegators of a validator",
+            reference("DelegatorList"),
+        )),
+    )
+    .get(
+        "/delegation_rewards",
+        Operation::new(
+            "Delegation rewards of an address",
+            array(opaque("DelegationRwdDetail")),
+        )
+        .query("address", string(), true)
+        .query("height", integer(), false),
+    )
+    .get(
+        "/validator_delegation",
+        Operation::new(
+            "Delegation history of a validator",
+            array(object(&[
+                ("return_rate", fixed_array(integer(), 2)),
+                ("self_delegation", integer()),
+                ("delegated", integer()),
+            ])),
+        )
+        .query("address", string(), true)
+        .query("epoch_size", integer(), false)
+        .query("epoch_cnt", integer(), false),
+    )
+    .get(
+        &A::ValidatorDetail.with_arg_template("NodeAddress"),
+        Operation::new("Detail of a validator", reference("ValidatorDetail")),
+    )
+    .get(
+        &A::ValidatorHistory.with_arg_template("NodeAddress"),
+        Operation::new(
+            "Signing records and rewards of a validator per epoch",
+            array(reference("ValidatorEpoch")),
+        )
+        .query("from_height", integer(), false)
+        .query("to_height", integer(), false),
+    )
+    .get(
+        "/display_checkpoint",
+        Operation::new("Checkpoints of the chain", opaque("CheckPointConfig")),
+    )
+    .get(
+        "/mem_pressure",
+        Operation::new(
+            "Memory usage and cache evictions",
+            object(&[
+                ("ceiling", nullable(integer())),
+                ("rss", integer()),
+                ("caches", array(opaque("CacheStat"))),
+            ]),
+        ),
+    )
+    .get(
+        "/admin/check_invariants",
+        Operation::new(
+            "Check the ledger invariants, requires the admin role",
+            object(&[
+                ("block_commit_count", integer()),
+                ("checked_at", integer()),
+                ("elapsed_ms", integer()),
+                ("violations", array(opaque("Divergence"))),
+            ]),
+        ),
+    )
+    .post(
+        "/admin/snapshot",
+        Operation::new(
+            "Export a ledger snapshot next to the ledger directory, \
+             requires the admin role",
+            object(&[
+                ("path", string()),
+                ("block_commit_count", integer()),
+                ("tendermint_height", integer()),
+            ]),
+        ),
+    )
+    .get(
+        "/admin/backup",
+        Operation::new(
+            "State of the background backups, requires the admin role",
+            object(&[
+                ("interval", integer()),
+                ("target", string()),
+                ("running", boolean()),
+                ("last_block_commit_count", nullable(integer())),
+                ("last_at", nullable(integer())),
+                ("last_elapsed_ms", nullable(integer())),
+                ("last_error", nullable(string())),
+                ("backups", array(string())),
+            ]),
+        ),
+    )
+    .get(
+        "/admin/log_level",
+        Operation::new(
+            "The log filter directives in effect, requires the admin role",
+            object(&[("directives", string())]),
+        ),
+    )
+    .post(
+        "/admin/log_level",
+        Operation::new(
+            "Replace the log filter directives until the next restart, \
+             e.g. `info,abciapp=debug`, requires the admin role",
+            object(&[("directives", string())]),
+        )
+        .body(object(&[("directives", string())])),
+    )
+    .post(
+        "/webhooks",
+        Operation::new(
+            "Watch addresses, their transactions are POSTed to `url`, \
+             requires the admin role",
+            reference("Subscription"),
+        )
+        .body(object(&[("url", string()), ("addresses", array(string()))])),
+    )
+    .get(
+        "/webhooks/{id}",
+        Operation::new(
+            "A watch list without its secret, requires the admin role",
+            reference("Subscription"),
+        ),
+    )
+    .delete(
+        "/webhooks/{id}",
+        Operation::new("Remove a watch list, requires the admin role", boolean()),
+    )
+    .get(
+        "/webhooks_stat",
+        Operation::new(
+            "Deliveries of the webhook notifications, \
+             requires the admin role",
+            object(&[
+                ("subscriptions", integer()),
+                ("queue_depth", integer()),
+                ("delivered_cnt", integer()),
+                ("retried_cnt", integer()),
+                ("dropped_cnt", integer()),
+            ]),
+        ),
+    )
+    .post(
+        "/view_keys",
+        Operation::new(
+            "Set or revoke the view key of an address, signed by the address",
+            boolean(),
+        )
+        .body(object(&[
+            (
+                "body",
+                object(&[
+                    ("address", string()),
+                    ("view_key", nullable(string())),
+                    ("timestamp", integer()),
+                ]),
+            ),
+            ("signature", string()),
+        ])),
+    )
+    .get(
+        "/view/balance_history",
+        Operation::new(
+            "Balance changes of an address per transaction, the oldest first, \
+             authenticated by the view key of the address",
+            object(&[
+                ("total_count", integer()),
+                ("txns", array(reference("BalanceHistoryEntry"))),
+            ]),
+        )
+        .query("address", string(), true)
+        .query("page", integer(), true)
+        .query("per_page", integer(), true)
+        .header(
+            VIEW_KEY_HEADER,
+            opaque("base64 encoded JSON of a ViewKeyAuth"),
+            true,
+        ),
+    )
+    .post(
+        "/audit/trace",
+        Operation::new(
+            "Amounts and asset types of the TXOs within `[from, to]` traced by \
+             the tracer decryption key, disabled unless `enable_audit_api`",
+            array(reference("AuditedTxo")),
+        )
+        .body(object(&[
+            ("dec_key", opaque("AssetTracerDecKeys")),
+            ("from", integer()),
+            ("to", integer()),
+        ])),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn all_routes_documented() {
+        let doc = spec();
+        for r in [
+            QueryServerRoutes::GetAbarMemos.route(),
+            QueryServerRoutes::GetOwnerMemo.with_arg_template("txo_sid"),
+            QueryServerRoutes::GetTxnDetails.with_arg_template("txn_sid"),
+            ApiRoutes::TxnProof.with_arg_template("sid"),
+            ApiRoutes::UtxoProof.with_arg_template("sid"),
+            ApiRoutes::Block.with_arg_template("height"),
+            ApiRoutes::ValidatorDetail.with_arg_template("NodeAddress"),
+            ApiRoutes::ValidatorHistory.with_arg_template("NodeAddress"),
+            ApiRoutes::Unbonding.with_arg_template("XfrPublicKey"),
+            ApiRoutes::ScheduledTransfers.with_arg_template("XfrPublicKey"),
+        ] {
+            assert!(doc.contains(&r), "{}", r);
+        }
+
+        let v: Value = serde_json::from_str(&SPEC).unwrap();
+        assert!(v["paths"]["/txn_proof/{sid}"]["get"]["responses"]["200"].is_object());
+        assert!(v["paths"]["/owned_abars"]["post"]["requestBody"].is_object());
+        assert!(v["paths"]["/webhooks/{id}"]["delete"].is_object());
+        assert!(v["paths"]["/audit/trace"]["post"]["requestBody"].is_object());
+        assert!(v["paths"]["/admin/log_level"]["get"].is_object());
+        assert!(v["paths"]["/admin/log_level"]["post"]["requestBody"].is_object());
+        assert_eq!(
+            v["paths"]["/view/balance_history"]["get"]["parameters"][3]["in"],
+            "header"
+        );
+    }
+}
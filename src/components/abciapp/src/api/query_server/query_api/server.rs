@@ -3,25 +3,49 @@
 //!
 
 use {
-    globutils::HashOf,
+    super::{
+        asset_index::{AssetHit, AssetIndex, AssetSummary},
+        ledger_api::UnbondingList,
+        staking_view::StakingCache,
+        view_key::ViewKeys,
+        webhook::Webhooks,
+    },
+    crate::api::mem_guard::{ResponseCache, MEM_GUARD, PRIORITY_RENDERED},
+    finutils::api::{DelegationInfo, ValidatorList},
+    globutils::{wallet, HashOf},
     lazy_static::lazy_static,
     ledger::{
         data_model::{
-            ATxoSID, AssetTypeCode, DefineAsset, IssuerPublicKey, StateCommitmentData,
-            Transaction, TxOutput, TxnIDHash, TxnSID, TxoSID, XfrAddress,
+            ATxoSID, AssetTypeCode, DefineAsset, IssuerPublicKey, Operation,
+            StateCommitmentData, SwapLeg, Transaction, TransferAsset, TxOutput,
+            TxnIDHash, TxnSID, TxoRef, TxoSID, XfrAddress,
         },
         staking::{ops::mint_fra::MintEntry, BlockHeight},
-        store::LedgerState,
+        store::{
+            api_cache::{
+                AbarRoot, AnchorRecord, ApiCache, BlockMeta, OpKind, StakingEventRecord,
+                ValidatorEpochRecord,
+            },
+            LedgerState, VERSION_WINDOW,
+        },
     },
     parking_lot::{Condvar, Mutex, RwLock},
     ruc::*,
-    std::{collections::HashSet, sync::Arc},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        sync::Arc,
+    },
     zei::{
+        noah_algebra::bn254::BN254Scalar,
         noah_api::anon_xfr::structs::{AxfrOwnerMemo, Commitment, MTLeafInfo},
-        OwnerMemo,
+        OwnerMemo, XfrPublicKey,
     },
 };
 
+// Bound of the in-memory cache, see `ResponseCache::insert`
+const TXN_LIGHT_CACHE_CAP: usize = 10_000;
+
 lazy_static! {
     /// the query_server will be notified every time
     /// a block is added to the ledgerState to update the data
@@ -29,19 +53,205 @@ lazy_static! {
         Arc::new((Mutex::new(false), Condvar::new()));
 }
 
+/// Data of an abar needed to spend it
+#[allow(missing_docs)]
+#[derive(Deserialize, Serialize)]
+pub struct AbarSpendInfo {
+    pub atxo_sid: ATxoSID,
+    pub commitment: Option<Commitment>,
+    pub memo: Option<AxfrOwnerMemo>,
+    pub proof: Option<MTLeafInfo>,
+}
+
+/// Everything needed to build an anonymous transfer,
+/// all fields are read from the same committed state.
+#[allow(missing_docs)]
+#[derive(Deserialize, Serialize)]
+pub struct AbarTransferMaterial {
+    pub block_commit_count: u64,
+    pub root: Option<BN254Scalar>,
+    pub root_version: Option<u64>,
+    pub abars: Vec<AbarSpendInfo>,
+    /// `(nullifier, is spent)`, `None` means the nullifier is invalid
+    pub nullifiers: Vec<(String, Option<bool>)>,
+}
+
+/// The committed abar merkle tree,
+/// the proofs should be built against a version not below `min_version`
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct AbarTreeInfo {
+    pub block_commit_count: u64,
+    pub version: u64,
+    pub root: BN254Scalar,
+    /// number of leaves
+    pub size: u64,
+    pub min_version: u64,
+}
+
+/// A view of the query server pinned to a block commit count,
+/// clients pass its `height` to later queries to get consistent reads
+/// across multiple requests.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct ReadView {
+    /// block commit count the view is pinned to
+    pub height: u64,
+    /// txos whose SIDs are below this number exist at `height`
+    pub txo_count: u64,
+    /// `height` is the latest block seen by the query server
+    pub latest: bool,
+}
+
+/// A transaction in the activity timeline of an address
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct TimelineEntry {
+    pub txn_sid: TxnSID,
+    /// tendermint height, `None` for the transactions indexed by old versions
+    pub height: Option<BlockHeight>,
+    /// header time of the block in unix seconds, `None` as `height`
+    pub timestamp: Option<i64>,
+    pub ops: Vec<OpKind>,
+}
+
+/// Non-confidential amounts of an asset type received and sent by an address
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct BalanceChange {
+    pub asset_type: AssetTypeCode,
+    pub received: u64,
+    pub sent: u64,
+}
+
+/// The balance changes of an address in a transaction
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct BalanceHistoryEntry {
+    pub txn_sid: TxnSID,
+    /// `None` as in `TimelineEntry`
+    pub height: Option<BlockHeight>,
+    pub timestamp: Option<i64>,
+    pub changes: Vec<BalanceChange>,
+    /// records of the address received or sent with a confidential
+    /// amount or asset type, which are not counted in `changes`
+    pub confidential_cnt: u64,
+}
+
+/// An output in the summary of an operation
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OutputSummary {
+    pub txo_sid: Option<TxoSID>,
+    pub owner: XfrPublicKey,
+    /// `None` if the amount is confidential
+    pub amount: Option<u64>,
+    /// `None` if the asset type is confidential
+    pub asset_type: Option<AssetTypeCode>,
+}
+
+/// An operation of a transaction, summarized for explorers
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpSummary {
+    Transfer {
+        spent: Vec<TxoSID>,
+        outputs: Vec<OutputSummary>,
+    },
+    Issue {
+        code: AssetTypeCode,
+        seq_num: u64,
+        outputs: Vec<OutputSummary>,
+    },
+    DefineAsset {
+        code: AssetTypeCode,
+        issuer: XfrPublicKey,
+        memo: String,
+    },
+    UpdateMemo {
+        code: AssetTypeCode,
+        memo: String,
+    },
+    Mint {
+        outputs: Vec<OutputSummary>,
+    },
+    Swap {
+        legs: [SwapLeg; 2],
+        spent: Vec<TxoSID>,
+        outputs: Vec<OutputSummary>,
+    },
+    /// operations without a detailed summary
+    Other(OpKind),
+}
+
+/// A committed transaction, with where and when it was committed
+/// and the txos it created and spent
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TxnDetails {
+    pub txn_sid: TxnSID,
+    pub txn_hash: Option<String>,
+    /// index of the block in the ledger
+    pub block_index: u64,
+    /// tendermint height, `None` for the transactions indexed by old versions
+    pub height: Option<BlockHeight>,
+    /// header time of the block in unix seconds, `None` as `height`
+    pub timestamp: Option<i64>,
+    /// in the order of creation
+    pub created_txos: Vec<TxoSID>,
+    /// including the txos created and spent by this transaction
+    pub spent_txos: Vec<TxoSID>,
+    pub ops: Vec<OpSummary>,
+    pub txn: Transaction,
+}
+
+/// An anonymous record found by its commitment
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct AbarEntry {
+    pub atxo_sid: ATxoSID,
+    /// base58 encoded
+    pub commitment: String,
+    /// to open the record, `None` if the api cache is disabled
+    pub memo: Option<AxfrOwnerMemo>,
+}
+
 /// A data container for API
 pub struct QueryServer {
+    /// the ledger being written, only for the admin endpoints
     pub(crate) ledger: Arc<RwLock<LedgerState>>,
-    pub(crate) ledger_cloned: LedgerState,
+    /// the clone taken at the last `update`, shared with the handlers
+    /// which run outside the lock of the query server
+    pub(crate) ledger_cloned: Arc<LedgerState>,
+    /// rendered responses of `query_txn_light`, evicted under memory pressure
+    pub(crate) txn_light_cache: Arc<ResponseCache<usize, String>>,
+    /// watch lists notified by webhooks
+    pub(crate) webhooks: Webhooks,
+    /// read-only keys of the addresses
+    pub(crate) view_keys: ViewKeys,
+    asset_index: AssetIndex,
+    staking_cache: StakingCache,
 }
 
 impl QueryServer {
     /// create query server
     pub fn new(ledger: Arc<RwLock<LedgerState>>) -> QueryServer {
-        let ledger_cloned = ledger.read().clone();
+        let ledger_cloned = Arc::new(ledger.read().clone());
+        let txn_light_cache = Arc::new(ResponseCache::new(
+            "txn_light",
+            PRIORITY_RENDERED,
+            TXN_LIGHT_CACHE_CAP,
+        ));
+        MEM_GUARD.register(txn_light_cache.clone());
+        let asset_index = AssetIndex::build(&ledger_cloned);
         QueryServer {
             ledger,
             ledger_cloned,
+            webhooks: Webhooks::start(),
+            view_keys: ViewKeys::load(),
+            asset_index,
+            staking_cache: StakingCache::default(),
+            txn_light_cache,
         }
     }
 
@@ -51,12 +261,7 @@ impl QueryServer {
         &self,
         issuer: &IssuerPublicKey,
     ) -> Option<Vec<(TxOutput, Option<OwnerMemo>)>> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
-            .issuances
-            .get(issuer)
+        self.api_cache()?.issuances.get(issuer)
     }
 
     /// Returns the set of records issued by a certain token code.
@@ -65,12 +270,7 @@ impl QueryServer {
         &self,
         code: &AssetTypeCode,
     ) -> Option<Vec<(TxOutput, Option<OwnerMemo>)>> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
-            .token_code_issuances
-            .get(code)
+        self.api_cache()?.token_code_issuances.get(code)
     }
 
     /// return `DefineAsset` according to `IssuerPublicKey`
@@ -79,10 +279,7 @@ impl QueryServer {
         &self,
         issuer: &IssuerPublicKey,
     ) -> Option<Vec<DefineAsset>> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
+        self.api_cache()?
             .created_assets
             .get(issuer)
             .map(|d| d.iter().map(|(_, v)| v).collect())
@@ -97,10 +294,8 @@ impl QueryServer {
         order_desc: bool,
     ) -> Result<(u64, Vec<(u64, MintEntry)>)> {
         if let Some(hist) = self
-            .ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
+            .api_cache()
+            .c(d!("the api cache is unavailable"))?
             .coinbase_oper_hist
             .get(address)
         {
@@ -142,6 +337,270 @@ impl QueryServer {
         Ok((0, vec![]))
     }
 
+    /// Returns the total count and a page(`[start, end)`) of the staking events
+    /// related to a validator, or to a delegator if `as_delegator` is true
+    pub fn get_staking_events(
+        &self,
+        pk: &XfrPublicKey,
+        as_delegator: bool,
+        start: usize,
+        end: usize,
+        order_desc: bool,
+    ) -> (u64, Vec<StakingEventRecord>) {
+        let api_cache = match self.api_cache() {
+            Some(c) => c,
+            None => return (0, vec![]),
+        };
+        let events = if as_delegator {
+            api_cache.staking_delegator_events.get(pk)
+        } else {
+            api_cache.staking_validator_events.get(pk)
+        }
+        .unwrap_or_default();
+
+        let len = events.len();
+        let page = if order_desc {
+            events
+                .into_iter()
+                .rev()
+                .skip(start)
+                .take(end - start)
+                .collect()
+        } else {
+            events.into_iter().skip(start).take(end - start).collect()
+        };
+
+        (len as u64, page)
+    }
+
+    /// The signing records and rewards of a validator in the epochs
+    /// `[first, last]`, epochs without any record are skipped
+    pub fn get_validator_history(
+        &self,
+        pk: &XfrPublicKey,
+        first: u64,
+        last: u64,
+    ) -> Vec<(u64, ValidatorEpochRecord)> {
+        self.api_cache()
+            .and_then(|c| c.staking_validator_hist.get(pk))
+            .map(|hist| {
+                (first..=last)
+                    .filter_map(|e| hist.get(&e).map(|r| (e, r)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// A committed transaction with its block, its txos and a summary of its operations
+    pub fn get_txn_details(&self, sid: TxnSID) -> Option<TxnDetails> {
+        let ledger = &self.ledger_cloned;
+        let [block_idx, _] = ledger.tx_to_block_location.get(&sid)?;
+        let mut ftx = ledger.get_transaction_light(sid).ok()?;
+        ftx.set_txo_id();
+        let api_cache = ledger.api_cache.as_ref()?;
+        let meta = api_cache.txn_meta.get(&sid);
+
+        // txo SIDs are allocated in the order `TxnEffect::compute_effect` creates
+        // the txos, a relative input is an offset back from the last one created
+        let mut ids = ftx.txo_ids.iter().copied();
+        let mut created = vec![];
+        let mut spent_txos = vec![];
+        let mut ops = vec![];
+        for op in ftx.txn.body.operations.iter() {
+            let summary = match op {
+                Operation::TransferAsset(t) => {
+                    let spent = resolve_inputs(t, &created);
+                    spent_txos.extend(spent.iter().copied());
+                    OpSummary::Transfer {
+                        spent,
+                        outputs: summarize_outputs(
+                            t.body.outputs.iter(),
+                            &mut ids,
+                            &mut created,
+                        ),
+                    }
+                }
+                Operation::SwapAsset(s) => {
+                    let spent = resolve_inputs(&s.transfer, &created);
+                    spent_txos.extend(spent.iter().copied());
+                    OpSummary::Swap {
+                        legs: s.legs.clone(),
+                        spent,
+                        outputs: summarize_outputs(
+                            s.transfer.body.outputs.iter(),
+                            &mut ids,
+                            &mut created,
+                        ),
+                    }
+                }
+                Operation::IssueAsset(i) => OpSummary::Issue {
+                    code: i.body.code,
+                    seq_num: i.body.seq_num,
+                    outputs: summarize_outputs(
+                        i.body.records.iter().map(|(o, _)| o),
+                        &mut ids,
+                        &mut created,
+                    ),
+                },
+                Operation::MintFra(m) => OpSummary::Mint {
+                    outputs: summarize_outputs(
+                        m.entries.iter().map(|e| &e.utxo),
+                        &mut ids,
+                        &mut created,
+                    ),
+                },
+                Operation::DefineAsset(d) => OpSummary::DefineAsset {
+                    code: d.body.asset.code,
+                    issuer: d.body.asset.issuer.key,
+                    memo: d.body.asset.memo.0.clone(),
+                },
+                Operation::UpdateMemo(u) => OpSummary::UpdateMemo {
+                    code: u.body.asset_type,
+                    memo: u.body.new_memo.0.clone(),
+                },
+                Operation::AbarToBar(_) => {
+                    created.extend(ids.next());
+                    OpSummary::Other(OpKind::from(op))
+                }
+                _ => OpSummary::Other(OpKind::from(op)),
+            };
+            ops.push(summary);
+        }
+
+        Some(TxnDetails {
+            txn_sid: sid,
+            txn_hash: api_cache.txn_sid_to_hash.get(&sid),
+            block_index: block_idx as u64,
+            height: meta.as_ref().map(|m| m.height),
+            timestamp: meta.as_ref().map(|m| m.timestamp),
+            created_txos: created,
+            spent_txos,
+            ops,
+            txn: ftx.txn,
+        })
+    }
+
+    /// Operation kinds of a transaction
+    pub fn get_txn_op_kinds(&self, sid: TxnSID) -> Vec<OpKind> {
+        if let Some(meta) = self.api_cache().and_then(|c| c.txn_meta.get(&sid)) {
+            meta.ops
+        } else {
+            // indexed by old versions
+            self.ledger_cloned
+                .get_transaction_light(sid)
+                .map(|tx| OpKind::of_txn(&tx.txn))
+                .unwrap_or_default()
+        }
+    }
+
+    /// Whether the transaction contains any operation in `kinds`
+    #[inline(always)]
+    pub fn txn_has_op(&self, sid: TxnSID, kinds: &[OpKind]) -> bool {
+        self.get_txn_op_kinds(sid).iter().any(|k| kinds.contains(k))
+    }
+
+    /// Returns the total count and a page(`[start, end)`) of the transactions
+    /// related to an address, in chronological order or the reverse,
+    /// only those containing any operation in `ops` are returned if it is not none
+    pub fn get_address_timeline(
+        &self,
+        address: &XfrAddress,
+        ops: Option<&[OpKind]>,
+        start: usize,
+        end: usize,
+        order_desc: bool,
+    ) -> (u64, Vec<TimelineEntry>) {
+        let api_cache = match self.api_cache() {
+            Some(c) => c,
+            None => return (0, vec![]),
+        };
+        let txns = if let Some(txns) = api_cache.related_transactions.get(address) {
+            txns
+        } else {
+            return (0, vec![]);
+        };
+
+        // txn SIDs are allocated in the order of commitment
+        let sids = txns
+            .iter()
+            .map(|(sid, _)| sid)
+            .filter(|sid| ops.map_or(true, |ops| self.txn_has_op(*sid, ops)))
+            .collect::<Vec<_>>();
+        let len = sids.len();
+        let page: Vec<TxnSID> = if order_desc {
+            sids.into_iter()
+                .rev()
+                .skip(start)
+                .take(end - start)
+                .collect()
+        } else {
+            sids.into_iter().skip(start).take(end - start).collect()
+        };
+
+        let entries = page
+            .into_iter()
+            .map(|sid| {
+                if let Some(meta) = api_cache.txn_meta.get(&sid) {
+                    TimelineEntry {
+                        txn_sid: sid,
+                        height: Some(meta.height),
+                        timestamp: Some(meta.timestamp),
+                        ops: meta.ops,
+                    }
+                } else {
+                    TimelineEntry {
+                        txn_sid: sid,
+                        height: None,
+                        timestamp: None,
+                        ops: self.get_txn_op_kinds(sid),
+                    }
+                }
+            })
+            .collect();
+
+        (len as u64, entries)
+    }
+
+    /// Returns the total number of the transactions related to an address
+    /// and the balance changes of the address in a page of them, the oldest first
+    pub fn get_balance_history(
+        &self,
+        address: &XfrAddress,
+        start: usize,
+        end: usize,
+    ) -> (u64, Vec<BalanceHistoryEntry>) {
+        let api_cache = match self.api_cache() {
+            Some(c) => c,
+            None => return (0, vec![]),
+        };
+        let txns = if let Some(txns) = api_cache.related_transactions.get(address) {
+            txns
+        } else {
+            return (0, vec![]);
+        };
+
+        let entries = txns
+            .iter()
+            .skip(start)
+            .take(end - start)
+            .filter_map(|(sid, _)| {
+                let ftx = self.ledger_cloned.get_transaction_light(sid).ok()?;
+                let meta = api_cache.txn_meta.get(&sid);
+                let (changes, confidential_cnt) =
+                    balance_changes(&ftx.txn, &address.key);
+                Some(BalanceHistoryEntry {
+                    txn_sid: sid,
+                    height: meta.as_ref().map(|m| m.height),
+                    timestamp: meta.as_ref().map(|m| m.timestamp),
+                    changes,
+                    confidential_cnt,
+                })
+            })
+            .collect();
+
+        (txns.len() as u64, entries)
+    }
+
     /// Returns a list of claim transactions of a given ledger address
     pub fn get_claim_transactions(
         &self,
@@ -151,10 +610,8 @@ impl QueryServer {
         order_desc: bool,
     ) -> Result<Vec<Option<Transaction>>> {
         if let Some(hist) = self
-            .ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
+            .api_cache()
+            .c(d!("the api cache is unavailable"))?
             .claim_hist_txns
             .get(address)
         {
@@ -217,11 +674,8 @@ impl QueryServer {
     pub fn get_related_transactions(
         &self,
         address: &XfrAddress,
-    ) -> Option<HashSet<TxnSID>> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
+    ) -> Option<BTreeSet<TxnSID>> {
+        self.api_cache()?
             .related_transactions
             .get(&address)
             .map(|d| d.iter().map(|(k, _)| k).collect())
@@ -233,11 +687,8 @@ impl QueryServer {
     pub fn get_related_transfers(
         &self,
         code: &AssetTypeCode,
-    ) -> Option<HashSet<TxnSID>> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
+    ) -> Option<BTreeSet<TxnSID>> {
+        self.api_cache()?
             .related_transfers
             .get(&code)
             .map(|d| d.iter().map(|(k, _)| k).collect())
@@ -246,45 +697,32 @@ impl QueryServer {
     /// Returns the owner of a given txo_sid.
     #[inline(always)]
     pub fn get_address_of_sid(&self, txo_sid: TxoSID) -> Option<XfrAddress> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
-            .utxos_to_map_index
-            .get(&txo_sid)
+        self.api_cache()?.utxos_to_map_index.get(&txo_sid)
     }
 
     /// Returns the authenticated txn (id, hash) of a given txo_sid.
     #[inline(always)]
     pub fn get_authenticated_txnid(&self, txo_sid: TxoSID) -> Option<TxnIDHash> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
-            .txo_to_txnid
-            .get(&txo_sid)
+        self.api_cache()?.txo_to_txnid.get(&txo_sid)
     }
 
     /// Returns the transaction hash of a given txn_sid.
     #[inline(always)]
     pub fn get_transaction_hash(&self, txn_sid: TxnSID) -> Option<String> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
-            .txn_sid_to_hash
-            .get(&txn_sid)
+        self.api_cache()?.txn_sid_to_hash.get(&txn_sid)
     }
 
     /// Returns the transaction sid of a given txn_hash.
     #[inline(always)]
     pub fn get_transaction_sid(&self, txn_hash: String) -> Option<TxnSID> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
-            .txn_hash_to_sid
-            .get(&txn_hash)
+        self.api_cache()?.txn_hash_to_sid.get(&txn_hash)
+    }
+
+    /// The api cache of the cloned ledger, missing if the ledger is opened
+    /// without it, the handlers answer 503 then, see `QueryState`
+    #[inline(always)]
+    pub fn api_cache(&self) -> Option<&ApiCache> {
+        self.ledger_cloned.api_cache.as_ref()
     }
 
     /// Returns most recent commits at query_server side.
@@ -293,15 +731,60 @@ impl QueryServer {
         self.ledger_cloned.get_block_commit_count()
     }
 
+    /// Pin a view to `height`, or to the latest block if `height` is none.
+    ///
+    /// The indexes are not versioned, so only the append-only data
+    /// (transactions, state commitments) can be served at a previous height,
+    /// queries on the mutable state require `ReadView::latest`.
+    pub fn read_view(&self, height: Option<u64>) -> Result<ReadView> {
+        let latest = self.ledger_cloned.get_block_commit_count();
+        let height = height.unwrap_or(latest);
+        if height > latest {
+            return Err(eg!(format!(
+                "height {} is not indexed yet, the latest one is {}",
+                height, latest
+            )));
+        }
+
+        let txo_count = if 0 == height {
+            0
+        } else {
+            self.ledger_cloned
+                .blocks
+                .get(height as usize - 1)
+                .c(d!("block not found"))?
+                .state
+                .txo_count
+        };
+
+        Ok(ReadView {
+            height,
+            txo_count,
+            latest: height == latest,
+        })
+    }
+
+    /// Whether the transaction has been committed at the height of `view`
+    #[inline(always)]
+    pub fn txn_visible(&self, view: &ReadView, sid: TxnSID) -> bool {
+        self.ledger_cloned
+            .tx_to_block_location
+            .get(&sid)
+            .map(|[block_idx, _]| (block_idx as u64) < view.height)
+            .unwrap_or(false)
+    }
+
+    /// Returns the commit count of the ledger,
+    /// `None` if the ledger is being written at the moment.
+    #[inline(always)]
+    pub fn get_ledger_commits(&self) -> Option<u64> {
+        self.ledger.try_read().map(|l| l.get_block_commit_count())
+    }
+
     /// Returns the owner memo required to decrypt the asset record stored at given index, if it exists.
     #[inline(always)]
     pub fn get_owner_memo(&self, txo_sid: TxoSID) -> Option<OwnerMemo> {
-        self.ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
-            .owner_memos
-            .get(&txo_sid)
+        self.api_cache()?.owner_memos.get(&txo_sid)
     }
 
     /// Returns the abar owner memo required to decrypt the asset record stored at given index, if it exists.
@@ -313,12 +796,26 @@ impl QueryServer {
             .and_then(|api| api.abar_memos.get(&atxo_sid))
     }
 
+    /// The anonymous record of `com` with its owner memo,
+    /// wallets open it without asking for the sid and the memo one by one
+    pub fn get_abar_entry(&self, com: &Commitment) -> Option<AbarEntry> {
+        let atxo_sid = self.ledger_cloned.get_owned_abar(com)?;
+        Some(AbarEntry {
+            atxo_sid,
+            commitment: wallet::commitment_to_base58(com),
+            memo: self.get_abar_memo(atxo_sid),
+        })
+    }
+
     /// Returns the owner memos required to decrypt the asset record stored at between start and end,
     /// include start and end, limit 100.
     #[inline(always)]
     pub fn get_abar_memos(&self, start: u64, end: u64) -> Vec<(u64, AxfrOwnerMemo)> {
         let mut memos = vec![];
-        let cache = self.ledger_cloned.api_cache.as_ref().unwrap();
+        let cache = match self.api_cache() {
+            Some(c) => c,
+            None => return memos,
+        };
         for i in start..=end {
             if let Some(memo) = cache.abar_memos.get(&ATxoSID(i)) {
                 memos.push((i, memo));
@@ -334,12 +831,8 @@ impl QueryServer {
     ) -> (HashOf<Option<StateCommitmentData>>, u64) {
         let block_count = self.ledger_cloned.get_block_commit_count();
         let commitment = self
-            .ledger_cloned
-            .api_cache
-            .as_ref()
-            .unwrap()
-            .state_commitment_version
-            .clone()
+            .api_cache()
+            .and_then(|c| c.state_commitment_version.clone())
             .unwrap_or_else(|| HashOf::new(&None));
         (commitment, block_count)
     }
@@ -361,6 +854,42 @@ impl QueryServer {
         self.ledger_cloned.check_nullifier_hash(null_hash).ok()
     }
 
+    /// Returns the memos, proofs, current root and nullifier status in one shot,
+    /// so that the proofs and the root always belong to the same version of the tree
+    pub fn get_abar_transfer_material(
+        &self,
+        atxo_sids: &[ATxoSID],
+        nullifiers: Vec<String>,
+    ) -> AbarTransferMaterial {
+        let abars = atxo_sids
+            .iter()
+            .map(|sid| AbarSpendInfo {
+                atxo_sid: *sid,
+                commitment: self.get_abar_commitment(*sid),
+                memo: self.get_abar_memo(*sid),
+                proof: self.get_abar_proof(*sid),
+            })
+            .collect::<Vec<_>>();
+        let root_version = abars
+            .iter()
+            .find_map(|a| a.proof.as_ref().map(|p| p.root_version));
+        let nullifiers = nullifiers
+            .into_iter()
+            .map(|n| {
+                let status = self.check_nullifier_hash(n.clone());
+                (n, status)
+            })
+            .collect();
+
+        AbarTransferMaterial {
+            block_commit_count: self.ledger_cloned.get_block_commit_count(),
+            root: self.ledger_cloned.get_abar_root_hash().ok(),
+            root_version,
+            abars,
+            nullifiers,
+        }
+    }
+
     /// Returns an int value for the max ATxoSid
     #[inline(always)]
     pub fn max_atxo_sid(&self) -> Option<usize> {
@@ -370,6 +899,37 @@ impl QueryServer {
             .and_then(|api| api.abar_memos.len().checked_sub(1))
     }
 
+    /// The version, the root and the size of the committed abar merkle tree
+    pub fn get_abar_tree_info(&self) -> Option<AbarTreeInfo> {
+        let ledger = &self.ledger_cloned;
+        let (version, size) = ledger.get_abar_tree_info().ok()?;
+        Some(AbarTreeInfo {
+            block_commit_count: ledger.get_block_commit_count(),
+            version,
+            root: ledger.get_abar_root_hash().ok()?,
+            size,
+            min_version: version.saturating_sub(VERSION_WINDOW),
+        })
+    }
+
+    /// The roots of the abar merkle tree after the blocks in `[from, to]`,
+    /// those indexed by old versions are skipped
+    pub fn get_abar_roots(
+        &self,
+        from: BlockHeight,
+        to: BlockHeight,
+    ) -> Vec<(BlockHeight, AbarRoot)> {
+        self.ledger_cloned
+            .api_cache
+            .as_ref()
+            .map(|api| {
+                (from..=to)
+                    .filter_map(|h| api.abar_roots.get(&h).map(|r| (h, r)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     /// Returns an int value for the max ATxoSid at a given block height
     #[inline(always)]
     pub fn max_atxo_sid_at_height(&self, height: BlockHeight) -> Option<usize> {
@@ -379,22 +939,198 @@ impl QueryServer {
             .and_then(|api| api.height_to_max_atxo.get(&height).unwrap_or(None))
     }
 
-    /// retrieve block reward rate at specified block height
+    /// The transactions and header info of the tendermint block at `height`
     #[inline(always)]
-    pub fn query_block_rewards_rate(&self, height: &BlockHeight) -> Option<[u128; 2]> {
+    pub fn get_block_meta(&self, height: BlockHeight) -> Option<BlockMeta> {
+        self.ledger_cloned
+            .api_cache
+            .as_ref()
+            .and_then(|api| api.block_meta.get(&height))
+    }
+
+    /// The transactions anchoring a document hash, `hash` is lowercase hex
+    #[inline(always)]
+    pub fn get_anchors(&self, hash: &str) -> Vec<AnchorRecord> {
+        self.ledger_cloned
+            .api_cache
+            .as_ref()
+            .and_then(|api| api.anchors.get(&hash.to_owned()))
+            .unwrap_or_default()
+    }
+
+    /// The blocks in `[from, to]`, those indexed by old versions are skipped
+    pub fn get_block_metas(&self, from: BlockHeight, to: BlockHeight) -> Vec<BlockMeta> {
         self.ledger_cloned
             .api_cache
             .as_ref()
-            .unwrap()
-            .staking_global_rate_hist
-            .get(height)
+            .map(|api| (from..=to).filter_map(|h| api.block_meta.get(&h)).collect())
+            .unwrap_or_default()
     }
 
-    /// update after a new block is created
+    /// retrieve block reward rate at specified block height
+    #[inline(always)]
+    pub fn query_block_rewards_rate(&self, height: &BlockHeight) -> Option<[u128; 2]> {
+        self.api_cache()?.staking_global_rate_hist.get(height)
+    }
+
+    /// update after a new block is created,
+    /// the ledger is cloned only if it is not being written at the moment
+    ///
+    /// NOTE: the clone is not published by `commit` as an immutable
+    /// snapshot of the block. A clone of `LedgerState` shares the on-disk
+    /// collections of the live ledger, so only its in-memory part would be
+    /// frozen, and cloning it inside `commit` adds the cost of the clone,
+    /// the staking state included, to every block.
     #[inline(always)]
     pub fn update(&mut self) {
         if let Some(l) = self.ledger.try_read() {
-            self.ledger_cloned = l.clone();
+            self.ledger_cloned = Arc::new(l.clone());
+        }
+        self.asset_index.update(&self.ledger_cloned);
+        self.staking_cache.invalidate();
+        self.webhooks.scan(&self.ledger_cloned);
+    }
+
+    /// Current validators, served from the cached staking view
+    #[inline(always)]
+    pub fn query_validators(&self) -> ValidatorList {
+        self.staking_cache.view(&self.ledger_cloned).validators()
+    }
+
+    /// Delegation info of `pk`, served from the cached staking view
+    #[inline(always)]
+    pub fn query_delegation_info(&self, pk: &XfrPublicKey) -> DelegationInfo {
+        self.staking_cache.delegation_info(&self.ledger_cloned, pk)
+    }
+
+    /// Pending unbondings of `pk`, served from the cached staking view
+    #[inline(always)]
+    pub fn query_unbonding(&self, pk: &XfrPublicKey) -> UnbondingList {
+        self.staking_cache.view(&self.ledger_cloned).unbondings(pk)
+    }
+
+    /// Returns the total count and a page(`[start, end)`) of the assets
+    /// whose symbol or name starts with `q`
+    #[inline(always)]
+    pub fn search_assets(
+        &self,
+        q: &str,
+        start: usize,
+        end: usize,
+    ) -> (u64, Vec<AssetHit>) {
+        self.asset_index.search(q, start, end)
+    }
+
+    /// Returns the total count and a page(`[start, end)`) of the assets
+    /// in the order of definition, only those of `issuer` if it is not none
+    pub fn list_assets(
+        &self,
+        issuer: Option<&XfrPublicKey>,
+        start: usize,
+        end: usize,
+    ) -> (u64, Vec<AssetSummary>) {
+        let (total_count, codes) = self.asset_index.list(issuer, start, end);
+        let assets = codes
+            .iter()
+            .filter_map(|code| self.ledger_cloned.get_asset_type(code))
+            .map(AssetSummary::from)
+            .collect();
+        (total_count, assets)
+    }
+}
+
+// take the SIDs of `outputs` from `ids`
+// the txos spent by a transfer, relative inputs resolved against the txos
+// created so far by the same transaction
+fn resolve_inputs(t: &TransferAsset, created: &[TxoSID]) -> Vec<TxoSID> {
+    t.body
+        .inputs
+        .iter()
+        .filter_map(|i| match *i {
+            TxoRef::Absolute(sid) => Some(sid),
+            TxoRef::Relative(offs) => created
+                .len()
+                .checked_sub(1 + offs as usize)
+                .map(|ix| created[ix]),
+        })
+        .collect()
+}
+
+// The amounts received and sent by `owner` in `tx` per asset type,
+// along with the number of its confidential records
+fn balance_changes(tx: &Transaction, owner: &XfrPublicKey) -> (Vec<BalanceChange>, u64) {
+    let mut received = vec![];
+    let mut sent = vec![];
+    for op in tx.body.operations.iter() {
+        match op {
+            Operation::TransferAsset(t) => {
+                sent.extend(t.body.transfer.inputs.iter());
+                received.extend(t.body.transfer.outputs.iter());
+            }
+            Operation::SwapAsset(s) => {
+                sent.extend(s.transfer.body.transfer.inputs.iter());
+                received.extend(s.transfer.body.transfer.outputs.iter());
+            }
+            Operation::IssueAsset(i) => {
+                received.extend(i.body.records.iter().map(|(o, _)| &o.record));
+            }
+            Operation::MintFra(m) => {
+                received.extend(m.entries.iter().map(|e| &e.utxo.record));
+            }
+            _ => {}
+        }
+    }
+
+    let mut changes = BTreeMap::<AssetTypeCode, (u64, u64)>::new();
+    let mut confidential_cnt = 0;
+    let records = received
+        .into_iter()
+        .map(|r| (r, true))
+        .chain(sent.into_iter().map(|r| (r, false)));
+    for (r, is_received) in records.filter(|(r, _)| r.public_key == *owner) {
+        match (r.amount.get_amount(), r.asset_type.get_asset_type()) {
+            (Some(am), Some(val)) => {
+                let c = changes.entry(AssetTypeCode { val }).or_default();
+                if is_received {
+                    c.0 = c.0.saturating_add(am);
+                } else {
+                    c.1 = c.1.saturating_add(am);
+                }
+            }
+            _ => confidential_cnt += 1,
         }
     }
+
+    let changes = changes
+        .into_iter()
+        .map(|(asset_type, (received, sent))| BalanceChange {
+            asset_type,
+            received,
+            sent,
+        })
+        .collect();
+    (changes, confidential_cnt)
+}
+
+fn summarize_outputs<'a>(
+    outputs: impl Iterator<Item = &'a TxOutput>,
+    ids: &mut impl Iterator<Item = TxoSID>,
+    created: &mut Vec<TxoSID>,
+) -> Vec<OutputSummary> {
+    outputs
+        .map(|o| {
+            let txo_sid = ids.next();
+            created.extend(txo_sid);
+            OutputSummary {
+                txo_sid,
+                owner: o.record.public_key,
+                amount: o.record.amount.get_amount(),
+                asset_type: o
+                    .record
+                    .asset_type
+                    .get_asset_type()
+                    .map(|val| AssetTypeCode { val }),
+            }
+        })
+        .collect()
 }
@@ -13,7 +13,8 @@ use {
     std::{sync::Arc, thread},
 };
 
-pub(crate) fn start_query_server(
+/// Serve the query routes on `addrs`, updated on each `BLOCK_CREATED`
+pub fn start_query_server(
     ledger: Arc<RwLock<LedgerState>>,
     addrs: &[(&str, u16)],
 ) -> Result<Arc<RwLock<QueryServer>>> {
@@ -0,0 +1,341 @@
+//!
+//! # Cached view of the staking state
+//!
+//! The validator list and the delegation info of the addresses are served from
+//! a snapshot of the staking state instead of being rebuilt on every request.
+//! A new block marks the view stale, the next request rebuilds it once it is
+//! older than `CFG.staking_cache_refresh` seconds.
+//!
+
+use {
+    super::ledger_api::{UnbondingEntry, UnbondingList},
+    crate::api::mem_guard::{
+        Evictable, ResponseCache, MEM_GUARD, PRIORITY_INDEX, PRIORITY_RENDERED,
+    },
+    config::abci::global_cfg::CFG,
+    finutils::api::{DelegationInfo, Validator, ValidatorList},
+    globutils::wallet,
+    ledger::{
+        staking::{BlockHeight, DelegationState, Staking},
+        store::LedgerState,
+    },
+    parking_lot::Mutex,
+    std::{
+        mem,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    },
+    zei::XfrPublicKey,
+};
+
+// Bound of the delegation infos built from the current view
+const DELEGATION_CACHE_CAP: usize = 100_000;
+
+/// The staking state of a block, with the aggregates of the validators
+pub struct StakingView {
+    staking: Staking,
+    block_rewards_rate: [u128; 2],
+    validators: ValidatorList,
+    built_at: Instant,
+}
+
+impl StakingView {
+    fn build(ledger: &LedgerState) -> Self {
+        let staking = ledger.get_staking().clone();
+        let validators = validator_list(&staking);
+        StakingView {
+            block_rewards_rate: ledger.staking_get_block_rewards_rate(),
+            staking,
+            validators,
+            built_at: Instant::now(),
+        }
+    }
+
+    #[allow(missing_docs)]
+    #[inline(always)]
+    pub fn height(&self) -> BlockHeight {
+        self.staking.cur_height()
+    }
+
+    /// Seconds since the view was built
+    #[inline(always)]
+    pub fn age(&self) -> u64 {
+        self.built_at.elapsed().as_secs()
+    }
+
+    /// Current validators, those who have not completed
+    /// the self-delegation are filtered out
+    pub fn validators(&self) -> ValidatorList {
+        let mut list = self.validators.clone();
+        list.cache_age = self.age();
+        list
+    }
+
+    /// Pending unbondings of `pk`, in the order of maturity
+    pub fn unbondings(&self, pk: &XfrPublicKey) -> UnbondingList {
+        let staking = &self.staking;
+        let current_height = staking.cur_height();
+        let entries = staking
+            .unbonding_queue_of(pk)
+            .into_iter()
+            .map(|u| UnbondingEntry {
+                delegator: wallet::public_key_to_base64(&u.id),
+                validator: staking
+                    .validator_app_pk_to_td_addr(&u.validator)
+                    .unwrap_or_default(),
+                amount: u.amount,
+                maturity_height: u.maturity_height,
+                remaining_blocks: u.maturity_height.saturating_sub(current_height),
+                matured: DelegationState::Free == u.state,
+            })
+            .collect::<Vec<_>>();
+
+        UnbondingList {
+            current_height,
+            total: entries.iter().map(|e| e.amount).sum(),
+            entries,
+            cache_age: self.age(),
+        }
+    }
+
+    fn delegation_info(&self, pk: &XfrPublicKey) -> DelegationInfo {
+        let staking = &self.staking;
+        let (
+            bond_amount,
+            bond_entries,
+            unbond_amount,
+            rwd_amount,
+            start_height,
+            end_height,
+            delegation_rwd_cnt,
+            proposer_rwd_cnt,
+        ) = staking
+            .delegation_get(pk)
+            .map(|d| {
+                let mut bond_amount = d.amount();
+                let bond_entries: Vec<(String, u64)> = d
+                    .delegations
+                    .iter()
+                    .filter_map(|(pk, am)| {
+                        staking
+                            .validator_app_pk_to_td_addr(pk)
+                            .ok()
+                            .map(|addr| (addr, *am))
+                    })
+                    .collect();
+                let mut unbond_amount = 0;
+                match d.state {
+                    DelegationState::Paid => {
+                        bond_amount = 0;
+                    }
+                    DelegationState::Free => {
+                        mem::swap(&mut bond_amount, &mut unbond_amount);
+                    }
+                    DelegationState::Bond => {
+                        if staking.cur_height()
+                            > d.end_height()
+                                .saturating_sub(staking.params().unbond_block_cnt())
+                        {
+                            mem::swap(&mut bond_amount, &mut unbond_amount);
+                        }
+                    }
+                }
+                // check temporary partial undelegators
+                unbond_amount += d.tmp_delegators.values().sum::<u64>();
+
+                (
+                    bond_amount,
+                    bond_entries,
+                    unbond_amount,
+                    d.rwd_amount,
+                    d.start_height(),
+                    d.end_height(),
+                    d.delegation_rwd_cnt,
+                    d.proposer_rwd_cnt,
+                )
+            })
+            .unwrap_or((0, vec![], 0, 0, 0, 0, 0, 0));
+
+        let mut info = DelegationInfo::new(
+            bond_amount,
+            bond_entries,
+            unbond_amount,
+            rwd_amount,
+            self.block_rewards_rate,
+            staking.delegation_info_global_amount(),
+            staking.validator_global_power(),
+        );
+        info.start_height = start_height;
+        info.current_height = staking.cur_height();
+        info.end_height = end_height;
+        info.delegation_rwd_cnt = delegation_rwd_cnt;
+        info.proposer_rwd_cnt = proposer_rwd_cnt;
+        info
+    }
+}
+
+// the ranks are computed once for all the validators
+fn validator_list(staking: &Staking) -> ValidatorList {
+    let validator_data = if let Some(v) = staking.validator_get_current() {
+        v
+    } else {
+        return ValidatorList::new(0, vec![]);
+    };
+
+    let mut power_list = validator_data
+        .body
+        .values()
+        .map(|v| v.td_power)
+        .collect::<Vec<_>>();
+    power_list.sort_unstable();
+
+    let validators = validator_data
+        .get_validator_addr_map()
+        .iter()
+        .flat_map(|(tendermint_addr, pk)| {
+            validator_data
+                .get_validator_by_id(pk)
+                .filter(|v| v.td_power != 0)
+                .map(|v| {
+                    let rank = power_list.len()
+                        - power_list.binary_search(&v.td_power).unwrap_or_else(|i| i);
+                    Validator::new(
+                        tendermint_addr.clone(),
+                        rank as u64,
+                        staking.delegation_has_addr(&pk),
+                        &v,
+                    )
+                })
+        })
+        .collect();
+
+    ValidatorList::new(staking.cur_height(), validators)
+}
+
+// The slot of the current view, dropped under memory pressure
+#[derive(Default)]
+struct ViewSlot(Mutex<Option<Arc<StakingView>>>);
+
+impl Evictable for ViewSlot {
+    fn name(&self) -> &'static str {
+        "staking_view"
+    }
+
+    fn priority(&self) -> u8 {
+        PRIORITY_INDEX
+    }
+
+    fn entries(&self) -> usize {
+        self.0.lock().is_some() as usize
+    }
+
+    fn evict(&self) -> usize {
+        self.0.lock().take().is_some() as usize
+    }
+}
+
+/// The current `StakingView`, rebuilt lazily after new blocks
+pub struct StakingCache {
+    view: Arc<ViewSlot>,
+    stale: AtomicBool,
+    delegations: Arc<ResponseCache<(BlockHeight, XfrPublicKey), DelegationInfo>>,
+}
+
+impl Default for StakingCache {
+    fn default() -> Self {
+        let delegations = Arc::new(ResponseCache::new(
+            "delegation_info",
+            PRIORITY_RENDERED,
+            DELEGATION_CACHE_CAP,
+        ));
+        MEM_GUARD.register(delegations.clone());
+        let view = Arc::new(ViewSlot::default());
+        MEM_GUARD.register(view.clone());
+        StakingCache {
+            view,
+            stale: AtomicBool::new(true),
+            delegations,
+        }
+    }
+}
+
+impl StakingCache {
+    /// Called after a new block is committed
+    #[inline(always)]
+    pub fn invalidate(&self) {
+        self.stale.store(true, Ordering::Relaxed);
+    }
+
+    /// The cached view, rebuilt from `ledger` if it is stale
+    /// and older than the configured refresh interval
+    pub fn view(&self, ledger: &LedgerState) -> Arc<StakingView> {
+        let mut view = self.view.0.lock();
+        if let Some(v) = view.as_ref() {
+            let fresh = !self.stale.load(Ordering::Relaxed)
+                || v.built_at.elapsed() < Duration::from_secs(CFG.staking_cache_refresh);
+            if fresh {
+                return v.clone();
+            }
+        }
+
+        self.stale.store(false, Ordering::Relaxed);
+        let v = Arc::new(StakingView::build(ledger));
+        *view = Some(v.clone());
+        v
+    }
+
+    /// The delegation info of `pk` in the current view
+    pub fn delegation_info(
+        &self,
+        ledger: &LedgerState,
+        pk: &XfrPublicKey,
+    ) -> DelegationInfo {
+        let view = self.view(ledger);
+        let key = (view.height(), *pk);
+        let mut info = self.delegations.get(&key).unwrap_or_else(|| {
+            let info = view.delegation_info(pk);
+            self.delegations.insert(key, info.clone());
+            info
+        });
+        info.cache_age = view.age();
+        info
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*, crate::api::mem_guard::Evictable, rand_chacha::ChaChaRng,
+        rand_core::SeedableRng, zei::XfrKeyPair,
+    };
+
+    #[test]
+    fn staking_cache() {
+        let ledger = LedgerState::tmp_ledger();
+        let cache = StakingCache::default();
+
+        let v0 = cache.view(&ledger);
+        assert!(Arc::ptr_eq(&v0, &cache.view(&ledger)));
+
+        // the refresh interval of the tests is 0
+        cache.invalidate();
+        let v1 = cache.view(&ledger);
+        assert!(!Arc::ptr_eq(&v0, &v1));
+        assert!(Arc::ptr_eq(&v1, &cache.view(&ledger)));
+
+        let pk = XfrKeyPair::generate(&mut ChaChaRng::from_entropy()).get_pk();
+        let info = cache.delegation_info(&ledger, &pk);
+        assert_eq!(info.bond, 0);
+        assert_eq!(info.current_height, v1.height());
+        assert_eq!(cache.delegations.entries(), 1);
+
+        // an evicted view is rebuilt on the next request
+        assert_eq!(cache.view.evict(), 1);
+        assert_eq!(cache.view.entries(), 0);
+        assert!(!Arc::ptr_eq(&v1, &cache.view(&ledger)));
+        assert_eq!(cache.view.entries(), 1);
+    }
+}
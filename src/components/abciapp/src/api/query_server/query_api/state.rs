@@ -0,0 +1,51 @@
+//!
+//! # The query server as seen by the handlers
+//!
+//! `QueryState` extracts the shared `QueryServer` of the app, and answers
+//! 503 instead of running the handler while the cloned ledger has no
+//! api cache, e.g. a node restored without its indexes. The handlers can
+//! then read the query server without unwrapping anything.
+//!
+
+use {
+    super::server::QueryServer,
+    crate::api::error::{internal, unavailable},
+    actix_web::{
+        dev::Payload, error::Error as ActixError, web, FromRequest, HttpRequest,
+    },
+    futures::future::{ready, Ready},
+    parking_lot::RwLock,
+    std::{ops::Deref, sync::Arc},
+};
+
+/// Reported while the api cache is unavailable
+pub const API_CACHE_UNAVAILABLE: &str =
+    "the indexes of the query server are unavailable";
+
+/// The query server, only extracted if it can answer the queries
+pub struct QueryState(web::Data<Arc<RwLock<QueryServer>>>);
+
+impl Deref for QueryState {
+    type Target = Arc<RwLock<QueryServer>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for QueryState {
+    type Config = ();
+    type Error = ActixError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let data = match req.app_data::<web::Data<Arc<RwLock<QueryServer>>>>() {
+            Some(data) => data.clone(),
+            None => return ready(Err(internal("the query server is not registered"))),
+        };
+        if data.read().api_cache().is_none() {
+            return ready(Err(unavailable(API_CACHE_UNAVAILABLE)));
+        }
+        ready(Ok(QueryState(data)))
+    }
+}
@@ -0,0 +1,117 @@
+//!
+//! # View keys registered to this node
+//!
+//! The owner of an address registers a read-only key by `POST /view_keys`,
+//! the endpoints under `/view` then accept the requests carrying a
+//! `ViewKeyAuth` of the address signed by that key, in the header
+//! `X-Findora-View-Key` as base64 encoded JSON.
+//!
+//! The registrations are saved under the ledger directory.
+//!
+
+use {
+    crate::api::error::unauthorized,
+    actix_web::{error::Error as ActixError, HttpRequest},
+    chrono::Utc,
+    config::abci::global_cfg::CFG,
+    globutils::wallet,
+    ledger::data_model::{b64dec, ViewKeyAuth, ViewKeyRegistration},
+    parking_lot::RwLock,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeMap, fs, path::PathBuf},
+    zei::XfrPublicKey,
+};
+
+/// Header of the authentication by a view key
+pub const VIEW_KEY_HEADER: &str = "X-Findora-View-Key";
+
+const VIEW_KEYS_FILE: &str = "view_keys.json";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Entry {
+    view_key: Option<XfrPublicKey>,
+    timestamp: i64,
+}
+
+/// The view keys of the addresses, keyed by the base64 encoded addresses
+pub struct ViewKeys {
+    keys: RwLock<BTreeMap<String, Entry>>,
+    path: PathBuf,
+}
+
+impl ViewKeys {
+    /// Load the saved registrations
+    pub fn load() -> Self {
+        let path = PathBuf::from(&CFG.ledger_dir).join(VIEW_KEYS_FILE);
+        let keys = fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default();
+        ViewKeys {
+            keys: RwLock::new(keys),
+            path,
+        }
+    }
+
+    /// Set or revoke the view key of an address, the registrations
+    /// older than the current one are rejected
+    pub fn register(&self, reg: &ViewKeyRegistration) -> Result<()> {
+        reg.verify().c(d!())?;
+
+        let address = wallet::public_key_to_base64(&reg.body.address);
+        let mut keys = self.keys.write();
+        if let Some(e) = keys.get(&address) {
+            if reg.body.timestamp <= e.timestamp {
+                return Err(eg!("a later registration exists"));
+            }
+        }
+        keys.insert(
+            address,
+            Entry {
+                view_key: reg.body.view_key,
+                timestamp: reg.body.timestamp,
+            },
+        );
+        self.save(&keys).c(d!())
+    }
+
+    /// The view key of an address, `None` if not registered or revoked
+    pub fn get(&self, address: &XfrPublicKey) -> Option<XfrPublicKey> {
+        self.keys
+            .read()
+            .get(&wallet::public_key_to_base64(address))
+            .and_then(|e| e.view_key)
+    }
+
+    /// Check the view key authentication of a request reading `address`
+    pub fn authorize(
+        &self,
+        req: &HttpRequest,
+        address: &XfrPublicKey,
+    ) -> Result<(), ActixError> {
+        let auth = req
+            .headers()
+            .get(VIEW_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| b64dec(v.trim()).ok())
+            .and_then(|b| serde_json::from_slice::<ViewKeyAuth>(&b).ok())
+            .ok_or_else(|| unauthorized("missing or malformed view key"))?;
+
+        if auth.body.address != *address {
+            return Err(unauthorized("the view key is for another address"));
+        }
+        if self.get(address) != Some(auth.view_key) {
+            return Err(unauthorized("the view key is not registered"));
+        }
+        auth.verify(Utc::now().timestamp())
+            .map_err(|e| unauthorized(e.generate_log(None)))
+    }
+
+    fn save(&self, keys: &BTreeMap<String, Entry>) -> Result<()> {
+        let body = serde_json::to_vec(keys).c(d!())?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, body).c(d!())?;
+        fs::rename(&tmp, &self.path).c(d!())
+    }
+}
@@ -0,0 +1,368 @@
+//!
+//! # Address watch list with webhook notifications
+//!
+//! A client registers a set of addresses and a callback URL, then each committed
+//! transaction touching one of the addresses is POSTed to the URL as JSON.
+//! The body is signed by the secret returned at registration, the header
+//! `X-Findora-Signature` carries `sha256=<hex of HMAC-SHA256(secret, body)>`.
+//!
+//! Notifications are delivered by a few workers, failed deliveries are retried
+//! with an exponential backoff, and dropped after `MAX_NOTIFY_ATTEMPTS`.
+//! The subscriptions are saved under the ledger directory, the transactions
+//! committed while the node is down are not notified.
+//!
+
+use {
+    crate::api::address::parse_address,
+    config::abci::global_cfg::CFG,
+    hmac::{Hmac, Mac},
+    ledger::{
+        data_model::{TxnSID, XfrAddress},
+        staking::BlockHeight,
+        store::{
+            api_cache::{get_related_addresses, OpKind},
+            LedgerState,
+        },
+    },
+    parking_lot::{Mutex, RwLock},
+    rand::Rng,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    sha2::Sha256,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fs,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            mpsc::{sync_channel, Receiver, SyncSender},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    },
+    zei::XfrPublicKey,
+};
+
+/// The most subscriptions of a node
+pub const MAX_SUBSCRIPTIONS: usize = 1000;
+
+/// The most addresses watched by a subscription
+pub const MAX_WATCHED_ADDRESSES: usize = 1000;
+
+/// Capacity of the notification queue, notifications beyond it are dropped
+pub const NOTIFY_QUEUE_CAP: usize = 10_000;
+
+/// Attempts of a delivery before it is dropped
+pub const MAX_NOTIFY_ATTEMPTS: u32 = 5;
+
+/// Header of the signature of a notification
+pub const SIGNATURE_HEADER: &str = "X-Findora-Signature";
+
+const NOTIFY_WORKERS: usize = 2;
+const NOTIFY_TIMEOUT_SECS: u64 = 10;
+const BACKOFF_BASE_MS: u64 = 500;
+const BACKOFF_MAX_MS: u64 = 30_000;
+
+const SUBSCRIPTIONS_FILE: &str = "webhooks.json";
+
+/// A watch list and where to notify it
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Subscription {
+    pub id: String,
+    pub url: String,
+    pub addresses: BTreeSet<XfrPublicKey>,
+    /// key of the HMAC signatures, only returned at registration
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub secret: String,
+}
+
+/// The body of a notification
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct Notification {
+    pub subscription_id: String,
+    pub txn_sid: TxnSID,
+    pub txn_hash: String,
+    /// tendermint height, `None` if the api cache is disabled
+    pub height: Option<BlockHeight>,
+    /// header time of the block in unix seconds, `None` as `height`
+    pub timestamp: Option<i64>,
+    pub ops: Vec<OpKind>,
+    /// the watched addresses touched by the transaction
+    pub addresses: Vec<XfrPublicKey>,
+}
+
+/// Delivery statistics, see `/webhooks_stat`
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookStat {
+    pub subscriptions: u64,
+    pub queue_depth: u64,
+    pub delivered_cnt: u64,
+    pub retried_cnt: u64,
+    pub dropped_cnt: u64,
+}
+
+struct NotifyTask {
+    url: String,
+    secret: String,
+    body: String,
+}
+
+#[derive(Default)]
+struct Counters {
+    queue_depth: AtomicU64,
+    delivered: AtomicU64,
+    retried: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// The registered subscriptions and the workers delivering their notifications
+pub struct Webhooks {
+    subs: RwLock<BTreeMap<String, Subscription>>,
+    // index of the next block to scan, `None` before the first scan
+    next_block: Mutex<Option<usize>>,
+    queue: SyncSender<NotifyTask>,
+    counters: Arc<Counters>,
+    path: PathBuf,
+}
+
+impl Webhooks {
+    /// Load the saved subscriptions and start the workers
+    pub fn start() -> Self {
+        let path = PathBuf::from(&CFG.ledger_dir).join(SUBSCRIPTIONS_FILE);
+        let subs = fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default();
+
+        let counters = Arc::new(Counters::default());
+        let (queue, receiver) = sync_channel(NOTIFY_QUEUE_CAP);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..NOTIFY_WORKERS {
+            let receiver = Arc::clone(&receiver);
+            let counters = Arc::clone(&counters);
+            thread::spawn(move || work(receiver, counters));
+        }
+
+        Webhooks {
+            subs: RwLock::new(subs),
+            next_block: Mutex::new(None),
+            queue,
+            counters,
+            path,
+        }
+    }
+
+    /// Register a watch list, the returned subscription carries its secret
+    pub fn subscribe(&self, url: &str, addresses: &[String]) -> Result<Subscription> {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(eg!("the callback should be an http(s) URL"));
+        }
+        if addresses.is_empty() || MAX_WATCHED_ADDRESSES < addresses.len() {
+            return Err(eg!(format!(
+                "watch 1 to {MAX_WATCHED_ADDRESSES} addresses at a time"
+            )));
+        }
+        let addresses = addresses
+            .iter()
+            .map(|a| parse_address(a).c(d!()))
+            .collect::<Result<BTreeSet<_>>>()?;
+
+        let mut rng = rand::thread_rng();
+        let sub = Subscription {
+            id: hex::encode(rng.gen::<[u8; 16]>()),
+            url: url.to_owned(),
+            addresses,
+            secret: hex::encode(rng.gen::<[u8; 32]>()),
+        };
+
+        let mut subs = self.subs.write();
+        if MAX_SUBSCRIPTIONS <= subs.len() {
+            return Err(eg!("too many subscriptions"));
+        }
+        subs.insert(sub.id.clone(), sub.clone());
+        self.save(&subs).c(d!())?;
+        Ok(sub)
+    }
+
+    /// A subscription without its secret
+    pub fn get(&self, id: &str) -> Option<Subscription> {
+        self.subs.read().get(id).cloned().map(|mut s| {
+            s.secret.clear();
+            s
+        })
+    }
+
+    /// Remove a subscription, the queued notifications are still delivered
+    pub fn unsubscribe(&self, id: &str) -> Result<bool> {
+        let mut subs = self.subs.write();
+        if subs.remove(id).is_none() {
+            return Ok(false);
+        }
+        self.save(&subs).c(d!())?;
+        Ok(true)
+    }
+
+    #[allow(missing_docs)]
+    pub fn stat(&self) -> WebhookStat {
+        let c = &self.counters;
+        WebhookStat {
+            subscriptions: self.subs.read().len() as u64,
+            queue_depth: c.queue_depth.load(Ordering::Relaxed),
+            delivered_cnt: c.delivered.load(Ordering::Relaxed),
+            retried_cnt: c.retried.load(Ordering::Relaxed),
+            dropped_cnt: c.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Queue the notifications of the blocks committed since the last scan,
+    /// called by `QueryServer::update`, the first scan only sets the start
+    pub fn scan(&self, ledger: &LedgerState) {
+        let len = ledger.blocks.len();
+        let from = match self.next_block.lock().replace(len) {
+            Some(from) => from,
+            None => return,
+        };
+
+        let subs = self.subs.read();
+        if subs.is_empty() {
+            return;
+        }
+        let api_cache = ledger.api_cache.as_ref();
+
+        for ftx in (from..len)
+            .filter_map(|i| ledger.blocks.get(i))
+            .flat_map(|b| b.txns.into_iter())
+        {
+            let related = get_related_addresses(&ftx.txn, |_| {});
+            let meta = api_cache.and_then(|a| a.txn_meta.get(&ftx.tx_id));
+            for sub in subs.values() {
+                let addresses = sub
+                    .addresses
+                    .iter()
+                    .filter(|pk| related.contains(&XfrAddress { key: **pk }))
+                    .copied()
+                    .collect::<Vec<_>>();
+                if addresses.is_empty() {
+                    continue;
+                }
+                let n = Notification {
+                    subscription_id: sub.id.clone(),
+                    txn_sid: ftx.tx_id,
+                    txn_hash: ftx.txn.hash_tm().hex().to_uppercase(),
+                    height: meta.as_ref().map(|m| m.height),
+                    timestamp: meta.as_ref().map(|m| m.timestamp),
+                    ops: OpKind::of_txn(&ftx.txn),
+                    addresses,
+                };
+                if let Ok(body) = serde_json::to_string(&n) {
+                    self.push(NotifyTask {
+                        url: sub.url.clone(),
+                        secret: sub.secret.clone(),
+                        body,
+                    });
+                }
+            }
+        }
+    }
+
+    fn push(&self, task: NotifyTask) {
+        self.counters.queue_depth.fetch_add(1, Ordering::Relaxed);
+        if self.queue.try_send(task).is_err() {
+            self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn save(&self, subs: &BTreeMap<String, Subscription>) -> Result<()> {
+        let body = serde_json::to_vec(subs).c(d!())?;
+        // write and rename, a half-written file would lose all the subscriptions
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, body).c(d!())?;
+        fs::rename(&tmp, &self.path).c(d!())
+    }
+}
+
+/// `sha256=<hex>` of the HMAC-SHA256 of `body` by `secret`,
+/// the value of the `X-Findora-Signature` header
+pub fn sign(secret: &str, body: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).c(d!())?;
+    mac.update(body);
+    Ok(format!(
+        "sha256={}",
+        hex::encode(mac.finalize().into_bytes())
+    ))
+}
+
+fn work(receiver: Arc<Mutex<Receiver<NotifyTask>>>, counters: Arc<Counters>) {
+    loop {
+        let task = receiver.lock().recv();
+        match task {
+            Ok(task) => {
+                counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                deliver(task, &counters);
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+fn deliver(task: NotifyTask, counters: &Counters) {
+    let signature = match sign(&task.secret, task.body.as_bytes()) {
+        Ok(s) => s,
+        Err(e) => {
+            e.print(None);
+            counters.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    };
+
+    for attempt in 0..MAX_NOTIFY_ATTEMPTS {
+        if 0 < attempt {
+            counters.retried.fetch_add(1, Ordering::Relaxed);
+            let backoff = BACKOFF_BASE_MS.saturating_mul(1 << (attempt - 1));
+            thread::sleep(Duration::from_millis(backoff.min(BACKOFF_MAX_MS)));
+        }
+
+        let res = attohttpc::post(&task.url)
+            .timeout(Duration::from_secs(NOTIFY_TIMEOUT_SECS))
+            .header(attohttpc::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature.as_str())
+            .text(&task.body)
+            .send()
+            .c(d!())
+            .and_then(|resp| resp.error_for_status().c(d!()));
+        match res {
+            Ok(_) => {
+                counters.delivered.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            Err(e) => {
+                e.print(Some(&format!(
+                    "notifying {}, attempt {}",
+                    task.url,
+                    attempt + 1
+                )));
+            }
+        }
+    }
+
+    counters.dropped.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hmac_signature() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?").unwrap(),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//!
+//! # Correlation IDs of the requests
+//!
+//! Each request of the REST services gets an ID, the one of its `X-Request-Id`
+//! header if valid, or a random one. The ID is echoed in the response header,
+//! and recorded by a `request` span around the handler.
+//!
+//! The ID of a transaction submission is kept along with the handle of the
+//! transaction, so the forwarding, `check_tx`, `deliver_tx` and the commit
+//! of the transaction are logged with it, under the target `txn_lifecycle`.
+//!
+
+use {
+    actix_service::{Service, Transform},
+    actix_web::{
+        dev::{ServiceRequest, ServiceResponse},
+        error::Error as ActixError,
+        http::{HeaderName, HeaderValue},
+        HttpMessage, HttpRequest,
+    },
+    futures::future::{ready, Ready},
+    lazy_static::lazy_static,
+    parking_lot::Mutex,
+    rand::Rng,
+    std::{
+        collections::{HashMap, VecDeque},
+        fmt,
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+    },
+    tracing::{info, info_span, Instrument},
+};
+
+/// Header of the correlation ID
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+// the oldest transactions are forgotten beyond this number
+const TRACKED_TXNS_CAP: usize = 10_000;
+
+/// The correlation ID of a request, in the extensions of the request
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+#[derive(Default)]
+struct TrackedTxns {
+    ids: HashMap<String, String>,
+    order: VecDeque<String>,
+}
+
+lazy_static! {
+    static ref TRACKED_TXNS: Mutex<TrackedTxns> = Mutex::new(TrackedTxns::default());
+}
+
+/// Assign the correlation IDs, see the module doc
+pub struct RequestTracing;
+
+impl<S, B> Transform<S> for RequestTracing
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = ActixError,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = ActixError;
+    type InitError = ();
+    type Transform = RequestTracingMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware { service }))
+    }
+}
+
+#[allow(missing_docs)]
+pub struct RequestTracingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RequestTracingMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = ActixError,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = ActixError;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| is_valid(v))
+            .map(|v| v.to_owned())
+            .unwrap_or_else(|| hex::encode(rand::thread_rng().gen::<[u8; 8]>()));
+        let span = info_span!(
+            "request",
+            request_id = %id,
+            method = %req.method(),
+            path = %req.path()
+        );
+        req.extensions_mut().insert(RequestId(id.clone()));
+
+        let fut = self.service.call(req);
+        Box::pin(
+            async move {
+                let mut res = fut.await?;
+                if let Ok(v) = HeaderValue::from_str(&id) {
+                    res.headers_mut()
+                        .insert(HeaderName::from_static("x-request-id"), v);
+                }
+                Ok(res)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// The correlation ID of a request, `None` out of `RequestTracing`
+pub fn request_id(req: &HttpRequest) -> Option<String> {
+    req.extensions().get::<RequestId>().map(|id| id.0.clone())
+}
+
+/// Log the later stages of the transaction `handle` with `request_id`
+pub fn track_txn(handle: &str, request_id: &str) {
+    let mut t = TRACKED_TXNS.lock();
+    if t.ids
+        .insert(handle.to_owned(), request_id.to_owned())
+        .is_none()
+    {
+        t.order.push_back(handle.to_owned());
+    }
+    while TRACKED_TXNS_CAP < t.order.len() {
+        if let Some(h) = t.order.pop_front() {
+            t.ids.remove(&h);
+        }
+    }
+}
+
+/// Log a stage of a tracked transaction, `done` for the last one,
+/// nothing is logged for the transactions not tracked
+pub fn txn_event(handle: &str, done: bool, event: impl fmt::Display) {
+    let id = {
+        let mut t = TRACKED_TXNS.lock();
+        if done {
+            t.order.retain(|h| h != handle);
+            t.ids.remove(handle)
+        } else {
+            t.ids.get(handle).cloned()
+        }
+    };
+    if let Some(id) = id {
+        info!(target: "txn_lifecycle", request_id = %id, handle = %handle, "{}", event);
+    }
+}
+
+// printable ASCII without separators, so it can be logged and echoed as is
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn request_id_validity() {
+        assert!(is_valid("3f2a-01_b.c"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("a b"));
+        assert!(!is_valid(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+}
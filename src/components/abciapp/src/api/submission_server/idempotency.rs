@@ -0,0 +1,164 @@
+//!
+//! # Idempotency keys of the submissions
+//!
+//! A client may tag a submission with a key of its own, a retry with the same key
+//! gets the handle of the first submission instead of being forwarded again.
+//! The keys are kept on disk, so they survive a restart of the node.
+//!
+
+use {
+    super::TxnHandle,
+    chrono::Utc,
+    ledger::store::fbnc::{new_mapx, Mapx},
+    ruc::*,
+    serde::{Deserialize, Serialize},
+};
+
+/// Entries older than this are dropped, the client is not retrying any more
+pub const IDEMPOTENCY_KEY_TTL_SECS: i64 = 24 * 3600;
+
+/// The oldest entry is dropped when the cache is full
+pub const MAX_IDEMPOTENCY_KEYS: usize = 100_000;
+
+/// Longer keys are refused
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 128;
+
+/// The header carrying the key of a submission
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    handle: TxnHandle,
+    // unix seconds
+    created_at: i64,
+}
+
+/// Handles of the submissions, keyed by the idempotency keys of the clients
+pub struct IdempotencyCache {
+    entries: Mapx<String, Entry>,
+}
+
+impl Default for IdempotencyCache {
+    fn default() -> Self {
+        Self::new("submission_server/idempotency_keys")
+    }
+}
+
+impl IdempotencyCache {
+    /// Open the cache persisted under `path`
+    pub fn new(path: &str) -> Self {
+        IdempotencyCache {
+            entries: new_mapx!(path),
+        }
+    }
+
+    /// Refuse the keys which are empty or too long
+    pub fn check_key(key: &str) -> Result<()> {
+        if key.is_empty() || MAX_IDEMPOTENCY_KEY_LEN < key.len() {
+            return Err(eg!(format!(
+                "the idempotency key should have 1 to {MAX_IDEMPOTENCY_KEY_LEN} bytes"
+            )));
+        }
+        Ok(())
+    }
+
+    /// The handle of the first submission with `key`,
+    /// `None` if the key is unknown or expired.
+    ///
+    /// Reusing a key for another transaction is an error.
+    pub fn get(&self, key: &str, handle: &TxnHandle) -> Result<Option<TxnHandle>> {
+        match self.entries.get(&key.to_owned()) {
+            Some(e)
+                if e.created_at + IDEMPOTENCY_KEY_TTL_SECS < Utc::now().timestamp() =>
+            {
+                Ok(None)
+            }
+            Some(e) if &e.handle != handle => Err(eg!(format!(
+                "the idempotency key is used by another transaction: {}",
+                e.handle.0
+            ))),
+            Some(e) => Ok(Some(e.handle)),
+            None => Ok(None),
+        }
+    }
+
+    /// Remember the handle of a submission forwarded to tendermint
+    pub fn insert(&mut self, key: String, handle: TxnHandle) {
+        let now = Utc::now().timestamp();
+        if MAX_IDEMPOTENCY_KEYS <= self.entries.len() {
+            self.evict(now);
+        }
+        if MAX_IDEMPOTENCY_KEYS <= self.entries.len() {
+            if let Some((oldest, _)) =
+                self.entries.iter().min_by_key(|(_, e)| e.created_at)
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(
+            key,
+            Entry {
+                handle,
+                created_at: now,
+            },
+        );
+    }
+
+    #[allow(missing_docs)]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[allow(missing_docs)]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // drop the expired entries, only when the cache is full,
+    // a scan of the whole map is too costly for every submission
+    fn evict(&mut self, now: i64) {
+        let expired = self
+            .entries
+            .iter()
+            .filter(|(_, e)| e.created_at + IDEMPOTENCY_KEY_TTL_SECS < now)
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>();
+        for k in expired {
+            self.entries.remove(&k);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn idempotency_cache() {
+        // the cache is on disk, start from an empty one on every run
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let mut cache = IdempotencyCache::new(&format!("test/idempotency_keys/{nanos}"));
+        let h0 = TxnHandle("h0".to_owned());
+        let h1 = TxnHandle("h1".to_owned());
+
+        assert!(IdempotencyCache::check_key("").is_err());
+        assert!(
+            IdempotencyCache::check_key(&"k".repeat(MAX_IDEMPOTENCY_KEY_LEN + 1))
+                .is_err()
+        );
+        assert!(IdempotencyCache::check_key("k0").is_ok());
+
+        assert_eq!(cache.get("k0", &h0).unwrap(), None);
+        cache.insert("k0".to_owned(), h0.clone());
+        assert_eq!(cache.get("k0", &h0).unwrap(), Some(h0.clone()));
+        assert!(cache.get("k0", &h1).is_err());
+        assert_eq!(cache.get("k1", &h1).unwrap(), None);
+        assert_eq!(cache.len(), 1);
+    }
+}
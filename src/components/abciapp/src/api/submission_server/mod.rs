@@ -2,15 +2,23 @@
 //! # service of operating tx
 //!
 
+pub mod idempotency;
+pub mod pending;
 pub mod submission_api;
 
 use {
+    crate::api::request_id::txn_event,
     fp_utils::tx::EVM_TX_TAG,
+    idempotency::IdempotencyCache,
     ledger::{
-        data_model::{BlockEffect, Transaction, TxnEffect, TxnSID, TxnTempSID, TxoSID},
-        store::LedgerState,
+        data_model::{
+            BlockEffect, Transaction, TxnEffect, TxnResult, TxnSID, TxnTempSID,
+            TxnValidationError, TxoSID,
+        },
+        store::{api_cache::get_related_addresses, LedgerState},
     },
     parking_lot::RwLock,
+    pending::PendingCache,
     rand_core::{CryptoRng, RngCore},
     ruc::*,
     serde::{Deserialize, Serialize},
@@ -43,6 +51,54 @@ pub enum TxnStatus {
     Pending,
 }
 
+/// Where a transaction is in its lifecycle,
+/// `Received -> Checked -> Delivered -> Committed | Rejected`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TxnLifecycle {
+    /// forwarded to tendermint, waiting for `check_tx`
+    Received,
+    /// accepted into the mem-pool
+    Checked,
+    /// applied to the block being built
+    Delivered {
+        /// tendermint height of the block
+        height: u64,
+    },
+    /// committed to the ledger
+    Committed {
+        /// tendermint height of the block
+        height: u64,
+        #[allow(missing_docs)]
+        txn_sid: TxnSID,
+        #[allow(missing_docs)]
+        txo_sids: Vec<TxoSID>,
+    },
+    /// rejected by `check_tx` or `deliver_tx`
+    Rejected {
+        /// the ABCI result code
+        code: u32,
+        #[allow(missing_docs)]
+        log: String,
+    },
+}
+
+/// The result of checking a transaction against the current ledger state,
+/// nothing is applied
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SimulatedTxn {
+    /// the sid the transaction would get as the first one of the next block
+    pub txn_sid: Option<TxnSID>,
+    /// the sids of the outputs the transaction would create
+    pub txo_sids: Vec<TxoSID>,
+    /// the minimum fee the transaction has to pay
+    pub min_fee: u64,
+    /// why the transaction would be rejected, `None` if it would be committed
+    pub error: Option<String>,
+    /// the kind of the rejection, if classified
+    pub kind: Option<TxnValidationError>,
+}
+
 /// use to create submissionServer
 pub enum CommitMode {
     /// all block
@@ -53,12 +109,34 @@ pub enum CommitMode {
     Manual, // Somebody else calls commit. Not this code.
 }
 
+/// Depth and counters of the queue of a `TxnForward`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ForwardQueueStat {
+    /// transactions waiting in the queue
+    pub depth: u64,
+    #[allow(missing_docs)]
+    pub capacity: u64,
+    /// transactions spilled to disk, waiting to be queued again
+    pub spilled: u64,
+    /// transactions forwarded since the start of the node
+    pub forwarded_cnt: u64,
+    /// retries since the start of the node
+    pub retried_cnt: u64,
+    /// transactions spilled since the start of the node
+    pub spilled_cnt: u64,
+}
+
 /// Define txforward trait,
 /// the impl of different functions is different, and the specific impl is in
 /// `src/components/abciapp/server/tx_sender.rs`
 #[allow(missing_docs)]
 pub trait TxnForward: AsRef<str> {
     fn forward_txn(&self, txn: Transaction) -> Result<()>;
+
+    /// `None` if the forwarder does not queue transactions
+    fn queue_stat(&self) -> Option<ForwardQueueStat> {
+        None
+    }
 }
 
 /// Define SubmissionServer
@@ -71,6 +149,10 @@ where
     block: Option<BlockEffect>,
     pending_txns: Vec<(TxnTempSID, TxnHandle, Transaction)>,
     txn_status: HashMap<TxnHandle, TxnStatus>,
+    // final states of the transactions, with the details missed by `txn_status`
+    txn_lifecycle: HashMap<TxnHandle, TxnLifecycle>,
+    pending_cache: Arc<RwLock<PendingCache>>,
+    idempotency_keys: IdempotencyCache,
     block_capacity: usize,
     prng: RNG,
     commit_mode: CommitMode,
@@ -93,6 +175,9 @@ where
             committed_state: ledger_state,
             block: None,
             txn_status: HashMap::new(),
+            txn_lifecycle: HashMap::new(),
+            pending_cache: Arc::new(RwLock::new(PendingCache::default())),
+            idempotency_keys: IdempotencyCache::default(),
             pending_txns: vec![],
             prng,
             block_capacity,
@@ -111,6 +196,9 @@ where
             committed_state: ledger_state,
             block: None,
             txn_status: HashMap::new(),
+            txn_lifecycle: HashMap::new(),
+            pending_cache: Arc::new(RwLock::new(PendingCache::default())),
+            idempotency_keys: IdempotencyCache::default(),
             pending_txns: vec![],
             prng,
             block_capacity: 0,
@@ -124,6 +212,53 @@ where
         self.txn_status.get(txn_handle).cloned()
     }
 
+    /// Where a transaction is in its lifecycle, `None` if it is unknown to this node
+    pub fn get_txn_lifecycle(&self, txn_handle: &TxnHandle) -> Option<TxnLifecycle> {
+        if let Some(s) = self.txn_lifecycle.get(txn_handle) {
+            return Some(s.clone());
+        }
+        if let Some(t) = self.pending_cache.read().get(txn_handle) {
+            return Some(t.lifecycle());
+        }
+        self.txn_status.get(txn_handle).and_then(|s| match s {
+            // submitted through other nodes
+            TxnStatus::Pending => Some(TxnLifecycle::Delivered {
+                height: self.committed_state.read().get_staking().cur_height(),
+            }),
+            TxnStatus::Rejected(log) => Some(TxnLifecycle::Rejected {
+                code: 1,
+                log: log.clone(),
+            }),
+            // always recorded in `txn_lifecycle` by `end_block`
+            TxnStatus::Committed(_) => None,
+        })
+    }
+
+    /// Called when `deliver_tx` fails,
+    /// the transaction may have been rejected before `cache_transaction`
+    pub fn txn_rejected(&mut self, txn_handle: TxnHandle, code: u32, log: &str) {
+        txn_event(
+            &txn_handle.0,
+            true,
+            format!("rejected by deliver_tx, code {code}: {log}"),
+        );
+        self.pending_cache.write().remove(&txn_handle);
+        self.txn_status
+            .insert(txn_handle.clone(), TxnStatus::Rejected(log.to_owned()));
+        self.txn_lifecycle.insert(
+            txn_handle,
+            TxnLifecycle::Rejected {
+                code,
+                log: log.to_owned(),
+            },
+        );
+    }
+
+    /// Transactions forwarded to tendermint but not committed yet
+    pub fn pending_cache(&self) -> Arc<RwLock<PendingCache>> {
+        self.pending_cache.clone()
+    }
+
     /// Determine if block is empty
     pub fn all_commited(&self) -> bool {
         self.block.is_none()
@@ -185,8 +320,27 @@ where
             let finalized_txns = ledger.finish_block(block).c(d!())?;
 
             // Update status of all committed transactions
+            let height = ledger.get_staking().cur_height();
+            let mut pending_cache = self.pending_cache.write();
             for (txn_temp_sid, handle, _txn) in self.pending_txns.drain(..) {
                 let committed_txn_info = finalized_txns.get(&txn_temp_sid).c(d!())?;
+                txn_event(
+                    &handle.0,
+                    true,
+                    format!(
+                        "committed at height {height} as txn {}",
+                        committed_txn_info.0 .0
+                    ),
+                );
+                pending_cache.remove(&handle);
+                self.txn_lifecycle.insert(
+                    handle.clone(),
+                    TxnLifecycle::Committed {
+                        height,
+                        txn_sid: committed_txn_info.0,
+                        txo_sids: committed_txn_info.1.clone(),
+                    },
+                );
                 self.txn_status
                     .insert(handle, TxnStatus::Committed(committed_txn_info.clone()));
             }
@@ -205,7 +359,7 @@ where
 
     /// The transaction will be applied to the effect_block after a series of judgments,
     /// and will be classified as pending or rejected depending on the result of the processing.
-    pub fn cache_transaction(&mut self, txn: Transaction) -> Result<TxnHandle> {
+    pub fn cache_transaction(&mut self, txn: Transaction) -> TxnResult<TxnHandle> {
         // Begin a block if the previous one has been commited
         if self.all_commited() {
             self.begin_block();
@@ -215,20 +369,20 @@ where
         let mut block = self.block.as_mut().unwrap();
         let ledger = self.committed_state.read();
         let handle = TxnHandle::new(&txn);
-        let temp_sid = TxnEffect::compute_effect(txn.clone())
-            .c(d!("Failed to compute txn effect"))
-            .and_then(|txn_effect| {
-                ledger
-                    .apply_transaction(&mut block, txn_effect)
-                    .c(d!("Failed to apply transaction"))
-            });
+        let features = ledger.get_staking().active_features();
+        let temp_sid = TxnEffect::compute_effect(txn.clone(), &features)
+            .and_then(|txn_effect| ledger.apply_transaction(&mut block, txn_effect));
         match temp_sid {
             Ok(temp_sid) => {
+                self.pending_cache
+                    .write()
+                    .delivered(&handle, ledger.get_staking().cur_height());
                 self.pending_txns.push((temp_sid, handle.clone(), txn));
                 self.txn_status.insert(handle.clone(), TxnStatus::Pending);
                 Ok(handle)
             }
             Err(e) => {
+                self.pending_cache.write().remove(&handle);
                 self.txn_status
                     .insert(handle, TxnStatus::Rejected(e.to_string()));
                 Err(e)
@@ -236,10 +390,80 @@ where
         }
     }
 
+    /// Run the checks of `check_tx` and `deliver_tx` on a transaction,
+    /// without forwarding or applying it
+    pub fn simulate_transaction(&self, txn: Transaction) -> SimulatedTxn {
+        let fee_params = self
+            .committed_state
+            .read()
+            .get_staking()
+            .params()
+            .fee_params();
+        let mut res = SimulatedTxn {
+            min_fee: txn.min_fee_with(&fee_params),
+            ..Default::default()
+        };
+
+        let checked: TxnResult<_> = if !txn.valid_in_abci_with(&fee_params) {
+            Err(eg!("insufficient fee or coinbase transaction").into())
+        } else {
+            txn.check_tx()
+                .and_then(|_| self.committed_state.read().simulate_transaction(txn))
+        };
+        match checked {
+            Ok((txn_sid, txo_sids)) => {
+                res.txn_sid = Some(txn_sid);
+                res.txo_sids = txo_sids;
+            }
+            Err(e) => {
+                res.kind = e.kind();
+                res.error = Some(e.to_string());
+            }
+        }
+
+        res
+    }
+
+    /// Handle a transaction tagged with an idempotency key by the client,
+    /// a retry with the same key returns the handle of the first submission
+    /// without forwarding the transaction again
+    pub fn handle_transaction_with_key(
+        &mut self,
+        txn: Transaction,
+        key: Option<String>,
+    ) -> Result<TxnHandle> {
+        let key = match key {
+            Some(key) => key,
+            None => return self.handle_transaction(txn).c(d!()),
+        };
+        IdempotencyCache::check_key(&key).c(d!())?;
+        if let Some(handle) = self
+            .idempotency_keys
+            .get(&key, &TxnHandle::new(&txn))
+            .c(d!())?
+        {
+            return Ok(handle);
+        }
+
+        // only the forwarded ones are remembered, a failed submission can be retried
+        let handle = self.handle_transaction(txn).c(d!())?;
+        self.idempotency_keys.insert(key, handle.clone());
+        Ok(handle)
+    }
+
     /// Handle the whole process when there's a new transaction
     pub fn handle_transaction(&mut self, txn: Transaction) -> Result<TxnHandle> {
         let txn_handle = TxnHandle::new(&txn);
-        self.txn_forwarder.forward_txn(txn).c(d!())?;
+        // track it before forwarding, `check_tx` may come at any time,
+        // a resubmitted transaction starts its lifecycle over
+        self.txn_lifecycle.remove(&txn_handle);
+        self.pending_cache
+            .write()
+            .insert(txn_handle.clone(), get_related_addresses(&txn, |_| {}));
+        self.txn_forwarder.forward_txn(txn).c(d!()).map_err(|e| {
+            self.pending_cache.write().remove(&txn_handle);
+            e
+        })?;
         Ok(txn_handle)
     }
 
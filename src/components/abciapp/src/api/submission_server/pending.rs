@@ -0,0 +1,220 @@
+//!
+//! # Transactions forwarded to tendermint but not committed yet
+//!
+//! Shared by the submission server and the ABCI callbacks,
+//! so that `check_tx` can record its result without locking the ledger.
+//!
+
+use {
+    super::{TxnHandle, TxnLifecycle},
+    chrono::Utc,
+    ledger::data_model::XfrAddress,
+    serde::{Deserialize, Serialize},
+    std::{
+        cmp::Ordering,
+        collections::{HashMap, HashSet},
+    },
+};
+
+/// Entries older than this are dropped,
+/// the transaction has been evicted from the mem-pool or committed elsewhere
+pub const PENDING_TXN_TTL_SECS: i64 = 600;
+
+/// The oldest entry is dropped when the cache is full
+pub const MAX_PENDING_TXNS: usize = 10_000;
+
+/// Result of the `check_tx` of tendermint
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CheckTxResult {
+    pub code: u32,
+    pub log: String,
+}
+
+/// A transaction which has not been committed
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PendingTxn {
+    /// handle returned by `submit_transaction`
+    pub handle: TxnHandle,
+    /// when the transaction was forwarded to tendermint, in unix seconds
+    pub received_at: i64,
+    /// `None` if it has not been checked by tendermint
+    pub check_tx: Option<CheckTxResult>,
+    /// tendermint height of the block including it, `None` if still in the mem-pool
+    pub delivered_height: Option<u64>,
+    #[serde(skip)]
+    addresses: HashSet<XfrAddress>,
+}
+
+impl PendingTxn {
+    // the cache is not ordered, this order does not depend on it
+    fn order(&self, other: &Self) -> Ordering {
+        self.received_at
+            .cmp(&other.received_at)
+            .then_with(|| self.handle.0.cmp(&other.handle.0))
+    }
+
+    /// Where it is in its lifecycle
+    pub fn lifecycle(&self) -> TxnLifecycle {
+        match (&self.check_tx, self.delivered_height) {
+            (_, Some(height)) => TxnLifecycle::Delivered { height },
+            (None, None) => TxnLifecycle::Received,
+            (Some(r), None) if 0 == r.code => TxnLifecycle::Checked,
+            (Some(r), None) => TxnLifecycle::Rejected {
+                code: r.code,
+                log: r.log.clone(),
+            },
+        }
+    }
+}
+
+/// Cache of the pending transactions, keyed by their handles
+#[derive(Debug, Default)]
+pub struct PendingCache {
+    txns: HashMap<TxnHandle, PendingTxn>,
+}
+
+impl PendingCache {
+    /// Track a transaction forwarded to tendermint
+    pub fn insert(&mut self, handle: TxnHandle, addresses: HashSet<XfrAddress>) {
+        let now = Utc::now().timestamp();
+        self.evict(now);
+        if MAX_PENDING_TXNS <= self.txns.len() {
+            if let Some(oldest) = self
+                .txns
+                .values()
+                .min_by(|a, b| a.order(b))
+                .map(|t| t.handle.clone())
+            {
+                self.txns.remove(&oldest);
+            }
+        }
+
+        self.txns.insert(
+            handle.clone(),
+            PendingTxn {
+                handle,
+                received_at: now,
+                check_tx: None,
+                delivered_height: None,
+                addresses,
+            },
+        );
+    }
+
+    /// Record the result of `check_tx`, untracked transactions are ignored
+    pub fn checked(&mut self, handle: &TxnHandle, code: u32, log: &str) {
+        if let Some(t) = self.txns.get_mut(handle) {
+            t.check_tx = Some(CheckTxResult {
+                code,
+                log: log.to_owned(),
+            });
+        }
+    }
+
+    /// The transaction has been applied to the block being built
+    pub fn delivered(&mut self, handle: &TxnHandle, height: u64) {
+        if let Some(t) = self.txns.get_mut(handle) {
+            t.delivered_height = Some(height);
+        }
+    }
+
+    /// The transaction has been committed or rejected
+    #[inline(always)]
+    pub fn remove(&mut self, handle: &TxnHandle) {
+        self.txns.remove(handle);
+    }
+
+    #[allow(missing_docs)]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.txns.is_empty()
+    }
+
+    #[allow(missing_docs)]
+    #[inline(always)]
+    pub fn get(&self, handle: &TxnHandle) -> Option<PendingTxn> {
+        self.txns.get(handle).cloned()
+    }
+
+    /// Pending transactions related to an address, the earliest first,
+    /// those received in the same second in the order of their handles
+    pub fn related_to(&self, address: &XfrAddress) -> Vec<PendingTxn> {
+        let mut txns = self
+            .txns
+            .values()
+            .filter(|t| t.addresses.contains(address))
+            .cloned()
+            .collect::<Vec<_>>();
+        txns.sort_by(|a, b| a.order(b));
+        txns
+    }
+
+    fn evict(&mut self, now: i64) {
+        self.txns
+            .retain(|_, t| now - t.received_at <= PENDING_TXN_TTL_SECS);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, rand_chacha::ChaChaRng, rand_core::SeedableRng, zei::XfrKeyPair};
+
+    #[test]
+    fn pending_cache() {
+        let mut prng = ChaChaRng::from_seed([0u8; 32]);
+        let alice = XfrAddress {
+            key: XfrKeyPair::generate(&mut prng).get_pk(),
+        };
+        let bob = XfrAddress {
+            key: XfrKeyPair::generate(&mut prng).get_pk(),
+        };
+
+        let mut cache = PendingCache::default();
+        let h0 = TxnHandle("h0".to_owned());
+        let h1 = TxnHandle("h1".to_owned());
+        cache.insert(h0.clone(), [alice].into_iter().collect());
+        cache.insert(h1.clone(), [alice, bob].into_iter().collect());
+        assert_eq!(cache.related_to(&alice).len(), 2);
+        assert_eq!(cache.related_to(&bob).len(), 1);
+
+        assert_eq!(cache.get(&h0).unwrap().lifecycle(), TxnLifecycle::Received);
+        cache.checked(&h0, 0, "");
+        cache.checked(&TxnHandle("untracked".to_owned()), 1, "");
+        assert_eq!(cache.get(&h0).unwrap().lifecycle(), TxnLifecycle::Checked);
+        cache.delivered(&h0, 10);
+        let t = cache.get(&h0).unwrap();
+        assert_eq!(t.lifecycle(), TxnLifecycle::Delivered { height: 10 });
+        assert!(cache.get(&h1).unwrap().check_tx.is_none());
+
+        cache.checked(&h1, 1, "invalid");
+        assert!(matches!(
+            cache.get(&h1).unwrap().lifecycle(),
+            TxnLifecycle::Rejected { code: 1, .. }
+        ));
+
+        cache.remove(&h0);
+        assert!(cache.get(&h0).is_none());
+
+        // received in the same second, in the order of the handles
+        let h2 = TxnHandle("h2".to_owned());
+        cache.insert(h2.clone(), [bob].into_iter().collect());
+        cache.txns.get_mut(&h1).unwrap().received_at = 100;
+        cache.txns.get_mut(&h2).unwrap().received_at = 100;
+        let related = |cache: &PendingCache| {
+            cache
+                .related_to(&bob)
+                .into_iter()
+                .map(|t| t.handle)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(related(&cache), vec![h1.clone(), h2.clone()]);
+        cache.txns.get_mut(&h2).unwrap().received_at = 99;
+        assert_eq!(related(&cache), vec![h2.clone(), h1.clone()]);
+        cache.remove(&h2);
+
+        cache.txns.get_mut(&h1).unwrap().received_at -= PENDING_TXN_TTL_SECS + 1;
+        cache.evict(Utc::now().timestamp());
+        assert!(cache.is_empty());
+    }
+}
@@ -3,11 +3,30 @@
 //!
 
 use {
-    super::{SubmissionServer, TxnForward, TxnHandle},
-    actix_cors::Cors,
-    actix_web::{error, middleware, web, App, HttpServer},
+    super::{
+        idempotency::{IdempotencyCache, IDEMPOTENCY_KEY_HEADER},
+        pending::PendingTxn,
+        ForwardQueueStat, SimulatedTxn, SubmissionServer, TxnForward, TxnHandle,
+        TxnLifecycle,
+    },
+    crate::{
+        abci::shutdown,
+        api::{
+            address::parse_address,
+            auth::Auth,
+            cors,
+            error::{
+                extractor_error, internal, invalid_param, not_found, txn_rejected,
+                unavailable,
+            },
+            request_id::{request_id, track_txn, txn_event, RequestTracing},
+            validation::PayloadLimit,
+        },
+    },
+    actix_web::{middleware, web, App, HttpRequest, HttpServer},
+    config::abci::global_cfg::CFG,
     finutils::api::NetworkRoute,
-    ledger::data_model::Transaction,
+    ledger::data_model::{Transaction, XfrAddress},
     parking_lot::RwLock,
     rand_core::{CryptoRng, RngCore},
     ruc::*,
@@ -34,6 +53,7 @@ async fn version() -> actix_web::Result<String> {
 
 /// Sending transactions to tendermint
 pub async fn submit_transaction<RNG, TF>(
+    req: HttpRequest,
     data: web::Data<Arc<RwLock<SubmissionServer<RNG, TF>>>>,
     body: web::Json<Transaction>,
 ) -> StdResult<web::Json<TxnHandle>, actix_web::error::Error>
@@ -41,18 +61,57 @@ where
     RNG: RngCore + CryptoRng,
     TF: TxnForward + Sync + Send,
 {
+    if shutdown::is_shutting_down() {
+        return Err(unavailable("the node is shutting down"));
+    }
+
+    // a retry with the same `Idempotency-Key` gets the handle of the first submission
+    let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(v) => {
+            let key = v
+                .to_str()
+                .map_err(|_| invalid_param("the idempotency key should be ASCII"))?;
+            IdempotencyCache::check_key(key)
+                .map_err(|e| invalid_param(e.to_string()))?;
+            Some(key.to_owned())
+        }
+        None => None,
+    };
+
     let tx = body.into_inner();
 
+    // tracked before forwarding, the forward workers may log it at any time
+    let handle = TxnHandle::new(&tx);
+    if let Some(id) = request_id(&req) {
+        track_txn(&handle.0, &id);
+    }
+    txn_event(&handle.0, false, "received");
+
     let mut submission_server = data.write();
     submission_server
-        .handle_transaction(tx)
+        .handle_transaction_with_key(tx, key)
         .map(web::Json)
         .map_err(|e| {
             e.print(None);
-            error::ErrorBadRequest(e.to_string())
+            txn_event(&handle.0, true, format_args!("not forwarded: {e}"));
+            txn_rejected(e.to_string())
         })
 }
 
+/// Check a transaction against the current ledger state without submitting it,
+/// wallets can pre-validate a transaction before broadcasting it
+pub async fn simulate_txn<RNG, TF>(
+    data: web::Data<Arc<RwLock<SubmissionServer<RNG, TF>>>>,
+    body: web::Json<Transaction>,
+) -> actix_web::Result<web::Json<SimulatedTxn>>
+where
+    RNG: RngCore + CryptoRng,
+    TF: TxnForward + Sync + Send,
+{
+    let tx = body.into_inner();
+    Ok(web::Json(data.read().simulate_transaction(tx)))
+}
+
 /// Queries the status of a transaction by its handle. Returns either a not committed message or a
 /// serialized TxnStatus.
 pub async fn txn_status<RNG, TF>(
@@ -65,16 +124,79 @@ where
 {
     let submission_server = data.write();
     let txn_status = submission_server.get_txn_status(&TxnHandle(info.clone()));
-    let res = if let Some(status) = txn_status {
-        serde_json::to_string(&status)?
+    if let Some(status) = txn_status {
+        serde_json::to_string(&status).map_err(internal)
     } else {
-        format!(
+        Err(not_found(format!(
             "No transaction with handle {} found. Please retry with a new handle.",
             &info
-        )
-    };
+        )))
+    }
+}
+
+/// Where a transaction is in its lifecycle, see `TxnLifecycle`
+pub async fn get_txn_status<RNG, TF>(
+    data: web::Data<Arc<RwLock<SubmissionServer<RNG, TF>>>>,
+    info: web::Path<String>,
+) -> actix_web::Result<web::Json<TxnLifecycle>>
+where
+    RNG: RngCore + CryptoRng,
+    TF: TxnForward + Sync + Send,
+{
+    let status = data.read().get_txn_lifecycle(&TxnHandle(info.clone()));
+    status
+        .map(web::Json)
+        .ok_or_else(|| not_found(format!("No transaction with handle {}", &info)))
+}
+
+/// A transaction forwarded to tendermint but not committed yet,
+/// reports its `check_tx` result and whether it has been included in a block
+pub async fn pending_txn<RNG, TF>(
+    data: web::Data<Arc<RwLock<SubmissionServer<RNG, TF>>>>,
+    info: web::Path<String>,
+) -> actix_web::Result<web::Json<PendingTxn>>
+where
+    RNG: RngCore + CryptoRng,
+    TF: TxnForward + Sync + Send,
+{
+    let pending_cache = data.read().pending_cache();
+    let txn = pending_cache.read().get(&TxnHandle(info.clone()));
+    txn.map(web::Json).ok_or_else(|| {
+        not_found(format!(
+            "No pending transaction with handle {}, see `txn_status`.",
+            &info
+        ))
+    })
+}
 
-    Ok(res)
+/// Pending transactions related to an address, the earliest first
+pub async fn pending_txns_for<RNG, TF>(
+    data: web::Data<Arc<RwLock<SubmissionServer<RNG, TF>>>>,
+    info: web::Path<String>,
+) -> actix_web::Result<web::Json<Vec<PendingTxn>>>
+where
+    RNG: RngCore + CryptoRng,
+    TF: TxnForward + Sync + Send,
+{
+    let key = parse_address(&info)
+        .c(d!())
+        .map_err(|e| invalid_param(e.to_string()))?;
+    let pending_cache = data.read().pending_cache();
+    let txns = pending_cache.read().related_to(&XfrAddress { key });
+    Ok(web::Json(txns))
+}
+
+/// Depth and counters of the queue forwarding transactions to tendermint
+pub async fn forward_queue<RNG, TF>(
+    data: web::Data<Arc<RwLock<SubmissionServer<RNG, TF>>>>,
+) -> actix_web::Result<web::Json<ForwardQueueStat>>
+where
+    RNG: RngCore + CryptoRng,
+    TF: TxnForward + Sync + Send,
+{
+    let stat = data.read().get_fwder().queue_stat();
+    stat.map(web::Json)
+        .ok_or_else(|| not_found("Transactions are not queued by this node"))
 }
 
 /// Structures exposed to the outside world
@@ -84,7 +206,12 @@ pub struct SubmissionApi;
 #[allow(missing_docs)]
 pub enum SubmissionRoutes {
     SubmitTransaction,
+    SimulateTxn,
     TxnStatus,
+    GetTxnStatus,
+    PendingTxn,
+    PendingTxnsFor,
+    ForwardQueue,
     Ping,
     Version,
 }
@@ -93,7 +220,12 @@ impl NetworkRoute for SubmissionRoutes {
     fn route(&self) -> String {
         let endpoint = match *self {
             SubmissionRoutes::SubmitTransaction => "submit_transaction",
+            SubmissionRoutes::SimulateTxn => "simulate_txn",
             SubmissionRoutes::TxnStatus => "txn_status",
+            SubmissionRoutes::GetTxnStatus => "get_txn_status",
+            SubmissionRoutes::PendingTxn => "pending_txn",
+            SubmissionRoutes::PendingTxnsFor => "pending_txns_for",
+            SubmissionRoutes::ForwardQueue => "forward_queue",
             SubmissionRoutes::Ping => "ping",
             SubmissionRoutes::Version => "version",
         };
@@ -113,26 +245,56 @@ impl SubmissionApi {
     ) -> Result<SubmissionApi> {
         let _ = actix_rt::System::new("findora API");
 
-        HttpServer::new(move || {
+        let server = HttpServer::new(move || {
+            let limit = PayloadLimit::new(CFG.limits.submission_payload_limit);
             App::new()
+                .wrap(limit)
+                .wrap(Auth)
+                .wrap(RequestTracing)
                 .wrap(middleware::Logger::default())
-                .wrap(Cors::permissive().supports_credentials())
-                .data(web::JsonConfig::default().limit(2048 * 1024))
+                .wrap(cors())
+                .app_data(
+                    web::JsonConfig::default()
+                        .limit(limit.largest())
+                        .error_handler(extractor_error),
+                )
+                .app_data(web::PathConfig::default().error_handler(extractor_error))
                 .data(submission_server.clone())
                 .route(
                     &SubmissionRoutes::SubmitTransaction.route(),
                     web::post().to(submit_transaction::<RNG, TF>),
                 )
+                .route(
+                    &SubmissionRoutes::SimulateTxn.route(),
+                    web::post().to(simulate_txn::<RNG, TF>),
+                )
+                .route(
+                    &SubmissionRoutes::ForwardQueue.route(),
+                    web::get().to(forward_queue::<RNG, TF>),
+                )
                 .route(&SubmissionRoutes::Ping.route(), web::get().to(ping))
                 .route(&SubmissionRoutes::Version.route(), web::get().to(version))
                 .route(
                     &SubmissionRoutes::TxnStatus.with_arg_template("handle"),
                     web::get().to(txn_status::<RNG, TF>),
                 )
+                .route(
+                    &SubmissionRoutes::GetTxnStatus.with_arg_template("handle"),
+                    web::get().to(get_txn_status::<RNG, TF>),
+                )
+                .route(
+                    &SubmissionRoutes::PendingTxn.with_arg_template("handle"),
+                    web::get().to(pending_txn::<RNG, TF>),
+                )
+                .route(
+                    &SubmissionRoutes::PendingTxnsFor.with_arg_template("address"),
+                    web::get().to(pending_txns_for::<RNG, TF>),
+                )
         })
         .bind(&format!("{host}:{port}"))
         .c(d!())?
         .run();
+        shutdown::register_server(server);
 
         info!("Submission server started");
 
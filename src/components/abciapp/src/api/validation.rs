@@ -0,0 +1,175 @@
+//!
+//! # Sanity limits of the requests
+//!
+//! The `PayloadLimit` middleware rejects the requests whose declared body
+//! exceeds the limit of their route, or whose URI is unreasonably long,
+//! before any handler runs. Bodies without a `Content-Length` are still
+//! capped by the JSON extractor at the largest limit of the service.
+//!
+//! Handlers taking a comma separated list parse it by `parse_list`,
+//! which bounds its length and never panics on a malformed item.
+//!
+
+use {
+    super::error::{invalid_param, payload_too_large},
+    actix_service::{Service, Transform},
+    actix_web::{
+        dev::{ServiceRequest, ServiceResponse},
+        error::Error as ActixError,
+        http::header,
+    },
+    config::abci::global_cfg::CFG,
+    futures::future::{ready, Either, Ready},
+    std::{
+        fmt,
+        task::{Context, Poll},
+    },
+};
+
+/// The longest path and query string of a request
+pub const MAX_URI_LEN: usize = 8 * 1024;
+
+/// Parse a comma separated list of at most `max` items, bounded by
+/// `max_batch_len` of the config too, a malformed item is a 400 error
+pub fn parse_list<T, E, F>(
+    list: &str,
+    max: usize,
+    parse: F,
+) -> Result<Vec<T>, ActixError>
+where
+    E: fmt::Display,
+    F: Fn(&str) -> Result<T, E>,
+{
+    let max = max.min(CFG.limits.max_batch_len);
+    let items = list.split(',').map(str::trim).collect::<Vec<_>>();
+    if items.len() > max {
+        return Err(invalid_param(format!("at most {max} items at a time")));
+    }
+    items
+        .into_iter()
+        .map(|i| {
+            if i.is_empty() {
+                return Err(invalid_param("empty item in the list"));
+            }
+            parse(i).map_err(|e| invalid_param(format!("invalid item `{i}`: {e}")))
+        })
+        .collect()
+}
+
+/// Reject the requests beyond the limits, see the module doc
+#[derive(Clone, Copy)]
+pub struct PayloadLimit {
+    default: usize,
+}
+
+impl PayloadLimit {
+    /// `default` is the limit of the routes not configured separately
+    pub fn new(default: usize) -> Self {
+        PayloadLimit { default }
+    }
+
+    /// The largest body of any route, the limit of the JSON extractor
+    pub fn largest(&self) -> usize {
+        CFG.limits
+            .route_payload_limits
+            .values()
+            .copied()
+            .fold(self.default, usize::max)
+    }
+}
+
+impl<S, B> Transform<S> for PayloadLimit
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = ActixError,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = ActixError;
+    type InitError = ();
+    type Transform = PayloadLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(PayloadLimitMiddleware {
+            service,
+            default: self.default,
+        }))
+    }
+}
+
+#[allow(missing_docs)]
+pub struct PayloadLimitMiddleware<S> {
+    service: S,
+    default: usize,
+}
+
+impl<S, B> Service for PayloadLimitMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = ActixError,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = ActixError;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        if req.path().len() + req.query_string().len() > MAX_URI_LEN {
+            return Either::Right(ready(Err(invalid_param(format!(
+                "the URI is longer than {MAX_URI_LEN} bytes"
+            )))));
+        }
+
+        let limit = CFG.limits.payload_limit(req.path(), self.default);
+        let declared = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .map(|v| v.to_str().ok().and_then(|v| v.parse::<usize>().ok()));
+        match declared {
+            Some(None) => {
+                Either::Right(ready(Err(invalid_param("invalid Content-Length"))))
+            }
+            Some(Some(len)) if len > limit => Either::Right(ready(Err(
+                payload_too_large(format!("the body is larger than {limit} bytes")),
+            ))),
+            _ => Either::Left(self.service.call(req)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let parse = |l: &str, max| parse_list(l, max, |i| i.parse::<u64>());
+
+        assert_eq!(vec![1, 2, 3], parse("1, 2,3", 10).unwrap());
+        assert!(parse("1,,3", 10).is_err());
+        assert!(parse("1,x", 10).is_err());
+        assert!(parse("", 10).is_err());
+        assert!(parse("1,2,3", 2).is_err());
+
+        // bounded by the config whatever the route allows
+        let max = CFG.limits.max_batch_len;
+        let list = vec!["7"; max + 1].join(",");
+        assert!(parse(&list, usize::MAX).is_err());
+        assert_eq!(max, parse(&list[2..], usize::MAX).unwrap().len());
+    }
+}
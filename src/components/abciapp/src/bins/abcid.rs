@@ -3,16 +3,17 @@
 //!
 
 use {
-    abciapp::abci,
-    ruc::*,
-    std::{
-        sync::{atomic::Ordering, mpsc::channel},
-        thread,
+    abciapp::{
+        abci::{self, shutdown},
+        api::logging,
     },
+    config::abci::global_cfg::CFG,
+    ruc::*,
+    std::{process, thread},
 };
 
 fn main() {
-    globutils::logging::init_logging(None);
+    pnk!(logging::init(CFG.log_json, CFG.log_level.as_deref()));
     tracing::info!(target: "abciapp", concat!(
         "Build: ",
         env!("VERGEN_SHA"),
@@ -20,23 +21,19 @@ fn main() {
         env!("VERGEN_BUILD_DATE")
     ));
 
-    let thread = thread::spawn(|| pnk!(abci::run()));
-
-    let (tx, rx) = channel();
-
-    pnk!(ctrlc::set_handler(move || {
-        println!("Waiting to exit.");
-        abci::IS_EXITING.store(true, Ordering::SeqCst);
-        while !abci::IN_SAFE_ITV.load(Ordering::SeqCst) {
-            sleep_ms!(10);
+    // `run` returns only in the one-shot modes, such as the snapshot export
+    thread::spawn(|| match abci::run() {
+        Ok(()) => process::exit(0),
+        Err(e) => {
+            e.print(None);
+            process::exit(1);
         }
+    });
 
-        pnk!(tx.send(()));
-    }));
-
-    pnk!(rx.recv());
+    pnk!(shutdown::wait_for_signal());
+    pnk!(shutdown::shutdown());
 
+    // the ABCI server never returns, so do not wait for it
     println!("Exiting...");
-    thread.thread().unpark();
-    thread.join().unwrap();
+    process::exit(0);
 }
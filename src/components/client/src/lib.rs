@@ -0,0 +1,649 @@
+//!
+//! # Typed client of the REST services
+//!
+//! One async method per route of the query and the submission servers,
+//! the paths are taken from their `NetworkRoute` implementations and the
+//! responses are decoded into the types the servers serialize.
+//!
+//! ```ignore
+//! let client = Client::new(ClientConfig::new("http://127.0.0.1"));
+//! let utxos = client.get_owned_utxos(&pk).await?;
+//! let handle = client.submit(&tx).await?;
+//! ```
+//!
+//! The HTTP requests are blocking ones run by `spawn_blocking`, so any
+//! tokio runtime will do. Failed requests are retried if the node is not
+//! reachable or answers `UNAVAILABLE`, any other error is returned at once.
+//!
+
+#![deny(warnings)]
+#![deny(missing_docs)]
+
+pub mod models;
+
+use {
+    abciapp::api::{
+        error::{ApiError, ErrorCode},
+        query_server::query_api::{ledger_api::ApiRoutes, QueryServerRoutes},
+        submission_server::submission_api::SubmissionRoutes,
+    },
+    finutils::api::NetworkRoute,
+    globutils::wallet,
+    ledger::data_model::{format_amount, parse_amount, ASSET_TYPE_FRA, FRA_DECIMALS},
+    models::*,
+    ruc::*,
+    serde::de::DeserializeOwned,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        time::Duration,
+    },
+};
+
+/// Where the servers are and how patient to be with them
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// base url of the query server, which also serves the ledger routes
+    pub query_url: String,
+    /// base url of the submission server
+    pub submission_url: String,
+    /// timeout of a whole request
+    pub timeout: Duration,
+    /// timeout of the connection
+    pub connect_timeout: Duration,
+    /// number of retries of a failed request
+    pub retries: u32,
+    /// delay before the first retry, doubled for each of the next ones
+    pub retry_delay: Duration,
+}
+
+impl ClientConfig {
+    /// The default ports of `serv_addr`, e.g. `http://127.0.0.1`
+    pub fn new(serv_addr: &str) -> Self {
+        let serv_addr = serv_addr.trim_end_matches('/');
+        ClientConfig {
+            query_url: format!("{serv_addr}:8668"),
+            submission_url: format!("{serv_addr}:8669"),
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(5),
+            retries: 3,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Whether a failed request is worth retrying
+enum Failure {
+    Retry(Box<dyn RucError>),
+    Fatal(Box<dyn RucError>),
+}
+
+#[derive(Clone)]
+enum Method {
+    Get,
+    Post(Vec<u8>),
+}
+
+/// Client of the servers of a node
+#[derive(Clone, Debug)]
+pub struct Client {
+    cfg: ClientConfig,
+}
+
+impl Client {
+    #[allow(missing_docs)]
+    pub fn new(cfg: ClientConfig) -> Self {
+        Client { cfg }
+    }
+
+    #[allow(missing_docs)]
+    pub fn config(&self) -> &ClientConfig {
+        &self.cfg
+    }
+
+    fn send_once(
+        cfg: &ClientConfig,
+        url: &str,
+        method: &Method,
+    ) -> std::result::Result<Vec<u8>, Failure> {
+        let req = match method {
+            Method::Get => attohttpc::get(url),
+            Method::Post(body) => attohttpc::post(url)
+                .header(attohttpc::header::CONTENT_TYPE, "application/json")
+                .bytes(body.clone()),
+        };
+        let resp = req
+            .timeout(cfg.timeout)
+            .connect_timeout(cfg.connect_timeout)
+            .send()
+            .c(d!(url.to_owned()))
+            .map_err(Failure::Retry)?;
+
+        let status = resp.status();
+        let body = resp.bytes().c(d!(url.to_owned())).map_err(Failure::Retry)?;
+        if status.is_success() {
+            return Ok(body);
+        }
+
+        match serde_json::from_slice::<ApiError>(&body) {
+            Ok(e) if e.code == ErrorCode::Unavailable => {
+                Err(Failure::Retry(eg!(e.to_string())))
+            }
+            Ok(e) => Err(Failure::Fatal(eg!(e.to_string()))),
+            Err(_) if status.is_server_error() => Err(Failure::Retry(eg!(format!(
+                "{url}: {status} {}",
+                String::from_utf8_lossy(&body)
+            )))),
+            Err(_) => Err(Failure::Fatal(eg!(format!(
+                "{url}: {status} {}",
+                String::from_utf8_lossy(&body)
+            )))),
+        }
+    }
+
+    async fn send(&self, url: String, method: Method) -> Result<Vec<u8>> {
+        let mut delay = self.cfg.retry_delay;
+        let mut attempt = 0;
+        loop {
+            let cfg = self.cfg.clone();
+            let (u, m) = (url.clone(), method.clone());
+            let res = tokio::task::spawn_blocking(move || Self::send_once(&cfg, &u, &m))
+                .await
+                .c(d!())?;
+            match res {
+                Ok(body) => return Ok(body),
+                Err(Failure::Fatal(e)) => return Err(e),
+                Err(Failure::Retry(e)) if attempt >= self.cfg.retries => return Err(e),
+                Err(Failure::Retry(_)) => {
+                    attempt += 1;
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: String) -> Result<T> {
+        let body = self.send(url.clone(), Method::Get).await.c(d!())?;
+        serde_json::from_slice(&body).c(d!(url))
+    }
+
+    /// For the routes answering a bare string instead of JSON
+    async fn get_text(&self, url: String) -> Result<String> {
+        let body = self.send(url, Method::Get).await.c(d!())?;
+        String::from_utf8(body).c(d!())
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        url: String,
+        tx: &Transaction,
+    ) -> Result<T> {
+        let body = serde_json::to_vec(tx).c(d!())?;
+        let resp = self.send(url.clone(), Method::Post(body)).await.c(d!())?;
+        serde_json::from_slice(&resp).c(d!(url))
+    }
+
+    fn query(&self, path: String) -> String {
+        format!("{}{}", self.cfg.query_url, path)
+    }
+
+    fn submission(&self, path: String) -> String {
+        format!("{}{}", self.cfg.submission_url, path)
+    }
+}
+
+fn b64(pk: &XfrPublicKey) -> String {
+    wallet::public_key_to_base64(pk)
+}
+
+fn join<T: ToString>(ids: impl IntoIterator<Item = T>) -> String {
+    ids.into_iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Routes of the query server
+impl Client {
+    /// The owner of a TXO, in base64
+    pub async fn get_address(&self, sid: TxoSID) -> Result<String> {
+        self.get_text(self.query(QueryServerRoutes::GetAddress.with_arg(&sid.0)))
+            .await
+    }
+
+    /// The spendable TXOs of `pk`
+    pub async fn get_owned_utxos(&self, pk: &XfrPublicKey) -> Result<BTreeSet<TxoSID>> {
+        self.get(self.query(QueryServerRoutes::GetOwnedUtxos.with_arg(&b64(pk))))
+            .await
+    }
+
+    /// The anonymous record of a commitment, in base58
+    pub async fn get_owned_abar(&self, commitment: &str) -> Result<Option<ATxoSID>> {
+        self.get(self.query(QueryServerRoutes::GetOwnedAbars.with_arg(&commitment)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_owner_memo(&self, sid: TxoSID) -> Result<Option<OwnerMemo>> {
+        self.get(self.query(QueryServerRoutes::GetOwnerMemo.with_arg(&sid.0)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_owner_memo_batch(
+        &self,
+        sids: &[TxoSID],
+    ) -> Result<Vec<Option<OwnerMemo>>> {
+        let arg = join(sids.iter().map(|s| s.0));
+        self.get(self.query(QueryServerRoutes::GetOwnerMemoBatch.with_arg(&arg)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_abar_commitment(&self, sid: ATxoSID) -> Result<Option<Commitment>> {
+        self.get(self.query(QueryServerRoutes::GetAbarCommitment.with_arg(&sid.0)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_abar_memo(&self, sid: ATxoSID) -> Result<Option<AxfrOwnerMemo>> {
+        self.get(self.query(QueryServerRoutes::GetAbarMemo.with_arg(&sid.0)))
+            .await
+    }
+
+    /// The memos of the anonymous records from `start` to `end`, at most 100
+    pub async fn get_abar_memos(
+        &self,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<(u64, AxfrOwnerMemo)>> {
+        let path = QueryServerRoutes::GetAbarMemos.route();
+        self.get(self.query(format!("{path}?start={start}&end={end}")))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_abar_proof(&self, sid: ATxoSID) -> Result<Option<MTLeafInfo>> {
+        self.get(self.query(QueryServerRoutes::GetAbarProof.with_arg(&sid.0)))
+            .await
+    }
+
+    /// Whether a nullifier is spent, `None` if it is invalid
+    pub async fn check_nullifier_hash(&self, hash: &str) -> Result<Option<bool>> {
+        self.get(self.query(QueryServerRoutes::CheckNullifierHash.with_arg(&hash)))
+            .await
+    }
+
+    /// Everything to build an anonymous transfer, at most 100 records
+    pub async fn get_abar_transfer_material(
+        &self,
+        sids: &[ATxoSID],
+        nullifiers: &[String],
+    ) -> Result<AbarTransferMaterial> {
+        let path = QueryServerRoutes::GetAbarTransferMaterial.route();
+        let mut url = format!("{path}?atxo_sids={}", join(sids.iter().map(|s| s.0)));
+        if !nullifiers.is_empty() {
+            url += &format!("&nullifiers={}", join(nullifiers));
+        }
+        self.get(self.query(url)).await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_max_atxo_sid(&self) -> Result<Option<usize>> {
+        self.get(self.query(QueryServerRoutes::GetMaxATxoSid.route()))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_max_atxo_sid_at_height(
+        &self,
+        height: u64,
+    ) -> Result<Option<usize>> {
+        self.get(self.query(QueryServerRoutes::GetMaxATxoSidAtHeight.with_arg(&height)))
+            .await
+    }
+
+    /// The transactions involving `pk`
+    pub async fn get_related_txns(&self, pk: &XfrPublicKey) -> Result<BTreeSet<TxnSID>> {
+        self.get(self.query(QueryServerRoutes::GetRelatedTxns.with_arg(&b64(pk))))
+            .await
+    }
+
+    /// The transfers of an asset
+    pub async fn get_related_xfrs(
+        &self,
+        code: &AssetTypeCode,
+    ) -> Result<BTreeSet<TxnSID>> {
+        self.get(
+            self.query(QueryServerRoutes::GetRelatedXfrs.with_arg(&code.to_base64())),
+        )
+        .await
+    }
+
+    /// The assets defined by `pk`
+    pub async fn get_created_assets(
+        &self,
+        pk: &XfrPublicKey,
+    ) -> Result<Vec<DefineAsset>> {
+        self.get(self.query(QueryServerRoutes::GetCreatedAssets.with_arg(&b64(pk))))
+            .await
+    }
+
+    /// The records issued by `pk`
+    pub async fn get_issued_records(
+        &self,
+        pk: &XfrPublicKey,
+    ) -> Result<Vec<(TxOutput, Option<OwnerMemo>)>> {
+        self.get(self.query(QueryServerRoutes::GetIssuedRecords.with_arg(&b64(pk))))
+            .await
+    }
+
+    /// The records issued of an asset
+    pub async fn get_issued_records_by_code(
+        &self,
+        code: &AssetTypeCode,
+    ) -> Result<Vec<(TxOutput, Option<OwnerMemo>)>> {
+        self.get(self.query(
+            QueryServerRoutes::GetIssuedRecordsByCode.with_arg(&code.to_base64()),
+        ))
+        .await
+    }
+
+    /// The transaction which created a TXO, with its hash
+    pub async fn get_authenticated_txnid_hash(&self, sid: TxoSID) -> Result<TxnIDHash> {
+        self.get(self.query(QueryServerRoutes::GetAuthencatedTxnIDHash.with_arg(&sid.0)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_transaction_hash(&self, sid: TxnSID) -> Result<String> {
+        self.get(self.query(QueryServerRoutes::GetTransactionHash.with_arg(&sid.0)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_transaction_sid(&self, hash: &str) -> Result<TxnSID> {
+        self.get(self.query(QueryServerRoutes::GetTransactionSid.with_arg(&hash)))
+            .await
+            .map(TxnSID)
+    }
+
+    #[allow(missing_docs)]
+    pub async fn get_txn_details(&self, sid: TxnSID) -> Result<TxnDetails> {
+        self.get(self.query(QueryServerRoutes::GetTxnDetails.with_arg(&sid.0)))
+            .await
+    }
+
+    /// Number of the blocks committed
+    pub async fn get_commits(&self) -> Result<u64> {
+        self.get(self.query(QueryServerRoutes::GetCommits.route()))
+            .await
+    }
+}
+
+/// Routes of the ledger
+impl Client {
+    #[allow(missing_docs)]
+    pub async fn query_utxo(&self, sid: TxoSID) -> Result<AuthenticatedUtxo> {
+        self.get(self.query(ApiRoutes::UtxoSid.with_arg(&sid.0)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_utxo_light(&self, sid: TxoSID) -> Result<UnAuthenticatedUtxo> {
+        self.get(self.query(ApiRoutes::UtxoSidLight.with_arg(&sid.0)))
+            .await
+    }
+
+    /// At most 10 TXOs, `None` for the spent ones
+    pub async fn query_utxos(
+        &self,
+        sids: &[TxoSID],
+    ) -> Result<Vec<Option<AuthenticatedUtxo>>> {
+        let arg = join(sids.iter().map(|s| s.0));
+        self.get(self.query(ApiRoutes::UtxoSidList.with_arg(&arg)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_asset_issuance_num(&self, code: &AssetTypeCode) -> Result<u64> {
+        self.get(self.query(ApiRoutes::AssetIssuanceNum.with_arg(&code.to_base64())))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_asset(&self, code: &AssetTypeCode) -> Result<AssetType> {
+        self.get(self.query(ApiRoutes::AssetToken.with_arg(&code.to_base64())))
+            .await
+    }
+
+    /// The decimals of an asset, FRA is known without a request
+    pub async fn query_asset_decimals(&self, code: &AssetTypeCode) -> Result<u8> {
+        if code.val == ASSET_TYPE_FRA {
+            return Ok(FRA_DECIMALS);
+        }
+        self.query_asset(code)
+            .await
+            .map(|t| t.properties.asset_rules.decimals)
+    }
+
+    /// The smallest units of an asset in `amount`, a decimal string like
+    /// `12.345` in its units, as the amounts of the transactions are
+    pub async fn parse_amount(&self, code: &AssetTypeCode, amount: &str) -> Result<u64> {
+        let decimals = self.query_asset_decimals(code).await.c(d!())?;
+        parse_amount(amount, decimals).c(d!())
+    }
+
+    /// `am` smallest units of an asset in its units, e.g. `12.345`
+    pub async fn format_amount(&self, code: &AssetTypeCode, am: u64) -> Result<String> {
+        self.query_asset_decimals(code)
+            .await
+            .map(|decimals| format_amount(am, decimals))
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_asset_policy(
+        &self,
+        code: &AssetTypeCode,
+    ) -> Result<Option<CustomAssetPolicy>> {
+        self.get(self.query(ApiRoutes::AssetPolicy.with_arg(&code.to_base64())))
+            .await
+    }
+
+    /// The traceable TXOs of an asset, `sig` is the signature of its
+    /// issuer on its code, as the `sig` parameter of the route
+    pub async fn query_tracked_txos(
+        &self,
+        code: &AssetTypeCode,
+        sig: &str,
+    ) -> Result<BTreeMap<TxoSID, (UnAuthenticatedUtxo, bool)>> {
+        let path = ApiRoutes::TrackedTxos.with_arg(&code.to_base64());
+        self.get(self.query(format!("{path}?sig={sig}"))).await
+    }
+
+    /// The code of a custom asset, derived from the code chosen by its issuer
+    pub async fn get_derived_asset_code(&self, code: &AssetTypeCode) -> Result<String> {
+        self.get_text(
+            self.query(ApiRoutes::GetDerivedAssetCode.with_arg(&code.to_base64())),
+        )
+        .await
+    }
+
+    /// The state commitment and the sequence id
+    pub async fn query_global_state(&self) -> Result<GlobalState> {
+        self.get(self.query(ApiRoutes::GlobalState.route())).await
+    }
+
+    /// The state commitment at a version of the ledger
+    pub async fn query_global_state_version(
+        &self,
+        version: u64,
+    ) -> Result<Option<StateCommitment>> {
+        self.get(self.query(ApiRoutes::GlobalStateVersion.with_arg(&version)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_txn(&self, sid: TxnSID) -> Result<AuthenticatedTransaction> {
+        self.get(self.query(ApiRoutes::TxnSid.with_arg(&sid.0)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_txn_light(&self, sid: TxnSID) -> Result<FinalizedTransaction> {
+        self.get(self.query(ApiRoutes::TxnSidLight.with_arg(&sid.0)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_txn_proof(&self, sid: TxnSID) -> Result<TxnProof> {
+        self.get(self.query(ApiRoutes::TxnProof.with_arg(&sid.0)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_utxo_proof(&self, sid: TxoSID) -> Result<UtxoProof> {
+        self.get(self.query(ApiRoutes::UtxoProof.with_arg(&sid.0)))
+            .await
+    }
+
+    /// The block at a tendermint height
+    pub async fn query_block(&self, height: u64) -> Result<BlockMeta> {
+        self.get(self.query(ApiRoutes::Block.with_arg(&height)))
+            .await
+    }
+
+    /// The TXOs of `pk`, with their owner memos
+    pub async fn query_owned_utxos(
+        &self,
+        pk: &XfrPublicKey,
+    ) -> Result<BTreeMap<TxoSID, (Utxo, Option<OwnerMemo>)>> {
+        self.get(self.query(ApiRoutes::OwnedUtxos.with_arg(&b64(pk))))
+            .await
+    }
+
+    /// The anonymous record of a commitment, in base58
+    pub async fn query_owned_abar(
+        &self,
+        commitment: &str,
+    ) -> Result<Option<(ATxoSID, ABARData)>> {
+        self.get(self.query(ApiRoutes::OwnedAbars.with_arg(&commitment)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_validators(&self) -> Result<ValidatorList> {
+        self.get(self.query(ApiRoutes::ValidatorList.route())).await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_delegation_info(
+        &self,
+        pk: &XfrPublicKey,
+    ) -> Result<DelegationInfo> {
+        self.get(self.query(ApiRoutes::DelegationInfo.with_arg(&b64(pk))))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_unbonding(&self, pk: &XfrPublicKey) -> Result<UnbondingList> {
+        self.get(self.query(ApiRoutes::Unbonding.with_arg(&b64(pk))))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_scheduled_transfers(
+        &self,
+        pk: &XfrPublicKey,
+    ) -> Result<ScheduleList> {
+        self.get(self.query(ApiRoutes::ScheduledTransfers.with_arg(&b64(pk))))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_delegator_list(
+        &self,
+        validator: &TendermintAddr,
+    ) -> Result<DelegatorList> {
+        self.get(self.query(ApiRoutes::DelegatorList.with_arg(validator)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_validator_detail(
+        &self,
+        validator: &TendermintAddr,
+    ) -> Result<ValidatorDetail> {
+        self.get(self.query(ApiRoutes::ValidatorDetail.with_arg(validator)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn query_validator_history(
+        &self,
+        validator: &TendermintAddr,
+    ) -> Result<Vec<ValidatorEpoch>> {
+        self.get(self.query(ApiRoutes::ValidatorHistory.with_arg(validator)))
+            .await
+    }
+}
+
+/// Routes of the submission server
+impl Client {
+    /// Submit a transaction, returns the handle to follow it
+    pub async fn submit(&self, tx: &Transaction) -> Result<TxnHandle> {
+        self.post(
+            self.submission(SubmissionRoutes::SubmitTransaction.route()),
+            tx,
+        )
+        .await
+    }
+
+    /// Check a transaction against the current state without submitting it
+    pub async fn simulate(&self, tx: &Transaction) -> Result<SimulatedTxn> {
+        self.post(self.submission(SubmissionRoutes::SimulateTxn.route()), tx)
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn txn_status(&self, handle: &TxnHandle) -> Result<TxnStatus> {
+        self.get(self.submission(SubmissionRoutes::TxnStatus.with_arg(&handle.0)))
+            .await
+    }
+
+    /// Where a transaction is in its lifecycle
+    pub async fn get_txn_status(&self, handle: &TxnHandle) -> Result<TxnLifecycle> {
+        self.get(self.submission(SubmissionRoutes::GetTxnStatus.with_arg(&handle.0)))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn pending_txn(&self, handle: &TxnHandle) -> Result<PendingTxn> {
+        self.get(self.submission(SubmissionRoutes::PendingTxn.with_arg(&handle.0)))
+            .await
+    }
+
+    /// The pending transactions involving `pk`
+    pub async fn pending_txns_for(&self, pk: &XfrPublicKey) -> Result<Vec<PendingTxn>> {
+        self.get(self.submission(SubmissionRoutes::PendingTxnsFor.with_arg(&b64(pk))))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn forward_queue(&self) -> Result<ForwardQueueStat> {
+        self.get(self.submission(SubmissionRoutes::ForwardQueue.route()))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn ping(&self) -> Result<String> {
+        self.get_text(self.submission(SubmissionRoutes::Ping.route()))
+            .await
+    }
+
+    #[allow(missing_docs)]
+    pub async fn version(&self) -> Result<String> {
+        self.get_text(self.submission(SubmissionRoutes::Version.route()))
+            .await
+    }
+}
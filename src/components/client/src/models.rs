@@ -0,0 +1,55 @@
+//!
+//! # Models of the requests and the responses
+//!
+//! The same types the servers serialize, re-exported in one place.
+//!
+
+pub use {
+    abciapp::api::{
+        error::{ApiError, ErrorCode},
+        query_server::query_api::{
+            ledger_api::{
+                ScheduleEntry, ScheduleList, TxnProof, UnbondingEntry, UnbondingList,
+                UtxoProof, ValidatorEpoch,
+            },
+            server::{
+                AbarSpendInfo, AbarTransferMaterial, OpSummary, OutputSummary,
+                TxnDetails,
+            },
+        },
+        submission_server::{
+            pending::{CheckTxResult, PendingTxn},
+            ForwardQueueStat, SimulatedTxn, TxnHandle, TxnLifecycle, TxnStatus,
+        },
+    },
+    finutils::api::{DelegationInfo, DelegatorList, ValidatorDetail, ValidatorList},
+    ledger::{
+        data_model::{
+            ABARData, ATxoSID, AssetType, AssetTypeCode, AuthenticatedTransaction,
+            AuthenticatedUtxo, CustomAssetPolicy, DefineAsset, FinalizedTransaction,
+            StateCommitmentData, Transaction, TxOutput, TxnIDHash, TxnSID, TxoSID,
+            UnAuthenticatedUtxo, Utxo,
+        },
+        staking::TendermintAddr,
+        store::api_cache::BlockMeta,
+    },
+    zei::{
+        noah_api::anon_xfr::structs::{AxfrOwnerMemo, Commitment, MTLeafInfo},
+        OwnerMemo, XfrPublicKey,
+    },
+};
+
+use {globutils::HashOf, serde::Deserialize};
+
+/// The state commitment of the ledger
+pub type StateCommitment = HashOf<Option<StateCommitmentData>>;
+
+/// Response of `global_state`
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize)]
+pub struct GlobalState {
+    pub commitment: StateCommitment,
+    /// the sequence id to build transactions with
+    pub seq_id: u64,
+    pub signature: String,
+}
@@ -0,0 +1,165 @@
+//!
+//! The client against a stub node, serving canned responses
+//! on the paths of the real routes.
+//!
+
+use {
+    ledger::data_model::Transaction,
+    platform_client::{
+        models::{TxnHandle, TxnStatus, TxoSID},
+        Client, ClientConfig,
+    },
+    std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    },
+};
+
+/// `(status, body)` of the n-th request on a path, the last one is repeated
+type Responses = HashMap<&'static str, Vec<(u16, &'static str)>>;
+
+struct StubNode {
+    url: String,
+    hits: Arc<HashMap<&'static str, AtomicUsize>>,
+}
+
+impl StubNode {
+    fn spawn(responses: Responses) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(
+            responses
+                .keys()
+                .map(|p| (*p, AtomicUsize::new(0)))
+                .collect::<HashMap<_, _>>(),
+        );
+
+        let h = Arc::clone(&hits);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let path = line.split_whitespace().nth(1).unwrap_or("").to_owned();
+                let mut len = 0;
+                loop {
+                    let mut hdr = String::new();
+                    reader.read_line(&mut hdr).unwrap();
+                    if hdr.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(v) = hdr.to_lowercase().strip_prefix("content-length:") {
+                        len = v.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+
+                let (status, body) = match responses.get(path.as_str()) {
+                    Some(r) => {
+                        let n = h[path.as_str()].fetch_add(1, Ordering::SeqCst);
+                        r[n.min(r.len() - 1)]
+                    }
+                    None => (404, r#"{"code":"NOT_FOUND","message":"no such route"}"#),
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+
+        StubNode { url, hits }
+    }
+
+    fn client(&self) -> Client {
+        let mut cfg = ClientConfig::new("http://unused");
+        cfg.query_url = self.url.clone();
+        cfg.submission_url = self.url.clone();
+        cfg.retry_delay = Duration::from_millis(10);
+        Client::new(cfg)
+    }
+
+    fn hits(&self, path: &str) -> usize {
+        self.hits[path].load(Ordering::SeqCst)
+    }
+}
+
+#[tokio::test]
+async fn test_typed_responses() {
+    let node = StubNode::spawn(
+        vec![
+            ("/get_commits", vec![(200, "7")]),
+            ("/get_owner_memo_batch/1,2", vec![(200, "[null,null]")]),
+            ("/txn_status/abc", vec![(200, r#"{"Committed":[3,[4,5]]}"#)]),
+            ("/submit_transaction", vec![(200, r#""abc""#)]),
+            ("/ping", vec![(200, "pong")]),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let client = node.client();
+
+    assert_eq!(7, client.get_commits().await.unwrap());
+    let memos = client
+        .get_owner_memo_batch(&[TxoSID(1), TxoSID(2)])
+        .await
+        .unwrap();
+    assert_eq!(2, memos.len());
+    assert!(memos.iter().all(|m| m.is_none()));
+
+    let handle = client.submit(&Transaction::from_seq_id(0)).await.unwrap();
+    assert_eq!(TxnHandle("abc".to_owned()), handle);
+    match client.txn_status(&handle).await.unwrap() {
+        TxnStatus::Committed((txn, txos)) => {
+            assert_eq!(3, txn.0);
+            assert_eq!(vec![TxoSID(4), TxoSID(5)], txos);
+        }
+        s => panic!("unexpected status: {s:?}"),
+    }
+    assert_eq!("pong", client.ping().await.unwrap());
+}
+
+#[tokio::test]
+async fn test_retry() {
+    let unavailable = r#"{"code":"UNAVAILABLE","message":"not ready"}"#;
+    let node = StubNode::spawn(
+        vec![
+            (
+                "/get_commits",
+                vec![(503, unavailable), (503, unavailable), (200, "9")],
+            ),
+            (
+                "/get_max_atxo_sid",
+                vec![(400, r#"{"code":"INVALID_PARAM","message":"bad"}"#)],
+            ),
+            ("/version", vec![(503, unavailable)]),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let client = node.client();
+
+    // retried until it succeeds
+    assert_eq!(9, client.get_commits().await.unwrap());
+    assert_eq!(3, node.hits("/get_commits"));
+
+    // not retried
+    let e = client.get_max_atxo_sid().await.unwrap_err();
+    assert!(e.to_string().contains("bad"));
+    assert_eq!(1, node.hits("/get_max_atxo_sid"));
+
+    // given up after the configured retries
+    assert!(client.version().await.is_err());
+    assert_eq!(1 + client.config().retries as usize, node.hits("/version"));
+}
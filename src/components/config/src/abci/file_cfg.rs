@@ -0,0 +1,113 @@
+//!
+//! # Configuration file of abcid
+//!
+//! A TOML file given by `--config` or `ABCI_CONFIG_FILE`,
+//! any field may be omitted.
+//!
+//! The order of precedence:
+//! command line arguments > environment variables > this file > the defaults.
+//!
+//! ```toml
+//! ledger_dir = "/data/findora"
+//! tendermint_port = 26657
+//! enable_query_service = true
+//! mem_ceiling_mb = 8192
+//! zstd_level = 3  # 0, the default, leaves the files uncompressed
+//! cors_allowed_origins = ["https://wallet.example.com"]
+//! max_batch_len = 100
+//!
+//! [route_payload_limits]
+//! "/submit_transaction" = 4194304
+//!
+//! [api_keys]
+//! "3f9c2b..." = "admin"
+//! "a71e04..." = "wallet"
+//! ```
+//!
+
+use {
+    super::global_cfg::ApiRole,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeMap, fs},
+};
+
+/// Fields correspond to the command line arguments of the same names
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileCfg {
+    pub abcid_host: Option<String>,
+    pub abcid_port: Option<u16>,
+    pub tendermint_host: Option<String>,
+    pub tendermint_port: Option<u16>,
+    pub submission_service_port: Option<u16>,
+    pub ledger_service_port: Option<u16>,
+    pub evm_http_port: Option<u16>,
+    pub evm_ws_port: Option<u16>,
+
+    pub ledger_dir: Option<String>,
+    pub checkpoint_file: Option<String>,
+    pub tendermint_node_self_addr: Option<String>,
+    pub tendermint_node_key_config_path: Option<String>,
+    pub ledger_snapshot_export: Option<String>,
+    pub ledger_snapshot_import: Option<String>,
+
+    pub mem_ceiling_mb: Option<u64>,
+    pub zstd_level: Option<i32>,
+    pub invariant_check_itv: Option<u64>,
+    pub staking_cache_refresh: Option<u64>,
+    pub admin_token: Option<String>,
+    /// static API keys and their roles, `public`, `wallet` or `admin`
+    pub api_keys: Option<BTreeMap<String, ApiRole>>,
+    pub jwt_secret: Option<String>,
+    /// `text` or `json`
+    pub log_format: Option<String>,
+    /// filter directives, e.g. `info,abciapp=debug`
+    pub log_level: Option<String>,
+    pub log_sample_every: Option<u64>,
+    pub arc_history: Option<String>,
+
+    pub enable_enterprise_web3: Option<bool>,
+    pub arc_fresh: Option<bool>,
+    pub enable_query_service: Option<bool>,
+    pub enable_audit_api: Option<bool>,
+    pub state_sync_interval: Option<u64>,
+    pub state_sync_keep: Option<usize>,
+    pub enable_state_sync_restore: Option<bool>,
+    pub backup_interval: Option<u64>,
+    pub backup_target: Option<String>,
+    pub backup_keep: Option<usize>,
+    pub disable_eth_empty_blocks: Option<bool>,
+    pub enable_eth_api_service: Option<bool>,
+    pub enable_eth_api_secondary: Option<bool>,
+
+    pub disable_cors: Option<bool>,
+    pub cors_allowed_origins: Option<Vec<String>>,
+    pub cors_allowed_methods: Option<Vec<String>>,
+    pub cors_allowed_headers: Option<Vec<String>>,
+
+    pub max_batch_len: Option<usize>,
+    /// in bytes
+    pub query_payload_limit: Option<usize>,
+    /// in bytes
+    pub submission_payload_limit: Option<usize>,
+    /// the largest bodies in bytes of the routes starting with the keys
+    pub route_payload_limits: Option<BTreeMap<String, usize>>,
+}
+
+impl FileCfg {
+    /// Load from a TOML file, unknown fields are errors,
+    /// so that a misspelled field is not silently ignored
+    pub fn from_file(path: &str) -> Result<FileCfg> {
+        let content = fs::read_to_string(path).c(d!(path))?;
+        toml::from_str(&content).c(d!(path))
+    }
+
+    /// Load the file if given, or use an empty one
+    pub fn load(path: Option<&str>) -> Result<FileCfg> {
+        path.map(FileCfg::from_file)
+            .transpose()
+            .map(|c| c.unwrap_or_default())
+    }
+}
@@ -13,6 +13,8 @@ use {
     toml,
 };
 
+pub mod file_cfg;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[allow(missing_docs)]
 pub struct CheckPointConfig {
@@ -155,6 +157,78 @@ pub struct CheckPointConfig {
 
     #[serde(default = "def_evm_staking_address")]
     pub evm_staking_address: String,
+
+    // Resource limits of transactions and blocks,
+    // always enforced in `check_tx`, enforced in `deliver_tx` since `txn_limits_height`.
+    #[serde(default = "def_txn_limits_height")]
+    pub txn_limits_height: i64,
+
+    #[serde(default = "def_max_txn_bytes")]
+    pub max_txn_bytes: u64,
+
+    #[serde(default = "def_max_txn_ops")]
+    pub max_txn_ops: u64,
+
+    #[serde(default = "def_max_block_txns")]
+    pub max_block_txns: u64,
+
+    // Distinct codes of the rejected txns, see `TxnValidationError`,
+    // always used in `check_tx`, used in `deliver_tx` since this height.
+    #[serde(default = "def_txn_error_codes_height")]
+    pub txn_error_codes_height: i64,
+
+    // `DefineAsset` can carry an `AssetMetadata` after this height
+    #[serde(default = "def_asset_metadata_height")]
+    pub asset_metadata_height: u64,
+
+    // `ReDelegation` is accepted after this height
+    #[serde(default = "def_redelegation_height")]
+    pub redelegation_height: i64,
+
+    // `UpdateParams` is accepted after this height, the on-chain parameters
+    // override the limits above and the `unbond_block_cnt` once set
+    #[serde(default = "def_chain_params_height")]
+    pub chain_params_height: i64,
+
+    // The state commitment covers the asset types, the issuance numbers
+    // and the staking state since this height
+    #[serde(default = "def_state_domains_height")]
+    pub state_domains_height: u64,
+
+    // `SwapAsset` is accepted after this height
+    #[serde(default = "def_asset_swap_height")]
+    pub asset_swap_height: i64,
+
+    // `ScheduleTransfer` and `CancelSchedule` are accepted after this height
+    #[serde(default = "def_scheduled_transfer_height")]
+    pub scheduled_transfer_height: i64,
+
+    // `AnchorData` is accepted after this height
+    #[serde(default = "def_anchor_data_height")]
+    pub anchor_data_height: i64,
+
+    // Co-signature rules on transfer inputs and outputs are accepted
+    // and checked after this height
+    #[serde(default = "def_cosig_height")]
+    pub cosig_height: i64,
+
+    // Transfer outputs can be time-locked after this height
+    #[serde(default = "def_time_lock_height")]
+    pub time_lock_height: i64,
+
+    // Assets can be defined with a transfer whitelist, and
+    // `UpdateTransferPolicy` is accepted after this height
+    #[serde(default = "def_transfer_policy_height")]
+    pub transfer_policy_height: i64,
+
+    // Transactions can carry an expiry height after this height
+    #[serde(default = "def_txn_expiry_height")]
+    pub txn_expiry_height: i64,
+
+    // Transfers can carry asset tracing policies and proofs after this height,
+    // they are checked against the tracing policies of the assets
+    #[serde(default = "def_asset_tracing_height")]
+    pub asset_tracing_height: i64,
 }
 
 fn def_fix_check_replay() -> u64 {
@@ -236,6 +310,74 @@ fn def_evm_staking_address() -> String {
     DEFAULT_CHECKPOINT_CONFIG.evm_staking_address.clone()
 }
 
+fn def_txn_limits_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.txn_limits_height
+}
+
+fn def_max_txn_bytes() -> u64 {
+    DEFAULT_CHECKPOINT_CONFIG.max_txn_bytes
+}
+
+fn def_max_txn_ops() -> u64 {
+    DEFAULT_CHECKPOINT_CONFIG.max_txn_ops
+}
+
+fn def_max_block_txns() -> u64 {
+    DEFAULT_CHECKPOINT_CONFIG.max_block_txns
+}
+
+fn def_txn_error_codes_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.txn_error_codes_height
+}
+
+fn def_asset_metadata_height() -> u64 {
+    DEFAULT_CHECKPOINT_CONFIG.asset_metadata_height
+}
+
+fn def_redelegation_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.redelegation_height
+}
+
+fn def_chain_params_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.chain_params_height
+}
+
+fn def_state_domains_height() -> u64 {
+    DEFAULT_CHECKPOINT_CONFIG.state_domains_height
+}
+
+fn def_asset_swap_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.asset_swap_height
+}
+
+fn def_scheduled_transfer_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.scheduled_transfer_height
+}
+
+fn def_anchor_data_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.anchor_data_height
+}
+
+fn def_cosig_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.cosig_height
+}
+
+fn def_time_lock_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.time_lock_height
+}
+
+fn def_transfer_policy_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.transfer_policy_height
+}
+
+fn def_txn_expiry_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.txn_expiry_height
+}
+
+fn def_asset_tracing_height() -> i64 {
+    DEFAULT_CHECKPOINT_CONFIG.asset_tracing_height
+}
+
 #[cfg(feature = "debug_env")]
 lazy_static! {
     static ref DEFAULT_CHECKPOINT_CONFIG: CheckPointConfig = CheckPointConfig {
@@ -282,6 +424,23 @@ lazy_static! {
         max_gas_price_limit: 0,
         evm_staking_inital_height: 128,
         evm_staking_address: "0x321DF28026D01858906D322533900aD3435eE964".to_owned(),
+        txn_limits_height: 0,
+        max_txn_bytes: 1024 * 1024,
+        max_txn_ops: 256,
+        max_block_txns: 5000,
+        txn_error_codes_height: 0,
+        asset_metadata_height: 0,
+        redelegation_height: 0,
+        chain_params_height: 0,
+        state_domains_height: 0,
+        asset_swap_height: 0,
+        scheduled_transfer_height: 0,
+        anchor_data_height: 0,
+        cosig_height: 0,
+        time_lock_height: 0,
+        transfer_policy_height: 0,
+        txn_expiry_height: 0,
+        asset_tracing_height: 0,
     };
 }
 
@@ -618,6 +777,23 @@ lazy_static! {
         max_gas_price_limit: 4636000,
         evm_staking_inital_height: 4636000,
         evm_staking_address: "0x38d49e3bd5144059c9f3bA10CF7306E84155B603".to_owned(),
+        txn_limits_height: 5000_0000,
+        max_txn_bytes: 1024 * 1024,
+        max_txn_ops: 256,
+        max_block_txns: 5000,
+        txn_error_codes_height: 5000_0000,
+        asset_metadata_height: 5000_0000,
+        redelegation_height: 5000_0000,
+        chain_params_height: 5000_0000,
+        state_domains_height: 5000_0000,
+        asset_swap_height: 5000_0000,
+        scheduled_transfer_height: 5000_0000,
+        anchor_data_height: 5000_0000,
+        cosig_height: 5000_0000,
+        time_lock_height: 5000_0000,
+        transfer_policy_height: 5000_0000,
+        txn_expiry_height: 5000_0000,
+        asset_tracing_height: 5000_0000,
     };
 }
 
@@ -746,6 +922,8 @@ impl ABCIConfig {
 }
 
 pub mod global_cfg {
+    #[cfg(not(test))]
+    use crate::abci::file_cfg::FileCfg;
     use crate::abci::CheckPointConfig;
     #[cfg(target_os = "linux")]
     use btm::BtmCfg;
@@ -756,6 +934,8 @@ pub mod global_cfg {
     use clap::{crate_authors, App, Arg, ArgMatches};
     use lazy_static::lazy_static;
     use ruc::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
     #[cfg(not(test))]
     use std::{env, process::exit};
 
@@ -764,6 +944,95 @@ pub mod global_cfg {
         pub static ref CFG: Config = pnk!(get_config());
     }
 
+    /// Role of a client of the REST services, each one includes the lower ones
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        Default,
+        Deserialize,
+        Eq,
+        Ord,
+        PartialEq,
+        PartialOrd,
+        Serialize,
+    )]
+    #[serde(rename_all = "snake_case")]
+    pub enum ApiRole {
+        /// requests without any credential
+        #[default]
+        Public,
+        Wallet,
+        Admin,
+    }
+
+    /// Credentials of the clients of the REST services
+    #[derive(Clone, Debug, Default)]
+    pub struct AuthCfg {
+        /// static API keys and their roles, `admin_token` is an admin key
+        pub api_keys: BTreeMap<String, ApiRole>,
+        /// HS256 secret of the JWTs, whose `role` claim is the role,
+        /// no JWT is accepted if missing
+        pub jwt_secret: Option<String>,
+    }
+
+    /// CORS policy of the REST services, `None` means anything is allowed
+    #[derive(Clone, Debug, Default)]
+    pub struct CorsCfg {
+        pub disabled: bool,
+        pub allowed_origins: Option<Vec<String>>,
+        pub allowed_methods: Option<Vec<String>>,
+        pub allowed_headers: Option<Vec<String>>,
+    }
+
+    impl CorsCfg {
+        /// The default policy, which allows everything
+        pub fn is_permissive(&self) -> bool {
+            !self.disabled
+                && self.allowed_origins.is_none()
+                && self.allowed_methods.is_none()
+                && self.allowed_headers.is_none()
+        }
+    }
+
+    /// Sanity limits of the requests of the REST services
+    #[derive(Clone, Debug)]
+    pub struct LimitsCfg {
+        /// the most items of a comma separated list in a request,
+        /// a route may have a lower limit of its own
+        pub max_batch_len: usize,
+        /// the largest body of a query request, in bytes
+        pub query_payload_limit: usize,
+        /// the largest body of a submission request, in bytes
+        pub submission_payload_limit: usize,
+        /// the largest bodies of the routes starting with the keys,
+        /// the longest matching key wins over the limits above
+        pub route_payload_limits: BTreeMap<String, usize>,
+    }
+
+    impl Default for LimitsCfg {
+        fn default() -> Self {
+            LimitsCfg {
+                max_batch_len: 100,
+                query_payload_limit: 1024 * 1024,
+                submission_payload_limit: 2048 * 1024,
+                route_payload_limits: BTreeMap::new(),
+            }
+        }
+    }
+
+    impl LimitsCfg {
+        /// The largest body of a request of `path`, `default` if no route matches
+        pub fn payload_limit(&self, path: &str, default: usize) -> usize {
+            self.route_payload_limits
+                .iter()
+                .filter(|(route, _)| path.starts_with(route.as_str()))
+                .max_by_key(|(route, _)| route.len())
+                .map(|(_, limit)| *limit)
+                .unwrap_or(default)
+        }
+    }
+
     #[derive(Default)]
     pub struct Config {
         pub abci_host: String,
@@ -784,6 +1053,36 @@ pub mod global_cfg {
         pub tendermint_node_self_addr: Option<String>,
         pub tendermint_node_key_config_path: Option<String>,
         pub ledger_dir: String,
+        pub mem_ceiling_mb: Option<u64>,
+        pub zstd_level: i32,
+        pub invariant_check_itv: u64,
+        pub staking_cache_refresh: u64,
+        pub admin_token: Option<String>,
+        pub enable_audit_api: bool,
+        /// export a state sync snapshot every this many blocks, 0 to disable
+        pub state_sync_interval: u64,
+        pub state_sync_keep: usize,
+        /// restore the snapshots offered by tendermint on an empty ledger
+        pub enable_state_sync_restore: bool,
+        /// export a backup snapshot every this many blocks, 0 to disable
+        pub backup_interval: u64,
+        /// a directory, or the http(s) URL of a bucket, see `api::backup`
+        pub backup_target: Option<String>,
+        pub backup_keep: usize,
+        /// log as JSON lines instead of text
+        pub log_json: bool,
+        /// initial filter directives of the logs, e.g. `info,abciapp=debug`
+        pub log_level: Option<String>,
+        /// log one of this many blocks in the per-block timing logs
+        pub log_sample_every: u64,
+        pub ledger_snapshot_export: Option<String>,
+        pub ledger_verify: bool,
+        /// the other binary to replay the ledger with, see `--ledger-replay-diff`
+        pub ledger_replay_diff: Option<String>,
+        pub ledger_snapshot_import: Option<String>,
+        pub cors: CorsCfg,
+        pub auth: AuthCfg,
+        pub limits: LimitsCfg,
         #[cfg(target_os = "linux")]
         pub btmcfg: BtmCfg,
         pub checkpoint: CheckPointConfig,
@@ -803,6 +1102,7 @@ pub mod global_cfg {
             .version(env!("VERGEN_SHA"))
             .author(crate_authors!())
             .about("An ABCI node implementation of FindoraNetwork.")
+            .arg_from_usage("-c, --config=[Path] 'a TOML file, overridden by the arguments and the environment variables'")
             .arg_from_usage("--abcid-host=[ABCId IP]")
             .arg_from_usage("--abcid-port=[ABCId Port]")
             .arg_from_usage("--enable-enterprise-web3 'enable enterprise-web3'")
@@ -822,6 +1122,33 @@ pub mod global_cfg {
             .arg_from_usage("--tendermint-node-key-config-path=[Path] 'such as: ${HOME}/.tendermint/config/priv_validator_key.json'")
             .arg_from_usage("-d, --ledger-dir=[Path]")
             .arg_from_usage("--checkpoint-file=[Path]")
+            .arg_from_usage("--mem-ceiling-mb=[MiB] 'evict reconstructible caches when the RSS exceeds this value'")
+            .arg_from_usage("--zstd-level=[Level] 'zstd level of the ledger status and the snapshot files, default to 0 (uncompressed)'")
+            .arg_from_usage("--invariant-check-itv=[Secs] 'interval of the background ledger invariant check, run on the query server, 0 to disable, default to 0'")
+            .arg_from_usage("--staking-cache-refresh=[Secs] 'minimum age of the cached staking view before it is rebuilt for a new block, default to 10'")
+            .arg_from_usage("--admin-token=[Token] 'bearer token of the /admin endpoints, they are disabled if missing'")
+            .arg_from_usage("--jwt-secret=[Secret] 'HS256 secret of the JWTs carrying a role claim, no JWT is accepted if missing'")
+            .arg_from_usage("--log-format=[Format] 'text or json, default to text'")
+            .arg_from_usage("--log-level=[Directives] 'per-module log levels, e.g. info,abciapp=debug, default to RUST_LOG or info'")
+            .arg_from_usage("--log-sample-every=[N] 'log the per-block timings of one block in every N, default to 1'")
+            .arg_from_usage("--enable-audit-api 'decrypt the traced records for the auditors submitting their tracer keys'")
+            .arg_from_usage("--state-sync-interval=[Blocks] 'export a state sync snapshot every N blocks, default to 0, disabled'")
+            .arg_from_usage("--state-sync-keep=[Num] 'state sync snapshots kept, default to 2'")
+            .arg_from_usage("--enable-state-sync-restore 'restore the snapshots offered by tendermint state sync when the ledger is empty'")
+            .arg_from_usage("--backup-interval=[Blocks] 'export a backup snapshot every N blocks in the background, default to 0, disabled'")
+            .arg_from_usage("--backup-target=[Target] 'directory or http(s) bucket URL of the backups, default to <ledger_dir>_backups'")
+            .arg_from_usage("--backup-keep=[Num] 'backups kept in the target, default to 7'")
+            .arg_from_usage("--ledger-snapshot-export=[Path] 'export the ledger data to a snapshot file and exit'")
+            .arg_from_usage("--ledger-verify 'replay the ledger offline, report any divergence and exit'")
+            .arg_from_usage("--ledger-replay-diff=[Binary] 'replay the ledger with this binary and the current one in lockstep, report the state at the first divergence and exit'")
+            .arg_from_usage("--ledger-snapshot-import=[Path] 'bootstrap an empty ledger directory from a snapshot file'")
+            .arg_from_usage("--disable-cors 'do not send any CORS headers from the REST services'")
+            .arg_from_usage("--cors-allowed-origins=[Origins] 'comma separated, default to any'")
+            .arg_from_usage("--cors-allowed-methods=[Methods] 'comma separated, default to any'")
+            .arg_from_usage("--cors-allowed-headers=[Headers] 'comma separated, default to any'")
+            .arg_from_usage("--max-batch-len=[Num] 'most items of a comma separated list in a request, default to 100'")
+            .arg_from_usage("--query-payload-limit=[Bytes] 'largest body of a query request, default to 1 MiB'")
+            .arg_from_usage("--submission-payload-limit=[Bytes] 'largest body of a submission request, default to 2 MiB'")
             .arg_from_usage("--enable-snapshot 'global switch for enabling snapshot functions'")
             .arg_from_usage("--snapshot-list 'list all available snapshots in the form of block height'")
             .arg_from_usage("--snapshot-target=[TargetPath] 'a data volume containing both ledger data and tendermint data'")
@@ -840,24 +1167,34 @@ pub mod global_cfg {
 
         print_version(&m);
 
+        let fc = m
+            .value_of("config")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("ABCI_CONFIG_FILE").ok());
+        let fc = FileCfg::load(fc.as_deref()).c(d!())?;
+
         let ah = m
             .value_of("abcid-host")
             .map(|v| v.to_owned())
             .or_else(|| env::var("ABCI_HOST").ok())
+            .or_else(|| fc.abcid_host.clone())
             .unwrap_or_else(|| "0.0.0.0".to_owned());
         let ap = m
             .value_of("abcid-port")
             .map(|v| v.to_owned())
             .or_else(|| env::var("ABCI_PORT").ok())
+            .or_else(|| fc.abcid_port.map(|v| v.to_string()))
             .unwrap_or_else(|| "26658".to_owned())
             .parse::<u16>()
             .c(d!())?;
-        let enable_enterprise_web3 = m.is_present("enable-enterprise-web3");
+        let enable_enterprise_web3 = m.is_present("enable-enterprise-web3")
+            || fc.enable_enterprise_web3.unwrap_or(false);
         let arh = {
             let trace = m
                 .value_of("arc-history")
                 .map(|v| v.to_owned())
                 .or_else(|| env::var("ARC_HISTORY").ok())
+                .or_else(|| fc.arc_history.clone())
                 .unwrap_or_else(|| "90,10".to_string())
                 .trim()
                 .to_owned();
@@ -885,16 +1222,18 @@ pub mod global_cfg {
                 return Err(eg!("invalid trace"));
             }
         };
-        let arf = m.is_present("arc-fresh");
+        let arf = m.is_present("arc-fresh") || fc.arc_fresh.unwrap_or(false);
         let th = m
             .value_of("tendermint-host")
             .map(|v| v.to_owned())
             .or_else(|| env::var("TENDERMINT_HOST").ok())
+            .or_else(|| fc.tendermint_host.clone())
             .unwrap_or_else(|| "0.0.0.0".to_owned());
         let tp = m
             .value_of("tendermint-port")
             .map(|v| v.to_owned())
             .or_else(|| env::var("TENDERMINT_PORT").ok())
+            .or_else(|| fc.tendermint_port.map(|v| v.to_string()))
             .unwrap_or_else(|| "26657".to_owned())
             .parse::<u16>()
             .c(d!())?;
@@ -902,6 +1241,7 @@ pub mod global_cfg {
             .value_of("submission-service-port")
             .map(|v| v.to_owned())
             .or_else(|| env::var("SUBMISSION_PORT").ok())
+            .or_else(|| fc.submission_service_port.map(|v| v.to_string()))
             .unwrap_or_else(|| "8669".to_owned())
             .parse::<u16>()
             .c(d!())?;
@@ -909,39 +1249,47 @@ pub mod global_cfg {
             .value_of("ledger-service-port")
             .map(|v| v.to_owned())
             .or_else(|| env::var("LEDGER_PORT").ok())
+            .or_else(|| fc.ledger_service_port.map(|v| v.to_string()))
             .unwrap_or_else(|| "8668".to_owned())
             .parse::<u16>()
             .c(d!())?;
         let eqs = m.is_present("enable-query-service")
-            || env::var("ENABLE_QUERY_SERVICE").is_ok();
+            || env::var("ENABLE_QUERY_SERVICE").is_ok()
+            || fc.enable_query_service.unwrap_or(false);
         let tnsa = m
             .value_of("tendermint-node-self-addr")
             .map(|v| v.to_owned())
-            .or_else(|| env::var("TD_NODE_SELF_ADDR").ok());
+            .or_else(|| env::var("TD_NODE_SELF_ADDR").ok())
+            .or_else(|| fc.tendermint_node_self_addr.clone());
         let tnkcp = m
             .value_of("tendermint-node-key-abci-path")
             .map(|v| v.to_owned())
-            .or_else(|| env::var("TENDERMINT_NODE_KEY_CONFIG_PATH").ok());
+            .or_else(|| env::var("TENDERMINT_NODE_KEY_CONFIG_PATH").ok())
+            .or_else(|| fc.tendermint_node_key_config_path.clone());
         let ld = m
             .value_of("ledger-dir")
             .map(|v| v.to_owned())
+            .or_else(|| env::var("LEDGER_DIR").ok())
+            .or_else(|| fc.ledger_dir.clone())
             .unwrap_or_else(|| {
-                env::var("LEDGER_DIR").unwrap_or_else(|_| {
-                    format!("{}/.tendermint/__findora__", pnk!(env::var("HOME")))
-                })
+                format!("{}/.tendermint/__findora__", pnk!(env::var("HOME")))
             });
         let eeb = m.is_present("disable-eth-empty-blocks")
-            || env::var("DISABLE_ETH_EMPTY_BLOCKS").is_ok();
+            || env::var("DISABLE_ETH_EMPTY_BLOCKS").is_ok()
+            || fc.disable_eth_empty_blocks.unwrap_or(false);
         let eas = m.is_present("enable-eth-api-service")
-            || env::var("ENABLE_ETH_API_SERVICE").is_ok();
+            || env::var("ENABLE_ETH_API_SERVICE").is_ok()
+            || fc.enable_eth_api_service.unwrap_or(false);
 
         let enable_eth_api_secondary = m.is_present("enable-eth-api-secondary")
-            || env::var("ENABLE_ETH_API_SECONDARY").is_ok();
+            || env::var("ENABLE_ETH_API_SECONDARY").is_ok()
+            || fc.enable_eth_api_secondary.unwrap_or(false);
 
         let ehp = m
             .value_of("evm-http-port")
             .map(|v| v.to_owned())
             .or_else(|| env::var("EVM_HTTP_PORT").ok())
+            .or_else(|| fc.evm_http_port.map(|v| v.to_string()))
             .unwrap_or_else(|| "8545".to_owned())
             .parse::<u16>()
             .c(d!())?;
@@ -949,12 +1297,130 @@ pub mod global_cfg {
             .value_of("evm-ws-port")
             .map(|v| v.to_owned())
             .or_else(|| env::var("EVM_WS_PORT").ok())
+            .or_else(|| fc.evm_ws_port.map(|v| v.to_string()))
             .unwrap_or_else(|| "8546".to_owned())
             .parse::<u16>()
             .c(d!())?;
+        let mcm = m
+            .value_of("mem-ceiling-mb")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("MEM_CEILING_MB").ok())
+            .or_else(|| fc.mem_ceiling_mb.map(|v| v.to_string()))
+            .map(|v| v.parse::<u64>().c(d!()))
+            .transpose()?;
+        let zl = m
+            .value_of("zstd-level")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("ZSTD_LEVEL").ok())
+            .or_else(|| fc.zstd_level.map(|v| v.to_string()))
+            .unwrap_or_else(|| "0".to_owned())
+            .parse::<i32>()
+            .c(d!())?;
+        let ici = m
+            .value_of("invariant-check-itv")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("INVARIANT_CHECK_ITV").ok())
+            .or_else(|| fc.invariant_check_itv.map(|v| v.to_string()))
+            .unwrap_or_else(|| "0".to_owned())
+            .parse::<u64>()
+            .c(d!())?;
+        let scr = m
+            .value_of("staking-cache-refresh")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("STAKING_CACHE_REFRESH").ok())
+            .or_else(|| fc.staking_cache_refresh.map(|v| v.to_string()))
+            .unwrap_or_else(|| "10".to_owned())
+            .parse::<u64>()
+            .c(d!())?;
+        let adt = m
+            .value_of("admin-token")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("ADMIN_TOKEN").ok())
+            .or_else(|| fc.admin_token.clone())
+            .filter(|t| !t.is_empty());
+        let log_json = match m
+            .value_of("log-format")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("LOG_FORMAT").ok())
+            .or_else(|| fc.log_format.clone())
+            .as_deref()
+        {
+            None | Some("text") => false,
+            Some("json") => true,
+            Some(f) => return Err(eg!(format!("unknown log format: {f}"))),
+        };
+        let ll = m
+            .value_of("log-level")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("LOG_LEVEL").ok())
+            .or_else(|| fc.log_level.clone())
+            .filter(|v| !v.is_empty());
+        let lsn = m
+            .value_of("log-sample-every")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("LOG_SAMPLE_EVERY").ok())
+            .or_else(|| fc.log_sample_every.map(|v| v.to_string()))
+            .unwrap_or_else(|| "1".to_owned())
+            .parse::<u64>()
+            .c(d!())?
+            .max(1);
+        let eaa = m.is_present("enable-audit-api")
+            || env::var("ENABLE_AUDIT_API").is_ok()
+            || fc.enable_audit_api.unwrap_or(false);
+        let ssi = m
+            .value_of("state-sync-interval")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("STATE_SYNC_INTERVAL").ok())
+            .or_else(|| fc.state_sync_interval.map(|v| v.to_string()))
+            .unwrap_or_else(|| "0".to_owned())
+            .parse::<u64>()
+            .c(d!())?;
+        let ssk = m
+            .value_of("state-sync-keep")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("STATE_SYNC_KEEP").ok())
+            .or_else(|| fc.state_sync_keep.map(|v| v.to_string()))
+            .unwrap_or_else(|| "2".to_owned())
+            .parse::<usize>()
+            .c(d!())?;
+        let essr = m.is_present("enable-state-sync-restore")
+            || env::var("ENABLE_STATE_SYNC_RESTORE").is_ok()
+            || fc.enable_state_sync_restore.unwrap_or(false);
+        let bi = m
+            .value_of("backup-interval")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("BACKUP_INTERVAL").ok())
+            .or_else(|| fc.backup_interval.map(|v| v.to_string()))
+            .unwrap_or_else(|| "0".to_owned())
+            .parse::<u64>()
+            .c(d!())?;
+        let bt = m
+            .value_of("backup-target")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("BACKUP_TARGET").ok())
+            .or_else(|| fc.backup_target.clone());
+        let bk = m
+            .value_of("backup-keep")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("BACKUP_KEEP").ok())
+            .or_else(|| fc.backup_keep.map(|v| v.to_string()))
+            .unwrap_or_else(|| "7".to_owned())
+            .parse::<usize>()
+            .c(d!())?;
+        let lse = m
+            .value_of("ledger-snapshot-export")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("LEDGER_SNAPSHOT_EXPORT").ok())
+            .or_else(|| fc.ledger_snapshot_export.clone());
+        let lsi = m
+            .value_of("ledger-snapshot-import")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("LEDGER_SNAPSHOT_IMPORT").ok())
+            .or_else(|| fc.ledger_snapshot_import.clone());
         let checkpoint_path = m
             .value_of("checkpoint-file")
             .map(|v| v.to_owned())
+            .or_else(|| fc.checkpoint_file.clone())
             .unwrap_or_else(|| String::from("./checkpoint.toml"));
 
         let res = Config {
@@ -976,6 +1442,32 @@ pub mod global_cfg {
             tendermint_node_self_addr: tnsa,
             tendermint_node_key_config_path: tnkcp,
             ledger_dir: ld,
+            mem_ceiling_mb: mcm,
+            zstd_level: zl,
+            invariant_check_itv: ici,
+            staking_cache_refresh: scr,
+            admin_token: adt.clone(),
+            enable_audit_api: eaa,
+            state_sync_interval: ssi,
+            state_sync_keep: ssk,
+            enable_state_sync_restore: essr,
+            backup_interval: bi,
+            backup_target: bt,
+            backup_keep: bk,
+            log_json,
+            log_level: ll,
+            log_sample_every: lsn,
+            ledger_snapshot_export: lse,
+            ledger_verify: m.is_present("ledger-verify")
+                || env::var("LEDGER_VERIFY").is_ok(),
+            ledger_replay_diff: m
+                .value_of("ledger-replay-diff")
+                .map(|v| v.to_owned())
+                .or_else(|| env::var("LEDGER_REPLAY_DIFF").ok()),
+            ledger_snapshot_import: lsi,
+            cors: parse_cors(&m, &fc),
+            auth: parse_auth(&m, &fc, adt.as_deref()),
+            limits: parse_limits(&m, &fc).c(d!())?,
             #[cfg(target_os = "linux")]
             btmcfg: parse_btmcfg(&m).c(d!())?,
             checkpoint: CheckPointConfig::from_file(&checkpoint_path).unwrap(),
@@ -984,6 +1476,106 @@ pub mod global_cfg {
         Ok(res)
     }
 
+    #[cfg(not(test))]
+    fn parse_cors(m: &ArgMatches, fc: &FileCfg) -> CorsCfg {
+        // an empty list or a "*" means any
+        let list = |name: &str, var: &str, file: &Option<Vec<String>>| {
+            m.value_of(name)
+                .map(|v| v.to_owned())
+                .or_else(|| env::var(var).ok())
+                .or_else(|| file.as_ref().map(|l| l.join(",")))
+                .map(|v| {
+                    v.split(',')
+                        .map(|i| i.trim().to_owned())
+                        .filter(|i| !i.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .filter(|l| !l.is_empty() && !l.iter().any(|i| "*" == i))
+        };
+
+        CorsCfg {
+            disabled: m.is_present("disable-cors")
+                || env::var("DISABLE_CORS").is_ok()
+                || fc.disable_cors.unwrap_or(false),
+            allowed_origins: list(
+                "cors-allowed-origins",
+                "CORS_ALLOWED_ORIGINS",
+                &fc.cors_allowed_origins,
+            ),
+            allowed_methods: list(
+                "cors-allowed-methods",
+                "CORS_ALLOWED_METHODS",
+                &fc.cors_allowed_methods,
+            ),
+            allowed_headers: list(
+                "cors-allowed-headers",
+                "CORS_ALLOWED_HEADERS",
+                &fc.cors_allowed_headers,
+            ),
+        }
+    }
+
+    #[cfg(not(test))]
+    fn parse_auth(m: &ArgMatches, fc: &FileCfg, admin_token: Option<&str>) -> AuthCfg {
+        let mut api_keys = fc.api_keys.clone().unwrap_or_default();
+        api_keys.retain(|k, _| !k.is_empty());
+        if let Some(t) = admin_token {
+            api_keys.insert(t.to_owned(), ApiRole::Admin);
+        }
+
+        AuthCfg {
+            api_keys,
+            jwt_secret: m
+                .value_of("jwt-secret")
+                .map(|v| v.to_owned())
+                .or_else(|| env::var("JWT_SECRET").ok())
+                .or_else(|| fc.jwt_secret.clone())
+                .filter(|s| !s.is_empty()),
+        }
+    }
+
+    #[cfg(not(test))]
+    fn parse_limits(m: &ArgMatches, fc: &FileCfg) -> Result<LimitsCfg> {
+        let default = LimitsCfg::default();
+        let num = |name: &str, var: &str, file: Option<usize>, default: usize| {
+            m.value_of(name)
+                .map(|v| v.to_owned())
+                .or_else(|| env::var(var).ok())
+                .map(|v| v.parse::<usize>().c(d!(format!("invalid `{name}`"))))
+                .transpose()
+                .map(|v| v.or(file).unwrap_or(default))
+                .and_then(|v| {
+                    if 0 == v {
+                        Err(eg!(format!("`{name}` should be positive")))
+                    } else {
+                        Ok(v)
+                    }
+                })
+        };
+
+        Ok(LimitsCfg {
+            max_batch_len: num(
+                "max-batch-len",
+                "MAX_BATCH_LEN",
+                fc.max_batch_len,
+                default.max_batch_len,
+            )?,
+            query_payload_limit: num(
+                "query-payload-limit",
+                "QUERY_PAYLOAD_LIMIT",
+                fc.query_payload_limit,
+                default.query_payload_limit,
+            )?,
+            submission_payload_limit: num(
+                "submission-payload-limit",
+                "SUBMISSION_PAYLOAD_LIMIT",
+                fc.submission_payload_limit,
+                default.submission_payload_limit,
+            )?,
+            route_payload_limits: fc.route_payload_limits.clone().unwrap_or_default(),
+        })
+    }
+
     #[cfg(not(test))]
     fn print_version(m: &ArgMatches) {
         if m.is_present("version") {
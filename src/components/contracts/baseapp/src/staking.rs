@@ -297,6 +297,40 @@ impl EVMStaking for BaseApp {
         Ok(())
     }
 
+    fn redelegate(
+        &self,
+        delegator: &XfrPublicKey,
+        src_validator: &[u8],
+        dst_validator: &[u8],
+        amount: u64,
+    ) -> Result<()> {
+        let delegator_address = mapping_address(delegator);
+
+        let from = H160::from_str(SYSTEM_ADDR).c(d!())?;
+
+        let amount =
+            EthereumDecimalsMapping::from_native_token(U256::from(amount)).c(d!())?;
+
+        if let Err(e) = self.modules.evm_module.redelegate(
+            &self.deliver_state,
+            from,
+            H160::from_slice(src_validator),
+            H160::from_slice(dst_validator),
+            delegator_address,
+            amount,
+        ) {
+            self.deliver_state.state.write().discard_session();
+            self.deliver_state.db.write().discard_session();
+            tracing::error!(target: "evm staking", "redelegate error:{:?}", e);
+            return Err(e);
+        };
+
+        self.deliver_state.state.write().commit_session();
+        self.deliver_state.db.write().commit_session();
+
+        Ok(())
+    }
+
     fn update_validator(
         &self,
         staker: &XfrPublicKey,
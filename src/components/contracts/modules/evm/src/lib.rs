@@ -819,6 +819,63 @@ impl<C: Config> App<C> {
         Ok(())
     }
 
+    pub fn redelegate(
+        &self,
+        ctx: &Context,
+        from: H160,
+        src_validator: H160,
+        dst_validator: H160,
+        delegator: H160,
+        amount: U256,
+    ) -> Result<()> {
+        let function = self
+            .contracts
+            .staking
+            .function("systemRedelegate")
+            .c(d!())?;
+
+        let src_validator = Token::Address(src_validator);
+        let dst_validator = Token::Address(dst_validator);
+        let delegator = Token::Address(delegator);
+        let amount = Token::Uint(amount);
+        let input = function
+            .encode_input(&[src_validator, dst_validator, delegator, amount])
+            .c(d!())?;
+
+        let gas_limit = u64::MAX;
+        let value = U256::zero();
+
+        tracing::info!(
+            target: "evm staking",
+            "systemRedelegate from:{:?} gas_limit:{} value:{} contracts_address:{:?} input:{}",
+            from,
+            gas_limit,
+            value,
+            self.contracts.staking_address,
+            hex::encode(&input)
+        );
+
+        let (_, logs, used_gas) = ActionRunner::<C>::execute_systemc_contract(
+            ctx,
+            input.clone(),
+            from,
+            gas_limit,
+            self.contracts.staking_address,
+            value,
+        )?;
+        Self::store_transaction(
+            ctx,
+            U256::from(gas_limit),
+            from,
+            self.contracts.staking_address,
+            value,
+            input,
+            &logs,
+            used_gas,
+        )?;
+        Ok(())
+    }
+
     pub fn claim(
         &self,
         ctx: &Context,
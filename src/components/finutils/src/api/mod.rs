@@ -2,6 +2,8 @@
 //! This module defines findora ledger/query rpc apis for server and client.
 //!
 
+pub mod openapi;
+
 use {
     ledger::staking::{
         self, StakerMemo, TendermintAddr, MAX_POWER_PERCENT_PER_VALIDATOR,
@@ -16,6 +18,9 @@ pub struct ValidatorList {
     validator_cnt: u64,
     cur_height: u64,
     validators: Vec<Validator>,
+    /// seconds since the served staking view was built
+    #[serde(default)]
+    pub cache_age: u64,
 }
 
 impl ValidatorList {
@@ -26,6 +31,7 @@ impl ValidatorList {
             validator_cnt: validators.len() as u64,
             cur_height,
             validators,
+            cache_age: 0,
         }
     }
 }
@@ -152,6 +158,9 @@ pub struct DelegationInfo {
     pub delegation_rwd_cnt: u64,
     /// how many times of proposing this validator has performed
     pub proposer_rwd_cnt: u64,
+    /// seconds since the served staking view was built
+    #[serde(default)]
+    pub cache_age: u64,
 }
 
 impl DelegationInfo {
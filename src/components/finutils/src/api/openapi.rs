@@ -0,0 +1,486 @@
+//!
+//! # OpenAPI document
+//!
+//! A small hand-maintained builder of OpenAPI 3 documents,
+//! the paths are taken from the `NetworkRoute` implementations,
+//! so the document can not drift from the routes served by the nodes.
+//!
+
+use {
+    serde_json::{json, Map, Value},
+    std::collections::BTreeMap,
+};
+
+/// Version of the OpenAPI specification being generated
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+/// Helpers to build the JSON schemas of the serde types
+pub mod schema {
+    use serde_json::{json, Map, Value};
+
+    #[allow(missing_docs)]
+    pub fn string() -> Value {
+        json!({"type": "string"})
+    }
+
+    #[allow(missing_docs)]
+    pub fn integer() -> Value {
+        json!({"type": "integer", "format": "int64", "minimum": 0})
+    }
+
+    #[allow(missing_docs)]
+    pub fn number() -> Value {
+        json!({"type": "number"})
+    }
+
+    #[allow(missing_docs)]
+    pub fn boolean() -> Value {
+        json!({"type": "boolean"})
+    }
+
+    /// `Vec<T>`, `BTreeSet<T>`
+    pub fn array(items: Value) -> Value {
+        json!({"type": "array", "items": items})
+    }
+
+    /// `[T; N]`, or a tuple whose elements have the same schema
+    pub fn fixed_array(items: Value, n: usize) -> Value {
+        json!({"type": "array", "items": items, "minItems": n, "maxItems": n})
+    }
+
+    /// `(A, B, ..)`, OpenAPI 3.0 can not describe the element types by position
+    pub fn tuple(items: Vec<Value>) -> Value {
+        let n = items.len();
+        json!({"type": "array", "items": {"oneOf": items}, "minItems": n, "maxItems": n})
+    }
+
+    /// `Option<T>`
+    pub fn nullable(s: Value) -> Value {
+        json!({"allOf": [s], "nullable": true})
+    }
+
+    /// `BTreeMap<K, V>` where `K` is serialized as a string
+    pub fn map(values: Value) -> Value {
+        json!({"type": "object", "additionalProperties": values})
+    }
+
+    /// A reference to a schema registered with `OpenApi::schema`
+    pub fn reference(name: &str) -> Value {
+        json!({"$ref": format!("#/components/schemas/{name}")})
+    }
+
+    /// A struct, all fields are required
+    pub fn object(fields: &[(&str, Value)]) -> Value {
+        let properties = fields
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect::<Map<_, _>>();
+        let required = fields.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+        json!({"type": "object", "properties": properties, "required": required})
+    }
+
+    /// An externally tagged enum, e.g. `{"Jailed": {...}}`
+    pub fn tagged(variants: &[(&str, Value)]) -> Value {
+        let one_of = variants
+            .iter()
+            .map(|(k, v)| object(&[(*k, v.clone())]))
+            .collect::<Vec<_>>();
+        json!({ "oneOf": one_of })
+    }
+
+    /// A type whose layout is defined by external crates,
+    /// clients are expected to treat it as an opaque value
+    pub fn opaque(description: &str) -> Value {
+        json!({"description": description})
+    }
+}
+
+/// A query or path parameter
+#[derive(Clone, Debug)]
+struct Param {
+    name: String,
+    location: &'static str,
+    required: bool,
+    schema: Value,
+}
+
+/// A `GET`, `POST` or `DELETE` operation
+#[derive(Clone, Debug)]
+pub struct Operation {
+    summary: String,
+    params: Vec<Param>,
+    body: Option<Value>,
+    response: Value,
+    text: bool,
+}
+
+impl Operation {
+    /// An operation responding with a JSON body
+    pub fn new(summary: &str, response: Value) -> Self {
+        Operation {
+            summary: summary.to_owned(),
+            params: vec![],
+            body: None,
+            response,
+            text: false,
+        }
+    }
+
+    /// An operation responding with a plain text body
+    pub fn text(summary: &str) -> Self {
+        Operation {
+            text: true,
+            ..Self::new(summary, schema::string())
+        }
+    }
+
+    /// Declare a path parameter, those not declared are regarded as strings
+    pub fn path(mut self, name: &str, schema: Value) -> Self {
+        self.params.push(Param {
+            name: name.to_owned(),
+            location: "path",
+            required: true,
+            schema,
+        });
+        self
+    }
+
+    /// Declare a query parameter
+    pub fn query(mut self, name: &str, schema: Value, required: bool) -> Self {
+        self.params.push(Param {
+            name: name.to_owned(),
+            location: "query",
+            required,
+            schema,
+        });
+        self
+    }
+
+    /// Declare a header parameter
+    pub fn header(mut self, name: &str, schema: Value, required: bool) -> Self {
+        self.params.push(Param {
+            name: name.to_owned(),
+            location: "header",
+            required,
+            schema,
+        });
+        self
+    }
+
+    /// Declare the JSON body of a `POST` operation
+    pub fn body(mut self, schema: Value) -> Self {
+        self.body = Some(schema);
+        self
+    }
+
+    fn to_json(&self, route: &str, error_schema: Option<&str>) -> Value {
+        let mut params = self.params.clone();
+        for name in path_params(route) {
+            if !params
+                .iter()
+                .any(|p| p.location == "path" && p.name == name)
+            {
+                params.push(Param {
+                    name,
+                    location: "path",
+                    required: true,
+                    schema: schema::string(),
+                });
+            }
+        }
+
+        let params = params
+            .into_iter()
+            .map(|p| {
+                json!({
+                    "name": p.name,
+                    "in": p.location,
+                    "required": p.required,
+                    "schema": p.schema,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let content_type = if self.text {
+            "text/plain"
+        } else {
+            "application/json"
+        };
+        let mut responses = Map::new();
+        responses.insert(
+            "200".to_owned(),
+            json!({
+                "description": "OK",
+                "content": {content_type: {"schema": self.response}},
+            }),
+        );
+        if let Some(e) = error_schema {
+            responses.insert(
+                "default".to_owned(),
+                json!({
+                    "description": "Error",
+                    "content": {"application/json": {"schema": schema::reference(e)}},
+                }),
+            );
+        }
+
+        let mut op = json!({
+            "summary": self.summary,
+            "parameters": params,
+            "responses": responses,
+        });
+        if let Some(body) = self.body.as_ref() {
+            op["requestBody"] = json!({
+                "required": true,
+                "content": {"application/json": {"schema": body}},
+            });
+        }
+        op
+    }
+}
+
+/// The builder of an OpenAPI document
+#[derive(Clone, Debug)]
+pub struct OpenApi {
+    title: String,
+    version: String,
+    // route => method => operation
+    paths: BTreeMap<String, BTreeMap<&'static str, Operation>>,
+    schemas: BTreeMap<String, Value>,
+    error_schema: Option<String>,
+}
+
+impl OpenApi {
+    #[allow(missing_docs)]
+    pub fn new(title: &str, version: &str) -> Self {
+        OpenApi {
+            title: title.to_owned(),
+            version: version.to_owned(),
+            paths: BTreeMap::new(),
+            schemas: BTreeMap::new(),
+            error_schema: None,
+        }
+    }
+
+    /// Add a `GET` operation, `route` is in the format of actix,
+    /// e.g. `ApiRoutes::UtxoSid.with_arg_template("sid")`
+    pub fn get(self, route: &str, op: Operation) -> Self {
+        self.operation(route, "get", op)
+    }
+
+    /// Add a `POST` operation, see `get`
+    pub fn post(self, route: &str, op: Operation) -> Self {
+        self.operation(route, "post", op)
+    }
+
+    /// Add a `DELETE` operation, see `get`
+    pub fn delete(self, route: &str, op: Operation) -> Self {
+        self.operation(route, "delete", op)
+    }
+
+    fn operation(mut self, route: &str, method: &'static str, op: Operation) -> Self {
+        let route = format!("/{}", route.trim_start_matches('/'));
+        self.paths.entry(route).or_default().insert(method, op);
+        self
+    }
+
+    /// Register a named schema, refer to it with `schema::reference`
+    pub fn schema(mut self, name: &str, s: Value) -> Self {
+        self.schemas.insert(name.to_owned(), s);
+        self
+    }
+
+    /// The schema of the error body shared by all operations
+    pub fn error_schema(mut self, name: &str) -> Self {
+        self.error_schema = Some(name.to_owned());
+        self
+    }
+
+    /// Check whether a path has been documented
+    pub fn contains(&self, route: &str) -> bool {
+        self.paths
+            .contains_key(&format!("/{}", route.trim_start_matches('/')))
+    }
+
+    /// Generate the document
+    pub fn build(&self) -> Value {
+        let paths = self
+            .paths
+            .iter()
+            .map(|(route, ops)| {
+                let ops = ops
+                    .iter()
+                    .map(|(method, op)| {
+                        let op = op.to_json(route, self.error_schema.as_deref());
+                        (method.to_string(), op)
+                    })
+                    .collect::<Map<_, _>>();
+                (route.clone(), Value::Object(ops))
+            })
+            .collect::<Map<_, _>>();
+
+        json!({
+            "openapi": OPENAPI_VERSION,
+            "info": {"title": self.title, "version": self.version},
+            "paths": paths,
+            "components": {"schemas": self.schemas},
+        })
+    }
+}
+
+// `/utxo_sid/{sid}` => ["sid"]
+fn path_params(route: &str) -> Vec<String> {
+    route
+        .split('/')
+        .filter_map(|seg| seg.strip_prefix('{')?.strip_suffix('}'))
+        .map(|s| s.to_owned())
+        .collect()
+}
+
+/// Schemas of the types defined in this module,
+/// registered as `ValidatorList`, `ValidatorDetail` etc.
+pub fn with_staking_schemas(doc: OpenApi) -> OpenApi {
+    use schema::*;
+
+    let rate = || fixed_array(integer(), 2);
+    doc.schema(
+        "StakerMemo",
+        object(&[
+            ("name", string()),
+            ("desc", string()),
+            ("website", string()),
+            ("logo", string()),
+        ]),
+    )
+    .schema(
+        "Validator",
+        object(&[
+            ("addr", string()),
+            ("power", integer()),
+            ("commission_rate", rate()),
+            ("accept_delegation", boolean()),
+            ("rank", integer()),
+            ("extra", reference("StakerMemo")),
+        ]),
+    )
+    .schema(
+        "ValidatorList",
+        object(&[
+            ("threshold", rate()),
+            ("validator_cnt", integer()),
+            ("cur_height", integer()),
+            ("validators", array(reference("Validator"))),
+            ("cache_age", integer()),
+        ]),
+    )
+    .schema(
+        "ValidatorDetail",
+        object(&[
+            ("addr", string()),
+            ("kind", string()),
+            ("is_online", boolean()),
+            ("voting_power", integer()),
+            ("voting_power_rank", integer()),
+            ("commission_rate", rate()),
+            ("self_staking", integer()),
+            ("fra_rewards", integer()),
+            ("memo", reference("StakerMemo")),
+            ("start_height", integer()),
+            ("cur_height", integer()),
+            ("block_signed_cnt", integer()),
+            ("block_proposed_cnt", integer()),
+            ("validator_realtime_apy", rate()),
+            ("delegator_cnt", integer()),
+        ]),
+    )
+    .schema(
+        "DelegatorList",
+        object(&[(
+            "delegators",
+            array(object(&[("addr", string()), ("amount", integer())])),
+        )]),
+    )
+    .schema(
+        "DelegationInfo",
+        object(&[
+            ("bond", integer()),
+            ("bond_entries", array(tuple(vec![string(), integer()]))),
+            ("unbond", integer()),
+            ("rewards", integer()),
+            ("return_rate", rate()),
+            ("global_delegation", integer()),
+            ("global_staking", integer()),
+            ("start_height", integer()),
+            ("end_height", integer()),
+            ("current_height", integer()),
+            ("delegation_rwd_cnt", integer()),
+            ("proposer_rwd_cnt", integer()),
+            ("cache_age", integer()),
+        ]),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{schema::*, *};
+
+    #[test]
+    fn openapi_document() {
+        let doc = with_staking_schemas(OpenApi::new("test", "0.1"))
+            .error_schema("ApiError")
+            .schema("ApiError", object(&[("code", string())]))
+            .get(
+                "/utxo_sid/{sid}",
+                Operation::new("a utxo", reference("Utxo")).path("sid", integer()),
+            )
+            .get(
+                "validator_detail/{addr}",
+                Operation::new("a validator", reference("ValidatorDetail")),
+            )
+            .get(
+                "/delegator_list",
+                Operation::new("delegators", reference("DelegatorList")).query(
+                    "page",
+                    integer(),
+                    true,
+                ),
+            )
+            .get("/ping", Operation::text("ping"))
+            .post(
+                "/delegator_list",
+                Operation::new("delegators of a list", reference("DelegatorList"))
+                    .body(array(string())),
+            );
+        assert!(doc.contains("validator_detail/{addr}"));
+
+        let v = doc.build();
+        assert_eq!(v["openapi"], OPENAPI_VERSION);
+
+        let p = &v["paths"]["/utxo_sid/{sid}"]["get"]["parameters"];
+        assert_eq!(p.as_array().unwrap().len(), 1);
+        assert_eq!(p[0]["schema"]["type"], "integer");
+
+        let p = &v["paths"]["/validator_detail/{addr}"]["get"]["parameters"][0];
+        assert_eq!(p["name"], "addr");
+        assert_eq!(p["in"], "path");
+        assert_eq!(p["schema"]["type"], "string");
+
+        let p = &v["paths"]["/delegator_list"]["get"]["parameters"][0];
+        assert_eq!(p["in"], "query");
+        let b = &v["paths"]["/delegator_list"]["post"]["requestBody"];
+        assert_eq!(b["content"]["application/json"]["schema"]["type"], "array");
+        assert!(v["paths"]["/delegator_list"]["get"]
+            .get("requestBody")
+            .is_none());
+
+        assert!(v["paths"]["/ping"]["get"]["responses"]["200"]["content"]
+            .get("text/plain")
+            .is_some());
+        assert_eq!(
+            v["paths"]["/ping"]["get"]["responses"]["default"]["content"]
+                ["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ApiError"
+        );
+        assert!(v["components"]["schemas"].get("ValidatorList").is_some());
+    }
+}
@@ -30,8 +30,18 @@ use std::str::FromStr;
 use fp_types::H160;
 
 use {
-    clap::{crate_authors, load_yaml, App},
-    finutils::common::{self, evm::*, get_keypair, utils},
+    clap::{crate_authors, load_yaml, App, ArgMatches},
+    finutils::common::{
+        self,
+        addressbook::{self, AddressBook},
+        evm::*,
+        get_keypair, keystore,
+        offline::{Action, Bundle, SignedBundle},
+        shell,
+        template::{self, Overrides, Template},
+        utils,
+    },
+    finutils::txn_builder::coin_selection::CoinSelection,
     fp_utils::ecdsa::SecpPair,
     globutils::wallet,
     ledger::{
@@ -39,10 +49,18 @@ use {
         staking::{StakerMemo, VALIDATORS_MIN},
     },
     ruc::*,
-    std::{fmt, fs},
-    zei::{noah_api::anon_xfr::structs::OpenAnonAssetRecordBuilder, XfrSecretKey},
+    std::{
+        fmt, fs,
+        path::{Path, PathBuf},
+    },
+    zei::{
+        noah_api::anon_xfr::structs::OpenAnonAssetRecordBuilder, XfrKeyPair,
+        XfrPublicKey, XfrSecretKey,
+    },
 };
 
+const DEFAULT_KEYSTORE: &str = "fn_keystore.json";
+
 fn main() {
     if let Err(e) = run() {
         tip_fail(e);
@@ -62,7 +80,193 @@ fn run() -> Result<()> {
         println!("{}", env!("VERGEN_SHA"));
     } else if let Some(m) = matches.subcommand_matches("genkey") {
         let gen_eth_address = m.is_present("gen-eth-address");
-        common::gen_key_and_print(gen_eth_address);
+        if let Some(count) = m.value_of("count") {
+            let count = count.parse::<u64>().c(d!("invalid count"))?;
+            let label = m.value_of("label").unwrap_or("key");
+            let path = m.value_of("keystore").unwrap_or(DEFAULT_KEYSTORE);
+            let password = keystore::read_password().c(d!())?;
+            let mut ks = keystore::Keystore::load_or_create(path).c(d!())?;
+            for e in ks
+                .generate(&password, count, label, gen_eth_address)
+                .c(d!())?
+            {
+                println!("{}\t{}", e.label, e.pubkey);
+            }
+            ks.save(path).c(d!())?;
+        } else {
+            common::gen_key_and_print(gen_eth_address);
+        }
+    } else if let Some(m) = matches.subcommand_matches("restore-key") {
+        let mnemonic = fs::read_to_string(m.value_of("mnemonic-path").unwrap())
+            .c(d!("Failed to read mnemonic file"))?;
+        let index = |name| -> Result<u32> {
+            m.value_of(name)
+                .map(|i| i.parse::<u32>().c(d!(format!("invalid {name}"))))
+                .unwrap_or(Ok(0))
+        };
+        let kp = common::restore_key(
+            &mnemonic,
+            index("account")?,
+            index("address")?,
+            m.is_present("gen-eth-address"),
+        )
+        .c(d!())?;
+
+        if let Some(label) = m.value_of("label") {
+            let path = m.value_of("keystore").unwrap_or(DEFAULT_KEYSTORE);
+            let password = keystore::read_password().c(d!())?;
+            let mut ks = keystore::Keystore::load_or_create(path).c(d!())?;
+            let e = ks.import(&password, label, &kp).c(d!())?;
+            println!("{}\t{}", e.label, e.pubkey);
+            ks.save(path).c(d!())?;
+        } else {
+            println!(
+                "\n\x1b[31;01mWallet Address:\x1b[00m {}\n\x1b[31;01mKey:\x1b[00m {}\n",
+                wallet::public_key_to_bech32(kp.get_pk_ref()),
+                serde_json::to_string_pretty(&kp).c(d!())?
+            );
+        }
+    } else if let Some(m) = matches.subcommand_matches("keystore") {
+        let path = m.value_of("keystore").unwrap_or(DEFAULT_KEYSTORE);
+        let mut ks = keystore::Keystore::load_or_create(path).c(d!())?;
+        if m.is_present("sync-usage") {
+            ks.sync_usage().c(d!())?;
+            ks.save(path).c(d!())?;
+        }
+        if let Some(label) = m.value_of("export") {
+            let e = ks.get(label).c(d!("label not found"))?;
+            println!("\x1b[31;01mPublic Key:\x1b[00m {}", e.pubkey);
+            if m.is_present("show-secret") {
+                let password = keystore::read_password().c(d!())?;
+                let sk = ks.export_secret(label, &password).c(d!())?;
+                println!(
+                    "\x1b[31;01mSecret Key:\x1b[00m {}",
+                    serde_json::to_string(&sk).c(d!())?
+                );
+            }
+        } else if m.is_present("list") || m.is_present("sync-usage") {
+            for e in ks.entries() {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    e.label,
+                    e.pubkey,
+                    alt!(e.used, "used", "unused"),
+                    e.related_txns
+                );
+            }
+        } else {
+            println!("{}", m.usage());
+        }
+    } else if let Some(m) = matches.subcommand_matches("offline") {
+        if let Some(m) = m.subcommand_matches("prepare") {
+            let (owner, action) = offline_action(m).c(d!())?;
+            let bundle = Bundle::prepare(owner, action).c(d!())?;
+            if let Some(code) = bundle.defined_asset() {
+                println!("type: {}", code.to_base64());
+            }
+            fs::write(
+                m.value_of("bundle").unwrap(),
+                serde_json::to_string_pretty(&bundle).c(d!())?,
+            )
+            .c(d!())?;
+            println!("digest: {}", bundle.digest().c(d!())?);
+        } else if let Some(m) = m.subcommand_matches("sign") {
+            let bundle = fs::read_to_string(m.value_of("bundle").unwrap())
+                .c(d!("Failed to read bundle file"))
+                .and_then(|b| {
+                    serde_json::from_str::<Bundle>(&b).c(d!("invalid bundle"))
+                })?;
+            let kp = read_keypair(m.value_of("seckey")).c(d!())?;
+
+            println!("digest: {}", bundle.digest().c(d!())?);
+            let signed = bundle.sign(&kp).c(d!())?;
+            fs::write(
+                m.value_of("signed").unwrap(),
+                serde_json::to_string_pretty(&signed).c(d!())?,
+            )
+            .c(d!())?;
+        } else if let Some(m) = m.subcommand_matches("submit") {
+            let bundle = fs::read_to_string(m.value_of("bundle").unwrap())
+                .c(d!("Failed to read bundle file"))
+                .and_then(|b| {
+                    serde_json::from_str::<Bundle>(&b).c(d!("invalid bundle"))
+                })?;
+            let signed = fs::read_to_string(m.value_of("signed").unwrap())
+                .c(d!("Failed to read signed transaction file"))
+                .and_then(|s| {
+                    serde_json::from_str::<SignedBundle>(&s)
+                        .c(d!("invalid signed transaction"))
+                })?;
+            println!("handle: {}", bundle.submit(&signed).c(d!())?);
+        } else {
+            println!("{}", m.usage());
+        }
+    } else if let Some(m) = matches.subcommand_matches("addressbook") {
+        let path = addressbook_path(m.value_of("book"));
+        let ks = addressbook_keystore(m, &path, m.is_present("encrypt")).c(d!())?;
+        let ks = ks.as_ref().map(|(ks, password)| (ks, password.as_str()));
+        let mut book = AddressBook::load(&path, ks).c(d!())?;
+        if let Some(name) = m.value_of("add") {
+            let addr = m.value_of("address").c(d!("missing address"))?;
+            let pk = utils::parse_address(addr).c(d!())?;
+            book.add(name, &pk).c(d!())?;
+            book.save(&path, ks).c(d!())?;
+        } else if let Some(name) = m.value_of("remove") {
+            book.remove(name).c(d!())?;
+            book.save(&path, ks).c(d!())?;
+        } else if m.is_present("list") {
+            for (name, addr) in book.entries() {
+                println!("{name}: {addr}");
+            }
+        } else if m.is_present("encrypt") {
+            book.save(&path, ks).c(d!())?;
+        } else {
+            println!("{}", m.usage());
+        }
+    } else if let Some(m) = matches.subcommand_matches("template") {
+        let dir = template::default_dir();
+        if let Some(m) = m.subcommand_matches("save") {
+            let (owner, action) = offline_action(m).c(d!())?;
+            let t = Template::new(m.value_of("name").unwrap(), owner, action).c(d!())?;
+            t.save(&dir).c(d!())?;
+            println!("{}", t.describe());
+        } else if let Some(m) = m.subcommand_matches("load") {
+            let t = Template::load(&dir, m.value_of("name").unwrap()).c(d!())?;
+            let overrides = Overrides {
+                amount: m
+                    .value_of("amount")
+                    .map(|am| {
+                        am.parse::<u64>()
+                            .c(d!("amount should be a 64-bits unsigned integer"))
+                    })
+                    .transpose()?,
+                to: m
+                    .value_of("to-wallet-address")
+                    .map(|addr| utils::parse_address(addr).c(d!()))
+                    .transpose()?,
+            };
+            let bundle = t.instantiate(&overrides).c(d!())?;
+            if let Some(path) = m.value_of("bundle") {
+                fs::write(path, serde_json::to_string_pretty(&bundle).c(d!())?)
+                    .c(d!())?;
+                println!("digest: {}", bundle.digest().c(d!())?);
+            } else {
+                let kp = read_keypair(m.value_of("seckey")).c(d!())?;
+                let signed = bundle.sign(&kp).c(d!())?;
+                println!("handle: {}", bundle.submit(&signed).c(d!())?);
+            }
+        } else if m.subcommand_matches("list").is_some() {
+            for t in Template::list(&dir).c(d!())? {
+                println!("{}", t.describe());
+            }
+        } else if let Some(m) = m.subcommand_matches("remove") {
+            Template::remove(&dir, m.value_of("name").unwrap()).c(d!())?;
+        } else {
+            println!("{}", m.usage());
+        }
+    } else if let Some(m) = matches.subcommand_matches("shell") {
+        let path = m.value_of("keystore").unwrap_or(DEFAULT_KEYSTORE);
+        shell::run(path).c(d!())?;
     } else if let Some(m) = matches.subcommand_matches("wallet") {
         if m.is_present("create") {
             let is_address_eth = m.is_present("gen-eth-address");
@@ -86,6 +290,18 @@ fn run() -> Result<()> {
                 None
             };
             common::show_account(seckey.as_deref(), asset, is_address_eth).c(d!())?;
+        } else if m.is_present("consolidate") {
+            let seckey = match m.value_of("seckey") {
+                Some(path) => {
+                    Some(fs::read_to_string(path).c(d!("Failed to read seckey file"))?)
+                }
+                None => None,
+            };
+            let is_address_eth = m.is_present("use-default-eth-address");
+            let asset = m
+                .value_of("asset")
+                .filter(|code| "fra" != code.to_lowercase());
+            common::consolidate(seckey.as_deref(), asset, is_address_eth).c(d!())?;
         } else {
             println!("{}", m.usage());
         }
@@ -197,14 +413,25 @@ fn run() -> Result<()> {
                 .unwrap()
                 .parse::<u64>()
                 .c(d!("amount should be a 64-bits unsigned integer"))?;
-            let hidden = m.is_present("hidden");
+            let confidential_amount =
+                m.is_present("hidden") || m.is_present("confidential-amount");
+            let confidential_type = m.is_present("confidential-type");
+            let tracking_key = match m.value_of("tracking-key") {
+                Some(path) => Some(
+                    fs::read_to_string(path)
+                        .c(d!("Failed to read tracking key file"))?,
+                ),
+                None => None,
+            };
             let is_address_eth = m.is_present("use-default-eth-address");
 
             common::issue_asset(
                 seckey.as_deref(),
                 code.unwrap(),
                 amount,
-                hidden,
+                confidential_amount,
+                confidential_type,
+                tracking_key.as_deref(),
                 is_address_eth,
             )
             .c(d!())?;
@@ -338,8 +565,16 @@ fn run() -> Result<()> {
             .c(d!())
             .and_then(|pk| wallet::public_key_from_base64(pk).c(d!()))
             .or_else(|_| {
-                m.value_of("to-wallet-address").c(d!()).and_then(|addr| {
-                    wallet::public_key_from_bech32(addr).c(d!("invalid wallet address"))
+                m.value_of("to-wallet-address")
+                    .c(d!())
+                    .and_then(|addr| utils::parse_address(addr).c(d!()))
+            })
+            .or_else(|_| {
+                m.value_of("to").c(d!("missing receiver")).and_then(|to| {
+                    addressbook_of(m, to.trim().starts_with(addressbook::NAME_PREFIX))
+                        .c(d!())?
+                        .resolve(to)
+                        .c(d!())
                 })
             })?;
         let am = m.value_of("amount");
@@ -361,6 +596,7 @@ fn run() -> Result<()> {
                 m.is_present("confidential-amount"),
                 m.is_present("confidential-type"),
                 is_address_eth,
+                coin_selection(m).c(d!())?,
             )
             .c(d!())?;
         }
@@ -385,9 +621,13 @@ fn run() -> Result<()> {
             .or_else(|_| {
                 m.value_of("to-wallet-address-file").c(d!()).and_then(|f| {
                     fs::read_to_string(f).c(d!()).and_then(|addrs| {
+                        let by_name = addrs.lines().any(|addr| {
+                            addr.trim().starts_with(addressbook::NAME_PREFIX)
+                        });
+                        let book = addressbook_of(m, by_name).c(d!())?;
                         addrs
                             .lines()
-                            .map(|addr| wallet::public_key_from_bech32(addr.trim()))
+                            .map(|addr| book.resolve(addr))
                             .collect::<Result<Vec<_>>>()
                             .c(d!("invalid file"))
                     })
@@ -407,9 +647,22 @@ fn run() -> Result<()> {
                 m.is_present("confidential-amount"),
                 m.is_present("confidential-type"),
                 is_address_eth,
+                coin_selection(m).c(d!())?,
             )
             .c(d!())?;
         }
+    } else if let Some(m) = matches.subcommand_matches("payout") {
+        let seckey = read_file_path(m.value_of("from-seckey")).c(d!())?;
+        common::payout(
+            seckey.as_deref(),
+            m.value_of("csv").c(d!())?,
+            m.value_of("asset"),
+            m.is_present("confidential-amount"),
+            m.is_present("confidential-type"),
+            m.is_present("use-default-eth-address"),
+            coin_selection(m).c(d!())?,
+        )
+        .c(d!())?;
     } else if matches.is_present("gen-eth-key") {
         let (pair, phrase, _) = SecpPair::generate_with_phrase(None);
         let kp = hex::encode(pair.seed());
@@ -517,9 +770,9 @@ fn run() -> Result<()> {
             .c(d!())
             .and_then(wallet::public_key_from_base64)
             .or_else(|_| {
-                m.value_of("to-wallet-address").c(d!()).and_then(|addr| {
-                    wallet::public_key_from_bech32(addr).c(d!("invalid wallet address"))
-                })
+                m.value_of("to-wallet-address")
+                    .c(d!())
+                    .and_then(|addr| utils::parse_address(addr).c(d!()))
             })?;
 
         // get the commitments for abar conversion and anon_fee
@@ -1128,6 +1381,113 @@ fn read_file_path(path: Option<&str>) -> Result<Option<String>> {
     })
 }
 
+fn addressbook_path(path: Option<&str>) -> PathBuf {
+    path.map(PathBuf::from)
+        .unwrap_or_else(addressbook::default_path)
+}
+
+// the keystore of `--keystore` and its password,
+// if the address book at `path` is sealed or to be sealed
+fn addressbook_keystore(
+    m: &ArgMatches,
+    path: &Path,
+    seal: bool,
+) -> Result<Option<(keystore::Keystore, String)>> {
+    if !seal && !AddressBook::is_sealed(path) {
+        return Ok(None);
+    }
+    let ks_path = m.value_of("keystore").unwrap_or(DEFAULT_KEYSTORE);
+    let ks = keystore::Keystore::load_or_create(ks_path).c(d!())?;
+    let password = keystore::read_password().c(d!())?;
+    Ok(Some((ks, password)))
+}
+
+// the address book of `--addressbook`, read only if a recipient is given by name
+fn addressbook_of(m: &ArgMatches, by_name: bool) -> Result<AddressBook> {
+    if !by_name {
+        return Ok(AddressBook::default());
+    }
+    let path = addressbook_path(m.value_of("addressbook"));
+    let ks = addressbook_keystore(m, &path, false).c(d!())?;
+    AddressBook::load(&path, ks.as_ref().map(|(ks, pw)| (ks, pw.as_str()))).c(d!())
+}
+
+// the key pair in the file of base64-formatted `XfrPrivateKey` at `path`
+fn read_keypair(path: Option<&str>) -> Result<XfrKeyPair> {
+    read_file_path(path)
+        .c(d!())?
+        .c(d!("missing secret key file"))
+        .and_then(|sk| {
+            serde_json::from_str::<XfrSecretKey>(&format!("\"{sk}\""))
+                .c(d!("Invalid secret key"))
+        })
+        .map(|sk| sk.into_keypair())
+}
+
+// the owner and the action of `offline prepare` and `template save`
+fn offline_action(m: &ArgMatches) -> Result<(XfrPublicKey, Action)> {
+    let owner = utils::parse_address(m.value_of("owner").unwrap()).c(d!())?;
+    let code = match m.value_of("code") {
+        Some(code) => {
+            Some(AssetTypeCode::new_from_base64(code).c(d!("invalid asset code"))?)
+        }
+        None => None,
+    };
+    let amount = || -> Result<u64> {
+        m.value_of("amount")
+            .c(d!("missing amount"))?
+            .parse::<u64>()
+            .c(d!("amount should be a 64-bits unsigned integer"))
+    };
+
+    let action = if m.is_present("define") {
+        let decimal = match m.value_of("decimal") {
+            Some(num) => num
+                .parse::<u8>()
+                .c(d!("decimal should be an 8-bits unsinged integer"))?,
+            None => FRA_DECIMALS,
+        };
+        let max_units = match m.value_of("maximum") {
+            Some(max) => Some(
+                max.parse::<u64>()
+                    .c(d!("maximum should be an unsigned integer"))?,
+            ),
+            None => None,
+        };
+        Action::Define {
+            code: code.unwrap_or_else(AssetTypeCode::gen_random),
+            memo: m.value_of("memo").c(d!("missing memo"))?.to_owned(),
+            decimal,
+            max_units,
+            transferable: m.is_present("transferable"),
+        }
+    } else if m.is_present("issue") {
+        Action::Issue {
+            code: code.c(d!("missing asset code"))?,
+            amount: amount()?,
+        }
+    } else {
+        let to = m
+            .value_of("to-wallet-address")
+            .c(d!("missing receiver"))
+            .and_then(|addr| utils::parse_address(addr).c(d!()))?;
+        Action::Transfer {
+            to,
+            amount: amount()?,
+            code,
+        }
+    };
+    Ok((owner, action))
+}
+
+// the `coin-selection` argument, largest first by default
+fn coin_selection(m: &ArgMatches) -> Result<CoinSelection> {
+    m.value_of("coin-selection")
+        .map(CoinSelection::parse)
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
 fn tip_fail(e: impl fmt::Display) {
     eprintln!("\n\x1b[31;01mFAIL !!!\x1b[00m");
     eprintln!(
@@ -251,6 +251,8 @@ mod issue {
                         id: None,
                         record: BlindAssetRecord::from_noah(&ba),
                         lien: None,
+                        cosig_rules: None,
+                        lock_until: 0,
                     },
                     None,
                 )
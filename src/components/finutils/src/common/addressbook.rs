@@ -0,0 +1,218 @@
+//!
+//! # Address book
+//!
+//! Names of the recipients, so transfers can be sent to `name:alice`
+//! instead of a pasted public key. The book is a JSON file, either in plain
+//! text or sealed under the password of a keystore.
+//!
+
+use {
+    super::{keystore::Keystore, utils::parse_address},
+    globutils::wallet,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        fs,
+        path::{Path, PathBuf},
+    },
+    zei::XfrPublicKey,
+};
+
+const ADDRESSBOOK_VERSION: u8 = 1;
+
+/// Recipients given by name are prefixed by this
+pub const NAME_PREFIX: &str = "name:";
+
+/// Where `fn addressbook` keeps the book if no path is given
+pub fn default_path() -> PathBuf {
+    PathBuf::from(format!("{}/addressbook.json", &*super::CFG_PATH))
+}
+
+// the file, with the entries in plain text or sealed
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BookFile {
+    version: u8,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    entries: BTreeMap<String, String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sealed: Option<String>,
+}
+
+/// Names of public keys
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    // bech32 addresses by name
+    entries: BTreeMap<String, String>,
+}
+
+impl AddressBook {
+    /// Whether the book at `path` is sealed, so a keystore is needed to load it
+    pub fn is_sealed(path: &Path) -> bool {
+        fs::read(path)
+            .ok()
+            .and_then(|b| serde_json::from_slice::<BookFile>(&b).ok())
+            .map(|f| f.sealed.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Load the book at `path`, an empty one if the file does not exist,
+    /// `ks` is the keystore and its password to unseal it
+    pub fn load(path: &Path, ks: Option<(&Keystore, &str)>) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let f = fs::read(path)
+            .c(d!())
+            .and_then(|b| serde_json::from_slice::<BookFile>(&b).c(d!()))?;
+        if f.version > ADDRESSBOOK_VERSION {
+            return Err(eg!(format!(
+                "unsupported address book version: {}",
+                f.version
+            )));
+        }
+
+        let entries = match f.sealed {
+            Some(sealed) => {
+                let (ks, password) =
+                    ks.c(d!("the address book is sealed, a keystore is needed"))?;
+                let plain = ks.unseal(password, &sealed).c(d!())?;
+                serde_json::from_slice(&plain).c(d!())?
+            }
+            None => f.entries,
+        };
+        Ok(AddressBook { entries })
+    }
+
+    /// Save the book to `path`, sealed under the password of `ks` if given
+    pub fn save(&self, path: &Path, ks: Option<(&Keystore, &str)>) -> Result<()> {
+        let f = match ks {
+            Some((ks, password)) => {
+                let plain = serde_json::to_vec(&self.entries).c(d!())?;
+                BookFile {
+                    version: ADDRESSBOOK_VERSION,
+                    entries: BTreeMap::new(),
+                    sealed: Some(ks.seal(password, &plain).c(d!())?),
+                }
+            }
+            None => BookFile {
+                version: ADDRESSBOOK_VERSION,
+                entries: self.entries.clone(),
+                sealed: None,
+            },
+        };
+        let data = serde_json::to_vec_pretty(&f).c(d!())?;
+        fs::write(path, data).c(d!())?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600)).c(d!())?;
+        }
+
+        Ok(())
+    }
+
+    /// Add `name`, an existing one is not replaced
+    pub fn add(&mut self, name: &str, pk: &XfrPublicKey) -> Result<()> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(eg!(format!("invalid name `{name}`")));
+        }
+        if self.entries.contains_key(name) {
+            return Err(eg!(format!("name already exists: {name}")));
+        }
+        self.entries
+            .insert(name.to_owned(), wallet::public_key_to_bech32(pk));
+        Ok(())
+    }
+
+    #[allow(missing_docs)]
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        self.entries
+            .remove(name)
+            .map(|_| ())
+            .c(d!(format!("name not found: {name}")))
+    }
+
+    #[allow(missing_docs)]
+    pub fn get(&self, name: &str) -> Result<XfrPublicKey> {
+        self.entries
+            .get(name)
+            .c(d!(format!("name not found: {name}")))
+            .and_then(|addr| wallet::public_key_from_bech32(addr).c(d!()))
+    }
+
+    /// The names and their bech32 addresses
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.entries.iter()
+    }
+
+    /// The public key of a recipient given as `name:<name>` in the book,
+    /// a bech32 address or a base64 public key
+    pub fn resolve(&self, recipient: &str) -> Result<XfrPublicKey> {
+        let recipient = recipient.trim();
+        match recipient.strip_prefix(NAME_PREFIX) {
+            Some(name) => self.get(name).c(d!()),
+            None => parse_address(recipient).c(d!()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        rand_chacha::ChaChaRng,
+        rand_core::{RngCore, SeedableRng},
+        zei::XfrKeyPair,
+    };
+
+    #[test]
+    fn addressbook_roundtrip() {
+        let mut prng = ChaChaRng::from_entropy();
+        let alice = XfrKeyPair::generate(&mut prng).get_pk();
+        let bob = XfrKeyPair::generate(&mut prng).get_pk();
+        let n = prng.next_u64();
+        let path = format!("/tmp/fn_addressbook_{n}.json");
+        let path = Path::new(&path);
+        let ks_path = format!("/tmp/fn_keystore_{n}.json");
+
+        let mut book = pnk!(AddressBook::load(path, None));
+        pnk!(book.add("alice", &alice));
+        assert!(book.add("alice", &bob).is_err());
+        assert!(book.add("bad name", &bob).is_err());
+        pnk!(book.save(path, None));
+        assert!(!AddressBook::is_sealed(path));
+
+        let mut book = pnk!(AddressBook::load(path, None));
+        assert_eq!(alice, pnk!(book.resolve("name:alice")));
+        assert!(book.resolve("name:bob").is_err());
+        let addr = wallet::public_key_to_bech32(&bob);
+        assert_eq!(bob, pnk!(book.resolve(&addr)));
+        assert_eq!(bob, pnk!(book.resolve(&wallet::public_key_to_base64(&bob))));
+        assert!(book.resolve("alice").is_err());
+
+        // sealed under the keystore
+        let mut ks = pnk!(Keystore::load_or_create(&ks_path));
+        assert!(book.save(path, Some((&ks, "pw"))).is_err());
+        pnk!(ks.generate("pw", 1, "deposit", false));
+        pnk!(book.add("bob", &bob));
+        assert!(book.save(path, Some((&ks, "wrong"))).is_err());
+        pnk!(book.save(path, Some((&ks, "pw"))));
+        assert!(AddressBook::is_sealed(path));
+        assert!(!fs::read_to_string(path).unwrap().contains(&addr));
+
+        assert!(AddressBook::load(path, None).is_err());
+        assert!(AddressBook::load(path, Some((&ks, "wrong"))).is_err());
+        let mut book = pnk!(AddressBook::load(path, Some((&ks, "pw"))));
+        assert_eq!(bob, pnk!(book.resolve("name:bob")));
+        pnk!(book.remove("alice"));
+        assert!(book.remove("alice").is_err());
+        assert_eq!(1, book.entries().count());
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(&ks_path);
+    }
+}
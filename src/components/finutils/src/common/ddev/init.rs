@@ -5,7 +5,7 @@ use crate::{
         ddev::{IDX_APP_8668, IDX_APP_8669, IDX_TM_RPC},
         utils::gen_transfer_op_xx,
     },
-    txn_builder::TransactionBuilder,
+    txn_builder::{coin_selection::CoinSelection, TransactionBuilder},
 };
 use globutils::{HashOf, SignatureOf};
 use ledger::{
@@ -123,6 +123,7 @@ pub(super) fn init(env: &mut Env) -> Result<()> {
             false,
             false,
             Some(AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType),
+            CoinSelection::default(),
         )
         .c(d!())
         .map(|principal_op| {
@@ -195,6 +196,7 @@ fn transfer_batch(
         confidential_am,
         confidential_ty,
         None,
+        CoinSelection::default(),
     )
     .c(d!())?;
     builder.add_operation(op);
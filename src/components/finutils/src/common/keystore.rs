@@ -0,0 +1,390 @@
+//!
+//! # Labeled keystore
+//!
+//! Keeps many key pairs in a single file. Public keys are stored in plain text,
+//! secret keys are encrypted with AES-256-GCM under a key derived from a password.
+//!
+
+use {
+    super::{gen_key, utils::get_related_txns},
+    aes_gcm::{
+        aead::{generic_array::GenericArray, Aead, KeyInit},
+        Aes256Gcm,
+    },
+    globutils::wallet,
+    nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg},
+    rand_chacha::ChaChaRng,
+    rand_core::{RngCore, SeedableRng},
+    ring::pbkdf2,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::{
+        env, fs,
+        io::{self, Write},
+        num::NonZeroU32,
+        os::unix::io::AsRawFd,
+        path::Path,
+    },
+    zei::{XfrKeyPair, XfrSecretKey},
+};
+
+const KEYSTORE_VERSION: u8 = 1;
+const SALT_LEN: usize = 32;
+const IV_LEN: usize = 12;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// The password will be read from this env var if present
+pub const KEYSTORE_PASSWORD_ENV: &str = "FN_KEYSTORE_PASSWORD";
+
+/// A labeled key pair
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeystoreEntry {
+    /// Unique name of the key
+    pub label: String,
+    /// Bech32 encoded public key
+    pub pubkey: String,
+    // base64(iv || ciphertext of the secret key)
+    secret: String,
+    /// Whether the key has seen on-chain activity
+    #[serde(default)]
+    pub used: bool,
+    /// Number of related transactions found in the last sync
+    #[serde(default)]
+    pub related_txns: usize,
+}
+
+/// A password protected collection of key pairs
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Keystore {
+    version: u8,
+    // base64 encoded salt of PBKDF2
+    salt: String,
+    entries: Vec<KeystoreEntry>,
+}
+
+impl Keystore {
+    /// Load the keystore from `path`, an empty one is returned if the file does not exist
+    pub fn load_or_create(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            let mut salt = [0u8; SALT_LEN];
+            ChaChaRng::from_entropy().fill_bytes(&mut salt);
+            return Ok(Keystore {
+                version: KEYSTORE_VERSION,
+                salt: base64::encode(salt),
+                entries: vec![],
+            });
+        }
+
+        let ks = fs::read(path)
+            .c(d!())
+            .and_then(|b| serde_json::from_slice::<Keystore>(&b).c(d!()))?;
+        if ks.version > KEYSTORE_VERSION {
+            return Err(eg!(format!("unsupported keystore version: {}", ks.version)));
+        }
+        Ok(ks)
+    }
+
+    /// Persist the keystore to `path`, readable by the owner only
+    pub fn save(&self, path: &str) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self).c(d!())?;
+
+        let mut opts = fs::OpenOptions::new();
+        opts.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            opts.mode(0o600);
+        }
+        let mut f = opts.open(path).c(d!(path))?;
+
+        // the mode is applied to a new file only, tighten an existing one
+        // before anything is written to it
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            f.set_permissions(fs::Permissions::from_mode(0o600))
+                .c(d!())?;
+        }
+
+        f.write_all(&data).c(d!(path))
+    }
+
+    #[allow(missing_docs)]
+    pub fn entries(&self) -> &[KeystoreEntry] {
+        &self.entries
+    }
+
+    #[allow(missing_docs)]
+    pub fn get(&self, label: &str) -> Option<&KeystoreEntry> {
+        self.entries.iter().find(|e| e.label == label)
+    }
+
+    /// Generate `count` new keys labeled as `<prefix>-<index>`,
+    /// the index continues from the existing entries with the same prefix.
+    pub fn generate(
+        &mut self,
+        password: &str,
+        count: u64,
+        prefix: &str,
+        is_address_eth: bool,
+    ) -> Result<Vec<&KeystoreEntry>> {
+        if prefix.is_empty() {
+            return Err(eg!("empty label prefix"));
+        }
+        self.check_password(password).c(d!())?;
+
+        let cipher = self.cipher(password).c(d!())?;
+        let start = self
+            .entries
+            .iter()
+            .filter_map(|e| e.label.strip_prefix(prefix)?.strip_prefix('-'))
+            .filter_map(|i| i.parse::<u64>().ok())
+            .max()
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let n = self.entries.len();
+
+        for idx in start..start.saturating_add(count) {
+            let (_, _, _, kp) = gen_key(is_address_eth);
+            self.push(&cipher, format!("{prefix}-{idx}"), &kp).c(d!())?;
+        }
+
+        Ok(self.entries[n..].iter().collect())
+    }
+
+    /// Add an existing key pair, e.g. one restored from a mnemonic, as `label`
+    pub fn import(
+        &mut self,
+        password: &str,
+        label: &str,
+        kp: &XfrKeyPair,
+    ) -> Result<&KeystoreEntry> {
+        if label.is_empty() {
+            return Err(eg!("empty label"));
+        }
+        if self.get(label).is_some() {
+            return Err(eg!(format!("label already exists: {label}")));
+        }
+        self.check_password(password).c(d!())?;
+
+        let cipher = self.cipher(password).c(d!())?;
+        self.push(&cipher, label.to_owned(), kp).c(d!())?;
+
+        Ok(self.entries.last().unwrap())
+    }
+
+    /// Decrypt the secret key of `label`
+    pub fn export_secret(&self, label: &str, password: &str) -> Result<XfrSecretKey> {
+        let entry = self.get(label).c(d!("label not found"))?;
+        let cipher = self.cipher(password).c(d!())?;
+        let sk = decrypt(&cipher, &entry.secret).c(d!("invalid password"))?;
+        serde_json::from_slice(&sk).c(d!())
+    }
+
+    /// Encrypt `data` under the password of the keystore, e.g. the address book,
+    /// the keystore must hold a key to check the password against
+    pub fn seal(&self, password: &str, data: &[u8]) -> Result<String> {
+        if self.entries.is_empty() {
+            return Err(eg!("no keys in the keystore to check the password against"));
+        }
+        self.check_password(password).c(d!())?;
+        let cipher = self.cipher(password).c(d!())?;
+        encrypt(&cipher, data).c(d!())
+    }
+
+    /// Decrypt what `seal` encrypted
+    pub fn unseal(&self, password: &str, sealed: &str) -> Result<Vec<u8>> {
+        let cipher = self.cipher(password).c(d!())?;
+        decrypt(&cipher, sealed).c(d!("invalid password"))
+    }
+
+    /// Look up the related transactions of every key on the query server
+    pub fn sync_usage(&mut self) -> Result<()> {
+        for e in self.entries.iter_mut() {
+            let pk = wallet::public_key_from_bech32(&e.pubkey).c(d!())?;
+            let n = get_related_txns(&pk).c(d!(&e.label))?.len();
+            e.related_txns = n;
+            e.used = 0 < n;
+        }
+        Ok(())
+    }
+
+    fn push(
+        &mut self,
+        cipher: &Aes256Gcm,
+        label: String,
+        kp: &XfrKeyPair,
+    ) -> Result<()> {
+        let sk = serde_json::to_string(&kp.get_sk()).c(d!())?;
+        self.entries.push(KeystoreEntry {
+            label,
+            pubkey: wallet::public_key_to_bech32(kp.get_pk_ref()),
+            secret: encrypt(cipher, sk.as_bytes()).c(d!())?,
+            used: false,
+            related_txns: 0,
+        });
+        Ok(())
+    }
+
+    // All entries share the same password, check it against the first one
+    fn check_password(&self, password: &str) -> Result<()> {
+        if let Some(e) = self.entries.first() {
+            self.export_secret(&e.label, password).c(d!()).map(|_| ())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn cipher(&self, password: &str) -> Result<Aes256Gcm> {
+        let salt = base64::decode(&self.salt).c(d!())?;
+        let mut derived_key = [0u8; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA512,
+            NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+            &salt,
+            password.as_bytes(),
+            &mut derived_key,
+        );
+        Ok(Aes256Gcm::new(GenericArray::from_slice(&derived_key)))
+    }
+}
+
+fn encrypt(cipher: &Aes256Gcm, plaintext: &[u8]) -> Result<String> {
+    let mut iv = [0u8; IV_LEN];
+    ChaChaRng::from_entropy().fill_bytes(&mut iv);
+    let mut data = cipher
+        .encrypt(GenericArray::from_slice(&iv), plaintext)
+        .map_err(|e| eg!(e))?;
+    let mut res = iv.to_vec();
+    res.append(&mut data);
+    Ok(base64::encode(res))
+}
+
+fn decrypt(cipher: &Aes256Gcm, data: &str) -> Result<Vec<u8>> {
+    let data = base64::decode(data).c(d!())?;
+    if data.len() <= IV_LEN {
+        return Err(eg!("invalid ciphertext"));
+    }
+    cipher
+        .decrypt(GenericArray::from_slice(&data[..IV_LEN]), &data[IV_LEN..])
+        .map_err(|e| eg!(e))
+}
+
+/// Read the keystore password from `FN_KEYSTORE_PASSWORD`, or from the stdin
+pub fn read_password() -> Result<String> {
+    if let Ok(p) = env::var(KEYSTORE_PASSWORD_ENV) {
+        return Ok(p);
+    }
+
+    print!("Keystore password: ");
+    io::stdout().flush().c(d!())?;
+    let p = read_line_hidden().c(d!())?;
+    let p = p.trim_end_matches(&['\r', '\n'][..]).to_owned();
+    if p.is_empty() {
+        return Err(eg!("empty password"));
+    }
+    Ok(p)
+}
+
+// Read a line from the stdin without echoing it,
+// the echo is left as is if the stdin is not a terminal, e.g. a pipe
+fn read_line_hidden() -> Result<String> {
+    let fd = io::stdin().as_raw_fd();
+    let orig = tcgetattr(fd).ok();
+    if let Some(orig) = orig.as_ref() {
+        let mut t = orig.clone();
+        t.local_flags.remove(LocalFlags::ECHO);
+        t.local_flags.insert(LocalFlags::ECHONL);
+        tcsetattr(fd, SetArg::TCSANOW, &t).c(d!())?;
+    }
+
+    let mut line = String::new();
+    let res = io::stdin().read_line(&mut line).c(d!());
+    if let Some(orig) = orig {
+        tcsetattr(fd, SetArg::TCSANOW, &orig).c(d!())?;
+    }
+    res.map(|_| line)
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::common::restore_key};
+
+    #[test]
+    fn keystore_roundtrip() {
+        let path = globutils::fresh_tmp_dir().join("keystore.json");
+        let path = path.to_string_lossy();
+        let mut ks = pnk!(Keystore::load_or_create(&path));
+        assert_eq!(pnk!(ks.generate("pw", 3, "deposit", false)).len(), 3);
+        assert!(ks.generate("wrong", 1, "deposit", false).is_err());
+        pnk!(ks.save(&path));
+
+        let mut ks = pnk!(Keystore::load_or_create(&path));
+        let labels = pnk!(ks.generate("pw", 1, "deposit", false))
+            .into_iter()
+            .map(|e| e.label.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["deposit-3".to_owned()]);
+        assert_eq!(ks.entries().len(), 4);
+
+        let e = ks.get("deposit-1").unwrap().clone();
+        let sk = pnk!(ks.export_secret("deposit-1", "pw"));
+        assert_eq!(
+            wallet::public_key_to_bech32(sk.into_keypair().get_pk_ref()),
+            e.pubkey
+        );
+        assert!(ks.export_secret("deposit-1", "wrong").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn keystore_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+
+        let dir = globutils::fresh_tmp_dir();
+        let mut ks = pnk!(Keystore::load_or_create(""));
+        pnk!(ks.generate("pw", 1, "deposit", false));
+
+        let new = dir.join("new.json");
+        pnk!(ks.save(&new.to_string_lossy()));
+        assert_eq!(0o600, mode(&new));
+
+        // an existing readable file is tightened and overwritten
+        let old = dir.join("old.json");
+        fs::write(&old, vec![b'x'; 4096]).unwrap();
+        fs::set_permissions(&old, fs::Permissions::from_mode(0o644)).unwrap();
+        pnk!(ks.save(&old.to_string_lossy()));
+        assert_eq!(0o600, mode(&old));
+        let ks = pnk!(Keystore::load_or_create(&old.to_string_lossy()));
+        assert!(ks.export_secret("deposit-0", "pw").is_ok());
+
+        // nothing is written into a missing dir
+        assert!(ks
+            .save(&dir.join("missing/ks.json").to_string_lossy())
+            .is_err());
+    }
+
+    #[test]
+    fn keystore_import_restored() {
+        let mnemonic = pnk!(wallet::generate_mnemonic_custom(24, "en"));
+        let kp = pnk!(restore_key(&mnemonic, 0, 0, false));
+        let kp_1 = pnk!(restore_key(&mnemonic, 0, 1, false));
+        assert_eq!(
+            pnk!(restore_key(&mnemonic, 0, 0, false)).get_pk(),
+            kp.get_pk()
+        );
+        assert_ne!(kp_1.get_pk(), kp.get_pk());
+        assert!(restore_key("not a mnemonic", 0, 0, false).is_err());
+
+        let path = globutils::fresh_tmp_dir().join("keystore.json");
+        let mut ks = pnk!(Keystore::load_or_create(&path.to_string_lossy()));
+        pnk!(ks.generate("pw", 1, "deposit", false));
+        assert!(ks.import("wrong", "backup", &kp).is_err());
+        assert!(ks.import("pw", "deposit-0", &kp).is_err());
+        let e = pnk!(ks.import("pw", "backup", &kp)).clone();
+        assert_eq!(e.pubkey, wallet::public_key_to_bech32(kp.get_pk_ref()));
+        let sk = pnk!(ks.export_secret("backup", "pw"));
+        assert_eq!(sk.into_keypair().get_pk(), kp.get_pk());
+    }
+}
@@ -8,6 +8,8 @@
 
 use std::str::FromStr;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod addressbook;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod dev;
 
@@ -15,6 +17,14 @@ pub mod dev;
 pub mod ddev;
 
 pub mod evm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod keystore;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod offline;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shell;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod template;
 pub mod utils;
 
 use {
@@ -22,15 +32,17 @@ use {
     crate::{
         api::DelegationInfo,
         common::utils::{mapping_address, new_tx_builder, send_tx},
-        txn_builder::TransactionBuilder,
+        txn_builder::{
+            coin_selection::CoinSelection, payout::PayoutBuilder, TransactionBuilder,
+        },
     },
     globutils::wallet,
     lazy_static::lazy_static,
     ledger::{
         data_model::{
-            gen_random_keypair, get_abar_commitment, ATxoSID, AssetRules, AssetTypeCode,
-            AssetTypePrefix, Transaction, TxoSID, ASSET_TYPE_FRA,
-            BLACK_HOLE_PUBKEY_STAKING,
+            format_amount, gen_random_keypair, get_abar_commitment, ATxoSID, AssetRules,
+            AssetTypeCode, AssetTypePrefix, Transaction, TxoSID, ASSET_TYPE_FRA,
+            BLACK_HOLE_PUBKEY_STAKING, FRA_DECIMALS,
         },
         staking::{
             check_delegation_amount, td_addr_to_bytes, td_pubkey_to_td_addr,
@@ -58,7 +70,10 @@ use {
                     AssetRecordType,
                     AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
                 },
-                structs::{XfrAmount, XfrAssetType},
+                structs::{
+                    AssetTracerEncKeys, TracerMemo, TracingPolicies, TracingPolicy,
+                    XfrAmount, XfrAssetType,
+                },
             },
         },
         XfrKeyPair, XfrPublicKey, XfrSecretKey,
@@ -426,6 +441,7 @@ pub fn setup(
 }
 
 #[allow(missing_docs)]
+#[allow(clippy::too_many_arguments)]
 pub fn transfer_asset(
     owner_sk: Option<&str>,
     target_addr: XfrPublicKey,
@@ -434,6 +450,7 @@ pub fn transfer_asset(
     confidential_am: bool,
     confidential_ty: bool,
     is_address_eth: bool,
+    selection: CoinSelection,
 ) -> Result<()> {
     transfer_asset_batch(
         owner_sk,
@@ -443,6 +460,7 @@ pub fn transfer_asset(
         confidential_am,
         confidential_ty,
         is_address_eth,
+        selection,
     )
     .c(d!())
 }
@@ -468,6 +486,7 @@ pub fn transfer_asset_x(
 }
 
 #[allow(missing_docs)]
+#[allow(clippy::too_many_arguments)]
 pub fn transfer_asset_batch(
     owner_sk: Option<&str>,
     target_addr: &[XfrPublicKey],
@@ -476,17 +495,18 @@ pub fn transfer_asset_batch(
     confidential_am: bool,
     confidential_ty: bool,
     is_address_eth: bool,
+    selection: CoinSelection,
 ) -> Result<()> {
     let from = restore_keypair_from_str_with_default(owner_sk, is_address_eth)?;
     let am = am.parse::<u64>().c(d!("'amount' must be an integer"))?;
 
-    transfer_asset_batch_x(
+    utils::transfer_batch_x(
         &from,
-        target_addr,
+        target_addr.iter().map(|addr| (*addr, am)).collect(),
         token_code,
-        am,
         confidential_am,
         confidential_ty,
+        selection,
     )
     .c(d!())
 }
@@ -608,6 +628,41 @@ pub fn gen_key(is_address_eth: bool) -> (String, String, String, XfrKeyPair) {
     (wallet_addr, mnemonic, key, kp)
 }
 
+/// Coin type of FRA in the BIP-44 paths of the keys derived from mnemonics,
+/// the keys are derived from `m/44'/917'/<account>'/0/<address>`,
+/// `gen_key` uses the first one, `m/44'/917'/0'/0/0`.
+pub const BIP44_COIN_FRA: u32 = 917;
+
+/// Restore a key pair from its mnemonic, see `BIP44_COIN_FRA` for the derivation path,
+/// eth addresses are always derived from the default path.
+pub fn restore_key(
+    mnemonic: &str,
+    account: u32,
+    address: u32,
+    is_address_eth: bool,
+) -> Result<XfrKeyPair> {
+    let mnemonic = mnemonic.trim();
+    if is_address_eth {
+        if 0 != account || 0 != address {
+            return Err(eg!("eth addresses only support the default path"));
+        }
+        return wallet::restore_keypair_from_mnemonic_secp256k1(mnemonic)
+            .c(d!("invalid mnemonic"));
+    }
+
+    if 0 == account && 0 == address {
+        // the same as `gen_key`
+        wallet::restore_keypair_from_mnemonic_default(mnemonic)
+    } else {
+        wallet::restore_keypair_from_mnemonic_bip44(
+            mnemonic,
+            "en",
+            &wallet::BipPath::new(BIP44_COIN_FRA, account, 0, address),
+        )
+    }
+    .c(d!("invalid mnemonic"))
+}
+
 #[allow(missing_docs)]
 pub fn gen_key_and_print(is_address_eth: bool) {
     let (wallet_addr, mnemonic, key, _) = gen_key(is_address_eth);
@@ -654,6 +709,74 @@ pub fn show_account(
     Ok(())
 }
 
+/// Merge the UTXOs of an asset, FRA by default, see `utils::consolidate`
+pub fn consolidate(
+    sk_str: Option<&str>,
+    asset: Option<&str>,
+    is_address_eth: bool,
+) -> Result<()> {
+    let kp = restore_keypair_from_str_with_default(sk_str, is_address_eth)?;
+    let token_code = asset
+        .map(|asset| AssetTypeCode::new_from_base64(asset).c(d!("Invalid asset code")))
+        .transpose()?;
+    let n = utils::consolidate(&kp, token_code).c(d!())?;
+    println!("UTXOs consolidated by {n} transactions");
+    Ok(())
+}
+
+/// Pay an asset, FRA by default, to the payees of the CSV file `csv`,
+/// see `PayoutBuilder::parse_csv` and `utils::payout`
+#[allow(clippy::too_many_arguments)]
+pub fn payout(
+    sk_str: Option<&str>,
+    csv: &str,
+    asset: Option<&str>,
+    confidential_am: bool,
+    confidential_ty: bool,
+    is_address_eth: bool,
+    selection: CoinSelection,
+) -> Result<()> {
+    let kp = restore_keypair_from_str_with_default(sk_str, is_address_eth)?;
+    let (code, decimals) = match asset {
+        Some(asset) => {
+            let code =
+                AssetTypeCode::new_from_base64(asset).c(d!("Invalid asset code"))?;
+            let decimals = utils::get_asset_type(&code.to_base64())
+                .c(d!())?
+                .properties
+                .asset_rules
+                .decimals;
+            (code, decimals)
+        }
+        None => (
+            AssetTypeCode {
+                val: ASSET_TYPE_FRA,
+            },
+            FRA_DECIMALS,
+        ),
+    };
+
+    let csv = fs::read_to_string(csv).c(d!("Failed to read the CSV file"))?;
+    let mut payout = PayoutBuilder::new(&code);
+    payout
+        .parse_csv(&csv, decimals)
+        .c(d!())?
+        .set_record_type(AssetRecordType::from_flags(
+            confidential_am,
+            confidential_ty,
+        ))
+        .set_coin_selection(selection);
+    println!(
+        "paying {} to {} payees",
+        format_amount(payout.total().c(d!())?, decimals),
+        payout.payees().len()
+    );
+
+    let n = utils::payout(&kp, &payout).c(d!())?;
+    println!("payout done by {n} transactions");
+    Ok(())
+}
+
 #[inline(always)]
 #[allow(missing_docs)]
 pub fn delegate(
@@ -831,12 +954,45 @@ pub fn issue_asset(
     sk_str: Option<&str>,
     asset: &str,
     amount: u64,
-    hidden: bool,
+    confidential_amount: bool,
+    confidential_type: bool,
+    tracking_key: Option<&str>,
     is_address_eth: bool,
 ) -> Result<()> {
+    // rejected by the ledger, which can't check the asset type of the issued records
+    if confidential_type {
+        return Err(eg!(
+            "issued records must have a non-confidential asset type, \
+             mask it in the following transfers by `fn transfer --confidential-type`"
+        ));
+    }
     let kp = restore_keypair_from_str_with_default(sk_str, is_address_eth)?;
     let code = AssetTypeCode::new_from_base64(asset).c(d!())?;
-    issue_asset_x(&kp, &code, amount, hidden).c(d!())
+    let confidentiality_flags =
+        AssetRecordType::from_flags(confidential_amount, confidential_type);
+
+    let mut tracing_policies = TracingPolicies::new();
+    if let Some(key) = tracking_key {
+        let enc_keys = serde_json::from_str::<AssetTracerEncKeys>(key)
+            .c(d!("invalid tracking key"))?;
+        tracing_policies.add(TracingPolicy {
+            enc_keys,
+            asset_tracing: true,
+            identity_tracing: None,
+        });
+    }
+
+    let tracer_memos =
+        issue_asset_xx(&kp, &code, amount, confidentiality_flags, &tracing_policies)
+            .c(d!())?;
+    if !tracer_memos.is_empty() {
+        println!(
+            "Tracer memos: {}",
+            serde_json::to_string(&tracer_memos).c(d!())?
+        );
+    }
+
+    Ok(())
 }
 
 #[allow(missing_docs)]
@@ -847,15 +1003,37 @@ pub fn issue_asset_x(
     hidden: bool,
 ) -> Result<()> {
     let confidentiality_flags = AssetRecordType::from_flags(hidden, false);
+    issue_asset_xx(
+        kp,
+        code,
+        amount,
+        confidentiality_flags,
+        &TracingPolicies::new(),
+    )
+    .c(d!())
+    .map(|_| ())
+}
 
+/// Issue an asset with the given confidentiality, traceable by `tracing_policies`,
+/// returns the tracer memos of the issued record
+pub fn issue_asset_xx(
+    kp: &XfrKeyPair,
+    code: &AssetTypeCode,
+    amount: u64,
+    confidentiality_flags: AssetRecordType,
+    tracing_policies: &TracingPolicies,
+) -> Result<Vec<TracerMemo>> {
     let mut builder = utils::new_tx_builder().c(d!())?;
-    builder
-        .add_basic_issue_asset(
+    let seq_id = builder.get_seq_id();
+    let tracer_memos = builder
+        .add_traced_issue_asset_with_rng(
             kp,
             code,
-            builder.get_seq_id(),
+            seq_id,
             amount,
             confidentiality_flags,
+            tracing_policies,
+            &mut ChaChaRng::from_entropy(),
         )
         .c(d!())?;
     utils::gen_fee_op(kp)
@@ -865,12 +1043,13 @@ pub fn issue_asset_x(
     let mut tx = builder.build_and_take_transaction()?;
     tx.sign_to_map(kp);
 
-    utils::send_tx(&tx)
+    utils::send_tx(&tx).c(d!())?;
+    Ok(tracer_memos)
 }
 
 /// Show a list of custom asset token created by a findora account
 pub fn show_asset(addr: &str) -> Result<()> {
-    let pk = wallet::public_key_from_bech32(addr).c(d!())?;
+    let pk = utils::parse_address(addr).c(d!())?;
     let assets = utils::get_created_assets(&pk).c(d!())?;
     for (code, _asset) in assets {
         let base64 = code.to_base64();
@@ -901,8 +1080,7 @@ pub fn convert_bar2abar(
         None => get_keypair(is_address_eth).c(d!())?,
     };
     // parse receiver AxfrPubKey
-    let to =
-        wallet::public_key_from_bech32(target_addr).c(d!("invalid 'target-addr'"))?;
+    let to = utils::parse_address(target_addr).c(d!("invalid 'target-addr'"))?;
     let sid = txo_sid.parse::<u64>().c(d!("error parsing TxoSID"))?;
 
     // Get OpenAssetRecord from given Owner XfrKeyPair and TxoSID
@@ -1030,8 +1208,7 @@ pub fn gen_anon_transfer_op(
     };
     let axfr_amount = amount.parse::<u64>().c(d!("error parsing amount"))?;
 
-    let to = wallet::public_key_from_bech32(to_address)
-        .c(d!("invalid 'to-xfr-public-key'"))?;
+    let to = utils::parse_address(to_address).c(d!("invalid 'to-xfr-public-key'"))?;
 
     let mut commitments = vec![com];
     if let Some(fra) = com_fra {
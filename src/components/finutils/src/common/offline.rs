@@ -0,0 +1,196 @@
+//!
+//! # Offline signing
+//!
+//! The online machine prepares a bundle with everything a transaction needs
+//! from the ledger, the air-gapped machine builds and signs the transaction
+//! from the bundle with only the key file, then the online machine checks
+//! the signed transaction against the bundle and submits it.
+//!
+
+use {
+    super::utils,
+    crate::txn_builder::{coin_selection::CoinSelection, TransactionBuilder},
+    ledger::data_model::{
+        AssetRules, AssetTypeCode, AssetTypePrefix, Operation, Transaction, TxoSID, Utxo,
+    },
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    std::collections::BTreeMap,
+    zei::{
+        noah_api::xfr::{asset_record::AssetRecordType, structs::OwnerMemo},
+        XfrKeyPair, XfrPublicKey,
+    },
+};
+
+/// What the transaction of a bundle does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum Action {
+    Define {
+        code: AssetTypeCode,
+        memo: String,
+        decimal: u8,
+        max_units: Option<u64>,
+        transferable: bool,
+    },
+    Issue {
+        code: AssetTypeCode,
+        amount: u64,
+    },
+    Transfer {
+        to: XfrPublicKey,
+        amount: u64,
+        code: Option<AssetTypeCode>,
+    },
+}
+
+/// An unsigned transaction, with the ledger state needed to build it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bundle {
+    /// The owner of the key signing the transaction
+    pub owner: XfrPublicKey,
+    /// Sequence number of the no-replay token
+    pub seq_id: u64,
+    /// Spendable utxos of the owner, to pay the fee and the transfers,
+    /// ordered to keep the digest stable across machines
+    pub utxos: BTreeMap<TxoSID, (Utxo, Option<OwnerMemo>)>,
+    #[allow(missing_docs)]
+    pub action: Action,
+}
+
+/// A transaction signed from a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedBundle {
+    /// The digest of the bundle the transaction is built from
+    pub digest: String,
+    #[allow(missing_docs)]
+    pub tx: Transaction,
+}
+
+impl Bundle {
+    /// Query the ledger for what `action` needs, on the online machine.
+    pub fn prepare(owner: XfrPublicKey, action: Action) -> Result<Self> {
+        Ok(Bundle {
+            owner,
+            seq_id: utils::get_seq_id().c(d!())?,
+            utxos: utils::get_spendable_utxos(&owner)
+                .c(d!())?
+                .into_iter()
+                .collect(),
+            action,
+        })
+    }
+
+    /// The digest of the bundle, to be compared on both machines
+    pub fn digest(&self) -> Result<String> {
+        serde_json::to_vec(self)
+            .c(d!())
+            .map(|bytes| hex::encode(Sha256::digest(bytes)))
+    }
+
+    /// Build and sign the transaction, without network access.
+    pub fn sign(&self, kp: &XfrKeyPair) -> Result<SignedBundle> {
+        if kp.get_pk() != self.owner {
+            return Err(eg!("the key is not the owner of the bundle"));
+        }
+
+        let mut builder = TransactionBuilder::from_seq_id(self.seq_id);
+        match &self.action {
+            Action::Define {
+                code,
+                memo,
+                decimal,
+                max_units,
+                transferable,
+            } => {
+                let mut rules = AssetRules::default();
+                rules.set_decimals(*decimal).c(d!())?;
+                rules.set_max_units(*max_units);
+                rules.set_transferable(*transferable);
+                builder
+                    .add_operation_create_asset(kp, Some(*code), rules, memo)
+                    .c(d!())?;
+                builder.add_operation(self.transfer_op(kp, vec![], None).c(d!())?);
+            }
+            Action::Issue { code, amount } => {
+                builder
+                    .add_basic_issue_asset(
+                        kp,
+                        code,
+                        self.seq_id,
+                        *amount,
+                        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                    )
+                    .c(d!())?;
+                builder.add_operation(self.transfer_op(kp, vec![], None).c(d!())?);
+            }
+            Action::Transfer { to, amount, code } => {
+                builder.add_operation(
+                    self.transfer_op(kp, vec![(*to, *amount)], *code).c(d!())?,
+                );
+            }
+        }
+
+        let mut tx = builder.build_and_take_transaction().c(d!())?;
+        tx.sign_to_map(kp);
+
+        Ok(SignedBundle {
+            digest: self.digest().c(d!())?,
+            tx,
+        })
+    }
+
+    /// Check that `signed` is the transaction of this bundle, signed by the owner,
+    /// then submit it and return the handle of the transaction.
+    pub fn submit(&self, signed: &SignedBundle) -> Result<String> {
+        if signed.digest != self.digest().c(d!())? {
+            return Err(eg!("the transaction is signed from another bundle"));
+        }
+        if signed.tx.body.no_replay_token.get_seq_id() != self.seq_id {
+            return Err(eg!("the sequence number does not match the bundle"));
+        }
+        let sig = signed
+            .tx
+            .pubkey_sign_map
+            .get(&self.owner)
+            .c(d!("the transaction is not signed by the owner"))?;
+        signed.tx.check_signature(&self.owner, sig).c(d!())?;
+
+        utils::send_tx_x(&signed.tx).c(d!())
+    }
+
+    /// The asset type of a defining bundle, as shown by `fn asset --create`
+    pub fn defined_asset(&self) -> Option<AssetTypeCode> {
+        match &self.action {
+            Action::Define { code, .. } => Some(
+                AssetTypeCode::from_prefix_and_raw_asset_type_code_2nd_update(
+                    AssetTypePrefix::UserDefined,
+                    code,
+                ),
+            ),
+            _ => None,
+        }
+    }
+
+    // a transfer paying the fee, spending the utxos of the bundle
+    fn transfer_op(
+        &self,
+        kp: &XfrKeyPair,
+        target_list: Vec<(XfrPublicKey, u64)>,
+        code: Option<AssetTypeCode>,
+    ) -> Result<Operation> {
+        utils::gen_transfer_op_with_utxos(
+            kp,
+            self.utxos.clone().into_iter().collect(),
+            target_list,
+            code,
+            true,
+            false,
+            false,
+            None,
+            CoinSelection::default(),
+        )
+        .c(d!())
+    }
+}
@@ -0,0 +1,354 @@
+//!
+//! # Interactive shell
+//!
+//! Keeps the loaded key pairs and a draft transaction in memory across commands,
+//! so multi-step flows need not pass keys, codes and SIDs around in files.
+//!
+
+use {
+    super::{
+        gen_key,
+        keystore::{read_password, Keystore},
+        utils,
+    },
+    crate::txn_builder::TransactionBuilder,
+    globutils::wallet,
+    ledger::data_model::{AssetRules, AssetTypeCode, AssetTypePrefix},
+    ruc::*,
+    rustyline::{
+        completion::Completer, error::ReadlineError, highlight::Highlighter,
+        hint::Hinter, validate::Validator, Context, Editor, Helper,
+    },
+    std::{collections::BTreeMap, thread, time::Duration},
+    zei::{noah_api::xfr::asset_record::AssetRecordType, XfrKeyPair, XfrPublicKey},
+};
+
+const COMMANDS: &[&str] = &[
+    "help", "key", "define", "issue", "transfer", "show", "submit", "discard", "exit",
+];
+
+const KEY_COMMANDS: &[&str] = &["gen", "load", "list"];
+
+const HELP: &str = "\
+key gen <label>                        generate a key kept in memory only
+key load <label>                       load a key from the keystore
+key list                               list the loaded keys
+define <label> <memo> [max units]      define a new asset issued by <label>
+issue <label> <code> <amount>          issue an asset defined by <label>
+transfer <label> <to> <amount> [code]  transfer FRA or <code> to a label or an address,
+                                       at most one transfer from each label per draft
+show                                   print the draft transaction
+submit                                 sign and submit the draft, then wait for the SIDs
+discard                                drop the draft transaction
+exit                                   leave the shell";
+
+// Rounds of polling the status of a submitted transaction, one second each
+const STATUS_POLLS: usize = 30;
+
+/// Run the shell until `exit` or EOF,
+/// `keystore_path` is the keystore read by `key load`.
+pub fn run(keystore_path: &str) -> Result<()> {
+    let mut rl = Editor::<ShellHelper>::new().c(d!())?;
+    rl.set_helper(Some(ShellHelper { labels: vec![] }));
+
+    let mut shell = Shell {
+        keystore_path: keystore_path.to_owned(),
+        keystore: None,
+        keys: BTreeMap::new(),
+        draft: None,
+    };
+    println!("{HELP}");
+
+    loop {
+        let line = match rl.readline("fn> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(eg!(e)),
+        };
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        if args.is_empty() {
+            continue;
+        }
+        rl.add_history_entry(line.as_str());
+
+        match args[0] {
+            "exit" | "quit" => break,
+            "help" => println!("{HELP}"),
+            _ => {
+                if let Err(e) = shell.exec(&args) {
+                    e.print(None);
+                }
+            }
+        }
+
+        if let Some(h) = rl.helper_mut() {
+            h.labels = shell.keys.keys().cloned().collect();
+        }
+    }
+
+    Ok(())
+}
+
+struct ShellHelper {
+    labels: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(' ').map(|i| i + 1).unwrap_or(0);
+        let word = &line[start..];
+        let labels = || self.labels.iter().map(|l| l.as_str()).collect::<Vec<_>>();
+        let candidates: Vec<&str> =
+            match line[..start].split_whitespace().collect::<Vec<_>>()[..] {
+                [] => COMMANDS.to_vec(),
+                ["key"] => KEY_COMMANDS.to_vec(),
+                ["define" | "issue" | "transfer"] | ["transfer", _] => labels(),
+                _ => vec![],
+            };
+
+        Ok((
+            start,
+            candidates
+                .into_iter()
+                .filter(|c| c.starts_with(word))
+                .map(|c| c.to_owned())
+                .collect(),
+        ))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+struct Shell {
+    keystore_path: String,
+    // loaded on the first `key load`, along with its password
+    keystore: Option<(Keystore, String)>,
+    keys: BTreeMap<String, XfrKeyPair>,
+    draft: Option<Draft>,
+}
+
+struct Draft {
+    builder: TransactionBuilder,
+    // labels of the keys to sign the transaction, in order
+    signers: Vec<String>,
+    // whether a transfer has paid the fee of the transaction
+    fee_paid: bool,
+    // issuances in the draft, each one needs a new sequence number
+    issuances: u64,
+}
+
+impl Shell {
+    fn exec(&mut self, args: &[&str]) -> Result<()> {
+        match args {
+            ["key", "gen", label] => {
+                let (_, _, _, kp) = gen_key(false);
+                println!("{}", wallet::public_key_to_bech32(kp.get_pk_ref()));
+                self.keys.insert(label.to_string(), kp);
+            }
+            ["key", "load", label] => {
+                if self.keystore.is_none() {
+                    let ks = Keystore::load_or_create(&self.keystore_path).c(d!())?;
+                    self.keystore = Some((ks, read_password().c(d!())?));
+                }
+                let (ks, password) = self.keystore.as_ref().unwrap();
+                let kp = ks.export_secret(label, password).c(d!())?.into_keypair();
+                println!("{}", wallet::public_key_to_bech32(kp.get_pk_ref()));
+                self.keys.insert(label.to_string(), kp);
+            }
+            ["key", "list"] => {
+                for (label, kp) in self.keys.iter() {
+                    println!(
+                        "{}\t{}",
+                        label,
+                        wallet::public_key_to_bech32(kp.get_pk_ref())
+                    );
+                }
+            }
+            ["define", label, memo, rest @ ..] if rest.len() <= 1 => {
+                let max_units = match rest.first() {
+                    Some(max) => Some(
+                        max.parse::<u64>()
+                            .c(d!("max units should be an unsigned integer"))?,
+                    ),
+                    None => None,
+                };
+                let kp = self.key(label).c(d!())?.clone();
+                let code = AssetTypeCode::gen_random();
+                let mut rules = AssetRules::default();
+                rules.set_max_units(max_units);
+
+                let draft = self.draft().c(d!())?;
+                draft
+                    .builder
+                    .add_operation_create_asset(&kp, Some(code), rules, memo)
+                    .c(d!())?;
+                draft.sign_by(label);
+                println!(
+                    "type: {}",
+                    AssetTypeCode::from_prefix_and_raw_asset_type_code_2nd_update(
+                        AssetTypePrefix::UserDefined,
+                        &code,
+                    )
+                    .to_base64()
+                );
+            }
+            ["issue", label, code, amount] => {
+                let code =
+                    AssetTypeCode::new_from_base64(code).c(d!("invalid asset code"))?;
+                let amount = amount
+                    .parse::<u64>()
+                    .c(d!("amount should be a 64-bits unsigned integer"))?;
+                let kp = self.key(label).c(d!())?.clone();
+
+                let draft = self.draft().c(d!())?;
+                let seq_num = draft.builder.get_seq_id() + draft.issuances;
+                draft
+                    .builder
+                    .add_basic_issue_asset(
+                        &kp,
+                        &code,
+                        seq_num,
+                        amount,
+                        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                    )
+                    .c(d!())?;
+                draft.issuances += 1;
+                draft.sign_by(label);
+            }
+            ["transfer", label, to, amount, rest @ ..] if rest.len() <= 1 => {
+                let target = self.address(to).c(d!())?;
+                let amount = amount
+                    .parse::<u64>()
+                    .c(d!("amount should be a 64-bits unsigned integer"))?;
+                let code = match rest.first() {
+                    Some(code) => Some(
+                        AssetTypeCode::new_from_base64(code)
+                            .c(d!("invalid asset code"))?,
+                    ),
+                    None => None,
+                };
+                let kp = self.key(label).c(d!())?.clone();
+
+                let draft = self.draft().c(d!())?;
+                let op = utils::gen_transfer_op_x(
+                    &kp,
+                    vec![(target, amount)],
+                    code,
+                    !draft.fee_paid,
+                    false,
+                    false,
+                    None,
+                )
+                .c(d!())?;
+                draft.builder.add_operation(op);
+                draft.fee_paid = true;
+                draft.sign_by(label);
+            }
+            ["show"] => match self.draft.as_ref() {
+                Some(draft) => println!(
+                    "{}",
+                    serde_json::to_string_pretty(draft.builder.transaction()).c(d!())?
+                ),
+                None => println!("no draft transaction"),
+            },
+            ["submit"] => {
+                let draft = self.draft.take().c(d!("no draft transaction"))?;
+                self.submit(draft).c(d!())?;
+            }
+            ["discard"] => {
+                self.draft = None;
+            }
+            _ => return Err(eg!("invalid command, see `help`")),
+        }
+
+        Ok(())
+    }
+
+    fn key(&self, label: &str) -> Result<&XfrKeyPair> {
+        self.keys
+            .get(label)
+            .c(d!(format!("key not loaded: {label}")))
+    }
+
+    // a loaded label or a fra address
+    fn address(&self, to: &str) -> Result<XfrPublicKey> {
+        match self.keys.get(to) {
+            Some(kp) => Ok(kp.get_pk()),
+            None => wallet::public_key_from_bech32(to).c(d!("invalid address")),
+        }
+    }
+
+    fn draft(&mut self) -> Result<&mut Draft> {
+        if self.draft.is_none() {
+            self.draft = Some(Draft {
+                builder: utils::new_tx_builder().c(d!())?,
+                signers: vec![],
+                fee_paid: false,
+                issuances: 0,
+            });
+        }
+        Ok(self.draft.as_mut().unwrap())
+    }
+
+    fn submit(&self, mut draft: Draft) -> Result<()> {
+        let signers = draft
+            .signers
+            .iter()
+            .map(|label| self.key(label).c(d!()))
+            .collect::<Result<Vec<_>>>()?;
+        let payer = signers.first().c(d!("empty draft transaction"))?;
+        if !draft.fee_paid {
+            let op = utils::gen_fee_op(payer).c(d!())?;
+            draft.builder.add_operation(op);
+        }
+
+        let mut tx = draft.builder.build_and_take_transaction().c(d!())?;
+        for kp in signers {
+            tx.sign_to_map(kp);
+        }
+        let handle = utils::send_tx_x(&tx).c(d!())?;
+
+        for _ in 0..STATUS_POLLS {
+            if let Some((txn_sid, txo_sids)) = utils::get_txn_status(&handle).c(d!())? {
+                println!("txn: {}", txn_sid.0);
+                println!(
+                    "txos: {}",
+                    txo_sids
+                        .iter()
+                        .map(|sid| sid.0.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                );
+                return Ok(());
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+
+        Err(eg!(format!("not committed yet, handle: {handle}")))
+    }
+}
+
+impl Draft {
+    fn sign_by(&mut self, label: &str) {
+        if !self.signers.iter().any(|l| l == label) {
+            self.signers.push(label.to_owned());
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//!
+//! # Transaction templates
+//!
+//! Named drafts of recurring transactions, e.g. a monthly issuance of an
+//! asset or a payment to the same address, kept as one JSON file each in
+//! the config directory of `fn`. A template is instantiated into an offline
+//! `Bundle` by its owner, so each use gets a new sequence number and the
+//! current UTXOs, the amount and the receiver can be changed on each use.
+//!
+
+use {
+    super::offline::{Action, Bundle},
+    globutils::wallet,
+    ledger::data_model::AssetTypeCode,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+    zei::XfrPublicKey,
+};
+
+const EXT: &str = ".json";
+
+/// Where `fn template` keeps the templates
+pub fn default_dir() -> PathBuf {
+    PathBuf::from(format!("{}/templates", &*super::CFG_PATH))
+}
+
+/// A named draft of a transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    /// ASCII letters, digits, `-` and `_`
+    pub name: String,
+    /// The owner of the key signing the transactions
+    pub owner: XfrPublicKey,
+    /// Issuing or transferring, defining an asset is done once only
+    pub action: Action,
+}
+
+/// The parts of a template to change on its use
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    #[allow(missing_docs)]
+    pub amount: Option<u64>,
+    /// The receiver of a transfer
+    pub to: Option<XfrPublicKey>,
+}
+
+impl Template {
+    #[allow(missing_docs)]
+    pub fn new(name: &str, owner: XfrPublicKey, action: Action) -> Result<Self> {
+        check_name(name).c(d!())?;
+        if let Action::Define { .. } = action {
+            return Err(eg!("an asset is defined once, there is nothing to repeat"));
+        }
+        Ok(Template {
+            name: name.to_owned(),
+            owner,
+            action,
+        })
+    }
+
+    /// Save to `dir`, an existing template of the same name is replaced
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir).c(d!())?;
+        let path = path_of(dir, &self.name).c(d!())?;
+        fs::write(path, serde_json::to_string_pretty(self).c(d!())?).c(d!())
+    }
+
+    /// The template `name` saved in `dir`
+    pub fn load(dir: &Path, name: &str) -> Result<Self> {
+        let path = path_of(dir, name).c(d!())?;
+        fs::read_to_string(path)
+            .c(d!(format!("no template named `{name}`")))
+            .and_then(|t| serde_json::from_str(&t).c(d!("invalid template")))
+    }
+
+    /// Remove the template `name` from `dir`
+    pub fn remove(dir: &Path, name: &str) -> Result<()> {
+        let path = path_of(dir, name).c(d!())?;
+        fs::remove_file(path).c(d!(format!("no template named `{name}`")))
+    }
+
+    /// The templates saved in `dir`, by name
+    pub fn list(dir: &Path) -> Result<Vec<Self>> {
+        let rd = match fs::read_dir(dir) {
+            Ok(rd) => rd,
+            Err(_) => return Ok(vec![]),
+        };
+        let mut templates = vec![];
+        for e in rd.filter_map(|e| e.ok()) {
+            let name = e.file_name().to_string_lossy().into_owned();
+            if let Some(name) = name.strip_suffix(EXT) {
+                templates.push(Self::load(dir, name).c(d!())?);
+            }
+        }
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    /// The action of the template, with the `overrides` applied
+    pub fn action(&self, overrides: &Overrides) -> Result<Action> {
+        let mut action = self.action.clone();
+        match &mut action {
+            Action::Issue { amount, .. } => {
+                if overrides.to.is_some() {
+                    return Err(eg!("an issuance has no receiver"));
+                }
+                if let Some(am) = overrides.amount {
+                    *amount = am;
+                }
+            }
+            Action::Transfer { to, amount, .. } => {
+                if let Some(am) = overrides.amount {
+                    *amount = am;
+                }
+                if let Some(pk) = overrides.to {
+                    *to = pk;
+                }
+            }
+            Action::Define { .. } => {
+                return Err(eg!("an asset is defined once, there is nothing to repeat"));
+            }
+        }
+        Ok(action)
+    }
+
+    /// A bundle of the template, with a new sequence number and the current
+    /// UTXOs of the owner, to sign on or off line
+    pub fn instantiate(&self, overrides: &Overrides) -> Result<Bundle> {
+        Bundle::prepare(self.owner, self.action(overrides).c(d!())?).c(d!())
+    }
+
+    /// What the template does, in a line
+    pub fn describe(&self) -> String {
+        let code = |code: Option<AssetTypeCode>| {
+            code.map(|c| c.to_base64())
+                .unwrap_or_else(|| "FRA".to_owned())
+        };
+        let what = match &self.action {
+            Action::Issue { code: c, amount } => {
+                format!("issue {amount} of {}", code(Some(*c)))
+            }
+            Action::Transfer {
+                to,
+                amount,
+                code: c,
+            } => format!(
+                "transfer {amount} of {} to {}",
+                code(*c),
+                wallet::public_key_to_bech32(to)
+            ),
+            Action::Define { code: c, .. } => format!("define {}", code(Some(*c))),
+        };
+        format!(
+            "{}: {what}, by {}",
+            self.name,
+            wallet::public_key_to_bech32(&self.owner)
+        )
+    }
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || '-' == c || '_' == c)
+    {
+        return Err(eg!(format!(
+            "invalid template name `{name}`, expected ASCII letters, digits, `-` and `_`"
+        )));
+    }
+    Ok(())
+}
+
+fn path_of(dir: &Path, name: &str) -> Result<PathBuf> {
+    check_name(name).c(d!())?;
+    Ok(dir.join(format!("{name}{EXT}")))
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, rand_chacha::ChaChaRng, rand_core::SeedableRng, zei::XfrKeyPair};
+
+    #[test]
+    fn test_template() {
+        let mut prng = ChaChaRng::from_entropy();
+        let owner = XfrKeyPair::generate(&mut prng).get_pk();
+        let bob = XfrKeyPair::generate(&mut prng).get_pk();
+        let code = AssetTypeCode::gen_random();
+        let dir = globutils::fresh_tmp_dir();
+
+        assert!(Template::new(
+            "define",
+            owner,
+            Action::Define {
+                code,
+                memo: String::new(),
+                decimal: 6,
+                max_units: None,
+                transferable: true,
+            }
+        )
+        .is_err());
+        let issue = Action::Issue { code, amount: 100 };
+        assert!(Template::new("../monthly", owner, issue.clone()).is_err());
+
+        Template::new("monthly-issue", owner, issue)
+            .unwrap()
+            .save(&dir)
+            .unwrap();
+        let pay = Action::Transfer {
+            to: owner,
+            amount: 5,
+            code: Some(code),
+        };
+        Template::new("pay_rent", owner, pay)
+            .unwrap()
+            .save(&dir)
+            .unwrap();
+
+        let names = Template::list(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["monthly-issue", "pay_rent"], names);
+
+        let t = Template::load(&dir, "pay_rent").unwrap();
+        let overrides = Overrides {
+            amount: Some(7),
+            to: Some(bob),
+        };
+        match t.action(&overrides).unwrap() {
+            Action::Transfer { to, amount, .. } => {
+                assert_eq!(bob, to);
+                assert_eq!(7, amount);
+            }
+            _ => panic!("not a transfer"),
+        }
+        let t = Template::load(&dir, "monthly-issue").unwrap();
+        assert!(t.action(&overrides).is_err());
+
+        Template::remove(&dir, "monthly-issue").unwrap();
+        assert!(Template::load(&dir, "monthly-issue").is_err());
+        assert_eq!(1, Template::list(&dir).unwrap().len());
+    }
+}
@@ -8,17 +8,22 @@ use {
     crate::{
         api::{DelegationInfo, ValidatorDetail},
         common::get_serv_addr,
-        txn_builder::{TransactionBuilder, TransferOperationBuilder},
+        txn_builder::{
+            coin_selection::CoinSelection, payout::PayoutBuilder, TransactionBuilder,
+            TransferOperationBuilder,
+        },
     },
     globutils::{wallet, HashOf, SignatureOf},
     ledger::{
         data_model::{
-            ABARData, ATxoSID, AssetType, AssetTypeCode, DefineAsset, Operation,
-            StateCommitmentData, Transaction, TransferType, TxoRef, TxoSID, Utxo,
-            ASSET_TYPE_FRA, BAR_TO_ABAR_TX_FEE_MIN, BLACK_HOLE_PUBKEY, TX_FEE_MIN,
+            b64enc, ABARData, ATxoSID, AssetType, AssetTypeCode, DefineAsset, Operation,
+            StateCommitmentData, Transaction, TransferType, TxnSID, TxoRef, TxoSID,
+            Utxo, ViewKeyAuth, ViewKeyRegistration, ASSET_TYPE_FRA,
+            BAR_TO_ABAR_TX_FEE_MIN, BLACK_HOLE_PUBKEY, TX_FEE_MIN,
         },
         staking::{
-            init::get_inital_validators, StakerMemo, TendermintAddrRef, FRA_TOTAL_AMOUNT,
+            init::get_inital_validators, params::ChainParamValues, StakerMemo,
+            TendermintAddrRef, FRA_TOTAL_AMOUNT,
         },
     },
     ruc::*,
@@ -27,8 +32,10 @@ use {
     sha2::{Digest, Sha256},
     sha3::Keccak256,
     std::{
-        collections::{BTreeMap, HashMap},
+        collections::{BTreeMap, HashMap, HashSet},
         str::FromStr,
+        thread,
+        time::{Duration, SystemTime, UNIX_EPOCH},
     },
     tendermint::{PrivateKey, PublicKey},
     tokio::runtime::Runtime,
@@ -57,6 +64,17 @@ use {
 // Part 1: utils for transfer assets //
 ///////////////////////////////////////
 
+/// The public key of an address in bech32, e.g. `fra1...`, or the legacy
+/// base64 encoding, bech32 is tried first as its checksum catches the typos
+pub fn parse_address(addr: &str) -> Result<XfrPublicKey> {
+    let addr = addr.trim();
+    wallet::public_key_from_bech32(addr)
+        .or_else(|_| wallet::public_key_from_base64(addr))
+        .c(d!(format!(
+            "invalid address `{addr}`, expected bech32 or base64"
+        )))
+}
+
 #[inline(always)]
 #[allow(missing_docs)]
 pub fn new_tx_builder() -> Result<TransactionBuilder> {
@@ -66,6 +84,11 @@ pub fn new_tx_builder() -> Result<TransactionBuilder> {
 #[inline(always)]
 #[allow(missing_docs)]
 pub fn send_tx(tx: &Transaction) -> Result<()> {
+    send_tx_x(tx).c(d!()).map(|_| ())
+}
+
+/// Same as `send_tx`, returns the handle to query the status of the transaction
+pub fn send_tx_x(tx: &Transaction) -> Result<String> {
     let url = format!("{}:8669/submit_transaction", get_serv_addr().c(d!())?);
     let tx_bytes = serde_json::to_vec(tx).c(d!())?;
 
@@ -76,7 +99,8 @@ pub fn send_tx(tx: &Transaction) -> Result<()> {
         .c(d!("fail to send transaction"))?
         .error_for_status()
         .c(d!())
-        .map(|_| ());
+        .and_then(|resp| resp.bytes().c(d!()))
+        .and_then(|b| serde_json::from_slice::<String>(&b).c(d!()));
 
     let tx_hash = Sha256::digest(tx_bytes);
     println!("{}", hex::encode(tx_hash));
@@ -84,6 +108,33 @@ pub fn send_tx(tx: &Transaction) -> Result<()> {
     ret
 }
 
+/// Query the status of a submitted transaction by its handle,
+/// returns the SIDs once it is committed, or `None` while it is pending
+pub fn get_txn_status(handle: &str) -> Result<Option<(TxnSID, Vec<TxoSID>)>> {
+    #[derive(Deserialize)]
+    enum TxnStatus {
+        Rejected(String),
+        Committed((TxnSID, Vec<TxoSID>)),
+        Pending,
+    }
+
+    let url = format!("{}:8669/txn_status/{}", get_serv_addr().c(d!())?, handle);
+
+    let status = attohttpc::get(&url)
+        .send()
+        .c(d!(url))?
+        .error_for_status()
+        .c(d!(url))?
+        .bytes()
+        .c(d!(url))
+        .and_then(|b| serde_json::from_slice::<TxnStatus>(&b).c(d!(url)))?;
+    match status {
+        TxnStatus::Committed(sids) => Ok(Some(sids)),
+        TxnStatus::Pending => Ok(None),
+        TxnStatus::Rejected(e) => Err(eg!(e)),
+    }
+}
+
 /// Fee is needless in a `UpdateValidator` operation
 #[inline(always)]
 pub fn set_initial_validators() -> Result<()> {
@@ -139,15 +190,38 @@ pub fn transfer_batch(
     token_code: Option<AssetTypeCode>,
     confidential_am: bool,
     confidential_ty: bool,
+) -> Result<()> {
+    transfer_batch_x(
+        owner_kp,
+        target_list,
+        token_code,
+        confidential_am,
+        confidential_ty,
+        CoinSelection::default(),
+    )
+    .c(d!())
+}
+
+/// Same as `transfer_batch`, the inputs are picked by `selection`
+pub fn transfer_batch_x(
+    owner_kp: &XfrKeyPair,
+    target_list: Vec<(XfrPublicKey, u64)>,
+    token_code: Option<AssetTypeCode>,
+    confidential_am: bool,
+    confidential_ty: bool,
+    selection: CoinSelection,
 ) -> Result<()> {
     let mut builder = new_tx_builder().c(d!())?;
-    let op = gen_transfer_op(
+    let op = gen_transfer_op_xx(
+        None,
         owner_kp,
         target_list,
         token_code,
+        true,
         confidential_am,
         confidential_ty,
         None,
+        selection,
     )
     .c(d!())?;
     builder.add_operation(op);
@@ -200,6 +274,7 @@ pub fn gen_transfer_op_x(
         confidential_am,
         confidential_ty,
         balance_type,
+        CoinSelection::default(),
     )
     .c(d!())
 }
@@ -209,12 +284,44 @@ pub fn gen_transfer_op_x(
 pub fn gen_transfer_op_xx(
     rpc_endpoint: Option<&str>,
     owner_kp: &XfrKeyPair,
+    target_list: Vec<(XfrPublicKey, u64)>,
+    token_code: Option<AssetTypeCode>,
+    auto_fee: bool,
+    confidential_am: bool,
+    confidential_ty: bool,
+    balance_type: Option<AssetRecordType>,
+    selection: CoinSelection,
+) -> Result<Operation> {
+    let utxos = get_owned_utxos_x(rpc_endpoint, owner_kp.get_pk_ref(), true).c(d!())?;
+    gen_transfer_op_with_utxos(
+        owner_kp,
+        utxos,
+        target_list,
+        token_code,
+        auto_fee,
+        confidential_am,
+        confidential_ty,
+        balance_type,
+        selection,
+    )
+    .c(d!())
+}
+
+/// Same as `gen_transfer_op_x`, but spends from the given utxos
+/// instead of querying the ledger, so it works without network access.
+///
+/// The inputs are picked by `selection`.
+#[allow(clippy::too_many_arguments)]
+pub fn gen_transfer_op_with_utxos(
+    owner_kp: &XfrKeyPair,
+    utxos: HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>,
     mut target_list: Vec<(XfrPublicKey, u64)>,
     token_code: Option<AssetTypeCode>,
     auto_fee: bool,
     confidential_am: bool,
     confidential_ty: bool,
     balance_type: Option<AssetRecordType>,
+    selection: CoinSelection,
 ) -> Result<Operation> {
     let mut op_fee: u64 = 0;
     if auto_fee {
@@ -225,18 +332,15 @@ pub fn gen_transfer_op_xx(
 
     let mut trans_builder = TransferOperationBuilder::new();
 
-    let mut am = target_list.iter().map(|(_, am)| *am).sum();
+    let mut am: u64 = target_list.iter().map(|(_, am)| *am).sum();
     if asset_type != ASSET_TYPE_FRA {
         am -= op_fee;
     } else {
         // if this is a FRA asset, set op_fee to 0, because fee has been added to am already.
         op_fee = 0;
     }
-    let mut i_am;
-    let utxos = get_owned_utxos_x(rpc_endpoint, owner_kp.get_pk_ref())
-        .c(d!())?
-        .into_iter();
 
+    let mut oars = BTreeMap::new();
     for (sid, (utxo, owner_memo)) in utxos {
         let oar = open_blind_asset_record(
             &utxo.0.record.into_noah(),
@@ -244,34 +348,27 @@ pub fn gen_transfer_op_xx(
             &owner_kp.into_noah(),
         )
         .c(d!())?;
+        if oar.asset_type == asset_type || oar.asset_type == ASSET_TYPE_FRA {
+            oars.insert(sid, oar);
+        }
+    }
 
-        if oar.asset_type != asset_type && oar.asset_type != ASSET_TYPE_FRA {
-            continue;
-        } else if oar.asset_type == ASSET_TYPE_FRA && op_fee != 0 {
-            // asset_type is a custom asset, need handle fee here
-            alt!(oar.amount < op_fee, i_am = oar.amount, i_am = op_fee);
-            op_fee -= i_am;
-
-            trans_builder
-                .add_input(TxoRef::Absolute(sid), oar, None, None, i_am)
-                .c(d!())?;
-
-            continue;
-        } else if am != 0 {
-            alt!(oar.amount < am, i_am = oar.amount, i_am = am);
-            //am = am.saturating_sub(i_am);
-            am -= i_am;
-
+    // the amount, then the fee if the asset is not FRA
+    for (ty, target) in [(asset_type, am), (ASSET_TYPE_FRA, op_fee)] {
+        let coins = oars
+            .iter()
+            .filter(|(_, oar)| oar.asset_type == ty)
+            .map(|(sid, oar)| (*sid, oar.amount))
+            .collect::<Vec<_>>();
+        let mut left = target;
+        for (sid, amount) in selection.select(&coins, target).c(d!())? {
+            let i_am = amount.min(left);
+            left -= i_am;
+            let oar = oars.remove(&sid).c(d!())?;
             trans_builder
                 .add_input(TxoRef::Absolute(sid), oar, None, None, i_am)
                 .c(d!())?;
         }
-
-        alt!(0 == am && 0 == op_fee, break);
-    }
-
-    if 0 != am || 0 != op_fee {
-        return Err(eg!("insufficient balance"));
     }
 
     if auto_fee {
@@ -324,6 +421,169 @@ pub fn gen_transfer_op_xx(
         .c(d!())
 }
 
+// room left in a transaction built to a size for its envelope and signatures
+const TXN_SIZE_MARGIN: usize = 2048;
+
+// seconds to wait for a transaction to be committed
+const TXN_COMMIT_POLLS: usize = 60;
+
+// wait for the transaction of `handle` to be committed,
+// before building the next one out of its outputs
+fn wait_for_commit(handle: &str) -> Result<()> {
+    for _ in 0..TXN_COMMIT_POLLS {
+        if get_txn_status(handle).c(d!())?.is_some() {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+    Err(eg!(format!("not committed yet, handle: {handle}")))
+}
+
+/// Merge the spendable UTXOs of an asset into one, by a transaction of as many
+/// of them as fit in `max_txn_bytes` at a time, each one committed before the next
+/// is built, returns the number of the transactions sent.
+///
+/// The UTXOs locked by co-signature rules are left untouched.
+pub fn consolidate(
+    owner_kp: &XfrKeyPair,
+    token_code: Option<AssetTypeCode>,
+) -> Result<usize> {
+    let asset_type = token_code.map(|code| code.val).unwrap_or(ASSET_TYPE_FRA);
+    let max_txn_bytes = get_chain_params().c(d!())?.max_txn_bytes as usize;
+
+    let mut sent = 0;
+    loop {
+        let mut inputs = vec![];
+        for (sid, (utxo, owner_memo)) in
+            get_spendable_utxos(owner_kp.get_pk_ref()).c(d!())?
+        {
+            if utxo.0.cosig_rules.is_some() {
+                continue;
+            }
+            let oar = open_blind_asset_record(
+                &utxo.0.record.into_noah(),
+                &owner_memo,
+                &owner_kp.into_noah(),
+            )
+            .c(d!())?;
+            if oar.asset_type == asset_type {
+                inputs.push((TxoRef::Absolute(sid), oar));
+            }
+        }
+        if inputs.len() < 2 {
+            return Ok(sent);
+        }
+        // the largest one first, so the fee is covered and the merged record is reused
+        inputs.sort_by(|a, b| b.1.amount.cmp(&a.1.amount));
+
+        let mut builder = new_tx_builder().c(d!())?;
+        let (fee, fee_op) = if ASSET_TYPE_FRA == asset_type {
+            (TX_FEE_MIN, None)
+        } else {
+            (0, Some(gen_fee_op(owner_kp).c(d!())?))
+        };
+        let budget = fee_op
+            .as_ref()
+            .map(|op| serde_json::to_vec(op).map(|v| v.len()))
+            .transpose()
+            .c(d!())?
+            .unwrap_or(0)
+            + TXN_SIZE_MARGIN;
+        let rt = inputs[0].1.get_record_type();
+        let (mut op, n) = TransferOperationBuilder::consolidate(
+            &inputs,
+            rt,
+            fee,
+            max_txn_bytes.saturating_sub(budget),
+        )
+        .c(d!())?;
+        if n < 2 {
+            return Err(eg!("no two UTXOs fit in a transaction"));
+        }
+
+        builder.add_operation(op.sign(owner_kp).c(d!())?.transaction().c(d!())?);
+        if let Some(op) = fee_op {
+            builder.add_operation(op);
+        }
+        let mut tx = builder.build_and_take_transaction().c(d!())?;
+        tx.sign_to_map(owner_kp);
+
+        let handle = send_tx_x(&tx).c(d!())?;
+        sent += 1;
+        println!(
+            "{n} of {} UTXOs merged, waiting to be committed",
+            inputs.len()
+        );
+        wait_for_commit(&handle).c(d!())?;
+    }
+}
+
+/// Pay the payees of `payout`, by a transfer of as many of them as fit in
+/// `max_txn_bytes` at a time, each one committed before the next is built
+/// out of the change, returns the number of the transactions sent.
+///
+/// The UTXOs locked by co-signature rules are not spent.
+pub fn payout(owner_kp: &XfrKeyPair, payout: &PayoutBuilder) -> Result<usize> {
+    let asset_type = payout.asset_type();
+    let max_txn_bytes = get_chain_params().c(d!())?.max_txn_bytes as usize;
+
+    let mut sent = 0;
+    let mut from = 0;
+    while from < payout.payees().len() {
+        let mut inputs = vec![];
+        for (sid, (utxo, owner_memo)) in
+            get_spendable_utxos(owner_kp.get_pk_ref()).c(d!())?
+        {
+            if utxo.0.cosig_rules.is_some() {
+                continue;
+            }
+            let oar = open_blind_asset_record(
+                &utxo.0.record.into_noah(),
+                &owner_memo,
+                &owner_kp.into_noah(),
+            )
+            .c(d!())?;
+            if oar.asset_type == asset_type {
+                inputs.push((TxoRef::Absolute(sid), oar));
+            }
+        }
+
+        let mut builder = new_tx_builder().c(d!())?;
+        let (fee, fee_op) = if ASSET_TYPE_FRA == asset_type {
+            (TX_FEE_MIN, None)
+        } else {
+            (0, Some(gen_fee_op(owner_kp).c(d!())?))
+        };
+        let budget = fee_op
+            .as_ref()
+            .map(|op| serde_json::to_vec(op).map(|v| v.len()))
+            .transpose()
+            .c(d!())?
+            .unwrap_or(0)
+            + TXN_SIZE_MARGIN;
+        let (mut op, n, _) = payout
+            .build_next(from, &inputs, fee, max_txn_bytes.saturating_sub(budget))
+            .c(d!(format!("paying from the payee {from}")))?;
+
+        builder.add_operation(op.sign(owner_kp).c(d!())?.transaction().c(d!())?);
+        if let Some(op) = fee_op {
+            builder.add_operation(op);
+        }
+        let mut tx = builder.build_and_take_transaction().c(d!())?;
+        tx.sign_to_map(owner_kp);
+
+        let handle = send_tx_x(&tx).c(d!())?;
+        sent += 1;
+        from += n;
+        println!(
+            "{from} of {} payees paid, waiting to be committed",
+            payout.payees().len()
+        );
+        wait_for_commit(&handle).c(d!())?;
+    }
+    Ok(sent)
+}
+
 /// for scenes that need to pay a standalone fee without other transfers
 #[inline(always)]
 #[allow(missing_docs)]
@@ -353,7 +613,9 @@ pub fn gen_fee_bar_to_abar(
         )
         .c(d!())?;
 
-    let utxos = get_owned_utxos(owner_kp.get_pk_ref()).c(d!())?.into_iter();
+    let utxos = get_owned_utxos_x(None, owner_kp.get_pk_ref(), true)
+        .c(d!())?
+        .into_iter();
     for (sid, (utxo, owner_memo)) in utxos {
         let oar = open_blind_asset_record(
             &utxo.0.record.into_noah(),
@@ -625,18 +887,28 @@ pub fn get_asset_all(kp: &XfrKeyPair) -> Result<BTreeMap<AssetTypeCode, u64>> {
 pub fn get_owned_utxos(
     addr: &XfrPublicKey,
 ) -> Result<HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>> {
-    get_owned_utxos_x(None, addr).c(d!())
+    get_owned_utxos_x(None, addr, false).c(d!())
 }
 
+/// Same as `get_owned_utxos`, but skips the time-locked utxos
+pub fn get_spendable_utxos(
+    addr: &XfrPublicKey,
+) -> Result<HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>> {
+    get_owned_utxos_x(None, addr, true).c(d!())
+}
+
+// the time-locked utxos are skipped if `spendable_only`
 fn get_owned_utxos_x(
     rpc_endpoint: Option<&str>,
     addr: &XfrPublicKey,
+    spendable_only: bool,
 ) -> Result<HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>> {
     let default_endpoint = format!("{}:8668", get_serv_addr().c(d!())?);
     let url = format!(
-        "{}/owned_utxos/{}",
+        "{}/owned_utxos/{}{}",
         rpc_endpoint.unwrap_or(default_endpoint.as_str()),
-        wallet::public_key_to_base64(addr)
+        wallet::public_key_to_base64(addr),
+        alt!(spendable_only, "?locked=false", "")
     );
 
     attohttpc::get(url)
@@ -652,6 +924,75 @@ fn get_owned_utxos_x(
         })
 }
 
+/// Return the sids of transactions related to the given address
+pub fn get_related_txns(addr: &XfrPublicKey) -> Result<HashSet<TxnSID>> {
+    let url = format!(
+        "{}:8667/get_related_txns/{}",
+        get_serv_addr().c(d!())?,
+        wallet::public_key_to_base64(addr)
+    );
+
+    attohttpc::get(url)
+        .send()
+        .c(d!())?
+        .error_for_status()
+        .c(d!())?
+        .bytes()
+        .c(d!())
+        .and_then(|b| serde_json::from_slice(&b).c(d!()))
+}
+
+/// Register the view key derived from `owner` to the query server
+pub fn register_view_key(owner: &XfrKeyPair) -> Result<()> {
+    let url = format!("{}:8667/view_keys", get_serv_addr().c(d!())?);
+    let reg = ViewKeyRegistration::new(owner, unix_now().c(d!())?);
+
+    attohttpc::post(url)
+        .json(&reg)
+        .c(d!())?
+        .send()
+        .c(d!())?
+        .error_for_status()
+        .c(d!())
+        .map(|_| ())
+}
+
+/// Return a page of the balance history of `address`,
+/// authenticated by its view key
+pub fn get_balance_history(
+    view_key: &XfrKeyPair,
+    address: &XfrPublicKey,
+    page: usize,
+    per_page: usize,
+) -> Result<Value> {
+    let url = format!(
+        "{}:8667/view/balance_history?address={}&page={}&per_page={}",
+        get_serv_addr().c(d!())?,
+        wallet::public_key_to_base64(address),
+        page,
+        per_page
+    );
+    let auth = ViewKeyAuth::new(view_key, *address, unix_now().c(d!())?);
+    let auth = b64enc(&serde_json::to_vec(&auth).c(d!())?);
+
+    attohttpc::get(url)
+        .header("X-Findora-View-Key", auth)
+        .send()
+        .c(d!())?
+        .error_for_status()
+        .c(d!())?
+        .bytes()
+        .c(d!())
+        .and_then(|b| serde_json::from_slice(&b).c(d!()))
+}
+
+fn unix_now() -> Result<i64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .c(d!())
+        .map(|d| d.as_secs() as i64)
+}
+
 /// Return the ABAR by commitment.
 pub fn get_owned_abar(com: &Commitment) -> Result<(ATxoSID, AnonAssetRecord)> {
     let url = format!(
@@ -679,8 +1020,9 @@ pub fn get_owned_abar(com: &Commitment) -> Result<(ATxoSID, AnonAssetRecord)> {
         })
 }
 
+/// The current sequence number of the ledger, the base of a no-replay token
 #[inline(always)]
-fn get_seq_id() -> Result<u64> {
+pub fn get_seq_id() -> Result<u64> {
     type Resp = (
         HashOf<Option<StateCommitmentData>>,
         u64,
@@ -700,6 +1042,20 @@ fn get_seq_id() -> Result<u64> {
         .map(|resp| resp.1)
 }
 
+/// The chain parameters in effect, e.g. the limits of the transactions
+pub fn get_chain_params() -> Result<ChainParamValues> {
+    let url = format!("{}:8668/params", get_serv_addr().c(d!())?);
+
+    attohttpc::get(&url)
+        .send()
+        .c(d!(url))?
+        .error_for_status()
+        .c(d!(url))?
+        .bytes()
+        .c(d!(url))
+        .and_then(|b| serde_json::from_slice::<ChainParamValues>(&b).c(d!(url)))
+}
+
 #[inline(always)]
 #[allow(missing_docs)]
 pub fn get_owner_memo_batch(ids: &[TxoSID]) -> Result<Vec<Option<OwnerMemo>>> {
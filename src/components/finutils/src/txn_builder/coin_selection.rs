@@ -0,0 +1,182 @@
+//!
+//! # Coin selection
+//!
+//! Which of the records of a wallet pay for a transfer, so the callers need
+//! not choose the SIDs themselves. The records are given as `(key, amount)`,
+//! the keys break the ties, so the selection is the same across machines.
+//!
+
+use {
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::{cmp::Reverse, fmt},
+};
+
+/// Branch and bound gives up after this many steps
+pub const BNB_MAX_TRIES: usize = 100_000;
+
+/// How the records paying for an amount are picked
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CoinSelection {
+    /// The largest records first, the fewest inputs
+    #[default]
+    LargestFirst,
+    /// The smallest records first, to clear the dust
+    SmallestFirst,
+    /// Records summing to the amount exactly, so there is no change output,
+    /// the largest first if there are none
+    BranchAndBound,
+}
+
+impl CoinSelection {
+    /// All the strategies, by their names
+    pub const ALL: [(&'static str, CoinSelection); 3] = [
+        ("largest-first", CoinSelection::LargestFirst),
+        ("smallest-first", CoinSelection::SmallestFirst),
+        ("branch-and-bound", CoinSelection::BranchAndBound),
+    ];
+
+    /// The strategy named `s`, see `ALL`
+    pub fn parse(s: &str) -> Result<Self> {
+        Self::ALL
+            .iter()
+            .find(|(n, _)| *n == s)
+            .map(|(_, s)| *s)
+            .c(d!(format!(
+                "unknown coin selection `{s}`, expected one of: {}",
+                Self::ALL.map(|(n, _)| n).join(", ")
+            )))
+    }
+
+    /// Pick records of `coins` summing to `target` at least,
+    /// an error is returned if all of them do not.
+    pub fn select<K: Ord + Clone>(
+        &self,
+        coins: &[(K, u64)],
+        target: u64,
+    ) -> Result<Vec<(K, u64)>> {
+        let total = coins.iter().map(|(_, am)| *am as u128).sum::<u128>();
+        if total < target as u128 {
+            return Err(eg!(format!("insufficient balance: {total} for {target}")));
+        }
+        if 0 == target {
+            return Ok(vec![]);
+        }
+
+        let mut sorted = coins.to_vec();
+        match self {
+            CoinSelection::SmallestFirst => {
+                sorted.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)))
+            }
+            _ => sorted.sort_by(|a, b| (Reverse(a.1), &a.0).cmp(&(Reverse(b.1), &b.0))),
+        }
+
+        if let CoinSelection::BranchAndBound = self {
+            if let Some(picked) = branch_and_bound(&sorted, target) {
+                return Ok(picked.into_iter().map(|i| sorted[i].clone()).collect());
+            }
+        }
+        Ok(accumulate(sorted, target))
+    }
+}
+
+impl fmt::Display for CoinSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = Self::ALL
+            .iter()
+            .find(|(_, s)| s == self)
+            .map(|(n, _)| *n)
+            .unwrap_or_default();
+        write!(f, "{name}")
+    }
+}
+
+// take the records in order until the target is reached
+fn accumulate<K>(sorted: Vec<(K, u64)>, target: u64) -> Vec<(K, u64)> {
+    let mut sum = 0u64;
+    sorted
+        .into_iter()
+        .take_while(|(_, am)| {
+            let more = sum < target;
+            sum = sum.saturating_add(*am);
+            more
+        })
+        .collect()
+}
+
+// indexes of the records of `sorted`, the largest first,
+// summing to `target` exactly, by a depth first search
+fn branch_and_bound<K>(sorted: &[(K, u64)], target: u64) -> Option<Vec<usize>> {
+    // the sum of the records after each index, to prune the branches falling short
+    let mut rest = vec![0u128; sorted.len() + 1];
+    for i in (0..sorted.len()).rev() {
+        rest[i] = rest[i + 1] + sorted[i].1 as u128;
+    }
+
+    let target = target as u128;
+    let mut picked = vec![];
+    let mut sum = 0u128;
+    let mut tries = 0;
+    // the index to try next, each record is included before it is excluded
+    let mut i = 0;
+    loop {
+        tries += 1;
+        if BNB_MAX_TRIES < tries {
+            return None;
+        }
+
+        let backtrack = if sum == target {
+            return Some(picked);
+        } else if i == sorted.len() || target < sum || sum + rest[i] < target {
+            true
+        } else {
+            sum += sorted[i].1 as u128;
+            picked.push(i);
+            i += 1;
+            false
+        };
+
+        if backtrack {
+            // exclude the last included record, and move on to the next one
+            let last = picked.pop()?;
+            sum -= sorted[last].1 as u128;
+            i = last + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_coin_selection() {
+        let coins = vec![(1, 50), (2, 10), (3, 7), (4, 30), (5, 3)];
+        let keys = |s: CoinSelection, target| {
+            s.select(&coins, target)
+                .unwrap()
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![1], keys(CoinSelection::LargestFirst, 40));
+        assert_eq!(vec![5, 3, 2, 4], keys(CoinSelection::SmallestFirst, 40));
+        assert_eq!(vec![4, 2], keys(CoinSelection::BranchAndBound, 40));
+        assert_eq!(vec![2, 3, 5], keys(CoinSelection::BranchAndBound, 20));
+        // no exact match
+        assert_eq!(vec![1, 4], keys(CoinSelection::BranchAndBound, 79));
+
+        assert!(CoinSelection::LargestFirst
+            .select(&coins, 0)
+            .unwrap()
+            .is_empty());
+        assert!(CoinSelection::SmallestFirst.select(&coins, 101).is_err());
+
+        for (name, s) in CoinSelection::ALL {
+            assert_eq!(s, CoinSelection::parse(name).unwrap());
+            assert_eq!(name, s.to_string());
+        }
+        assert!(CoinSelection::parse("random").is_err());
+    }
+}
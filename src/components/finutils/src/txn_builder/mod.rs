@@ -5,34 +5,45 @@
 #![deny(warnings)]
 #![allow(clippy::needless_borrow)]
 
+pub mod coin_selection;
+pub mod payout;
+
 use {
     curve25519_dalek::scalar::Scalar,
     digest::Digest,
     fp_types::{crypto::MultiSigner, H160},
-    globutils::{wallet, Serialized, SignatureOf},
+    globutils::{wallet, HashOf, Serialized, SignatureOf},
     ledger::{
         converter::ConvertAccount,
         data_model::{
-            get_abar_commitment, AbarConvNote, AbarToBarOps, AnonTransferOps,
-            AssetRules, AssetTypeCode, BarAnonConvNote, BarToAbarOps, ConfidentialMemo,
-            DefineAsset, DefineAssetBody, IndexedSignature, IssueAsset, IssueAssetBody,
-            IssuerKeyPair, IssuerPublicKey, Memo, NoReplayToken, Operation, Transaction,
-            TransactionBody, TransferAsset, TransferAssetBody, TransferType, TxOutput,
-            TxoRef, TxoSID, UpdateMemo, UpdateMemoBody, ASSET_TYPE_FRA,
+            get_abar_commitment, parse_amount, AbarConvNote, AbarToBarOps, AnchorData,
+            AnchorDataBody, AnchorLabel, AnonTransferOps, AssetMetadata, AssetRules,
+            AssetTypeCode, BarAnonConvNote, BarToAbarOps, ConfidentialMemo,
+            CustomAssetPolicy, DefineAsset, DefineAssetBody, FeeParams,
+            IndexedSignature, IssueAsset, IssueAssetBody, IssuerKeyPair,
+            IssuerPublicKey, Memo, NoReplayToken, Operation, SignatureRules, SwapAsset,
+            SwapLeg, Transaction, TransactionBody, TransferAsset, TransferAssetBody,
+            TransferType, TxOutput, TxoRef, TxoSID, UpdateMemo, UpdateMemoBody,
+            UpdateTransferPolicy, UpdateTransferPolicyBody, XfrAddress, ASSET_TYPE_FRA,
             BAR_TO_ABAR_TX_FEE_MIN, BLACK_HOLE_PUBKEY, FEE_CALCULATING_FUNC, TX_FEE_MIN,
         },
         staking::{
             is_valid_tendermint_addr,
             ops::{
+                cancel_schedule::CancelScheduleOps,
                 claim::ClaimOps,
                 delegation::DelegationOps,
                 fra_distribution::FraDistributionOps,
                 governance::{ByzantineKind, GovernanceOps},
+                redelegation::ReDelegationOps,
                 replace_staker::ReplaceStakerOps,
+                schedule_transfer::{Data as ScheduleData, ScheduleTransferOps},
                 undelegation::UnDelegationOps,
+                update_params::UpdateParamsOps,
                 update_staker::UpdateStakerOps,
                 update_validator::UpdateValidatorOps,
             },
+            params::{ChainParamValues, ChainParams},
             td_addr_to_string, BlockHeight, PartialUnDelegation, StakerMemo,
             TendermintAddr, Validator,
         },
@@ -40,7 +51,7 @@ use {
     rand_chacha::ChaChaRng,
     rand_core::SeedableRng,
     ruc::*,
-    serde::{Deserialize, Serialize},
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
     sha2::Sha512,
     std::{
         cmp::Ordering,
@@ -75,12 +86,12 @@ use {
                 },
                 structs::{
                     AssetRecord, AssetRecordTemplate, AssetType, OpenAssetRecord,
-                    TracingPolicies, TracingPolicy,
+                    TracerMemo, TracingPolicies, TracingPolicy,
                 },
                 XfrNotePolicies,
             },
         },
-        BlindAssetRecord, OwnerMemo, XfrKeyPair, XfrPublicKey,
+        BlindAssetRecord, OwnerMemo, XfrKeyPair, XfrPublicKey, XfrSignature,
     },
 };
 
@@ -90,6 +101,53 @@ macro_rules! no_transfer_err {
     };
 }
 
+/// The holder of a key that signs transactions and operations,
+/// the secret key may live outside of this process,
+/// e.g. in a hardware wallet, a remote HSM or an air-gapped machine.
+pub trait Signer {
+    /// The public key of the signer
+    fn public_key(&self) -> XfrPublicKey;
+
+    /// Sign the serialized bytes of a message, as produced by `Serialized::new`
+    fn sign_bytes(&self, msg: &[u8]) -> Result<XfrSignature>;
+
+    /// Sign a message, the signature is checked against `public_key`
+    /// before it is returned
+    fn sign<T>(&self, msg: &T) -> Result<SignatureOf<T>>
+    where
+        Self: Sized,
+        T: Serialize + DeserializeOwned,
+    {
+        let sig = self
+            .sign_bytes(Serialized::new(msg).as_ref())
+            .c(d!("signer failed"))?;
+        let sig: SignatureOf<T> = serde_json::to_value(sig)
+            .and_then(serde_json::from_value)
+            .c(d!())?;
+        sig.verify(&self.public_key(), msg)
+            .c(d!("invalid signature from the signer"))?;
+        Ok(sig)
+    }
+}
+
+impl Signer for XfrKeyPair {
+    fn public_key(&self) -> XfrPublicKey {
+        self.get_pk()
+    }
+
+    fn sign_bytes(&self, msg: &[u8]) -> Result<XfrSignature> {
+        XfrKeyPair::sign(self, msg).c(d!())
+    }
+
+    fn sign<T>(&self, msg: &T) -> Result<SignatureOf<T>>
+    where
+        Self: Sized,
+        T: Serialize + DeserializeOwned,
+    {
+        Ok(SignatureOf::new(self, msg))
+    }
+}
+
 /// Definition of a fee operation, as a inner data structure of FeeInputs
 pub struct FeeInput {
     /// Amount
@@ -129,6 +187,42 @@ impl FeeInputs {
     }
 }
 
+/// What a transaction is checked against before submission,
+/// see `TransactionBuilder::validate_locally`
+pub trait LedgerView {
+    /// The chain parameters in effect, as served by `/params` of the query server
+    fn params(&self) -> ChainParamValues;
+
+    /// Check the transaction against the ledger state, e.g. by `simulate_txn`
+    /// of the submission server, nothing is checked by default
+    fn check_state(&self, _tx: &Transaction) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl LedgerView for ChainParamValues {
+    fn params(&self) -> ChainParamValues {
+        self.clone()
+    }
+}
+
+/// Identifies an exported `TransactionBuilder`, see `TransactionBuilder::export_json`
+pub const TXN_BUILDER_SCHEMA: &str = "findora/txn_builder";
+
+/// Current version of the exported `TransactionBuilder`,
+/// bumped with each incompatible change of its fields.
+///
+/// Version 0 is the bare transaction of `TransactionBuilder::serialize_str`.
+pub const TXN_BUILDER_SCHEMA_VERSION: u64 = 1;
+
+// The envelope of an exported builder
+#[derive(Serialize, Deserialize)]
+struct BuilderDraft<B> {
+    schema: String,
+    version: u64,
+    builder: B,
+}
+
 /// An simple builder for findora transaction
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionBuilder {
@@ -328,8 +422,15 @@ impl TransactionBuilder {
 
     /// Create a instance from seq_id
     pub fn from_seq_id(seq_id: u64) -> Self {
-        let mut prng = ChaChaRng::from_entropy();
-        let no_replay_token = NoReplayToken::new(&mut prng, seq_id);
+        Self::from_seq_id_with_rng(seq_id, &mut ChaChaRng::from_entropy())
+    }
+
+    /// Create a instance from seq_id, the no-replay token is generated by `prng`
+    pub fn from_seq_id_with_rng<R: CryptoRng + RngCore>(
+        seq_id: u64,
+        prng: &mut R,
+    ) -> Self {
+        let no_replay_token = NoReplayToken::new(prng, seq_id);
         TransactionBuilder {
             txn: Transaction::from_seq_id(seq_id),
             outputs: 0,
@@ -344,6 +445,14 @@ impl TransactionBuilder {
     pub fn get_seq_id(&self) -> u64 {
         self.no_replay_token.get_seq_id()
     }
+
+    /// The transaction will be rejected once the block commit count exceeds
+    /// `seq_id + blocks`, should be called before signing.
+    pub fn set_expiry(&mut self, blocks: u64) -> &mut Self {
+        let seq_id = self.txn.body.no_replay_token.get_seq_id();
+        self.txn.set_expiry(seq_id.saturating_add(blocks));
+        self
+    }
 }
 
 impl TransactionBuilder {
@@ -356,17 +465,95 @@ impl TransactionBuilder {
         amount: u64,
         confidentiality_flags: AssetRecordType,
     ) -> Result<&mut Self> {
-        let mut prng = ChaChaRng::from_entropy();
-        let ar = AssetRecordTemplate::with_no_asset_tracing(
+        self.add_basic_issue_asset_with_rng(
+            key_pair,
+            token_code,
+            seq_num,
             amount,
-            token_code.val,
             confidentiality_flags,
-            key_pair.get_pk().into_noah(),
-        );
+            &mut ChaChaRng::from_entropy(),
+        )
+    }
+
+    /// Same as `add_basic_issue_asset`, the amount is a decimal string like
+    /// `12.345` in the units of an asset with `decimals` decimals
+    pub fn add_basic_issue_asset_amount(
+        &mut self,
+        key_pair: &XfrKeyPair,
+        token_code: &AssetTypeCode,
+        decimals: u8,
+        seq_num: u64,
+        amount: &str,
+        confidentiality_flags: AssetRecordType,
+    ) -> Result<&mut Self> {
+        let amount = parse_amount(amount, decimals).c(d!())?;
+        self.add_basic_issue_asset(
+            key_pair,
+            token_code,
+            seq_num,
+            amount,
+            confidentiality_flags,
+        )
+    }
+
+    /// Same as `add_basic_issue_asset`, with a caller-supplied RNG
+    pub fn add_basic_issue_asset_with_rng<R: CryptoRng + RngCore>(
+        &mut self,
+        key_pair: &XfrKeyPair,
+        token_code: &AssetTypeCode,
+        seq_num: u64,
+        amount: u64,
+        confidentiality_flags: AssetRecordType,
+        prng: &mut R,
+    ) -> Result<&mut Self> {
+        self.add_traced_issue_asset_with_rng(
+            key_pair,
+            token_code,
+            seq_num,
+            amount,
+            confidentiality_flags,
+            &TracingPolicies::new(),
+            prng,
+        )
+        .c(d!())?;
+        Ok(self)
+    }
+
+    /// Issue an asset record traceable by `tracing_policies`,
+    /// returns the tracer memos from which the tracers can decrypt
+    /// the confidential amount and asset type of the record
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_traced_issue_asset_with_rng<R: CryptoRng + RngCore>(
+        &mut self,
+        key_pair: &XfrKeyPair,
+        token_code: &AssetTypeCode,
+        seq_num: u64,
+        amount: u64,
+        confidentiality_flags: AssetRecordType,
+        tracing_policies: &TracingPolicies,
+        prng: &mut R,
+    ) -> Result<Vec<TracerMemo>> {
+        let ar = if tracing_policies.is_empty() {
+            AssetRecordTemplate::with_no_asset_tracing(
+                amount,
+                token_code.val,
+                confidentiality_flags,
+                key_pair.get_pk().into_noah(),
+            )
+        } else {
+            AssetRecordTemplate::with_asset_tracing(
+                amount,
+                token_code.val,
+                confidentiality_flags,
+                key_pair.get_pk().into_noah(),
+                tracing_policies.clone(),
+            )
+        };
+        let identity_ctexts = vec![vec![]; tracing_policies.len()];
 
         let pc_gens = PedersenCommitmentRistretto::default();
-        let (ba, _, owner_memo) =
-            build_blind_asset_record(&mut prng, &pc_gens, &ar, vec![]);
+        let (ba, tracer_memos, owner_memo) =
+            build_blind_asset_record(prng, &pc_gens, &ar, identity_ctexts);
         self.add_operation_issue_asset(
             key_pair,
             token_code,
@@ -376,11 +563,15 @@ impl TransactionBuilder {
                     id: None,
                     record: BlindAssetRecord::from_noah(&ba),
                     lien: None,
+                    cosig_rules: None,
+                    lock_until: 0,
                 },
                 owner_memo.map(|om| OwnerMemo::from_noah(&om).unwrap()),
             )],
         )
-        .c(d!())
+        .c(d!())?;
+
+        Ok(tracer_memos)
     }
 
     #[allow(missing_docs)]
@@ -557,32 +748,82 @@ impl TransactionBuilder {
         token_code: Option<AssetTypeCode>,
         asset_rules: AssetRules,
         memo: &str,
+    ) -> Result<&mut Self> {
+        self.add_operation_create_asset_with_metadata(
+            key_pair,
+            token_code,
+            asset_rules,
+            memo,
+            None,
+        )
+        .c(d!())
+    }
+
+    /// Same as `add_operation_create_asset`,
+    /// with the display metadata of the asset
+    pub fn add_operation_create_asset_with_metadata(
+        &mut self,
+        key_pair: &XfrKeyPair,
+        token_code: Option<AssetTypeCode>,
+        asset_rules: AssetRules,
+        memo: &str,
+        metadata: Option<AssetMetadata>,
     ) -> Result<&mut Self> {
         let token_code = match token_code {
             Some(code) => code,
             None => AssetTypeCode::gen_random(),
         };
         let iss_keypair = IssuerKeyPair { keypair: &key_pair };
+        let mut body = DefineAssetBody::new(
+            &token_code,
+            &IssuerPublicKey {
+                key: *key_pair.get_pk_ref(),
+            },
+            asset_rules,
+            Some(Memo(memo.into())),
+            Some(ConfidentialMemo {}),
+        )
+        .c(d!())?;
+        if let Some(metadata) = metadata {
+            body.set_metadata(metadata).c(d!())?;
+        }
         self.txn.add_operation(Operation::DefineAsset(
-            DefineAsset::new(
-                DefineAssetBody::new(
-                    &token_code,
-                    &IssuerPublicKey {
-                        key: *key_pair.get_pk_ref(),
-                    },
-                    asset_rules,
-                    Some(Memo(memo.into())),
-                    Some(ConfidentialMemo {}),
-                )
-                .c(d!())?,
-                &iss_keypair,
-            )
-            .c(d!())?,
+            DefineAsset::new(body, &iss_keypair).c(d!())?,
         ));
 
         Ok(self)
     }
 
+    /// Same as `add_operation_create_asset`, the issuer signs with a `Signer`
+    pub fn add_operation_create_asset_by_signer<S: Signer>(
+        &mut self,
+        signer: &S,
+        token_code: Option<AssetTypeCode>,
+        asset_rules: AssetRules,
+        memo: &str,
+    ) -> Result<&mut Self> {
+        let token_code = token_code.unwrap_or_else(AssetTypeCode::gen_random);
+        let pubkey = IssuerPublicKey {
+            key: signer.public_key(),
+        };
+        let body = DefineAssetBody::new(
+            &token_code,
+            &pubkey,
+            asset_rules,
+            Some(Memo(memo.into())),
+            Some(ConfidentialMemo {}),
+        )
+        .c(d!())?;
+        let signature = signer.sign(&body).c(d!())?;
+        self.txn.add_operation(Operation::DefineAsset(DefineAsset {
+            body,
+            pubkey,
+            signature,
+        }));
+
+        Ok(self)
+    }
+
     /// Add asset issuing operation to builder and return modified builder
     pub fn add_operation_issue_asset(
         &mut self,
@@ -603,9 +844,54 @@ impl TransactionBuilder {
         Ok(self)
     }
 
+    /// Same as `add_operation_issue_asset`, the issuer signs with a `Signer`
+    pub fn add_operation_issue_asset_by_signer<S: Signer>(
+        &mut self,
+        signer: &S,
+        token_code: &AssetTypeCode,
+        seq_num: u64,
+        records_and_memos: &[(TxOutput, Option<OwnerMemo>)],
+    ) -> Result<&mut Self> {
+        let body =
+            IssueAssetBody::new(token_code, seq_num, &records_and_memos).c(d!())?;
+        let signature = signer.sign(&body).c(d!())?;
+        self.txn.add_operation(Operation::IssueAsset(IssueAsset {
+            body,
+            pubkey: IssuerPublicKey {
+                key: signer.public_key(),
+            },
+            signature,
+        }));
+        Ok(self)
+    }
+
     /// Add asset transfer operation to builder and return modified builder
     #[allow(clippy::too_many_arguments)]
     pub fn add_operation_transfer_asset(
+        &mut self,
+        keys: &XfrKeyPair,
+        input_sids: Vec<TxoRef>,
+        input_records: &[OpenAssetRecord],
+        input_tracing_policies: Vec<Option<TracingPolicy>>,
+        input_identity_commitments: Vec<Option<ACCommitment>>,
+        output_records: &[AssetRecord],
+        output_identity_commitments: Vec<Option<ACCommitment>>,
+    ) -> Result<&mut Self> {
+        self.add_operation_transfer_asset_with_rng(
+            keys,
+            input_sids,
+            input_records,
+            input_tracing_policies,
+            input_identity_commitments,
+            output_records,
+            output_identity_commitments,
+            &mut ChaChaRng::from_entropy(),
+        )
+    }
+
+    /// Same as `add_operation_transfer_asset`, with a caller-supplied RNG
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_operation_transfer_asset_with_rng<R: CryptoRng + RngCore>(
         &mut self,
         keys: &XfrKeyPair,
         input_sids: Vec<TxoRef>,
@@ -614,8 +900,8 @@ impl TransactionBuilder {
         _input_identity_commitments: Vec<Option<ACCommitment>>,
         output_records: &[AssetRecord],
         _output_identity_commitments: Vec<Option<ACCommitment>>,
+        prng: &mut R,
     ) -> Result<&mut Self> {
-        let mut prng = ChaChaRng::from_entropy();
         let mut input_asset_records = vec![];
         for (oar, tracing_policy) in
             input_records.iter().zip(input_tracing_policies.iter())
@@ -626,7 +912,7 @@ impl TransactionBuilder {
             }
             input_asset_records.push(
                 AssetRecord::from_open_asset_record_with_asset_tracing_but_no_identity(
-                    &mut prng,
+                    prng,
                     oar.clone(),
                     policies,
                 )
@@ -636,7 +922,7 @@ impl TransactionBuilder {
 
         let mut xfr = TransferAsset::new(
             TransferAssetBody::new(
-                &mut prng,
+                prng,
                 input_sids,
                 &input_asset_records[..],
                 output_records,
@@ -675,6 +961,49 @@ impl TransactionBuilder {
         self
     }
 
+    /// Add an operation to replace the transfer whitelist of an asset,
+    /// the asset must have been defined with a transfer policy
+    pub fn add_operation_update_transfer_policy(
+        &mut self,
+        auth_key_pair: &XfrKeyPair,
+        asset_code: AssetTypeCode,
+        whitelist: Vec<XfrPublicKey>,
+    ) -> &mut Self {
+        let update = UpdateTransferPolicy::new(
+            UpdateTransferPolicyBody {
+                asset_type: asset_code,
+                policy: CustomAssetPolicy::new(whitelist),
+                no_replay_token: self.txn.body.no_replay_token,
+            },
+            auth_key_pair,
+        );
+        self.txn
+            .add_operation(Operation::UpdateTransferPolicy(update));
+        self
+    }
+
+    /// Add an operation to anchor the hash of a document,
+    /// fails if the label is empty or too long
+    pub fn add_operation_anchor_data(
+        &mut self,
+        keypair: &XfrKeyPair,
+        hash: [u8; 32],
+        label: AnchorLabel,
+    ) -> Result<&mut Self> {
+        let anchor = AnchorData::new(
+            AnchorDataBody {
+                hash,
+                label,
+                no_replay_token: self.txn.body.no_replay_token,
+            },
+            keypair,
+        );
+        anchor.verify().c(d!())?;
+        self.txn
+            .add_operation(Operation::AnchorData(Box::new(anchor)));
+        Ok(self)
+    }
+
     /// Add an operation to convert a Blind Asset Record to a Anonymous record and return the Commitment
     /// # Arguments
     /// * `auth_key_pair` -  XfrKeyPair of the owner BAR for conversion
@@ -981,6 +1310,28 @@ impl TransactionBuilder {
         self.add_operation(Operation::UnDelegation(Box::new(op)))
     }
 
+    /// Add a operation to move some FRA from a validator to another one,
+    /// without waiting for the unbonding period, only for the EVM staking
+    pub fn add_operation_redelegation(
+        &mut self,
+        keypair: &XfrKeyPair,
+        src_validator: TendermintAddr,
+        dst_validator: TendermintAddr,
+        amount: u64,
+    ) -> Result<&mut Self> {
+        if src_validator == dst_validator {
+            return Err(eg!("the source and the target validator are the same"));
+        }
+        let op = ReDelegationOps::new(
+            keypair,
+            src_validator,
+            dst_validator,
+            amount,
+            self.txn.body.no_replay_token,
+        );
+        Ok(self.add_operation(Operation::ReDelegation(op)))
+    }
+
     /// Add a operation to claim all the rewards
     pub fn add_operation_claim(
         &mut self,
@@ -992,6 +1343,42 @@ impl TransactionBuilder {
         self.add_operation(Operation::Claim(op))
     }
 
+    /// Add a operation to pay `amount` FRA to `receiver` every `interval` blocks,
+    /// from `start_height` to `end_height`, the escrow(see `Data::total_amount`)
+    /// must be paid to `BLACK_HOLE_PUBKEY_STAKING` by a transfer of the same transaction
+    pub fn add_operation_schedule_transfer(
+        &mut self,
+        keypair: &XfrKeyPair,
+        receiver: XfrPublicKey,
+        amount: u64,
+        interval: BlockHeight,
+        start_height: BlockHeight,
+        end_height: BlockHeight,
+    ) -> Result<&mut Self> {
+        let body = ScheduleData::new(
+            receiver,
+            amount,
+            interval,
+            start_height,
+            end_height,
+            self.txn.body.no_replay_token,
+        );
+        body.total_amount().c(d!())?;
+        let op = ScheduleTransferOps::new(keypair, body);
+        Ok(self.add_operation(Operation::ScheduleTransfer(Box::new(op))))
+    }
+
+    /// Add a operation to stop a scheduled transfer of `keypair`,
+    /// the rest of the escrow is refunded by the next coinbase transaction
+    pub fn add_operation_cancel_schedule(
+        &mut self,
+        keypair: &XfrKeyPair,
+        id: u64,
+    ) -> &mut Self {
+        let op = CancelScheduleOps::new(keypair, id, self.txn.body.no_replay_token);
+        self.add_operation(Operation::CancelSchedule(op))
+    }
+
     #[allow(missing_docs)]
     pub fn add_operation_fra_distribution(
         &mut self,
@@ -1003,6 +1390,18 @@ impl TransactionBuilder {
             .map(move |op| self.add_operation(Operation::FraDistribution(op)))
     }
 
+    /// Add a operation to adjust the on-chain parameters,
+    /// it must be signed by the validators like `UpdateValidator`
+    pub fn add_operation_update_params(
+        &mut self,
+        kps: &[&XfrKeyPair],
+        params: ChainParams,
+    ) -> Result<&mut Self> {
+        UpdateParamsOps::new(kps, params, self.txn.body.no_replay_token)
+            .c(d!())
+            .map(move |op| self.add_operation(Operation::UpdateParams(op)))
+    }
+
     #[allow(missing_docs)]
     pub fn add_operation_governance(
         &mut self,
@@ -1096,6 +1495,12 @@ impl TransactionBuilder {
         Ok(self)
     }
 
+    /// Same as `sign`, the transaction is signed with a `Signer`
+    pub fn sign_by_signer<S: Signer>(&mut self, signer: &S) -> Result<&mut Self> {
+        let sig = signer.sign(&self.txn.body).c(d!())?;
+        self.add_signature(&signer.public_key(), sig).c(d!())
+    }
+
     /// Signing this transaction with XfrKeyPair, but insert to `Transaction.pubkey_sign_map`
     pub fn sign_to_map(&mut self, kp: &XfrKeyPair) -> &mut Self {
         self.txn.sign_to_map(kp);
@@ -1114,6 +1519,124 @@ impl TransactionBuilder {
         // Unwrap is safe because the underlying transaction is guaranteed to be serializable.
         serde_json::to_string(&self.txn).unwrap()
     }
+
+    /// Export the whole state of the builder, so that a draft can be persisted
+    /// and resumed by `import_json`, also by a later version of the library.
+    ///
+    /// The anonymous operations must be finished by `build` first,
+    /// their pending notes are not exported.
+    pub fn export_json(&self) -> Result<String> {
+        if !(self.abar_bar_cache.is_empty()
+            && self.abar_ar_cache.is_empty()
+            && self.abar_abar_cache.is_empty())
+        {
+            return Err(eg!("pending anonymous operations, call `build` first"));
+        }
+        serde_json::to_string(&BuilderDraft {
+            schema: TXN_BUILDER_SCHEMA.to_owned(),
+            version: TXN_BUILDER_SCHEMA_VERSION,
+            builder: self,
+        })
+        .c(d!())
+    }
+
+    /// Resume a builder exported by `export_json`,
+    /// or one around a transaction of `serialize_str`
+    pub fn import_json(s: &str) -> Result<Self> {
+        let v = serde_json::from_str::<serde_json::Value>(s).c(d!())?;
+        if v.get("schema").is_none() {
+            let txn = serde_json::from_value::<Transaction>(v).c(d!())?;
+            return Ok(TransactionBuilder {
+                no_replay_token: txn.body.no_replay_token,
+                txn,
+                outputs: 0,
+                abar_bar_cache: vec![],
+                abar_ar_cache: vec![],
+                abar_abar_cache: vec![],
+            });
+        }
+
+        let draft =
+            serde_json::from_value::<BuilderDraft<serde_json::Value>>(v).c(d!())?;
+        if TXN_BUILDER_SCHEMA != draft.schema {
+            return Err(eg!(format!("unknown schema: {}", draft.schema)));
+        }
+        if TXN_BUILDER_SCHEMA_VERSION < draft.version {
+            return Err(eg!(format!(
+                "schema version {} is newer than {}, upgrade the library",
+                draft.version, TXN_BUILDER_SCHEMA_VERSION
+            )));
+        }
+        serde_json::from_value(draft.builder).c(d!())
+    }
+
+    /// Size in bytes of the transaction as submitted, which is compared with
+    /// `max_txn_bytes`, the signatures added later make it larger
+    pub fn estimated_size(&self) -> usize {
+        self.serialize().len()
+    }
+
+    /// Number of operations, which is compared with `max_txn_ops`
+    #[inline(always)]
+    pub fn operation_count(&self) -> usize {
+        self.txn.body.operations.len()
+    }
+
+    /// Run the checks of `check_tx` that need no ledger state:
+    /// the size, the number of operations, the fee and the signatures,
+    /// then `LedgerView::check_state`.
+    ///
+    /// All the violated limits are reported in the error.
+    pub fn validate_locally<V: LedgerView>(&self, view: &V) -> Result<()> {
+        let params = view.params();
+        let fee_params = FeeParams {
+            tx_fee_min: params.tx_fee_min,
+            bar_to_abar_tx_fee_min: params.bar_to_abar_tx_fee_min,
+            ..FeeParams::current()
+        };
+
+        let mut violations = vec![];
+        let size = self.estimated_size() as u64;
+        if params.max_txn_bytes < size {
+            violations.push(format!(
+                "txn of {size} bytes exceeds the limit of {}",
+                params.max_txn_bytes
+            ));
+        }
+        let ops = self.operation_count() as u64;
+        if params.max_txn_ops < ops {
+            violations.push(format!(
+                "txn of {ops} operations exceeds the limit of {}",
+                params.max_txn_ops
+            ));
+        }
+        if !self.txn.valid_in_abci_with(&fee_params) {
+            violations.push(format!(
+                "txn pays less than the fee of {}",
+                self.txn.min_fee_with(&fee_params)
+            ));
+        }
+        if let Err(e) = self.txn.check_tx() {
+            violations.push(format!("missing signatures: {e}"));
+        }
+
+        if !violations.is_empty() {
+            return Err(eg!(violations.join("; ")));
+        }
+        view.check_state(&self.txn).c(d!())
+    }
+
+    /// A line for each operation, in order, for signing UIs to show
+    /// what the transaction does
+    pub fn to_summary(&self) -> String {
+        self.txn
+            .summary()
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("{}. {s}", i + 1))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 /// Generates an asset record from an asset record template using optional identity proof.
@@ -1232,6 +1755,33 @@ fn gen_bar_conv_note(
     }
 }
 
+// Compare the input total and the output total of each asset type
+fn check_balance_by_asset(
+    inputs: impl Iterator<Item = (AssetType, u64)>,
+    outputs: impl Iterator<Item = (AssetType, u64)>,
+) -> Result<()> {
+    let mut totals: BTreeMap<AssetType, (u64, u64)> = BTreeMap::new();
+    for (asset_type, am) in inputs {
+        let t = totals.entry(asset_type).or_default();
+        t.0 = t.0.checked_add(am).c(d!("input amount overflow"))?;
+    }
+    for (asset_type, am) in outputs {
+        let t = totals.entry(asset_type).or_default();
+        t.1 = t.1.checked_add(am).c(d!("output amount overflow"))?;
+    }
+
+    for (asset_type, (input_total, output_total)) in totals.into_iter() {
+        if input_total != output_total {
+            return Err(eg!(format!(
+                "{}: {input_total} != {output_total}",
+                AssetTypeCode { val: asset_type }.to_base64()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 /// TransferOperationBuilder constructs transfer operations using the factory pattern
 /// Inputs and outputs are added iteratively before being signed by all input record owners
 #[derive(Clone, Serialize, Deserialize, Default)]
@@ -1247,6 +1797,20 @@ pub struct TransferOperationBuilder {
     transfer: Option<TransferAsset>,
     transfer_type: TransferType,
     auto_refund: bool,
+    // (amount, receiver) of the FRA fee output, inserted by `balance`
+    #[serde(default)]
+    fee: Option<(u64, XfrPublicKey)>,
+    #[serde(default)]
+    fee_balanced: bool,
+    // co-signature rules of the spent outputs, by input index
+    #[serde(default)]
+    input_cosig_rules: BTreeMap<usize, SignatureRules>,
+    // co-signature rules of the new outputs, by output index
+    #[serde(default)]
+    output_cosig_rules: BTreeMap<usize, SignatureRules>,
+    // block counts before which the new outputs can not be spent, by output index
+    #[serde(default)]
+    output_lock_until: BTreeMap<usize, u64>,
 }
 
 impl TransferOperationBuilder {
@@ -1264,9 +1828,88 @@ impl TransferOperationBuilder {
         self
     }
 
-    /// TxoRef is the location of the input on the ledger and the amount is how much of the record
-    /// should be spent in the transfer. See tests for example usage.
-    pub fn add_input(
+    /// Reserve `amount` FRA of the unspent part of the inputs as a fee paid to `fee_address`,
+    /// the fee output is inserted by `balance`, so with `auto_refund` disabled,
+    /// `create` fails unless `balance` has been called.
+    pub fn add_fee(
+        &mut self,
+        amount: u64,
+        fee_address: XfrPublicKey,
+    ) -> Result<&mut Self> {
+        if self.transfer.is_some() {
+            return Err(eg!(
+                ("Cannot mutate a transfer that has been signed".to_string())
+            ));
+        }
+        if self.fee.is_some() {
+            return Err(eg!("fee has already been added"));
+        }
+        self.fee = Some((amount, fee_address));
+        Ok(self)
+    }
+
+    /// The input `idx` spends an output locked by co-signature rules,
+    /// `rules` must be the same as those of the output on the ledger
+    pub fn set_input_cosig_rules(
+        &mut self,
+        idx: usize,
+        rules: SignatureRules,
+    ) -> Result<&mut Self> {
+        if self.transfer.is_some() {
+            return Err(eg!(
+                ("Cannot mutate a transfer that has been signed".to_string())
+            ));
+        }
+        if idx >= self.input_records.len() {
+            return Err(eg!(format!("input {idx} does not exist")));
+        }
+        self.input_cosig_rules.insert(idx, rules);
+        Ok(self)
+    }
+
+    /// Lock the output `idx` by co-signature rules, spending it will need
+    /// the co-signatures besides the signature of its owner
+    pub fn set_output_cosig_rules(
+        &mut self,
+        idx: usize,
+        rules: SignatureRules,
+    ) -> Result<&mut Self> {
+        if self.transfer.is_some() {
+            return Err(eg!(
+                ("Cannot mutate a transfer that has been signed".to_string())
+            ));
+        }
+        if idx >= self.output_records.len() {
+            return Err(eg!(format!("output {idx} does not exist")));
+        }
+        if !rules.is_satisfiable() {
+            return Err(eg!("co-signature rules can never be satisfied"));
+        }
+        self.output_cosig_rules.insert(idx, rules);
+        Ok(self)
+    }
+
+    /// Lock the output `idx` until the block count of the ledger reaches `height`
+    pub fn set_output_lock_until(
+        &mut self,
+        idx: usize,
+        height: u64,
+    ) -> Result<&mut Self> {
+        if self.transfer.is_some() {
+            return Err(eg!(
+                ("Cannot mutate a transfer that has been signed".to_string())
+            ));
+        }
+        if idx >= self.output_records.len() {
+            return Err(eg!(format!("output {idx} does not exist")));
+        }
+        self.output_lock_until.insert(idx, height);
+        Ok(self)
+    }
+
+    /// TxoRef is the location of the input on the ledger and the amount is how much of the record
+    /// should be spent in the transfer. See tests for example usage.
+    pub fn add_input(
         &mut self,
         txo_sid: TxoRef,
         open_ar: OpenAssetRecord,
@@ -1332,6 +1975,30 @@ impl TransferOperationBuilder {
         Ok(self)
     }
 
+    /// Adds an untraced output of `amount`, a decimal string like `12.345`
+    /// in the units of an asset with `decimals` decimals
+    pub fn add_output_amount(
+        &mut self,
+        token_code: &AssetTypeCode,
+        decimals: u8,
+        amount: &str,
+        asset_record_type: AssetRecordType,
+        receiver: XfrPublicKey,
+    ) -> Result<&mut Self> {
+        let amount = parse_amount(amount, decimals).c(d!())?;
+        self.add_output(
+            &AssetRecordTemplate::with_no_asset_tracing(
+                amount,
+                token_code.val,
+                asset_record_type,
+                receiver,
+            ),
+            None,
+            None,
+            None,
+        )
+    }
+
     /// Adds output to the records, and stores the asset amount blinds and type blind in the blinds parameter passed in.
     pub fn add_output_and_store_blinds<R: CryptoRng + RngCore>(
         &mut self,
@@ -1402,25 +2069,128 @@ impl TransferOperationBuilder {
 
     // Check if outputs and inputs are balanced
     fn check_balance(&self) -> Result<()> {
-        let input_total: u64 = self
-            .input_records
-            .iter()
-            .fold(0, |acc, ar| acc + ar.open_asset_record.amount);
-        let output_total = self
-            .output_records
-            .iter()
-            .fold(0, |acc, ar| acc + ar.open_asset_record.amount);
-        if input_total != output_total {
-            return Err(eg!(format!("{input_total} != {output_total}")));
+        check_balance_by_asset(
+            self.input_records.iter().map(|ar| {
+                (ar.open_asset_record.asset_type, ar.open_asset_record.amount)
+            }),
+            self.output_records.iter().map(|ar| {
+                (ar.open_asset_record.asset_type, ar.open_asset_record.amount)
+            }),
+        )
+        .c(d!())
+    }
+
+    /// Merge the records of `inputs`, all of the same asset and owner, into one record
+    /// of the type `rt` back to their owner, `fee` FRA is paid out of the merged
+    /// amount, so it must be zero for other assets.
+    ///
+    /// The longest prefix of `inputs` whose transfer serializes within `max_bytes`
+    /// is taken, returns the created builder, yet to be signed,
+    /// along with the number of the inputs taken.
+    pub fn consolidate(
+        inputs: &[(TxoRef, OpenAssetRecord)],
+        rt: AssetRecordType,
+        fee: u64,
+        max_bytes: usize,
+    ) -> Result<(Self, usize)> {
+        let (_, first) = inputs.first().c(d!("no inputs"))?;
+        if inputs.iter().any(|(_, oar)| {
+            oar.asset_type != first.asset_type
+                || oar.get_pub_key() != first.get_pub_key()
+        }) {
+            return Err(eg!("inputs of different assets or owners"));
+        }
+        if 0 < fee && first.asset_type != ASSET_TYPE_FRA {
+            return Err(eg!("the fee can only be paid out of FRA inputs"));
         }
 
-        Ok(())
+        enum Probe {
+            Fits(TransferOperationBuilder),
+            TooLarge,
+            // not enough for the fee
+            Short,
+        }
+
+        let build = |n: usize| -> Result<Probe> {
+            let total = inputs[..n]
+                .iter()
+                .try_fold(0u64, |acc, (_, oar)| acc.checked_add(oar.amount))
+                .c(d!("amount overflow"))?;
+            if total <= fee {
+                return Ok(Probe::Short);
+            }
+
+            let mut op = Self::new();
+            for (sid, oar) in inputs[..n].iter() {
+                op.add_input(*sid, oar.clone(), None, None, oar.amount)
+                    .c(d!())?;
+            }
+            op.add_output(
+                &AssetRecordTemplate::with_no_asset_tracing(
+                    total - fee,
+                    first.asset_type,
+                    rt,
+                    *first.get_pub_key(),
+                ),
+                None,
+                None,
+                None,
+            )
+            .c(d!())?;
+            if 0 < fee {
+                op.add_output(
+                    &AssetRecordTemplate::with_no_asset_tracing(
+                        fee,
+                        ASSET_TYPE_FRA,
+                        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                        *BLACK_HOLE_PUBKEY,
+                    ),
+                    None,
+                    None,
+                    None,
+                )
+                .c(d!())?;
+            }
+            op.create(TransferType::Standard).c(d!())?;
+
+            let size = serde_json::to_vec(&op.transaction().c(d!())?)
+                .c(d!())?
+                .len();
+            Ok(alt!(size <= max_bytes, Probe::Fits(op), Probe::TooLarge))
+        };
+
+        // both the size and the amount grow with the number of inputs, so bisect it
+        let (mut lo, mut hi) = (0, inputs.len());
+        let mut best = None;
+        while lo < hi {
+            let mid = lo + (hi - lo + 1) / 2;
+            match build(mid).c(d!())? {
+                Probe::Fits(op) => {
+                    best = Some((op, mid));
+                    lo = mid;
+                }
+                Probe::Short => lo = mid,
+                Probe::TooLarge => hi = mid - 1,
+            }
+        }
+        best.c(d!(
+            "the inputs fitting in a transfer are not enough for the fee"
+        ))
     }
 
     /// Ensures that outputs and inputs are balanced by adding remainder outputs for leftover asset
-    /// amounts
+    /// amounts, the balance is checked for each asset type separately
     pub fn balance(&mut self, rt: Option<AssetRecordType>) -> Result<&mut Self> {
-        let mut prng = ChaChaRng::from_entropy();
+        self.balance_with_rng(rt, &mut ChaChaRng::from_entropy())
+    }
+
+    /// Same as `balance`, the remainder and fee records are generated
+    /// by a caller-supplied RNG
+    pub fn balance_with_rng<R: CryptoRng + RngCore>(
+        &mut self,
+        rt: Option<AssetRecordType>,
+        prng: &mut R,
+    ) -> Result<&mut Self> {
         if self.transfer.is_some() {
             return Err(eg!(
                 ("Cannot mutate a transfer that has been signed".to_string())
@@ -1430,11 +2200,45 @@ impl TransferOperationBuilder {
         // for: repeated/idempotent balance
         let mut amt_cache = vec![];
 
-        let spend_total: u64 = self.spend_amounts.iter().sum();
+        // the fee is taken from the unspent part of FRA inputs
+        let mut spend_amounts = self.spend_amounts.clone();
+        let mut fee_record = None;
+        if let Some((fee, fee_address)) = self.fee.filter(|_| !self.fee_balanced) {
+            let mut left = fee;
+            for (spend_amount, ar) in
+                spend_amounts.iter_mut().zip(self.input_records.iter())
+            {
+                if 0 == left {
+                    break;
+                }
+                if ASSET_TYPE_FRA == ar.open_asset_record.asset_type {
+                    let n = ar
+                        .open_asset_record
+                        .amount
+                        .saturating_sub(*spend_amount)
+                        .min(left);
+                    *spend_amount += n;
+                    left -= n;
+                }
+            }
+            if 0 < left {
+                return Err(eg!(format!("insufficient FRA inputs for fee: {fee}")));
+            }
+            let ar_template = AssetRecordTemplate::with_no_asset_tracing(
+                fee,
+                ASSET_TYPE_FRA,
+                AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                fee_address.into_noah(),
+            );
+            fee_record = Some(
+                AssetRecord::from_template_no_identity_tracing(prng, &ar_template)
+                    .c(d!())?,
+            );
+        }
+
         let mut partially_consumed_inputs = Vec::new();
 
-        for (idx, ((spend_amount, ar), policies)) in self
-            .spend_amounts
+        for (idx, ((spend_amount, ar), policies)) in spend_amounts
             .iter()
             .zip(self.input_records.iter())
             .zip(self.inputs_tracing_policies.iter())
@@ -1443,7 +2247,9 @@ impl TransferOperationBuilder {
             let amt = ar.open_asset_record.get_amount();
             match spend_amount.cmp(amt) {
                 Ordering::Greater => {
-                    return Err(eg!());
+                    return Err(eg!(format!(
+                        "input[{idx}]: spend amount {spend_amount} > {amt}"
+                    )));
                 }
                 Ordering::Less => {
                     let asset_type = *ar.open_asset_record.get_asset_type();
@@ -1458,13 +2264,11 @@ impl TransferOperationBuilder {
                         policies.clone(),
                     );
                     let ar = AssetRecord::from_template_no_identity_tracing(
-                        &mut prng,
+                        prng,
                         &ar_template,
                     )
                     .c(d!())?;
-                    partially_consumed_inputs.push(ar);
-                    self.outputs_tracing_policies.push(policies.clone());
-                    self.output_identity_commitments.push(None);
+                    partially_consumed_inputs.push((ar, policies.clone()));
 
                     // for: repeated/idempotent balance
                     amt_cache.push((idx, *amt));
@@ -1473,16 +2277,35 @@ impl TransferOperationBuilder {
             }
         }
 
-        let output_total = self
-            .output_records
-            .iter()
-            .fold(0, |acc, ar| acc + ar.open_asset_record.amount);
-        if spend_total != output_total {
-            return Err(eg!(format!("{spend_total} != {output_total}")));
+        // every asset type must be balanced on its own
+        check_balance_by_asset(
+            spend_amounts
+                .iter()
+                .zip(self.input_records.iter())
+                .map(|(am, ar)| (ar.open_asset_record.asset_type, *am)),
+            self.output_records
+                .iter()
+                .chain(fee_record.iter())
+                .map(|ar| {
+                    (ar.open_asset_record.asset_type, ar.open_asset_record.amount)
+                }),
+        )
+        .c(d!())?;
+
+        for (ar, policies) in partially_consumed_inputs.into_iter() {
+            self.output_records.push(ar);
+            self.outputs_tracing_policies.push(policies);
+            self.output_identity_commitments.push(None);
+        }
+        if let Some(ar) = fee_record {
+            self.output_records.push(ar);
+            self.outputs_tracing_policies.push(TracingPolicies::new());
+            self.output_identity_commitments.push(None);
+            self.fee_balanced = true;
         }
-        self.output_records.append(&mut partially_consumed_inputs);
 
         // for: repeated/idempotent balance
+        self.spend_amounts = spend_amounts;
         amt_cache.into_iter().for_each(|(idx, am)| {
             self.spend_amounts[idx] = am;
         });
@@ -1493,13 +2316,24 @@ impl TransferOperationBuilder {
     /// Finalize the transaction and prepare for signing. Once called, the transaction cannot be
     /// modified.
     pub fn create(&mut self, transfer_type: TransferType) -> Result<&mut Self> {
+        self.create_with_rng(transfer_type, &mut ChaChaRng::from_entropy())
+    }
+
+    /// Same as `create`, the transfer note is generated by a caller-supplied RNG
+    pub fn create_with_rng<R: CryptoRng + RngCore>(
+        &mut self,
+        transfer_type: TransferType,
+        prng: &mut R,
+    ) -> Result<&mut Self> {
         if self.auto_refund {
-            self.balance(None).c(d!())?;
+            self.balance_with_rng(None, prng).c(d!())?;
         } else {
+            if self.fee.is_some() && !self.fee_balanced {
+                return Err(eg!("the fee is not balanced, call `balance` first"));
+            }
             self.check_balance().c(d!())?;
         }
 
-        let mut prng = ChaChaRng::from_entropy();
         let num_inputs = self.input_records.len();
         let num_outputs = self.output_records.len();
         let xfr_policies = XfrNotePolicies::new(
@@ -1508,8 +2342,8 @@ impl TransferOperationBuilder {
             self.outputs_tracing_policies.clone(),
             vec![None; num_outputs],
         );
-        let body = TransferAssetBody::new(
-            &mut prng,
+        let mut body = TransferAssetBody::new(
+            prng,
             self.input_sids.clone(),
             &self.input_records,
             &self.output_records,
@@ -1518,6 +2352,17 @@ impl TransferOperationBuilder {
             transfer_type,
         )
         .c(d!())?;
+        if !self.input_cosig_rules.is_empty() {
+            body.input_cosig_rules = (0..num_inputs)
+                .map(|idx| self.input_cosig_rules.get(&idx).cloned())
+                .collect();
+        }
+        for (idx, rules) in self.output_cosig_rules.iter() {
+            body.outputs[*idx].cosig_rules = Some(rules.clone());
+        }
+        for (idx, height) in self.output_lock_until.iter() {
+            body.outputs[*idx].lock_until = *height;
+        }
         self.transfer = Some(TransferAsset::new(body).c(d!())?);
         Ok(self)
     }
@@ -1542,6 +2387,20 @@ impl TransferOperationBuilder {
         Ok(self)
     }
 
+    /// Same as `sign`, the input owner signs with a `Signer`
+    pub fn sign_by_signer<S: Signer>(&mut self, signer: &S) -> Result<&mut Self> {
+        let trn = self.transfer.as_mut().c(d!(no_transfer_err!()))?;
+        let sig = IndexedSignature {
+            signature: signer.sign(&(trn.body.clone(), None)).c(d!())?,
+            address: XfrAddress {
+                key: signer.public_key(),
+            },
+            input_idx: None,
+        };
+        trn.attach_signature(sig).c(d!())?;
+        Ok(self)
+    }
+
     #[allow(missing_docs)]
     pub fn create_input_signature(
         &self,
@@ -1555,6 +2414,29 @@ impl TransferOperationBuilder {
         Ok(sig)
     }
 
+    /// Co-sign the input `input_idx`, which has co-signature rules
+    pub fn cosign(&mut self, kp: &XfrKeyPair, input_idx: usize) -> Result<&mut Self> {
+        self.transfer
+            .as_mut()
+            .c(d!(no_transfer_err!()))?
+            .cosign(kp, input_idx)
+            .c(d!())?;
+        Ok(self)
+    }
+
+    /// Same as `cosign`, the co-signature is returned to be attached by another party
+    pub fn create_cosignature(
+        &self,
+        kp: &XfrKeyPair,
+        input_idx: usize,
+    ) -> Result<IndexedSignature<TransferAssetBody>> {
+        let trn = self.transfer.as_ref().c(d!(no_transfer_err!()))?;
+        if trn.body.input_cosig_rules(input_idx).is_none() {
+            return Err(eg!(format!("input {input_idx} needs no co-signatures")));
+        }
+        Ok(trn.body.compute_body_signature(kp, Some(input_idx)))
+    }
+
     #[allow(missing_docs)]
     pub fn attach_signature(
         &mut self,
@@ -1576,6 +2458,47 @@ impl TransferOperationBuilder {
         Ok(Operation::TransferAsset(self.transfer.clone().c(d!())?))
     }
 
+    /// Hash of the finalized transfer body, all signers must agree on it
+    pub fn body_hash(&self) -> Result<HashOf<TransferAssetBody>> {
+        self.transfer
+            .as_ref()
+            .c(d!(no_transfer_err!()))
+            .map(|t| HashOf::new(&t.body))
+    }
+
+    /// Serialize the builder so that it can be handed to another party to sign offline
+    pub fn serialize_str(&self) -> Result<String> {
+        serde_json::to_string(self).c(d!())
+    }
+
+    /// Restore a builder serialized by `serialize_str`
+    pub fn deserialize_str(s: &str) -> Result<Self> {
+        serde_json::from_str(s).c(d!())
+    }
+
+    /// Merge the signatures attached by another party to the same transfer,
+    /// the bodies of both sides must have the same hash.
+    pub fn merge_signatures(&mut self, other: &Self) -> Result<&mut Self> {
+        if self.body_hash().c(d!())? != other.body_hash().c(d!())? {
+            return Err(eg!("transfer body mismatch"));
+        }
+
+        let trn = self.transfer.as_mut().c(d!(no_transfer_err!()))?;
+        for sig in other
+            .transfer
+            .as_ref()
+            .c(d!(no_transfer_err!()))?
+            .body_signatures
+            .iter()
+        {
+            if !trn.body_signatures.contains(sig) {
+                trn.attach_signature(sig.clone())
+                    .c(d!("invalid signature"))?;
+            }
+        }
+        Ok(self)
+    }
+
     /// Checks to see whether all necessary signatures are present and valid
     pub fn validate_signatures(&mut self) -> Result<&mut Self> {
         if self.transfer.is_none() {
@@ -1596,8 +2519,121 @@ impl TransferOperationBuilder {
                 return Err(eg!(("Not all signatures present")));
             }
         }
+        trn.body.check_cosignatures(&trn.body_signatures).c(d!())?;
+        Ok(self)
+    }
+}
+
+/// SwapBuilder constructs an atomic swap between the two parties of `legs`,
+/// one of them creates and signs the swap, then hands it to the other one to
+/// check the terms, sign and submit it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SwapBuilder {
+    legs: [SwapLeg; 2],
+    builder: TransferOperationBuilder,
+}
+
+impl SwapBuilder {
+    #[allow(missing_docs)]
+    pub fn new(legs: [SwapLeg; 2]) -> Self {
+        Self {
+            legs,
+            builder: TransferOperationBuilder::new(),
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn legs(&self) -> &[SwapLeg; 2] {
+        &self.legs
+    }
+
+    /// Spend `amount` of a non-confidential record of one of the parties,
+    /// the rest of the record is refunded to its owner.
+    pub fn add_input(
+        &mut self,
+        txo_sid: TxoRef,
+        open_ar: OpenAssetRecord,
+        amount: u64,
+    ) -> Result<&mut Self> {
+        let owner = *open_ar.get_pub_key();
+        if !self.legs.iter().any(|l| l.party.into_noah() == owner) {
+            return Err(eg!("not a record of the parties"));
+        }
+        self.builder
+            .add_input(txo_sid, open_ar, None, None, amount)
+            .c(d!())?;
+        Ok(self)
+    }
+
+    /// Add the outputs of both legs and finalize the swap,
+    /// the inputs must cover what each party gives.
+    pub fn create(&mut self) -> Result<&mut Self> {
+        for (giver, leg) in self.legs.iter().enumerate() {
+            let template = AssetRecordTemplate::with_no_asset_tracing(
+                leg.amount,
+                leg.asset_type.val,
+                AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                self.legs[1 - giver].party.into_noah(),
+            );
+            self.builder
+                .add_output(&template, None, None, None)
+                .c(d!())?;
+        }
+        self.builder.create(TransferType::Standard).c(d!())?;
+        self.swap().c(d!())?.check_terms().c(d!())?;
+        Ok(self)
+    }
+
+    /// Sign the swap as one of its parties, after checking its terms
+    pub fn sign(&mut self, kp: &XfrKeyPair) -> Result<&mut Self> {
+        if !self.legs.iter().any(|l| l.party == kp.get_pk()) {
+            return Err(eg!("not a party of the swap"));
+        }
+        self.swap().c(d!())?.check_terms().c(d!())?;
+        self.builder.sign(kp).c(d!())?;
+        Ok(self)
+    }
+
+    /// Merge the signature of the counterparty to the same swap
+    pub fn merge_signatures(&mut self, other: &Self) -> Result<&mut Self> {
+        if self.legs != other.legs {
+            return Err(eg!("swap terms mismatch"));
+        }
+        self.builder.merge_signatures(&other.builder).c(d!())?;
+        Ok(self)
+    }
+
+    /// Checks that both parties have signed
+    pub fn validate_signatures(&mut self) -> Result<&mut Self> {
+        self.builder.validate_signatures().c(d!())?;
         Ok(self)
     }
+
+    #[allow(missing_docs)]
+    pub fn swap(&self) -> Result<SwapAsset> {
+        let transfer = self.builder.transfer.clone().c(d!(no_transfer_err!()))?;
+        Ok(SwapAsset {
+            legs: self.legs.clone(),
+            transfer,
+        })
+    }
+
+    /// Return the swap operation
+    pub fn transaction(&self) -> Result<Operation> {
+        self.swap()
+            .c(d!())
+            .map(|s| Operation::SwapAsset(Box::new(s)))
+    }
+
+    /// Serialize the half-signed swap to hand it to the counterparty
+    pub fn serialize_str(&self) -> Result<String> {
+        serde_json::to_string(self).c(d!())
+    }
+
+    /// Restore a swap serialized by `serialize_str`
+    pub fn deserialize_str(s: &str) -> Result<Self> {
+        serde_json::from_str(s).c(d!())
+    }
 }
 
 /// AnonTransferOperationBuilder builders anon transfer operation using the factory pattern.
@@ -2015,6 +3051,7 @@ mod tests {
         super::*,
         ledger::{
             data_model::{ATxoSID, BlockEffect, TxnEffect, TxoRef},
+            staking::features::{Feature, FeatureSet},
             store::utils::fra_gen_initial_tx,
             store::LedgerState,
         },
@@ -2029,7 +3066,7 @@ mod tests {
                         build_blind_asset_record, open_blind_asset_record,
                         AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
                     },
-                    structs::AssetType as AT,
+                    structs::{AssetTracerKeyPair, AssetType as AT},
                 },
             },
         },
@@ -2245,33 +3282,776 @@ mod tests {
     }
 
     #[test]
-    fn test_check_fee_with_ledger() {
-        let mut ledger = LedgerState::tmp_ledger();
-        let fra_owner_kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
-        let bob_kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
-        assert_eq!(
-            bob_kp.get_sk().into_keypair().noah_to_bytes(),
-            bob_kp.noah_to_bytes()
-        );
+    fn test_transfer_op_builder_merge_signatures() {
+        let mut prng = ChaChaRng::from_entropy();
+        let pc_gens = PedersenCommitmentRistretto::default();
+        let code = AssetTypeCode::gen_random();
+        let alice = XfrKeyPair::generate(&mut prng);
+        let bob = XfrKeyPair::generate(&mut prng);
+        let charlie = XfrKeyPair::generate(&mut prng);
 
-        let mut tx = fra_gen_initial_tx(&fra_owner_kp);
-        assert!(tx.check_fee());
+        let mut gen_input = |kp: &XfrKeyPair| {
+            let ar = AssetRecordTemplate::with_no_asset_tracing(
+                100,
+                code.val,
+                NonConfidentialAmount_NonConfidentialAssetType,
+                kp.get_pk().into_noah(),
+            );
+            let (ba, _, memo) =
+                build_blind_asset_record(&mut prng, &pc_gens, &ar, vec![]);
+            open_blind_asset_record(&ba, &memo, &kp.into_noah()).unwrap()
+        };
+        let oar_alice = gen_input(&alice);
+        let oar_bob = gen_input(&bob);
+        let output = AssetRecordTemplate::with_no_asset_tracing(
+            200,
+            code.val,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            charlie.get_pk().into_noah(),
+        );
 
-        let effect = TxnEffect::compute_effect(tx.clone()).unwrap();
-        let mut block = ledger.start_block().unwrap();
-        let tmp_sid = ledger.apply_transaction(&mut block, effect).unwrap();
-        let txo_sid = ledger
-            .finish_block(block)
+        let mut op = TransferOperationBuilder::new();
+        op.add_input(TxoRef::Relative(0), oar_alice.clone(), None, None, 100)
             .unwrap()
-            .remove(&tmp_sid)
+            .add_input(TxoRef::Relative(1), oar_bob, None, None, 100)
             .unwrap()
-            .1[0];
+            .add_output(&output, None, None, None)
+            .unwrap()
+            .create(TransferType::Standard)
+            .unwrap();
 
-        macro_rules! transfer_to_bob {
-            ($txo_sid: expr, $bob_pk: expr) => {{
-                let output_bob_fra_template = AssetRecordTemplate::with_no_asset_tracing(
-                    100 * TX_FEE_MIN,
-                    ASSET_TYPE_FRA,
+        // bob signs his copy offline
+        let mut op_bob =
+            TransferOperationBuilder::deserialize_str(&op.serialize_str().unwrap())
+                .unwrap();
+        assert_eq!(op.body_hash().unwrap(), op_bob.body_hash().unwrap());
+        op_bob.sign(&bob).unwrap();
+
+        op.sign(&alice).unwrap();
+        assert!(op.validate_signatures().is_err());
+        op.merge_signatures(&op_bob).unwrap();
+        op.merge_signatures(&op_bob).unwrap();
+        assert_eq!(op.transfer.as_ref().unwrap().body_signatures.len(), 2);
+        assert!(op.validate_signatures().is_ok());
+
+        // signatures of another transfer can not be merged
+        let mut other = TransferOperationBuilder::new();
+        other
+            .add_input(TxoRef::Relative(0), oar_alice, None, None, 50)
+            .unwrap()
+            .add_output(
+                &AssetRecordTemplate::with_no_asset_tracing(
+                    50,
+                    code.val,
+                    NonConfidentialAmount_NonConfidentialAssetType,
+                    charlie.get_pk().into_noah(),
+                ),
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+            .create(TransferType::Standard)
+            .unwrap()
+            .sign(&alice)
+            .unwrap();
+        assert!(op.merge_signatures(&other).is_err());
+    }
+
+    #[test]
+    fn test_transfer_op_builder_cosignatures() {
+        let mut prng = ChaChaRng::from_entropy();
+        let pc_gens = PedersenCommitmentRistretto::default();
+        let code = AssetTypeCode::gen_random();
+        let alice = XfrKeyPair::generate(&mut prng);
+        let bob = XfrKeyPair::generate(&mut prng);
+        let escrows = (0..3)
+            .map(|_| XfrKeyPair::generate(&mut prng))
+            .collect::<Vec<_>>();
+        let rules = SignatureRules {
+            threshold: 2,
+            weights: escrows.iter().map(|kp| (kp.get_pk(), 1)).collect(),
+        };
+
+        let ar = AssetRecordTemplate::with_no_asset_tracing(
+            100,
+            code.val,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            alice.get_pk().into_noah(),
+        );
+        let (ba, _, memo) = build_blind_asset_record(&mut prng, &pc_gens, &ar, vec![]);
+        let oar = open_blind_asset_record(&ba, &memo, &alice.into_noah()).unwrap();
+        let output = AssetRecordTemplate::with_no_asset_tracing(
+            100,
+            code.val,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            bob.get_pk().into_noah(),
+        );
+
+        // an output locked by rules which can never be satisfied is rejected
+        let mut op = TransferOperationBuilder::new();
+        op.add_input(TxoRef::Relative(0), oar.clone(), None, None, 100)
+            .unwrap()
+            .add_output(&output, None, None, None)
+            .unwrap();
+        let impossible = SignatureRules {
+            threshold: 3,
+            ..rules.clone()
+        };
+        assert!(op.set_output_cosig_rules(0, impossible).is_err());
+        op.set_output_cosig_rules(0, rules.clone())
+            .unwrap()
+            .create(TransferType::Standard)
+            .unwrap();
+        assert_eq!(
+            op.transfer.as_ref().unwrap().body.outputs[0].cosig_rules,
+            Some(rules.clone())
+        );
+        assert!(op.cosign(&escrows[0], 0).is_err());
+
+        // spending an input locked by 2-of-3 escrow keys
+        let mut op = TransferOperationBuilder::new();
+        op.add_input(TxoRef::Relative(0), oar, None, None, 100)
+            .unwrap()
+            .add_output(&output, None, None, None)
+            .unwrap()
+            .set_input_cosig_rules(0, rules)
+            .unwrap()
+            .create(TransferType::Standard)
+            .unwrap()
+            .sign(&alice)
+            .unwrap();
+        assert!(op.validate_signatures().is_err());
+
+        op.cosign(&escrows[0], 0).unwrap();
+        assert!(op.validate_signatures().is_err());
+
+        // a co-signature of a wrong key does not count
+        op.cosign(&bob, 0).unwrap();
+        assert!(op.validate_signatures().is_err());
+        assert!(op.cosign(&escrows[1], 1).is_err());
+
+        let sig = op.create_cosignature(&escrows[2], 0).unwrap();
+        op.attach_signature(sig).unwrap();
+        assert!(op.validate_signatures().is_ok());
+
+        // the rules are rejected before the feature is active
+        let mut tx = TransactionBuilder::from_seq_id(0);
+        tx.add_operation(op.transaction().unwrap());
+        let err =
+            TxnEffect::compute_effect(tx.into_transaction(), &FeatureSet::default())
+                .unwrap_err();
+        assert!(err.to_string().contains("CoSignature"));
+    }
+
+    #[test]
+    fn test_builder_with_rng() {
+        let tx_1 = TransactionBuilder::from_seq_id_with_rng(
+            1,
+            &mut ChaChaRng::from_seed([7u8; 32]),
+        );
+        let tx_2 = TransactionBuilder::from_seq_id_with_rng(
+            1,
+            &mut ChaChaRng::from_seed([7u8; 32]),
+        );
+        assert_eq!(tx_1.no_replay_token, tx_2.no_replay_token);
+
+        let mut prng = ChaChaRng::from_seed([9u8; 32]);
+        let alice = XfrKeyPair::generate(&mut prng);
+        let code = AssetTypeCode::gen_random();
+        let mut tx = TransactionBuilder::from_seq_id_with_rng(1, &mut prng);
+        pnk!(tx.add_basic_issue_asset_with_rng(
+            &alice,
+            &code,
+            0,
+            1000,
+            AssetRecordType::ConfidentialAmount_ConfidentialAssetType,
+            &mut prng,
+        ));
+        assert_eq!(tx.get_relative_outputs().len(), 1);
+
+        // the remainder and the fee records are drawn from the same RNG
+        let ar = AssetRecordTemplate::with_no_asset_tracing(
+            100 * TX_FEE_MIN,
+            ASSET_TYPE_FRA,
+            AssetRecordType::ConfidentialAmount_NonConfidentialAssetType,
+            alice.get_pk().into_noah(),
+        );
+        let (ba, _, memo) = build_blind_asset_record(
+            &mut prng,
+            &PedersenCommitmentRistretto::default(),
+            &ar,
+            vec![],
+        );
+        let oar = open_blind_asset_record(&ba, &memo, &alice.into_noah()).unwrap();
+        let output = AssetRecordTemplate::with_no_asset_tracing(
+            10 * TX_FEE_MIN,
+            ASSET_TYPE_FRA,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            alice.get_pk().into_noah(),
+        );
+        let transfer = |seed: u8| {
+            let mut prng = ChaChaRng::from_seed([seed; 32]);
+            let mut op = TransferOperationBuilder::new();
+            op.add_input(
+                TxoRef::Relative(0),
+                oar.clone(),
+                None,
+                None,
+                10 * TX_FEE_MIN,
+            )
+            .unwrap()
+            .add_output_and_store_blinds(
+                &output,
+                None,
+                &mut prng,
+                &mut Default::default(),
+            )
+            .unwrap()
+            .add_fee(TX_FEE_MIN, *BLACK_HOLE_PUBKEY)
+            .unwrap()
+            .create_with_rng(TransferType::Standard, &mut prng)
+            .unwrap();
+            // output, confidential remainder, fee
+            assert_eq!(op.output_records.len(), 3);
+            serde_json::to_string(&op.transaction().unwrap()).unwrap()
+        };
+        assert_eq!(transfer(3), transfer(3));
+        assert_ne!(transfer(3), transfer(4));
+    }
+
+    #[test]
+    fn test_traced_issue_asset() {
+        let mut prng = ChaChaRng::from_seed([9u8; 32]);
+        let alice = XfrKeyPair::generate(&mut prng);
+        let tracer = AssetTracerKeyPair::generate(&mut prng);
+        let code = AssetTypeCode::gen_random();
+        let mut policies = TracingPolicies::new();
+        policies.add(TracingPolicy {
+            enc_keys: tracer.enc_key.clone(),
+            asset_tracing: true,
+            identity_tracing: None,
+        });
+
+        let mut tx = TransactionBuilder::from_seq_id_with_rng(1, &mut prng);
+        let memos = pnk!(tx.add_traced_issue_asset_with_rng(
+            &alice,
+            &code,
+            0,
+            1000,
+            AssetRecordType::ConfidentialAmount_NonConfidentialAssetType,
+            &policies,
+            &mut prng,
+        ));
+        assert_eq!(memos.len(), 1);
+
+        let memos = pnk!(tx.add_traced_issue_asset_with_rng(
+            &alice,
+            &code,
+            1,
+            1000,
+            AssetRecordType::ConfidentialAmount_NonConfidentialAssetType,
+            &TracingPolicies::new(),
+            &mut prng,
+        ));
+        assert!(memos.is_empty());
+        assert_eq!(tx.get_relative_outputs().len(), 2);
+    }
+
+    // signs through `sign_bytes` only, like a device holding the secret key
+    struct ExternalSigner {
+        pk: XfrPublicKey,
+        kp: XfrKeyPair,
+    }
+
+    impl Signer for ExternalSigner {
+        fn public_key(&self) -> XfrPublicKey {
+            self.pk
+        }
+
+        fn sign_bytes(&self, msg: &[u8]) -> Result<XfrSignature> {
+            self.kp.sign(msg).c(d!())
+        }
+    }
+
+    #[test]
+    fn test_external_signer() {
+        let mut prng = ChaChaRng::from_seed([7u8; 32]);
+        let kp = XfrKeyPair::generate(&mut prng);
+        let signer = ExternalSigner {
+            pk: kp.get_pk(),
+            kp: kp.clone(),
+        };
+        let code = AssetTypeCode::gen_random();
+
+        let mut by_key = TransactionBuilder::from_seq_id_with_rng(1, &mut prng);
+        pnk!(by_key.add_operation_create_asset(
+            &kp,
+            Some(code),
+            AssetRules::default(),
+            "test"
+        ));
+        let mut by_signer = TransactionBuilder::from_seq_id_with_rng(1, &mut prng);
+        pnk!(by_signer.add_operation_create_asset_by_signer(
+            &signer,
+            Some(code),
+            AssetRules::default(),
+            "test"
+        ));
+        match (
+            &by_key.txn.body.operations[0],
+            &by_signer.txn.body.operations[0],
+        ) {
+            (Operation::DefineAsset(a), Operation::DefineAsset(b)) => {
+                assert_eq!(a.body, b.body);
+                pnk!(b.signature.verify(&kp.get_pk(), &b.body));
+            }
+            _ => panic!("unexpected operations"),
+        }
+
+        pnk!(by_signer.sign_by_signer(&signer));
+        assert_eq!(by_signer.txn.signatures.len(), 1);
+
+        // a signature that does not match the public key is refused
+        let wrong = ExternalSigner {
+            pk: kp.get_pk(),
+            kp: XfrKeyPair::generate(&mut prng),
+        };
+        assert!(by_signer.sign_by_signer(&wrong).is_err());
+    }
+
+    // a device which does not sign, e.g. unplugged or declined by its user
+    struct DecliningSigner(XfrPublicKey);
+
+    impl Signer for DecliningSigner {
+        fn public_key(&self) -> XfrPublicKey {
+            self.0
+        }
+
+        fn sign_bytes(&self, _: &[u8]) -> Result<XfrSignature> {
+            Err(eg!("declined"))
+        }
+    }
+
+    #[test]
+    fn test_signer_failures() {
+        let mut prng = ChaChaRng::from_seed([8u8; 32]);
+        let kp = XfrKeyPair::generate(&mut prng);
+        let code = AssetTypeCode::gen_random();
+        let signer = ExternalSigner {
+            pk: kp.get_pk(),
+            kp: kp.clone(),
+        };
+        let declined = DecliningSigner(kp.get_pk());
+        let wrong = ExternalSigner {
+            pk: kp.get_pk(),
+            kp: XfrKeyPair::generate(&mut prng),
+        };
+
+        // nothing is added to the transaction by a failed signing
+        let mut builder = TransactionBuilder::from_seq_id_with_rng(1, &mut prng);
+        for res in [
+            builder
+                .add_operation_create_asset_by_signer(
+                    &declined,
+                    Some(code),
+                    AssetRules::default(),
+                    "test",
+                )
+                .map(|_| ()),
+            builder
+                .add_operation_create_asset_by_signer(
+                    &wrong,
+                    Some(code),
+                    AssetRules::default(),
+                    "test",
+                )
+                .map(|_| ()),
+            builder
+                .add_operation_issue_asset_by_signer(&declined, &code, 0, &[])
+                .map(|_| ()),
+            builder
+                .add_operation_issue_asset_by_signer(&wrong, &code, 0, &[])
+                .map(|_| ()),
+            builder.sign_by_signer(&declined).map(|_| ()),
+        ] {
+            assert!(res.is_err());
+        }
+        assert!(builder.txn.body.operations.is_empty());
+        assert!(builder.txn.signatures.is_empty());
+
+        // the same calls succeed with a working signer
+        pnk!(builder.add_operation_issue_asset_by_signer(&signer, &code, 0, &[]));
+        pnk!(builder.sign_by_signer(&signer));
+        assert_eq!(builder.txn.body.operations.len(), 1);
+
+        // no transfer to sign before `create`
+        assert!(TransferOperationBuilder::new()
+            .sign_by_signer(&signer)
+            .is_err());
+    }
+
+    #[test]
+    fn test_swap_builder() {
+        let mut prng = ChaChaRng::from_entropy();
+        let pc_gens = PedersenCommitmentRistretto::default();
+        let code_a = AssetTypeCode::gen_random();
+        let code_b = AssetTypeCode::gen_random();
+        let alice = XfrKeyPair::generate(&mut prng);
+        let bob = XfrKeyPair::generate(&mut prng);
+        let charlie = XfrKeyPair::generate(&mut prng);
+
+        let mut gen_input = |kp: &XfrKeyPair, code: &AssetTypeCode| {
+            let ar = AssetRecordTemplate::with_no_asset_tracing(
+                100,
+                code.val,
+                NonConfidentialAmount_NonConfidentialAssetType,
+                kp.get_pk().into_noah(),
+            );
+            let (ba, _, memo) =
+                build_blind_asset_record(&mut prng, &pc_gens, &ar, vec![]);
+            open_blind_asset_record(&ba, &memo, &kp.into_noah()).unwrap()
+        };
+        let oar_alice = gen_input(&alice, &code_a);
+        let oar_bob = gen_input(&bob, &code_b);
+        let oar_charlie = gen_input(&charlie, &code_a);
+        let legs = [
+            SwapLeg {
+                party: alice.get_pk(),
+                asset_type: code_a,
+                amount: 30,
+            },
+            SwapLeg {
+                party: bob.get_pk(),
+                asset_type: code_b,
+                amount: 40,
+            },
+        ];
+
+        let mut swap = SwapBuilder::new(legs.clone());
+        assert!(swap
+            .add_input(TxoRef::Relative(2), oar_charlie, 30)
+            .is_err());
+        swap.add_input(TxoRef::Relative(0), oar_alice.clone(), 30)
+            .unwrap()
+            .add_input(TxoRef::Relative(1), oar_bob, 40)
+            .unwrap()
+            .create()
+            .unwrap()
+            .sign(&alice)
+            .unwrap();
+        assert!(swap.sign(&charlie).is_err());
+        assert!(swap.validate_signatures().is_err());
+
+        // bob checks the terms and signs his copy
+        let mut swap_bob =
+            SwapBuilder::deserialize_str(&swap.serialize_str().unwrap()).unwrap();
+        assert_eq!(swap_bob.legs(), &legs);
+        swap_bob.sign(&bob).unwrap();
+
+        swap.merge_signatures(&swap_bob).unwrap();
+        assert!(swap.validate_signatures().is_ok());
+        assert!(matches!(
+            swap.transaction().unwrap(),
+            Operation::SwapAsset(_)
+        ));
+
+        // the transfer must match the legs
+        let mut s = swap.swap().unwrap();
+        s.legs[1].amount = 39;
+        assert!(s.check_terms().is_err());
+
+        // bob gives nothing
+        let mut swap = SwapBuilder::new(legs);
+        swap.add_input(TxoRef::Relative(0), oar_alice, 30).unwrap();
+        assert!(swap.create().is_err());
+    }
+
+    #[test]
+    fn test_transfer_op_builder_multi_asset_balance() {
+        let mut prng = ChaChaRng::from_entropy();
+        let pc_gens = PedersenCommitmentRistretto::default();
+        let code_1 = AssetTypeCode::gen_random();
+        let code_2 = AssetTypeCode::gen_random();
+        let alice = XfrKeyPair::generate(&mut prng);
+        let bob = XfrKeyPair::generate(&mut prng);
+
+        let mut gen_input = |code: &AssetTypeCode| {
+            let ar = AssetRecordTemplate::with_no_asset_tracing(
+                1000,
+                code.val,
+                NonConfidentialAmount_NonConfidentialAssetType,
+                alice.get_pk().into_noah(),
+            );
+            let (ba, _, memo) =
+                build_blind_asset_record(&mut prng, &pc_gens, &ar, vec![]);
+            open_blind_asset_record(&ba, &memo, &alice.into_noah()).unwrap()
+        };
+        let oar_1 = gen_input(&code_1);
+        let oar_2 = gen_input(&code_2);
+        let output = |am: u64, code: &AssetTypeCode| {
+            AssetRecordTemplate::with_no_asset_tracing(
+                am,
+                code.val,
+                NonConfidentialAmount_NonConfidentialAssetType,
+                bob.get_pk().into_noah(),
+            )
+        };
+
+        // The totals are equal, but each asset is imbalanced
+        for auto_refund in [true, false] {
+            let spend = alt!(auto_refund, 20, 1000);
+            let res = TransferOperationBuilder::new()
+                .auto_refund(auto_refund)
+                .add_input(TxoRef::Relative(0), oar_1.clone(), None, None, spend)
+                .unwrap()
+                .add_input(TxoRef::Relative(1), oar_2.clone(), None, None, spend)
+                .unwrap()
+                .add_output(&output(spend + 10, &code_1), None, None, None)
+                .unwrap()
+                .add_output(&output(spend - 10, &code_2), None, None, None)
+                .unwrap()
+                .create(TransferType::Standard)
+                .map(|_| ());
+            assert!(res.is_err());
+        }
+
+        // Remainders are generated for each asset type
+        let mut op = TransferOperationBuilder::new();
+        op.add_input(TxoRef::Relative(0), oar_1, None, None, 20)
+            .unwrap()
+            .add_input(TxoRef::Relative(1), oar_2, None, None, 30)
+            .unwrap()
+            .add_output(&output(20, &code_1), None, None, None)
+            .unwrap()
+            .add_output(&output(30, &code_2), None, None, None)
+            .unwrap()
+            .balance(None)
+            .unwrap();
+        assert_eq!(op.output_records.len(), 4);
+        assert_eq!(op.output_records.len(), op.outputs_tracing_policies.len());
+        let remainders = op.output_records[2..]
+            .iter()
+            .map(|ar| (ar.open_asset_record.asset_type, ar.open_asset_record.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(remainders, vec![(code_1.val, 980), (code_2.val, 970)]);
+
+        op.create(TransferType::Standard)
+            .unwrap()
+            .sign(&alice)
+            .unwrap()
+            .transaction()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_transfer_op_builder_add_fee() {
+        let mut prng = ChaChaRng::from_entropy();
+        let pc_gens = PedersenCommitmentRistretto::default();
+        let alice = XfrKeyPair::generate(&mut prng);
+        let bob = XfrKeyPair::generate(&mut prng);
+
+        let ar = AssetRecordTemplate::with_no_asset_tracing(
+            100 * TX_FEE_MIN,
+            ASSET_TYPE_FRA,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            alice.get_pk().into_noah(),
+        );
+        let (ba, _, memo) = build_blind_asset_record(&mut prng, &pc_gens, &ar, vec![]);
+        let oar = open_blind_asset_record(&ba, &memo, &alice.into_noah()).unwrap();
+        let output_template = AssetRecordTemplate::with_no_asset_tracing(
+            10 * TX_FEE_MIN,
+            ASSET_TYPE_FRA,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            bob.get_pk().into_noah(),
+        );
+
+        let oar_no_refund = oar.clone();
+
+        // The fee can not exceed the unspent part of inputs
+        let res = TransferOperationBuilder::new()
+            .add_input(
+                TxoRef::Relative(0),
+                oar.clone(),
+                None,
+                None,
+                10 * TX_FEE_MIN,
+            )
+            .unwrap()
+            .add_output(&output_template, None, None, None)
+            .unwrap()
+            .add_fee(91 * TX_FEE_MIN, *BLACK_HOLE_PUBKEY)
+            .unwrap()
+            .balance(None)
+            .map(|_| ());
+        assert!(res.is_err());
+
+        let mut op = TransferOperationBuilder::new();
+        op.add_input(TxoRef::Relative(0), oar, None, None, 10 * TX_FEE_MIN)
+            .unwrap()
+            .add_output(&output_template, None, None, None)
+            .unwrap()
+            .add_fee(TX_FEE_MIN, *BLACK_HOLE_PUBKEY)
+            .unwrap();
+        assert!(op.add_fee(TX_FEE_MIN, *BLACK_HOLE_PUBKEY).is_err());
+
+        // output to bob, refund to alice, fee
+        op.balance(None).unwrap().balance(None).unwrap();
+        assert_eq!(op.output_records.len(), 3);
+        assert_eq!(
+            op.output_records[1].open_asset_record.amount,
+            89 * TX_FEE_MIN
+        );
+        assert_eq!(op.output_records[2].open_asset_record.amount, TX_FEE_MIN);
+
+        let op = op
+            .create(TransferType::Standard)
+            .unwrap()
+            .sign(&alice)
+            .unwrap()
+            .transaction()
+            .unwrap();
+        let mut tx = TransactionBuilder::from_seq_id(1);
+        tx.add_operation(op);
+        assert!(tx.check_fee());
+
+        // without auto_refund, the fee is never dropped silently,
+        // the outputs would balance the inputs without it
+        let change = AssetRecordTemplate::with_no_asset_tracing(
+            90 * TX_FEE_MIN,
+            ASSET_TYPE_FRA,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            alice.get_pk().into_noah(),
+        );
+        let new_op = |spend: u64| {
+            let mut op = TransferOperationBuilder::new();
+            op.auto_refund(false)
+                .add_input(
+                    TxoRef::Relative(0),
+                    oar_no_refund.clone(),
+                    None,
+                    None,
+                    spend,
+                )
+                .unwrap()
+                .add_output(&output_template, None, None, None)
+                .unwrap()
+                .add_fee(TX_FEE_MIN, *BLACK_HOLE_PUBKEY)
+                .unwrap();
+            op
+        };
+        let mut op = new_op(100 * TX_FEE_MIN);
+        op.add_output(&change, None, None, None).unwrap();
+        assert!(op.create(TransferType::Standard).is_err());
+        assert!(op.transaction().is_err());
+        assert_eq!(op.output_records.len(), 2);
+
+        // unless it is balanced before
+        let mut op = new_op(10 * TX_FEE_MIN);
+        op.balance(None).unwrap();
+        let op = op
+            .create(TransferType::Standard)
+            .unwrap()
+            .sign(&alice)
+            .unwrap()
+            .transaction()
+            .unwrap();
+        let mut tx = TransactionBuilder::from_seq_id(1);
+        tx.add_operation(op);
+        assert!(tx.check_fee());
+    }
+
+    #[test]
+    fn test_transfer_op_builder_consolidate() {
+        let mut prng = ChaChaRng::from_entropy();
+        let pc_gens = PedersenCommitmentRistretto::default();
+        let alice = XfrKeyPair::generate(&mut prng);
+
+        let inputs = (0..5)
+            .map(|i| {
+                let ar = AssetRecordTemplate::with_no_asset_tracing(
+                    10 * TX_FEE_MIN,
+                    ASSET_TYPE_FRA,
+                    NonConfidentialAmount_NonConfidentialAssetType,
+                    alice.get_pk().into_noah(),
+                );
+                let (ba, _, memo) =
+                    build_blind_asset_record(&mut prng, &pc_gens, &ar, vec![]);
+                let oar =
+                    open_blind_asset_record(&ba, &memo, &alice.into_noah()).unwrap();
+                (TxoRef::Absolute(TxoSID(i)), oar)
+            })
+            .collect::<Vec<_>>();
+        let rt = NonConfidentialAmount_NonConfidentialAssetType;
+
+        let (op, n) =
+            TransferOperationBuilder::consolidate(&inputs, rt, TX_FEE_MIN, usize::MAX)
+                .unwrap();
+        assert_eq!(5, n);
+        assert_eq!(op.output_records.len(), 2);
+        assert_eq!(
+            op.output_records[0].open_asset_record.amount,
+            49 * TX_FEE_MIN
+        );
+
+        // limited to the size of a transfer of 3 inputs
+        let (op, _) = TransferOperationBuilder::consolidate(
+            &inputs[..3],
+            rt,
+            TX_FEE_MIN,
+            usize::MAX,
+        )
+        .unwrap();
+        let size = serde_json::to_vec(&op.transaction().unwrap())
+            .unwrap()
+            .len();
+        let (_, n) =
+            TransferOperationBuilder::consolidate(&inputs, rt, TX_FEE_MIN, size)
+                .unwrap();
+        assert_eq!(3, n);
+
+        // nothing fits
+        assert!(
+            TransferOperationBuilder::consolidate(&inputs, rt, TX_FEE_MIN, 10).is_err()
+        );
+        // not enough for the fee
+        assert!(TransferOperationBuilder::consolidate(
+            &inputs[..1],
+            rt,
+            10 * TX_FEE_MIN,
+            usize::MAX
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_check_fee_with_ledger() {
+        let mut ledger = LedgerState::tmp_ledger();
+        let fra_owner_kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+        let bob_kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+        assert_eq!(
+            bob_kp.get_sk().into_keypair().noah_to_bytes(),
+            bob_kp.noah_to_bytes()
+        );
+
+        let mut tx = fra_gen_initial_tx(&fra_owner_kp);
+        assert!(tx.check_fee());
+
+        let effect = TxnEffect::compute_effect(tx.clone(), &FeatureSet::all()).unwrap();
+        let mut block = ledger.start_block().unwrap();
+        let tmp_sid = ledger.apply_transaction(&mut block, effect).unwrap();
+        let txo_sid = ledger
+            .finish_block(block)
+            .unwrap()
+            .remove(&tmp_sid)
+            .unwrap()
+            .1[0];
+
+        macro_rules! transfer_to_bob {
+            ($txo_sid: expr, $bob_pk: expr) => {{
+                let output_bob_fra_template = AssetRecordTemplate::with_no_asset_tracing(
+                    100 * TX_FEE_MIN,
+                    ASSET_TYPE_FRA,
                     NonConfidentialAmount_NonConfidentialAssetType,
                     $bob_pk,
                 );
@@ -2314,7 +4094,9 @@ mod tests {
             .unwrap();
         assert!(tx2.check_fee());
 
-        let effect = TxnEffect::compute_effect(tx2.into_transaction()).unwrap();
+        let effect =
+            TxnEffect::compute_effect(tx2.into_transaction(), &FeatureSet::all())
+                .unwrap();
         let mut block = ledger.start_block().unwrap();
         let tmp_sid = ledger.apply_transaction(&mut block, effect).unwrap();
         // txo_sid[0]: fra_owner to bob
@@ -2343,7 +4125,9 @@ mod tests {
             .add_fee(fi));
         assert!(tx3.check_fee());
 
-        let effect = TxnEffect::compute_effect(tx3.into_transaction()).unwrap();
+        let effect =
+            TxnEffect::compute_effect(tx3.into_transaction(), &FeatureSet::all())
+                .unwrap();
         let mut block = ledger.start_block().unwrap();
         let tmp_sid = ledger.apply_transaction(&mut block, effect).unwrap();
         // txo_sid[0]: fra_owner to bob
@@ -2373,7 +4157,9 @@ mod tests {
             .unwrap();
         assert!(tx4.check_fee());
 
-        let effect = TxnEffect::compute_effect(tx4.into_transaction()).unwrap();
+        let effect =
+            TxnEffect::compute_effect(tx4.into_transaction(), &FeatureSet::all())
+                .unwrap();
         let mut block = ledger.start_block().unwrap();
         ledger.apply_transaction(&mut block, effect).unwrap();
         ledger.finish_block(block).unwrap();
@@ -2381,11 +4167,83 @@ mod tests {
         // Ensure that FRA can only be defined only once.
         tx.body.no_replay_token =
             NoReplayToken::new(&mut ChaChaRng::from_entropy(), 100);
-        let effect = TxnEffect::compute_effect(tx).unwrap();
+        let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
         let mut block = ledger.start_block().unwrap();
         assert!(ledger.apply_transaction(&mut block, effect).is_err());
     }
 
+    #[test]
+    fn test_time_locked_outputs() {
+        let mut ledger = LedgerState::tmp_ledger();
+        let fra_owner_kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+        let bob_kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+
+        // one block per transaction, `None` if rejected
+        let apply = |ledger: &mut LedgerState, tx: Transaction| {
+            let res = ledger.apply_block(vec![tx]).unwrap().pop().unwrap();
+            res.map(|(_, sids)| sids)
+        };
+        let transfer = |ledger: &LedgerState,
+                        sid: TxoSID,
+                        from: &XfrKeyPair,
+                        to: &XfrKeyPair,
+                        lock_until: u64| {
+            let record = ledger.get_utxo_light(sid).unwrap().utxo.0.record;
+            let oar =
+                open_blind_asset_record(&record.into_noah(), &None, &from.into_noah())
+                    .unwrap();
+            let output = AssetRecordTemplate::with_no_asset_tracing(
+                100 * TX_FEE_MIN,
+                ASSET_TYPE_FRA,
+                NonConfidentialAmount_NonConfidentialAssetType,
+                to.get_pk().into_noah(),
+            );
+            let op = TransferOperationBuilder::new()
+                .add_input(TxoRef::Absolute(sid), oar, None, None, 100 * TX_FEE_MIN)
+                .unwrap()
+                .add_output(&output, None, None, None)
+                .unwrap()
+                .set_output_lock_until(0, lock_until)
+                .unwrap()
+                .create(TransferType::Standard)
+                .unwrap()
+                .sign(from)
+                .unwrap()
+                .transaction()
+                .unwrap();
+            let mut tx =
+                TransactionBuilder::from_seq_id(ledger.get_block_commit_count());
+            tx.add_operation(op);
+            tx.into_transaction()
+        };
+
+        let sid = apply(&mut ledger, fra_gen_initial_tx(&fra_owner_kp)).unwrap()[0];
+
+        // locked until two more blocks are committed
+        let lock_until = ledger.get_block_commit_count() + 2;
+        let tx = transfer(&ledger, sid, &fra_owner_kp, &bob_kp, lock_until);
+        if !ledger
+            .get_staking()
+            .active_features()
+            .is_active(Feature::TimeLock)
+        {
+            assert!(apply(&mut ledger, tx).is_none());
+            return;
+        }
+        let sid = apply(&mut ledger, tx).unwrap()[0];
+        let utxo = ledger.get_utxo_light(sid).unwrap().utxo;
+        assert_eq!(utxo.0.lock_until, lock_until);
+        assert!(utxo.0.is_locked(ledger.get_block_commit_count()));
+
+        let tx = transfer(&ledger, sid, &bob_kp, &fra_owner_kp, 0);
+        assert!(apply(&mut ledger, tx).is_none());
+
+        // the empty block of the rejected transaction is counted
+        assert_eq!(ledger.get_block_commit_count(), lock_until);
+        let tx = transfer(&ledger, sid, &bob_kp, &fra_owner_kp, 0);
+        assert!(apply(&mut ledger, tx).is_some());
+    }
+
     #[test]
     fn test_operation_bar_to_abar() {
         let mut builder = TransactionBuilder::from_seq_id(1);
@@ -2470,7 +4328,7 @@ mod tests {
 
         // post transaction steps test
         let txn = builder.build_and_take_transaction().unwrap();
-        let compute_effect = TxnEffect::compute_effect(txn).unwrap();
+        let compute_effect = TxnEffect::compute_effect(txn, &FeatureSet::all()).unwrap();
         let mut block = BlockEffect::default();
         let block_result = block.add_txn_effect(compute_effect);
         assert!(block_result.is_ok());
@@ -2559,7 +4417,7 @@ mod tests {
         assert!(result.is_ok());
 
         let txn = builder.build_and_take_transaction().unwrap();
-        let compute_effect = TxnEffect::compute_effect(txn).unwrap();
+        let compute_effect = TxnEffect::compute_effect(txn, &FeatureSet::all()).unwrap();
         let mut block = BlockEffect::default();
         let block_result = block.add_txn_effect(compute_effect);
         assert!(block_result.is_ok());
@@ -2938,4 +4796,99 @@ mod tests {
             assert_eq!(b.extra_fee_estimation().unwrap(), 0);
         }
     }
+
+    #[test]
+    fn test_introspection() {
+        let mut prng = ChaChaRng::from_entropy();
+        let kp = XfrKeyPair::generate(&mut prng);
+        let code = AssetTypeCode::gen_random();
+
+        let mut builder = TransactionBuilder::from_seq_id(1);
+        pnk!(builder.add_operation_create_asset(
+            &kp,
+            Some(code),
+            AssetRules::default(),
+            "memo"
+        ));
+        pnk!(builder.add_operation_anchor_data(
+            &kp,
+            [7; 32],
+            AnchorLabel {
+                kind: "invoice".to_owned(),
+                name: "42".to_owned(),
+            }
+        ));
+        builder.sign(&kp);
+
+        assert_eq!(builder.operation_count(), 2);
+        assert_eq!(builder.estimated_size(), builder.serialize().len());
+
+        let summary = builder.to_summary();
+        let lines = summary.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("1. define the asset"));
+        assert!(lines[0].contains(&code.to_base64()));
+        assert!(lines[1].starts_with("2. anchor the invoice \"42\""));
+
+        let mut params = ChainParamValues {
+            max_txn_bytes: builder.estimated_size() as u64,
+            max_txn_ops: 2,
+            max_block_txns: 1000,
+            tx_fee_min: 0,
+            bar_to_abar_tx_fee_min: 0,
+            unbond_block_cnt: 1,
+            feature_heights: Default::default(),
+        };
+        assert!(builder.validate_locally(&params).is_ok());
+
+        params.max_txn_ops = 1;
+        params.max_txn_bytes -= 1;
+        let e = builder.validate_locally(&params).unwrap_err().to_string();
+        assert!(e.contains("bytes exceeds"));
+        assert!(e.contains("operations exceeds"));
+
+        // no fee paid
+        params.max_txn_ops = 2;
+        params.max_txn_bytes += 1;
+        params.tx_fee_min = TX_FEE_MIN;
+        assert!(builder.validate_locally(&params).is_err());
+    }
+
+    #[test]
+    fn test_export_import_json() {
+        let mut prng = ChaChaRng::from_entropy();
+        let kp = XfrKeyPair::generate(&mut prng);
+
+        let mut builder = TransactionBuilder::from_seq_id(3);
+        pnk!(builder.add_operation_create_asset(
+            &kp,
+            None,
+            AssetRules::default(),
+            "draft"
+        ));
+        let json = pnk!(builder.export_json());
+        let v = pnk!(serde_json::from_str::<serde_json::Value>(&json));
+        assert_eq!(v["schema"], TXN_BUILDER_SCHEMA);
+        assert_eq!(v["version"], TXN_BUILDER_SCHEMA_VERSION);
+
+        let mut resumed = pnk!(TransactionBuilder::import_json(&json));
+        assert_eq!(resumed.transaction(), builder.transaction());
+        assert_eq!(resumed.no_replay_token, builder.no_replay_token);
+        assert_eq!(resumed.outputs, builder.outputs);
+
+        // the draft goes on
+        resumed.sign(&kp);
+        assert_eq!(resumed.transaction().signatures.len(), 1);
+
+        // a bare transaction
+        let bare = pnk!(TransactionBuilder::import_json(&builder.serialize_str()));
+        assert_eq!(bare.transaction(), builder.transaction());
+
+        let mut newer = v.clone();
+        newer["version"] = (TXN_BUILDER_SCHEMA_VERSION + 1).into();
+        assert!(TransactionBuilder::import_json(&newer.to_string()).is_err());
+        let mut other = v;
+        other["schema"] = "other".into();
+        assert!(TransactionBuilder::import_json(&other.to_string()).is_err());
+    }
 }
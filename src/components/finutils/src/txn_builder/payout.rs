@@ -0,0 +1,384 @@
+//!
+//! # Batch payouts
+//!
+//! Pay one asset to many recipients, e.g. a reward distribution, given as
+//! a CSV of `address,amount` rows. The payees are split into transfers of
+//! as many of them as fit in a transaction, each one balanced on its own,
+//! with the change back to the payer.
+//!
+
+use {
+    super::{coin_selection::CoinSelection, TransferOperationBuilder},
+    globutils::wallet,
+    ledger::data_model::{
+        parse_amount, AssetTypeCode, TransferType, TxoRef, ASSET_TYPE_FRA,
+        BLACK_HOLE_PUBKEY,
+    },
+    ruc::*,
+    zei::{
+        noah_api::xfr::{
+            asset_record::AssetRecordType,
+            structs::{AssetRecordTemplate, AssetType, OpenAssetRecord},
+        },
+        XfrPublicKey,
+    },
+};
+
+/// The most rows of a payout
+pub const PAYOUT_MAX_ROWS: usize = 50_000;
+
+/// The most payees of one transfer, whatever its size
+pub const PAYOUT_MAX_OUTPUTS: usize = 1000;
+
+/// Payees of an asset, to be paid by one or more transfers
+#[derive(Clone, Debug)]
+pub struct PayoutBuilder {
+    asset_type: AssetType,
+    payees: Vec<(XfrPublicKey, u64)>,
+    record_type: AssetRecordType,
+    selection: CoinSelection,
+}
+
+impl PayoutBuilder {
+    /// A payout of the asset `code`, in non-confidential records
+    pub fn new(code: &AssetTypeCode) -> Self {
+        PayoutBuilder {
+            asset_type: code.val,
+            payees: vec![],
+            record_type: AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+            selection: CoinSelection::default(),
+        }
+    }
+
+    /// The type of the records paid
+    pub fn set_record_type(&mut self, rt: AssetRecordType) -> &mut Self {
+        self.record_type = rt;
+        self
+    }
+
+    /// How the records paying each transfer are picked
+    pub fn set_coin_selection(&mut self, selection: CoinSelection) -> &mut Self {
+        self.selection = selection;
+        self
+    }
+
+    #[allow(missing_docs)]
+    pub fn add_payee(&mut self, pk: XfrPublicKey, am: u64) -> Result<&mut Self> {
+        if 0 == am {
+            return Err(eg!("zero amount"));
+        }
+        if PAYOUT_MAX_ROWS <= self.payees.len() {
+            return Err(eg!(format!("more than {PAYOUT_MAX_ROWS} payees")));
+        }
+        self.payees.push((pk, am));
+        Ok(self)
+    }
+
+    /// Add the payees of `csv`, one `address,amount` per line, the address
+    /// in bech32 or a base64 public key, the amount a decimal string in the
+    /// units of an asset with `decimals` decimals, e.g. `12.345`.
+    ///
+    /// Empty lines, lines starting with `#` and a header line starting
+    /// with `address` are skipped.
+    pub fn parse_csv(&mut self, csv: &str, decimals: u8) -> Result<&mut Self> {
+        let mut header = true;
+        for (i, line) in csv.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if header && line.to_lowercase().starts_with("address") {
+                header = false;
+                continue;
+            }
+            header = false;
+
+            let ln = i + 1;
+            let (addr, am) = line
+                .split_once(',')
+                .c(d!(format!("line {ln}: expected `address,amount`")))?;
+            let addr = addr.trim();
+            let pk = wallet::public_key_from_bech32(addr)
+                .or_else(|_| wallet::public_key_from_base64(addr))
+                .c(d!(format!("line {ln}: invalid address `{addr}`")))?;
+            let am = parse_amount(am, decimals).c(d!(format!("line {ln}")))?;
+            self.add_payee(pk, am).c(d!(format!("line {ln}")))?;
+        }
+        Ok(self)
+    }
+
+    #[allow(missing_docs)]
+    pub fn asset_type(&self) -> AssetType {
+        self.asset_type
+    }
+
+    #[allow(missing_docs)]
+    pub fn payees(&self) -> &[(XfrPublicKey, u64)] {
+        &self.payees
+    }
+
+    /// The sum of the payouts
+    pub fn total(&self) -> Result<u64> {
+        self.payees
+            .iter()
+            .try_fold(0u64, |acc, (_, am)| acc.checked_add(*am))
+            .c(d!("amount overflow"))
+    }
+
+    /// A transfer paying the longest run of the payees from the index `from`
+    /// that serializes within `max_bytes`, out of `inputs`, records of the
+    /// asset of one owner. `fee` FRA is paid out of the inputs too, so it must
+    /// be zero for other assets.
+    ///
+    /// Returns the created builder, yet to be signed, along with the number
+    /// of the payees paid and the indexes of the inputs spent.
+    pub fn build_next(
+        &self,
+        from: usize,
+        inputs: &[(TxoRef, OpenAssetRecord)],
+        fee: u64,
+        max_bytes: usize,
+    ) -> Result<(TransferOperationBuilder, usize, Vec<usize>)> {
+        let payees = self.payees.get(from..).c(d!("no payees left"))?;
+        if payees.is_empty() {
+            return Err(eg!("no payees left"));
+        }
+        if inputs
+            .iter()
+            .any(|(_, oar)| oar.asset_type != self.asset_type)
+        {
+            return Err(eg!("inputs of another asset"));
+        }
+        if 0 < fee && self.asset_type != ASSET_TYPE_FRA {
+            return Err(eg!("the fee can only be paid out of FRA inputs"));
+        }
+        let coins = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, (_, oar))| (i, oar.amount))
+            .collect::<Vec<_>>();
+
+        enum Probe {
+            Fits(TransferOperationBuilder, Vec<usize>),
+            TooLarge,
+            // not enough in the inputs
+            Short,
+        }
+
+        let build = |n: usize| -> Result<Probe> {
+            let target = payees[..n]
+                .iter()
+                .try_fold(fee, |acc, (_, am)| acc.checked_add(*am))
+                .c(d!("amount overflow"))?;
+            let picked = match self.selection.select(&coins, target) {
+                Ok(picked) => picked,
+                Err(_) => return Ok(Probe::Short),
+            };
+
+            let mut op = TransferOperationBuilder::new();
+            let mut left = target;
+            for (i, am) in picked.iter() {
+                let spent = (*am).min(left);
+                left -= spent;
+                let (sid, oar) = &inputs[*i];
+                op.add_input(*sid, oar.clone(), None, None, spent).c(d!())?;
+            }
+            for (pk, am) in payees[..n].iter() {
+                op.add_output(
+                    &AssetRecordTemplate::with_no_asset_tracing(
+                        *am,
+                        self.asset_type,
+                        self.record_type,
+                        pk.into_noah(),
+                    ),
+                    None,
+                    None,
+                    None,
+                )
+                .c(d!())?;
+            }
+            if 0 < fee {
+                op.add_output(
+                    &AssetRecordTemplate::with_no_asset_tracing(
+                        fee,
+                        ASSET_TYPE_FRA,
+                        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                        *BLACK_HOLE_PUBKEY,
+                    ),
+                    None,
+                    None,
+                    None,
+                )
+                .c(d!())?;
+            }
+            op.balance(None)
+                .c(d!())?
+                .create(TransferType::Standard)
+                .c(d!())?;
+
+            let size = serde_json::to_vec(&op.transaction().c(d!())?)
+                .c(d!())?
+                .len();
+            Ok(alt!(
+                size <= max_bytes,
+                Probe::Fits(op, picked.into_iter().map(|(i, _)| i).collect()),
+                Probe::TooLarge
+            ))
+        };
+
+        // both the size and the amount grow with the number of payees, so bisect it
+        let (mut lo, mut hi) = (0, payees.len().min(PAYOUT_MAX_OUTPUTS));
+        let mut best = None;
+        // why the last probe, of one payee if none fits, failed
+        let mut short = false;
+        while lo < hi {
+            let mid = lo + (hi - lo + 1) / 2;
+            match build(mid).c(d!())? {
+                Probe::Fits(op, spent) => {
+                    best = Some((op, mid, spent));
+                    lo = mid;
+                }
+                Probe::Short => {
+                    short = true;
+                    hi = mid - 1;
+                }
+                Probe::TooLarge => {
+                    short = false;
+                    hi = mid - 1;
+                }
+            }
+        }
+        best.c(d!(alt!(
+            short,
+            "insufficient balance for the next payee",
+            "no payee fits in a transfer"
+        )))
+    }
+
+    /// All the transfers of the payout at once, out of `inputs`, see `build_next`.
+    ///
+    /// The transfers do not spend the change of each other, so they can be
+    /// sent together, but `inputs` must be spread enough for that,
+    /// otherwise pay by `build_next` once the change of the last transfer
+    /// is committed.
+    pub fn build(
+        &self,
+        inputs: &[(TxoRef, OpenAssetRecord)],
+        fee: u64,
+        max_bytes: usize,
+    ) -> Result<Vec<TransferOperationBuilder>> {
+        let mut pool = inputs.to_vec();
+        let mut ops = vec![];
+        let mut from = 0;
+        while from < self.payees.len() {
+            let (op, n, mut spent) =
+                self.build_next(from, &pool, fee, max_bytes).c(d!())?;
+            spent.sort_unstable();
+            for i in spent.into_iter().rev() {
+                pool.remove(i);
+            }
+            ops.push(op);
+            from += n;
+        }
+        Ok(ops)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        ledger::data_model::{TxoSID, FRA_DECIMALS, TX_FEE_MIN},
+        rand_chacha::ChaChaRng,
+        rand_core::SeedableRng,
+        zei::{
+            noah_algebra::ristretto::PedersenCommitmentRistretto,
+            noah_api::xfr::asset_record::{
+                build_blind_asset_record, open_blind_asset_record,
+            },
+            XfrKeyPair,
+        },
+    };
+
+    #[test]
+    fn test_payout_builder() {
+        let mut prng = ChaChaRng::from_entropy();
+        let pc_gens = PedersenCommitmentRistretto::default();
+        let alice = XfrKeyPair::generate(&mut prng);
+        let bobs = (0..6)
+            .map(|_| XfrKeyPair::generate(&mut prng).get_pk())
+            .collect::<Vec<_>>();
+
+        let mut csv = "address,amount\n# rewards\n\n".to_owned();
+        for (i, pk) in bobs.iter().enumerate() {
+            csv += &format!("{},{}.5\n", wallet::public_key_to_bech32(pk), i + 1);
+        }
+        let mut payout = PayoutBuilder::new(&AssetTypeCode {
+            val: ASSET_TYPE_FRA,
+        });
+        payout.parse_csv(&csv, FRA_DECIMALS).unwrap();
+        assert_eq!(6, payout.payees().len());
+        assert_eq!((bobs[0], 1_500_000), payout.payees()[0]);
+        assert_eq!(24_000_000, payout.total().unwrap());
+
+        let mut bad = PayoutBuilder::new(&AssetTypeCode {
+            val: ASSET_TYPE_FRA,
+        });
+        assert!(bad.parse_csv("nobody,1", FRA_DECIMALS).is_err());
+        assert!(bad.parse_csv("address,amount\naddress,1", 6).is_err());
+        let addr = wallet::public_key_to_bech32(&bobs[0]);
+        assert!(bad.parse_csv(&format!("{addr},0.0000001"), 6).is_err());
+        assert!(bad.parse_csv(&format!("{addr},0"), 6).is_err());
+
+        let inputs = (0..4)
+            .map(|i| {
+                let ar = AssetRecordTemplate::with_no_asset_tracing(
+                    10_000_000,
+                    ASSET_TYPE_FRA,
+                    AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                    alice.get_pk().into_noah(),
+                );
+                let (ba, _, memo) =
+                    build_blind_asset_record(&mut prng, &pc_gens, &ar, vec![]);
+                let oar =
+                    open_blind_asset_record(&ba, &memo, &alice.into_noah()).unwrap();
+                (TxoRef::Absolute(TxoSID(i)), oar)
+            })
+            .collect::<Vec<_>>();
+
+        // all in one transfer, with the change back to alice
+        let (op, n, spent) = payout
+            .build_next(0, &inputs, TX_FEE_MIN, usize::MAX)
+            .unwrap();
+        assert_eq!(6, n);
+        assert_eq!(3, spent.len());
+        assert_eq!(8, op.output_records.len());
+
+        // limited to the size of a transfer of 2 payees
+        let (op, _, _) = payout
+            .build_next(4, &inputs, TX_FEE_MIN, usize::MAX)
+            .unwrap();
+        let size = serde_json::to_vec(&op.transaction().unwrap())
+            .unwrap()
+            .len();
+        let ops = payout.build(&inputs, TX_FEE_MIN, size).unwrap();
+        assert!(1 < ops.len());
+        let paid = ops
+            .iter()
+            .flat_map(|op| op.output_records.iter())
+            .filter(|ar| {
+                bobs.contains(&XfrPublicKey::from_noah(
+                    ar.open_asset_record.get_pub_key(),
+                ))
+            })
+            .count();
+        assert_eq!(6, paid);
+
+        // nothing fits
+        assert!(payout.build_next(0, &inputs, TX_FEE_MIN, 10).is_err());
+        // not enough for the first payee
+        assert!(payout
+            .build_next(5, &inputs[..1], 4_000_000, usize::MAX)
+            .is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//!
+//! # Transaction builder
+//!
+//! A thin wrapper of the builder of `finutils`,
+//! so the transactions are checked by the same code as the nodes.
+//!
+
+use {
+    crate::{keys::KeyPair, to_pyerr},
+    finutils::txn_builder::TransactionBuilder as PlatformTransactionBuilder,
+    ledger::{
+        data_model::{AssetRules, AssetTypeCode, FRA_DECIMALS},
+        staking::params::ChainParamValues,
+    },
+    pyo3::prelude::*,
+    ruc::*,
+    zei::noah_api::xfr::asset_record::AssetRecordType,
+};
+
+/// Builds, signs and serializes a transaction
+#[pyclass(unsendable)]
+#[derive(Clone)]
+pub struct TransactionBuilder {
+    pub(crate) builder: PlatformTransactionBuilder,
+}
+
+#[pymethods]
+impl TransactionBuilder {
+    /// `seq_id` is the sequence id of the ledger, see `Client.seq_id`
+    #[new]
+    pub fn new(seq_id: u64) -> Self {
+        TransactionBuilder {
+            builder: PlatformTransactionBuilder::from_seq_id(seq_id),
+        }
+    }
+
+    /// Define a new asset, returns its code in base64,
+    /// a random one if `code` is not given
+    #[pyo3(signature = (kp, memo, code = None, max_units = None, decimals = None))]
+    fn define_asset(
+        &mut self,
+        kp: &KeyPair,
+        memo: &str,
+        code: Option<&str>,
+        max_units: Option<u64>,
+        decimals: Option<u8>,
+    ) -> PyResult<String> {
+        let code = match code {
+            Some(c) => AssetTypeCode::new_from_base64(c)
+                .c(d!())
+                .map_err(to_pyerr)?,
+            None => AssetTypeCode::gen_random(),
+        };
+        let rules = AssetRules {
+            max_units,
+            decimals: decimals.unwrap_or(FRA_DECIMALS),
+            ..Default::default()
+        };
+        self.builder
+            .add_operation_create_asset(&kp.kp, Some(code), rules, memo)
+            .c(d!())
+            .map_err(to_pyerr)?;
+        Ok(code.to_base64())
+    }
+
+    /// Issue `amount` units of the asset `code` to its issuer,
+    /// `seq_num` must be higher than the one of any former issuance
+    #[pyo3(signature = (kp, code, seq_num, amount, confidential = false))]
+    fn issue(
+        &mut self,
+        kp: &KeyPair,
+        code: &str,
+        seq_num: u64,
+        amount: u64,
+        confidential: bool,
+    ) -> PyResult<()> {
+        let code = AssetTypeCode::new_from_base64(code)
+            .c(d!())
+            .map_err(to_pyerr)?;
+        self.builder
+            .add_basic_issue_asset(
+                &kp.kp,
+                &code,
+                seq_num,
+                amount,
+                AssetRecordType::from_flags(confidential, false),
+            )
+            .c(d!())
+            .map_err(to_pyerr)
+            .map(|_| ())
+    }
+
+    /// Add an operation in the JSON of the ledger, e.g. a transfer
+    fn add_operation_json(&mut self, op: &str) -> PyResult<()> {
+        let op = serde_json::from_str(op).c(d!()).map_err(to_pyerr)?;
+        self.builder.add_operation(op);
+        Ok(())
+    }
+
+    /// Pay the fee from the outputs of the transaction owned by `kp`,
+    /// as the last operation
+    fn add_fee(&mut self, kp: &KeyPair) -> PyResult<()> {
+        self.builder
+            .add_fee_relative_auto(&kp.kp)
+            .c(d!())
+            .map_err(to_pyerr)
+            .map(|_| ())
+    }
+
+    /// Build the pending anonymous operations, if any
+    fn build(&mut self) -> PyResult<()> {
+        self.builder.build().c(d!()).map_err(to_pyerr).map(|_| ())
+    }
+
+    /// Sign the transaction with `kp`
+    fn sign(&mut self, kp: &KeyPair) {
+        self.builder.sign(&kp.kp);
+    }
+
+    /// The transaction in JSON, as submitted to the nodes
+    fn transaction(&self) -> String {
+        self.builder.serialize_str()
+    }
+
+    /// The handle of the transaction
+    fn handle(&self) -> String {
+        self.builder.transaction().handle()
+    }
+
+    /// One numbered line per operation
+    fn summary(&self) -> String {
+        self.builder.to_summary()
+    }
+
+    /// Size in bytes of the transaction as it would be submitted
+    fn estimated_size(&self) -> usize {
+        self.builder.estimated_size()
+    }
+
+    /// Number of operations in the transaction
+    fn operation_count(&self) -> usize {
+        self.builder.operation_count()
+    }
+
+    /// Check the transaction as the nodes would,
+    /// `params` is the JSON served by `/params`, see `Client.params`
+    fn validate(&self, params: &str) -> PyResult<()> {
+        let params = serde_json::from_str::<ChainParamValues>(params)
+            .c(d!())
+            .map_err(to_pyerr)?;
+        self.builder
+            .validate_locally(&params)
+            .c(d!())
+            .map_err(to_pyerr)
+    }
+
+    /// The whole builder as versioned JSON
+    fn export_json(&self) -> PyResult<String> {
+        self.builder.export_json().c(d!()).map_err(to_pyerr)
+    }
+
+    /// Restore a builder from `export_json`, or from a bare transaction
+    #[staticmethod]
+    fn import_json(s: &str) -> PyResult<Self> {
+        PlatformTransactionBuilder::import_json(s)
+            .c(d!())
+            .map(|builder| TransactionBuilder { builder })
+            .map_err(to_pyerr)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TransactionBuilder({} operations, {} bytes)",
+            self.operation_count(),
+            self.estimated_size()
+        )
+    }
+}
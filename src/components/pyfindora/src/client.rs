@@ -0,0 +1,194 @@
+//!
+//! # Client of the REST routes of a node
+//!
+//! The paths are taken from the `NetworkRoute` implementations of the
+//! servers, the responses are returned as decoded JSON.
+//!
+
+use {
+    crate::{builder::TransactionBuilder, to_pyerr},
+    abciapp::api::{
+        query_server::query_api::{ledger_api::ApiRoutes, QueryServerRoutes},
+        submission_server::submission_api::SubmissionRoutes,
+    },
+    finutils::api::NetworkRoute,
+    pyo3::prelude::*,
+    ruc::*,
+    serde_json::Value,
+};
+
+const QUERY_PORT: u16 = 8667;
+const LEDGER_PORT: u16 = 8668;
+const SUBMISSION_PORT: u16 = 8669;
+
+/// A client of the servers of a node
+#[pyclass]
+#[derive(Clone)]
+pub struct Client {
+    serv_addr: String,
+}
+
+impl Client {
+    fn url(&self, port: u16, path: &str) -> String {
+        format!("{}:{}{}", self.serv_addr, port, path)
+    }
+
+    fn get_bytes(&self, port: u16, path: &str) -> Result<Vec<u8>> {
+        let url = self.url(port, path);
+        attohttpc::get(&url)
+            .send()
+            .c(d!(url))?
+            .error_for_status()
+            .c(d!(url))?
+            .bytes()
+            .c(d!(url))
+    }
+
+    fn post_tx(&self, tx: String) -> Result<String> {
+        let url = self.url(
+            SUBMISSION_PORT,
+            &SubmissionRoutes::SubmitTransaction.route(),
+        );
+        attohttpc::post(&url)
+            .header(attohttpc::header::CONTENT_TYPE, "application/json")
+            .bytes(tx.into_bytes())
+            .send()
+            .c(d!(url))?
+            .error_for_status()
+            .c(d!(url))?
+            .bytes()
+            .c(d!(url))
+            .and_then(|b| serde_json::from_slice::<String>(&b).c(d!(url)))
+    }
+
+    fn get(&self, py: Python, port: u16, path: &str) -> PyResult<PyObject> {
+        let bytes = self.get_bytes(port, path).map_err(to_pyerr)?;
+        let s = String::from_utf8(bytes).c(d!()).map_err(to_pyerr)?;
+        py.import("json")?
+            .call_method1("loads", (s,))
+            .map(|v| v.into())
+    }
+}
+
+#[pymethods]
+impl Client {
+    /// `serv_addr` is the scheme and the host of the node,
+    /// e.g. `http://127.0.0.1`, the ports are the default ones
+    #[new]
+    fn new(serv_addr: &str) -> Self {
+        Client {
+            serv_addr: serv_addr.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// The sequence id of the ledger, to build transactions with
+    fn seq_id(&self) -> PyResult<u64> {
+        let bytes = self
+            .get_bytes(LEDGER_PORT, &ApiRoutes::GlobalState.route())
+            .map_err(to_pyerr)?;
+        serde_json::from_slice::<Value>(&bytes)
+            .c(d!())
+            .and_then(|v| v[1].as_u64().c(d!("invalid global state")))
+            .map_err(to_pyerr)
+    }
+
+    /// A new builder at the current sequence id
+    fn new_builder(&self) -> PyResult<TransactionBuilder> {
+        self.seq_id().map(TransactionBuilder::new)
+    }
+
+    /// The chain parameters in effect, in JSON, see `TransactionBuilder.validate`
+    fn params(&self) -> PyResult<String> {
+        self.get_bytes(QUERY_PORT, "/params")
+            .and_then(|b| String::from_utf8(b).c(d!()))
+            .map_err(to_pyerr)
+    }
+
+    /// The definition of the asset `code`, in base64
+    fn asset_type(&self, py: Python, code: &str) -> PyResult<PyObject> {
+        self.get(py, LEDGER_PORT, &ApiRoutes::AssetToken.with_arg(&code))
+    }
+
+    /// The issuance sequence number of the asset `code`
+    fn asset_issuance_num(&self, py: Python, code: &str) -> PyResult<PyObject> {
+        self.get(
+            py,
+            LEDGER_PORT,
+            &ApiRoutes::AssetIssuanceNum.with_arg(&code),
+        )
+    }
+
+    /// The unspent TXO `sid`
+    fn utxo(&self, py: Python, sid: u64) -> PyResult<PyObject> {
+        self.get(py, LEDGER_PORT, &ApiRoutes::UtxoSid.with_arg(&sid))
+    }
+
+    /// The sids of the unspent TXOs of `public_key`, in base64
+    fn owned_utxos(&self, py: Python, public_key: &str) -> PyResult<PyObject> {
+        self.get(
+            py,
+            QUERY_PORT,
+            &QueryServerRoutes::GetOwnedUtxos.with_arg(&public_key),
+        )
+    }
+
+    /// The assets defined by `public_key`, in base64
+    fn created_assets(&self, py: Python, public_key: &str) -> PyResult<PyObject> {
+        self.get(
+            py,
+            QUERY_PORT,
+            &QueryServerRoutes::GetCreatedAssets.with_arg(&public_key),
+        )
+    }
+
+    /// The records issued by `public_key`, in base64
+    fn issued_records(&self, py: Python, public_key: &str) -> PyResult<PyObject> {
+        self.get(
+            py,
+            QUERY_PORT,
+            &QueryServerRoutes::GetIssuedRecords.with_arg(&public_key),
+        )
+    }
+
+    /// The records issued of the asset `code`, in base64
+    fn issued_records_by_code(&self, py: Python, code: &str) -> PyResult<PyObject> {
+        self.get(
+            py,
+            QUERY_PORT,
+            &QueryServerRoutes::GetIssuedRecordsByCode.with_arg(&code),
+        )
+    }
+
+    /// The transactions related to `public_key`, in base64
+    fn related_txns(&self, py: Python, public_key: &str) -> PyResult<PyObject> {
+        self.get(
+            py,
+            QUERY_PORT,
+            &QueryServerRoutes::GetRelatedTxns.with_arg(&public_key),
+        )
+    }
+
+    /// The current validators
+    fn validators(&self, py: Python) -> PyResult<PyObject> {
+        self.get(py, LEDGER_PORT, &ApiRoutes::ValidatorList.route())
+    }
+
+    /// Submit the transaction of `builder`, returns its handle
+    fn submit(&self, builder: &TransactionBuilder) -> PyResult<String> {
+        self.post_tx(builder.builder.serialize_str())
+            .map_err(to_pyerr)
+    }
+
+    /// The status of a submitted transaction, by its handle
+    fn txn_status(&self, py: Python, handle: &str) -> PyResult<PyObject> {
+        self.get(
+            py,
+            SUBMISSION_PORT,
+            &SubmissionRoutes::TxnStatus.with_arg(&handle),
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Client({})", self.serv_addr)
+    }
+}
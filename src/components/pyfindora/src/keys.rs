@@ -0,0 +1,63 @@
+//!
+//! # Key pairs
+//!
+
+use {crate::to_pyerr, globutils::wallet, pyo3::prelude::*, ruc::*, zei::XfrKeyPair};
+
+/// A key pair owning assets
+#[pyclass]
+#[derive(Clone)]
+pub struct KeyPair {
+    pub(crate) kp: XfrKeyPair,
+}
+
+#[pymethods]
+impl KeyPair {
+    /// A new random key pair
+    #[staticmethod]
+    fn generate() -> Self {
+        let mnemonic = wallet::generate_mnemonic_default();
+        KeyPair {
+            kp: wallet::restore_keypair_from_mnemonic_default(&mnemonic).unwrap(),
+        }
+    }
+
+    /// Restore a key pair from a mnemonic, with the default bip44 path
+    #[staticmethod]
+    fn from_mnemonic(phrase: &str) -> PyResult<Self> {
+        wallet::restore_keypair_from_mnemonic_default(phrase)
+            .c(d!())
+            .map(|kp| KeyPair { kp })
+            .map_err(to_pyerr)
+    }
+
+    /// Restore a key pair from the JSON of `to_json`
+    #[staticmethod]
+    fn from_json(s: &str) -> PyResult<Self> {
+        serde_json::from_str(s)
+            .c(d!())
+            .map(|kp| KeyPair { kp })
+            .map_err(to_pyerr)
+    }
+
+    /// The key pair in JSON, including the secret key
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.kp).c(d!()).map_err(to_pyerr)
+    }
+
+    /// The address in bech32
+    #[getter]
+    fn address(&self) -> String {
+        wallet::public_key_to_bech32(self.kp.get_pk_ref())
+    }
+
+    /// The public key in base64, as used by the query routes
+    #[getter]
+    fn public_key(&self) -> String {
+        wallet::public_key_to_base64(self.kp.get_pk_ref())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("KeyPair({})", self.address())
+    }
+}
@@ -0,0 +1,48 @@
+//!
+//! # Python bindings
+//!
+//! The `findora` Python module, for scripting issuances and audits
+//! against a node without a Rust toolchain:
+//!
+//! ```python
+//! import findora
+//!
+//! client = findora.Client("https://prod-mainnet.prod.findora.org")
+//! kp = findora.KeyPair.from_mnemonic("...")
+//! b = client.new_builder()
+//! code = b.define_asset(kp, "my token", max_units=10**12)
+//! b.add_fee(kp)
+//! b.sign(kp)
+//! handle = client.submit(b)
+//! ```
+//!
+//! Build with `maturin build --release` in this directory.
+//!
+
+#![deny(warnings)]
+#![deny(missing_docs)]
+
+mod builder;
+mod client;
+mod keys;
+
+use {
+    pyo3::{exceptions::PyRuntimeError, prelude::*},
+    std::fmt::Display,
+};
+
+pub use {builder::TransactionBuilder, client::Client, keys::KeyPair};
+
+/// Errors of the ledger and of the nodes surface as `RuntimeError`
+pub(crate) fn to_pyerr(e: impl Display) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+/// The `findora` module
+#[pymodule]
+fn findora(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Client>()?;
+    m.add_class::<KeyPair>()?;
+    m.add_class::<TransactionBuilder>()?;
+    Ok(())
+}
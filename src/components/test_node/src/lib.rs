@@ -0,0 +1,238 @@
+//!
+//! # An in-process node for integration tests
+//!
+//! The submission server and the query server of `abciapp` over a temporary
+//! ledger, without tendermint. Submitted transactions wait in a local queue
+//! in place of the mempool, and `advance_block` commits them the way the
+//! abci callbacks do, so tests run against real routes with no network.
+//!
+//! The query server is notified of new blocks through globals,
+//! hence there can be only one node in a process.
+//!
+
+#![deny(warnings)]
+#![deny(missing_docs)]
+
+use {
+    abciapp::api::{
+        query_server::{query_api::service::start_query_server, BLOCK_CREATED},
+        submission_server::{
+            submission_api::{SubmissionApi, SubmissionRoutes},
+            SubmissionServer, TxnForward, TxnHandle, TxnStatus,
+        },
+    },
+    finutils::api::NetworkRoute,
+    ledger::{
+        data_model::{Transaction, TxnSID, TxoSID},
+        store::{api_cache, LedgerState},
+    },
+    parking_lot::{Mutex, RwLock},
+    rand_chacha::ChaChaRng,
+    rand_core::SeedableRng,
+    ruc::*,
+    std::{
+        mem,
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+const HOST: &str = "127.0.0.1";
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+static SPAWNED: AtomicBool = AtomicBool::new(false);
+
+type Mempool = Arc<Mutex<Vec<Transaction>>>;
+
+/// Queues the submitted transactions instead of sending them to tendermint
+pub struct LocalForward {
+    mempool: Mempool,
+}
+
+impl AsRef<str> for LocalForward {
+    fn as_ref(&self) -> &str {
+        "local"
+    }
+}
+
+impl TxnForward for LocalForward {
+    fn forward_txn(&self, txn: Transaction) -> Result<()> {
+        self.mempool.lock().push(txn);
+        Ok(())
+    }
+}
+
+/// A node serving the REST routes on local ports
+pub struct TestNode {
+    submission: Arc<RwLock<SubmissionServer<ChaChaRng, LocalForward>>>,
+    mempool: Mempool,
+    query_port: u16,
+    submission_port: u16,
+}
+
+impl TestNode {
+    /// Start the servers on free ports over an empty temporary ledger,
+    /// returns once both of them answer.
+    pub fn spawn() -> Result<Self> {
+        if SPAWNED.swap(true, Ordering::SeqCst) {
+            return Err(eg!("there can be only one node in a process"));
+        }
+
+        let ledger = Arc::new(RwLock::new(LedgerState::tmp_ledger()));
+        let mempool = Mempool::default();
+        let submission = SubmissionServer::new_no_auto_commit(
+            ChaChaRng::from_entropy(),
+            Arc::clone(&ledger),
+            LocalForward {
+                mempool: Arc::clone(&mempool),
+            },
+        )
+        .c(d!())
+        .map(|s| Arc::new(RwLock::new(s)))?;
+
+        let query_port = free_port().c(d!())?;
+        let submission_port = free_port().c(d!())?;
+
+        start_query_server(ledger, &[(HOST, query_port)])
+            .c(d!())?
+            .write()
+            .update();
+        let s = Arc::clone(&submission);
+        thread::spawn(move || {
+            pnk!(SubmissionApi::create(s, HOST, submission_port));
+        });
+
+        let node = TestNode {
+            submission,
+            mempool,
+            query_port,
+            submission_port,
+        };
+        node.wait_ready().c(d!())?;
+        Ok(node)
+    }
+
+    fn wait_ready(&self) -> Result<()> {
+        let urls = [
+            self.query_url() + "/get_commits",
+            self.submission_url() + &SubmissionRoutes::Ping.route(),
+        ];
+        let start = Instant::now();
+        for url in urls.iter() {
+            while !attohttpc::get(url)
+                .send()
+                .map(|r| r.is_success())
+                .unwrap_or(false)
+            {
+                if start.elapsed() > READY_TIMEOUT {
+                    return Err(eg!(format!("{url} is not ready")));
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+        Ok(())
+    }
+
+    /// Url of the query routes, the ledger routes are served there too
+    pub fn query_url(&self) -> String {
+        format!("http://{}:{}", HOST, self.query_port)
+    }
+
+    /// Url of the submission routes
+    pub fn submission_url(&self) -> String {
+        format!("http://{}:{}", HOST, self.submission_port)
+    }
+
+    /// The ledger of the node, to be checked directly
+    pub fn ledger(&self) -> Arc<RwLock<LedgerState>> {
+        self.submission.read().borrowable_ledger_state()
+    }
+
+    /// Sequence id to build transactions with
+    pub fn seq_id(&self) -> u64 {
+        self.ledger().read().get_block_commit_count()
+    }
+
+    /// Number of transactions waiting for the next block
+    pub fn pending_count(&self) -> usize {
+        self.mempool.lock().len()
+    }
+
+    /// Submit a transaction through the submission route,
+    /// it waits for the next block
+    pub fn submit(&self, txn: &Transaction) -> Result<TxnHandle> {
+        let url = self.submission_url() + &SubmissionRoutes::SubmitTransaction.route();
+        attohttpc::post(&url)
+            .json(txn)
+            .c(d!())?
+            .send()
+            .c(d!(url))?
+            .error_for_status()
+            .c(d!(url))?
+            .json::<TxnHandle>()
+            .c(d!(url))
+    }
+
+    /// The status of a submitted transaction, `None` if it is unknown
+    pub fn txn_status(&self, handle: &TxnHandle) -> Option<TxnStatus> {
+        self.submission.read().get_txn_status(handle)
+    }
+
+    /// Commit the queued transactions in a new block, as
+    /// `begin_block`, `deliver_tx` and `commit` would,
+    /// returns the height of the block.
+    pub fn advance_block(&self) -> Result<u64> {
+        let txns = mem::take(&mut *self.mempool.lock());
+        let mut s = self.submission.write();
+        let ledger = s.borrowable_ledger_state();
+
+        let height = ledger.read().get_tendermint_height() + 1;
+        ledger
+            .write()
+            .get_staking_mut()
+            .set_custom_block_height(height);
+
+        s.begin_block();
+        for txn in txns {
+            // rejected ones are recorded in their status
+            let _ = s.cache_transaction(txn);
+        }
+        s.end_block().c(d!())?;
+
+        let mut l = ledger.write();
+        l.set_tendermint_height(height);
+        api_cache::update_api_cache(&mut l).c(d!())?;
+        drop(l);
+
+        let mut created = BLOCK_CREATED.0.lock();
+        *created = true;
+        BLOCK_CREATED.1.notify_one();
+
+        Ok(height)
+    }
+
+    /// Submit a transaction and commit it in a new block,
+    /// returns its sid and the sids of its outputs.
+    pub fn submit_and_commit(&self, txn: &Transaction) -> Result<(TxnSID, Vec<TxoSID>)> {
+        let handle = self.submit(txn).c(d!())?;
+        self.advance_block().c(d!())?;
+        match self.txn_status(&handle) {
+            Some(TxnStatus::Committed(sids)) => Ok(sids),
+            Some(TxnStatus::Rejected(e)) => Err(eg!(e)),
+            s => Err(eg!(format!("not committed: {s:?}"))),
+        }
+    }
+}
+
+fn free_port() -> Result<u16> {
+    TcpListener::bind((HOST, 0))
+        .c(d!())?
+        .local_addr()
+        .c(d!())
+        .map(|a| a.port())
+}
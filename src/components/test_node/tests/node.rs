@@ -0,0 +1,49 @@
+//!
+//! A transaction through the routes of an in-process node.
+//!
+
+use {
+    abciapp::api::query_server::query_api::ledger_api::ApiRoutes,
+    finutils::{api::NetworkRoute, txn_builder::TransactionBuilder},
+    ledger::data_model::{AssetRules, AssetTypeCode},
+    rand_chacha::ChaChaRng,
+    rand_core::SeedableRng,
+    test_node::TestNode,
+    zei::XfrKeyPair,
+};
+
+#[test]
+fn test_define_asset() {
+    let node = TestNode::spawn().unwrap();
+    assert!(TestNode::spawn().is_err());
+    assert_eq!(0, node.seq_id());
+
+    // an empty block
+    assert_eq!(1, node.advance_block().unwrap());
+
+    let kp = XfrKeyPair::generate(&mut ChaChaRng::from_seed([0; 32]));
+    let code = AssetTypeCode::gen_random();
+    let mut builder = TransactionBuilder::from_seq_id(node.seq_id());
+    builder
+        .add_operation_create_asset(&kp, Some(code), AssetRules::default(), "test")
+        .unwrap()
+        .build()
+        .unwrap();
+    builder.sign(&kp);
+    let txn = builder.take_transaction();
+
+    let handle = node.submit(&txn).unwrap();
+    assert_eq!(1, node.pending_count());
+    assert!(node.ledger().read().get_asset_type(&code).is_none());
+
+    node.advance_block().unwrap();
+    assert_eq!(0, node.pending_count());
+    assert!(node.txn_status(&handle).is_some());
+    assert!(node.ledger().read().get_asset_type(&code).is_some());
+
+    let url = node.query_url() + &ApiRoutes::AssetToken.with_arg(&code.to_base64());
+    assert!(attohttpc::get(&url).send().unwrap().is_success());
+
+    // the same definition twice is rejected
+    assert!(node.submit_and_commit(&txn).is_err());
+}
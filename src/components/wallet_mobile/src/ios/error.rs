@@ -0,0 +1,104 @@
+//!
+//! # Error codes of the C interface
+//!
+//! The functions returning a pointer return null on failure, the ones
+//! returning a `FfiErrorCode` return it directly. In both cases the code and
+//! the message of the last failure on the calling thread can be fetched with
+//! `findora_ffi_last_error_code` and `findora_ffi_last_error_message`.
+//!
+//! The values of the codes are stable, new ones are only appended.
+//!
+
+use crate::rust::string_to_c_char;
+use std::{cell::RefCell, fmt::Display, os::raw::c_char, ptr};
+
+/// Result of a call to the C interface
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiErrorCode {
+    Ok = 0,
+    /// A required pointer argument is null
+    NullPointer = 1,
+    /// An argument can not be parsed
+    InvalidArgument = 2,
+    /// The operation can not be added to, or built into, the transaction
+    BuildFailed = 3,
+    /// The transaction would be rejected by the node
+    ValidationFailed = 4,
+    /// Serializing or deserializing failed
+    SerdeFailed = 5,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(FfiErrorCode, String)>> = RefCell::new(None);
+}
+
+pub(super) fn set_last_error(code: FfiErrorCode, msg: impl Display) {
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some((code, msg.to_string())));
+}
+
+/// Records the error of `res`, if any, and converts it to a code.
+pub(super) fn to_code<T, E: Display>(
+    res: Result<T, E>,
+    code: FfiErrorCode,
+) -> FfiErrorCode {
+    match res {
+        Ok(_) => FfiErrorCode::Ok,
+        Err(e) => {
+            set_last_error(code, e);
+            code
+        }
+    }
+}
+
+/// Boxes the value of `res`, or records its error and returns null.
+pub(super) fn boxed_or_null<T, E: Display>(
+    res: Result<T, E>,
+    code: FfiErrorCode,
+) -> *mut T {
+    match res {
+        Ok(v) => Box::into_raw(Box::new(v)),
+        Err(e) => {
+            set_last_error(code, e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the string of `res`, or records its error and returns null.
+pub(super) fn string_or_null<E: Display>(
+    res: Result<String, E>,
+    code: FfiErrorCode,
+) -> *mut c_char {
+    match res {
+        Ok(s) => string_to_c_char(s),
+        Err(e) => {
+            set_last_error(code, e);
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+/// The code of the last failure on this thread, `Ok` if none.
+pub extern "C" fn findora_ffi_last_error_code() -> FfiErrorCode {
+    LAST_ERROR
+        .with(|e| e.borrow().as_ref().map(|(c, _)| *c))
+        .unwrap_or(FfiErrorCode::Ok)
+}
+
+#[no_mangle]
+/// The message of the last failure on this thread, null if none.
+/// The string must be released with `findora_ffi_string_free`.
+pub extern "C" fn findora_ffi_last_error_message() -> *mut c_char {
+    LAST_ERROR
+        .with(|e| e.borrow().as_ref().map(|(_, m)| m.replace('\0', " ")))
+        .map(string_to_c_char)
+        .unwrap_or(ptr::null_mut())
+}
+
+#[no_mangle]
+/// Forgets the last failure on this thread.
+pub extern "C" fn findora_ffi_clear_last_error() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
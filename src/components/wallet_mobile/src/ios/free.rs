@@ -1,5 +1,6 @@
 use crate::rust::types;
 use crate::rust::*;
+use std::{ffi::CString, os::raw::c_char};
 
 #[no_mangle]
 /// # Safety
@@ -20,3 +21,38 @@ pub unsafe extern "C" fn findora_ffi_fee_inputs_free(ptr: *mut FeeInputs) {
     }
     let _ = Box::from_raw(ptr);
 }
+
+#[no_mangle]
+/// # Safety
+///
+pub unsafe extern "C" fn findora_ffi_transaction_builder_free(
+    ptr: *mut TransactionBuilder,
+) {
+    if ptr.is_null() {
+        return;
+    }
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+/// # Safety
+///
+pub unsafe extern "C" fn findora_ffi_transfer_operation_builder_free(
+    ptr: *mut TransferOperationBuilder,
+) {
+    if ptr.is_null() {
+        return;
+    }
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+/// Releases a string returned by the library.
+/// # Safety
+///
+pub unsafe extern "C" fn findora_ffi_string_free(ptr: *mut c_char) {
+    if ptr.is_null() {
+        return;
+    }
+    let _ = CString::from_raw(ptr);
+}
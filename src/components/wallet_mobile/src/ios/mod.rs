@@ -1,4 +1,5 @@
 pub mod asset_rules;
+pub mod error;
 pub mod evm;
 pub mod fee;
 pub mod free;
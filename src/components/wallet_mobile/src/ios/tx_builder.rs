@@ -1,4 +1,7 @@
-use super::parse_u64;
+use super::{
+    error::{boxed_or_null, string_or_null, to_code, FfiErrorCode},
+    parse_u64,
+};
 use crate::rust::{
     c_char_to_string, string_to_c_char, AssetRules, ClientAssetRecord, FeeInputs,
     OwnerMemo, TransactionBuilder,
@@ -13,11 +16,10 @@ pub extern "C" fn findora_ffi_transaction_builder_add_fee_relative_auto(
     builder: &TransactionBuilder,
     kp: &XfrKeyPair,
 ) -> *mut TransactionBuilder {
-    if let Ok(info) = builder.clone().add_fee_relative_auto(kp.clone()) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_fee_relative_auto(kp.clone()),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 /// Use this func to get the necessary infomations for generating `Relative Inputs`
@@ -38,11 +40,10 @@ pub extern "C" fn findora_ffi_transaction_builder_add_fee(
     builder: &TransactionBuilder,
     inputs: &FeeInputs,
 ) -> *mut TransactionBuilder {
-    if let Ok(info) = builder.clone().add_fee(inputs.clone()) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_fee(inputs.clone()),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 /// A simple fee checker for mainnet v1.0.
@@ -86,16 +87,15 @@ pub extern "C" fn findora_ffi_transaction_builder_add_operation_create_asset(
     token_code: *const c_char,
     asset_rules: &AssetRules,
 ) -> *mut TransactionBuilder {
-    if let Ok(info) = builder.clone().add_operation_create_asset(
-        key_pair,
-        c_char_to_string(memo),
-        c_char_to_string(token_code),
-        asset_rules.clone(),
-    ) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_operation_create_asset(
+            key_pair,
+            c_char_to_string(memo),
+            c_char_to_string(token_code),
+            asset_rules.clone(),
+        ),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 /// Wraps around TransactionBuilder to add an asset issuance to a transaction builder instance.
@@ -118,17 +118,16 @@ pub extern "C" fn findora_ffi_transaction_builder_add_basic_issue_asset(
     conf_amount: bool,
 ) -> *mut TransactionBuilder {
     let amount = parse_u64(amount);
-    if let Ok(info) = builder.clone().add_basic_issue_asset(
-        key_pair,
-        c_char_to_string(code),
-        seq_num,
-        amount,
-        conf_amount,
-    ) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_basic_issue_asset(
+            key_pair,
+            c_char_to_string(code),
+            seq_num,
+            amount,
+            conf_amount,
+        ),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 /// Adds an operation to the transaction builder that adds a hash to the ledger's custom data
@@ -146,15 +145,14 @@ pub extern "C" fn findora_ffi_transaction_builder_add_operation_update_memo(
     code: *const c_char,
     new_memo: *const c_char,
 ) -> *mut TransactionBuilder {
-    if let Ok(info) = builder.clone().add_operation_update_memo(
-        auth_key_pair,
-        c_char_to_string(code),
-        c_char_to_string(new_memo),
-    ) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_operation_update_memo(
+            auth_key_pair,
+            c_char_to_string(code),
+            c_char_to_string(new_memo),
+        ),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 #[no_mangle]
@@ -165,15 +163,14 @@ pub extern "C" fn findora_ffi_transaction_builder_add_operation_delegate(
     validator: *const c_char,
 ) -> *mut TransactionBuilder {
     let amount = parse_u64(amount);
-    if let Ok(info) = builder.clone().add_operation_delegate(
-        keypair,
-        amount,
-        c_char_to_string(validator),
-    ) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_operation_delegate(
+            keypair,
+            amount,
+            c_char_to_string(validator),
+        ),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 #[no_mangle]
@@ -181,11 +178,10 @@ pub extern "C" fn findora_ffi_transaction_builder_add_operation_undelegate(
     builder: &TransactionBuilder,
     keypair: &XfrKeyPair,
 ) -> *mut TransactionBuilder {
-    if let Ok(info) = builder.clone().add_operation_undelegate(keypair) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_operation_undelegate(keypair),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 #[no_mangle]
@@ -196,15 +192,14 @@ pub extern "C" fn findora_ffi_transaction_builder_add_operation_undelegate_parti
     target_validator: *const c_char,
 ) -> *mut TransactionBuilder {
     let am = parse_u64(am);
-    if let Ok(info) = builder.clone().add_operation_undelegate_partially(
-        keypair,
-        am,
-        c_char_to_string(target_validator),
-    ) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_operation_undelegate_partially(
+            keypair,
+            am,
+            c_char_to_string(target_validator),
+        ),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 #[no_mangle]
@@ -216,11 +211,10 @@ pub extern "C" fn findora_ffi_transaction_builder_add_operation_claim(
     let td_addr = c_char_to_string(td_addr);
     let addr = td_addr.strip_prefix("0x").unwrap_or(&td_addr);
     let td_address = hex::decode(addr).expect("addr format error!");
-    if let Ok(info) = builder.clone().add_operation_claim(td_address, keypair) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_operation_claim(td_address, keypair),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 #[no_mangle]
@@ -234,14 +228,12 @@ pub extern "C" fn findora_ffi_transaction_builder_add_operation_claim_custom(
     let td_addr = c_char_to_string(td_addr);
     let addr = td_addr.strip_prefix("0x").unwrap_or(&td_addr);
     let td_address = hex::decode(addr).expect("addr format error!");
-    if let Ok(info) = builder
-        .clone()
-        .add_operation_claim_custom(td_address, keypair, am)
-    {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder
+            .clone()
+            .add_operation_claim_custom(td_address, keypair, am),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 /// Adds a serialized transfer asset operation to a transaction builder instance.
@@ -253,11 +245,10 @@ pub extern "C" fn findora_ffi_transaction_builder_add_transfer_operation(
     builder: &TransactionBuilder,
     op: *const c_char,
 ) -> *mut TransactionBuilder {
-    if let Ok(info) = builder.clone().add_transfer_operation(c_char_to_string(op)) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_transfer_operation(c_char_to_string(op)),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 /// Adds a serialized transfer account operation to a transaction builder instance.
@@ -295,17 +286,16 @@ pub extern "C" fn findora_ffi_transaction_builder_add_operation_convert_account(
         Some(hex::decode(lowlevel_data_str).unwrap())
     };
 
-    if let Ok(builder) = builder.clone().add_transfer_to_account_operation(
-        amount,
-        addr,
-        kp,
-        asset,
-        lowlevel_data,
-    ) {
-        Box::into_raw(Box::new(builder))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(
+        builder.clone().add_transfer_to_account_operation(
+            amount,
+            addr,
+            kp,
+            asset,
+            lowlevel_data,
+        ),
+        FfiErrorCode::BuildFailed,
+    )
 }
 
 #[no_mangle]
@@ -313,11 +303,7 @@ pub extern "C" fn findora_ffi_transaction_builder_sign(
     builder: &TransactionBuilder,
     kp: &XfrKeyPair,
 ) -> *mut TransactionBuilder {
-    if let Ok(info) = builder.clone().sign(kp) {
-        Box::into_raw(Box::new(info))
-    } else {
-        std::ptr::null_mut()
-    }
+    boxed_or_null(builder.clone().sign(kp), FfiErrorCode::BuildFailed)
 }
 
 /// Extracts the serialized form of a transaction.
@@ -359,3 +345,53 @@ pub extern "C" fn findora_ffi_transaction_builder_get_owner_memo(
         std::ptr::null_mut()
     }
 }
+
+/// Size in bytes of the transaction as it would be submitted.
+#[no_mangle]
+pub extern "C" fn findora_ffi_transaction_builder_estimated_size(
+    builder: &TransactionBuilder,
+) -> usize {
+    builder.estimated_size()
+}
+
+/// Checks the transaction against the limits, the fee rule and the signature
+/// checks of the node, before submitting it.
+/// @param {string} params - Chain parameters in JSON, as served by `/params`.
+/// @return `ValidationFailed` with every violation in the last error message.
+#[no_mangle]
+pub extern "C" fn findora_ffi_transaction_builder_validate(
+    builder: &TransactionBuilder,
+    params: *const c_char,
+) -> FfiErrorCode {
+    if params.is_null() {
+        return to_code(Err::<(), _>("null params"), FfiErrorCode::NullPointer);
+    }
+    to_code(
+        builder.validate_locally(c_char_to_string(params)),
+        FfiErrorCode::ValidationFailed,
+    )
+}
+
+/// Exports the whole builder as versioned JSON, to be resumed later by
+/// `findora_ffi_transaction_builder_import_json`.
+#[no_mangle]
+pub extern "C" fn findora_ffi_transaction_builder_export_json(
+    builder: &TransactionBuilder,
+) -> *mut c_char {
+    string_or_null(builder.export_json(), FfiErrorCode::SerdeFailed)
+}
+
+/// Imports a builder exported by `findora_ffi_transaction_builder_export_json`,
+/// or a bare serialized transaction.
+#[no_mangle]
+pub extern "C" fn findora_ffi_transaction_builder_import_json(
+    s: *const c_char,
+) -> *mut TransactionBuilder {
+    if s.is_null() {
+        return boxed_or_null(Err("null json"), FfiErrorCode::NullPointer);
+    }
+    boxed_or_null(
+        TransactionBuilder::import_json(c_char_to_string(s)),
+        FfiErrorCode::SerdeFailed,
+    )
+}
@@ -93,3 +93,16 @@ fn test_asset_rules_from_str_empty_str_max_units() {
         serde_json::from_str::<PlatformAssetRules>(&actual_serialized_json).unwrap();
     assert_eq!(res.max_units, None);
 }
+
+#[test]
+fn t_transaction_builder_json_roundtrip() {
+    let kp = new_keypair();
+    let builder = TransactionBuilder::new(7)
+        .add_operation_undelegate(&kp)
+        .unwrap();
+    let json = builder.export_json().unwrap();
+    let imported = TransactionBuilder::import_json(json.clone()).unwrap();
+    assert_eq!(json, imported.export_json().unwrap());
+    assert_eq!(builder.estimated_size(), imported.estimated_size());
+    assert!(TransactionBuilder::import_json("{}".to_owned()).is_err());
+}
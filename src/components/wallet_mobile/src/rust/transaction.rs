@@ -13,7 +13,9 @@ use ledger::{
         gen_random_keypair, AssetTypeCode, AuthenticatedTransaction, Operation,
         TransferType, TxOutput,
     },
-    staking::{td_addr_to_bytes, PartialUnDelegation, TendermintAddr},
+    staking::{
+        params::ChainParamValues, td_addr_to_bytes, PartialUnDelegation, TendermintAddr,
+    },
 };
 use ruc::{d, eg, err::RucResult as NewRucResult, Result as RucResult};
 use serde_json::Result;
@@ -150,6 +152,8 @@ impl TransactionBuilder {
                         id: None,
                         record: new.0,
                         lien: None,
+                        cosig_rules: None,
+                        lock_until: 0,
                     },
                 });
                 base
@@ -369,6 +373,33 @@ impl TransactionBuilder {
                 memo: memo.into_noah(),
             })
     }
+
+    /// Size in bytes of the transaction as it would be submitted.
+    pub fn estimated_size(&self) -> usize {
+        self.get_builder().estimated_size()
+    }
+
+    /// Checks the transaction against the limits, the fee rule and the signature
+    /// checks of the node.
+    /// @param {string} params - Chain parameters in JSON, as served by `/params`.
+    pub fn validate_locally(&self, params: String) -> RucResult<()> {
+        let params = serde_json::from_str::<ChainParamValues>(&params).c(d!())?;
+        self.get_builder().validate_locally(&params).c(d!())
+    }
+
+    /// Exports the whole builder as versioned JSON.
+    pub fn export_json(&self) -> RucResult<String> {
+        self.get_builder().export_json().c(d!())
+    }
+
+    /// Imports a builder exported by `export_json`, or a bare serialized transaction.
+    pub fn import_json(s: String) -> RucResult<TransactionBuilder> {
+        PlatformTransactionBuilder::import_json(&s)
+            .c(d!())
+            .map(|transaction_builder| TransactionBuilder {
+                transaction_builder,
+            })
+    }
 }
 
 #[derive(Clone, Default)]
@@ -56,8 +56,8 @@ use {
             BLACK_HOLE_PUBKEY, BLACK_HOLE_PUBKEY_STAKING, TX_FEE_MIN,
         },
         staking::{
-            td_addr_to_bytes, PartialUnDelegation, TendermintAddr,
-            MAX_DELEGATION_AMOUNT, MIN_DELEGATION_AMOUNT,
+            params::ChainParamValues, td_addr_to_bytes, PartialUnDelegation,
+            TendermintAddr, MAX_DELEGATION_AMOUNT, MIN_DELEGATION_AMOUNT,
         },
     },
     rand_chacha::ChaChaRng,
@@ -305,6 +305,8 @@ impl TransactionBuilder {
                             id: None,
                             record: new.0,
                             lien: None,
+                            cosig_rules: None,
+                            lock_until: 0,
                         },
                     }
                     .to_json()
@@ -863,6 +865,55 @@ impl TransactionBuilder {
                 memo: memo.into_noah(),
             })
     }
+
+    /// Size in bytes of the transaction as it would be submitted.
+    pub fn estimated_size(&self) -> usize {
+        self.get_builder().estimated_size()
+    }
+
+    /// Number of operations in the transaction.
+    pub fn operation_count(&self) -> usize {
+        self.get_builder().operation_count()
+    }
+
+    /// Checks the transaction against the limits, the fee rule and the signature
+    /// checks of the node, before submitting it.
+    /// @param {string} params - Chain parameters in JSON, as served by `/params`.
+    /// @throws Will throw an error listing every violation.
+    pub fn validate_locally(&self, params: String) -> Result<(), JsValue> {
+        let params = serde_json::from_str::<ChainParamValues>(&params)
+            .c(d!())
+            .map_err(error_to_jsvalue)?;
+        self.get_builder()
+            .validate_locally(&params)
+            .c(d!())
+            .map_err(error_to_jsvalue)
+    }
+
+    /// One numbered line per operation, describing what is about to be signed.
+    pub fn to_summary(&self) -> String {
+        self.get_builder().to_summary()
+    }
+
+    /// Exports the whole builder as versioned JSON, to be resumed later by `import_json`.
+    pub fn export_json(&self) -> Result<String, JsValue> {
+        self.get_builder()
+            .export_json()
+            .c(d!())
+            .map_err(error_to_jsvalue)
+    }
+
+    /// Imports a builder exported by `export_json`, or a bare serialized transaction.
+    pub fn import_json(s: String) -> Result<TransactionBuilder, JsValue> {
+        let transaction_builder = PlatformTransactionBuilder::import_json(&s)
+            .c(d!())
+            .map_err(error_to_jsvalue)?;
+
+        Ok(TransactionBuilder {
+            transaction_builder,
+            commitments: Default::default(),
+        })
+    }
 }
 
 fn generate_extra(nonce: U256, fee: Option<U256>) -> SignedExtra {
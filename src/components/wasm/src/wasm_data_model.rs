@@ -497,6 +497,12 @@ impl AssetType {
     ///     "updatable":false,
     ///     "transfer_multisig_rules":null,
     ///     "max_units":5000
+    ///   },
+    ///   "metadata":{
+    ///     "decimals":6,
+    ///     "symbol":"TST",
+    ///     "name":"test token",
+    ///     "url":"https://example.com/tst.json"
     ///   }
     /// }
     ///
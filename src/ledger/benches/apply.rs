@@ -0,0 +1,265 @@
+//!
+//! # Benchmarks of the apply path of the ledger
+//!
+//! - `compute_effect`: `TxnEffect::compute_effect` of each kind of transaction
+//! - `apply_transaction`: checking a block of effects against the ledger
+//! - `finish_block`: committing a block of the configured mix
+//!
+//! The transactions of a block are configured by `LEDGER_BENCH_MIX`,
+//! e.g. `plain=20,confidential=5,anon=1`, the default.
+//!
+//! - `plain`: issue and transfer of non-confidential records
+//! - `confidential`: issue and transfer of confidential records
+//! - `anon`: conversion of a record to an anonymous one, its proof is
+//!   generated in the setup, which dominates the time of `finish_block`
+//!
+
+use {
+    criterion::{criterion_group, criterion_main, Criterion},
+    ledger::{
+        data_model::{
+            AssetRules, AssetTypeCode, BarAnonConvNote, BarToAbarOps, BlockEffect,
+            Operation, Transaction, TxnEffect,
+        },
+        store::{helpers::*, LedgerState},
+    },
+    rand_chacha::ChaChaRng,
+    rand_core::SeedableRng,
+    ruc::*,
+    std::{
+        env,
+        time::{Duration, Instant},
+    },
+    zei::{
+        noah_api::{
+            anon_xfr::ar_to_abar::gen_ar_to_abar_note,
+            parameters::ProverParams,
+            xfr::asset_record::{open_blind_asset_record, AssetRecordType},
+        },
+        XfrKeyPair,
+    },
+};
+
+const DEFAULT_MIX: &str = "plain=20,confidential=5,anon=1";
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Plain,
+    Confidential,
+    Anon,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Plain => "plain",
+            Kind::Confidential => "confidential",
+            Kind::Anon => "anon",
+        }
+    }
+}
+
+// the number of transactions of each kind in a block
+fn mix() -> Vec<(Kind, usize)> {
+    let mix = env::var("LEDGER_BENCH_MIX").unwrap_or_else(|_| DEFAULT_MIX.to_owned());
+    pnk!(mix
+        .split(',')
+        .map(|kv| {
+            let (k, n) = kv.split_once('=').c(d!(kv))?;
+            let kind = match k.trim() {
+                "plain" => Kind::Plain,
+                "confidential" => Kind::Confidential,
+                "anon" => Kind::Anon,
+                k => return Err(eg!(format!("unknown kind: {k}"))),
+            };
+            Ok((kind, n.trim().parse::<usize>().c(d!(kv))?))
+        })
+        .collect::<Result<Vec<_>>>())
+}
+
+// A ledger with an asset defined by `issuer`
+struct Fixture {
+    ledger: LedgerState,
+    issuer: XfrKeyPair,
+    code: AssetTypeCode,
+    seq_num: u64,
+    prng: ChaChaRng,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let mut prng = ChaChaRng::from_seed([0; 32]);
+        let mut ledger = LedgerState::tmp_ledger();
+        let issuer = build_keys(&mut prng);
+        let code = AssetTypeCode::gen_random();
+        let tx = pnk!(create_definition_transaction(
+            &code,
+            &issuer,
+            AssetRules::default(),
+            None,
+            ledger.get_block_commit_count(),
+        ));
+        apply_transaction(&mut ledger, tx);
+
+        Fixture {
+            ledger,
+            issuer,
+            code,
+            seq_num: 0,
+            prng,
+        }
+    }
+
+    fn txn(&mut self, kind: Kind) -> Transaction {
+        self.seq_num += 1;
+        let recipient = build_keys(&mut self.prng).get_pk();
+        let record_type = match kind {
+            Kind::Plain => {
+                AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType
+            }
+            Kind::Confidential => {
+                AssetRecordType::ConfidentialAmount_ConfidentialAssetType
+            }
+            Kind::Anon => return self.anon_txn(),
+        };
+        create_issue_and_transfer_txn_with_record_type(
+            &mut self.ledger,
+            &self.code,
+            100,
+            &self.issuer,
+            &recipient,
+            self.seq_num,
+            record_type,
+        )
+        .0
+    }
+
+    // issue a record in its own block, then convert it
+    fn anon_txn(&mut self) -> Transaction {
+        let tx = create_issuance_txn(
+            &mut self.ledger,
+            &self.code,
+            100,
+            self.seq_num,
+            AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+            &self.issuer,
+        );
+        let (_, txo_sids) = apply_transaction(&mut self.ledger, tx);
+        let txo_sid = txo_sids[0];
+        let record = pnk!(self.ledger.get_utxo_light(txo_sid)).utxo.0.record;
+        let oar = pnk!(open_blind_asset_record(
+            &record.into_noah(),
+            &None,
+            &self.issuer.into_noah()
+        ));
+
+        let receiver = build_keys(&mut self.prng).get_pk();
+        let params = pnk!(ProverParams::gen_ar_to_abar());
+        let note = pnk!(gen_ar_to_abar_note(
+            &mut self.prng,
+            &params,
+            &oar,
+            &self.issuer.into_noah(),
+            &receiver.into_noah(),
+        ));
+
+        let mut tx = Transaction::from_seq_id(self.ledger.get_block_commit_count());
+        let op = pnk!(BarToAbarOps::new(
+            BarAnonConvNote::ArNote(Box::new(note)),
+            txo_sid,
+            tx.body.no_replay_token,
+        ));
+        tx.add_operation(Operation::BarToAbar(Box::new(op)));
+        tx
+    }
+
+    fn block(&mut self, mix: &[(Kind, usize)]) -> Vec<Transaction> {
+        mix.iter()
+            .flat_map(|(kind, n)| vec![*kind; *n])
+            .map(|kind| self.txn(kind))
+            .collect()
+    }
+
+    fn effects(&self, txns: &[Transaction]) -> Vec<TxnEffect> {
+        let features = self.ledger.get_staking().active_features();
+        txns.iter()
+            .map(|tx| pnk!(TxnEffect::compute_effect(tx.clone(), &features)))
+            .collect()
+    }
+}
+
+fn compute_effect(c: &mut Criterion) {
+    let mut f = Fixture::new();
+    let features = f.ledger.get_staking().active_features();
+    let mut group = c.benchmark_group("compute_effect");
+    for kind in [Kind::Plain, Kind::Confidential, Kind::Anon] {
+        let tx = f.txn(kind);
+        group.bench_function(kind.name(), |b| {
+            b.iter(|| pnk!(TxnEffect::compute_effect(tx.clone(), &features)))
+        });
+    }
+    group.finish();
+}
+
+// the block is not committed, so the same transactions are checked each time
+fn apply_txn_effects(c: &mut Criterion) {
+    let mut f = Fixture::new();
+    let txns = f.block(&mix());
+    let effects = f.effects(&txns);
+    let ledger = &f.ledger;
+
+    c.bench_function("apply_transaction", |b| {
+        b.iter_batched(
+            || {
+                let mut block = BlockEffect::default();
+                *block.get_staking_simulator_mut() = ledger.get_staking().clone();
+                (block, effects.clone())
+            },
+            |(mut block, effects)| {
+                for te in effects {
+                    pnk!(ledger.apply_transaction(&mut block, te));
+                }
+                block
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+// every iteration commits a new block, only `finish_block` is timed
+fn commit_block(c: &mut Criterion) {
+    let mut f = Fixture::new();
+    let mix = mix();
+
+    let mut group = c.benchmark_group("finish_block");
+    group.sample_size(10);
+    group.bench_function(txns_desc(&mix), |b| {
+        b.iter_custom(|iters| {
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..iters {
+                let txns = f.block(&mix);
+                let effects = f.effects(&txns);
+                let mut block = pnk!(f.ledger.start_block());
+                for te in effects {
+                    pnk!(f.ledger.apply_transaction(&mut block, te));
+                }
+
+                let start = Instant::now();
+                pnk!(f.ledger.finish_block(block));
+                elapsed += start.elapsed();
+            }
+            elapsed
+        })
+    });
+    group.finish();
+}
+
+fn txns_desc(mix: &[(Kind, usize)]) -> String {
+    mix.iter()
+        .map(|(k, n)| format!("{}={}", k.name(), n))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+criterion_group!(benches, compute_effect, apply_txn_effects, commit_block);
+criterion_main!(benches);
@@ -0,0 +1,103 @@
+//!
+//! # Export the state of a ledger as the genesis of a new chain
+//!
+//! ```text
+//! export-genesis <ledger_dir> <genesis_in> <genesis_out> <new_chain_id>
+//! ```
+//!
+//! `genesis_in` is the tendermint genesis of the old chain, its consensus
+//! parameters and genesis time are kept. The chain id, the initial height,
+//! the validators and the `app_state` are replaced by the ones of the ledger.
+//!
+//! The node must be stopped during the export.
+//!
+
+use {
+    ledger::store::{genesis::AppState, LedgerState},
+    ruc::*,
+    serde_json::{json, Value},
+    std::{env, fs},
+};
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let res = match args.iter().map(|a| a.as_str()).collect::<Vec<_>>()[..] {
+        [ledger_dir, genesis_in, genesis_out, chain_id] => {
+            export(ledger_dir, genesis_in, genesis_out, chain_id)
+        }
+        _ => Err(eg!(
+            "usage: export-genesis <ledger_dir> <genesis_in> <genesis_out> <new_chain_id>"
+        )),
+    };
+    if let Err(e) = res {
+        e.print(None);
+        std::process::exit(1);
+    }
+}
+
+fn export(
+    ledger_dir: &str,
+    genesis_in: &str,
+    genesis_out: &str,
+    chain_id: &str,
+) -> Result<()> {
+    let mut genesis =
+        serde_json::from_slice::<Value>(&fs::read(genesis_in).c(d!(genesis_in))?)
+            .c(d!())?;
+    let old_chain_id = genesis["chain_id"]
+        .as_str()
+        .c(d!("no chain_id in the genesis"))?
+        .to_owned();
+
+    env::set_var("BNC_DATA_DIR", format!("{ledger_dir}/__bnc__"));
+    let ledger = LedgerState::load_or_init(ledger_dir).c(d!())?;
+    let g = ledger.export_genesis(&old_chain_id).c(d!())?;
+
+    let validators = ledger
+        .get_staking()
+        .validator_get_current()
+        .c(d!("no validators"))?
+        .body
+        .values()
+        .filter(|v| 0 < v.td_power)
+        .map(|v| {
+            json!({
+                "address": hex::encode_upper(&v.td_addr),
+                "pub_key": {
+                    "type": "tendermint/PubKeyEd25519",
+                    "value": base64::encode(&v.td_pubkey),
+                },
+                "power": v.td_power.to_string(),
+                "name": "",
+            })
+        })
+        .collect::<Vec<_>>();
+    if validators.is_empty() {
+        return Err(eg!("no validators with vote power"));
+    }
+
+    let (height, utxos, abars, n_validators) = (
+        g.checkpoint.height,
+        g.utxos.len(),
+        g.checkpoint.abar_count,
+        validators.len(),
+    );
+    let commitment = serde_json::to_string(&g.checkpoint.state_commitment).c(d!())?;
+
+    genesis["chain_id"] = json!(chain_id);
+    genesis["initial_height"] = json!((height + 1).to_string());
+    genesis["validators"] = json!(validators);
+    genesis["app_hash"] = json!("");
+    genesis["app_state"] = serde_json::to_value(AppState { ledger: Some(g) }).c(d!())?;
+    fs::write(genesis_out, serde_json::to_vec_pretty(&genesis).c(d!())?)
+        .c(d!(genesis_out))?;
+
+    println!(
+        "exported {old_chain_id} at height {height}, state commitment {commitment}"
+    );
+    println!("{utxos} unspent TXOs, {n_validators} validators");
+    if 0 < abars {
+        println!("WARNING: {abars} anonymous records are not exported");
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//!
+//! # Replay a recorded block log at full speed
+//!
+//! ```text
+//! ledger-bench dump <ledger_dir> <blocks_file>
+//! ledger-bench replay <blocks_file>
+//! ```
+//!
+//! `dump` records the blocks of a ledger, one json object per line,
+//! `replay` applies them to an empty ledger and reports the throughput.
+//! The storage is global to a process, so run the two in turn.
+//!
+
+use {
+    ledger::{data_model::FinalizedBlock, store::LedgerState},
+    ruc::*,
+    std::{
+        env,
+        fs::File,
+        io::{BufRead, BufReader},
+        path::Path,
+        time::{Duration, Instant},
+    },
+};
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let res = match args.iter().map(|a| a.as_str()).collect::<Vec<_>>()[..] {
+        ["dump", ledger_dir, blocks] => dump(ledger_dir, blocks),
+        ["replay", blocks] => replay(blocks),
+        _ => Err(eg!(
+            "usage: ledger-bench dump <ledger_dir> <blocks_file> | replay <blocks_file>"
+        )),
+    };
+    if let Err(e) = res {
+        e.print(None);
+        std::process::exit(1);
+    }
+}
+
+fn dump(ledger_dir: &str, blocks: &str) -> Result<()> {
+    env::set_var("BNC_DATA_DIR", format!("{ledger_dir}/__bnc__"));
+    let ledger = LedgerState::load_or_init(ledger_dir).c(d!())?;
+    let n = ledger.dump_blocks(blocks).c(d!())?;
+    println!("{n} blocks dumped to {blocks}");
+    Ok(())
+}
+
+fn replay(blocks: &str) -> Result<()> {
+    let dir = globutils::fresh_tmp_dir();
+    env::set_var("BNC_DATA_DIR", dir.join("__bnc__"));
+    let ledger_dir = dir.join("ledger").to_string_lossy().into_owned();
+    let mut ledger = LedgerState::new(&ledger_dir, Some("bench")).c(d!())?;
+
+    let r = BufReader::new(File::open(Path::new(blocks)).c(d!(blocks))?);
+    let mut n_blocks = 0u64;
+    let mut n_txns = 0u64;
+    let mut elapsed = Duration::ZERO;
+    let mut slowest = (0, Duration::ZERO);
+    let mut divergences = vec![];
+    for line in r.lines() {
+        // parsing is not timed
+        let b: FinalizedBlock = serde_json::from_str(&line.c(d!())?).c(d!())?;
+        let txns = b.txns.len() as u64;
+
+        let start = Instant::now();
+        ledger.replay_block(b, &mut divergences).c(d!())?;
+        let t = start.elapsed();
+
+        n_blocks += 1;
+        n_txns += txns;
+        elapsed += t;
+        if slowest.1 < t {
+            slowest = (n_blocks, t);
+        }
+        if !divergences.is_empty() {
+            break;
+        }
+    }
+
+    let secs = elapsed.as_secs_f64();
+    println!("blocks: {n_blocks}, txns: {n_txns}, seconds: {secs:.3}");
+    println!(
+        "TPS: {:.1}, blocks per second: {:.1}",
+        n_txns as f64 / secs,
+        n_blocks as f64 / secs
+    );
+    println!(
+        "slowest block: {} in {} ms",
+        slowest.0,
+        slowest.1.as_millis()
+    );
+
+    for d in divergences.iter() {
+        println!(
+            "diverged at height {}, {}: expected {}, found {}",
+            d.height, d.what, d.expected, d.found
+        );
+    }
+    if divergences.is_empty() {
+        Ok(())
+    } else {
+        Err(eg!(
+            "the replay diverges, the throughput covers the blocks before"
+        ))
+    }
+}
@@ -0,0 +1,62 @@
+//!
+//! # Amounts in the units of the assets
+//!
+//! The ledger only knows the smallest units of an asset, people read and
+//! write amounts in units scaled down by its decimals, e.g. `12.345` FRA
+//! is `12345000` on the ledger as FRA has 6 decimals.
+//!
+
+use {super::MAX_DECIMALS_LENGTH, ruc::*};
+
+/// The smallest units in `s`, a decimal string like `12.345`,
+/// of an asset with `decimals` decimals.
+///
+/// More fractional digits than `decimals`, signs, exponents and
+/// amounts beyond `u64::MAX` are refused rather than rounded.
+pub fn parse_amount(s: &str, decimals: u8) -> Result<u64> {
+    if decimals > MAX_DECIMALS_LENGTH {
+        return Err(eg!(format!("invalid decimals: {decimals}")));
+    }
+
+    let s = s.trim();
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    if (int.is_empty() && frac.is_empty())
+        || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return Err(eg!(format!("invalid amount: `{s}`")));
+    }
+    if frac.len() > decimals as usize {
+        return Err(eg!(format!(
+            "too many fractional digits in `{s}`, the asset has {decimals} decimals"
+        )));
+    }
+
+    let unit = 10_u64.pow(decimals as u32);
+    let digits = |d: &str| {
+        if d.is_empty() {
+            Ok(0)
+        } else {
+            d.parse::<u64>().c(d!(format!("amount overflow: `{s}`")))
+        }
+    };
+    let frac = digits(frac)? * 10_u64.pow((decimals as usize - frac.len()) as u32);
+    digits(int)?
+        .checked_mul(unit)
+        .and_then(|am| am.checked_add(frac))
+        .c(d!(format!("amount overflow: `{s}`")))
+}
+
+/// `am` smallest units of an asset with `decimals` decimals, as a decimal
+/// string without trailing zeros, e.g. `12.345`, the inverse of `parse_amount`
+pub fn format_amount(am: u64, decimals: u8) -> String {
+    // 10^20 does not fit in a u64, `decimals` is checked on definition
+    let decimals = decimals.min(MAX_DECIMALS_LENGTH);
+    let unit = 10_u64.pow(decimals as u32);
+    let frac = format!("{:0w$}", am % unit, w = decimals as usize);
+    let frac = frac.trim_end_matches('0');
+    if frac.is_empty() {
+        format!("{}", am / unit)
+    } else {
+        format!("{}.{}", am / unit, frac)
+    }
+}
@@ -1,18 +1,24 @@
 use {
     crate::{
         data_model::{
-            AbarConvNote, AbarToBarOps, AnonTransferOps, AssetType, AssetTypeCode,
-            BarToAbarOps, DefineAsset, IssueAsset, IssuerPublicKey, Memo, NoReplayToken,
-            Operation, Transaction, TransferAsset, TransferType, TxOutput, TxnTempSID,
-            TxoRef, TxoSID, UpdateMemo,
+            AbarConvNote, AbarToBarOps, AnchorData, AnonTransferOps, AssetType,
+            AssetTypeCode, BarToAbarOps, CustomAssetPolicy, DefineAsset, IssueAsset,
+            IssuerPublicKey, Memo, NoReplayToken, Operation, Reject, Transaction,
+            TransferAsset, TransferType, TxOutput, TxnResult, TxnTempSID,
+            TxnValidationError, TxoRef, TxoSID, UpdateMemo, UpdateTransferPolicy,
+            MAX_ANCHORS_PER_TXN,
         },
         staking::{
             self,
+            features::{Feature, FeatureSet},
             ops::{
-                claim::ClaimOps, delegation::DelegationOps,
-                fra_distribution::FraDistributionOps, governance::GovernanceOps,
-                replace_staker::ReplaceStakerOps, undelegation::UnDelegationOps,
-                update_staker::UpdateStakerOps, update_validator::UpdateValidatorOps,
+                cancel_schedule::CancelScheduleOps, claim::ClaimOps,
+                delegation::DelegationOps, fra_distribution::FraDistributionOps,
+                governance::GovernanceOps, redelegation::ReDelegationOps,
+                replace_staker::ReplaceStakerOps,
+                schedule_transfer::ScheduleTransferOps, undelegation::UnDelegationOps,
+                update_params::UpdateParamsOps, update_staker::UpdateStakerOps,
+                update_validator::UpdateValidatorOps,
             },
         },
     },
@@ -37,7 +43,7 @@ use {
             },
             parameters::bulletproofs::BulletproofParams,
             xfr::{
-                structs::{XfrAmount, XfrAssetType},
+                structs::{TracingPolicies, XfrAmount, XfrAssetType},
                 verify_xfr_body,
             },
         },
@@ -83,12 +89,25 @@ pub struct TxnEffect {
     pub asset_types_involved: HashSet<AssetTypeCode>,
     /// Memo updates
     pub memo_updates: Vec<(AssetTypeCode, XfrPublicKey, Memo)>,
+    /// Transfer policy updates
+    pub policy_updates: Vec<(AssetTypeCode, XfrPublicKey, CustomAssetPolicy)>,
+    /// Senders and receivers of non-confidential asset types in transfers,
+    /// checked against the transfer policies
+    pub transfer_parties: Vec<(AssetTypeCode, XfrPublicKey)>,
+    /// Tracing policies declared for the non-confidential inputs and outputs
+    /// of transfers, checked against the tracing policies of the assets,
+    /// empty before asset tracing is active
+    pub tracing_policies: Vec<(AssetTypeCode, TracingPolicies)>,
+    /// Non-confidential asset types converted to anonymous records
+    pub bar_conv_types: HashSet<AssetTypeCode>,
 
     /// Staking operations
     pub delegations: Vec<DelegationOps>,
     /// Staking operations
     pub undelegations: Vec<UnDelegationOps>,
     /// Staking operations
+    pub redelegations: Vec<ReDelegationOps>,
+    /// Staking operations
     pub claims: Vec<ClaimOps>,
     /// Staking operations
     pub governances: Vec<GovernanceOps>,
@@ -97,6 +116,12 @@ pub struct TxnEffect {
     /// Staking operations
     pub fra_distributions: Vec<FraDistributionOps>,
     /// Staking operations
+    pub update_params: Vec<UpdateParamsOps>,
+    /// Staking operations
+    pub schedule_transfers: Vec<ScheduleTransferOps>,
+    /// Staking operations
+    pub cancel_schedules: Vec<CancelScheduleOps>,
+    /// Staking operations
     pub update_stakers: Vec<UpdateStakerOps>,
     /// Newly created Anon Blind Asset Records
     pub bar_conv_abars: Vec<AnonAssetRecord>,
@@ -131,15 +156,29 @@ impl TxnEffect {
     /// `input_txos` and that Transfer should be valid if all those TXO SIDs
     /// exist unspent in the ledger and correspond to the correct
     /// TxOutput).
-    pub fn compute_effect(txn: Transaction) -> Result<TxnEffect> {
+    ///
+    /// The operations relying on a feature which is not in `features`,
+    /// the ones active at the height of the block, are rejected.
+    pub fn compute_effect(
+        txn: Transaction,
+        features: &FeatureSet,
+    ) -> TxnResult<TxnEffect> {
         let mut te = TxnEffect::default();
         let mut txo_count: usize = 0;
+        let mut anchors: usize = 0;
+
+        if let Some(expiry) = txn.body.expiry {
+            features.require(Feature::TxnExpiry).c(d!())?;
+            if expiry < txn.body.no_replay_token.get_seq_id() {
+                return Err(eg!("transaction expires before its seq_id").into());
+            }
+        }
 
         for op in txn.body.operations.iter() {
             macro_rules! check_nonce {
                 ($i: expr) => {
                     if $i.get_nonce() != txn.body.no_replay_token {
-                        return Err(eg!(("nonce does not match")));
+                        return Err(eg!(("nonce does not match")).into());
                     }
                 };
             }
@@ -152,7 +191,12 @@ impl TxnEffect {
                     });
                 }
                 Operation::TransferAsset(trn) => {
-                    te.add_transfer_asset(trn, &mut txo_count).c(d!())?;
+                    te.add_transfer_asset(trn, features, &mut txo_count)?;
+                }
+                Operation::SwapAsset(swap) => {
+                    features.require(Feature::AssetSwap).c(d!())?;
+                    te.add_transfer_asset(&swap.transfer, features, &mut txo_count)?;
+                    swap.check_terms()?;
                 }
                 Operation::Claim(i) => {
                     check_nonce!(i);
@@ -169,6 +213,12 @@ impl TxnEffect {
                     i.verify().c(d!())?;
                     te.undelegations.push(i.as_ref().clone());
                 }
+                Operation::ReDelegation(i) => {
+                    check_nonce!(i);
+                    features.require(Feature::ReDelegation).c(d!())?;
+                    i.verify().c(d!())?;
+                    te.redelegations.push(i.clone());
+                }
                 Operation::UpdateStaker(i) => {
                     check_nonce!(i);
                     i.verify().c(d!())?;
@@ -187,18 +237,28 @@ impl TxnEffect {
                         .insert(i.data.height, i.clone())
                         .is_some()
                     {
-                        return Err(eg!("dup entries"));
+                        return Err(eg!("dup entries").into());
                     }
                 }
                 Operation::DefineAsset(def) => {
-                    te.add_define_asset(def).c(d!())?;
+                    if def.body.asset.metadata.is_some() {
+                        features.require(Feature::AssetMetadata).c(d!())?;
+                    }
+                    if def.body.asset.asset_rules.transfer_policy.is_some() {
+                        features.require(Feature::TransferPolicy).c(d!())?;
+                    }
+                    te.add_define_asset(def)?;
                 }
                 Operation::IssueAsset(iss) => {
-                    te.add_issue_asset(iss, &mut txo_count).c(d!())?;
+                    te.add_issue_asset(iss, &mut txo_count)?;
                 }
                 Operation::UpdateMemo(update_memo) => {
                     te.add_update_memo(&txn, update_memo).c(d!())?;
                 }
+                Operation::UpdateTransferPolicy(update) => {
+                    features.require(Feature::TransferPolicy).c(d!())?;
+                    te.add_update_transfer_policy(&txn, update).c(d!())?;
+                }
                 Operation::Governance(i) => {
                     check_nonce!(i);
                     te.governances.push(i.clone());
@@ -207,6 +267,33 @@ impl TxnEffect {
                     check_nonce!(i);
                     te.fra_distributions.push(i.clone());
                 }
+                Operation::UpdateParams(i) => {
+                    check_nonce!(i);
+                    te.update_params.push(i.clone());
+                }
+                Operation::ScheduleTransfer(i) => {
+                    check_nonce!(i);
+                    features.require(Feature::ScheduledTransfer).c(d!())?;
+                    i.verify().c(d!())?;
+                    te.schedule_transfers.push(i.as_ref().clone());
+                }
+                Operation::CancelSchedule(i) => {
+                    check_nonce!(i);
+                    features.require(Feature::ScheduledTransfer).c(d!())?;
+                    i.verify().c(d!())?;
+                    te.cancel_schedules.push(i.clone());
+                }
+                Operation::AnchorData(anchor) => {
+                    features.require(Feature::DataAnchor).c(d!())?;
+                    anchors += 1;
+                    if MAX_ANCHORS_PER_TXN < anchors {
+                        return Err(eg!(format!(
+                            "at most {MAX_ANCHORS_PER_TXN} anchors per transaction"
+                        ))
+                        .into());
+                    }
+                    te.add_anchor_data(&txn, anchor).c(d!())?;
+                }
                 Operation::ConvertAccount(i) => {
                     check_nonce!(i)
                 }
@@ -234,12 +321,22 @@ impl TxnEffect {
     //         - Fully checked here
     //     2) The token id is available.
     //         - Partially checked here
-    fn add_define_asset(&mut self, def: &DefineAsset) -> Result<()> {
+    fn add_define_asset(&mut self, def: &DefineAsset) -> TxnResult<()> {
         // (1)
-        def.signature.verify(&def.pubkey.key, &def.body).c(d!())?;
+        def.signature
+            .verify(&def.pubkey.key, &def.body)
+            .c(d!())
+            .reject(TxnValidationError::SignatureInvalid)?;
 
         let code = def.body.asset.code;
 
+        if let Some(metadata) = def.body.asset.metadata.as_ref() {
+            metadata
+                .check(def.body.asset.asset_rules.decimals)
+                .c(d!())
+                .reject(TxnValidationError::PolicyViolation)?;
+        }
+
         let token = AssetType {
             properties: *def.body.asset.clone(),
             ..Default::default()
@@ -249,7 +346,7 @@ impl TxnEffect {
         if self.new_asset_codes.contains_key(&code)
             || self.new_issuance_nums.contains_key(&code)
         {
-            return Err(eg!());
+            return Err(eg!().into());
         }
 
         self.issuance_keys.insert(code, token.properties.issuer);
@@ -275,9 +372,9 @@ impl TxnEffect {
         &mut self,
         iss: &IssueAsset,
         txo_count: &mut usize,
-    ) -> Result<()> {
+    ) -> TxnResult<()> {
         if iss.body.num_outputs != iss.body.records.len() {
-            return Err(eg!());
+            return Err(eg!().into());
         }
 
         let code = iss.body.code;
@@ -291,18 +388,21 @@ impl TxnEffect {
 
         if let Some(last_num) = iss_nums.last() {
             if seq_num <= *last_num {
-                return Err(eg!());
+                return Err(TxnValidationError::IssuanceReplay.reject(eg!()));
             }
         }
         iss_nums.push(seq_num);
 
         // (2)
-        iss.signature.verify(&iss.pubkey.key, &iss.body).c(d!())?;
+        iss.signature
+            .verify(&iss.pubkey.key, &iss.body)
+            .c(d!())
+            .reject(TxnValidationError::SignatureInvalid)?;
 
         // (3)
         if let Some(prior_key) = self.issuance_keys.get(&code) {
             if iss.pubkey != *prior_key {
-                return Err(eg!());
+                return Err(TxnValidationError::SignatureInvalid.reject(eg!()));
             }
         } else {
             self.issuance_keys.insert(code, iss.pubkey);
@@ -312,7 +412,7 @@ impl TxnEffect {
         for (output, _) in iss.body.records.iter() {
             // (4)
             if output.record.public_key != iss.pubkey.key {
-                return Err(eg!());
+                return Err(eg!().into());
             }
 
             // ONLY SIMPLE TxOutputs!
@@ -321,14 +421,16 @@ impl TxnEffect {
                     id: None,
                     record: output.record.clone(),
                     lien: None,
+                    cosig_rules: None,
+                    lock_until: 0,
                 })
             {
-                return Err(eg!());
+                return Err(eg!().into());
             }
 
             // (5)
             if output.record.asset_type != XfrAssetType::NonConfidential(code.val) {
-                return Err(eg!());
+                return Err(eg!().into());
             }
 
             if let XfrAmount::NonConfidential(amt) = output.record.amount {
@@ -345,8 +447,9 @@ impl TxnEffect {
     }
 
     // An asset transfer is valid iff:
-    //     1) The signatures on the body (a) all are valid and (b)
-    //        there is a signature for each input key
+    //     1) The signatures on the body (a) all are valid, (b)
+    //        there is a signature for each input key and (c) the
+    //        co-signatures of each input reach the threshold of its rules
     //          - Fully checked here
     //     2) The UTXOs (a) exist on the ledger and (b) match the noah transaction.
     //          - Partially checked here -- anything which hasn't
@@ -361,19 +464,30 @@ impl TxnEffect {
     fn add_transfer_asset(
         &mut self,
         trn: &TransferAsset,
+        features: &FeatureSet,
         txo_count: &mut usize,
-    ) -> Result<()> {
+    ) -> TxnResult<()> {
         let params = &mut *PARAMS.lock();
         let prng = &mut *PRNG.lock();
 
+        // co-signature rules are neither accepted nor checked before the feature
+        let cosig_active = features.is_active(Feature::CoSignature);
+        if !trn.body.input_cosig_rules.is_empty()
+            || trn.body.outputs.iter().any(|o| o.cosig_rules.is_some())
+        {
+            features.require(Feature::CoSignature).c(d!())?;
+        }
+
         if trn.body.inputs.len() != trn.body.transfer.inputs.len() {
-            return Err(eg!());
+            return Err(eg!().into());
         }
         if trn.body.outputs.len() != trn.body.transfer.outputs.len() {
-            return Err(eg!());
+            return Err(eg!().into());
         }
 
-        // Refuse any transfer with policies for now
+        // Asset tracing policies and proofs are refused before the feature,
+        // once it is active, the proofs are checked by `verify_xfr_body` below
+        let tracing_active = features.is_active(Feature::AssetTracing);
         let c1 = trn
             .body
             .policies
@@ -386,6 +500,24 @@ impl TxnEffect {
             .outputs_tracing_policies
             .iter()
             .any(|x| !x.is_empty());
+        let c5 = trn
+            .body
+            .transfer
+            .asset_tracing_memos
+            .iter()
+            .any(|x| !x.is_empty());
+        let c8 = !trn
+            .body
+            .transfer
+            .proofs
+            .asset_tracing_proof
+            .asset_type_and_amount_proofs
+            .is_empty();
+        if !tracing_active && (c1 || c2 || c5 || c8) {
+            return Err(eg!().into());
+        }
+
+        // Refuse any transfer with identity tracing for now
         let c3 = trn
             .body
             .policies
@@ -398,12 +530,6 @@ impl TxnEffect {
             .outputs_sig_commitments
             .iter()
             .any(|x| !x.is_none());
-        let c5 = trn
-            .body
-            .transfer
-            .asset_tracing_memos
-            .iter()
-            .any(|x| !x.is_empty());
         let c6 = trn
             .body
             .transfer
@@ -420,15 +546,9 @@ impl TxnEffect {
             .outputs_identity_proofs
             .iter()
             .any(|x| !x.is_empty());
-        let c8 = !trn
-            .body
-            .transfer
-            .proofs
-            .asset_tracing_proof
-            .asset_type_and_amount_proofs
-            .is_empty();
-        if c1 || c2 || c3 || c4 || c5 || c6 || c7 || c8 {
-            return Err(eg!());
+        if c3 || c4 || c6 || c7 {
+            return Err(TxnValidationError::PolicyViolation
+                .reject(eg!("identity tracing is not supported")));
         }
 
         // Transfer outputs must match outputs noah transaction
@@ -439,7 +559,7 @@ impl TxnEffect {
             .zip(trn.body.transfer.outputs.iter())
         {
             if output.record != record.clone() {
-                return Err(eg!());
+                return Err(TxnValidationError::AmountMismatch.reject(eg!()));
             }
         }
 
@@ -447,7 +567,7 @@ impl TxnEffect {
         if !trn.body.lien_assignments.is_empty()
             || trn.body.transfer_type != TransferType::Standard
         {
-            return Err(eg!());
+            return Err(eg!().into());
         }
         let (lien_inputs, lien_outputs) = {
             let mut inps = trn
@@ -472,7 +592,7 @@ impl TxnEffect {
                         *ele_out = Some(hash);
                     }
                     _ => {
-                        return Err(eg!());
+                        return Err(eg!().into());
                     }
                 }
             }
@@ -486,7 +606,7 @@ impl TxnEffect {
                 // (1a) all body signatures are valid
                 for sig in &trn.body_signatures {
                     if !trn.body.verify_body_signature(sig) {
-                        return Err(eg!());
+                        return Err(TxnValidationError::SignatureInvalid.reject(eg!()));
                     }
                     input_keys.insert(sig.address.key.noah_to_bytes());
                 }
@@ -494,32 +614,62 @@ impl TxnEffect {
                 // (1b) all input record owners have signed
                 for record in trn.body.transfer.inputs.iter() {
                     if !input_keys.contains(&record.public_key.noah_to_bytes()) {
-                        return Err(eg!());
+                        return Err(TxnValidationError::SignatureInvalid
+                            .reject(eg!("missing the signature of an input owner")));
                     }
                 }
 
+                // (1c) inputs with co-signature rules have been co-signed
+                if cosig_active {
+                    trn.body
+                        .check_cosignatures(&trn.body_signatures)
+                        .c(d!())
+                        .reject(TxnValidationError::SignatureInvalid)?;
+                }
+
                 verify_xfr_body(
                     prng,
                     params,
                     &trn.body.transfer.into_noah(),
                     &trn.body.policies.to_ref(),
                 )
-                .c(d!())?;
+                .c(d!())
+                .reject(TxnValidationError::AmountMismatch)?;
             }
         }
         // (3)
         let mut input_types = HashSet::new();
-        for ((inp, record), lien) in trn
+        for (idx, ((inp, record), lien)) in trn
             .body
             .inputs
             .iter()
             .zip(trn.body.transfer.inputs.iter())
             .zip(lien_inputs)
+            .enumerate()
         {
+            let cosig_rules = trn.body.input_cosig_rules(idx).cloned();
+
             // NOTE: We assume that any confidential-type asset records
             // have no atypical transfer restrictions. Be careful!
             if let Some(inp_code) = record.asset_type.get_asset_type() {
                 input_types.insert(AssetTypeCode { val: inp_code });
+                self.transfer_parties.push((
+                    AssetTypeCode { val: inp_code },
+                    XfrPublicKey::from_noah(&record.public_key),
+                ));
+                // a missing entry declares no policies, the ledger refuses it
+                // for a traced asset
+                if tracing_active {
+                    self.tracing_policies.push((
+                        AssetTypeCode { val: inp_code },
+                        trn.body
+                            .policies
+                            .inputs_tracing_policies
+                            .get(idx)
+                            .cloned()
+                            .unwrap_or_default(),
+                    ));
+                }
                 //asset_types_involved.insert(AssetTypeCode { val: inp_code });
             }
 
@@ -529,18 +679,22 @@ impl TxnEffect {
                 TxoRef::Relative(offs) => {
                     // (2).(a)
                     if offs as usize >= *txo_count {
-                        return Err(eg!());
+                        return Err(eg!().into());
                     }
                     let ix = (*txo_count - 1) - (offs as usize);
                     match &self.txos[ix] {
                         None => {
-                            return Err(eg!());
+                            return Err(TxnValidationError::InputSpent.reject(eg!()));
                         }
                         Some(txo) => {
                             // (2).(b)
-                            if txo.record != record.clone() || txo.lien != lien.cloned()
+                            if txo.record != record.clone()
+                                || txo.lien != lien.cloned()
+                                || txo.cosig_rules != cosig_rules
                             {
-                                return Err(eg!());
+                                return Err(
+                                    TxnValidationError::AmountMismatch.reject(eg!())
+                                );
                             }
                             self.internally_spent_txos.push(txo.clone());
                         }
@@ -550,7 +704,7 @@ impl TxnEffect {
                 TxoRef::Absolute(txo_sid) => {
                     // (2).(a), partially
                     if self.input_txos.contains_key(&txo_sid) {
-                        return Err(eg!());
+                        return Err(TxnValidationError::InputSpent.reject(eg!()));
                     }
 
                     self.input_txos.insert(
@@ -559,6 +713,8 @@ impl TxnEffect {
                             id: None,
                             record: record.clone(),
                             lien: lien.cloned(),
+                            cosig_rules,
+                            lock_until: 0,
                         },
                     );
                 }
@@ -567,18 +723,51 @@ impl TxnEffect {
 
         self.txos.reserve(trn.body.transfer.outputs.len());
         let mut conf_transfer = false;
-        for (out, lien) in trn.body.transfer.outputs.iter().zip(lien_outputs) {
+        for (idx, ((out, lien), output)) in trn
+            .body
+            .transfer
+            .outputs
+            .iter()
+            .zip(lien_outputs)
+            .zip(trn.body.outputs.iter())
+            .enumerate()
+        {
+            if output.lock_until != 0 {
+                features.require(Feature::TimeLock).c(d!())?;
+            }
+            if let Some(rules) = output.cosig_rules.as_ref() {
+                if !rules.is_satisfiable() {
+                    return Err(eg!("co-signature rules can never be satisfied").into());
+                }
+            }
             if let XfrAssetType::Confidential(_) = out.asset_type {
                 conf_transfer = true;
             }
             if let Some(out_code) = out.asset_type.get_asset_type() {
                 self.asset_types_involved
                     .insert(AssetTypeCode { val: out_code });
+                self.transfer_parties.push((
+                    AssetTypeCode { val: out_code },
+                    XfrPublicKey::from_noah(&out.public_key),
+                ));
+                if tracing_active {
+                    self.tracing_policies.push((
+                        AssetTypeCode { val: out_code },
+                        trn.body
+                            .policies
+                            .outputs_tracing_policies
+                            .get(idx)
+                            .cloned()
+                            .unwrap_or_default(),
+                    ));
+                }
             }
             self.txos.push(Some(TxOutput {
                 id: None,
                 record: out.clone(),
                 lien: lien.cloned(),
+                cosig_rules: output.cosig_rules.clone(),
+                lock_until: output.lock_until,
             }));
             *txo_count += 1;
         }
@@ -620,6 +809,45 @@ impl TxnEffect {
         Ok(())
     }
 
+    // A transfer policy update is valid iff:
+    // 1) The signature is valid.
+    // 2) The asset type has a transfer policy (checked later).
+    // 3) The signing key is the asset issuer key (checked later).
+    fn add_update_transfer_policy(
+        &mut self,
+        txn: &Transaction,
+        update: &UpdateTransferPolicy,
+    ) -> Result<()> {
+        if txn.body.no_replay_token != update.body.no_replay_token {
+            return Err(eg!("replay token not match"));
+        }
+        // 1)
+        update
+            .signature
+            .verify(&update.pubkey, &update.body)
+            .c(d!())?;
+        self.policy_updates.push((
+            update.body.asset_type,
+            update.pubkey,
+            update.body.policy.clone(),
+        ));
+
+        Ok(())
+    }
+
+    // An anchor is valid iff:
+    // 1) The label is within the size limit.
+    // 2) The signature is valid.
+    //
+    // It changes nothing in the ledger, the query server indexes it.
+    fn add_anchor_data(&self, txn: &Transaction, anchor: &AnchorData) -> Result<()> {
+        if txn.body.no_replay_token != anchor.body.no_replay_token {
+            return Err(eg!("replay token not match"));
+        }
+        // 1) and 2)
+        anchor.verify().c(d!())
+    }
+
     /// A bar to abar note is valid iff
     /// 1. the signature is correct,
     /// 2. the ZKP can be verified,
@@ -631,6 +859,10 @@ impl TxnEffect {
         // verify the note signature & Plonk proof
         bar_to_abar.verify()?;
 
+        if let Some(code) = bar_to_abar.input_record().asset_type.get_asset_type() {
+            self.bar_conv_types.insert(AssetTypeCode { val: code });
+        }
+
         // list input_txo to spend
         self.input_txos.insert(
             bar_to_abar.txo_sid,
@@ -638,6 +870,8 @@ impl TxnEffect {
                 id: None,
                 record: bar_to_abar.input_record(),
                 lien: None,
+                cosig_rules: None,
+                lock_until: 0,
             },
         );
         // push new ABAR created
@@ -660,6 +894,8 @@ impl TxnEffect {
             id: None,
             record: abar_to_bar.note.get_output(),
             lien: None,
+            cosig_rules: None,
+            lock_until: 0,
         }));
 
         Ok(())
@@ -721,6 +957,8 @@ pub struct BlockEffect {
     pub issuance_keys: HashMap<AssetTypeCode, IssuerPublicKey>,
     /// Memo updates
     pub memo_updates: HashMap<AssetTypeCode, Memo>,
+    /// Transfer policy updates
+    pub policy_updates: HashMap<AssetTypeCode, CustomAssetPolicy>,
     /// counter for consensus integration; will add to a running count when applied.
     pub pulse_count: u64,
     /// simulator for safety
@@ -738,8 +976,8 @@ impl BlockEffect {
     ///   if `txn` would not interfere with any transaction in the block, the
     ///       new temp SID representing the transaction.
     ///   Otherwise, Err(...)
-    pub fn add_txn_effect(&mut self, txn_effect: TxnEffect) -> Result<TxnTempSID> {
-        self.check_txn_effect(&txn_effect).c(d!())?;
+    pub fn add_txn_effect(&mut self, txn_effect: TxnEffect) -> TxnResult<TxnTempSID> {
+        self.check_txn_effect(&txn_effect)?;
 
         // By construction, no_replay_tokens entries are unique
         self.no_replay_tokens
@@ -771,6 +1009,10 @@ impl BlockEffect {
             self.memo_updates.insert(code, memo);
         }
 
+        for (code, _, policy) in txn_effect.policy_updates {
+            self.policy_updates.insert(code, policy);
+        }
+
         // collect ABARs generated from BAR to ABAR
         let mut current_txn_abars: Vec<AnonAssetRecord> = vec![];
         for abar in txn_effect.bar_conv_abars {
@@ -797,11 +1039,11 @@ impl BlockEffect {
         Ok(temp_sid)
     }
 
-    fn check_txn_effect(&mut self, txn_effect: &TxnEffect) -> Result<()> {
+    fn check_txn_effect(&mut self, txn_effect: &TxnEffect) -> TxnResult<()> {
         // Check that no inputs are consumed twice
         for (input_sid, _) in txn_effect.input_txos.iter() {
             if self.input_txos.contains_key(&input_sid) {
-                return Err(eg!());
+                return Err(TxnValidationError::InputSpent.reject(eg!()));
             }
         }
 
@@ -810,13 +1052,13 @@ impl BlockEffect {
         for axfr_note in txn_effect.axfr_bodies.iter() {
             for nullifier in axfr_note.body.inputs.iter() {
                 if self.new_nullifiers.contains(nullifier) {
-                    return Err(eg!());
+                    return Err(TxnValidationError::InputSpent.reject(eg!()));
                 }
             }
         }
         for inputs in txn_effect.abar_conv_inputs.iter() {
             if self.new_nullifiers.contains(&inputs.get_input()) {
-                return Err(eg!());
+                return Err(TxnValidationError::InputSpent.reject(eg!()));
             }
         }
 
@@ -827,7 +1069,7 @@ impl BlockEffect {
                 if self.new_asset_codes.contains_key(&type_code)
                     || self.new_issuance_nums.contains_key(&type_code)
                 {
-                    return Err(eg!());
+                    return Err(eg!().into());
                 }
             }
 
@@ -835,19 +1077,32 @@ impl BlockEffect {
                 if self.new_asset_codes.contains_key(&type_code)
                     || self.new_issuance_nums.contains_key(&type_code)
                 {
-                    return Err(eg!());
+                    return Err(eg!().into());
                 }
 
                 // Debug-check that issued assets are registered in `issuance_keys`
                 if !nums.is_empty() && !txn_effect.issuance_keys.contains_key(&type_code)
                 {
-                    return Err(eg!());
+                    return Err(eg!().into());
                 }
             }
             // Ensure that each asset's memo can only be updated once per block
             for (type_code, _, _) in txn_effect.memo_updates.iter() {
                 if self.memo_updates.contains_key(&type_code) {
-                    return Err(eg!());
+                    return Err(eg!().into());
+                }
+            }
+            // The same for the transfer policies, and an asset can not be
+            // transferred after the update of its policy in the same block,
+            // the new policy takes effect in the next block
+            for (type_code, _, _) in txn_effect.policy_updates.iter() {
+                if self.policy_updates.contains_key(&type_code) {
+                    return Err(eg!().into());
+                }
+            }
+            for (type_code, _) in txn_effect.transfer_parties.iter() {
+                if self.policy_updates.contains_key(type_code) {
+                    return Err(eg!("the transfer policy is being updated").into());
                 }
             }
         }
@@ -865,12 +1120,13 @@ impl BlockEffect {
         if flag {
             for txn in self.txns.iter() {
                 if txn.body.no_replay_token == txn_effect.txn.body.no_replay_token {
-                    return Err(eg!());
+                    return Err(eg!().into());
                 }
             }
         }
 
-        // NOTE: set at the last position
+        // NOTE: set at the last position,
+        // the staking rejections are not classified
         self.check_staking(&txn_effect).c(d!())?;
 
         Ok(())
@@ -897,6 +1153,10 @@ impl BlockEffect {
                 .c(d!())?;
         }
 
+        for i in txn_effect.redelegations.iter() {
+            i.check_run(&mut self.staking_simulator).c(d!())?;
+        }
+
         for i in txn_effect.claims.iter() {
             i.check_run(&mut self.staking_simulator).c(d!())?;
         }
@@ -914,6 +1174,19 @@ impl BlockEffect {
                 .c(d!())?;
         }
 
+        for i in txn_effect.update_params.iter() {
+            i.check_run(&mut self.staking_simulator).c(d!())?;
+        }
+
+        for i in txn_effect.schedule_transfers.iter() {
+            i.check_run(&mut self.staking_simulator, &txn_effect.txn)
+                .c(d!())?;
+        }
+
+        for i in txn_effect.cancel_schedules.iter() {
+            i.check_run(&mut self.staking_simulator).c(d!())?;
+        }
+
         Ok(())
     }
 
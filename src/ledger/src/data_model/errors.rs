@@ -0,0 +1,129 @@
+//!
+//! # Reasons of rejecting a transaction
+//!
+//! The checks of a transaction return a `TxnRejection`, which carries the
+//! kind of the rejection, if it is classified, next to the `ruc` error.
+//! An untyped error converts into an unclassified rejection by `?`.
+//!
+
+use {
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::fmt,
+};
+
+/// The result of the checks of a transaction
+pub type TxnResult<T> = std::result::Result<T, TxnRejection>;
+
+/// Why a transaction is rejected by `TxnEffect::compute_effect`
+/// or by the checks of the ledger status
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxnValidationError {
+    /// a signature is invalid, or a required signature is missing
+    SignatureInvalid,
+    /// an input is spent, or spent twice in the transaction
+    InputSpent,
+    /// an issuance sequence number is used again
+    IssuanceReplay,
+    /// the records do not match the inputs, or the amounts do not balance
+    AmountMismatch,
+    /// the rules or the policies of an asset are violated
+    PolicyViolation,
+}
+
+impl TxnValidationError {
+    /// All kinds, in the order of their codes
+    pub const ALL: [TxnValidationError; 5] = [
+        TxnValidationError::SignatureInvalid,
+        TxnValidationError::InputSpent,
+        TxnValidationError::IssuanceReplay,
+        TxnValidationError::AmountMismatch,
+        TxnValidationError::PolicyViolation,
+    ];
+
+    #[allow(missing_docs)]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TxnValidationError::SignatureInvalid => "signature_invalid",
+            TxnValidationError::InputSpent => "input_spent",
+            TxnValidationError::IssuanceReplay => "issuance_replay",
+            TxnValidationError::AmountMismatch => "amount_mismatch",
+            TxnValidationError::PolicyViolation => "policy_violation",
+        }
+    }
+
+    /// The ABCI code of the rejection,
+    /// after the codes `1..=5` of the unclassified errors and the resource limits
+    pub fn code(self) -> u32 {
+        match self {
+            TxnValidationError::SignatureInvalid => 6,
+            TxnValidationError::InputSpent => 7,
+            TxnValidationError::IssuanceReplay => 8,
+            TxnValidationError::AmountMismatch => 9,
+            TxnValidationError::PolicyViolation => 10,
+        }
+    }
+
+    /// Reject a transaction with this kind
+    pub fn reject(self, err: Box<dyn RucError>) -> TxnRejection {
+        TxnRejection {
+            kind: Some(self),
+            err,
+        }
+    }
+}
+
+impl fmt::Display for TxnValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rejected({})", self.as_str())
+    }
+}
+
+/// A rejected transaction
+#[derive(Debug)]
+pub struct TxnRejection {
+    kind: Option<TxnValidationError>,
+    err: Box<dyn RucError>,
+}
+
+impl TxnRejection {
+    /// The kind of the rejection, `None` if it is not classified
+    pub fn kind(&self) -> Option<TxnValidationError> {
+        self.kind
+    }
+
+    /// The ABCI code of the rejection, 1 if it is not classified
+    pub fn code(&self) -> u32 {
+        self.kind.map_or(1, TxnValidationError::code)
+    }
+}
+
+impl From<Box<dyn RucError>> for TxnRejection {
+    fn from(err: Box<dyn RucError>) -> Self {
+        TxnRejection { kind: None, err }
+    }
+}
+
+impl fmt::Display for TxnRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Some(kind) => write!(f, "{kind}: {}", self.err),
+            None => write!(f, "{}", self.err),
+        }
+    }
+}
+
+impl std::error::Error for TxnRejection {}
+
+/// Classify the error of a check
+pub trait Reject<T> {
+    /// Reject with `kind` on error
+    fn reject(self, kind: TxnValidationError) -> TxnResult<T>;
+}
+
+impl<T> Reject<T> for Result<T> {
+    fn reject(self, kind: TxnValidationError) -> TxnResult<T> {
+        self.map_err(|e| kind.reject(e))
+    }
+}
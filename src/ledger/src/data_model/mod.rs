@@ -6,20 +6,33 @@
 #![allow(clippy::assertions_on_constants)]
 
 mod __trash__;
+mod amount;
 mod effects;
+mod errors;
+mod summary;
 mod test;
+mod view_key;
 
+pub use amount::{format_amount, parse_amount};
 pub use effects::{BlockEffect, TxnEffect};
+pub use errors::{Reject, TxnRejection, TxnResult, TxnValidationError};
+pub use view_key::{
+    derive_view_key, ViewKeyAuth, ViewKeyAuthBody, ViewKeyBody, ViewKeyRegistration,
+    VIEW_KEY_AUTH_TTL,
+};
 
 use {
     crate::{
         converter::ConvertAccount,
         staking::{
+            features::FeatureSet,
             ops::{
-                claim::ClaimOps, delegation::DelegationOps,
-                fra_distribution::FraDistributionOps, governance::GovernanceOps,
-                mint_fra::MintFraOps, replace_staker::ReplaceStakerOps,
-                undelegation::UnDelegationOps, update_staker::UpdateStakerOps,
+                cancel_schedule::CancelScheduleOps, claim::ClaimOps,
+                delegation::DelegationOps, fra_distribution::FraDistributionOps,
+                governance::GovernanceOps, mint_fra::MintFraOps,
+                redelegation::ReDelegationOps, replace_staker::ReplaceStakerOps,
+                schedule_transfer::ScheduleTransferOps, undelegation::UnDelegationOps,
+                update_params::UpdateParamsOps, update_staker::UpdateStakerOps,
                 update_validator::UpdateValidatorOps,
             },
             Staking,
@@ -86,8 +99,16 @@ use {
 const RANDOM_CODE_LENGTH: usize = 16;
 const MAX_DECIMALS_LENGTH: u8 = 19;
 
+/// Limits of the metadata of an asset, see `AssetMetadata::check`
+pub const MAX_ASSET_SYMBOL_LEN: usize = 16;
+#[allow(missing_docs)]
+pub const MAX_ASSET_NAME_LEN: usize = 64;
+#[allow(missing_docs)]
+pub const MAX_ASSET_URL_LEN: usize = 256;
+
 #[inline(always)]
-fn b64enc<T: ?Sized + AsRef<[u8]>>(input: &T) -> String {
+#[allow(missing_docs)]
+pub fn b64enc<T: ?Sized + AsRef<[u8]>>(input: &T) -> String {
     base64::encode_config(input, base64::URL_SAFE)
 }
 
@@ -611,6 +632,12 @@ impl SignatureRules {
         }
         Ok(())
     }
+
+    /// Whether the threshold can be reached by all keys together
+    pub fn is_satisfiable(&self) -> bool {
+        let keys = self.weights.iter().map(|(k, _)| k.to_bytes()).collect();
+        self.check_signature_set(&keys).is_ok()
+    }
 }
 
 /// Simple asset rules
@@ -633,6 +660,11 @@ pub struct AssetRules {
     pub max_units: Option<u64>,
     /// Decimals: default to FRA_DECIMALS
     pub decimals: u8,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    /// Transfer policy: Optional whitelist of the addresses which can hold the asset,
+    ///  the issuer can update it by `UpdateTransferPolicy`.
+    pub transfer_policy: Option<CustomAssetPolicy>,
 }
 impl Default for AssetRules {
     #[inline(always)]
@@ -644,6 +676,7 @@ impl Default for AssetRules {
             max_units: None,
             transfer_multisig_rules: None,
             decimals: FRA_DECIMALS,
+            transfer_policy: None,
         }
     }
 }
@@ -687,6 +720,16 @@ impl AssetRules {
         self
     }
 
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn set_transfer_policy(
+        &mut self,
+        policy: Option<CustomAssetPolicy>,
+    ) -> &mut Self {
+        self.transfer_policy = policy;
+        self
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn set_decimals(&mut self, decimals: u8) -> Result<&mut Self> {
@@ -698,6 +741,59 @@ impl AssetRules {
     }
 }
 
+/// Display metadata of an asset for explorers and wallets,
+/// the full document is kept off-chain at `url` and pinned by `content_hash`
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AssetMetadata {
+    /// the same as the decimals of the asset rules
+    pub decimals: u8,
+    /// ticker of the asset, ASCII letters and digits
+    pub symbol: String,
+    /// name for display
+    pub name: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub url: String,
+    /// hex encoded sha256 of the document at `url`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub content_hash: String,
+}
+
+impl AssetMetadata {
+    /// Check the size limits, and that `decimals` agrees with the asset rules
+    pub fn check(&self, decimals: u8) -> Result<()> {
+        if self.decimals != decimals {
+            return Err(eg!("the decimals of the metadata and the rules differ"));
+        }
+        if self.symbol.is_empty()
+            || MAX_ASSET_SYMBOL_LEN < self.symbol.len()
+            || !self.symbol.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(eg!(format!(
+                "the symbol should have 1 to {MAX_ASSET_SYMBOL_LEN} ASCII letters or digits"
+            )));
+        }
+        if MAX_ASSET_NAME_LEN < self.name.len() {
+            return Err(eg!(format!(
+                "the name should have at most {MAX_ASSET_NAME_LEN} bytes"
+            )));
+        }
+        if MAX_ASSET_URL_LEN < self.url.len() {
+            return Err(eg!(format!(
+                "the url should have at most {MAX_ASSET_URL_LEN} bytes"
+            )));
+        }
+        if !self.content_hash.is_empty()
+            && (64 != self.content_hash.len()
+                || !self.content_hash.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            return Err(eg!("the content hash should be a hex encoded sha256"));
+        }
+        Ok(())
+    }
+}
+
 #[allow(missing_docs)]
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Asset {
@@ -716,6 +812,9 @@ pub struct Asset {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
     pub policy: Option<(Box<Policy>, PolicyGlobals)>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub metadata: Option<AssetMetadata>,
 }
 
 /// Note:
@@ -731,6 +830,16 @@ pub struct AssetType {
 }
 
 impl AssetType {
+    /// The smallest units in `s`, a decimal string in the units of the asset
+    pub fn parse_amount(&self, s: &str) -> Result<u64> {
+        parse_amount(s, self.properties.asset_rules.decimals).c(d!())
+    }
+
+    /// `am` smallest units in the units of the asset, e.g. `12.345`
+    pub fn format_amount(&self, am: u64) -> String {
+        format_amount(am, self.properties.asset_rules.decimals)
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn has_issuance_restrictions(&self) -> bool {
@@ -751,6 +860,7 @@ impl AssetType {
             // Only relevant for issue operations
             ret.asset_rules.max_units = asset.asset_rules.max_units;
             ret.asset_rules.decimals = asset.asset_rules.decimals;
+            ret.metadata = asset.metadata.clone();
 
             ret
         };
@@ -764,10 +874,28 @@ impl AssetType {
     }
 }
 
-#[allow(missing_docs)]
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+/// Custom transfer policy of an asset, updatable by the issuer
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CustomAssetPolicy {
-    policy: Vec<u8>, // serialized policy, underlying form TBD.
+    /// Only these addresses and the issuer can send or receive the asset,
+    /// stored as a vector so that serialization is deterministic
+    pub whitelist: Vec<XfrPublicKey>,
+}
+
+impl CustomAssetPolicy {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(mut whitelist: Vec<XfrPublicKey>) -> Self {
+        whitelist.sort_by_key(|k| k.to_bytes());
+        whitelist.dedup();
+        CustomAssetPolicy { whitelist }
+    }
+
+    /// Whether `key` can send or receive the asset issued by `issuer`
+    #[inline(always)]
+    pub fn allows(&self, key: &XfrPublicKey, issuer: &IssuerPublicKey) -> bool {
+        issuer.key == *key || self.whitelist.contains(key)
+    }
 }
 
 #[allow(missing_docs)]
@@ -886,6 +1014,22 @@ pub struct TxOutput {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
     pub lien: Option<HashOf<Vec<TxOutput>>>,
+    /// Co-signatures required to spend it, besides the signature of its owner
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub cosig_rules: Option<SignatureRules>,
+    /// Can not be spent before the block count of the ledger reaches it
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub lock_until: u64,
+}
+
+impl TxOutput {
+    /// Whether it can not be spent at the block count `height`
+    #[inline(always)]
+    pub fn is_locked(&self, height: u64) -> bool {
+        height < self.lock_until
+    }
 }
 
 #[allow(missing_docs)]
@@ -975,6 +1119,12 @@ pub struct TransferAssetBody {
 
     /// Only Standard type supported
     pub transfer_type: TransferType,
+
+    /// Co-signature rules of each input, must equal to those of the spent outputs,
+    /// empty if none of the inputs requires co-signatures
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub input_cosig_rules: Vec<Option<SignatureRules>>,
 }
 
 impl TransferAssetBody {
@@ -1026,6 +1176,8 @@ impl TransferAssetBody {
                 id: None,
                 record: rec.clone(),
                 lien: None,
+                cosig_rules: None,
+                lock_until: 0,
             })
             .collect();
         Ok(TransferAssetBody {
@@ -1035,9 +1187,43 @@ impl TransferAssetBody {
             lien_assignments,
             transfer,
             transfer_type,
+            input_cosig_rules: vec![],
         })
     }
 
+    /// Co-signature rules of the input `idx`
+    #[inline(always)]
+    pub fn input_cosig_rules(&self, idx: usize) -> Option<&SignatureRules> {
+        self.input_cosig_rules.get(idx).and_then(|r| r.as_ref())
+    }
+
+    /// The co-signatures of each input reach the threshold of its rules
+    pub fn check_cosignatures(
+        &self,
+        sigs: &[IndexedSignature<TransferAssetBody>],
+    ) -> Result<()> {
+        if !self.input_cosig_rules.is_empty()
+            && self.input_cosig_rules.len() != self.inputs.len()
+        {
+            return Err(eg!("co-signature rules do not match the inputs"));
+        }
+
+        for (idx, rules) in self.input_cosig_rules.iter().enumerate() {
+            if let Some(rules) = rules {
+                let keys = sigs
+                    .iter()
+                    .filter(|s| s.input_idx == Some(idx))
+                    .map(|s| s.address.key.to_bytes())
+                    .collect();
+                rules
+                    .check_signature_set(&keys)
+                    .c(d!(format!("not enough co-signatures for input {idx}")))?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Computes a body signature. A body signature represents consent to some part of the asset transfer. If an
     /// input_idx is specified, the signature is a co-signature.
     #[inline(always)]
@@ -1128,6 +1314,13 @@ impl DefineAssetBody {
             asset: Box::new(asset_def),
         })
     }
+
+    /// Attach the display metadata, before the body is signed
+    pub fn set_metadata(&mut self, metadata: AssetMetadata) -> Result<&mut Self> {
+        metadata.check(self.asset.asset_rules.decimals).c(d!())?;
+        self.asset.metadata = Some(metadata);
+        Ok(self)
+    }
 }
 
 #[allow(missing_docs)]
@@ -1140,6 +1333,14 @@ pub struct UpdateMemoBody {
     pub no_replay_token: NoReplayToken,
 }
 
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct UpdateTransferPolicyBody {
+    pub asset_type: AssetTypeCode,
+    pub policy: CustomAssetPolicy,
+    pub no_replay_token: NoReplayToken,
+}
+
 #[allow(missing_docs)]
 #[derive(Clone, Debug, Deserialize)]
 pub enum AssetTypePrefix {
@@ -1262,6 +1463,15 @@ impl TransferAsset {
         self.body.compute_body_signature(keypair, None)
     }
 
+    /// Co-sign the input `input_idx`
+    pub fn cosign(&mut self, keypair: &XfrKeyPair, input_idx: usize) -> Result<()> {
+        if self.body.input_cosig_rules(input_idx).is_none() {
+            return Err(eg!(format!("input {input_idx} needs no co-signatures")));
+        }
+        let sig = self.body.compute_body_signature(keypair, Some(input_idx));
+        self.attach_signature(sig).c(d!())
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn get_owner_memos_ref(&self) -> Vec<Option<OwnerMemo>> {
@@ -1286,6 +1496,91 @@ impl TransferAsset {
     }
 }
 
+/// One side of a swap, `party` gives `amount` of `asset_type` to the other one
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SwapLeg {
+    pub party: XfrPublicKey,
+    pub asset_type: AssetTypeCode,
+    pub amount: u64,
+}
+
+/// An atomic swap of two asset types between two parties,
+/// both of them sign `transfer` as the owners of its inputs.
+///
+/// Only non-confidential records can be swapped, so the ledger can check
+/// that the transfer does exactly what the legs say.
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SwapAsset {
+    pub legs: [SwapLeg; 2],
+    pub transfer: TransferAsset,
+}
+
+impl SwapAsset {
+    /// Check that every record of the transfer belongs to one of the parties,
+    /// both of them spend an input, and for each asset type, the receiver
+    /// of a leg gets `amount` from its giver, the rest goes back as change.
+    pub fn check_terms(&self) -> TxnResult<()> {
+        let [a, b] = &self.legs;
+        if a.party == b.party {
+            return Err(eg!("the parties of a swap must differ").into());
+        }
+        if a.asset_type == b.asset_type {
+            return Err(eg!("a swap must exchange two asset types").into());
+        }
+        if 0 == a.amount || 0 == b.amount {
+            return Err(eg!("zero amount in a swap").into());
+        }
+
+        let party_of = |pk: &XfrPublicKey| {
+            self.legs
+                .iter()
+                .position(|l| l.party == *pk)
+                .c(d!("not a party of the swap"))
+        };
+        let open = |r: &BlindAssetRecord| match (r.asset_type, r.amount) {
+            (XfrAssetType::NonConfidential(val), XfrAmount::NonConfidential(am)) => {
+                Ok((AssetTypeCode { val }, am as i128))
+            }
+            _ => Err(eg!("confidential records can not be swapped")),
+        };
+
+        // (party, asset type) => amount received - amount spent
+        let mut net: HashMap<(usize, AssetTypeCode), i128> = HashMap::new();
+        let mut spent = [false; 2];
+        for r in self.transfer.body.transfer.inputs.iter() {
+            let p = party_of(&r.public_key).c(d!())?;
+            let (code, am) = open(r).c(d!())?;
+            *net.entry((p, code)).or_default() -= am;
+            spent[p] = true;
+        }
+        for r in self.transfer.body.transfer.outputs.iter() {
+            let p = party_of(&r.public_key).c(d!())?;
+            let (code, am) = open(r).c(d!())?;
+            *net.entry((p, code)).or_default() += am;
+        }
+        if spent.contains(&false) {
+            return Err(eg!("both parties must spend an input").into());
+        }
+
+        let mut expected = HashMap::new();
+        for (giver, leg) in self.legs.iter().enumerate() {
+            expected.insert((giver, leg.asset_type), -(leg.amount as i128));
+            expected.insert((1 - giver, leg.asset_type), leg.amount as i128);
+        }
+        for k in net.keys().chain(expected.keys()) {
+            let got = net.get(k).copied().unwrap_or_default();
+            if got != expected.get(k).copied().unwrap_or_default() {
+                return Err(TxnValidationError::AmountMismatch
+                    .reject(eg!("the transfer does not match the terms of the swap")));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[allow(missing_docs)]
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct IssueAsset {
@@ -1382,6 +1677,94 @@ impl UpdateMemo {
     }
 }
 
+/// Operation data for updating the transfer policy of a findora custom asset
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct UpdateTransferPolicy {
+    /// Inner data to update
+    pub body: UpdateTransferPolicyBody,
+    /// The public key of the asset issuer
+    pub pubkey: XfrPublicKey,
+    /// the signature
+    pub signature: SignatureOf<UpdateTransferPolicyBody>,
+}
+
+impl UpdateTransferPolicy {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(body: UpdateTransferPolicyBody, signing_key: &XfrKeyPair) -> Self {
+        let signature = SignatureOf::new(&signing_key, &body);
+        UpdateTransferPolicy {
+            body,
+            pubkey: *signing_key.get_pk_ref(),
+            signature,
+        }
+    }
+}
+
+/// The most bytes in the label of an anchor, `kind` and `name` together
+pub const ANCHOR_LABEL_MAX_LEN: usize = 64;
+
+/// The most `AnchorData` operations in a transaction
+pub const MAX_ANCHORS_PER_TXN: usize = 16;
+
+/// What an anchored hash stands for, the ledger does not interpret it
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AnchorLabel {
+    /// type of the document, like `invoice` or `contract`, not empty
+    pub kind: String,
+    /// name or reference of the document, can be empty
+    #[serde(default)]
+    pub name: String,
+}
+
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AnchorDataBody {
+    /// hash of the document, usually its sha256
+    pub hash: [u8; 32],
+    pub label: AnchorLabel,
+    pub no_replay_token: NoReplayToken,
+}
+
+/// Operation data for committing the hash of a document to the ledger,
+/// the transaction proves that the document existed at the time of its block
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AnchorData {
+    /// Inner data to anchor
+    pub body: AnchorDataBody,
+    /// The findora account publickey
+    pub pubkey: XfrPublicKey,
+    /// the signature
+    pub signature: SignatureOf<AnchorDataBody>,
+}
+
+impl AnchorData {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(body: AnchorDataBody, signing_key: &XfrKeyPair) -> Self {
+        let signature = SignatureOf::new(&signing_key, &body);
+        AnchorData {
+            body,
+            pubkey: *signing_key.get_pk_ref(),
+            signature,
+        }
+    }
+
+    /// Check the size of the label and the signature
+    pub fn verify(&self) -> Result<()> {
+        let label = &self.body.label;
+        if label.kind.is_empty() {
+            return Err(eg!("the kind of an anchor label can not be empty"));
+        }
+        if ANCHOR_LABEL_MAX_LEN < label.kind.len() + label.name.len() {
+            return Err(eg!(format!(
+                "the label of an anchor is limited to {ANCHOR_LABEL_MAX_LEN} bytes"
+            )));
+        }
+        self.signature.verify(&self.pubkey, &self.body).c(d!())
+    }
+}
+
 /// A note which enumerates the transparent and confidential BAR to
 /// Anon Asset record conversion.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -1694,6 +2077,20 @@ pub enum Operation {
     TransferAnonAsset(Box<AnonTransferOps>),
     ///replace staker.
     ReplaceStaker(ReplaceStakerOps),
+    /// Update the transfer policy of a findora custom asset
+    UpdateTransferPolicy(UpdateTransferPolicy),
+    /// Move FRA token from a validator to another without unbonding
+    ReDelegation(ReDelegationOps),
+    /// Adjust the on-chain parameters of the network
+    UpdateParams(UpdateParamsOps),
+    /// Exchange two asset types between two parties atomically
+    SwapAsset(Box<SwapAsset>),
+    /// Register a recurring payment of FRA paid from escrow
+    ScheduleTransfer(Box<ScheduleTransferOps>),
+    /// Stop a recurring payment and refund the rest of its escrow
+    CancelSchedule(CancelScheduleOps),
+    /// Commit the hash of a document to the ledger
+    AnchorData(Box<AnchorData>),
 }
 
 impl Operation {
@@ -1703,15 +2100,22 @@ impl Operation {
             Operation::UpdateStaker(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::Delegation(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::UnDelegation(i) => Serialized::new(i).as_ref().to_vec(),
+            Operation::ReDelegation(i) => Serialized::new(i).as_ref().to_vec(),
+            Operation::UpdateParams(i) => Serialized::new(i).as_ref().to_vec(),
+            Operation::ScheduleTransfer(i) => Serialized::new(i).as_ref().to_vec(),
+            Operation::CancelSchedule(i) => Serialized::new(i).as_ref().to_vec(),
+            Operation::AnchorData(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::Claim(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::FraDistribution(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::UpdateValidator(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::Governance(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::UpdateMemo(i) => Serialized::new(i).as_ref().to_vec(),
+            Operation::UpdateTransferPolicy(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::ConvertAccount(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::BarToAbar(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::ReplaceStaker(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::TransferAsset(i) => Serialized::new(i).as_ref().to_vec(),
+            Operation::SwapAsset(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::IssueAsset(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::DefineAsset(i) => Serialized::new(i).as_ref().to_vec(),
             Operation::MintFra(i) => Serialized::new(i).as_ref().to_vec(),
@@ -1728,11 +2132,17 @@ fn set_no_replay_token(op: &mut Operation, no_replay_token: NoReplayToken) {
         Operation::UpdateStaker(i) => i.set_nonce(no_replay_token),
         Operation::Delegation(i) => i.set_nonce(no_replay_token),
         Operation::UnDelegation(i) => i.set_nonce(no_replay_token),
+        Operation::ReDelegation(i) => i.set_nonce(no_replay_token),
         Operation::Claim(i) => i.set_nonce(no_replay_token),
         Operation::FraDistribution(i) => i.set_nonce(no_replay_token),
+        Operation::UpdateParams(i) => i.set_nonce(no_replay_token),
+        Operation::ScheduleTransfer(i) => i.set_nonce(no_replay_token),
+        Operation::CancelSchedule(i) => i.set_nonce(no_replay_token),
         Operation::UpdateValidator(i) => i.set_nonce(no_replay_token),
         Operation::Governance(i) => i.set_nonce(no_replay_token),
         Operation::UpdateMemo(i) => i.body.no_replay_token = no_replay_token,
+        Operation::UpdateTransferPolicy(i) => i.body.no_replay_token = no_replay_token,
+        Operation::AnchorData(i) => i.body.no_replay_token = no_replay_token,
         Operation::ConvertAccount(i) => i.set_nonce(no_replay_token),
         Operation::BarToAbar(i) => i.set_nonce(no_replay_token),
         Operation::AbarToBar(i) => i.set_nonce(no_replay_token),
@@ -1745,6 +2155,11 @@ fn set_no_replay_token(op: &mut Operation, no_replay_token: NoReplayToken) {
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, Default)]
 pub struct TransactionBody {
     pub no_replay_token: NoReplayToken,
+    /// The last block commit count(same unit as the seq_id of the no replay token)
+    /// at which this transaction can still be applied, no limit if none
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub expiry: Option<u64>,
     pub operations: Vec<Operation>,
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
@@ -1769,6 +2184,10 @@ impl TransactionBody {
     pub fn digest(&self) -> Vec<u8> {
         let mut bytes = vec![];
         bytes.extend_from_slice(Serialized::new(&self.no_replay_token).as_ref());
+        // keep the digest of transactions without an expiry unchanged
+        if self.expiry.is_some() {
+            bytes.extend_from_slice(Serialized::new(&self.expiry).as_ref());
+        }
         bytes.extend_from_slice(Serialized::new(&self.credentials).as_ref());
         bytes.extend_from_slice(Serialized::new(&self.policy_options).as_ref());
         bytes.extend_from_slice(Serialized::new(&self.memos).as_ref());
@@ -2050,6 +2469,7 @@ impl FinalizedTransaction {
             .iter_mut()
             .flat_map(|new| match new {
                 Operation::TransferAsset(d) => d.body.outputs.iter_mut().collect(),
+                Operation::SwapAsset(d) => d.transfer.body.outputs.iter_mut().collect(),
                 Operation::MintFra(d) => {
                     d.entries.iter_mut().map(|et| &mut et.utxo).collect()
                 }
@@ -2086,6 +2506,29 @@ pub const TX_FEE_MIN: u64 = 10_000; // 0.01 FRA
 /// Double the regular fee
 pub const BAR_TO_ABAR_TX_FEE_MIN: u64 = 20_000; // 0.02 FRA (2*TX_FEE_MIN)
 
+/// The fee rules of the network, see `Transaction::check_fee`
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct FeeParams {
+    /// minimum fee of a transaction, in FRA units
+    pub tx_fee_min: u64,
+    /// minimum fee of a transaction containing a `BarToAbar` operation
+    pub bar_to_abar_tx_fee_min: u64,
+    /// fees are paid by non-confidential FRA outputs to this address,
+    /// which burns them
+    pub fee_destination: XfrPublicKey,
+}
+
+impl FeeParams {
+    /// The built-in rules, `ChainParams::fee_params` for those in effect
+    pub fn current() -> Self {
+        FeeParams {
+            tx_fee_min: TX_FEE_MIN,
+            bar_to_abar_tx_fee_min: BAR_TO_ABAR_TX_FEE_MIN,
+            fee_destination: XfrPublicKey::from_noah(&BLACK_HOLE_PUBKEY),
+        }
+    }
+}
+
 /// Calculate the FEE with inputs and outputs number.
 pub const FEE_CALCULATING_FUNC: fn(u32, u32) -> u32 = |x: u32, y: u32| {
     let extra_outputs = y.saturating_sub(x);
@@ -2105,10 +2548,36 @@ impl Transaction {
     /// All-in-one checker
     #[inline(always)]
     pub fn valid_in_abci(&self) -> bool {
-        self.check_fee() && !self.is_coinbase_tx()
+        self.valid_in_abci_with(&FeeParams::current())
+    }
+
+    /// All-in-one checker, with the fee rules set on chain
+    #[inline(always)]
+    pub fn valid_in_abci_with(&self, params: &FeeParams) -> bool {
+        self.check_fee_with(params) && !self.is_coinbase_tx()
+    }
+
+    /// The minimum fee required by this transaction
+    #[inline(always)]
+    pub fn min_fee(&self) -> u64 {
+        self.min_fee_with(&FeeParams::current())
+    }
+
+    /// The minimum fee required by this transaction under `params`
+    pub fn min_fee_with(&self, params: &FeeParams) -> u64 {
+        // Charge double the min fee if the transaction is BarToAbar
+        if self
+            .body
+            .operations
+            .iter()
+            .any(|op| matches!(op, Operation::BarToAbar(_)))
+        {
+            params.bar_to_abar_tx_fee_min
+        } else {
+            params.tx_fee_min
+        }
     }
 
-    #[allow(clippy::if_same_then_else)]
     /// A simple fee checker
     ///
     /// The check logic is as follows:
@@ -2117,20 +2586,21 @@ impl Transaction {
     /// - Fee destination == BLACK_HOLE_PUBKEY
     /// - A transaction with an `Operation` of defining/issuing FRA need NOT fee
     /// - A transaction with all addresses of inputs equal to BLACK_HOLE_PUBKEY need NOT fee
+    #[inline(always)]
     pub fn check_fee(&self) -> bool {
+        self.check_fee_with(&FeeParams::current())
+    }
+
+    #[allow(clippy::if_same_then_else)]
+    /// `check_fee` under the fee rules of `params`
+    pub fn check_fee_with(&self, params: &FeeParams) -> bool {
         // This method can not completely solve the DOS risk,
         // we should further limit the number of txo[s] in every operation.
         //
         // But it seems enough when we combine it with limiting
         // the payload size of submission-server's http-requests.
 
-        let mut min_fee = TX_FEE_MIN;
-        // Charge double the min fee if the transaction is BarToAbar
-        for op in self.body.operations.iter() {
-            if let Operation::BarToAbar(_a) = op {
-                min_fee = BAR_TO_ABAR_TX_FEE_MIN;
-            }
-        }
+        let min_fee = self.min_fee_with(params);
 
         self.is_coinbase_tx()
             || self.body.operations.iter().any(|ops| {
@@ -2233,6 +2703,22 @@ impl Transaction {
         tx
     }
 
+    /// Set the last block commit count at which this transaction can be applied,
+    /// existing signatures will be invalidated.
+    #[inline(always)]
+    pub fn set_expiry(&mut self, expiry: u64) {
+        self.body.expiry = Some(expiry);
+    }
+
+    /// Check if the transaction has expired at `block_commit_count`
+    #[inline(always)]
+    pub fn is_expired(&self, block_commit_count: u64) -> bool {
+        self.body
+            .expiry
+            .map(|e| e < block_commit_count)
+            .unwrap_or(false)
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn add_operation(&mut self, mut op: Operation) {
@@ -2272,6 +2758,9 @@ impl Transaction {
                 Operation::TransferAsset(xfr_asset) => {
                     memos.append(&mut xfr_asset.get_owner_memos_ref());
                 }
+                Operation::SwapAsset(swap) => {
+                    memos.append(&mut swap.transfer.get_owner_memos_ref());
+                }
                 Operation::MintFra(mint_asset) => {
                     memos.append(&mut mint_asset.get_owner_memos_ref());
                 }
@@ -2291,7 +2780,7 @@ impl Transaction {
     /// This will never panic on a well formed transaction, but may panic on a malformed one.
     #[inline(always)]
     pub fn get_outputs_ref(&self, include_spent: bool) -> Vec<TxOutput> {
-        let eff = TxnEffect::compute_effect(self.clone()).unwrap();
+        let eff = TxnEffect::compute_effect(self.clone(), &FeatureSet::all()).unwrap();
         if !include_spent {
             eff.txos.into_iter().flatten().collect()
         } else {
@@ -2345,54 +2834,76 @@ impl Transaction {
     /// but also needs to sign the whole transaction, otherwise it will not be passed here
     #[allow(missing_docs)]
     #[inline(always)]
-    pub fn check_tx(&self) -> Result<()> {
-        let select_check = |tx: &Transaction, pk: &XfrPublicKey| -> Result<()> {
-            if tx.signatures.is_empty() {
+    pub fn check_tx(&self) -> TxnResult<()> {
+        let select_check = |tx: &Transaction, pk: &XfrPublicKey| -> TxnResult<()> {
+            let checked = if tx.signatures.is_empty() {
                 tx.check_has_signature_from_map(pk)
             } else {
                 tx.check_has_signature(pk)
-            }
+            };
+            checked.c(d!()).reject(TxnValidationError::SignatureInvalid)
         };
 
         for operation in self.body.operations.iter() {
             match operation {
                 Operation::TransferAsset(o) => {
                     for pk in o.get_owner_addresses().iter() {
-                        select_check(self, pk).c(d!())?;
+                        select_check(self, pk)?;
+                    }
+                }
+                Operation::SwapAsset(o) => {
+                    for pk in o.transfer.get_owner_addresses().iter() {
+                        select_check(self, pk)?;
                     }
                 }
                 Operation::IssueAsset(o) => {
-                    select_check(self, &o.pubkey.key).c(d!())?;
+                    select_check(self, &o.pubkey.key)?;
                 }
                 Operation::DefineAsset(o) => {
-                    select_check(self, &o.pubkey.key).c(d!())?;
+                    select_check(self, &o.pubkey.key)?;
                 }
                 Operation::UpdateMemo(o) => {
-                    select_check(self, &o.pubkey).c(d!())?;
+                    select_check(self, &o.pubkey)?;
+                }
+                Operation::UpdateTransferPolicy(o) => {
+                    select_check(self, &o.pubkey)?;
+                }
+                Operation::AnchorData(o) => {
+                    select_check(self, &o.pubkey)?;
                 }
                 Operation::UpdateStaker(o) => {
-                    select_check(self, &o.pubkey).c(d!())?;
+                    select_check(self, &o.pubkey)?;
                 }
                 Operation::Delegation(o) => {
-                    select_check(self, &o.pubkey).c(d!())?;
+                    select_check(self, &o.pubkey)?;
                 }
                 Operation::UnDelegation(o) => {
-                    select_check(self, &o.pubkey).c(d!())?;
+                    select_check(self, &o.pubkey)?;
+                }
+                Operation::ReDelegation(o) => {
+                    select_check(self, &o.pubkey)?;
                 }
                 Operation::Claim(o) => {
-                    select_check(self, &o.pubkey).c(d!())?;
+                    select_check(self, &o.pubkey)?;
+                }
+                Operation::ScheduleTransfer(o) => {
+                    select_check(self, &o.pubkey)?;
+                }
+                Operation::CancelSchedule(o) => {
+                    select_check(self, &o.pubkey)?;
                 }
                 Operation::UpdateValidator(_) => {}
                 Operation::Governance(_) => {}
                 Operation::FraDistribution(_) => {}
+                Operation::UpdateParams(_) => {}
                 Operation::MintFra(_) => {}
                 Operation::ConvertAccount(o) => {
-                    select_check(self, &o.signer).c(d!())?;
+                    select_check(self, &o.signer)?;
                 }
                 Operation::ReplaceStaker(o) => {
                     if !o.get_related_pubkeys().is_empty() {
                         for pk in o.get_related_pubkeys() {
-                            select_check(self, &pk).c(d!())?;
+                            select_check(self, &pk)?;
                         }
                     }
                 }
@@ -2431,6 +2942,30 @@ pub struct StateCommitmentData {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
     pub staking: Option<HashOf<Staking>>,
+    /// sub-hashes of the state domains, since `state_domains_height`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub domains: Option<StateDomainHashes>,
+}
+
+/// The version of `StateDomainHashes`,
+/// bumped whenever a domain is added or its encoding changes
+pub const STATE_DOMAINS_VERSION: u32 = 2;
+
+/// Hashes of the state which is not covered by the UTXO bitmap
+/// and the merkle trees, so that a divergence there changes the app hash
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StateDomainHashes {
+    /// see `STATE_DOMAINS_VERSION`
+    pub version: u32,
+    /// Root of the sparse merkle tree of `AssetTypeCode => AssetType`
+    pub asset_types: BitDigest,
+    /// Root of the sparse merkle tree of `AssetTypeCode => issuance number`
+    pub issuance_nums: BitDigest,
+    /// Root of the sparse merkle tree of the unspent TXOs, see `UtxoTree`
+    pub utxos: BitDigest,
+    /// hash(Staking)
+    pub staking: HashOf<Staking>,
 }
 
 impl StateCommitmentData {
@@ -0,0 +1,193 @@
+//!
+//! # Human-readable descriptions of operations
+//!
+//! One line per operation, for signing UIs to show what is signed.
+//! Addresses are in bech32, amounts of FRA in FRA units, amounts of the
+//! other assets in their smallest units, confidential values are hidden.
+//!
+
+use {
+    super::{AssetTypeCode, Operation, Transaction, ASSET_TYPE_FRA, FRA_DECIMALS},
+    globutils::wallet,
+    zei::{BlindAssetRecord, XfrPublicKey},
+};
+
+fn addr(pk: &XfrPublicKey) -> String {
+    wallet::public_key_to_bech32(pk)
+}
+
+fn fra(am: u64) -> String {
+    let unit = 10_u64.pow(FRA_DECIMALS as u32);
+    format!(
+        "{}.{:0w$} FRA",
+        am / unit,
+        am % unit,
+        w = FRA_DECIMALS as usize
+    )
+}
+
+fn value(am: Option<u64>, code: Option<AssetTypeCode>) -> String {
+    match (am, code) {
+        (Some(am), Some(code)) if code.val == ASSET_TYPE_FRA => fra(am),
+        (Some(am), Some(code)) => format!("{am} of {}", code.to_base64()),
+        (None, Some(code)) if code.val == ASSET_TYPE_FRA => {
+            "a hidden amount of FRA".to_owned()
+        }
+        (None, Some(code)) => format!("a hidden amount of {}", code.to_base64()),
+        (Some(am), None) => format!("{am} of a hidden asset"),
+        (None, None) => "a hidden amount of a hidden asset".to_owned(),
+    }
+}
+
+fn record(r: &BlindAssetRecord) -> String {
+    let code = r
+        .asset_type
+        .get_asset_type()
+        .map(|val| AssetTypeCode { val });
+    format!(
+        "{} to {}",
+        value(r.amount.get_amount(), code),
+        addr(&r.public_key)
+    )
+}
+
+fn records<'a>(rs: impl Iterator<Item = &'a BlindAssetRecord>) -> String {
+    rs.map(record).collect::<Vec<_>>().join("; ")
+}
+
+impl Operation {
+    /// What the operation does, in a line
+    pub fn summary(&self) -> String {
+        match self {
+            Operation::TransferAsset(x) => format!(
+                "transfer {} inputs: {}",
+                x.body.inputs.len(),
+                records(x.body.outputs.iter().map(|o| &o.record))
+            ),
+            Operation::IssueAsset(i) => format!(
+                "issue #{} of {}: {}",
+                i.body.seq_num,
+                i.body.code.to_base64(),
+                records(i.body.records.iter().map(|(o, _)| &o.record))
+            ),
+            Operation::DefineAsset(d) => format!(
+                "define the asset {}, issued by {}",
+                d.body.asset.code.to_base64(),
+                addr(&d.pubkey.key)
+            ),
+            Operation::UpdateMemo(u) => format!(
+                "update the memo of {} to \"{}\"",
+                u.body.asset_type.to_base64(),
+                u.body.new_memo.0
+            ),
+            Operation::UpdateTransferPolicy(u) => format!(
+                "update the transfer policy of {}",
+                u.body.asset_type.to_base64()
+            ),
+            Operation::UpdateStaker(u) => {
+                format!("update the validator info of {}", addr(&u.pubkey))
+            }
+            Operation::Delegation(d) if d.body.new_validator.is_some() => format!(
+                "stake {} as the new validator {}",
+                fra(d.body.amount),
+                d.body.validator
+            ),
+            Operation::Delegation(d) => format!(
+                "delegate {} to the validator {}",
+                fra(d.body.amount),
+                d.body.validator
+            ),
+            Operation::UnDelegation(u) => format!("undelegate by {}", addr(&u.pubkey)),
+            Operation::ReDelegation(r) => {
+                format!("redelegate the delegation of {}", addr(&r.pubkey))
+            }
+            Operation::Claim(c) => format!(
+                "claim {} of the rewards of {}",
+                c.get_claim_amount()
+                    .map(fra)
+                    .unwrap_or_else(|| "all".to_owned()),
+                addr(&c.get_claim_publickey())
+            ),
+            Operation::UpdateValidator(v) => format!(
+                "set {} validators from the height {}",
+                v.data.body.len(),
+                v.data.height
+            ),
+            Operation::Governance(g) => format!(
+                "penalize the byzantine validator {}",
+                g.get_related_pubkeys().last().map(addr).unwrap_or_default()
+            ),
+            Operation::FraDistribution(f) => format!(
+                "distribute {} to {} addresses",
+                fra(f.data.alloc_table.values().sum()),
+                f.data.alloc_table.len()
+            ),
+            Operation::MintFra(m) => format!(
+                "mint the rewards of the height {}: {}",
+                m.height,
+                records(m.entries.iter().map(|e| &e.utxo.record))
+            ),
+            Operation::ConvertAccount(c) => format!(
+                "convert {} of {} to an EVM account",
+                c.value,
+                addr(&c.signer)
+            ),
+            Operation::BarToAbar(b) => format!(
+                "make the TXO {} anonymous: {}",
+                b.txo_sid.0,
+                record(&b.input_record())
+            ),
+            Operation::AbarToBar(a) => format!(
+                "make an anonymous record public: {}",
+                record(&a.note.get_output())
+            ),
+            Operation::TransferAnonAsset(t) => format!(
+                "transfer {} anonymous records to {} anonymous records",
+                t.note.body.inputs.len(),
+                t.note.body.outputs.len()
+            ),
+            Operation::ReplaceStaker(r) => format!(
+                "replace the staker {}",
+                r.get_related_pubkeys()
+                    .first()
+                    .map(addr)
+                    .unwrap_or_default()
+            ),
+            Operation::UpdateParams(p) => format!(
+                "update the chain parameters: {}",
+                serde_json::to_string(&p.data.params).unwrap_or_default()
+            ),
+            Operation::SwapAsset(s) => format!(
+                "swap {} from {} for {} from {}",
+                value(Some(s.legs[0].amount), Some(s.legs[0].asset_type)),
+                addr(&s.legs[0].party),
+                value(Some(s.legs[1].amount), Some(s.legs[1].asset_type)),
+                addr(&s.legs[1].party)
+            ),
+            Operation::ScheduleTransfer(s) => format!(
+                "pay {} to {} every {} blocks, from the height {} to {}",
+                fra(s.body.amount),
+                addr(&s.body.receiver),
+                s.body.interval,
+                s.body.start_height,
+                s.body.end_height
+            ),
+            Operation::CancelSchedule(c) => {
+                format!("cancel the scheduled transfer #{}", c.body.id)
+            }
+            Operation::AnchorData(a) => format!(
+                "anchor the {} \"{}\": {}",
+                a.body.label.kind,
+                a.body.label.name,
+                hex::encode(a.body.hash)
+            ),
+        }
+    }
+}
+
+impl Transaction {
+    /// The summary of each operation, in order
+    pub fn summary(&self) -> Vec<String> {
+        self.body.operations.iter().map(|op| op.summary()).collect()
+    }
+}
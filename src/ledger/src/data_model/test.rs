@@ -299,6 +299,8 @@ fn gen_fee_operation(
                     public_key: dest_pubkey,
                 },
                 lien: None,
+                cosig_rules: None,
+                lock_until: 0,
             }],
             lien_assignments: Vec::new(),
             transfer: Box::new(XfrBody {
@@ -312,11 +314,63 @@ fn gen_fee_operation(
                 owners_memos: Vec::new(),
             }),
             transfer_type: TransferType::Standard,
+            input_cosig_rules: Vec::new(),
         },
         body_signatures: Vec::new(),
     })
 }
 
+#[test]
+fn test_fee_params() {
+    let params = FeeParams::current();
+    assert_eq!(params.tx_fee_min, TX_FEE_MIN);
+    assert_eq!(params.bar_to_abar_tx_fee_min, 2 * TX_FEE_MIN);
+    assert_eq!(
+        params.fee_destination,
+        XfrPublicKey::from_noah(&BLACK_HOLE_PUBKEY)
+    );
+    assert_eq!(gen_sample_tx().min_fee(), TX_FEE_MIN);
+}
+
+#[test]
+fn test_check_fee_boundaries() {
+    let black_hole = XfrPublicKey::from_noah(&BLACK_HOLE_PUBKEY);
+    let other =
+        XfrPublicKey::noah_from_bytes(&[9; ed25519_dalek::PUBLIC_KEY_LENGTH][..])
+            .unwrap();
+    let with_fee = |am: Option<u64>, code: Option<u8>, to: XfrPublicKey| {
+        let mut tx = gen_sample_tx();
+        tx.add_operation(gen_fee_operation(
+            am,
+            code.map(|c| NoahAssetType([c; ASSET_TYPE_LENGTH])),
+            to,
+        ));
+        tx
+    };
+
+    // exactly the minimum is enough, one unit less is not
+    assert!(with_fee(Some(TX_FEE_MIN), Some(0), black_hole).check_fee());
+    assert!(!with_fee(Some(TX_FEE_MIN - 1), Some(0), black_hole).check_fee());
+    assert!(!with_fee(Some(0), Some(0), black_hole).check_fee());
+
+    // neither another asset, another destination nor a hidden amount or type
+    assert!(!with_fee(Some(TX_FEE_MIN), Some(9), black_hole).check_fee());
+    assert!(!with_fee(Some(TX_FEE_MIN), Some(0), other).check_fee());
+    assert!(!with_fee(None, Some(0), black_hole).check_fee());
+    assert!(!with_fee(Some(TX_FEE_MIN), None, black_hole).check_fee());
+
+    // the minimum set on chain replaces the default one
+    let params = FeeParams {
+        tx_fee_min: 3 * TX_FEE_MIN,
+        ..FeeParams::current()
+    };
+    let tx = with_fee(Some(3 * TX_FEE_MIN - 1), Some(0), black_hole);
+    assert_eq!(tx.min_fee_with(&params), 3 * TX_FEE_MIN);
+    assert!(tx.check_fee());
+    assert!(!tx.check_fee_with(&params));
+    assert!(with_fee(Some(3 * TX_FEE_MIN), Some(0), black_hole).check_fee_with(&params));
+}
+
 #[test]
 fn test_check_fee() {
     let mut tx = gen_sample_tx();
@@ -395,3 +449,59 @@ fn test_check_fee() {
     tx.add_operation(invalid_destination_not_black_hole);
     assert!(tx.check_fee());
 }
+
+#[test]
+fn test_view_key() {
+    let mut prng = ChaChaRng::from_seed([0u8; 32]);
+    let owner = XfrKeyPair::generate(&mut prng);
+    let other = XfrKeyPair::generate(&mut prng);
+
+    // derived the same each time, and differs from the spend key
+    let view = derive_view_key(&owner);
+    assert_eq!(view.get_pk(), derive_view_key(&owner).get_pk());
+    assert_ne!(view.get_pk(), owner.get_pk());
+
+    let reg = ViewKeyRegistration::new(&owner, 100);
+    assert_eq!(reg.body.view_key, Some(view.get_pk()));
+    pnk!(reg.verify());
+    let mut forged = ViewKeyRegistration::new(&other, 100);
+    forged.body.address = owner.get_pk();
+    assert!(forged.verify().is_err());
+    pnk!(ViewKeyRegistration::revoke(&owner, 101).verify());
+
+    let auth = ViewKeyAuth::new(&view, owner.get_pk(), 100);
+    pnk!(auth.verify(100 + VIEW_KEY_AUTH_TTL));
+    assert!(auth.verify(101 + VIEW_KEY_AUTH_TTL).is_err());
+    let mut tampered = auth;
+    tampered.body.address = other.get_pk();
+    assert!(tampered.verify(100).is_err());
+}
+
+#[test]
+fn test_amount() {
+    assert_eq!(12_345_000, pnk!(parse_amount("12.345", FRA_DECIMALS)));
+    assert_eq!(12_000_000, pnk!(parse_amount("12", FRA_DECIMALS)));
+    assert_eq!(500_000, pnk!(parse_amount(".5", FRA_DECIMALS)));
+    assert_eq!(1, pnk!(parse_amount("0.000001", FRA_DECIMALS)));
+    assert_eq!(7, pnk!(parse_amount("7", 0)));
+    assert_eq!(u64::MAX, pnk!(parse_amount("18446744073709.551615", 6)));
+
+    for bad in ["", ".", "1.2.3", "-1", "1e6", "0x10", " 1 2", "0.0000001"] {
+        assert!(parse_amount(bad, FRA_DECIMALS).is_err(), "{bad}");
+    }
+    assert!(parse_amount("18446744073709.551616", 6).is_err());
+    assert!(parse_amount("18446744073709551616", 0).is_err());
+    assert!(parse_amount("1", MAX_DECIMALS_LENGTH + 1).is_err());
+
+    assert_eq!("12.345", format_amount(12_345_000, FRA_DECIMALS));
+    assert_eq!("12", format_amount(12_000_000, FRA_DECIMALS));
+    assert_eq!("0.000001", format_amount(1, FRA_DECIMALS));
+    assert_eq!("0", format_amount(0, FRA_DECIMALS));
+    assert_eq!("7", format_amount(7, 0));
+    for am in [0, 1, 999_999, 1_000_000, u64::MAX] {
+        for decimals in [0, 6, MAX_DECIMALS_LENGTH] {
+            let s = format_amount(am, decimals);
+            assert_eq!(am, pnk!(parse_amount(&s, decimals)));
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//!
+//! # View keys
+//!
+//! A view key is a key pair derived from the key pair of an address, the
+//! owner registers its public key to a query server by a signature of the
+//! address, then the requests signed by the view key can read the history
+//! of the address. The view key can not sign anything for the address.
+//!
+//! The owner memos are encrypted to the key of the address itself, opening
+//! them still needs the spend key, the history only carries what the ledger
+//! stores in clear.
+//!
+
+use {
+    globutils::SignatureOf,
+    rand_chacha::ChaChaRng,
+    rand_core::SeedableRng,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    zei::{noah_algebra::serialization::NoahFromToBytes, XfrKeyPair, XfrPublicKey},
+};
+
+/// Seconds an authentication by a view key is accepted for
+pub const VIEW_KEY_AUTH_TTL: i64 = 300;
+
+const VIEW_KEY_DOMAIN: &[u8] = b"findora view key";
+
+/// Derive the view key of an address, the same key pair is derived each time
+pub fn derive_view_key(owner: &XfrKeyPair) -> XfrKeyPair {
+    let mut hasher = Sha256::new();
+    hasher.update(VIEW_KEY_DOMAIN);
+    hasher.update(owner.get_sk_ref().noah_to_bytes());
+    XfrKeyPair::generate(&mut ChaChaRng::from_seed(hasher.finalize().into()))
+}
+
+/// Set or revoke the view key of an address
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ViewKeyBody {
+    pub address: XfrPublicKey,
+    /// `None` to revoke the registered one
+    pub view_key: Option<XfrPublicKey>,
+    /// unix seconds, a registration is only replaced by a later one
+    pub timestamp: i64,
+}
+
+/// A `ViewKeyBody` signed by the key of the address
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ViewKeyRegistration {
+    pub body: ViewKeyBody,
+    pub signature: SignatureOf<ViewKeyBody>,
+}
+
+impl ViewKeyRegistration {
+    /// Register the view key derived from `owner`
+    pub fn new(owner: &XfrKeyPair, timestamp: i64) -> Self {
+        let view_key = derive_view_key(owner).get_pk();
+        Self::sign(owner, Some(view_key), timestamp)
+    }
+
+    /// Revoke the view key of `owner`
+    pub fn revoke(owner: &XfrKeyPair, timestamp: i64) -> Self {
+        Self::sign(owner, None, timestamp)
+    }
+
+    fn sign(owner: &XfrKeyPair, view_key: Option<XfrPublicKey>, timestamp: i64) -> Self {
+        let body = ViewKeyBody {
+            address: owner.get_pk(),
+            view_key,
+            timestamp,
+        };
+        let signature = SignatureOf::new(owner, &body);
+        ViewKeyRegistration { body, signature }
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn verify(&self) -> Result<()> {
+        self.signature
+            .verify(&self.body.address, &self.body)
+            .c(d!())
+    }
+}
+
+/// What a view key signs to read the history of an address
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ViewKeyAuthBody {
+    pub address: XfrPublicKey,
+    /// unix seconds, expires after `VIEW_KEY_AUTH_TTL`
+    pub timestamp: i64,
+}
+
+/// The authentication of a request by a view key
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ViewKeyAuth {
+    pub view_key: XfrPublicKey,
+    pub body: ViewKeyAuthBody,
+    pub signature: SignatureOf<ViewKeyAuthBody>,
+}
+
+impl ViewKeyAuth {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(view_key: &XfrKeyPair, address: XfrPublicKey, timestamp: i64) -> Self {
+        let body = ViewKeyAuthBody { address, timestamp };
+        let signature = SignatureOf::new(view_key, &body);
+        ViewKeyAuth {
+            view_key: view_key.get_pk(),
+            body,
+            signature,
+        }
+    }
+
+    /// Check the signature and the age of the authentication at `now`
+    pub fn verify(&self, now: i64) -> Result<()> {
+        if VIEW_KEY_AUTH_TTL < (now - self.body.timestamp).abs() {
+            return Err(eg!("the authentication has expired"));
+        }
+        self.signature.verify(&self.view_key, &self.body).c(d!())
+    }
+}
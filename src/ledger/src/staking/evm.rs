@@ -38,6 +38,15 @@ pub trait EVMStaking: Sync + Send + 'static {
     /// undelegate call
     fn undelegate(&self, from: &XfrPublicKey, td_addr: &[u8], amount: u64)
         -> Result<()>;
+    /// redelegate call, move `amount` from `src_validator` to `dst_validator`
+    /// without an unbonding period
+    fn redelegate(
+        &self,
+        from: &XfrPublicKey,
+        src_validator: &[u8],
+        dst_validator: &[u8],
+        amount: u64,
+    ) -> Result<()>;
     ///update the memo and rate of the validator
     fn update_validator(
         &self,
@@ -0,0 +1,145 @@
+//!
+//! # Height-gated Features
+//!
+//! New validation rules are activated at a block height, so all the nodes
+//! switch at the same block. The heights come from the checkpoint file,
+//! and can be scheduled on chain by an `UpdateParams` operation,
+//! see `ChainParams::feature_heights`.
+//!
+
+use {
+    crate::staking::{params::ChainParams, BlockHeight},
+    config::abci::global_cfg::CFG,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::collections::BTreeSet,
+};
+
+/// A consensus change activated at a block height
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// `DefineAsset` can carry an `AssetMetadata`
+    AssetMetadata,
+    /// the `ReDelegation` operation
+    ReDelegation,
+    /// the `SwapAsset` operation
+    AssetSwap,
+    /// the `ScheduleTransfer` and `CancelSchedule` operations
+    ScheduledTransfer,
+    /// the `AnchorData` operation
+    DataAnchor,
+    /// co-signature rules on transfer inputs and outputs
+    CoSignature,
+    /// time-locked transfer outputs
+    TimeLock,
+    /// transfer whitelists of custom assets, and `UpdateTransferPolicy`
+    TransferPolicy,
+    /// the expiry height of transactions
+    TxnExpiry,
+    /// asset tracing policies and proofs in transfers
+    AssetTracing,
+}
+
+impl Feature {
+    /// All the known features
+    pub const ALL: [Feature; 10] = [
+        Feature::AssetMetadata,
+        Feature::ReDelegation,
+        Feature::AssetSwap,
+        Feature::ScheduledTransfer,
+        Feature::DataAnchor,
+        Feature::CoSignature,
+        Feature::TimeLock,
+        Feature::TransferPolicy,
+        Feature::TxnExpiry,
+        Feature::AssetTracing,
+    ];
+
+    /// The activation height of the checkpoint file,
+    /// the feature is active in the blocks after it
+    pub fn checkpoint_height(self) -> BlockHeight {
+        match self {
+            Feature::AssetMetadata => CFG.checkpoint.asset_metadata_height,
+            Feature::ReDelegation => CFG.checkpoint.redelegation_height.max(0) as u64,
+            Feature::AssetSwap => CFG.checkpoint.asset_swap_height.max(0) as u64,
+            Feature::ScheduledTransfer => {
+                CFG.checkpoint.scheduled_transfer_height.max(0) as u64
+            }
+            Feature::DataAnchor => CFG.checkpoint.anchor_data_height.max(0) as u64,
+            Feature::CoSignature => CFG.checkpoint.cosig_height.max(0) as u64,
+            Feature::TimeLock => CFG.checkpoint.time_lock_height.max(0) as u64,
+            Feature::TransferPolicy => {
+                CFG.checkpoint.transfer_policy_height.max(0) as u64
+            }
+            Feature::TxnExpiry => CFG.checkpoint.txn_expiry_height.max(0) as u64,
+            Feature::AssetTracing => CFG.checkpoint.asset_tracing_height.max(0) as u64,
+        }
+    }
+}
+
+/// The features active in a block, an input of `TxnEffect::compute_effect`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FeatureSet(BTreeSet<Feature>);
+
+impl FeatureSet {
+    /// All the features, for the transactions already in the ledger
+    #[inline(always)]
+    pub fn all() -> Self {
+        FeatureSet(Feature::ALL.iter().copied().collect())
+    }
+
+    /// The features active in the block at height `h`
+    pub fn at_height(params: &ChainParams, h: BlockHeight) -> Self {
+        FeatureSet(
+            Feature::ALL
+                .iter()
+                .copied()
+                .filter(|f| params.feature_height(*f) < h)
+                .collect(),
+        )
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn is_active(&self, f: Feature) -> bool {
+        self.0.contains(&f)
+    }
+
+    /// Reject what relies on an inactive feature
+    #[inline(always)]
+    pub fn require(&self, f: Feature) -> Result<()> {
+        if self.is_active(f) {
+            Ok(())
+        } else {
+            Err(eg!(format!("feature `{f:?}` is not active yet")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, std::collections::BTreeMap};
+
+    #[test]
+    fn feature_set() {
+        let h = Feature::ReDelegation.checkpoint_height();
+        let params = ChainParams::default();
+        assert!(!FeatureSet::at_height(&params, h).is_active(Feature::ReDelegation));
+        assert!(FeatureSet::at_height(&params, h + 1).is_active(Feature::ReDelegation));
+
+        let params = ChainParams {
+            feature_heights: BTreeMap::from([(Feature::ReDelegation, h + 10)]),
+            ..Default::default()
+        };
+        let features = FeatureSet::at_height(&params, h + 1);
+        assert!(features.require(Feature::ReDelegation).is_err());
+        assert!(FeatureSet::at_height(&params, h + 11)
+            .require(Feature::ReDelegation)
+            .is_ok());
+
+        assert_eq!(FeatureSet::all().0.len(), Feature::ALL.len());
+    }
+}
@@ -6,6 +6,7 @@
 //! - manage the distribution of investment income
 //! - manage on-chain governance
 //! - manage the official re-distribution of FRA
+//! - manage the scheduled transfers paid from escrow
 //!
 
 #![deny(warnings)]
@@ -17,8 +18,10 @@ use {num_bigint::BigUint, std::convert::TryFrom};
 
 pub mod cosig;
 pub mod evm;
+pub mod features;
 pub mod init;
 pub mod ops;
+pub mod params;
 
 use {
     crate::{
@@ -31,6 +34,7 @@ use {
     cosig::CoSigRule,
     cryptohash::sha256::{self, Digest},
     fbnc::{new_mapx, Mapx},
+    features::FeatureSet,
     globutils::wallet,
     indexmap::IndexMap,
     lazy_static::lazy_static,
@@ -38,6 +42,7 @@ use {
         fra_distribution::FraDistributionOps,
         mint_fra::{MintKind, MINT_AMOUNT_LIMIT},
     },
+    params::ChainParams,
     parking_lot::Mutex,
     rand::random,
     ruc::*,
@@ -66,6 +71,12 @@ type DAHCP = (Arc<Mutex<Sender<DAH>>>, Arc<Mutex<Receiver<DAH>>>);
 // pk, height, <struct DelegationRwdDetail>
 type DRH = (XfrPublicKey, BlockHeight, DelegationRwdDetail);
 type DRHCP = (Arc<Mutex<Sender<DRH>>>, Arc<Mutex<Receiver<DRH>>>);
+// height, <enum StakingEvent>
+type SEH = (BlockHeight, StakingEvent);
+type SEHCP = (Arc<Mutex<Sender<SEH>>>, Arc<Mutex<Receiver<SEH>>>);
+// pk, height, <struct ValidatorBlockRecord>
+type VBH = (XfrPublicKey, BlockHeight, ValidatorBlockRecord);
+type VBHCP = (Arc<Mutex<Sender<VBH>>>, Arc<Mutex<Receiver<VBH>>>);
 
 macro_rules! chan {
     () => {{
@@ -97,6 +108,10 @@ lazy_static! {
     pub static ref CHAN_D_AMOUNT_HIST: DAHCP = chan!();
     #[allow(missing_docs)]
     pub static ref CHAN_D_RWD_HIST: DRHCP = chan!();
+    #[allow(missing_docs)]
+    pub static ref CHAN_STAKING_EVENT_HIST: SEHCP = chan!();
+    #[allow(missing_docs)]
+    pub static ref CHAN_V_BLOCK_HIST: VBHCP = chan!();
 }
 
 /// Reserved accounts of Findora Foundation.
@@ -233,6 +248,18 @@ pub struct Staking {
     // FRA CoinBase.
     coinbase: CoinBase,
     cr: ConsensusRng,
+    // on-chain parameters, not serialized until the first update,
+    // so the state hash of the old blocks is not changed
+    #[serde(default, skip_serializing_if = "ChainParams::is_default")]
+    params: ChainParams,
+    // pre-authorized transfers, not serialized until the first one,
+    // so the state hash of the old blocks is not changed
+    #[serde(default, skip_serializing_if = "Schedules::is_empty")]
+    schedules: Schedules,
+    // set on the clones checking a transaction without applying it,
+    // nothing is recorded to the history from them
+    #[serde(skip)]
+    dry_run: bool,
 }
 
 impl Default for Staking {
@@ -265,9 +292,46 @@ impl Staking {
             cur_height: 0,
             coinbase: CoinBase::gen(),
             cr: ConsensusRng::default(),
+            params: ChainParams::default(),
+            schedules: Schedules::default(),
+            dry_run: false,
         }
     }
 
+    /// A clone for checking a transaction without applying it,
+    /// it records nothing to the history.
+    #[inline(always)]
+    pub fn dry_run(&self) -> Self {
+        Staking {
+            dry_run: true,
+            ..self.clone()
+        }
+    }
+
+    // `KEEP_HIST` nodes record the history of the applied transactions only
+    #[inline(always)]
+    fn keep_hist(&self) -> bool {
+        *KEEP_HIST && !self.dry_run
+    }
+
+    /// The parameters set on chain.
+    #[inline(always)]
+    pub fn params(&self) -> &ChainParams {
+        &self.params
+    }
+
+    /// Apply the set parameters of `update`, used by `UpdateParams`.
+    #[inline(always)]
+    pub fn params_update(&mut self, update: &ChainParams) {
+        self.params.merge(update);
+    }
+
+    /// The features active in the current block.
+    #[inline(always)]
+    pub fn active_features(&self) -> FeatureSet {
+        FeatureSet::at_height(&self.params, self.cur_height)
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn cur_height(&self) -> BlockHeight {
@@ -286,6 +350,30 @@ impl Staking {
         }
     }
 
+    // record a staking event of current block height
+    #[inline(always)]
+    fn record_event(&self, ev: StakingEvent) {
+        if self.keep_hist() {
+            ruc::info_omit!(CHAN_STAKING_EVENT_HIST
+                .0
+                .lock()
+                .send((self.cur_height, ev)));
+        }
+    }
+
+    /// record the signing or the rewards of a validator at the height `h`
+    #[inline(always)]
+    pub fn record_validator_block(
+        &self,
+        pk: &XfrPublicKey,
+        h: BlockHeight,
+        r: ValidatorBlockRecord,
+    ) {
+        if *KEEP_HIST {
+            CHAN_V_BLOCK_HIST.0.lock().send((*pk, h, r)).unwrap();
+        }
+    }
+
     ///get the delegationInfo
     pub fn delegation_info_global_amount(&self) -> Amount {
         self.delegation_info.global_amount
@@ -454,13 +542,14 @@ impl Staking {
     // after they have been removed from tendermint core.
     fn validator_clean_invalid_items(&mut self) {
         let h = self.cur_height;
+        let unbond_block_cnt = self.params.unbond_block_cnt();
 
-        if CFG.checkpoint.unbond_block_cnt > h {
+        if unbond_block_cnt > h {
             return;
         }
 
         if let Some(old) = self
-            .validator_get_effective_at_height(h - CFG.checkpoint.unbond_block_cnt)
+            .validator_get_effective_at_height(h - unbond_block_cnt)
             .map(|ovd| {
                 ovd.body
                     .iter()
@@ -493,6 +582,8 @@ impl Staking {
             self.validator_check_power(power, validator).c(d!())?;
         }
 
+        let old_power = self.validator_get_power(validator).unwrap_or(0);
+
         self.validator_get_effective_at_height_mut(self.cur_height)
             .c(d!("failed to get effective validators at current height"))
             .and_then(|cur| {
@@ -507,7 +598,16 @@ impl Staking {
                     })
                     .c(d!("validator not exists"))
             })
-            .map(|_| self.validator_align_power(validator))
+            .map(|_| self.validator_align_power(validator))?;
+
+        // the api cache only records it for the validators that have been jailed
+        if !decrease && 0 == old_power && 0 < power {
+            self.record_event(StakingEvent::Unjailed {
+                validator: *validator,
+            });
+        }
+
+        Ok(())
     }
 
     // functions:
@@ -562,9 +662,11 @@ impl Staking {
     /// update staker
     pub fn update_staker(&mut self, new: &Validator) -> Result<()> {
         let vd = self.validator_get_current_mut().c(d!())?;
+        let mut old_rate = None;
         let res = vd.body.values_mut().any(|v| {
             if v.id == new.id {
                 v.memo = new.memo.clone();
+                old_rate = Some(v.commission_rate);
                 v.commission_rate = new.commission_rate;
                 return true;
             }
@@ -572,6 +674,13 @@ impl Staking {
         });
 
         if res {
+            if let Some(old) = old_rate.filter(|r| *r != new.commission_rate) {
+                self.record_event(StakingEvent::CommissionChanged {
+                    validator: new.id,
+                    old,
+                    new: new.commission_rate,
+                });
+            }
             Ok(())
         } else {
             Err(eg!("Cannot update staker"))
@@ -750,6 +859,7 @@ impl Staking {
         }
 
         let h = self.cur_height;
+        let keep_hist = self.keep_hist();
         let new = || Delegation {
             delegations: map! {B validator => 0},
             id: owner,
@@ -783,7 +893,7 @@ impl Staking {
         *d.delegations.entry(validator).or_insert(0) += am;
 
         // record self-delegation amount for a validator
-        if owner == validator && *KEEP_HIST {
+        if owner == validator && keep_hist {
             CHAN_V_SELF_D_HIST
                 .0
                 .lock()
@@ -796,7 +906,7 @@ impl Staking {
             if owner != validator {
                 *v.delegators.entry(owner).or_insert(0) += am;
                 v.delegators.sort_by(|_, v1, _, v2| v2.cmp(&v1));
-                if *KEEP_HIST {
+                if keep_hist {
                     CHAN_D_AMOUNT_HIST
                         .0
                         .lock()
@@ -837,6 +947,7 @@ impl Staking {
         }
 
         let h = self.cur_height;
+        let unbond_block_cnt = self.params.unbond_block_cnt();
         let mut orig_h = None;
         let mut is_validator = false;
 
@@ -848,7 +959,7 @@ impl Staking {
             if BLOCK_HEIGHT_MAX == d.end_height {
                 if d.end_height != h {
                     orig_h = Some(d.end_height);
-                    d.end_height = h + CFG.checkpoint.unbond_block_cnt;
+                    d.end_height = h + unbond_block_cnt;
                 }
             } else {
                 return Err(eg!("delegator is not bonded"));
@@ -902,7 +1013,7 @@ impl Staking {
                 .map(|set| set.remove(addr));
             self.delegation_info
                 .end_height_map
-                .entry(h + CFG.checkpoint.unbond_block_cnt)
+                .entry(h + unbond_block_cnt)
                 .or_default()
                 .insert(*addr);
         }
@@ -924,6 +1035,8 @@ impl Staking {
 
         let new_tmp_delegator;
         let h = self.cur_height;
+        let keep_hist = self.keep_hist();
+        let unbond_block_cnt = self.params.unbond_block_cnt();
         let is_validator = self.addr_is_validator(addr);
 
         let target_validator = self
@@ -965,14 +1078,14 @@ impl Staking {
                     receiver_pk: Some(d.id),
                     tmp_delegators: map! {B},
                     start_height: d.start_height,
-                    end_height: h + CFG.checkpoint.unbond_block_cnt,
+                    end_height: h + unbond_block_cnt,
                     state: DelegationState::Bond,
                     rwd_amount: 0,
                     delegation_rwd_cnt: 0,
                     proposer_rwd_cnt: 0,
                 };
                 // record per-block-height self-delegation amount for a validator
-                if target_validator == *addr && *KEEP_HIST {
+                if target_validator == *addr && keep_hist {
                     CHAN_V_SELF_D_HIST
                         .0
                         .lock()
@@ -994,7 +1107,7 @@ impl Staking {
             .insert(pu.new_delegator_id, new_tmp_delegator);
         self.delegation_info
             .end_height_map
-            .entry(h + CFG.checkpoint.unbond_block_cnt)
+            .entry(h + unbond_block_cnt)
             .or_default()
             .insert(pu.new_delegator_id);
 
@@ -1221,6 +1334,44 @@ impl Staking {
             .collect()
     }
 
+    /// Pending unbondings of `addr` in the order of maturity.
+    ///
+    /// The queue is the `end_height_map`, it holds the delegation of `addr`
+    /// once it is fully undelegated, and the temporary delegators split from it
+    /// by the partial undelegations. The principal of an entry turns `Free`
+    /// in the first block after its maturity height, and becomes liquid
+    /// when it is paid back by the next coinbase transaction.
+    pub fn unbonding_queue_of(&self, addr: &XfrPublicKey) -> Vec<Unbonding> {
+        let d = if let Some(d) = self.delegation_get(addr) {
+            d
+        } else {
+            return vec![];
+        };
+
+        let mut queue =
+            d.tmp_delegators
+                .keys()
+                .chain([addr])
+                .filter_map(|id| self.delegation_get(id))
+                .filter(|d| {
+                    BLOCK_HEIGHT_MAX != d.end_height && DelegationState::Paid != d.state
+                })
+                .flat_map(|d| {
+                    d.delegations.iter().filter(|(_, am)| 0 < **am).map(
+                        move |(v, am)| Unbonding {
+                            id: d.id,
+                            validator: *v,
+                            amount: *am,
+                            maturity_height: d.end_height,
+                            state: d.state,
+                        },
+                    )
+                })
+                .collect::<Vec<_>>();
+        queue.sort_by_key(|u| u.maturity_height);
+        queue
+    }
+
     /// Clean delegation states along with each new block.
     #[inline(always)]
     pub fn delegation_process(&mut self) {
@@ -1334,9 +1485,13 @@ impl Staking {
 
         // punish itself
         // the delegators under validator do not contain self-delegation message, so give a none on it.
-        let am = self.delegation_get(addr).c(d!())?.amount();
-        self.governance_penalty_sub_amount(None, addr, am * percent[0] / percent[1])
-            .c(d!())?;
+        let am = self.delegation_get(addr).c(d!())?.amount() * percent[0] / percent[1];
+        self.governance_penalty_sub_amount(None, addr, am).c(d!())?;
+        self.record_event(StakingEvent::Slashed {
+            validator: *addr,
+            delegator: None,
+            amount: am,
+        });
 
         if self.addr_is_validator(addr) {
             // punish related delegators
@@ -1352,18 +1507,25 @@ impl Staking {
             // also modify the amount in the delegators under validator,
             // make the interface delegation_info and delegator_list data the same
             pl().into_iter().for_each(|(pk, p_am)| {
-                ruc::info_omit!(self.governance_penalty_sub_amount(
-                    Some(addr),
-                    &pk,
-                    p_am
-                ));
+                if ruc::info!(self.governance_penalty_sub_amount(Some(addr), &pk, p_am))
+                    .is_ok()
+                {
+                    self.record_event(StakingEvent::Slashed {
+                        validator: *addr,
+                        delegator: Some(pk),
+                        amount: p_am,
+                    });
+                }
             });
 
             // punish its vote power
-            self.validator_get_power(addr).c(d!()).and_then(|power| {
-                self.validator_change_power(addr, power * percent[0] / percent[1], true)
-                    .c(d!())
-            })?;
+            let power = self.validator_get_power(addr).c(d!())?;
+            self.validator_change_power(addr, power * percent[0] / percent[1], true)
+                .c(d!())?;
+
+            if 0 < power && 0 == self.validator_get_power(addr).c(d!())? {
+                self.record_event(StakingEvent::Jailed { validator: *addr });
+            }
         }
 
         Ok(())
@@ -1376,6 +1538,7 @@ impl Staking {
         delegator: &XfrPublicKey,
         mut am: Amount,
     ) -> Result<()> {
+        let keep_hist = self.keep_hist();
         let d = if let Some(d) = self
             .delegation_info
             .global_delegation_records_map
@@ -1386,7 +1549,7 @@ impl Staking {
             return Err(eg!("not exists"));
         };
 
-        if *KEEP_HIST {
+        if keep_hist {
             let r = DelegationRwdDetail {
                 bond: d.amount(),
                 amount: 0,
@@ -1536,6 +1699,10 @@ impl Staking {
         for o in tx.body.operations.iter() {
             if let Operation::MintFra(ref ops) = o {
                 for et in ops.entries.iter() {
+                    if let MintKind::Scheduled(id) = et.kind {
+                        self.schedule_pay(id, &et.utxo.record.public_key, et.amount);
+                        continue;
+                    }
                     if let Some(d) = self.delegation_get_mut(&et.target_pk) {
                         if DelegationState::Free == d.state {
                             if MintKind::UnStake == et.kind && d.amount() == et.amount {
@@ -1604,6 +1771,85 @@ impl Staking {
         &self.coinbase.distribution_plan
     }
 
+    /// Register a scheduled transfer whose escrow has been paid,
+    /// return the id of the new schedule.
+    pub fn schedule_add(&mut self, st: ScheduledTransfer) -> u64 {
+        let id = self.schedules.next_id;
+        self.schedules.next_id += 1;
+        self.schedules.active.insert(id, st);
+        id
+    }
+
+    /// Stop the payments of a schedule, the rest of
+    /// its escrow will be refunded to the owner.
+    pub fn schedule_cancel(&mut self, owner: &XfrPublicKey, id: u64) -> Result<()> {
+        let st = self
+            .schedules
+            .active
+            .get_mut(&id)
+            .c(d!("schedule not found"))?;
+        if st.owner != *owner {
+            return Err(eg!("not the owner of the schedule"));
+        }
+        if st.canceled {
+            return Err(eg!("already canceled"));
+        }
+        st.canceled = true;
+        Ok(())
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn schedule_get(&self, id: u64) -> Option<&ScheduledTransfer> {
+        self.schedules.active.get(&id)
+    }
+
+    /// The active schedules paid from or to `pk`, in the order of their ids
+    pub fn schedule_get_by_addr(
+        &self,
+        pk: &XfrPublicKey,
+    ) -> Vec<(u64, &ScheduledTransfer)> {
+        self.schedules
+            .active
+            .iter()
+            .filter(|(_, st)| st.owner == *pk || st.receiver == *pk)
+            .map(|(id, st)| (*id, st))
+            .collect()
+    }
+
+    /// The payments due in the current block, including the refunds
+    /// of the canceled schedules: `(id, receiver, amount)`
+    pub fn schedule_get_due(&self) -> Vec<(u64, XfrPublicKey, Amount)> {
+        self.schedules
+            .active
+            .iter()
+            .filter_map(|(id, st)| {
+                st.due_at(self.cur_height).map(|(pk, am)| (*id, pk, am))
+            })
+            .collect()
+    }
+
+    // a payment of a schedule has been minted, see `schedule_get_due`,
+    // the payments are computed from the committed state, so the schedule
+    // may have been canceled in the same block
+    fn schedule_pay(&mut self, id: u64, receiver: &XfrPublicKey, am: Amount) {
+        let h = self.cur_height;
+        if let Some(st) = self.schedules.active.get_mut(&id) {
+            if st.canceled && st.owner == *receiver && st.balance == am {
+                st.balance = 0;
+            } else if st.receiver == *receiver && st.amount == am && st.next_height <= h
+            {
+                st.balance = st.balance.saturating_sub(am);
+                st.next_height += st.interval;
+            } else {
+                return;
+            }
+            if 0 == st.balance || (!st.canceled && st.next_height > st.end_height) {
+                self.schedules.active.remove(&id);
+            }
+        }
+    }
+
     #[cfg(all(not(target_arch = "wasm32"), feature = "fin_storage"))]
     /// set_proposer_rewards sets the rewards for the block proposer
     /// All rewards are allocated to the proposer only
@@ -2055,6 +2301,80 @@ pub struct Delegation {
     pub delegation_rwd_cnt: u64,
 }
 
+/// Staking events emitted during block processing,
+/// they will be recorded only if `KEEP_HIST` is set.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum StakingEvent {
+    /// A penalty has been applied to the validator itself,
+    /// or to one of its delegators if `delegator` is not none.
+    Slashed {
+        #[allow(missing_docs)]
+        validator: XfrPublicKey,
+        #[allow(missing_docs)]
+        delegator: Option<XfrPublicKey>,
+        /// penalty amount in FRA units
+        amount: Amount,
+    },
+    /// The vote power of a validator has been punished to zero
+    Jailed {
+        #[allow(missing_docs)]
+        validator: XfrPublicKey,
+    },
+    /// The vote power of a validator has been increased from zero
+    Unjailed {
+        #[allow(missing_docs)]
+        validator: XfrPublicKey,
+    },
+    /// The commission rate of a validator has been updated
+    CommissionChanged {
+        #[allow(missing_docs)]
+        validator: XfrPublicKey,
+        #[allow(missing_docs)]
+        old: [u64; 2],
+        #[allow(missing_docs)]
+        new: [u64; 2],
+    },
+}
+
+impl StakingEvent {
+    /// The validator which this event is about
+    #[inline(always)]
+    pub fn validator(&self) -> &XfrPublicKey {
+        match self {
+            StakingEvent::Slashed { validator, .. }
+            | StakingEvent::Jailed { validator }
+            | StakingEvent::Unjailed { validator }
+            | StakingEvent::CommissionChanged { validator, .. } => validator,
+        }
+    }
+}
+
+/// What a validator did in a block,
+/// it will be recorded only if `KEEP_HIST` is set.
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorBlockRecord {
+    /// if it co-signed the block, none if not known
+    pub signed: Option<bool>,
+    /// rewards received as the proposer, including the commissions
+    pub rewards: Amount,
+}
+
+/// An entry of the unbonding queue, see `Staking::unbonding_queue_of`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Unbonding {
+    /// the undelegated address, or the temporary delegator
+    /// of a partial undelegation
+    pub id: XfrPublicKey,
+    #[allow(missing_docs)]
+    pub validator: XfrPublicKey,
+    #[allow(missing_docs)]
+    pub amount: Amount,
+    /// the principal will be freed after this height
+    pub maturity_height: BlockHeight,
+    /// `Free` if it is waiting for the payment of the coinbase
+    pub state: DelegationState,
+}
+
 /// Detail of each reward entry.
 #[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DelegationRwdDetail {
@@ -2395,6 +2715,50 @@ impl CoinBase {
     }
 }
 
+/// A transfer of `amount` FRA from the escrow of `owner` to `receiver`,
+/// paid by the coinbase every `interval` blocks until `end_height`.
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledTransfer {
+    pub owner: XfrPublicKey,
+    pub receiver: XfrPublicKey,
+    pub amount: Amount,
+    pub interval: BlockHeight,
+    /// height of the next payment
+    pub next_height: BlockHeight,
+    pub end_height: BlockHeight,
+    /// what is left in the escrow
+    pub balance: Amount,
+    /// the balance is being refunded to the owner
+    pub canceled: bool,
+}
+
+impl ScheduledTransfer {
+    /// The payment due in the block at height `h`, if any,
+    /// a canceled schedule pays its balance back to the owner.
+    pub fn due_at(&self, h: BlockHeight) -> Option<(XfrPublicKey, Amount)> {
+        if self.canceled {
+            Some((self.owner, self.balance))
+        } else if self.next_height <= h {
+            Some((self.receiver, self.amount))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+struct Schedules {
+    next_id: u64,
+    active: BTreeMap<u64, ScheduledTransfer>,
+}
+
+impl Schedules {
+    fn is_empty(&self) -> bool {
+        0 == self.next_id
+    }
+}
+
 /// `sha256(pubkey)[..20]`
 #[inline(always)]
 pub fn td_pubkey_to_td_addr(pubkey: &[u8]) -> String {
@@ -2481,7 +2845,7 @@ pub fn deny_relative_inputs(x: &TransferAsset) -> Result<()> {
 #[cfg(test)]
 #[allow(missing_docs)]
 mod test {
-    use super::*;
+    use {super::*, rand_chacha::ChaChaRng, rand_core::SeedableRng};
 
     // **NOTE**
     //
@@ -2524,4 +2888,180 @@ mod test {
 
         [lb, 100_0000]
     }
+
+    #[test]
+    fn unbonding_queue() {
+        let mut prng = ChaChaRng::from_entropy();
+        let mut gen_pk = || XfrKeyPair::generate(&mut prng).get_pk();
+        let (addr, v0, v1, tmp0, tmp1) =
+            (gen_pk(), gen_pk(), gen_pk(), gen_pk(), gen_pk());
+
+        let delegation = |id: XfrPublicKey,
+                          v: XfrPublicKey,
+                          am: Amount,
+                          end_height: BlockHeight,
+                          state: DelegationState| Delegation {
+            delegations: map! {B v => am},
+            id,
+            receiver_pk: None,
+            tmp_delegators: map! {B},
+            start_height: 1,
+            end_height,
+            state,
+            rwd_amount: 0,
+            delegation_rwd_cnt: 0,
+            proposer_rwd_cnt: 0,
+        };
+
+        let mut staking = Staking::new();
+        let mut d = delegation(addr, v0, 300, BLOCK_HEIGHT_MAX, DelegationState::Bond);
+        d.tmp_delegators = map! {B tmp0 => 100, tmp1 => 200};
+        let records = &mut staking.delegation_info.global_delegation_records_map;
+        records.insert(addr, d);
+        records.insert(tmp0, delegation(tmp0, v1, 100, 90, DelegationState::Free));
+        records.insert(tmp1, delegation(tmp1, v0, 200, 50, DelegationState::Bond));
+
+        let queue = staking.unbonding_queue_of(&addr);
+        assert_eq!(
+            queue
+                .iter()
+                .map(|u| (u.id, u.validator, u.amount, u.maturity_height))
+                .collect::<Vec<_>>(),
+            vec![(tmp1, v0, 200, 50), (tmp0, v1, 100, 90)]
+        );
+        assert_eq!(queue[1].state, DelegationState::Free);
+
+        // fully undelegated, the paid entries are not pending any more
+        let records = &mut staking.delegation_info.global_delegation_records_map;
+        records.get_mut(&addr).unwrap().end_height = 70;
+        records.get_mut(&tmp0).unwrap().state = DelegationState::Paid;
+        assert_eq!(
+            staking
+                .unbonding_queue_of(&addr)
+                .iter()
+                .map(|u| (u.id, u.maturity_height))
+                .collect::<Vec<_>>(),
+            vec![(tmp1, 50), (addr, 70)]
+        );
+
+        assert!(staking.unbonding_queue_of(&gen_pk()).is_empty());
+    }
+
+    #[test]
+    fn scheduled_transfers() {
+        use {
+            crate::data_model::ASSET_TYPE_FRA,
+            ops::mint_fra::{MintEntry, MintFraOps},
+        };
+
+        let mut prng = ChaChaRng::from_entropy();
+        let mut gen_pk = || XfrKeyPair::generate(&mut prng).get_pk();
+        let (owner, receiver) = (gen_pk(), gen_pk());
+
+        // mint the given payments as `system_mint_pay` does
+        let pay = |staking: &mut Staking, due: Vec<(u64, XfrPublicKey, Amount)>| {
+            let entries = due
+                .into_iter()
+                .map(|(id, pk, am)| {
+                    MintEntry::new(MintKind::Scheduled(id), pk, None, am, ASSET_TYPE_FRA)
+                })
+                .collect();
+            let op = Operation::MintFra(MintFraOps::new(staking.cur_height(), entries));
+            staking.coinbase_check_and_pay(&Transaction::from_operation_coinbase_mint(
+                op, 0,
+            ));
+        };
+
+        let mut staking = Staking::new();
+        let st = ScheduledTransfer {
+            owner,
+            receiver,
+            amount: 10,
+            interval: 5,
+            next_height: 10,
+            end_height: 20,
+            balance: 30,
+            canceled: false,
+        };
+        let id0 = staking.schedule_add(st.clone());
+        let id1 = staking.schedule_add(st);
+        assert_eq!(staking.schedule_get_by_addr(&receiver).len(), 2);
+        assert!(staking.schedule_get_by_addr(&gen_pk()).is_empty());
+
+        staking.set_custom_block_height(9);
+        assert!(staking.schedule_get_due().is_empty());
+        staking.set_custom_block_height(10);
+        let due = staking.schedule_get_due();
+        assert_eq!(due, vec![(id0, receiver, 10), (id1, receiver, 10)]);
+        pay(&mut staking, due);
+        assert_eq!(staking.schedule_get(id0).unwrap().balance, 20);
+        assert_eq!(staking.schedule_get(id0).unwrap().next_height, 15);
+        assert!(staking.schedule_get_due().is_empty());
+
+        // only the owner can cancel, a payment computed before
+        // the cancellation is still taken from the escrow
+        staking.set_custom_block_height(15);
+        let due = staking.schedule_get_due();
+        assert!(staking.schedule_cancel(&receiver, id1).is_err());
+        staking.schedule_cancel(&owner, id1).unwrap();
+        assert!(staking.schedule_cancel(&owner, id1).is_err());
+        pay(&mut staking, due);
+        assert_eq!(staking.schedule_get(id1).unwrap().balance, 10);
+
+        // the refund of the rest
+        staking.set_custom_block_height(16);
+        let due = staking.schedule_get_due();
+        assert_eq!(due, vec![(id1, owner, 10)]);
+        pay(&mut staking, due);
+        assert!(staking.schedule_get(id1).is_none());
+
+        // paid off
+        staking.set_custom_block_height(20);
+        let due = staking.schedule_get_due();
+        pay(&mut staking, due);
+        assert!(staking.schedule_get(id0).is_none());
+        assert!(staking.schedule_get_by_addr(&owner).is_empty());
+    }
+
+    #[test]
+    fn dry_run_records_nothing() {
+        let id = XfrKeyPair::generate(&mut ChaChaRng::from_entropy()).get_pk();
+        let v = pnk!(Validator::new_staker(
+            vec![1; 32],
+            id,
+            [1, 100],
+            StakerMemo::default()
+        ));
+        let mut staking = Staking::new();
+        staking
+            .validator_get_current_mut()
+            .unwrap()
+            .body
+            .insert(id, v.clone());
+
+        // not a part of the state
+        let mut sim = staking.dry_run();
+        assert_eq!(staking.keep_hist(), *KEEP_HIST);
+        assert!(!sim.keep_hist());
+        assert_eq!(
+            pnk!(serde_json::to_vec(&sim)),
+            pnk!(serde_json::to_vec(&staking))
+        );
+
+        // checked as usual, but the change of the commission is not recorded
+        let changed = Validator {
+            commission_rate: [2, 100],
+            ..v
+        };
+        pnk!(sim.update_staker(&changed));
+        assert_eq!(
+            sim.validator_get_current_one_by_id(&id)
+                .unwrap()
+                .commission_rate,
+            [2, 100]
+        );
+        assert!(!CHAN_STAKING_EVENT_HIST.1.lock().try_iter().any(|(_, ev)| {
+            matches!(ev, StakingEvent::CommissionChanged { validator, .. } if validator == id)
+        }));
+    }
 }
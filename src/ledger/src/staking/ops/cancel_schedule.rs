@@ -0,0 +1,88 @@
+//!
+//! # Cancel Schedule
+//!
+//! Stop a scheduled transfer, the rest of its escrow is refunded to the owner.
+//!
+
+use {
+    crate::{data_model::NoReplayToken, staking::Staking},
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    zei::{XfrKeyPair, XfrPublicKey, XfrSignature},
+};
+
+/// Used as the inner object of a `CancelSchedule Operation`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CancelScheduleOps {
+    pub(crate) body: Data,
+    pub(crate) pubkey: XfrPublicKey,
+    signature: XfrSignature,
+}
+
+impl CancelScheduleOps {
+    /// Check the validity of an operation by running it in a staking simulator.
+    #[inline(always)]
+    pub fn check_run(&self, staking_simulator: &mut Staking) -> Result<()> {
+        self.apply(staking_simulator).c(d!())
+    }
+
+    /// Cancel the schedule in the target `Staking` instance.
+    pub fn apply(&self, staking: &mut Staking) -> Result<()> {
+        self.verify()
+            .c(d!())
+            .and_then(|_| staking.schedule_cancel(&self.pubkey, self.body.id).c(d!()))
+    }
+
+    /// Verify signature.
+    #[inline(always)]
+    pub fn verify(&self) -> Result<()> {
+        self.pubkey
+            .verify(&self.body.to_bytes(), &self.signature)
+            .c(d!())
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_related_pubkeys(&self) -> Vec<XfrPublicKey> {
+        vec![self.pubkey]
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(keypair: &XfrKeyPair, id: u64, nonce: NoReplayToken) -> Self {
+        let body = Data { id, nonce };
+        let signature = keypair.sign(&body.to_bytes()).unwrap();
+        CancelScheduleOps {
+            body,
+            pubkey: keypair.get_pk(),
+            signature,
+        }
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn set_nonce(&mut self, nonce: NoReplayToken) {
+        self.body.nonce = nonce;
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_nonce(&self) -> NoReplayToken {
+        self.body.nonce
+    }
+}
+
+/// The body of a cancel schedule operation.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Data {
+    /// id of the schedule
+    pub id: u64,
+    nonce: NoReplayToken,
+}
+
+impl Data {
+    #[inline(always)]
+    fn to_bytes(&self) -> Vec<u8> {
+        pnk!(bincode::serialize(self))
+    }
+}
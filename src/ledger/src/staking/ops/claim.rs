@@ -66,6 +66,12 @@ impl ClaimOps {
         self.pubkey
     }
 
+    #[inline(always)]
+    /// The amount to claim, `None` means all the rewards
+    pub fn get_claim_amount(&self) -> Option<u64> {
+        self.body.amount
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn get_related_pubkeys(&self) -> Vec<XfrPublicKey> {
@@ -87,6 +87,8 @@ impl MintEntry {
             id: None,
             record: BlindAssetRecord::from_noah(&ba),
             lien: None,
+            cosig_rules: None,
+            lock_until: 0,
         };
 
         MintEntry {
@@ -105,4 +107,6 @@ pub enum MintKind {
     Claim,
     UnStake,
     Other,
+    /// a payment or the refund of a `ScheduledTransfer`, by its id
+    Scheduled(u64),
 }
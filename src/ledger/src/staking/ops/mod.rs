@@ -7,12 +7,16 @@
 //! In the current implementation, the first operation must be a `TransferAsset`.
 //!
 
+pub mod cancel_schedule;
 pub mod claim;
 pub mod delegation;
 pub mod fra_distribution;
 pub mod governance;
 pub mod mint_fra;
+pub mod redelegation;
 pub mod replace_staker;
+pub mod schedule_transfer;
 pub mod undelegation;
+pub mod update_params;
 pub mod update_staker;
 pub mod update_validator;
@@ -0,0 +1,156 @@
+//!
+//! # Re-Delegation Operation
+//!
+//! Move a part of a delegation from one validator to another at once,
+//! without waiting for the unbonding period.
+//!
+//! Only supported by the EVM staking.
+//!
+
+use {
+    crate::{
+        data_model::NoReplayToken,
+        staking::{evm::EVM_STAKING, td_addr_to_bytes, Amount, Staking, TendermintAddr},
+    },
+    config::abci::global_cfg::CFG,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    zei::{XfrKeyPair, XfrPublicKey, XfrSignature},
+};
+
+/// Used as the inner object of a `ReDelegation Operation`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ReDelegationOps {
+    body: Data,
+    pub(crate) pubkey: XfrPublicKey,
+    signature: XfrSignature,
+}
+
+impl ReDelegationOps {
+    /// Check the validity of an operation by running it in a staking simulator.
+    #[inline(always)]
+    pub fn check_run(&self, staking_simulator: &mut Staking) -> Result<()> {
+        self.apply(staking_simulator).c(d!())
+    }
+
+    /// Apply the re-delegation to the EVM staking.
+    pub fn apply(&self, staking: &Staking) -> Result<()> {
+        // the activation height is checked by `TxnEffect::compute_effect`
+        if staking.cur_height() as i64 <= CFG.checkpoint.evm_staking_inital_height {
+            return Err(eg!("re-delegation is only supported by the EVM staking"));
+        }
+
+        self.verify().c(d!())?;
+        self.body.check().c(d!())?;
+
+        EVM_STAKING.get().c(d!())?.write().redelegate(
+            &self.pubkey,
+            &td_addr_to_bytes(&self.body.src_validator).c(d!())?,
+            &td_addr_to_bytes(&self.body.dst_validator).c(d!())?,
+            self.body.amount,
+        )
+    }
+
+    /// Verify signature.
+    #[inline(always)]
+    pub fn verify(&self) -> Result<()> {
+        self.pubkey
+            .verify(&self.body.to_bytes(), &self.signature)
+            .c(d!())
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_related_pubkeys(&self) -> Vec<XfrPublicKey> {
+        vec![self.pubkey]
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(
+        keypair: &XfrKeyPair,
+        src_validator: TendermintAddr,
+        dst_validator: TendermintAddr,
+        amount: Amount,
+        nonce: NoReplayToken,
+    ) -> Self {
+        let body = Data {
+            src_validator,
+            dst_validator,
+            amount,
+            nonce,
+        };
+        let signature = keypair.sign(&body.to_bytes()).unwrap();
+        ReDelegationOps {
+            body,
+            pubkey: keypair.get_pk(),
+            signature,
+        }
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn set_nonce(&mut self, nonce: NoReplayToken) {
+        self.body.nonce = nonce;
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_nonce(&self) -> NoReplayToken {
+        self.body.nonce
+    }
+}
+
+// The body of a re-delegation operation.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+struct Data {
+    src_validator: TendermintAddr,
+    dst_validator: TendermintAddr,
+    amount: Amount,
+    nonce: NoReplayToken,
+}
+
+impl Data {
+    #[inline(always)]
+    fn to_bytes(&self) -> Vec<u8> {
+        pnk!(bincode::serialize(self))
+    }
+
+    // the amount and the existence of the delegation
+    // are checked by the staking contract
+    fn check(&self) -> Result<()> {
+        if self.src_validator == self.dst_validator {
+            return Err(eg!("the source and the target validator are the same"));
+        }
+        if 0 == self.amount {
+            return Err(eg!("re-delegation of zero amount"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, rand_chacha::ChaChaRng, rand_core::SeedableRng};
+
+    #[test]
+    fn redelegation_check() {
+        let kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+        let src = "A".repeat(40);
+        let dst = "B".repeat(40);
+        let nonce = NoReplayToken::default();
+
+        let op = ReDelegationOps::new(&kp, src.clone(), dst.clone(), 100, nonce);
+        assert!(op.verify().is_ok());
+        assert!(op.body.check().is_ok());
+
+        let mut forged = op.clone();
+        forged.body.amount = 200;
+        assert!(forged.verify().is_err());
+
+        let op = ReDelegationOps::new(&kp, src.clone(), src, 100, nonce);
+        assert!(op.body.check().is_err());
+        let op = ReDelegationOps::new(&kp, dst.clone(), "C".repeat(40), 0, nonce);
+        assert!(op.body.check().is_err());
+    }
+}
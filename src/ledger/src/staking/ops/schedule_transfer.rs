@@ -0,0 +1,221 @@
+//!
+//! # Schedule Transfer
+//!
+//! Pre-authorize a recurring payment of FRA: the total amount is paid into
+//! escrow by a transfer of the same transaction, then the coinbase pays the
+//! receiver every `interval` blocks, from `start_height` to `end_height`.
+//!
+
+use {
+    crate::{
+        data_model::{
+            NoReplayToken, Operation, Transaction, ASSET_TYPE_FRA,
+            BLACK_HOLE_PUBKEY_STAKING,
+        },
+        staking::{
+            deny_relative_inputs, Amount, BlockHeight, ScheduledTransfer, Staking,
+        },
+    },
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    zei::{
+        noah_api::xfr::structs::{XfrAmount, XfrAssetType},
+        XfrKeyPair, XfrPublicKey, XfrSignature,
+    },
+};
+
+/// Used as the inner object of a `ScheduleTransfer Operation`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleTransferOps {
+    pub(crate) body: Data,
+    pub(crate) pubkey: XfrPublicKey,
+    signature: XfrSignature,
+}
+
+impl ScheduleTransferOps {
+    /// Check the validity of an operation by running it in a staking simulator.
+    #[inline(always)]
+    pub fn check_run(
+        &self,
+        staking_simulator: &mut Staking,
+        tx: &Transaction,
+    ) -> Result<()> {
+        self.apply(staking_simulator, tx).c(d!())
+    }
+
+    /// Register the schedule in the target `Staking` instance.
+    pub fn apply(&self, staking: &mut Staking, tx: &Transaction) -> Result<()> {
+        self.verify().c(d!())?;
+
+        if self.body.start_height <= staking.cur_height() {
+            return Err(eg!("the start height has passed"));
+        }
+        let total = self.body.total_amount().c(d!())?;
+        let escrow = check_schedule_context(tx, &self.pubkey).c(d!())?;
+        if escrow != total {
+            return Err(eg!(format!(
+                "the escrow should be {total}, but {escrow} is paid"
+            )));
+        }
+
+        staking.schedule_add(ScheduledTransfer {
+            owner: self.pubkey,
+            receiver: self.body.receiver,
+            amount: self.body.amount,
+            interval: self.body.interval,
+            next_height: self.body.start_height,
+            end_height: self.body.end_height,
+            balance: total,
+            canceled: false,
+        });
+        Ok(())
+    }
+
+    /// Verify signature.
+    #[inline(always)]
+    pub fn verify(&self) -> Result<()> {
+        self.pubkey
+            .verify(&self.body.to_bytes(), &self.signature)
+            .c(d!())
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_related_pubkeys(&self) -> Vec<XfrPublicKey> {
+        vec![self.pubkey, self.body.receiver]
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(keypair: &XfrKeyPair, body: Data) -> Self {
+        let signature = keypair.sign(&body.to_bytes()).unwrap();
+        ScheduleTransferOps {
+            body,
+            pubkey: keypair.get_pk(),
+            signature,
+        }
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn set_nonce(&mut self, nonce: NoReplayToken) {
+        self.body.nonce = nonce;
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_nonce(&self) -> NoReplayToken {
+        self.body.nonce
+    }
+}
+
+/// The body of a schedule transfer operation.
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Data {
+    pub receiver: XfrPublicKey,
+    /// FRA paid at each height
+    pub amount: Amount,
+    pub interval: BlockHeight,
+    /// height of the first payment
+    pub start_height: BlockHeight,
+    /// no payment after this height
+    pub end_height: BlockHeight,
+    nonce: NoReplayToken,
+}
+
+impl Data {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(
+        receiver: XfrPublicKey,
+        amount: Amount,
+        interval: BlockHeight,
+        start_height: BlockHeight,
+        end_height: BlockHeight,
+        nonce: NoReplayToken,
+    ) -> Self {
+        Data {
+            receiver,
+            amount,
+            interval,
+            start_height,
+            end_height,
+            nonce,
+        }
+    }
+
+    /// How many times the receiver will be paid
+    pub fn payments(&self) -> Result<u64> {
+        if 0 == self.amount || 0 == self.interval {
+            return Err(eg!("zero amount or interval"));
+        }
+        if self.start_height > self.end_height {
+            return Err(eg!("the schedule ends before it starts"));
+        }
+        Ok((self.end_height - self.start_height) / self.interval + 1)
+    }
+
+    /// The escrow to be paid when registering the schedule
+    pub fn total_amount(&self) -> Result<Amount> {
+        self.payments()
+            .c(d!())?
+            .checked_mul(self.amount)
+            .c(d!("overflow"))
+    }
+
+    #[inline(always)]
+    fn to_bytes(&self) -> Vec<u8> {
+        pnk!(bincode::serialize(self))
+    }
+}
+
+// The FRA paid by `owner` to the staking black hole in the transaction,
+// which carries only one schedule and no delegation, as the escrows of
+// both are paid the same way.
+fn check_schedule_context(tx: &Transaction, owner: &XfrPublicKey) -> Result<Amount> {
+    let mut schedules = 0;
+    for op in tx.body.operations.iter() {
+        match op {
+            Operation::ScheduleTransfer(_) => schedules += 1,
+            Operation::Delegation(_) => {
+                return Err(eg!("a delegation can not pay the escrow"));
+            }
+            _ => {}
+        }
+    }
+    if 1 != schedules {
+        return Err(eg!("only one schedule is allowed per transaction"));
+    }
+
+    let target_pk = XfrPublicKey::from_noah(&BLACK_HOLE_PUBKEY_STAKING);
+    let mut am: Amount = 0;
+    for op in tx.body.operations.iter() {
+        if let Operation::TransferAsset(ref x) = op {
+            deny_relative_inputs(x).c(d!())?;
+            for o in x.body.outputs.iter() {
+                if o.record.public_key != target_pk {
+                    continue;
+                }
+                if x.body
+                    .transfer
+                    .inputs
+                    .iter()
+                    .any(|i| i.public_key != *owner)
+                {
+                    return Err(eg!("the escrow must be paid by the owner"));
+                }
+                match (o.record.asset_type, o.record.amount) {
+                    (
+                        XfrAssetType::NonConfidential(ty),
+                        XfrAmount::NonConfidential(n),
+                    ) if ty == ASSET_TYPE_FRA => {
+                        am = am.checked_add(n).c(d!("overflow"))?;
+                    }
+                    _ => return Err(eg!("the escrow must be non-confidential FRA")),
+                }
+            }
+        }
+    }
+    Ok(am)
+}
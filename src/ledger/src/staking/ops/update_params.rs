@@ -0,0 +1,72 @@
+//!
+//! # Update On-chain Parameters
+//!
+//! Adjust the limits and rules of the network, see `ChainParams`.
+//!
+//! **NOTE**: always use the same multi-signature rules as `UpdateValidator`.
+//!
+
+use {
+    crate::{
+        data_model::NoReplayToken,
+        staking::{cosig::CoSigOp, params::ChainParams, Staking},
+    },
+    config::abci::global_cfg::CFG,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    zei::{XfrKeyPair, XfrPublicKey},
+};
+
+/// Used as the inner object of a `UpdateParams Operation`.
+pub type UpdateParamsOps = CoSigOp<Data>;
+
+impl UpdateParamsOps {
+    /// Check the validity of an operation by running it in a staking simulator.
+    #[inline(always)]
+    pub fn check_run(&self, staking_simulator: &mut Staking) -> Result<()> {
+        self.apply(staking_simulator).c(d!())
+    }
+
+    /// Apply new parameters to the target `Staking` instance,
+    /// they take effect from the next block.
+    pub fn apply(&self, staking: &mut Staking) -> Result<()> {
+        if staking.cur_height() as i64 <= CFG.checkpoint.chain_params_height {
+            return Err(eg!("on-chain parameters are not enabled yet"));
+        }
+
+        self.verify(staking)
+            .c(d!())
+            .and_then(|_| self.data.params.check().c(d!()))
+            .and_then(|_| {
+                staking
+                    .params()
+                    .check_schedule(&self.data.params, staking.cur_height())
+                    .c(d!())
+            })
+            .map(|_| staking.params_update(&self.data.params))
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_related_pubkeys(&self) -> Vec<XfrPublicKey> {
+        self.cosigs.keys().copied().collect()
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(
+        kps: &[&XfrKeyPair],
+        params: ChainParams,
+        nonce: NoReplayToken,
+    ) -> Result<Self> {
+        let mut op = CoSigOp::create(Data { params }, nonce);
+        op.batch_sign(kps).c(d!()).map(|_| op)
+    }
+}
+
+/// The body of a `UpdateParams Operation`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Data {
+    /// The parameters to set, the `None` ones are kept unchanged.
+    pub params: ChainParams,
+}
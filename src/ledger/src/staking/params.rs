@@ -0,0 +1,240 @@
+//!
+//! # On-chain Parameters
+//!
+//! Limits and rules of the network which can be adjusted at runtime
+//! by an `UpdateParams` operation, instead of a new release.
+//! The unset ones fall back to the checkpoint file or the built-in constants.
+//!
+
+use {
+    crate::{
+        data_model::{FeeParams, BAR_TO_ABAR_TX_FEE_MIN, TX_FEE_MIN},
+        staking::{features::Feature, BlockHeight},
+    },
+    config::abci::global_cfg::CFG,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::collections::BTreeMap,
+};
+
+/// The parameters set on chain, `None` stands for the default value.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ChainParams {
+    /// the maximum size of a transaction in bytes
+    #[serde(default)]
+    pub max_txn_bytes: Option<u64>,
+    /// the maximum number of operations in a transaction
+    #[serde(default)]
+    pub max_txn_ops: Option<u64>,
+    /// the maximum number of transactions in a block
+    #[serde(default)]
+    pub max_block_txns: Option<u64>,
+    /// minimum fee of a transaction, in FRA units
+    #[serde(default)]
+    pub tx_fee_min: Option<u64>,
+    /// minimum fee of a transaction containing a `BarToAbar` operation
+    #[serde(default)]
+    pub bar_to_abar_tx_fee_min: Option<u64>,
+    /// how many blocks an undelegated amount stays locked
+    #[serde(default)]
+    pub unbond_block_cnt: Option<BlockHeight>,
+    /// the features are active in the blocks after these heights
+    #[serde(default)]
+    pub feature_heights: BTreeMap<Feature, BlockHeight>,
+}
+
+/// The parameters in effect, as served by `/params`
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ChainParamValues {
+    pub max_txn_bytes: u64,
+    pub max_txn_ops: u64,
+    pub max_block_txns: u64,
+    pub tx_fee_min: u64,
+    pub bar_to_abar_tx_fee_min: u64,
+    pub unbond_block_cnt: BlockHeight,
+    pub feature_heights: BTreeMap<Feature, BlockHeight>,
+}
+
+impl ChainParams {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn is_default(&self) -> bool {
+        Self::default().eq(self)
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn max_txn_bytes(&self) -> u64 {
+        self.max_txn_bytes.unwrap_or(CFG.checkpoint.max_txn_bytes)
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn max_txn_ops(&self) -> u64 {
+        self.max_txn_ops.unwrap_or(CFG.checkpoint.max_txn_ops)
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn max_block_txns(&self) -> u64 {
+        self.max_block_txns.unwrap_or(CFG.checkpoint.max_block_txns)
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn unbond_block_cnt(&self) -> BlockHeight {
+        self.unbond_block_cnt
+            .unwrap_or(CFG.checkpoint.unbond_block_cnt)
+    }
+
+    /// The activation height of `f`
+    #[inline(always)]
+    pub fn feature_height(&self, f: Feature) -> BlockHeight {
+        self.feature_heights
+            .get(&f)
+            .copied()
+            .unwrap_or_else(|| f.checkpoint_height())
+    }
+
+    /// The fee rules in effect
+    pub fn fee_params(&self) -> FeeParams {
+        let mut params = FeeParams::current();
+        params.tx_fee_min = self.tx_fee_min.unwrap_or(TX_FEE_MIN);
+        params.bar_to_abar_tx_fee_min = self
+            .bar_to_abar_tx_fee_min
+            .unwrap_or(BAR_TO_ABAR_TX_FEE_MIN);
+        params
+    }
+
+    #[allow(missing_docs)]
+    pub fn values(&self) -> ChainParamValues {
+        let fee = self.fee_params();
+        ChainParamValues {
+            max_txn_bytes: self.max_txn_bytes(),
+            max_txn_ops: self.max_txn_ops(),
+            max_block_txns: self.max_block_txns(),
+            tx_fee_min: fee.tx_fee_min,
+            bar_to_abar_tx_fee_min: fee.bar_to_abar_tx_fee_min,
+            unbond_block_cnt: self.unbond_block_cnt(),
+            feature_heights: Feature::ALL
+                .iter()
+                .map(|f| (*f, self.feature_height(*f)))
+                .collect(),
+        }
+    }
+
+    /// None of the limits can be zero,
+    /// a zero fee would let anyone flood the network for free.
+    pub fn check(&self) -> Result<()> {
+        [
+            ("max_txn_bytes", self.max_txn_bytes),
+            ("max_txn_ops", self.max_txn_ops),
+            ("max_block_txns", self.max_block_txns),
+            ("tx_fee_min", self.tx_fee_min),
+            ("bar_to_abar_tx_fee_min", self.bar_to_abar_tx_fee_min),
+            ("unbond_block_cnt", self.unbond_block_cnt),
+        ]
+        .iter()
+        .try_for_each(|(name, v)| {
+            if Some(0) == *v {
+                Err(eg!(format!("`{name}` can not be zero")))
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    /// A feature can only be scheduled in the future before it is active,
+    /// the nodes which have already applied a block under the old rules
+    /// would diverge otherwise.
+    pub fn check_schedule(&self, update: &ChainParams, h: BlockHeight) -> Result<()> {
+        update.feature_heights.iter().try_for_each(|(f, at)| {
+            if self.feature_height(*f) < h {
+                Err(eg!(format!("feature `{f:?}` is already active")))
+            } else if *at < h {
+                Err(eg!(format!(
+                    "feature `{f:?}` can not be activated in the past"
+                )))
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    /// Apply the set parameters of `update`, keep the others
+    pub fn merge(&mut self, update: &ChainParams) {
+        macro_rules! set {
+            ($($field: ident),+) => {
+                $(
+                    if update.$field.is_some() {
+                        self.$field = update.$field;
+                    }
+                )+
+            };
+        }
+        set!(
+            max_txn_bytes,
+            max_txn_ops,
+            max_block_txns,
+            tx_fee_min,
+            bar_to_abar_tx_fee_min,
+            unbond_block_cnt
+        );
+        self.feature_heights.extend(update.feature_heights.iter());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chain_params() {
+        let mut params = ChainParams::default();
+        assert!(params.is_default());
+        assert_eq!(params.fee_params(), FeeParams::current());
+        assert_eq!(
+            params.values().unbond_block_cnt,
+            CFG.checkpoint.unbond_block_cnt
+        );
+
+        let update = ChainParams {
+            tx_fee_min: Some(2 * TX_FEE_MIN),
+            max_block_txns: Some(100),
+            ..Default::default()
+        };
+        assert!(update.check().is_ok());
+        params.merge(&update);
+        params.merge(&ChainParams {
+            unbond_block_cnt: Some(10),
+            ..Default::default()
+        });
+
+        let values = params.values();
+        assert_eq!(values.tx_fee_min, 2 * TX_FEE_MIN);
+        assert_eq!(values.bar_to_abar_tx_fee_min, BAR_TO_ABAR_TX_FEE_MIN);
+        assert_eq!(values.max_block_txns, 100);
+        assert_eq!(values.max_txn_ops, CFG.checkpoint.max_txn_ops);
+        assert_eq!(values.unbond_block_cnt, 10);
+
+        let zero = ChainParams {
+            tx_fee_min: Some(0),
+            ..Default::default()
+        };
+        assert!(zero.check().is_err());
+
+        let h = Feature::ReDelegation.checkpoint_height();
+        let schedule = |at| ChainParams {
+            feature_heights: BTreeMap::from([(Feature::ReDelegation, at)]),
+            ..Default::default()
+        };
+        assert!(params.check_schedule(&schedule(h + 10), h).is_ok());
+        assert!(params.check_schedule(&schedule(h + 10), h + 1).is_err());
+        params.merge(&schedule(h + 10));
+        assert_eq!(params.feature_height(Feature::ReDelegation), h + 10);
+        assert!(params.check_schedule(&schedule(h + 20), h + 5).is_ok());
+        assert!(params.check_schedule(&schedule(h + 2), h + 5).is_err());
+        assert!(params.check_schedule(&schedule(h + 20), h + 11).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//!
+//! # Read access to a ledger
+//!
+//! What the consumers of a ledger read from it, as object-safe traits, so
+//! they can be given a `MockLedger` instead of a `LedgerState` in tests.
+//! Only the unauthenticated views are included, the proofs belong to the
+//! merkle trees of a real ledger.
+//!
+
+use {
+    super::LedgerState,
+    crate::data_model::{
+        AssetType, AssetTypeCode, FinalizedTransaction, StateCommitmentData, TxnSID,
+        TxoSID, UnAuthenticatedUtxo, Utxo,
+    },
+    globutils::HashOf,
+    ruc::*,
+    std::collections::BTreeMap,
+    zei::{OwnerMemo, XfrPublicKey},
+};
+
+/// The current state of a ledger
+pub trait LedgerAccess {
+    /// An unspent TXO along with the transaction creating it
+    fn get_utxo_light(&self, sid: TxoSID) -> Option<UnAuthenticatedUtxo>;
+
+    /// The unspent TXOs of `addr`, with their owner memos
+    fn get_owned_utxos(
+        &self,
+        addr: &XfrPublicKey,
+    ) -> Result<BTreeMap<TxoSID, (Utxo, Option<OwnerMemo>)>>;
+
+    /// The definition of an asset
+    fn get_asset_type(&self, code: &AssetTypeCode) -> Option<AssetType>;
+
+    /// The sequence number of the last issuance of an asset
+    fn get_issuance_num(&self, code: &AssetTypeCode) -> Option<u64>;
+
+    /// The state commitment, along with the block commit count
+    fn get_state_commitment(&self) -> (HashOf<Option<StateCommitmentData>>, u64);
+
+    /// The sequence id to build transactions with
+    fn get_block_commit_count(&self) -> u64;
+
+    /// Height of the last committed block
+    fn get_tendermint_height(&self) -> u64;
+}
+
+/// The history of a ledger
+pub trait ArchiveAccess {
+    /// A committed transaction
+    fn get_transaction_light(&self, id: TxnSID) -> Result<FinalizedTransaction>;
+
+    /// Number of the committed transactions
+    fn get_transaction_count(&self) -> usize;
+
+    /// Number of the committed blocks
+    fn get_block_count(&self) -> usize;
+}
+
+impl LedgerAccess for LedgerState {
+    fn get_utxo_light(&self, sid: TxoSID) -> Option<UnAuthenticatedUtxo> {
+        LedgerState::get_utxo_light(self, sid)
+    }
+
+    fn get_owned_utxos(
+        &self,
+        addr: &XfrPublicKey,
+    ) -> Result<BTreeMap<TxoSID, (Utxo, Option<OwnerMemo>)>> {
+        LedgerState::get_owned_utxos(self, addr)
+    }
+
+    fn get_asset_type(&self, code: &AssetTypeCode) -> Option<AssetType> {
+        LedgerState::get_asset_type(self, code)
+    }
+
+    fn get_issuance_num(&self, code: &AssetTypeCode) -> Option<u64> {
+        LedgerState::get_issuance_num(self, code)
+    }
+
+    fn get_state_commitment(&self) -> (HashOf<Option<StateCommitmentData>>, u64) {
+        LedgerState::get_state_commitment(self)
+    }
+
+    fn get_block_commit_count(&self) -> u64 {
+        LedgerState::get_block_commit_count(self)
+    }
+
+    fn get_tendermint_height(&self) -> u64 {
+        LedgerState::get_tendermint_height(self)
+    }
+}
+
+impl ArchiveAccess for LedgerState {
+    fn get_transaction_light(&self, id: TxnSID) -> Result<FinalizedTransaction> {
+        LedgerState::get_transaction_light(self, id)
+    }
+
+    fn get_transaction_count(&self) -> usize {
+        LedgerState::get_transaction_count(self)
+    }
+
+    fn get_block_count(&self) -> usize {
+        LedgerState::get_block_count(self)
+    }
+}
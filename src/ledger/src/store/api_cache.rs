@@ -4,13 +4,14 @@
 use {
     crate::{
         data_model::{
-            ATxoSID, AssetTypeCode, AssetTypePrefix, DefineAsset, IssueAsset,
-            IssuerPublicKey, Operation, StateCommitmentData, Transaction, TxOutput,
-            TxnIDHash, TxnSID, TxoSID, XfrAddress,
+            ATxoSID, AnchorLabel, AssetTypeCode, AssetTypePrefix, DefineAsset,
+            IssueAsset, IssuerPublicKey, Operation, StateCommitmentData, Transaction,
+            TxOutput, TxnIDHash, TxnSID, TxoSID, XfrAddress,
         },
         staking::{
             ops::mint_fra::MintEntry, Amount, BlockHeight, DelegationRwdDetail,
-            CHAN_D_AMOUNT_HIST, CHAN_GLOB_RATE_HIST, CHAN_V_SELF_D_HIST, KEEP_HIST,
+            StakingEvent, TendermintAddr, CHAN_D_AMOUNT_HIST, CHAN_GLOB_RATE_HIST,
+            CHAN_STAKING_EVENT_HIST, CHAN_V_BLOCK_HIST, CHAN_V_SELF_D_HIST, KEEP_HIST,
         },
         store::LedgerState,
     },
@@ -20,11 +21,187 @@ use {
     ruc::*,
     serde::{Deserialize, Serialize},
     std::collections::HashSet,
-    zei::{noah_api::anon_xfr::structs::AxfrOwnerMemo, OwnerMemo, XfrPublicKey},
+    zei::{
+        noah_algebra::bn254::BN254Scalar, noah_api::anon_xfr::structs::AxfrOwnerMemo,
+        OwnerMemo, XfrPublicKey,
+    },
 };
 
 type Issuances = Vec<(TxOutput, Option<OwnerMemo>)>;
 
+/// Number of blocks in an epoch of `ApiCache::staking_validator_hist`
+pub const VALIDATOR_HIST_EPOCH: BlockHeight = 1000;
+
+/// Signing record and rewards of a validator in an epoch
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ValidatorEpochRecord {
+    pub signed_cnt: u64,
+    pub missed_cnt: u64,
+    pub rewards: Amount,
+}
+
+/// A staking event and the block height where it happened
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StakingEventRecord {
+    pub height: BlockHeight,
+    pub event: StakingEvent,
+}
+
+/// Kinds of operations, used to classify transactions
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpKind {
+    Transfer,
+    Issue,
+    DefineAsset,
+    UpdateMemo,
+    UpdateStaker,
+    Delegation,
+    #[serde(rename = "undelegation")]
+    UnDelegation,
+    #[serde(rename = "redelegation")]
+    ReDelegation,
+    Claim,
+    UpdateValidator,
+    Governance,
+    FraDistribution,
+    Mint,
+    ConvertAccount,
+    BarToAbar,
+    AbarToBar,
+    AnonTransfer,
+    ReplaceStaker,
+    UpdateTransferPolicy,
+    UpdateParams,
+    Swap,
+    ScheduleTransfer,
+    CancelSchedule,
+    AnchorData,
+}
+
+impl From<&Operation> for OpKind {
+    fn from(op: &Operation) -> Self {
+        match op {
+            Operation::TransferAsset(_) => OpKind::Transfer,
+            Operation::IssueAsset(_) => OpKind::Issue,
+            Operation::DefineAsset(_) => OpKind::DefineAsset,
+            Operation::UpdateMemo(_) => OpKind::UpdateMemo,
+            Operation::UpdateStaker(_) => OpKind::UpdateStaker,
+            Operation::Delegation(_) => OpKind::Delegation,
+            Operation::UnDelegation(_) => OpKind::UnDelegation,
+            Operation::ReDelegation(_) => OpKind::ReDelegation,
+            Operation::Claim(_) => OpKind::Claim,
+            Operation::UpdateValidator(_) => OpKind::UpdateValidator,
+            Operation::Governance(_) => OpKind::Governance,
+            Operation::FraDistribution(_) => OpKind::FraDistribution,
+            Operation::MintFra(_) => OpKind::Mint,
+            Operation::ConvertAccount(_) => OpKind::ConvertAccount,
+            Operation::BarToAbar(_) => OpKind::BarToAbar,
+            Operation::AbarToBar(_) => OpKind::AbarToBar,
+            Operation::TransferAnonAsset(_) => OpKind::AnonTransfer,
+            Operation::ReplaceStaker(_) => OpKind::ReplaceStaker,
+            Operation::UpdateTransferPolicy(_) => OpKind::UpdateTransferPolicy,
+            Operation::UpdateParams(_) => OpKind::UpdateParams,
+            Operation::SwapAsset(_) => OpKind::Swap,
+            Operation::ScheduleTransfer(_) => OpKind::ScheduleTransfer,
+            Operation::CancelSchedule(_) => OpKind::CancelSchedule,
+            Operation::AnchorData(_) => OpKind::AnchorData,
+        }
+    }
+}
+
+impl OpKind {
+    /// Kinds of the operations in a transaction, without duplicates
+    pub fn of_txn(txn: &Transaction) -> Vec<OpKind> {
+        let mut kinds = vec![];
+        for k in txn.body.operations.iter().map(OpKind::from) {
+            if !kinds.contains(&k) {
+                kinds.push(k);
+            }
+        }
+        kinds
+    }
+
+    /// Parse a comma separated list like `define_asset,transfer`,
+    /// `staking` stands for all the staking operations
+    pub fn parse_list(s: &str) -> Result<Vec<OpKind>> {
+        let mut kinds = vec![];
+        for name in s.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()) {
+            if "staking" == name {
+                kinds.extend_from_slice(&[
+                    OpKind::UpdateStaker,
+                    OpKind::Delegation,
+                    OpKind::UnDelegation,
+                    OpKind::ReDelegation,
+                    OpKind::Claim,
+                    OpKind::UpdateValidator,
+                    OpKind::Governance,
+                    OpKind::ReplaceStaker,
+                ]);
+            } else {
+                kinds.push(
+                    serde_json::from_value(serde_json::Value::String(name.to_owned()))
+                        .c(d!(format!("unknown operation: {name}")))?,
+                );
+            }
+        }
+        if kinds.is_empty() {
+            return Err(eg!("empty operation list"));
+        }
+        Ok(kinds)
+    }
+}
+
+/// Where and when a transaction was committed
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TxnMeta {
+    pub height: BlockHeight,
+    /// header time of the block, in unix seconds
+    pub timestamp: i64,
+    pub ops: Vec<OpKind>,
+}
+
+/// A transaction anchoring a document hash, see `Operation::AnchorData`
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AnchorRecord {
+    pub txn_sid: TxnSID,
+    pub txn_hash: String,
+    pub signer: XfrPublicKey,
+    pub label: AnchorLabel,
+    pub height: BlockHeight,
+    /// header time of the block, in unix seconds
+    pub timestamp: i64,
+}
+
+/// A committed tendermint block, the transactions are empty if it has none
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BlockMeta {
+    pub height: BlockHeight,
+    /// header time of the block, in unix seconds
+    pub timestamp: i64,
+    /// tendermint address of the proposer, empty if unknown
+    pub proposer: TendermintAddr,
+    /// index of the block in the ledger, `None` if it has no transactions
+    pub block_index: Option<u64>,
+    pub txns: Vec<TxnSID>,
+}
+
+/// The abar merkle tree after a block
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AbarRoot {
+    pub version: u64,
+    pub root: BN254Scalar,
+    /// number of leaves
+    pub size: u64,
+}
+
 /// Used in APIs
 #[derive(Clone, Deserialize, Serialize)]
 pub struct ApiCache {
@@ -39,6 +216,8 @@ pub struct ApiCache {
     pub coinbase_oper_hist: Mapx<XfrAddress, Mapxnk<BlockHeight, MintEntry>>,
     /// Created assets
     pub created_assets: Mapx<IssuerPublicKey, Mapxnk<AssetTypeCode, DefineAsset>>,
+    /// codes of the created assets, in the order of definition
+    pub defined_assets: Mapxnk<u64, AssetTypeCode>,
     /// issuance mapped by public key
     pub issuances: Mapx<IssuerPublicKey, Issuances>,
     /// issuance mapped by token code
@@ -57,8 +236,16 @@ pub struct ApiCache {
     pub txn_sid_to_hash: Mapxnk<TxnSID, String>,
     /// txn hash to txn sid
     pub txn_hash_to_sid: Mapx<String, TxnSID>,
+    /// block metadata and operation kinds of a txn
+    pub txn_meta: Mapxnk<TxnSID, TxnMeta>,
+    /// transactions and header info of each tendermint block
+    pub block_meta: Mapxnk<BlockHeight, BlockMeta>,
     /// max (latest) atxo sid at block height
     pub height_to_max_atxo: Mapxnk<BlockHeight, Option<usize>>,
+    /// root of the abar merkle tree at block height
+    pub abar_roots: Mapxnk<BlockHeight, AbarRoot>,
+    /// anchors of a document hash (hex encoded), in the order of commitment
+    pub anchors: Mapx<String, Vec<AnchorRecord>>,
     /// global rate history
     pub staking_global_rate_hist: Mapxnk<BlockHeight, [u128; 2]>,
     /// - self-delegation amount history
@@ -71,6 +258,14 @@ pub struct ApiCache {
     /// rewards history, used on some pulic nodes, such as fullnode
     pub staking_delegation_rwd_hist:
         Mapx<XfrPublicKey, Mapxnk<BlockHeight, DelegationRwdDetail>>,
+    /// staking events related to a validator, in the order of occurrence
+    pub staking_validator_events: Mapx<XfrPublicKey, Vec<StakingEventRecord>>,
+    /// staking events related to a delegator, in the order of occurrence
+    pub staking_delegator_events: Mapx<XfrPublicKey, Vec<StakingEventRecord>>,
+    /// validators being jailed currently, and the height where they were jailed
+    pub staking_jailed: Mapx<XfrPublicKey, BlockHeight>,
+    /// signing records and rewards of the validators, keyed by the epoch index
+    pub staking_validator_hist: Mapx<XfrPublicKey, Mapxnk<u64, ValidatorEpochRecord>>,
     /// there are no transactions lost before last_sid
     pub last_sid: Mapx<String, u64>,
     /// State commitment history.
@@ -93,6 +288,7 @@ impl ApiCache {
                 "api_cache/{prefix}coinbase_oper_hist",
             )),
             created_assets: new_mapx!(format!("api_cache/{prefix}created_assets",)),
+            defined_assets: new_mapxnk!(format!("api_cache/{prefix}defined_assets",)),
             issuances: new_mapx!(format!("api_cache/{prefix}issuances",)),
             token_code_issuances: new_mapx!(format!(
                 "api_cache/{prefix}token_code_issuances",
@@ -106,12 +302,16 @@ impl ApiCache {
             atxo_to_txnid: new_mapx!(format!("api_cache/{prefix}atxo_to_txnid",)),
             txn_sid_to_hash: new_mapxnk!(format!("api_cache/{prefix}txn_sid_to_hash",)),
             txn_hash_to_sid: new_mapx!(format!("api_cache/{prefix}txn_hash_to_sid",)),
+            txn_meta: new_mapxnk!(format!("api_cache/{prefix}txn_meta",)),
+            block_meta: new_mapxnk!(format!("api_cache/{prefix}block_meta",)),
             staking_global_rate_hist: new_mapxnk!(format!(
                 "api_cache/{prefix}staking_global_rate_hist",
             )),
             height_to_max_atxo: new_mapxnk!(format!(
                 "api_cache/{prefix}height_to_max_atxo",
             )),
+            abar_roots: new_mapxnk!(format!("api_cache/{prefix}abar_roots",)),
+            anchors: new_mapx!(format!("api_cache/{prefix}anchors",)),
             staking_self_delegation_hist: new_mapx!(format!(
                 "api_cache/{prefix}staking_self_delegation_hist",
             )),
@@ -121,6 +321,16 @@ impl ApiCache {
             staking_delegation_rwd_hist: new_mapx!(format!(
                 "api_cache/{prefix}staking_delegation_rwd_hist",
             )),
+            staking_validator_events: new_mapx!(format!(
+                "api_cache/{prefix}staking_validator_events",
+            )),
+            staking_delegator_events: new_mapx!(format!(
+                "api_cache/{prefix}staking_delegator_events",
+            )),
+            staking_jailed: new_mapx!(format!("api_cache/{prefix}staking_jailed",)),
+            staking_validator_hist: new_mapx!(format!(
+                "api_cache/{prefix}staking_validator_hist",
+            )),
             last_sid: new_mapx!(format!("api_cache/{prefix}last_sid",)),
             state_commitment_version: None,
         }
@@ -142,16 +352,19 @@ impl ApiCache {
         let issuer = creation.pubkey;
         let mut tmp = creation.clone();
         tmp.body.asset.code = code;
-        self.created_assets
-            .entry(issuer)
-            .or_insert_with(|| {
-                new_mapxnk!(format!(
-                    "api_cache/{}created_assets/{}",
-                    prefix,
-                    issuer.to_base64()
-                ))
-            })
-            .insert(code, tmp);
+        let mut assets = self.created_assets.entry(issuer).or_insert_with(|| {
+            new_mapxnk!(format!(
+                "api_cache/{}created_assets/{}",
+                prefix,
+                issuer.to_base64()
+            ))
+        });
+        // a replayed definition keeps its place in the order
+        if !assets.contains_key(&code) {
+            self.defined_assets
+                .insert(self.defined_assets.len() as u64, code);
+        }
+        assets.insert(code, tmp);
     }
 
     /// Cache issuance records
@@ -175,6 +388,41 @@ impl ApiCache {
         save_issuance!(token_issuances, token_code);
     }
 
+    /// Index a staking event by its validator and delegator(if any)
+    pub fn cache_staking_event(&mut self, height: BlockHeight, event: StakingEvent) {
+        let validator = *event.validator();
+        match event {
+            StakingEvent::Jailed { .. } => {
+                self.staking_jailed.insert(validator, height);
+            }
+            StakingEvent::Unjailed { .. } => {
+                // a validator gets its first vote power, not an unjailing
+                if self.staking_jailed.remove(&validator).is_none() {
+                    return;
+                }
+            }
+            _ => {}
+        }
+
+        if let StakingEvent::Slashed {
+            delegator: Some(d), ..
+        } = event
+        {
+            self.staking_delegator_events
+                .entry(d)
+                .or_insert_with(Vec::new)
+                .push(StakingEventRecord {
+                    height,
+                    event: event.clone(),
+                });
+        }
+
+        self.staking_validator_events
+            .entry(validator)
+            .or_insert_with(Vec::new)
+            .push(StakingEventRecord { height, event });
+    }
+
     /// Cache history style data
     ///
     /// Note: This function's data will migrate to findora scanner.
@@ -211,6 +459,38 @@ impl ApiCache {
                     .insert(h, r);
             });
 
+        CHAN_STAKING_EVENT_HIST
+            .1
+            .lock()
+            .try_iter()
+            .for_each(|(h, ev)| self.cache_staking_event(h, ev));
+
+        let prefix = self.prefix.clone();
+        CHAN_V_BLOCK_HIST
+            .1
+            .lock()
+            .try_iter()
+            .for_each(|(pk, h, r)| {
+                #[allow(unused_mut)]
+                let mut hist =
+                    self.staking_validator_hist.entry(pk).or_insert_with(|| {
+                        new_mapxnk!(format!(
+                            "api_cache/{}staking_validator_hist/{}",
+                            prefix,
+                            wallet::public_key_to_base64(&pk)
+                        ))
+                    });
+                let mut e = hist
+                    .entry(h / VALIDATOR_HIST_EPOCH)
+                    .or_insert_with(ValidatorEpochRecord::default);
+                match r.signed {
+                    Some(true) => e.signed_cnt += 1,
+                    Some(false) => e.missed_cnt += 1,
+                    None => {}
+                }
+                e.rewards = e.rewards.saturating_add(r.rewards);
+            });
+
         //         CHAN_D_RWD_HIST.1.lock().try_iter().for_each(|(pk, h, r)| {
         // #[allow(unused_mut)]
         // let mut dd =
@@ -270,10 +550,14 @@ where
             Operation::ReplaceStaker(i) => staking_gen!(i),
             Operation::Delegation(i) => staking_gen!(i),
             Operation::UnDelegation(i) => staking_gen!(i),
+            Operation::ReDelegation(i) => staking_gen!(i),
             Operation::Claim(i) => staking_gen!(i),
             Operation::UpdateValidator(i) => staking_gen!(i),
             Operation::Governance(i) => staking_gen!(i),
             Operation::FraDistribution(i) => staking_gen!(i),
+            Operation::UpdateParams(i) => staking_gen!(i),
+            Operation::ScheduleTransfer(i) => staking_gen!(i),
+            Operation::CancelSchedule(i) => staking_gen!(i),
             Operation::MintFra(i) => staking_gen!(i),
             Operation::BarToAbar(i) => {
                 related_addresses.insert(XfrAddress {
@@ -306,6 +590,11 @@ where
                     });
                 }
             }
+            Operation::SwapAsset(swap) => {
+                for leg in swap.legs.iter() {
+                    related_addresses.insert(XfrAddress { key: leg.party });
+                }
+            }
             Operation::IssueAsset(issue_asset) => {
                 related_addresses.insert(XfrAddress {
                     key: issue_asset.pubkey.key,
@@ -321,6 +610,12 @@ where
                     key: update_memo.pubkey,
                 });
             }
+            Operation::UpdateTransferPolicy(update) => {
+                related_addresses.insert(XfrAddress { key: update.pubkey });
+            }
+            Operation::AnchorData(anchor) => {
+                related_addresses.insert(XfrAddress { key: anchor.pubkey });
+            }
         }
     }
     related_addresses
@@ -332,11 +627,14 @@ pub fn get_transferred_nonconfidential_assets(
 ) -> HashSet<AssetTypeCode> {
     let mut transferred_assets = HashSet::new();
     for op in &txn.body.operations {
-        if let Operation::TransferAsset(transfer) = op {
-            for input in transfer.body.transfer.inputs.iter() {
-                if let Some(asset_type) = input.asset_type.get_asset_type() {
-                    transferred_assets.insert(AssetTypeCode { val: asset_type });
-                }
+        let transfer = match op {
+            Operation::TransferAsset(transfer) => transfer,
+            Operation::SwapAsset(swap) => &swap.transfer,
+            _ => continue,
+        };
+        for input in transfer.body.transfer.inputs.iter() {
+            if let Some(asset_type) = input.asset_type.get_asset_type() {
+                transferred_assets.insert(AssetTypeCode { val: asset_type });
             }
         }
     }
@@ -500,7 +798,30 @@ pub fn update_api_cache(ledger: &mut LedgerState) -> Result<()> {
 
     api_cache.cache_hist_data();
 
-    let block = if let Some(b) = ledger.blocks.last() {
+    // the last block is left from a previous height if this one has no transactions
+    let last_block = ledger.blocks.last();
+    let new_block = last_block
+        .as_ref()
+        .and_then(|b| b.txns.first())
+        .map(|tx| !api_cache.txn_meta.contains_key(&tx.tx_id))
+        .unwrap_or(false);
+    let height = ledger.status.td_commit_height;
+    api_cache.block_meta.insert(
+        height,
+        BlockMeta {
+            height,
+            timestamp: ledger.status.td_commit_time,
+            proposer: ledger.status.td_commit_proposer.clone(),
+            block_index: alt!(new_block, Some(ledger.blocks.len() as u64 - 1), None),
+            txns: last_block
+                .as_ref()
+                .filter(|_| new_block)
+                .map(|b| b.txns.iter().map(|tx| tx.tx_id).collect())
+                .unwrap_or_default(),
+        },
+    );
+
+    let block = if let Some(b) = last_block {
         b
     } else {
         ledger.api_cache = Some(api_cache);
@@ -608,6 +929,17 @@ pub fn update_api_cache(ledger: &mut LedgerState) -> Result<()> {
                 .insert(txn_sid, Default::default());
         }
 
+        if new_block {
+            api_cache.txn_meta.insert(
+                txn_sid,
+                TxnMeta {
+                    height: ledger.status.td_commit_height,
+                    timestamp: ledger.status.td_commit_time,
+                    ops: OpKind::of_txn(&curr_txn),
+                },
+            );
+        }
+
         // Add created asset
         for op in &curr_txn.body.operations {
             match op {
@@ -620,6 +952,20 @@ pub fn update_api_cache(ledger: &mut LedgerState) -> Result<()> {
                 Operation::IssueAsset(issue_asset) => {
                     api_cache.cache_issuance(&issue_asset);
                 }
+                Operation::AnchorData(anchor) => {
+                    api_cache
+                        .anchors
+                        .entry(hex::encode(anchor.body.hash))
+                        .or_insert_with(Vec::new)
+                        .push(AnchorRecord {
+                            txn_sid,
+                            txn_hash: curr_txn.hash_tm().hex().to_uppercase(),
+                            signer: anchor.pubkey,
+                            label: anchor.body.label.clone(),
+                            height: ledger.status.td_commit_height,
+                            timestamp: ledger.status.td_commit_time,
+                        });
+                }
                 _ => {}
             };
         }
@@ -663,6 +1009,19 @@ pub fn update_api_cache(ledger: &mut LedgerState) -> Result<()> {
     let block_height = ledger.status.td_commit_height;
     api_cache.height_to_max_atxo.insert(block_height, max_atxo);
 
+    // Update block height to the root of the abar merkle tree
+    if let Some(d) = ledger.status.anon_state_commitment_data.as_ref() {
+        let (version, size) = ledger.get_abar_tree_info().c(d!())?;
+        api_cache.abar_roots.insert(
+            block_height,
+            AbarRoot {
+                version,
+                root: d.abar_root_hash,
+                size,
+            },
+        );
+    }
+
     ledger.api_cache = Some(api_cache);
 
     Ok(())
@@ -0,0 +1,272 @@
+//!
+//! # Property tests of the consensus safety
+//!
+//! Random sequences of valid and invalid transactions are applied to the
+//! ledger, asserting that:
+//! - applying any of them never panics
+//! - a rejected transaction leaves the block untouched
+//! - the same sequence gives the same state commitments on another ledger,
+//!   and so does the sequence without the rejected transactions
+//!
+//! The runner is seeded, so a failure is reproduced by running it again,
+//! `PROPTEST_CASES` sets the number of sequences, 16 by default.
+//!
+
+#![cfg(test)]
+#![allow(missing_docs)]
+
+use {
+    super::{helpers::*, LedgerState},
+    crate::data_model::{
+        AssetRules, AssetTypeCode, IssuerPublicKey, Operation, Transaction, TxnEffect,
+        TxoRef, TxoSID,
+    },
+    proptest::{
+        prelude::*,
+        test_runner::{Config, RngAlgorithm, TestRng, TestRunner},
+    },
+    rand_chacha::ChaChaRng,
+    rand_core::SeedableRng,
+    std::env,
+    zei::{noah_api::xfr::asset_record::AssetRecordType, XfrKeyPair},
+};
+
+const KEYS: u8 = 3;
+const CODES: u8 = 3;
+
+#[derive(Clone, Debug)]
+enum Action {
+    Define {
+        issuer: u8,
+        code: u8,
+    },
+    Issue {
+        issuer: u8,
+        code: u8,
+        seq_num: u64,
+        confidential: bool,
+    },
+    IssueAndTransfer {
+        issuer: u8,
+        code: u8,
+        seq_num: u64,
+        to: u8,
+    },
+    // resubmit an earlier transaction
+    Replay {
+        idx: usize,
+    },
+}
+
+// how a built transaction is broken
+#[derive(Clone, Copy, Debug)]
+enum Tamper {
+    Untouched,
+    // bump the issuance number after signing
+    SeqNum,
+    // spend an arbitrary TXO
+    Input(u64),
+    // swap the signer keys
+    Signer,
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        (0..KEYS, 0..CODES).prop_map(|(issuer, code)| Action::Define { issuer, code }),
+        (0..KEYS, 0..CODES, 0..8u64, any::<bool>()).prop_map(
+            |(issuer, code, seq_num, confidential)| Action::Issue {
+                issuer,
+                code,
+                seq_num,
+                confidential
+            }
+        ),
+        (0..KEYS, 0..CODES, 0..8u64, 0..KEYS).prop_map(|(issuer, code, seq_num, to)| {
+            Action::IssueAndTransfer {
+                issuer,
+                code,
+                seq_num,
+                to,
+            }
+        }),
+        any::<usize>().prop_map(|idx| Action::Replay { idx }),
+    ]
+}
+
+fn tamper() -> impl Strategy<Value = Tamper> {
+    prop_oneof![
+        4 => Just(Tamper::Untouched),
+        1 => Just(Tamper::SeqNum),
+        1 => (0..16u64).prop_map(Tamper::Input),
+        1 => Just(Tamper::Signer),
+    ]
+}
+
+fn blocks() -> impl Strategy<Value = Vec<Vec<(Action, Tamper)>>> {
+    prop::collection::vec(prop::collection::vec((action(), tamper()), 1..6), 1..5)
+}
+
+fn key(i: u8) -> XfrKeyPair {
+    XfrKeyPair::generate(&mut ChaChaRng::from_seed([i; 32]))
+}
+
+fn code(i: u8) -> AssetTypeCode {
+    AssetTypeCode::new_from_vec(vec![i + 1; 32])
+}
+
+// Build the transactions once, all runs are fed the same bytes
+fn build(blocks: &[Vec<(Action, Tamper)>]) -> Vec<Vec<Transaction>> {
+    let mut ledger = LedgerState::tmp_ledger();
+    let mut built: Vec<Transaction> = vec![];
+    let mut res = vec![];
+    for b in blocks.iter() {
+        let mut txns = vec![];
+        for (a, t) in b.iter() {
+            let mut tx = build_txn(&mut ledger, a, &built);
+            tamper_with(&mut tx, *t);
+            built.push(tx.clone());
+            txns.push(tx);
+        }
+        res.push(txns);
+    }
+    res
+}
+
+fn build_txn(
+    ledger: &mut LedgerState,
+    a: &Action,
+    built: &[Transaction],
+) -> Transaction {
+    match *a {
+        Action::Define { issuer, code: c } => create_definition_transaction(
+            &code(c),
+            &key(issuer),
+            AssetRules::default(),
+            None,
+            0,
+        )
+        .unwrap(),
+        Action::Issue {
+            issuer,
+            code: c,
+            seq_num,
+            confidential,
+        } => {
+            let record_type = if confidential {
+                AssetRecordType::ConfidentialAmount_ConfidentialAssetType
+            } else {
+                AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType
+            };
+            create_issuance_txn(ledger, &code(c), 10, seq_num, record_type, &key(issuer))
+        }
+        Action::IssueAndTransfer {
+            issuer,
+            code: c,
+            seq_num,
+            to,
+        } => {
+            create_issue_and_transfer_txn(
+                ledger,
+                &code(c),
+                10,
+                &key(issuer),
+                &key(to).get_pk(),
+                seq_num,
+            )
+            .0
+        }
+        Action::Replay { idx } if !built.is_empty() => built[idx % built.len()].clone(),
+        Action::Replay { .. } => Transaction::from_seq_id(0),
+    }
+}
+
+fn tamper_with(tx: &mut Transaction, t: Tamper) {
+    let stranger = IssuerPublicKey {
+        key: key(KEYS).get_pk(),
+    };
+    for op in tx.body.operations.iter_mut() {
+        match (t, op) {
+            (Tamper::SeqNum, Operation::IssueAsset(i)) => i.body.seq_num += 1,
+            (Tamper::Input(sid), Operation::TransferAsset(x)) => {
+                x.body.inputs = vec![TxoRef::Absolute(TxoSID(sid))];
+            }
+            (Tamper::Signer, Operation::DefineAsset(d)) => d.pubkey = stranger,
+            (Tamper::Signer, Operation::IssueAsset(i)) => i.pubkey = stranger,
+            (Tamper::Signer, Operation::TransferAsset(x)) => x.body_signatures.clear(),
+            _ => {}
+        }
+    }
+}
+
+// Apply each block on a new ledger,
+// returns the state commitments and whether each transaction is accepted
+#[allow(clippy::type_complexity)]
+fn run(blocks: &[Vec<Transaction>]) -> (Vec<String>, Vec<Vec<bool>>) {
+    let mut ledger = LedgerState::tmp_ledger();
+    let mut hashes = vec![];
+    let mut accepted = vec![];
+    for txns in blocks.iter() {
+        let mut block = ledger.start_block().unwrap();
+        let features = ledger.get_staking().active_features();
+        let acc = txns
+            .iter()
+            .map(|tx| {
+                let before = block.clone();
+                let res = TxnEffect::compute_effect(tx.clone(), &features)
+                    .and_then(|te| ledger.apply_transaction(&mut block, te));
+                if res.is_err() {
+                    assert_eq!(
+                        block, before,
+                        "a rejected transaction changed the block"
+                    );
+                }
+                res.is_ok()
+            })
+            .collect();
+        ledger.finish_block(block).unwrap();
+        hashes.push(serde_json::to_string(&ledger.get_state_commitment().0).unwrap());
+        accepted.push(acc);
+    }
+    (hashes, accepted)
+}
+
+#[test]
+fn apply_is_deterministic() {
+    let cases = env::var("PROPTEST_CASES")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(16);
+    let mut runner = TestRunner::new_with_rng(
+        Config {
+            cases,
+            ..Config::default()
+        },
+        TestRng::deterministic_rng(RngAlgorithm::ChaCha),
+    );
+
+    runner
+        .run(&blocks(), |actions| {
+            let blocks = build(&actions);
+            let (hashes, accepted) = run(&blocks);
+
+            let (again, accepted_again) = run(&blocks);
+            prop_assert_eq!(&hashes, &again);
+            prop_assert_eq!(&accepted, &accepted_again);
+
+            let valid = blocks
+                .iter()
+                .zip(accepted.iter())
+                .map(|(txns, acc)| {
+                    txns.iter()
+                        .zip(acc.iter())
+                        .filter(|(_, ok)| **ok)
+                        .map(|(tx, _)| tx.clone())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let (without_rejected, _) = run(&valid);
+            prop_assert_eq!(&hashes, &without_rejected);
+            Ok(())
+        })
+        .unwrap();
+}
@@ -0,0 +1,154 @@
+//!
+//! # Checkpointed genesis
+//!
+//! The committed state of a ledger, i.e. the asset types, the unspent TXOs
+//! and the staking state, exported as the `app_state` of a new genesis,
+//! along with the last block of the chain it is exported from.
+//!
+//! The new chain starts at the height after that block, so the heights in
+//! the staking state keep their meaning. The unspent TXOs are renumbered
+//! from 0, as the outputs of a single transaction in the first block.
+//!
+//! Not carried over: the history, the anonymous records and the contract
+//! state.
+//!
+
+use {
+    super::LedgerState,
+    crate::{
+        data_model::{
+            AssetType, AssetTypeCode, StateCommitmentData, Transaction, TxOutput,
+            TxnTempSID,
+        },
+        staking::Staking,
+    },
+    globutils::HashOf,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+};
+
+/// Current version of the genesis state
+pub const GENESIS_STATE_VERSION: u64 = 1;
+
+/// The last block of the chain a genesis state is exported from
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ChainCheckpoint {
+    pub chain_id: String,
+    /// tendermint height
+    pub height: u64,
+    pub block_commit_count: u64,
+    pub state_commitment: HashOf<Option<StateCommitmentData>>,
+    /// anonymous records of the chain, which are not carried over
+    pub abar_count: u64,
+}
+
+/// The ledger part of the `app_state` of a genesis
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GenesisState {
+    pub version: u64,
+    pub checkpoint: ChainCheckpoint,
+    pub asset_types: Vec<(AssetTypeCode, AssetType)>,
+    pub issuance_nums: Vec<(AssetTypeCode, u64)>,
+    pub issuance_amounts: Vec<(AssetTypeCode, u64)>,
+    /// in the order of their sids in the old chain
+    pub utxos: Vec<TxOutput>,
+    pub staking: Staking,
+}
+
+/// The `app_state` of a genesis, `ledger` is missing in a plain one
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AppState {
+    #[serde(default)]
+    pub ledger: Option<GenesisState>,
+}
+
+impl GenesisState {
+    /// Parse the `app_state_bytes` of `InitChain`,
+    /// `None` if there is no ledger state in it
+    pub fn from_app_state(bytes: &[u8]) -> Result<Option<Self>> {
+        if bytes.iter().all(|b| b.is_ascii_whitespace()) {
+            return Ok(None);
+        }
+        let g = serde_json::from_slice::<AppState>(bytes).c(d!())?.ledger;
+        if let Some(g) = g.as_ref() {
+            if g.version > GENESIS_STATE_VERSION {
+                return Err(eg!(format!(
+                    "unsupported genesis state version: {}",
+                    g.version
+                )));
+            }
+        }
+        Ok(g)
+    }
+}
+
+impl LedgerState {
+    /// Export the committed state as a genesis state of a new chain
+    /// restarting from this one, known as `chain_id`.
+    ///
+    /// NOTE: the anonymous records are not exported.
+    pub fn export_genesis(&self, chain_id: &str) -> Result<GenesisState> {
+        let status = &self.status;
+        let checkpoint = ChainCheckpoint {
+            chain_id: chain_id.to_owned(),
+            height: self.get_tendermint_height(),
+            block_commit_count: self.get_block_commit_count(),
+            state_commitment: status
+                .state_commitment_versions
+                .last()
+                .c(d!("no committed block"))?,
+            abar_count: status.next_atxo.0,
+        };
+
+        let mut utxos = status.utxos.iter().collect::<Vec<_>>();
+        utxos.sort_by_key(|(sid, _)| *sid);
+
+        Ok(GenesisState {
+            version: GENESIS_STATE_VERSION,
+            checkpoint,
+            asset_types: status.asset_types.iter().collect(),
+            issuance_nums: status.issuance_num.iter().collect(),
+            issuance_amounts: status.issuance_amounts.iter().collect(),
+            utxos: utxos.into_iter().map(|(_, u)| u.0).collect(),
+            staking: self.get_staking().clone(),
+        })
+    }
+
+    /// Load a genesis state into an empty ledger, in `InitChain`.
+    ///
+    /// The TXOs are committed as the first block of the ledger.
+    pub fn import_genesis(&mut self, g: GenesisState) -> Result<()> {
+        if 0 != self.get_block_commit_count() || 0 != self.get_next_txo().0 {
+            return Err(eg!("the ledger is not empty"));
+        }
+
+        let status = &mut self.status;
+        for (code, at) in g.asset_types {
+            status.asset_types.insert(code, at);
+        }
+        for (code, n) in g.issuance_nums {
+            status.issuance_num.insert(code, n);
+        }
+        for (code, n) in g.issuance_amounts {
+            status.issuance_amounts.insert(code, n);
+        }
+        status.genesis_checkpoint = Some(g.checkpoint);
+
+        let mut block = self.start_block().c(d!())?;
+        block.txns.push(Transaction::from_seq_id(0));
+        block.temp_sids.push(TxnTempSID(0));
+        block.txos.push(g.utxos.into_iter().map(Some).collect());
+        block.staking_simulator = g.staking;
+
+        self.finish_block(block).c(d!()).map(|_| ())
+    }
+
+    /// The chain this ledger restarted from, if any
+    #[inline(always)]
+    pub fn get_genesis_checkpoint(&self) -> Option<&ChainCheckpoint> {
+        self.status.genesis_checkpoint.as_ref()
+    }
+}
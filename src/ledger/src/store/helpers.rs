@@ -107,7 +107,8 @@ pub fn apply_transaction(
     ledger: &mut LedgerState,
     tx: Transaction,
 ) -> (TxnSID, Vec<TxoSID>) {
-    match TxnEffect::compute_effect(tx) {
+    let features = ledger.get_staking().active_features();
+    match TxnEffect::compute_effect(tx, &features) {
         Ok(effect) => {
             let mut block = ledger.start_block().unwrap();
             let temp_sid = ledger.apply_transaction(&mut block, effect).unwrap();
@@ -135,12 +136,34 @@ pub fn create_issue_and_transfer_txn(
     issuer_keys: &XfrKeyPair,
     recipient_pk: &XfrPublicKey,
     seq_num: u64,
+) -> (Transaction, AssetRecord) {
+    create_issue_and_transfer_txn_with_record_type(
+        ledger,
+        code,
+        amount,
+        issuer_keys,
+        recipient_pk,
+        seq_num,
+        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+    )
+}
+
+/// `create_issue_and_transfer_txn` with both records of `record_type`
+#[allow(clippy::too_many_arguments)]
+pub fn create_issue_and_transfer_txn_with_record_type(
+    ledger: &mut LedgerState,
+    code: &AssetTypeCode,
+    amount: u64,
+    issuer_keys: &XfrKeyPair,
+    recipient_pk: &XfrPublicKey,
+    seq_num: u64,
+    record_type: AssetRecordType,
 ) -> (Transaction, AssetRecord) {
     // issue operation
     let ar_template = AssetRecordTemplate::with_no_asset_tracing(
         amount,
         code.val,
-        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+        record_type,
         issuer_keys.get_pk().into_noah(),
     );
     let pc_gens = PedersenCommitmentRistretto::default();
@@ -155,6 +178,8 @@ pub fn create_issue_and_transfer_txn(
                 id: None,
                 record: BlindAssetRecord::from_noah(&ba),
                 lien: None,
+                cosig_rules: None,
+                lock_until: 0,
             },
             None,
         )],
@@ -174,7 +199,7 @@ pub fn create_issue_and_transfer_txn(
     let ar_template = AssetRecordTemplate::with_no_asset_tracing(
         amount,
         code.val,
-        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+        record_type,
         recipient_pk.into_noah(),
     );
     let ar = AssetRecord::from_template_no_identity_tracing(
@@ -210,6 +235,7 @@ pub fn create_issue_and_transfer_txn_with_asset_tracing(
     issuer_keys: &XfrKeyPair,
     recipient_pk: &XfrPublicKey,
     seq_num: u64,
+    record_type: AssetRecordType,
     tracing_policy: TracingPolicy,
 ) -> (Transaction, AssetRecord) {
     let tracing_policies = TracingPolicies::from_policy(tracing_policy);
@@ -243,6 +269,8 @@ pub fn create_issue_and_transfer_txn_with_asset_tracing(
                 id: None,
                 record: BlindAssetRecord::from_noah(&ba),
                 lien: None,
+                cosig_rules: None,
+                lock_until: 0,
             },
             None,
         )],
@@ -262,7 +290,7 @@ pub fn create_issue_and_transfer_txn_with_asset_tracing(
     let ar_template = AssetRecordTemplate::with_asset_tracing(
         amount,
         code.val,
-        AssetRecordType::ConfidentialAmount_NonConfidentialAssetType,
+        record_type,
         recipient_pk.into_noah(),
         tracing_policies.clone(),
     );
@@ -327,6 +355,8 @@ pub fn create_issuance_txn(
                 id: None,
                 record: BlindAssetRecord::from_noah(&ba),
                 lien: None,
+                cosig_rules: None,
+                lock_until: 0,
             },
             None,
         )],
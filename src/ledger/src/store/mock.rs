@@ -0,0 +1,277 @@
+//!
+//! # An in-memory ledger for unit tests
+//!
+//! `MockLedger` keeps the TXOs, the assets and the transactions in plain
+//! maps and moves through a script of transitions. The effect of each
+//! transaction is computed, but checked only for spent inputs and
+//! redefined assets, so a test can reach any state with a few of them.
+//!
+
+use {
+    super::access::{ArchiveAccess, LedgerAccess},
+    crate::{
+        data_model::{
+            AssetType, AssetTypeCode, FinalizedTransaction, OutputPosition,
+            StateCommitmentData, Transaction, TxnEffect, TxnSID, TxoSID,
+            UnAuthenticatedUtxo, Utxo,
+        },
+        staking::features::FeatureSet,
+    },
+    globutils::HashOf,
+    ruc::*,
+    std::collections::{BTreeMap, HashMap, VecDeque},
+    zei::{OwnerMemo, XfrPublicKey},
+};
+
+/// A step of the script of a `MockLedger`
+#[derive(Clone, Debug)]
+pub enum MockTransition {
+    /// Apply a transaction to the open block
+    Txn(Transaction),
+    /// Commit the open block, it may be empty
+    EndBlock,
+}
+
+/// A ledger in memory, see the module doc
+#[derive(Clone, Debug, Default)]
+pub struct MockLedger {
+    script: VecDeque<MockTransition>,
+    txns: Vec<FinalizedTransaction>,
+    utxos: BTreeMap<TxoSID, (Utxo, TxnSID, OutputPosition)>,
+    asset_types: HashMap<AssetTypeCode, AssetType>,
+    issuance_nums: HashMap<AssetTypeCode, u64>,
+    next_txo: u64,
+    block_count: u64,
+}
+
+impl MockLedger {
+    /// An empty ledger, with no script
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty ledger, which will go through `script`
+    pub fn with_script(script: Vec<MockTransition>) -> Self {
+        MockLedger {
+            script: script.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Append a transition to the script
+    pub fn push(&mut self, t: MockTransition) -> &mut Self {
+        self.script.push_back(t);
+        self
+    }
+
+    /// Number of the transitions left in the script
+    pub fn remaining(&self) -> usize {
+        self.script.len()
+    }
+
+    /// Apply the next transition of the script,
+    /// returns `false` if there is none.
+    pub fn step(&mut self) -> Result<bool> {
+        match self.script.pop_front() {
+            Some(t) => self.apply(t).c(d!()).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Apply the transitions of the script up to the next `EndBlock`, included
+    pub fn next_block(&mut self) -> Result<()> {
+        while let Some(t) = self.script.pop_front() {
+            let end = matches!(t, MockTransition::EndBlock);
+            self.apply(t).c(d!())?;
+            if end {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the whole script
+    pub fn run(&mut self) -> Result<()> {
+        while self.step().c(d!())? {}
+        Ok(())
+    }
+
+    /// Apply a transition out of the script
+    pub fn apply(&mut self, t: MockTransition) -> Result<()> {
+        match t {
+            MockTransition::Txn(txn) => self.apply_txn(txn).c(d!()),
+            MockTransition::EndBlock => {
+                self.block_count += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn apply_txn(&mut self, txn: Transaction) -> Result<()> {
+        let effect = TxnEffect::compute_effect(txn, &FeatureSet::all()).c(d!())?;
+
+        if let Some(sid) = effect
+            .input_txos
+            .keys()
+            .find(|sid| !self.utxos.contains_key(sid))
+        {
+            return Err(eg!(format!("input {} is unknown or spent", sid.0)));
+        }
+        if let Some(code) = effect
+            .new_asset_codes
+            .keys()
+            .find(|code| self.asset_types.contains_key(code))
+        {
+            return Err(eg!(format!("asset {} is defined", code.to_base64())));
+        }
+
+        for sid in effect.input_txos.keys() {
+            self.utxos.remove(sid);
+        }
+        self.asset_types.extend(effect.new_asset_codes);
+        for (code, nums) in effect.new_issuance_nums {
+            if let Some(n) = nums.into_iter().max() {
+                self.issuance_nums.insert(code, n);
+            }
+        }
+
+        let tx_id = TxnSID(self.txns.len());
+        let mut txo_ids = vec![];
+        for (i, txo) in effect.txos.into_iter().enumerate() {
+            if let Some(mut txo) = txo {
+                let sid = TxoSID(self.next_txo);
+                self.next_txo += 1;
+                txo.id = Some(sid);
+                self.utxos
+                    .insert(sid, (Utxo(txo), tx_id, OutputPosition(i)));
+                txo_ids.push(sid);
+            }
+        }
+        self.txns.push(FinalizedTransaction {
+            txn: effect.txn,
+            tx_id,
+            txo_ids,
+            atxo_ids: vec![],
+            merkle_id: tx_id.0 as u64,
+        });
+
+        Ok(())
+    }
+}
+
+impl LedgerAccess for MockLedger {
+    fn get_utxo_light(&self, sid: TxoSID) -> Option<UnAuthenticatedUtxo> {
+        self.utxos
+            .get(&sid)
+            .map(|(utxo, tx_id, pos)| UnAuthenticatedUtxo {
+                utxo: utxo.clone(),
+                txn: self.txns[tx_id.0].clone(),
+                utxo_location: *pos,
+            })
+    }
+
+    fn get_owned_utxos(
+        &self,
+        addr: &XfrPublicKey,
+    ) -> Result<BTreeMap<TxoSID, (Utxo, Option<OwnerMemo>)>> {
+        Ok(self
+            .utxos
+            .iter()
+            .filter(|(_, (utxo, _, _))| &utxo.0.record.public_key == addr)
+            .map(|(sid, (utxo, tx_id, pos))| {
+                let memo = self.txns[tx_id.0]
+                    .txn
+                    .get_owner_memos_ref()
+                    .get(pos.0)
+                    .and_then(|m| m.clone());
+                (*sid, (utxo.clone(), memo))
+            })
+            .collect())
+    }
+
+    fn get_asset_type(&self, code: &AssetTypeCode) -> Option<AssetType> {
+        self.asset_types.get(code).cloned()
+    }
+
+    fn get_issuance_num(&self, code: &AssetTypeCode) -> Option<u64> {
+        self.issuance_nums.get(code).copied()
+    }
+
+    fn get_state_commitment(&self) -> (HashOf<Option<StateCommitmentData>>, u64) {
+        (HashOf::new(&None), self.block_count)
+    }
+
+    fn get_block_commit_count(&self) -> u64 {
+        self.block_count
+    }
+
+    fn get_tendermint_height(&self) -> u64 {
+        self.block_count
+    }
+}
+
+impl ArchiveAccess for MockLedger {
+    fn get_transaction_light(&self, id: TxnSID) -> Result<FinalizedTransaction> {
+        self.txns.get(id.0).cloned().c(d!())
+    }
+
+    fn get_transaction_count(&self) -> usize {
+        self.txns.len()
+    }
+
+    fn get_block_count(&self) -> usize {
+        self.block_count as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{data_model::AssetRules, store::helpers::create_definition_transaction},
+        rand_chacha::ChaChaRng,
+        rand_core::SeedableRng,
+        zei::XfrKeyPair,
+    };
+
+    #[test]
+    fn test_scripted_define_asset() {
+        let kp = XfrKeyPair::generate(&mut ChaChaRng::from_seed([0; 32]));
+        let code = AssetTypeCode::gen_random();
+        let txn =
+            create_definition_transaction(&code, &kp, AssetRules::default(), None, 0)
+                .unwrap();
+
+        let mut ledger = MockLedger::with_script(vec![
+            MockTransition::EndBlock,
+            MockTransition::Txn(txn.clone()),
+            MockTransition::EndBlock,
+            MockTransition::Txn(txn),
+        ]);
+        let view: &dyn LedgerAccess = &ledger;
+        assert_eq!(0, view.get_block_commit_count());
+
+        ledger.next_block().unwrap();
+        assert_eq!(1, ledger.get_block_commit_count());
+        assert_eq!(2, ledger.remaining());
+
+        ledger.next_block().unwrap();
+        let view: &dyn LedgerAccess = &ledger;
+        assert_eq!(2, view.get_block_commit_count());
+        let archive: &dyn ArchiveAccess = &ledger;
+        assert_eq!(1, archive.get_transaction_count());
+        let (code, _) = ledger
+            .get_transaction_light(TxnSID(0))
+            .map(|tx| TxnEffect::compute_effect(tx.txn, &FeatureSet::all()).unwrap())
+            .unwrap()
+            .new_asset_codes
+            .into_iter()
+            .next()
+            .unwrap();
+        assert!(ledger.get_asset_type(&code).is_some());
+
+        // the same asset again
+        assert!(ledger.step().is_err());
+        assert_eq!(1, ledger.get_transaction_count());
+    }
+}
@@ -2,10 +2,18 @@
 //! # Findora ledger store implementation
 //!
 
+pub mod access;
 pub mod api_cache;
+mod fuzz;
+pub mod genesis;
 pub mod helpers;
+pub mod mock;
+pub mod snapshot;
+pub mod state_hash;
 mod test;
 pub mod utils;
+pub mod utxo_tree;
+pub mod verify;
 
 pub use fbnc;
 
@@ -15,12 +23,15 @@ use {
             ATxoSID, AnonStateCommitmentData, AssetType, AssetTypeCode, AssetTypePrefix,
             AuthenticatedBlock, AuthenticatedTransaction, AuthenticatedUtxo,
             AuthenticatedUtxoStatus, BlockEffect, BlockSID, FinalizedBlock,
-            FinalizedTransaction, IssuerPublicKey, Operation, OutputPosition,
-            StateCommitmentData, Transaction, TxnEffect, TxnSID, TxnTempSID, TxoSID,
+            FinalizedTransaction, IssuerPublicKey, Operation, OutputPosition, Reject,
+            StateCommitmentData, StateDomainHashes, Transaction, TxOutput, TxnEffect,
+            TxnResult, TxnSID, TxnTempSID, TxnValidationError, TxoSID,
             UnAuthenticatedUtxo, Utxo, UtxoStatus, BLACK_HOLE_PUBKEY,
         },
         staking::{
-            Amount, Power, Staking, TendermintAddrRef, FF_PK_EXTRA_120_0000, FF_PK_LIST,
+            features::{Feature, FeatureSet},
+            td_addr_to_string, Amount, Power, Staking, TendermintAddr,
+            TendermintAddrRef, ValidatorBlockRecord, FF_PK_EXTRA_120_0000, FF_PK_LIST,
             FRA_TOTAL_AMOUNT, KEEP_HIST,
         },
         LSSED_VAR, SNAPSHOT_ENTRIES_DIR,
@@ -32,6 +43,7 @@ use {
     digest::Digest,
     fbnc::{new_mapx, new_mapxnk, new_vecx, Mapx, Mapxnk, Vecx},
     fin_db::RocksDB,
+    genesis::ChainCheckpoint,
     globutils::wallet,
     globutils::{HashOf, ProofOf},
     merkle_tree::AppendOnlyMerkle,
@@ -43,6 +55,7 @@ use {
     sha2::Sha512,
     sliding_set::SlidingSet,
     sparse_merkle_tree::{Key, SmtMap256},
+    state_hash::{StateTrees, TouchedState},
     std::{
         collections::{BTreeMap, HashMap, HashSet},
         env,
@@ -56,6 +69,7 @@ use {
         state::{ChainState, State},
         store::{ImmutablePrefixedStore, PrefixedStore},
     },
+    utxo_tree::SparseMerkleProof,
     zei::{
         noah_accumulators::merkle_tree::{
             ImmutablePersistentMerkleTree, PersistentMerkleTree, Proof, TreePath,
@@ -82,7 +96,22 @@ use {
 };
 
 const TRANSACTION_WINDOW_WIDTH: u64 = 128;
-const VERSION_WINDOW: u64 = 100;
+/// Anonymous proofs against a root more than this many versions behind
+/// the abar merkle tree are rejected
+pub const VERSION_WINDOW: u64 = 100;
+
+// TXO SIDs put into the UTXO tree per block before the state domains are
+// activated, about 64 node writes each
+const UTXO_TREE_BACKFILL: u64 = 1024;
+
+/// Current schema version of the `LedgerStatus` snapshot
+pub const LEDGER_STATUS_VERSION: u64 = 1;
+
+// Prefix of a compressed `LedgerStatus` snapshot, followed by the version of
+// the encoding (u64, big endian) and a zstd frame of the JSON;
+// a plain JSON snapshot starts with '{'.
+const STATUS_ZSTD_MAGIC: &[u8; 8] = b"FRALSTAT";
+const STATUS_ZSTD_VERSION: u64 = 1;
 const GENESIS_ANON_HASH: &str =
     "2501917d72f915a3afb91ae561a0e4230d5d4edbb9b62fb7e2ea41f18c3038b5";
 
@@ -93,9 +122,14 @@ type TmpSidMap = HashMap<TxnTempSID, (TxnSID, Vec<TxoSID>)>;
 pub struct LedgerState {
     // major part of State
     status: LedgerStatus,
+    // where the ledger data is stored
+    basedir: String,
 
     /// The `FinalizedTransaction`s consist of a Transaction and an index into
     /// `merkle` representing its hash.
+    ///
+    /// NOTE: blocks live in the on-disk fbnc store and are loaded on demand
+    /// by `get_transaction*`, so the history does not stay in memory.
     pub blocks: Vecx<FinalizedBlock>,
     /// <tx id> => [<block id>, <tx idx in block>]
     pub tx_to_block_location: Mapxnk<TxnSID, [usize; 2]>,
@@ -117,6 +151,8 @@ pub struct LedgerState {
     abar_state: Arc<RwLock<State<RocksDB>>>,
     // Sparse Merkle Tree to hold nullifier Set
     nullifier_set: Arc<RwLock<SmtMap256<RocksDB>>>,
+    // Sparse Merkle Trees of the state domains
+    state_trees: Arc<RwLock<StateTrees>>,
 }
 
 impl LedgerState {
@@ -148,12 +184,12 @@ impl LedgerState {
         &self,
         block: &mut BlockEffect,
         txe: TxnEffect,
-    ) -> Result<TxnTempSID> {
+    ) -> TxnResult<TxnTempSID> {
         let tx = txe.txn.clone();
+        let features = self.get_staking().active_features();
         self.status
-            .check_txn_effects(&txe, &self.abar_state)
-            .c(d!())
-            .and_then(|_| block.add_txn_effect(txe).c(d!()))
+            .check_txn_effects(&txe, &features, &self.abar_state)
+            .and_then(|_| block.add_txn_effect(txe))
             .map(|tmpid| {
                 // NOTE: set at the last position
                 block.staking_simulator.coinbase_check_and_pay(&tx);
@@ -161,6 +197,64 @@ impl LedgerState {
             })
     }
 
+    /// Check a transaction against the current state as `apply_transaction` does,
+    /// without changing anything.
+    ///
+    /// Returns the sids the transaction would get as the first one of the next block.
+    pub fn simulate_transaction(
+        &self,
+        tx: Transaction,
+    ) -> TxnResult<(TxnSID, Vec<TxoSID>)> {
+        let mut block = BlockEffect::default();
+        *block.get_staking_simulator_mut() = self.get_staking().dry_run();
+        let features = self.get_staking().active_features();
+        let txe = TxnEffect::compute_effect(tx, &features)?;
+        self.apply_transaction(&mut block, txe)?;
+
+        let base_sid = self.get_next_txo().0;
+        let txo_sids = block
+            .txos
+            .first()
+            .c(d!())?
+            .iter()
+            .enumerate()
+            .filter(|(_, txo)| txo.is_some())
+            .map(|(idx, _)| TxoSID(base_sid + idx as u64))
+            .collect();
+
+        Ok((self.get_next_txn(), txo_sids))
+    }
+
+    /// Apply a whole block of transactions in one call, invalid ones are skipped.
+    ///
+    /// The UTXO bitmap, the merkle trees and the ledger status are updated
+    /// only once in `finish_block`, instead of once per transaction.
+    ///
+    /// Returns the final sids of each transaction in the input order,
+    /// `None` for the skipped ones.
+    pub fn apply_block(
+        &mut self,
+        txns: Vec<Transaction>,
+    ) -> Result<Vec<Option<(TxnSID, Vec<TxoSID>)>>> {
+        let mut block = self.start_block().c(d!())?;
+        let features = self.get_staking().active_features();
+        let tmp_sids = txns
+            .into_iter()
+            .map(|tx| {
+                ruc::info!(TxnEffect::compute_effect(tx, &features)
+                    .c(d!())
+                    .and_then(|te| self.apply_transaction(&mut block, te).c(d!())))
+                .ok()
+            })
+            .collect::<Vec<_>>();
+
+        let mut tsm = self.finish_block(block).c(d!())?;
+        Ok(tmp_sids
+            .into_iter()
+            .map(|id| id.and_then(|id| tsm.remove(&id)))
+            .collect())
+    }
+
     // Update the UTXO bitmap
     fn update_utxo_map(
         &mut self,
@@ -313,10 +407,12 @@ impl LedgerState {
         }
 
         let backup_next_txn_sid = self.status.next_txn.0;
-        let (tsm, base_sid, max_sid) = self.status.apply_block_effects(&mut block);
+        let (tsm, base_sid, max_sid, touched) =
+            self.status.apply_block_effects(&mut block).c(d!())?;
 
         self.update_utxo_map(base_sid, max_sid, &block.temp_sids, &tsm)
             .c(d!())
+            .and_then(|_| self.update_state_trees(touched).c(d!()))
             .and_then(|_| self.update_state(block, &tsm, backup_next_txn_sid).c(d!()))
             .map(|_| tsm)
     }
@@ -385,6 +481,30 @@ impl LedgerState {
         self.status.td_commit_height
     }
 
+    /// Set the header time of the block being committed, in unix seconds
+    #[inline(always)]
+    pub fn set_tendermint_time(&mut self, timestamp: i64) {
+        self.status.td_commit_time = timestamp;
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_tendermint_time(&self) -> i64 {
+        self.status.td_commit_time
+    }
+
+    /// Set the proposer address of the block being committed
+    #[inline(always)]
+    pub fn set_tendermint_proposer(&mut self, td_addr: &[u8]) {
+        self.status.td_commit_proposer = td_addr_to_string(td_addr);
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_tendermint_proposer(&self) -> &str {
+        &self.status.td_commit_proposer
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn get_next_txn(&self) -> TxnSID {
@@ -432,6 +552,73 @@ impl LedgerState {
         ret
     }
 
+    // whether the last state commitment covers the state domains
+    #[inline(always)]
+    fn state_domains_activated(&self) -> bool {
+        self.status
+            .state_commitment_data
+            .as_ref()
+            .map_or(false, |d| d.domains.is_some())
+    }
+
+    // The TXOs below the cursor are in the UTXO tree already, those above
+    // it are left to `backfill_utxo_tree`. The other trees are filled by
+    // `compute_state_domains` once activated, only the changes are applied
+    // after that.
+    fn update_state_trees(&self, touched: TouchedState) -> Result<()> {
+        let mut trees = self.state_trees.write();
+        let cursor = self.status.utxo_tree_cursor;
+        for sid in touched.txos.into_iter().filter(|sid| sid.0 < cursor) {
+            let utxo = self.status.utxos.get(&sid);
+            trees.set_utxo(sid, utxo.as_ref().map(|u| &u.0));
+        }
+
+        if !self.state_domains_activated() {
+            return Ok(());
+        }
+        for code in touched.asset_codes {
+            if let Some(at) = self.status.asset_types.get(&code) {
+                trees.set_asset_type(&code, &at).c(d!())?;
+            }
+            if let Some(num) = self.status.issuance_num.get(&code) {
+                trees.set_issuance_num(&code, num).c(d!())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Put the unspent TXOs from the cursor on into the UTXO tree,
+    // at most `limit` SIDs are visited
+    fn backfill_utxo_tree(&mut self, limit: u64) {
+        let from = self.status.utxo_tree_cursor;
+        let to = self.status.next_txo.0.min(from.saturating_add(limit));
+        let mut trees = self.state_trees.write();
+        for sid in (from..to).map(TxoSID) {
+            if let Some(utxo) = self.status.utxos.get(&sid) {
+                trees.set_utxo(sid, Some(&utxo.0));
+            }
+        }
+        self.status.utxo_tree_cursor = to;
+    }
+
+    fn compute_state_domains(&mut self) -> Result<StateDomainHashes> {
+        // what is left of the backfill, and the TXOs of this block
+        self.backfill_utxo_tree(u64::MAX);
+
+        let mut trees = self.state_trees.write();
+        if !self.state_domains_activated() {
+            for (code, at) in self.status.asset_types.iter() {
+                trees.set_asset_type(&code, &at).c(d!())?;
+            }
+            for (code, num) in self.status.issuance_num.iter() {
+                trees.set_issuance_num(&code, num).c(d!())?;
+            }
+        }
+        trees.commit().c(d!())?;
+
+        Ok(trees.domains(self.get_staking()))
+    }
+
     #[allow(missing_docs)]
     pub fn compute_and_save_state_commitment_data(&mut self, pulse_count: u64) {
         let staking_data = if self.get_tendermint_height()
@@ -442,6 +629,16 @@ impl LedgerState {
         } else {
             None
         };
+        let domains =
+            if self.get_tendermint_height() < CFG.checkpoint.state_domains_height {
+                // spread the filling of the UTXO tree over the blocks
+                // before the activation
+                self.backfill_utxo_tree(UTXO_TREE_BACKFILL);
+                pnk!(self.state_trees.write().commit());
+                None
+            } else {
+                Some(pnk!(self.compute_state_domains()))
+            };
 
         let state_commitment_data = StateCommitmentData {
             bitmap: self.utxo_map.write().compute_checksum(),
@@ -458,6 +655,7 @@ impl LedgerState {
             txo_count: self.get_next_txo().0,
             pulse_count,
             staking: staking_data,
+            domains,
         };
 
         self.status
@@ -532,6 +730,17 @@ impl LedgerState {
         ))
     }
 
+    /// The version and the number of leaves of the committed merkle tree
+    /// of abar commitments
+    pub fn get_abar_tree_info(&self) -> Result<(u64, u64)> {
+        let abar_query_state = State::new(self.abar_state.read().chain_state(), false);
+        let store = ImmutablePrefixedStore::new("abar_store", &abar_query_state);
+        let mt = ImmutablePersistentMerkleTree::new(store).c(d!())?;
+
+        // no abar is ever removed from `ax_utxos`
+        Ok((mt.version(), self.status.ax_utxos.len() as u64))
+    }
+
     #[inline(always)]
     /// Generates a MTLeafInfo from the latest committed version of tree from committed state and
     /// ignore session cache
@@ -609,6 +818,7 @@ impl LedgerState {
         let utxo_map_path = format!("{}/{}utxo_map", basedir, &prefix);
         let abar_store_path = format!("{}/{}abar_store", basedir, &prefix);
         let nullifier_store_path = format!("{}/{}nullifier_store", basedir, &prefix);
+        let state_trees_path = format!("{}/{}state_trees", basedir, &prefix);
 
         // These iterms will be set under ${BNC_DATA_DIR}
         fs::create_dir_all(&basedir).c(d!())?;
@@ -628,6 +838,7 @@ impl LedgerState {
 
         let mut ledger = LedgerState {
             status: LedgerStatus::new(&basedir, &snapshot_file).c(d!())?,
+            basedir: basedir.to_owned(),
             block_merkle: Arc::new(RwLock::new(
                 LedgerState::init_merkle_log(&block_merkle_path).c(d!())?,
             )),
@@ -645,6 +856,9 @@ impl LedgerState {
             nullifier_set: Arc::new(RwLock::new(
                 LedgerState::init_nullifier_smt(&nullifier_store_path).c(d!())?,
             )),
+            state_trees: Arc::new(RwLock::new(
+                StateTrees::open(&state_trees_path).c(d!())?,
+            )),
         };
 
         ledger.status.refresh_data();
@@ -714,6 +928,11 @@ impl LedgerState {
 
         let h = s.cur_height;
 
+        // the rewards of this block are recorded in the history of the validator
+        let rwd_of =
+            |s: &Staking| s.delegation_get(&pk).map(|d| d.rwd_amount).unwrap_or(0);
+        let rwd_before = rwd_of(s);
+
         // Total balance for coinbase ( Staking rewards distribution address )
         let cbl = s.coinbase_balance();
 
@@ -767,6 +986,16 @@ impl LedgerState {
                 .c(d!())?;
         }
 
+        let s = self.get_staking();
+        s.record_validator_block(
+            &pk,
+            h,
+            ValidatorBlockRecord {
+                signed: None,
+                rewards: rwd_of(s).saturating_sub(rwd_before),
+            },
+        );
+
         Ok(())
     }
 
@@ -1047,6 +1276,26 @@ impl LedgerState {
         Ok(res)
     }
 
+    /// Get the traceable TXOs of an asset with tracing policies, along with
+    /// whether they have been spent, the tracing memos can be found in the
+    /// transactions carrying them
+    pub fn get_tracked_txos(
+        &self,
+        code: &AssetTypeCode,
+    ) -> BTreeMap<TxoSID, (UnAuthenticatedUtxo, bool)> {
+        self.status
+            .get_tracked_sids(code)
+            .into_iter()
+            .filter_map(|sid| {
+                self.get_utxo_light(sid)
+                    .map(|au| (sid, (au, false)))
+                    .or_else(|| {
+                        self.get_spent_utxo_light(sid).map(|au| (sid, (au, true)))
+                    })
+            })
+            .collect()
+    }
+
     /// Get all abars with sid which are associated with a diversified public key
     #[allow(dead_code)]
     pub fn get_owned_abar(&self, com: &Commitment) -> Option<ATxoSID> {
@@ -1103,6 +1352,10 @@ impl LedgerState {
     #[allow(missing_docs)]
     pub fn insert_asset_type(&mut self, code: AssetTypeCode, at: AssetType) {
         self.status.asset_types.insert(code, at);
+        pnk!(self.update_state_trees(TouchedState {
+            asset_codes: HashSet::from([code]),
+            ..Default::default()
+        }));
     }
 
     #[inline(always)]
@@ -1123,6 +1376,12 @@ impl LedgerState {
         (commitment, block_count)
     }
 
+    /// The data of the latest state commitment
+    #[inline(always)]
+    pub fn get_state_commitment_data(&self) -> Option<&StateCommitmentData> {
+        self.status.state_commitment_data.as_ref()
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn get_anon_state_commitment(&self) -> (Vec<u8>, u64) {
@@ -1133,6 +1392,21 @@ impl LedgerState {
         (hash, block_count)
     }
 
+    /// The TXO of `sid` if it is unspent, and its proof against the `utxos`
+    /// root of the latest state commitment.
+    ///
+    /// `None` before the state domains are committed.
+    pub fn get_utxo_proof(
+        &self,
+        sid: TxoSID,
+    ) -> Option<(Option<TxOutput>, SparseMerkleProof)> {
+        if !self.state_domains_activated() {
+            return None;
+        }
+        let utxo = self.status.utxos.get(&sid).map(|u| u.0);
+        Some((utxo, self.state_trees.read().prove_utxo(sid)))
+    }
+
     /// Get utxo status and its proof data
     pub fn get_utxo_status(&self, addr: TxoSID) -> AuthenticatedUtxoStatus {
         let state_commitment_data = self.status.state_commitment_data.as_ref().unwrap();
@@ -1185,6 +1459,9 @@ impl LedgerState {
     }
 
     /// Query the transaction by a TxnSID without its proof data to reduce latency
+    ///
+    /// NOTE: `tx_to_block_location` is the on-disk index of the history,
+    /// only the block holding `id` is read from the fbnc store.
     pub fn get_transaction_light(&self, id: TxnSID) -> Result<FinalizedTransaction> {
         self.tx_to_block_location
             .get(&id)
@@ -1242,6 +1519,38 @@ impl LedgerState {
             .state_commitment_versions
             .get((block_height - 1) as usize)
     }
+
+    /// Checksum of the UTXO bitmap after the block `version`(1-based block commit count)
+    #[inline(always)]
+    pub fn get_utxo_checksum(&self, version: u64) -> Option<BitDigest> {
+        version
+            .checked_sub(1)
+            .and_then(|idx| self.blocks.get(idx as usize))
+            .map(|b| b.state.bitmap)
+    }
+
+    /// State commitments of the blocks in `[from, to]`(1-based block commit count),
+    /// together with the data each of them commits to
+    pub fn get_state_commitment_history(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Vec<(
+        u64,
+        HashOf<Option<StateCommitmentData>>,
+        StateCommitmentData,
+    )> {
+        (from.max(1)..=to.min(self.get_block_commit_count()))
+            .map_while(|h| {
+                let idx = (h - 1) as usize;
+                self.status
+                    .state_commitment_versions
+                    .get(idx)
+                    .zip(self.blocks.get(idx))
+                    .map(|(c, b)| (h, c, b.state))
+            })
+            .collect()
+    }
 }
 
 /// The main LedgerStatus of findora ledger
@@ -1249,6 +1558,9 @@ impl LedgerState {
 pub struct LedgerStatus {
     /// the file path of the snapshot
     pub snapshot_file: String,
+    /// schema version of the snapshot, see `LedgerStatus::migrate`
+    #[serde(default = "default_status_version")]
+    version: u64,
     /// all currently-unspent TXOs
     #[serde(default = "default_status_utxos")]
     utxos: Mapxnk<TxoSID, Utxo>,
@@ -1286,6 +1598,9 @@ pub struct LedgerStatus {
     /// Registered asset types
     #[serde(default = "default_status_asset_types")]
     asset_types: Mapx<AssetTypeCode, AssetType>,
+    /// Traceable TXOs of the assets with tracing policies
+    #[serde(default = "default_status_tracked_sids")]
+    tracked_sids: Mapx<AssetTypeCode, HashSet<TxoSID>>,
     /// Issuance number is always increasing
     #[serde(default = "default_status_issuance_num")]
     issuance_num: Mapx<AssetTypeCode, u64>,
@@ -1322,16 +1637,31 @@ pub struct LedgerStatus {
     /// tendermint commit height
     #[serde(default = "default_status_td_commit_height")]
     td_commit_height: u64,
+    /// header time of the last committed tendermint block, in unix seconds
+    #[serde(default = "default_status_td_commit_time")]
+    td_commit_time: i64,
+    /// proposer of the last committed tendermint block, empty if unknown
+    #[serde(default)]
+    td_commit_proposer: TendermintAddr,
+    /// the chain this one restarted from, see `LedgerState::import_genesis`
+    #[serde(default)]
+    genesis_checkpoint: Option<ChainCheckpoint>,
+    /// the TXOs below this SID are in the UTXO tree of the state domains
+    #[serde(default)]
+    utxo_tree_cursor: u64,
 }
 
 impl LedgerStatus {
+    /// Unspent TXOs of an address, in ascending order
     #[inline(always)]
-    #[allow(missing_docs)]
     pub fn get_owned_utxos(&self, addr: &XfrPublicKey) -> Vec<TxoSID> {
-        self.owned_utxos
-            .get(addr)
-            .map(|v| v.iter().cloned().collect())
-            .unwrap_or_default()
+        sorted_sids(self.owned_utxos.get(addr))
+    }
+
+    /// Traceable TXOs of an asset, in ascending order
+    #[inline(always)]
+    pub fn get_tracked_sids(&self, code: &AssetTypeCode) -> Vec<TxoSID> {
+        sorted_sids(self.tracked_sids.get(code))
     }
 
     #[inline(always)]
@@ -1394,8 +1724,10 @@ impl LedgerStatus {
     #[inline(always)]
     pub fn new(basedir: &str, snapshot_file: &str) -> Result<LedgerStatus> {
         let path = format!("{basedir}/{snapshot_file}");
-        match fs::read_to_string(path) {
-            Ok(s) => serde_json::from_str(&s).c(d!()),
+        match fs::read(path) {
+            Ok(s) => Self::decode(&s)
+                .c(d!())
+                .and_then(|mut status| status.migrate().c(d!()).map(|_| status)),
             Err(e) => {
                 if ErrorKind::NotFound != e.kind() {
                     Err(eg!(e))
@@ -1406,9 +1738,73 @@ impl LedgerStatus {
         }
     }
 
+    /// Serialize to the content of `snapshot_file`,
+    /// compressed by zstd at `zstd_level`, or plain JSON with 0.
+    pub fn encode(&self, zstd_level: i32) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(self).c(d!())?;
+        if 0 == zstd_level {
+            return Ok(json);
+        }
+
+        let mut res = STATUS_ZSTD_MAGIC.to_vec();
+        res.extend_from_slice(&STATUS_ZSTD_VERSION.to_be_bytes());
+        res.extend(zstd::encode_all(&json[..], zstd_level).c(d!())?);
+        Ok(res)
+    }
+
+    /// Deserialize from the content of `snapshot_file`, compressed or not
+    pub fn decode(bytes: &[u8]) -> Result<LedgerStatus> {
+        let body = match bytes.strip_prefix(&STATUS_ZSTD_MAGIC[..]) {
+            Some(rest) if rest.len() >= 8 => {
+                let (v, frame) = rest.split_at(8);
+                let v = u64::from_be_bytes(v.try_into().c(d!())?);
+                if v > STATUS_ZSTD_VERSION {
+                    return Err(eg!(format!("unsupported ledger status encoding: {v}")));
+                }
+                zstd::decode_all(frame).c(d!())?
+            }
+            Some(_) => return Err(eg!("truncated ledger status")),
+            None => return serde_json::from_slice(bytes).c(d!()),
+        };
+        serde_json::from_slice(&body).c(d!())
+    }
+
+    /// Upgrade a loaded snapshot to `LEDGER_STATUS_VERSION` step by step.
+    ///
+    /// Fields are decoded by name, so a new field only needs a
+    /// `#[serde(default = ...)]`; a step is added here when existing data
+    /// must be rewritten. A snapshot written by a newer version is refused,
+    /// its data may not mean what this version expects.
+    pub fn migrate(&mut self) -> Result<()> {
+        if self.version > LEDGER_STATUS_VERSION {
+            return Err(eg!(format!(
+                "LedgerStatus version {} is newer than {}, upgrade the node first",
+                self.version, LEDGER_STATUS_VERSION
+            )));
+        }
+
+        while self.version < LEDGER_STATUS_VERSION {
+            match self.version {
+                // the `version` field itself is introduced, no data changes
+                0 => {}
+                v => return Err(eg!(format!("no migration step from version {v}"))),
+            }
+            self.version += 1;
+        }
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
     fn create(snapshot_file: &str) -> Result<LedgerStatus> {
         Ok(LedgerStatus {
             snapshot_file: snapshot_file.to_owned(),
+            version: LEDGER_STATUS_VERSION,
             sliding_set: default_status_sliding_set(),
             utxos: default_status_utxos(),
             nonconfidential_balances: default_status_nonconfidential_balances(),
@@ -1424,6 +1820,7 @@ impl LedgerStatus {
             anon_state_commitment_versions:
                 default_status_anon_state_commitment_versions(),
             asset_types: default_status_asset_types(),
+            tracked_sids: default_status_tracked_sids(),
             issuance_num: default_status_issuance_num(),
             next_txn: default_status_next_txn(),
             next_txo: default_status_next_txo(),
@@ -1434,6 +1831,10 @@ impl LedgerStatus {
             block_commit_count: default_status_block_commit_count(),
             staking: default_status_staking(),
             td_commit_height: default_status_td_commit_height(),
+            td_commit_time: default_status_td_commit_time(),
+            td_commit_proposer: TendermintAddr::default(),
+            genesis_checkpoint: None,
+            utxo_tree_cursor: 0,
         })
     }
 
@@ -1455,34 +1856,56 @@ impl LedgerStatus {
     fn check_txn_effects(
         &self,
         txn_effect: &TxnEffect,
+        features: &FeatureSet,
         abar_state: &Arc<RwLock<State<RocksDB>>>,
-    ) -> Result<()> {
+    ) -> TxnResult<()> {
         // The current transactions seq_id must be within the sliding window over seq_ids
         let (rand, seq_id) = (
             txn_effect.txn.body.no_replay_token.get_rand(),
             txn_effect.txn.body.no_replay_token.get_seq_id(),
         );
         if seq_id > self.block_commit_count {
-            return Err(eg!(("Transaction seq_id ahead of block_count")));
+            return Err(eg!(("Transaction seq_id ahead of block_count")).into());
         } else if seq_id + TRANSACTION_WINDOW_WIDTH < self.block_commit_count {
-            return Err(eg!(("Transaction seq_id too far behind block_count")));
+            return Err(eg!(("Transaction seq_id too far behind block_count")).into());
         } else {
             // Check to see that this nrpt has not been seen before
             if self.sliding_set.has_key_at(seq_id as usize, rand) {
                 return Err(eg!(format!(
                     "No replay token ({rand:?}, {seq_id})seen before at  possible replay",
-                )));
+                )).into());
             }
         }
 
+        if features.is_active(Feature::TxnExpiry)
+            && txn_effect.txn.is_expired(self.block_commit_count)
+        {
+            return Err(eg!(format!(
+                "Transaction expired at {:?}, current block count: {}",
+                txn_effect.txn.body.expiry, self.block_commit_count
+            ))
+            .into());
+        }
+
         // 1. Each input must be unspent and correspond to the claimed record
         // 2. Inputs with transfer restrictions can only be owned by the asset issuer
+        // 3. Inputs must not be time-locked, once the locks are active
+        let time_lock = features.is_active(Feature::TimeLock);
         for (inp_sid, inp_record) in txn_effect.input_txos.iter() {
             // (1)
-            let inp_utxo = self.utxos.get(inp_sid).c(d!("Input must be unspent"))?;
+            let inp_utxo = self
+                .utxos
+                .get(inp_sid)
+                .c(d!("Input must be unspent"))
+                .reject(TxnValidationError::InputSpent)?;
             let record = &(inp_utxo.0);
+            // the lock is not claimed by the spender, it is checked in (3)
+            let inp_record = &TxOutput {
+                lock_until: record.lock_until,
+                ..inp_record.clone()
+            };
             if record != inp_record {
-                return Err(eg!((format!(
+                return Err(TxnValidationError::AmountMismatch.reject(eg!(format!(
                     "Input must correspond to claimed record: {} != {}",
                     serde_json::to_string(&record).c(d!())?,
                     serde_json::to_string(inp_record).unwrap()
@@ -1503,11 +1926,32 @@ impl LedgerStatus {
                 if !asset_type.properties.asset_rules.transferable
                     && asset_type.properties.issuer.deref() != &record.record.public_key
                 {
-                    return Err(eg!(
-                        ("Non-transferable asset type must be owned by asset issuer")
-                    ));
+                    return Err(TxnValidationError::PolicyViolation.reject(eg!(
+                        "Non-transferable asset type must be owned by asset issuer"
+                    )));
                 }
             }
+            // (3)
+            if time_lock && record.is_locked(self.block_commit_count) {
+                return Err(eg!(format!(
+                    "Input {} is locked until {}, current block count: {}",
+                    inp_sid.0, record.lock_until, self.block_commit_count
+                ))
+                .into());
+            }
+        }
+
+        // Internally spent inputs must not be time-locked
+        if let Some(record) = txn_effect
+            .internally_spent_txos
+            .iter()
+            .find(|r| time_lock && r.is_locked(self.block_commit_count))
+        {
+            return Err(eg!(format!(
+                "Input is locked until {}, current block count: {}",
+                record.lock_until, self.block_commit_count
+            ))
+            .into());
         }
 
         // Internally spend inputs with transfer restrictions can only be owned by the asset issuer
@@ -1526,9 +1970,9 @@ impl LedgerStatus {
                 if !asset_type.properties.asset_rules.transferable
                     && asset_type.properties.issuer.deref() != &record.record.public_key
                 {
-                    return Err(eg!(
-                        ("Non-transferable asset type must be owned by asset issuer")
-                    ));
+                    return Err(TxnValidationError::PolicyViolation.reject(eg!(
+                        "Non-transferable asset type must be owned by asset issuer"
+                    )));
                 }
             }
         }
@@ -1536,13 +1980,16 @@ impl LedgerStatus {
         // New asset types must not already exist
         for (code, _asset_type) in txn_effect.new_asset_codes.iter() {
             if self.asset_types.contains_key(&code) {
-                return Err(eg!(format!("Asset type {:?} already defined", &code)));
+                return Err(
+                    eg!(format!("Asset type {:?} already defined", &code)).into()
+                );
             }
             if self.issuance_num.contains_key(&code) {
                 return Err(eg!(format!(
                     "Asset type {:?} is being defined after issue",
                     &code
-                )));
+                ))
+                .into());
             }
 
             // Asset issuance should match the currently registered key
@@ -1582,14 +2029,14 @@ impl LedgerStatus {
                 .c(d!())?;
             let proper_key = asset_type.properties.issuer;
             if *iss_key != proper_key {
-                return Err(eg!(
-                    ("Issuance key is not the same as key of properties issuer")
-                ));
+                return Err(TxnValidationError::SignatureInvalid.reject(eg!(
+                    "Issuance key is not the same as key of properties issuer"
+                )));
             }
 
             if seq_nums.is_empty() {
                 if !txn_effect.new_asset_codes.contains_key(&code) {
-                    return Err(eg!(("Code is not contained in new asset codes")));
+                    return Err(eg!(("Code is not contained in new asset codes")).into());
                 }
             // We could re-check that self.issuance_num doesn't contain `code`,
             // but currently it's redundant with the new-asset-type checks
@@ -1597,7 +2044,8 @@ impl LedgerStatus {
                 let curr_seq_num_limit = self.issuance_num.get(&code).unwrap_or(0);
                 let min_seq_num = seq_nums.first().c(d!())?;
                 if *min_seq_num < curr_seq_num_limit {
-                    return Err(eg!(("Minimum seq num is less than limit")));
+                    return Err(TxnValidationError::IssuanceReplay
+                        .reject(eg!("Minimum seq num is less than limit")));
                 }
             }
         }
@@ -1615,7 +2063,8 @@ impl LedgerStatus {
             if let Some(cap) = asset_type.properties.asset_rules.max_units {
                 let current_amount = self.issuance_amounts.get(code).unwrap_or(0);
                 if current_amount.checked_add(*amount).c(d!())? > cap {
-                    return Err(eg!(("Amount exceeds asset cap")));
+                    return Err(TxnValidationError::PolicyViolation
+                        .reject(eg!("Amount exceeds asset cap")));
                 }
             }
         }
@@ -1628,7 +2077,8 @@ impl LedgerStatus {
                 .or_else(|| txn_effect.new_asset_codes.get(&code).cloned())
                 .c(d!())?;
             if asset_type.has_issuance_restrictions() {
-                return Err(eg!(("This asset type has issuance restrictions")));
+                return Err(TxnValidationError::PolicyViolation
+                    .reject(eg!("This asset type has issuance restrictions")));
             }
         }
 
@@ -1640,10 +2090,20 @@ impl LedgerStatus {
             if !asset.properties.asset_rules.updatable
                 || asset.properties.issuer != (IssuerPublicKey { key: memo_update.1 })
             {
-                return Err(eg!(("Non updatable asset or issuer mismatch")));
+                return Err(eg!(("Non updatable asset or issuer mismatch")).into());
             }
         }
 
+        // Transfer policies, once they are active
+        if features.is_active(Feature::TransferPolicy) {
+            self.check_transfer_policies(txn_effect)?;
+        }
+
+        // Asset tracing, once it is active
+        if features.is_active(Feature::AssetTracing) {
+            self.check_tracing_policies(txn_effect)?;
+        }
+
         // Until we can distinguish assets that have policies that invoke transfer restrictions
         // from those that don't, prevent any non-confidential assets with transfer restrictions
         // from becoming confidential
@@ -1654,9 +2114,9 @@ impl LedgerStatus {
                 .or_else(|| txn_effect.new_asset_codes.get(&code).cloned())
                 .c(d!())?;
             if asset_type.has_transfer_restrictions() {
-                return Err(eg!(
-                    ("non-confidential assets with transfer restrictions can't become confidential")
-                ));
+                return Err(TxnValidationError::PolicyViolation.reject(eg!(
+                    "non-confidential assets with transfer restrictions can't become confidential"
+                )));
             }
         }
 
@@ -1673,7 +2133,8 @@ impl LedgerStatus {
         for axfr_note in txn_effect.axfr_bodies.iter() {
             for input in &axfr_note.body.inputs {
                 if self.spent_abars.get(&input).is_some() {
-                    return Err(eg!("Input abar must be unspent"));
+                    return Err(TxnValidationError::InputSpent
+                        .reject(eg!("Input abar must be unspent")));
                 }
             }
 
@@ -1689,7 +2150,7 @@ impl LedgerStatus {
             .c(d!())?;
             let abar_version = axfr_note.body.merkle_root_version;
             if abar_mt.version() - abar_version > VERSION_WINDOW {
-                return Err(eg!("Proof is old, need rebuild!"));
+                return Err(eg!("Proof is old, need rebuild!").into());
             }
             let version_root = abar_mt
                 .get_root_with_depth_and_version(MERKLE_TREE_DEPTH, abar_version)
@@ -1707,13 +2168,14 @@ impl LedgerStatus {
         // An axfr_abar_conv requires versioned merkle root hash for verification.
         for abar_conv in &txn_effect.abar_conv_inputs {
             if self.spent_abars.get(&abar_conv.get_input()).is_some() {
-                return Err(eg!("Input abar must be unspent"));
+                return Err(TxnValidationError::InputSpent
+                    .reject(eg!("Input abar must be unspent")));
             }
 
             // Get verifier params
             let abar_version = abar_conv.get_merkle_root_version();
             if abar_mt.version() - abar_version > VERSION_WINDOW {
-                return Err(eg!("Proof is old, need rebuild!"));
+                return Err(eg!("Proof is old, need rebuild!").into());
             }
             let version_root = abar_mt
                 .get_root_with_depth_and_version(MERKLE_TREE_DEPTH, abar_version)
@@ -1726,15 +2188,124 @@ impl LedgerStatus {
         Ok(())
     }
 
+    // Senders, receivers and updates must be allowed by the transfer policies
+    fn check_transfer_policies(&self, txn_effect: &TxnEffect) -> TxnResult<()> {
+        // Transfer policy updates
+        // Only the issuer can update, and only assets defined with a policy have one
+        for (code, key, _) in txn_effect.policy_updates.iter() {
+            let asset = self.asset_types.get(code).c(d!())?;
+            if asset.properties.asset_rules.transfer_policy.is_none()
+                || asset.properties.issuer != (IssuerPublicKey { key: *key })
+            {
+                return Err(eg!(("No transfer policy or issuer mismatch")).into());
+            }
+        }
+
+        // Senders and receivers must be allowed by the transfer policies,
+        // and the assets with policies can not become anonymous
+        let get_policy = |code: &AssetTypeCode| {
+            self.asset_types
+                .get(code)
+                .or_else(|| txn_effect.new_asset_codes.get(code).cloned())
+                .and_then(|a| {
+                    let issuer = a.properties.issuer;
+                    a.properties
+                        .asset_rules
+                        .transfer_policy
+                        .map(|p| (p, issuer))
+                })
+        };
+        for (code, key) in txn_effect.transfer_parties.iter() {
+            if let Some((policy, issuer)) = get_policy(code) {
+                if !policy.allows(key, &issuer) {
+                    return Err(TxnValidationError::PolicyViolation.reject(eg!(
+                        format!(
+                            "{} is not allowed by the transfer policy of {}",
+                            wallet::public_key_to_base64(key),
+                            code.to_base64()
+                        )
+                    )));
+                }
+            }
+        }
+        for code in txn_effect.bar_conv_types.iter() {
+            if get_policy(code).is_some() {
+                return Err(TxnValidationError::PolicyViolation.reject(eg!(
+                    "assets with transfer policies can't become anonymous"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Transfers must carry exactly the tracing policies of the assets,
+    // so the tracing proofs verified in the TxnEffect can't be skipped.
+    //
+    // The policies can only be matched on non-confidential asset types, so the
+    // records of a traced asset must never get a confidential type: issuances
+    // are non-confidential, the confidential transfers of assets with transfer
+    // restrictions, tracing policies included, are refused in
+    // `check_txn_effects`, and the conversions to anonymous records are refused
+    // here.
+    fn check_tracing_policies(&self, txn_effect: &TxnEffect) -> TxnResult<()> {
+        let get_asset = |code: &AssetTypeCode| {
+            self.asset_types
+                .get(code)
+                .or_else(|| txn_effect.new_asset_codes.get(code).cloned())
+        };
+
+        for (code, policies) in txn_effect.tracing_policies.iter() {
+            let asset_type = get_asset(code).c(d!())?;
+            if asset_type.get_tracing_policies_ref() != policies {
+                return Err(TxnValidationError::PolicyViolation.reject(eg!(format!(
+                    "tracing policies mismatch for {}",
+                    code.to_base64()
+                ))));
+            }
+        }
+        for code in txn_effect.bar_conv_types.iter() {
+            if get_asset(code)
+                .map(|a| !a.get_tracing_policies_ref().is_empty())
+                .unwrap_or(false)
+            {
+                return Err(TxnValidationError::PolicyViolation.reject(eg!(
+                    "assets with tracing policies can't become anonymous"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     // This function assumes that `block` is COMPLETELY CONSISTENT with the
     // ledger state. Calling `check_txn_effects` for each TxnEffect getting
     // mixed into the BlockEffect *should* be enough to guarantee that (if
     // that is ever false, it's a bug).
     //
     // This drains every field of `block` except `txns` and `temp_sids`.
+    //
+    // Returns the new sids, the range of the new TXOs,
+    // and the state changed by the block.
     #[allow(unused_mut)]
     #[allow(clippy::unwrap_or_default)]
-    fn apply_block_effects(&mut self, block: &mut BlockEffect) -> (TmpSidMap, u64, u64) {
+    fn apply_block_effects(
+        &mut self,
+        block: &mut BlockEffect,
+    ) -> Result<(TmpSidMap, u64, u64, TouchedState)> {
+        // checked by `check_txn_effects`, but fail before changing anything
+        if let Some(code) = block
+            .policy_updates
+            .keys()
+            .find(|code| self.asset_types.get(*code).is_none())
+        {
+            return Err(eg!(format!(
+                "transfer policy update of an unknown asset: {}",
+                code.to_base64()
+            )));
+        }
+
+        let mut touched = TouchedState::default();
         let base_sid = self.next_txo.0;
         let handle_asset_type_code = |code: AssetTypeCode| {
             AssetTypeCode::from_prefix_and_raw_asset_type_code(
@@ -1770,12 +2341,21 @@ impl LedgerStatus {
                 }
                 self.spent_utxos.insert(inp_sid, v);
             }
+            touched.txos.push(inp_sid);
         }
 
         // Apply memo updates
         for (code, memo) in block.memo_updates.drain() {
             let mut asset = self.asset_types.get_mut(&code).unwrap();
             asset.properties.memo = memo;
+            touched.asset_codes.insert(code);
+        }
+
+        // Apply transfer policy updates
+        for (code, policy) in block.policy_updates.drain() {
+            let mut asset = self.asset_types.get_mut(&code).c(d!())?;
+            asset.properties.asset_rules.transfer_policy = Some(policy);
+            touched.asset_codes.insert(code);
         }
 
         for (code, amount) in block.issuance_amounts.drain() {
@@ -1789,6 +2369,7 @@ impl LedgerStatus {
         // a TxoSID. TxoSID assignments are based on the order TXOs appear in
         // the transaction.
         let mut new_utxo_sids: HashMap<TxnTempSID, (TxnSID, Vec<TxoSID>)> = map! {};
+        let mut typed_sids: Vec<(AssetTypeCode, TxoSID)> = vec![];
         {
             let mut next_txn = self.next_txn.0;
             let mut next_txo = self.next_txo.0;
@@ -1803,6 +2384,11 @@ impl LedgerStatus {
                     let txo_sid = next_txo;
                     next_txo += 1;
                     if let Some(tx_output) = txo {
+                        if let Some(code) = tx_output.record.asset_type.get_asset_type()
+                        {
+                            typed_sids
+                                .push((AssetTypeCode { val: code }, TxoSID(txo_sid)));
+                        }
                         self.owned_utxos
                             .entry(tx_output.record.public_key)
                             .or_insert_with(HashSet::new)
@@ -1816,6 +2402,7 @@ impl LedgerStatus {
                             e.saturating_add(utxo.get_nonconfidential_balance());
                         self.utxos.insert(TxoSID(txo_sid), utxo);
                         txn_utxo_sids.push(TxoSID(txo_sid));
+                        touched.txos.push(TxoSID(txo_sid));
                     }
                 }
 
@@ -1831,19 +2418,36 @@ impl LedgerStatus {
             // One more than the greatest sequence number, or 0
             let new_max_seq_num = seq_nums.last().map(|x| x + 1).unwrap_or(0);
             self.issuance_num.insert(code, new_max_seq_num);
+            touched.asset_codes.insert(code);
         }
 
         // Register new asset types
         for (code, asset_type) in block.new_asset_codes.drain() {
             let code = handle_asset_type_code(code);
             self.asset_types.insert(code, asset_type.clone());
+            touched.asset_codes.insert(code);
+        }
+
+        // Index the outputs of the traced assets
+        for (code, sid) in typed_sids {
+            let traced = self
+                .asset_types
+                .get(&code)
+                .map(|a| !a.get_tracing_policies_ref().is_empty())
+                .unwrap_or(false);
+            if traced {
+                self.tracked_sids
+                    .entry(code)
+                    .or_insert_with(HashSet::new)
+                    .insert(sid);
+            }
         }
 
         // issuance_keys should already have been checked
         block.issuance_keys.clear();
 
         let max_sid = self.next_txo.0;
-        (new_utxo_sids, base_sid, max_sid)
+        Ok((new_utxo_sids, base_sid, max_sid, touched))
     }
 
     /// Check if an txo_sid is unspent.
@@ -1910,6 +2514,14 @@ fn default_status_utxos() -> Mapxnk<TxoSID, Utxo> {
     new_mapxnk!(SNAPSHOT_ENTRIES_DIR.to_owned() + "/utxo")
 }
 
+// The sets of sids are kept unordered in the status,
+// the order of the responses built from them must not depend on it.
+fn sorted_sids(sids: Option<HashSet<TxoSID>>) -> Vec<TxoSID> {
+    let mut sids = sids.unwrap_or_default().into_iter().collect::<Vec<_>>();
+    sids.sort_unstable();
+    sids
+}
+
 fn default_status_owned_utxos() -> Mapx<XfrPublicKey, HashSet<TxoSID>> {
     new_mapx!(SNAPSHOT_ENTRIES_DIR.to_owned() + "/owned_utxos")
 }
@@ -1960,6 +2572,10 @@ fn default_status_asset_types() -> Mapx<AssetTypeCode, AssetType> {
     new_mapx!(SNAPSHOT_ENTRIES_DIR.to_owned() + "/asset_types")
 }
 
+fn default_status_tracked_sids() -> Mapx<AssetTypeCode, HashSet<TxoSID>> {
+    new_mapx!(SNAPSHOT_ENTRIES_DIR.to_owned() + "/tracked_sids")
+}
+
 fn default_status_issuance_num() -> Mapx<AssetTypeCode, u64> {
     new_mapx!(SNAPSHOT_ENTRIES_DIR.to_owned() + "/issuance_num")
 }
@@ -2000,6 +2616,15 @@ fn default_status_td_commit_height() -> u64 {
     0
 }
 
+fn default_status_td_commit_time() -> i64 {
+    0
+}
+
+// snapshots written before the `version` field existed
+fn default_status_version() -> u64 {
+    0
+}
+
 fn default_status_sliding_set() -> SlidingSet<[u8; 8]> {
     SlidingSet::<[u8; 8]>::new(TRANSACTION_WINDOW_WIDTH as usize)
 }
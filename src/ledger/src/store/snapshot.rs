@@ -0,0 +1,510 @@
+//!
+//! # Ledger snapshot
+//!
+//! Packs the whole ledger directory into a single versioned file,
+//! so that a new node can bootstrap from it instead of replaying the history.
+//!
+//! Layout: `MAGIC | header length(u64, big endian) | header(json) | contents`,
+//! contents of all entries are concatenated in the order of `SnapshotHeader::entries`,
+//! in a single zstd stream if `SnapshotHeader::zstd` is set.
+//!
+//! A ledger in use is exported in two steps: a `SnapshotCheckpoint` is
+//! taken between two blocks, then packed while the ledger goes on.
+//!
+
+use {
+    super::{flush_data, LedgerState},
+    crate::data_model::StateCommitmentData,
+    globutils::HashOf,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    std::{
+        fs::{self, File},
+        io::{self, BufReader, BufWriter, Read, Write},
+        path::{Component, Path, PathBuf},
+    },
+};
+
+/// Current version of the snapshot format,
+/// version 2 adds the optional compression of the contents
+pub const SNAPSHOT_FORMAT_VERSION: u64 = 2;
+
+const MAGIC: &[u8; 8] = b"FRALSNAP";
+
+/// Bound of the header length read from a snapshot file
+const MAX_HEADER_LEN: u64 = 16 * 1024 * 1024;
+
+/// A file in the snapshot
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SnapshotEntry {
+    /// path relative to the ledger directory, separated by '/'
+    pub path: String,
+    pub size: u64,
+}
+
+/// Metadata of a snapshot
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SnapshotHeader {
+    pub version: u64,
+    pub block_commit_count: u64,
+    pub tendermint_height: u64,
+    pub state_commitment: Option<HashOf<Option<StateCommitmentData>>>,
+    pub entries: Vec<SnapshotEntry>,
+    /// hex encoded sha256 over the path, size and content of every entry
+    pub digest: String,
+    /// the contents are compressed, sizes and digest are of the original ones
+    #[serde(default)]
+    pub zstd: bool,
+}
+
+/// A point-in-time copy of the ledger directory,
+/// see `LedgerState::checkpoint_snapshot`
+pub struct SnapshotCheckpoint {
+    dir: PathBuf,
+    status_file: String,
+    block_commit_count: u64,
+    tendermint_height: u64,
+    state_commitment: Option<HashOf<Option<StateCommitmentData>>>,
+}
+
+impl SnapshotCheckpoint {
+    #[allow(missing_docs)]
+    pub fn block_commit_count(&self) -> u64 {
+        self.block_commit_count
+    }
+
+    #[allow(missing_docs)]
+    pub fn tendermint_height(&self) -> u64 {
+        self.tendermint_height
+    }
+
+    /// Pack the checkpoint into a snapshot file at `path`,
+    /// compressed by zstd at `zstd_level`, or not with 0.
+    ///
+    /// The contents are hashed again while they are written,
+    /// the export fails if they differ from the digest of the header.
+    pub fn export(&self, path: &str, zstd_level: i32) -> Result<SnapshotHeader> {
+        let mut files = vec![];
+        collect_files(&self.dir, &|_| false, &mut files).c(d!())?;
+        files.sort();
+
+        // the status first, as in the previous versions
+        let mut sources =
+            vec![(self.status_file.clone(), self.dir.join(&self.status_file))];
+        for f in files {
+            let rel = relative_path(&self.dir, &f).c(d!())?;
+            if rel != self.status_file {
+                sources.push((rel, f));
+            }
+        }
+
+        let mut hasher = Sha256::new();
+        let mut entries = Vec::with_capacity(sources.len());
+        for (rel, src) in sources.iter() {
+            let size = hash_entry(&mut hasher, rel, src).c(d!())?;
+            entries.push(SnapshotEntry {
+                path: rel.clone(),
+                size,
+            });
+        }
+
+        let header = SnapshotHeader {
+            version: SNAPSHOT_FORMAT_VERSION,
+            block_commit_count: self.block_commit_count,
+            tendermint_height: self.tendermint_height,
+            state_commitment: self.state_commitment.clone(),
+            entries,
+            digest: hex::encode(hasher.finalize()),
+            zstd: 0 != zstd_level,
+        };
+
+        let tmp_path = format!("{path}.tmp");
+        let res = write_snapshot(&tmp_path, &header, &sources, zstd_level)
+            .c(d!())
+            .and_then(|digest| {
+                if digest == header.digest {
+                    fs::rename(&tmp_path, path).c(d!())
+                } else {
+                    Err(eg!("the checkpoint changed during the export"))
+                }
+            });
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+
+        res.map(|_| header)
+    }
+}
+
+impl Drop for SnapshotCheckpoint {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl LedgerState {
+    /// Export the ledger directory to a snapshot file at `path`,
+    /// compressed by zstd at `zstd_level`, or not with 0.
+    ///
+    /// The ledger must not be changed during the export,
+    /// use `checkpoint_snapshot` to export a ledger in use.
+    /// NOTE: `BNC_DATA_DIR` should be located in the ledger directory,
+    /// which is the case of `abcid`.
+    pub fn export_snapshot(
+        &self,
+        path: &str,
+        zstd_level: i32,
+    ) -> Result<SnapshotHeader> {
+        let staging = format!("{path}.staging");
+        self.checkpoint(&staging, Some(Path::new(path)))
+            .c(d!())?
+            .export(path, zstd_level)
+            .c(d!())
+    }
+
+    /// Take a point-in-time copy of the ledger directory in `dir`,
+    /// to be exported later by `SnapshotCheckpoint::export` while the
+    /// ledger goes on.
+    ///
+    /// The ledger must not be changed during the checkpoint, it is expected
+    /// to be called between two blocks. The RocksDB tables are immutable,
+    /// so they are hard linked, only the other files are copied.
+    pub fn checkpoint_snapshot(&self, dir: &str) -> Result<SnapshotCheckpoint> {
+        self.checkpoint(dir, None).c(d!())
+    }
+
+    // `out` is skipped if it is located in the ledger directory
+    fn checkpoint(&self, dir: &str, out: Option<&Path>) -> Result<SnapshotCheckpoint> {
+        flush_data();
+
+        let dir = PathBuf::from(dir);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).c(d!())?;
+        }
+        fs::create_dir_all(&dir).c(d!())?;
+
+        // removed on drop, if anything goes wrong below
+        let cp = SnapshotCheckpoint {
+            dir,
+            status_file: self.status.snapshot_file.clone(),
+            block_commit_count: self.get_block_commit_count(),
+            tendermint_height: self.get_tendermint_height(),
+            state_commitment: self.status.state_commitment_versions.last(),
+        };
+
+        let basedir = Path::new(&self.basedir);
+        let staging = fs::canonicalize(&cp.dir).c(d!())?;
+        let out = out.and_then(|p| fs::canonicalize(p).ok());
+        let skip = |p: &Path| {
+            fs::canonicalize(p).map_or(false, |p| {
+                p.starts_with(&staging) || Some(&p) == out.as_ref()
+            })
+        };
+
+        let status = serde_json::to_vec(&self.status).c(d!())?;
+        fs::write(cp.dir.join(&cp.status_file), status).c(d!())?;
+
+        // the mutable files first, then the tables they refer to
+        let mut files = vec![];
+        collect_files(basedir, &skip, &mut files).c(d!())?;
+        let mut tables = vec![];
+        for f in files {
+            let rel = relative_path(basedir, &f).c(d!())?;
+            if rel == cp.status_file {
+                continue;
+            }
+            let dst = cp.dir.join(&rel);
+            if let Some(parent) = dst.parent() {
+                fs::create_dir_all(parent).c(d!())?;
+            }
+            if is_immutable(&f) {
+                tables.push(rel);
+            } else {
+                fs::copy(&f, &dst).c(d!(rel))?;
+            }
+        }
+
+        // a table dropped in the meantime, e.g. by a compaction, may still
+        // be referred to by the copied manifests
+        for rel in tables {
+            let src = basedir.join(&rel);
+            let dst = cp.dir.join(&rel);
+            match fs::hard_link(&src, &dst) {
+                Ok(_) => {}
+                Err(e) if io::ErrorKind::NotFound == e.kind() => {
+                    return Err(eg!(format!("{rel} removed during the checkpoint")));
+                }
+                Err(_) => {
+                    fs::copy(&src, &dst).c(d!(rel))?;
+                }
+            }
+        }
+
+        Ok(cp)
+    }
+
+    /// Restore a snapshot into `basedir`, which must be empty or not exist.
+    ///
+    /// Nothing is written to `basedir` unless the integrity check passes,
+    /// the ledger can be opened with `LedgerState::load_or_init` afterwards.
+    pub fn import_snapshot(path: &str, basedir: &str) -> Result<SnapshotHeader> {
+        let target = Path::new(basedir);
+        if target.exists() && fs::read_dir(target).c(d!())?.next().is_some() {
+            return Err(eg!(format!("{basedir} is not empty")));
+        }
+
+        let mut r = BufReader::new(File::open(path).c(d!())?);
+        let header = read_header(&mut r).c(d!())?;
+
+        let tmp_dir =
+            PathBuf::from(format!("{}.importing", basedir.trim_end_matches('/')));
+        if tmp_dir.exists() {
+            fs::remove_dir_all(&tmp_dir).c(d!())?;
+        }
+
+        let extracted = if header.zstd {
+            zstd::Decoder::with_buffer(r)
+                .c(d!())
+                .and_then(|mut z| extract(&mut z, &header, &tmp_dir).c(d!()))
+        } else {
+            extract(&mut r, &header, &tmp_dir).c(d!())
+        };
+        let res = extracted.and_then(|_| {
+            if target.exists() {
+                fs::remove_dir(target).c(d!())?;
+            }
+            fs::rename(&tmp_dir, target).c(d!())
+        });
+        if res.is_err() {
+            let _ = fs::remove_dir_all(&tmp_dir);
+        }
+
+        res.map(|_| header)
+    }
+
+    /// Read the header of a snapshot without extracting it
+    pub fn read_snapshot_header(path: &str) -> Result<SnapshotHeader> {
+        read_header(&mut BufReader::new(File::open(path).c(d!())?)).c(d!())
+    }
+
+    /// Check the ledger loaded from an imported snapshot against its header.
+    ///
+    /// The digest of the header only proves the files were not altered
+    /// after the export, the state commitment is computed again from
+    /// the utxo map, the merkle trees and the trees of the state domains here.
+    pub fn verify_snapshot(&self, header: &SnapshotHeader) -> Result<()> {
+        if self.get_block_commit_count() != header.block_commit_count
+            || self.get_tendermint_height() != header.tendermint_height
+        {
+            return Err(eg!("the ledger is not at the height of the snapshot"));
+        }
+        if self.status.state_commitment_versions.last() != header.state_commitment {
+            return Err(eg!("state commitment mismatch"));
+        }
+
+        let data = if let Some(data) = self.get_state_commitment_data() {
+            data
+        } else if header.state_commitment.is_none() {
+            return Ok(());
+        } else {
+            return Err(eg!("no state commitment data"));
+        };
+        if Some(data.compute_commitment()) != header.state_commitment
+            || data.bitmap != self.utxo_map.write().compute_checksum()
+            || data.block_merkle != self.block_merkle.read().get_root_hash()
+            || data.transaction_merkle_commitment
+                != self.txn_merkle.read().get_root_hash()
+            || data.txo_count != self.get_next_txo().0
+            || data.domains.as_ref().map_or(false, |d| {
+                *d != self.state_trees.read().domains(self.get_staking())
+            })
+        {
+            return Err(eg!("the state does not match its commitment"));
+        }
+
+        Ok(())
+    }
+}
+
+fn read_header<R: Read>(r: &mut R) -> Result<SnapshotHeader> {
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic).c(d!())?;
+    if &magic != MAGIC {
+        return Err(eg!("not a ledger snapshot"));
+    }
+
+    let mut len = [0u8; 8];
+    r.read_exact(&mut len).c(d!())?;
+    let len = u64::from_be_bytes(len);
+    if len > MAX_HEADER_LEN {
+        return Err(eg!(format!("snapshot header too large: {len} bytes")));
+    }
+    let mut h = vec![0u8; len as usize];
+    r.read_exact(&mut h).c(d!())?;
+    let header = serde_json::from_slice::<SnapshotHeader>(&h).c(d!())?;
+
+    if header.version > SNAPSHOT_FORMAT_VERSION {
+        return Err(eg!(format!(
+            "unsupported snapshot version: {}",
+            header.version
+        )));
+    }
+
+    Ok(header)
+}
+
+// returns the digest of the contents written
+fn write_snapshot(
+    path: &str,
+    header: &SnapshotHeader,
+    sources: &[(String, PathBuf)],
+    zstd_level: i32,
+) -> Result<String> {
+    let mut w = BufWriter::new(File::create(path).c(d!())?);
+    let h = serde_json::to_vec(header).c(d!())?;
+    w.write_all(MAGIC).c(d!())?;
+    w.write_all(&(h.len() as u64).to_be_bytes()).c(d!())?;
+    w.write_all(&h).c(d!())?;
+    let digest = if header.zstd {
+        let mut z = zstd::Encoder::new(w, zstd_level).c(d!())?;
+        let digest = write_contents(&mut z, header, sources).c(d!())?;
+        w = z.finish().c(d!())?;
+        digest
+    } else {
+        write_contents(&mut w, header, sources).c(d!())?
+    };
+    w.flush().c(d!())?;
+    w.get_ref().sync_all().c(d!())?;
+    Ok(digest)
+}
+
+fn write_contents<W: Write>(
+    w: &mut W,
+    header: &SnapshotHeader,
+    sources: &[(String, PathBuf)],
+) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    for (e, (_, src)) in header.entries.iter().zip(sources.iter()) {
+        hasher.update(e.path.as_bytes());
+        hasher.update(e.size.to_be_bytes());
+        let mut f = File::open(src).c(d!())?;
+        let mut n = 0;
+        loop {
+            let i = f.read(&mut buf).c(d!())?;
+            if 0 == i {
+                break;
+            }
+            hasher.update(&buf[..i]);
+            w.write_all(&buf[..i]).c(d!())?;
+            n += i as u64;
+        }
+        if n != e.size {
+            return Err(eg!(format!("{} changed during export", e.path)));
+        }
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn extract<R: Read>(r: &mut R, header: &SnapshotHeader, dir: &Path) -> Result<()> {
+    let mut hasher = Sha256::new();
+    for e in header.entries.iter() {
+        let dst = safe_join(dir, &e.path).c(d!())?;
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent).c(d!())?;
+        }
+
+        hasher.update(e.path.as_bytes());
+        hasher.update(e.size.to_be_bytes());
+
+        let mut w = BufWriter::new(File::create(&dst).c(d!())?);
+        let mut src = r.by_ref().take(e.size);
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut n = 0;
+        loop {
+            let i = src.read(&mut buf).c(d!())?;
+            if 0 == i {
+                break;
+            }
+            hasher.update(&buf[..i]);
+            w.write_all(&buf[..i]).c(d!())?;
+            n += i as u64;
+        }
+        w.flush().c(d!())?;
+
+        if n != e.size {
+            return Err(eg!(format!("{} is truncated", e.path)));
+        }
+    }
+
+    if hex::encode(hasher.finalize()) != header.digest {
+        return Err(eg!("snapshot digest mismatch"));
+    }
+
+    Ok(())
+}
+
+fn collect_files(
+    dir: &Path,
+    skip: &dyn Fn(&Path) -> bool,
+    res: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir).c(d!())? {
+        let p = entry.c(d!())?.path();
+        if skip(&p) {
+            continue;
+        }
+        if p.is_dir() {
+            collect_files(&p, skip, res).c(d!())?;
+        } else if p.is_file() {
+            res.push(p);
+        }
+    }
+    Ok(())
+}
+
+// the tables of RocksDB are never changed once written
+#[inline(always)]
+fn is_immutable(p: &Path) -> bool {
+    p.extension().map_or(false, |ext| "sst" == ext)
+}
+
+fn relative_path(base: &Path, p: &Path) -> Result<String> {
+    p.strip_prefix(base)
+        .c(d!())?
+        .components()
+        .map(|c| c.as_os_str().to_str().c(d!("non-utf8 path")))
+        .collect::<Result<Vec<_>>>()
+        .map(|v| v.join("/"))
+}
+
+// reject absolute paths and `..`, all entries must stay in `dir`
+fn safe_join(dir: &Path, rel: &str) -> Result<PathBuf> {
+    let rel = Path::new(rel);
+    if rel.components().all(|c| matches!(c, Component::Normal(_))) {
+        Ok(dir.join(rel))
+    } else {
+        Err(eg!(format!("invalid entry path: {}", rel.display())))
+    }
+}
+
+fn hash_entry(hasher: &mut Sha256, rel: &str, src: &Path) -> Result<u64> {
+    let size = fs::metadata(src).c(d!())?.len();
+    hasher.update(rel.as_bytes());
+    hasher.update(size.to_be_bytes());
+
+    let mut f = BufReader::new(File::open(src).c(d!())?);
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let i = f.read(&mut buf).c(d!())?;
+        if 0 == i {
+            break;
+        }
+        hasher.update(&buf[..i]);
+    }
+
+    Ok(size)
+}
@@ -0,0 +1,113 @@
+//!
+//! # State Domains
+//!
+//! The asset types, the issuance numbers and the unspent TXOs are kept in
+//! sparse merkle trees, their roots are committed in
+//! `StateCommitmentData::domains`, along with the hash of the staking state.
+//!
+//! The UTXO tree is filled a few TXOs per block ahead of
+//! `CFG.checkpoint.state_domains_height`, the other trees at once in the
+//! first block after it. They are updated with the state touched by each
+//! block afterwards, the changes are written by `StateTrees::commit`.
+//!
+
+use {
+    super::utxo_tree::{leaf_hash, SparseMerkleProof, UtxoTree},
+    crate::{
+        data_model::{
+            AssetType, AssetTypeCode, StateDomainHashes, TxOutput, TxoSID,
+            STATE_DOMAINS_VERSION,
+        },
+        staking::Staking,
+    },
+    fin_db::RocksDB,
+    globutils::HashOf,
+    ruc::*,
+    sparse_merkle_tree::{Key, SmtMap256, ZERO_DIGEST},
+    std::collections::HashSet,
+};
+
+/// The parts of the state changed by a block
+#[derive(Default)]
+pub struct TouchedState {
+    /// with a new definition or issuance number
+    pub asset_codes: HashSet<AssetTypeCode>,
+    /// the spent and the new TXOs
+    pub txos: Vec<TxoSID>,
+}
+
+/// The sparse merkle trees of the state domains
+pub struct StateTrees {
+    asset_types: SmtMap256<RocksDB>,
+    issuance_nums: SmtMap256<RocksDB>,
+    utxos: UtxoTree,
+}
+
+impl StateTrees {
+    /// Open the trees under `path`, an empty one is created if missing
+    pub fn open(path: &str) -> Result<Self> {
+        let open = |name: &str| -> Result<SmtMap256<RocksDB>> {
+            let path = format!("{path}_{name}");
+            RocksDB::open(&path)
+                .c(d!("failed to open db"))
+                .map(SmtMap256::new)
+        };
+        Ok(StateTrees {
+            asset_types: open("asset_types").c(d!())?,
+            issuance_nums: open("issuance_nums").c(d!())?,
+            utxos: UtxoTree::new(&format!("{path}_utxos")),
+        })
+    }
+
+    #[allow(missing_docs)]
+    pub fn set_asset_type(
+        &mut self,
+        code: &AssetTypeCode,
+        at: &AssetType,
+    ) -> Result<()> {
+        let v = bincode::serialize(at).c(d!())?;
+        self.asset_types.set(&key(code).c(d!())?, Some(v)).c(d!())
+    }
+
+    #[allow(missing_docs)]
+    pub fn set_issuance_num(&mut self, code: &AssetTypeCode, num: u64) -> Result<()> {
+        let v = num.to_be_bytes().to_vec();
+        self.issuance_nums.set(&key(code).c(d!())?, Some(v)).c(d!())
+    }
+
+    /// Set the TXO of `sid`, `None` for a spent one
+    #[inline(always)]
+    pub fn set_utxo(&mut self, sid: TxoSID, utxo: Option<&TxOutput>) {
+        self.utxos.set(sid, utxo.map(leaf_hash));
+    }
+
+    /// The proof of the TXO of `sid` against `StateDomainHashes::utxos`
+    #[inline(always)]
+    pub fn prove_utxo(&self, sid: TxoSID) -> SparseMerkleProof {
+        self.utxos.prove(sid)
+    }
+
+    /// Write the changes of the block to the stores
+    pub fn commit(&mut self) -> Result<()> {
+        self.utxos.commit();
+        self.asset_types.commit().c(d!())?;
+        self.issuance_nums.commit().c(d!()).map(|_| ())
+    }
+
+    /// The roots of the trees, with the hash of `staking`
+    pub fn domains(&self, staking: &Staking) -> StateDomainHashes {
+        StateDomainHashes {
+            version: STATE_DOMAINS_VERSION,
+            asset_types: self.asset_types.merkle_root().unwrap_or(ZERO_DIGEST),
+            issuance_nums: self.issuance_nums.merkle_root().unwrap_or(ZERO_DIGEST),
+            utxos: self.utxos.root(),
+            staking: HashOf::new(staking),
+        }
+    }
+}
+
+// the asset codes are 32 bytes long, same as the keys of the trees
+#[inline(always)]
+fn key(code: &AssetTypeCode) -> Result<Key> {
+    Key::from_bytes(code.val.0.to_vec()).c(d!())
+}
@@ -1,13 +1,25 @@
 #![cfg(test)]
 #![allow(missing_docs)]
 use {
-    super::{helpers::*, *},
+    super::{
+        helpers::*,
+        verify::{diff_dumps, diff_traces},
+        *,
+    },
     crate::{
         data_model::{
-            get_abar_commitment, AssetRules, AssetTypeCode, IssueAsset, IssueAssetBody,
+            get_abar_commitment, AnchorData, AnchorDataBody, AnchorLabel, AssetMetadata,
+            AssetRules, AssetTypeCode, CustomAssetPolicy, IssueAsset, IssueAssetBody,
             IssuerKeyPair, Memo, Operation, Transaction, TransferAsset,
-            TransferAssetBody, TransferType, TxOutput, TxnEffect, TxoRef, TxoSID,
-            ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY, TX_FEE_MIN,
+            TransferAssetBody, TransferType, TxOutput, TxnEffect, TxnRejection, TxoRef,
+            TxoSID, UpdateTransferPolicy, UpdateTransferPolicyBody,
+            ANCHOR_LABEL_MAX_LEN, ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY,
+            MAX_ANCHORS_PER_TXN, MAX_ASSET_NAME_LEN, MAX_ASSET_SYMBOL_LEN,
+            MAX_ASSET_URL_LEN, STATE_DOMAINS_VERSION, TX_FEE_MIN,
+        },
+        staking::{
+            features::{Feature, FeatureSet},
+            params::ChainParams,
         },
         store::{helpers::create_definition_transaction, utils::fra_gen_initial_tx},
     },
@@ -23,7 +35,11 @@ use {
                 asset_record::{
                     build_blind_asset_record, open_blind_asset_record, AssetRecordType,
                 },
-                structs::{AssetRecord, AssetRecordTemplate},
+                structs::{
+                    AssetRecord, AssetRecordTemplate, AssetTracerKeyPair, TracingPolicy,
+                    XfrAmount, XfrAssetType,
+                },
+                trace_assets,
             },
         },
         BlindAssetRecord, XfrKeyPair,
@@ -61,6 +77,7 @@ fn test_compute_and_save_block_hash() {
         txo_count: 0,
         pulse_count: 0,
         staking: None,
+        domains: None,
     };
 
     let count_original = ledger_state.status.block_commit_count;
@@ -68,6 +85,14 @@ fn test_compute_and_save_block_hash() {
     let b = ledger_state.start_block().unwrap();
     ledger_state.finish_block(b).unwrap();
     data.block_merkle = ledger_state.block_merkle.read().get_root_hash();
+    if CFG.checkpoint.state_domains_height == 0 {
+        let domains = ledger_state
+            .state_trees
+            .read()
+            .domains(ledger_state.get_staking());
+        assert_eq!(domains.version, STATE_DOMAINS_VERSION);
+        data.domains = Some(domains);
+    }
 
     let first_hash = data.compute_commitment();
 
@@ -112,7 +137,7 @@ fn test_asset_creation_valid() {
     let asset_create = asset_creation_operation(&asset_body, &keypair);
     let seq_id = state.get_block_commit_count();
     let tx = Transaction::from_operation(Operation::DefineAsset(asset_create), seq_id);
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
     {
         let mut block = state.start_block().unwrap();
         state.apply_transaction(&mut block, effect).unwrap();
@@ -123,6 +148,100 @@ fn test_asset_creation_valid() {
     assert_eq!(0, state.get_asset_type(&token_code).unwrap().units);
 }
 
+#[test]
+fn test_state_domains() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    if 0 < CFG.checkpoint.state_domains_height {
+        return;
+    }
+
+    // the first block activates the domains
+    let b = state.start_block().unwrap();
+    state.finish_block(b).unwrap();
+    let domains =
+        |s: &LedgerState| s.get_state_commitment_data().unwrap().domains.clone();
+    let before = domains(&state).unwrap();
+
+    let keypair = build_keys(&mut prng);
+    let code = AssetTypeCode::gen_random();
+    let seq_id = state.get_block_commit_count();
+    let tx = create_definition_transaction(
+        &code,
+        &keypair,
+        AssetRules::default(),
+        None,
+        seq_id,
+    )
+    .unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
+    let mut block = state.start_block().unwrap();
+    state.apply_transaction(&mut block, effect).unwrap();
+    state.finish_block(block).unwrap();
+
+    let after = domains(&state).unwrap();
+    assert_ne!(before.asset_types, after.asset_types);
+    assert_eq!(before.issuance_nums, after.issuance_nums);
+    assert_eq!(before.utxos, after.utxos);
+    assert_eq!(after, state.state_trees.read().domains(state.get_staking()));
+    assert!(state.check_stores().is_empty());
+}
+
+#[test]
+fn test_utxo_tree_backfill() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    if 0 < CFG.checkpoint.state_domains_height {
+        return;
+    }
+
+    let issuer = build_keys(&mut prng);
+    let recipient = build_keys(&mut prng).get_pk();
+    let code = AssetTypeCode::gen_random();
+    let tx = create_definition_transaction(
+        &code,
+        &issuer,
+        AssetRules::default(),
+        None,
+        state.get_block_commit_count(),
+    )
+    .unwrap();
+    apply_transaction(&mut state, tx);
+    for i in 0..3 {
+        let (tx, _) =
+            create_issue_and_transfer_txn(&mut state, &code, 10, &issuer, &recipient, i);
+        apply_transaction(&mut state, tx);
+    }
+
+    // the whole UTXO set is in the tree once activated
+    let next_txo = state.get_next_txo().0;
+    assert_eq!(state.status.utxo_tree_cursor, next_txo);
+    let root = state
+        .get_state_commitment_data()
+        .unwrap()
+        .domains
+        .as_ref()
+        .unwrap()
+        .utxos;
+
+    // filled again one TXO at a time, as before the activation
+    let dir = globutils::fresh_tmp_dir().to_string_lossy().into_owned();
+    *state.state_trees.write() = StateTrees::open(&format!("{dir}/trees")).unwrap();
+    state.status.utxo_tree_cursor = 0;
+    for sid in 1..=next_txo {
+        state.backfill_utxo_tree(1);
+        assert_eq!(state.status.utxo_tree_cursor, sid);
+    }
+    state.backfill_utxo_tree(1);
+    assert_eq!(state.status.utxo_tree_cursor, next_txo);
+
+    state.state_trees.write().commit().unwrap();
+    assert_eq!(
+        state.state_trees.read().domains(state.get_staking()).utxos,
+        root
+    );
+}
+
 // Change the signature to have the wrong public key
 #[test]
 fn test_asset_creation_invalid_public_key() {
@@ -145,7 +264,181 @@ fn test_asset_creation_invalid_public_key() {
     asset_create.pubkey.key = *keypair.get_pk_ref();
     let tx = Transaction::from_operation(Operation::DefineAsset(asset_create), 0);
 
-    assert!(TxnEffect::compute_effect(tx).is_err());
+    assert!(TxnEffect::compute_effect(tx, &FeatureSet::all()).is_err());
+}
+
+#[test]
+fn test_asset_metadata() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    let keypair = build_keys(&mut prng);
+    let mut rules = AssetRules::default();
+    rules.set_decimals(6).unwrap();
+    let metadata = AssetMetadata {
+        decimals: 6,
+        symbol: "TST".to_owned(),
+        name: "test token".to_owned(),
+        url: "https://example.com/tst.json".to_owned(),
+        content_hash: "ab".repeat(32),
+    };
+
+    let code = AssetTypeCode::gen_random();
+    let seq_id = state.get_block_commit_count();
+    let define = |metadata: &AssetMetadata| {
+        let mut body =
+            asset_creation_body(&code, keypair.get_pk_ref(), rules.clone(), None, None);
+        // bypass `set_metadata` to reach the checks of `TxnEffect`
+        body.asset.metadata = Some(metadata.clone());
+        let op = asset_creation_operation(&body, &keypair);
+        Transaction::from_operation(Operation::DefineAsset(op), seq_id)
+    };
+
+    for invalid in [
+        AssetMetadata {
+            decimals: 2,
+            ..metadata.clone()
+        },
+        AssetMetadata {
+            symbol: "T-ST".to_owned(),
+            ..metadata.clone()
+        },
+        AssetMetadata {
+            name: "n".repeat(MAX_ASSET_NAME_LEN + 1),
+            ..metadata.clone()
+        },
+        AssetMetadata {
+            content_hash: "xyz".to_owned(),
+            ..metadata.clone()
+        },
+        AssetMetadata {
+            content_hash: "a".repeat(63),
+            ..metadata.clone()
+        },
+        AssetMetadata {
+            content_hash: "g".repeat(64),
+            ..metadata.clone()
+        },
+        AssetMetadata {
+            symbol: String::new(),
+            ..metadata.clone()
+        },
+        AssetMetadata {
+            symbol: "S".repeat(MAX_ASSET_SYMBOL_LEN + 1),
+            ..metadata.clone()
+        },
+        AssetMetadata {
+            url: "u".repeat(MAX_ASSET_URL_LEN + 1),
+            ..metadata.clone()
+        },
+    ] {
+        let e =
+            TxnEffect::compute_effect(define(&invalid), &FeatureSet::all()).unwrap_err();
+        assert_eq!(e.kind(), Some(TxnValidationError::PolicyViolation));
+        assert_eq!(e.code(), 10);
+        let mut body =
+            asset_creation_body(&code, keypair.get_pk_ref(), rules.clone(), None, None);
+        assert!(body.set_metadata(invalid).is_err());
+    }
+
+    // the limits are inclusive, the content hash is optional
+    let longest = AssetMetadata {
+        symbol: "S".repeat(MAX_ASSET_SYMBOL_LEN),
+        name: "n".repeat(MAX_ASSET_NAME_LEN),
+        url: "u".repeat(MAX_ASSET_URL_LEN),
+        content_hash: String::new(),
+        ..metadata.clone()
+    };
+    assert!(TxnEffect::compute_effect(define(&longest), &FeatureSet::all()).is_ok());
+
+    // refused up to the activation height, included
+    let params = ChainParams::default();
+    let h = params.feature_height(Feature::AssetMetadata);
+    let e =
+        TxnEffect::compute_effect(define(&metadata), &FeatureSet::at_height(&params, h))
+            .unwrap_err();
+    assert_eq!(e.code(), 1);
+    assert!(TxnEffect::compute_effect(
+        define(&metadata),
+        &FeatureSet::at_height(&params, h + 1)
+    )
+    .is_ok());
+
+    let features = state.get_staking().active_features();
+    let effect = TxnEffect::compute_effect(define(&metadata), &features);
+    if !features.is_active(Feature::AssetMetadata) {
+        assert!(effect.is_err());
+        return;
+    }
+    let mut block = state.start_block().unwrap();
+    state
+        .apply_transaction(&mut block, effect.unwrap())
+        .unwrap();
+    state.finish_block(block).unwrap();
+
+    let token_code = AssetTypeCode::from_prefix_and_raw_asset_type_code(
+        AssetTypePrefix::UserDefined,
+        &code,
+        &CFG.checkpoint,
+        state.get_tendermint_height(),
+    );
+    let asset = state.get_asset_type(&token_code).unwrap();
+    assert_eq!(asset.properties.metadata, Some(metadata));
+    assert!(!asset.has_transfer_restrictions());
+}
+
+#[test]
+fn test_anchor_data() {
+    let mut prng = ChaChaRng::from_entropy();
+    let keypair = build_keys(&mut prng);
+    let anchors = |label: &AnchorLabel, n: usize| {
+        let mut tx = Transaction::from_seq_id(0);
+        for i in 0..n {
+            let body = AnchorDataBody {
+                hash: [i as u8; 32],
+                label: label.clone(),
+                no_replay_token: tx.body.no_replay_token,
+            };
+            let op = AnchorData::new(body, &keypair);
+            tx.add_operation(Operation::AnchorData(Box::new(op)));
+        }
+        tx
+    };
+    let label = AnchorLabel {
+        kind: "invoice".to_owned(),
+        name: "2026-0042".to_owned(),
+    };
+
+    assert!(TxnEffect::compute_effect(anchors(&label, 1), &FeatureSet::all()).is_ok());
+    assert!(
+        TxnEffect::compute_effect(anchors(&label, 1), &FeatureSet::default()).is_err()
+    );
+    assert!(TxnEffect::compute_effect(
+        anchors(&label, MAX_ANCHORS_PER_TXN + 1),
+        &FeatureSet::all()
+    )
+    .is_err());
+
+    for invalid in [
+        AnchorLabel {
+            kind: String::new(),
+            ..label.clone()
+        },
+        AnchorLabel {
+            name: "n".repeat(ANCHOR_LABEL_MAX_LEN),
+            ..label.clone()
+        },
+    ] {
+        assert!(
+            TxnEffect::compute_effect(anchors(&invalid, 1), &FeatureSet::all()).is_err()
+        );
+    }
+
+    // signed by another key
+    let mut tx = anchors(&label, 1);
+    if let Operation::AnchorData(op) = &mut tx.body.operations[0] {
+        op.pubkey = *build_keys(&mut prng).get_pk_ref();
+    }
+    assert!(TxnEffect::compute_effect(tx, &FeatureSet::all()).is_err());
 }
 
 #[test]
@@ -172,7 +465,7 @@ fn test_asset_transfer() {
         &CFG.checkpoint,
         ledger.get_tendermint_height(),
     );
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
     {
         let mut block = ledger.start_block().unwrap();
         ledger.apply_transaction(&mut block, effect).unwrap();
@@ -200,6 +493,8 @@ fn test_asset_transfer() {
                     id: None,
                     record: BlindAssetRecord::from_noah(&ba),
                     lien: None,
+                    cosig_rules: None,
+                    lock_until: 0,
                 },
                 None,
             ),
@@ -208,6 +503,8 @@ fn test_asset_transfer() {
                     id: None,
                     record: BlindAssetRecord::from_noah(&ba.clone()),
                     lien: None,
+                    cosig_rules: None,
+                    lock_until: 0,
                 },
                 None,
             ),
@@ -223,7 +520,7 @@ fn test_asset_transfer() {
     let tx = Transaction::from_operation(issue_op, ledger.get_block_commit_count());
 
     // Commit issuance to block
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
 
     let mut block = ledger.start_block().unwrap();
     let temp_sid = ledger.apply_transaction(&mut block, effect).unwrap();
@@ -244,6 +541,17 @@ fn test_asset_transfer() {
         assert!(utxo_status.status == UtxoStatus::Unspent);
     }
 
+    if let Some(domains) = ledger.get_state_commitment_data().unwrap().domains.as_ref() {
+        for txo_id in &txos {
+            let (utxo, proof) = ledger.get_utxo_proof(*txo_id).unwrap();
+            assert!(utxo.is_some());
+            assert!(proof.verify(&domains.utxos, utxo.as_ref()));
+        }
+        let (utxo, proof) = ledger.get_utxo_proof(ledger.get_next_txo()).unwrap();
+        assert!(utxo.is_none());
+        assert!(proof.verify(&domains.utxos, None));
+    }
+
     // Store txo_sids for subsequent transfers
     let txo_sid = txos[0];
     let second_txo_id = txos[1];
@@ -285,15 +593,27 @@ fn test_asset_transfer() {
 
     let mut second_transfer = transfer.clone();
     transfer.sign(&key_pair);
-    let tx = Transaction::from_operation(
-        Operation::TransferAsset(transfer),
-        ledger.get_block_commit_count(),
-    );
-
-    // Commit first transfer
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let spend = Operation::TransferAsset(transfer.clone());
+    let seq_id = ledger.get_block_commit_count();
+    let tx = Transaction::from_operation(spend.clone(), seq_id);
+
+    // A transfer creating value is refused
+    let mut forged = transfer;
+    forged.body.transfer.outputs[0].amount = XfrAmount::NonConfidential(1000);
+    let forged = Transaction::from_operation(Operation::TransferAsset(forged), seq_id);
+    let e = TxnEffect::compute_effect(forged, &FeatureSet::all()).unwrap_err();
+    assert_eq!(e.kind(), Some(TxnValidationError::AmountMismatch));
+    assert_eq!(e.code(), 9);
+
+    // Commit first transfer, its input can't be spent again in the same block
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
     let mut block = ledger.start_block().unwrap();
     let temp_sid = ledger.apply_transaction(&mut block, effect).unwrap();
+    let again = Transaction::from_operation(spend.clone(), seq_id);
+    let effect = TxnEffect::compute_effect(again, &FeatureSet::all()).unwrap();
+    let e = ledger.apply_transaction(&mut block, effect).unwrap_err();
+    assert_eq!(e.kind(), Some(TxnValidationError::InputSpent));
+    assert_eq!(e.code(), 7);
 
     let (_txn_sid, _txos) = ledger
         .finish_block(block)
@@ -309,6 +629,11 @@ fn test_asset_transfer() {
     assert!(!input_bar_proof.is_valid(state_commitment));
     assert!(utxo_status.status == UtxoStatus::Spent);
 
+    // nor in a later block
+    let again = Transaction::from_operation(spend, ledger.get_block_commit_count());
+    let e = ledger.simulate_transaction(again).unwrap_err();
+    assert_eq!(e.kind(), Some(TxnValidationError::InputSpent));
+
     // Adversary will attempt to spend the same blind asset record at another index
     second_transfer.body.inputs = vec![TxoRef::Absolute(second_txo_id)];
 
@@ -318,8 +643,8 @@ fn test_asset_transfer() {
     let tx =
         Transaction::from_operation(Operation::TransferAsset(second_transfer), seq_id);
 
-    let effect = TxnEffect::compute_effect(tx);
-    assert!(effect.is_err());
+    let e = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap_err();
+    assert_eq!(e.kind(), Some(TxnValidationError::SignatureInvalid));
 }
 
 // Sign with the wrong key.
@@ -346,7 +671,9 @@ fn test_asset_creation_invalid_signature() {
     asset_create.pubkey.key = *keypair2.get_pk_ref();
     let tx = Transaction::from_operation(Operation::DefineAsset(asset_create), 0); // OK because no ledger interaction
 
-    assert!(TxnEffect::compute_effect(tx).is_err());
+    let e = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap_err();
+    assert_eq!(e.kind(), Some(TxnValidationError::SignatureInvalid));
+    assert_eq!(e.code(), 6);
 }
 
 #[test]
@@ -373,7 +700,7 @@ fn asset_issued() {
         ledger.get_tendermint_height(),
     );
 
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
     {
         let mut block = ledger.start_block().unwrap();
         ledger.apply_transaction(&mut block, effect).unwrap();
@@ -399,6 +726,8 @@ fn asset_issued() {
                 id: None,
                 record: BlindAssetRecord::from_noah(&ba),
                 lien: None,
+                cosig_rules: None,
+                lock_until: 0,
             },
             None,
         )],
@@ -414,7 +743,7 @@ fn asset_issued() {
     let tx = Transaction::from_operation(issue_op, seq_id);
     let second_tx = tx.clone();
 
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
 
     let mut block = ledger.start_block().unwrap();
     let temp_sid = ledger.apply_transaction(&mut block, effect).unwrap();
@@ -426,7 +755,7 @@ fn asset_issued() {
         .unwrap();
 
     // shouldn't be able to replay issuance
-    let effect = TxnEffect::compute_effect(second_tx).unwrap();
+    let effect = TxnEffect::compute_effect(second_tx, &FeatureSet::all()).unwrap();
     let mut block = ledger.start_block().unwrap();
     let result = ledger.apply_transaction(&mut block, effect);
     assert!(result.is_err());
@@ -561,7 +890,7 @@ pub fn test_transferable() {
     transfer.sign(&alice);
     let seq_id = ledger.get_block_commit_count();
     let tx = Transaction::from_operation(Operation::TransferAsset(transfer), seq_id);
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
 
     let mut block = ledger.start_block().unwrap();
     let res = ledger.apply_transaction(&mut block, effect);
@@ -599,7 +928,7 @@ pub fn test_transferable() {
     transfer.sign(&alice);
     let seq_id = ledger.get_block_commit_count();
     let tx = Transaction::from_operation(Operation::TransferAsset(transfer), seq_id);
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
 
     let res = ledger.apply_transaction(&mut block, effect);
     assert!(res.is_err());
@@ -642,11 +971,250 @@ pub fn test_transferable() {
     .unwrap();
     transfer.sign(&alice);
     tx.body.operations.push(Operation::TransferAsset(transfer));
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
     let res = ledger.apply_transaction(&mut block, effect);
     assert!(res.is_err());
 }
 
+#[test]
+pub fn test_transfer_policy() {
+    let mut ledger = LedgerState::tmp_ledger();
+    let issuer = XfrKeyPair::generate(&mut ledger.get_prng());
+    let alice = XfrKeyPair::generate(&mut ledger.get_prng());
+    let bob = XfrKeyPair::generate(&mut ledger.get_prng());
+
+    let code = AssetTypeCode::gen_random();
+    let seq_id = ledger.get_block_commit_count();
+    let tx = create_definition_transaction(
+        &code,
+        &issuer,
+        AssetRules::default()
+            .set_transfer_policy(Some(CustomAssetPolicy::new(vec![alice.get_pk()])))
+            .clone(),
+        None,
+        seq_id,
+    )
+    .unwrap();
+    let new_code = AssetTypeCode::from_prefix_and_raw_asset_type_code(
+        AssetTypePrefix::UserDefined,
+        &code,
+        &CFG.checkpoint,
+        ledger.get_tendermint_height(),
+    );
+    let features = ledger.get_staking().active_features();
+    if !features.is_active(Feature::TransferPolicy) {
+        assert!(TxnEffect::compute_effect(tx, &features).is_err());
+        return;
+    }
+    apply_transaction(&mut ledger, tx);
+
+    // the issuer and alice are allowed
+    let (tx, _) = create_issue_and_transfer_txn(
+        &mut ledger,
+        &new_code,
+        100,
+        &issuer,
+        alice.get_pk_ref(),
+        0,
+    );
+    let (_, sids) = apply_transaction(&mut ledger, tx);
+    let sid = sids[0];
+
+    let transfer_to_bob = |ledger: &mut LedgerState| {
+        let bar = ledger.get_utxo_light(sid).unwrap().utxo.0.record;
+        let template = AssetRecordTemplate::with_no_asset_tracing(
+            100,
+            new_code.val,
+            AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+            bob.get_pk().into_noah(),
+        );
+        let record = AssetRecord::from_template_no_identity_tracing(
+            &mut ledger.get_prng(),
+            &template,
+        )
+        .unwrap();
+        let mut transfer = TransferAsset::new(
+            TransferAssetBody::new(
+                &mut ledger.get_prng(),
+                vec![TxoRef::Absolute(sid)],
+                &[AssetRecord::from_open_asset_record_no_asset_tracing(
+                    open_blind_asset_record(&bar.into_noah(), &None, &alice.into_noah())
+                        .unwrap(),
+                )],
+                &[record],
+                None,
+                vec![],
+                TransferType::Standard,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        transfer.sign(&alice);
+        let seq_id = ledger.get_block_commit_count();
+        let tx = Transaction::from_operation(Operation::TransferAsset(transfer), seq_id);
+        ledger.apply_block(vec![tx]).unwrap().pop().unwrap()
+    };
+    let update_policy = |ledger: &mut LedgerState, kp: &XfrKeyPair| {
+        let mut tx = Transaction::from_seq_id(ledger.get_block_commit_count());
+        let body = UpdateTransferPolicyBody {
+            asset_type: new_code,
+            policy: CustomAssetPolicy::new(vec![alice.get_pk(), bob.get_pk()]),
+            no_replay_token: tx.body.no_replay_token,
+        };
+        tx.add_operation(Operation::UpdateTransferPolicy(UpdateTransferPolicy::new(
+            body, kp,
+        )));
+        ledger.apply_block(vec![tx]).unwrap().pop().unwrap()
+    };
+
+    // bob is not in the whitelist
+    assert!(transfer_to_bob(&mut ledger).is_none());
+
+    // only the issuer can update the whitelist
+    assert!(update_policy(&mut ledger, &alice).is_none());
+    assert!(update_policy(&mut ledger, &issuer).is_some());
+    let asset = ledger.get_asset_type(&new_code).unwrap();
+    assert_eq!(
+        asset
+            .properties
+            .asset_rules
+            .transfer_policy
+            .unwrap()
+            .whitelist
+            .len(),
+        2
+    );
+
+    assert!(transfer_to_bob(&mut ledger).is_some());
+}
+
+#[test]
+pub fn test_asset_tracing() {
+    let mut ledger = LedgerState::tmp_ledger();
+    let issuer = XfrKeyPair::generate(&mut ledger.get_prng());
+    let alice = XfrKeyPair::generate(&mut ledger.get_prng());
+    let tracer = AssetTracerKeyPair::generate(&mut ledger.get_prng());
+    let policy = TracingPolicy {
+        enc_keys: tracer.enc_key.clone(),
+        asset_tracing: true,
+        identity_tracing: None,
+    };
+
+    let code = AssetTypeCode::gen_random();
+    let seq_id = ledger.get_block_commit_count();
+    let tx = create_definition_transaction(
+        &code,
+        &issuer,
+        AssetRules::default()
+            .add_tracing_policy(policy.clone())
+            .clone(),
+        None,
+        seq_id,
+    )
+    .unwrap();
+    let new_code = AssetTypeCode::from_prefix_and_raw_asset_type_code(
+        AssetTypePrefix::UserDefined,
+        &code,
+        &CFG.checkpoint,
+        ledger.get_tendermint_height(),
+    );
+    apply_transaction(&mut ledger, tx);
+
+    let h = ChainParams::default().feature_height(Feature::AssetTracing);
+    let plain = |ledger: &mut LedgerState, seq_num: u64| {
+        create_issue_and_transfer_txn(
+            ledger,
+            &new_code,
+            100,
+            &issuer,
+            alice.get_pk_ref(),
+            seq_num,
+        )
+        .0
+    };
+    let traced = |ledger: &mut LedgerState, seq_num: u64, rt: AssetRecordType| {
+        create_issue_and_transfer_txn_with_asset_tracing(
+            ledger,
+            &new_code,
+            100,
+            &issuer,
+            alice.get_pk_ref(),
+            seq_num,
+            rt,
+            policy.clone(),
+        )
+        .0
+    };
+    let conf_amount = AssetRecordType::ConfidentialAmount_NonConfidentialAssetType;
+
+    // before the feature, transfers of traced assets carry no tracing data
+    ledger.get_staking_mut().set_custom_block_height(h);
+    let tx = traced(&mut ledger, 0, conf_amount);
+    assert_eq!(ledger.simulate_transaction(tx).unwrap_err().code(), 1);
+    let tx = plain(&mut ledger, 0);
+    let (_, before) = ledger
+        .apply_block(vec![tx.clone()])
+        .unwrap()
+        .pop()
+        .unwrap()
+        .unwrap();
+
+    // and stay readable once it is active, e.g. by `get_outputs_ref`
+    assert!(TxnEffect::compute_effect(tx, &FeatureSet::all()).is_ok());
+
+    // once active, transfers must carry the tracing proofs
+    ledger.get_staking_mut().set_custom_block_height(h + 1);
+    let tx = plain(&mut ledger, 1);
+    let e = ledger.simulate_transaction(tx).unwrap_err();
+    assert_eq!(e.kind(), Some(TxnValidationError::PolicyViolation));
+
+    // and a traced asset can't get a confidential type, where it can't be traced
+    let tx = traced(
+        &mut ledger,
+        1,
+        AssetRecordType::ConfidentialAmount_ConfidentialAssetType,
+    );
+    let e = ledger.simulate_transaction(tx).unwrap_err();
+    assert_eq!(e.kind(), Some(TxnValidationError::PolicyViolation));
+
+    let tx = traced(&mut ledger, 1, conf_amount);
+    let (_, sids) = ledger
+        .apply_block(vec![tx])
+        .unwrap()
+        .pop()
+        .unwrap()
+        .unwrap();
+
+    // the issued records are spent in the same transactions
+    let tracked_sids = ledger.status.get_tracked_sids(&new_code);
+    assert_eq!(tracked_sids, [before, sids.clone()].concat());
+
+    // the tracer can decrypt the amounts of the tracked txos
+    let tracked = ledger.get_tracked_txos(&new_code);
+    assert_eq!(tracked.len(), tracked_sids.len());
+    let (au, spent) = tracked.get(&sids[0]).unwrap();
+    assert!(!spent);
+    let traced = au
+        .txn
+        .txn
+        .body
+        .operations
+        .iter()
+        .find_map(|op| match op {
+            Operation::TransferAsset(t) => {
+                Some(trace_assets(&t.body.transfer.into_noah(), &tracer).unwrap())
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert!(
+        traced
+            .iter()
+            .any(|(amount, asset_type, _, _)| *amount == 100
+                && *asset_type == new_code.val)
+    );
+}
+
 #[test]
 pub fn test_max_units() {
     let mut ledger = LedgerState::tmp_ledger();
@@ -691,7 +1259,7 @@ pub fn test_max_units() {
             AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
             &issuer,
         );
-        let effect = TxnEffect::compute_effect(tx).unwrap();
+        let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
 
         let mut block = ledger.start_block().unwrap();
         let res = ledger.apply_transaction(&mut block, effect);
@@ -706,7 +1274,7 @@ pub fn test_max_units() {
             AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
             &issuer,
         );
-        let effect = TxnEffect::compute_effect(tx).unwrap();
+        let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
         ledger.apply_transaction(&mut block, effect).unwrap();
         ledger.finish_block(block).unwrap();
 
@@ -719,7 +1287,7 @@ pub fn test_max_units() {
             AssetRecordType::ConfidentialAmount_NonConfidentialAssetType,
             &issuer,
         );
-        let effect = TxnEffect::compute_effect(tx).unwrap();
+        let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
         let mut block = ledger.start_block().unwrap();
         let res = ledger.apply_transaction(&mut block, effect);
         assert!(res.is_err());
@@ -797,7 +1365,7 @@ fn test_check_fee_with_ledger() {
     let tx = fra_gen_initial_tx(&fra_owner_kp);
     assert!(tx.check_fee());
 
-    let effect = TxnEffect::compute_effect(tx.clone()).unwrap();
+    let effect = TxnEffect::compute_effect(tx.clone(), &FeatureSet::all()).unwrap();
     let mut block = ledger.start_block().unwrap();
     let tmp_sid = ledger.apply_transaction(&mut block, effect).unwrap();
     let txo_sid = ledger
@@ -813,13 +1381,13 @@ fn test_check_fee_with_ledger() {
     );
     assert!(tx2.check_fee());
 
-    let effect = TxnEffect::compute_effect(tx2).unwrap();
+    let effect = TxnEffect::compute_effect(tx2, &FeatureSet::all()).unwrap();
     let mut block = ledger.start_block().unwrap();
     ledger.apply_transaction(&mut block, effect).unwrap();
     ledger.finish_block(block).unwrap();
 
     // Ensure that FRA can only be defined only once.
-    let effect = TxnEffect::compute_effect(tx).unwrap();
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
     let mut block = ledger.start_block().unwrap();
     assert!(ledger.apply_transaction(&mut block, effect).is_err());
 }
@@ -906,3 +1474,708 @@ fn test_update_anon_stores() {
     assert_eq!(state.status.owned_ax_utxos.get(&new_com), Some(ATxoSID(0)));
     assert_eq!(state.status.owned_ax_utxos.get(&new_com2), Some(ATxoSID(1)));
 }
+
+#[test]
+fn test_ledger_status_migration() {
+    let state = LedgerState::tmp_ledger();
+    assert_eq!(state.status.version(), LEDGER_STATUS_VERSION);
+
+    // a snapshot written before the `version` field existed
+    let mut legacy = serde_json::to_value(&state.status).unwrap();
+    legacy.as_object_mut().unwrap().remove("version");
+    legacy
+        .as_object_mut()
+        .unwrap()
+        .insert("field_from_the_future".to_owned(), serde_json::Value::Null);
+    let mut status: LedgerStatus = serde_json::from_value(legacy).unwrap();
+    assert_eq!(status.version(), 0);
+
+    status.migrate().unwrap();
+    assert_eq!(status, state.status);
+
+    // snapshots of a newer version are refused
+    status.version = LEDGER_STATUS_VERSION + 1;
+    assert!(status.migrate().is_err());
+    assert_eq!(status.version(), LEDGER_STATUS_VERSION + 1);
+}
+
+#[test]
+fn test_ledger_status_zstd() {
+    let state = LedgerState::tmp_ledger();
+
+    let plain = state.status.encode(0).unwrap();
+    assert_eq!(plain, serde_json::to_vec(&state.status).unwrap());
+    let compressed = state.status.encode(3).unwrap();
+    assert!(compressed.len() < plain.len());
+
+    // both are accepted, so the snapshots written before keep loading
+    assert_eq!(LedgerStatus::decode(&plain).unwrap(), state.status);
+    assert_eq!(LedgerStatus::decode(&compressed).unwrap(), state.status);
+
+    // an encoding of a newer version is rejected
+    let mut newer = compressed.clone();
+    newer[15] += 1;
+    assert!(LedgerStatus::decode(&newer).is_err());
+    assert!(LedgerStatus::decode(&compressed[..12]).is_err());
+}
+
+#[test]
+fn test_cache_staking_event() {
+    use crate::staking::StakingEvent;
+
+    let mut prng = ChaChaRng::from_entropy();
+    let v = XfrKeyPair::generate(&mut prng).get_pk();
+    let d = XfrKeyPair::generate(&mut prng).get_pk();
+    let mut cache = api_cache::ApiCache::new("test_cache_staking_event/");
+
+    // first vote power of a new validator
+    cache.cache_staking_event(1, StakingEvent::Unjailed { validator: v });
+    assert!(cache.staking_validator_events.get(&v).is_none());
+
+    cache.cache_staking_event(
+        2,
+        StakingEvent::Slashed {
+            validator: v,
+            delegator: Some(d),
+            amount: 10,
+        },
+    );
+    cache.cache_staking_event(2, StakingEvent::Jailed { validator: v });
+    cache.cache_staking_event(3, StakingEvent::Unjailed { validator: v });
+
+    let hist = cache.staking_validator_events.get(&v).unwrap();
+    assert_eq!(
+        hist.iter().map(|r| r.height).collect::<Vec<_>>(),
+        vec![2, 2, 3]
+    );
+    assert_eq!(hist[2].event, StakingEvent::Unjailed { validator: v });
+    assert_eq!(cache.staking_delegator_events.get(&d).unwrap().len(), 1);
+    assert!(cache.staking_jailed.get(&v).is_none());
+}
+
+#[test]
+fn test_txn_expiry() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+
+    let keypair = build_keys(&mut prng);
+    let code = AssetTypeCode::gen_random();
+    let asset_body = asset_creation_body(
+        &code,
+        keypair.get_pk_ref(),
+        AssetRules::default(),
+        None,
+        None,
+    );
+    let asset_create = asset_creation_operation(&asset_body, &keypair);
+    let seq_id = state.get_block_commit_count();
+    let mut tx =
+        Transaction::from_operation(Operation::DefineAsset(asset_create), seq_id);
+
+    // can not expire before its own seq_id
+    if 0 < seq_id {
+        tx.set_expiry(seq_id - 1);
+        assert!(TxnEffect::compute_effect(tx.clone(), &FeatureSet::all()).is_err());
+    }
+
+    tx.set_expiry(seq_id);
+    assert!(!tx.is_expired(seq_id));
+    assert!(tx.is_expired(seq_id + 1));
+    assert!(TxnEffect::compute_effect(tx.clone(), &FeatureSet::default()).is_err());
+    let effect = TxnEffect::compute_effect(tx, &FeatureSet::all()).unwrap();
+
+    // the ledger checks the expiry once the feature is active
+    if !state
+        .get_staking()
+        .active_features()
+        .is_active(Feature::TxnExpiry)
+    {
+        return;
+    }
+    state.status.incr_block_commit_count();
+    let mut block = state.start_block().unwrap();
+    assert!(state.apply_transaction(&mut block, effect).is_err());
+}
+
+#[test]
+fn test_apply_block() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    let keypair = build_keys(&mut prng);
+    let seq_id = state.get_block_commit_count();
+
+    let mut txns = (0..3)
+        .map(|_| {
+            let asset_body = asset_creation_body(
+                &AssetTypeCode::gen_random(),
+                keypair.get_pk_ref(),
+                AssetRules::default(),
+                None,
+                None,
+            );
+            let asset_create = asset_creation_operation(&asset_body, &keypair);
+            Transaction::from_operation(Operation::DefineAsset(asset_create), seq_id)
+        })
+        .collect::<Vec<_>>();
+
+    // duplicated no replay token
+    txns.push(txns[0].clone());
+
+    let next_txn = state.get_next_txn().0;
+    let res = state.apply_block(txns).unwrap();
+    assert_eq!(res.len(), 4);
+    assert!(res[3].is_none());
+    assert_eq!(
+        res.iter()
+            .take(3)
+            .map(|r| r.as_ref().unwrap().0 .0)
+            .collect::<Vec<_>>(),
+        (next_txn..next_txn + 3).collect::<Vec<_>>()
+    );
+    assert_eq!(state.get_next_txn().0, next_txn + 3);
+}
+
+#[test]
+fn test_simulate_transaction() {
+    let mut ledger = LedgerState::tmp_ledger();
+    let issuer = XfrKeyPair::generate(&mut ledger.get_prng());
+    let alice = XfrKeyPair::generate(&mut ledger.get_prng());
+
+    let code = AssetTypeCode::gen_random();
+    let seq_id = ledger.get_block_commit_count();
+    let tx = create_definition_transaction(
+        &code,
+        &issuer,
+        AssetRules::default(),
+        None,
+        seq_id,
+    )
+    .unwrap();
+    let new_code = AssetTypeCode::from_prefix_and_raw_asset_type_code(
+        AssetTypePrefix::UserDefined,
+        &code,
+        &CFG.checkpoint,
+        ledger.get_tendermint_height(),
+    );
+    apply_transaction(&mut ledger, tx);
+
+    let (tx, _) = create_issue_and_transfer_txn(
+        &mut ledger,
+        &new_code,
+        100,
+        &issuer,
+        alice.get_pk_ref(),
+        0,
+    );
+    let next_txn = ledger.get_next_txn();
+    let next_txo = ledger.get_next_txo();
+    let simulated = ledger.simulate_transaction(tx.clone()).unwrap();
+    assert_eq!(ledger.get_next_txn(), next_txn);
+    assert_eq!(ledger.get_next_txo(), next_txo);
+
+    let applied = ledger.apply_block(vec![tx.clone()]).unwrap().pop().unwrap();
+    assert_eq!(Some(simulated), applied);
+
+    // the issuance sequence number has been used
+    assert!(ledger.simulate_transaction(tx).is_err());
+    let (tx, _) = create_issue_and_transfer_txn(
+        &mut ledger,
+        &new_code,
+        100,
+        &issuer,
+        alice.get_pk_ref(),
+        0,
+    );
+    let e = ledger.simulate_transaction(tx).unwrap_err();
+    assert_eq!(e.kind(), Some(TxnValidationError::IssuanceReplay));
+    assert_eq!(e.code(), 8);
+}
+
+#[test]
+fn test_sids_order() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut ledger = LedgerState::tmp_ledger();
+    let pk = XfrKeyPair::generate(&mut prng).get_pk();
+    let code = AssetTypeCode::gen_random();
+
+    // the order of the sets does not leak into the responses
+    let sids = (0..64).map(TxoSID).collect::<Vec<_>>();
+    let set = sids.iter().rev().copied().collect::<HashSet<_>>();
+    ledger.status.owned_utxos.insert(pk, set.clone());
+    ledger.status.tracked_sids.insert(code, set);
+    assert_eq!(ledger.status.get_owned_utxos(&pk), sids);
+    assert_eq!(ledger.status.get_tracked_sids(&code), sids);
+
+    // unknown or emptied
+    let other = XfrKeyPair::generate(&mut prng).get_pk();
+    assert!(ledger.status.get_owned_utxos(&other).is_empty());
+    ledger.status.owned_utxos.insert(pk, HashSet::new());
+    assert!(ledger.status.get_owned_utxos(&pk).is_empty());
+}
+
+#[test]
+fn test_rejection_codes() {
+    let codes = TxnValidationError::ALL
+        .iter()
+        .map(|k| k.code())
+        .collect::<Vec<_>>();
+    assert_eq!(codes, (6..=10).collect::<Vec<_>>());
+
+    // the kind is carried by the type, not found in the message
+    let e = TxnRejection::from(eg!(TxnValidationError::InputSpent.to_string()));
+    assert_eq!(e.kind(), None);
+    assert_eq!(e.code(), 1);
+    let e = TxnValidationError::InputSpent.reject(eg!("spent"));
+    assert_eq!(e.kind(), Some(TxnValidationError::InputSpent));
+    assert_eq!(e.code(), 7);
+    assert!(e.to_string().starts_with("rejected(input_spent): "));
+
+    // an unclassified rejection stays so through `?`
+    let wrapped = || -> TxnResult<()> {
+        Err::<(), _>(eg!("unclassified")).c(d!())?;
+        Ok(())
+    };
+    assert_eq!(wrapped().unwrap_err().code(), 1);
+    let classified = || -> TxnResult<()> {
+        Err::<(), _>(eg!("unclassified")).reject(TxnValidationError::PolicyViolation)?;
+        Ok(())
+    };
+    assert_eq!(
+        classified().unwrap_err().kind(),
+        Some(TxnValidationError::PolicyViolation)
+    );
+}
+
+#[test]
+fn test_snapshot_export_import() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    let keypair = build_keys(&mut prng);
+    let asset_body = asset_creation_body(
+        &AssetTypeCode::gen_random(),
+        keypair.get_pk_ref(),
+        AssetRules::default(),
+        None,
+        None,
+    );
+    let asset_create = asset_creation_operation(&asset_body, &keypair);
+    let tx = Transaction::from_operation(
+        Operation::DefineAsset(asset_create),
+        state.get_block_commit_count(),
+    );
+    apply_transaction(&mut state, tx);
+
+    let tmp = globutils::fresh_tmp_dir().to_string_lossy().into_owned();
+    let path = format!("{tmp}/ledger.snapshot");
+    let header = state.export_snapshot(&path, 3).unwrap();
+    assert!(header.zstd);
+    assert_eq!(header.block_commit_count, state.get_block_commit_count());
+    assert_eq!(
+        header.state_commitment,
+        state.status.state_commitment_versions.last()
+    );
+
+    let target = format!("{tmp}/restored");
+    let imported = LedgerState::import_snapshot(&path, &target).unwrap();
+    assert_eq!(imported.digest, header.digest);
+    for e in header.entries.iter() {
+        let len = fs::metadata(format!("{target}/{}", e.path)).unwrap().len();
+        assert_eq!(len, e.size);
+    }
+
+    // the target is not empty now
+    assert!(LedgerState::import_snapshot(&path, &target).is_err());
+
+    // the state commitment is computed again from the trees
+    assert!(state.verify_snapshot(&header).is_ok());
+    let mut forged = header.clone();
+    forged.state_commitment = Some(HashOf::new(&None));
+    assert!(state.verify_snapshot(&forged).is_err());
+
+    // the header length is bounded
+    let huge = format!("{tmp}/huge.snapshot");
+    fs::write(
+        &huge,
+        [&b"FRALSNAP"[..], &u64::MAX.to_be_bytes()[..]].concat(),
+    )
+    .unwrap();
+    assert!(LedgerState::read_snapshot_header(&huge).is_err());
+
+    // uncompressed, same contents
+    let plain = format!("{tmp}/ledger.snapshot.plain");
+    let plain_header = state.export_snapshot(&plain, 0).unwrap();
+    assert!(!plain_header.zstd);
+    assert_eq!(plain_header.digest, header.digest);
+    let target = format!("{tmp}/restored_plain");
+    assert!(LedgerState::import_snapshot(&plain, &target).is_ok());
+
+    // the trees of the state domains are checked too
+    if header.state_commitment.is_some() && 0 == CFG.checkpoint.state_domains_height {
+        let utxo = TxOutput {
+            id: None,
+            record: BlindAssetRecord {
+                amount: XfrAmount::NonConfidential(1),
+                asset_type: XfrAssetType::NonConfidential(ASSET_TYPE_FRA),
+                public_key: keypair.get_pk(),
+            },
+            lien: None,
+            cosig_rules: None,
+            lock_until: 0,
+        };
+        state
+            .state_trees
+            .write()
+            .set_utxo(TxoSID(u64::MAX), Some(&utxo));
+        assert!(state.verify_snapshot(&header).is_err());
+        state.state_trees.write().set_utxo(TxoSID(u64::MAX), None);
+        assert!(state.verify_snapshot(&header).is_ok());
+    }
+
+    // a checkpoint is not changed by the next blocks
+    let cp = state
+        .checkpoint_snapshot(&format!("{tmp}/checkpoint"))
+        .unwrap();
+    let table = format!("{}/fake.sst", state.basedir);
+    fs::write(&table, b"table").unwrap();
+    let cp2 = state
+        .checkpoint_snapshot(&format!("{tmp}/checkpoint2"))
+        .unwrap();
+    {
+        use std::os::unix::fs::MetadataExt;
+        let linked = fs::metadata(format!("{tmp}/checkpoint2/fake.sst")).unwrap();
+        assert_eq!(linked.ino(), fs::metadata(&table).unwrap().ino());
+    }
+    fs::remove_file(&table).unwrap();
+    drop(cp2);
+    assert!(!std::path::Path::new(&format!("{tmp}/checkpoint2")).exists());
+
+    let count = state.get_block_commit_count();
+    let tx = Transaction::from_operation(
+        Operation::DefineAsset(asset_creation_operation(
+            &asset_creation_body(
+                &AssetTypeCode::gen_random(),
+                keypair.get_pk_ref(),
+                AssetRules::default(),
+                None,
+                None,
+            ),
+            &keypair,
+        )),
+        count,
+    );
+    apply_transaction(&mut state, tx);
+    assert_eq!(state.get_block_commit_count(), count + 1);
+    let cp_path = format!("{tmp}/checkpoint.snapshot");
+    let cp_header = cp.export(&cp_path, 3).unwrap();
+    assert_eq!(cp_header.block_commit_count, count);
+    assert!(
+        LedgerState::import_snapshot(&cp_path, &format!("{tmp}/restored_cp")).is_ok()
+    );
+
+    // tampered contents are rejected
+    let mut data = fs::read(&path).unwrap();
+    *data.last_mut().unwrap() ^= 1;
+    fs::write(&path, data).unwrap();
+    let target = format!("{tmp}/tampered");
+    assert!(LedgerState::import_snapshot(&path, &target).is_err());
+    assert!(!std::path::Path::new(&target).exists());
+}
+
+#[test]
+fn test_genesis_export_import() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    let issuer = build_keys(&mut prng);
+    let recipient = build_keys(&mut prng).get_pk();
+    let code = AssetTypeCode::gen_random();
+
+    let tx = create_definition_transaction(
+        &code,
+        &issuer,
+        AssetRules::default(),
+        None,
+        state.get_block_commit_count(),
+    )
+    .unwrap();
+    apply_transaction(&mut state, tx);
+    let (tx, _) =
+        create_issue_and_transfer_txn(&mut state, &code, 100, &issuer, &recipient, 0);
+    apply_transaction(&mut state, tx);
+
+    let g = state.export_genesis("old-chain").unwrap();
+    assert_eq!(
+        g.checkpoint.block_commit_count,
+        state.get_block_commit_count()
+    );
+    assert_eq!(
+        g.checkpoint.state_commitment,
+        state.get_state_commitment().0
+    );
+    let n_utxos = g.utxos.len();
+    let issuance_num = state.get_issuance_num(&code);
+    assert_eq!(state.get_status().get_owned_utxos(&recipient).len(), 1);
+
+    let bytes = serde_json::to_vec(&genesis::AppState { ledger: Some(g) }).unwrap();
+    let g = genesis::GenesisState::from_app_state(&bytes)
+        .unwrap()
+        .unwrap();
+    assert!(genesis::GenesisState::from_app_state(b"")
+        .unwrap()
+        .is_none());
+    assert!(genesis::GenesisState::from_app_state(b"{}")
+        .unwrap()
+        .is_none());
+
+    let mut restored = LedgerState::tmp_ledger();
+    restored.import_genesis(g.clone()).unwrap();
+    assert_eq!(restored.get_genesis_checkpoint(), Some(&g.checkpoint));
+    assert_eq!(restored.get_block_commit_count(), 1);
+    assert_eq!(restored.get_next_txo().0 as usize, n_utxos);
+    assert!(restored.get_asset_type(&code).is_some());
+    assert_eq!(restored.get_issuance_num(&code), issuance_num);
+
+    let owned = restored.get_status().get_owned_utxos(&recipient);
+    assert_eq!(owned.len(), 1);
+    let utxo = restored.get_utxo_light(owned[0]).unwrap();
+    assert_eq!(utxo.utxo.0.record.public_key, recipient);
+
+    // only into an empty ledger
+    assert!(restored.import_genesis(g).is_err());
+}
+
+#[test]
+fn test_state_commitment_history() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    let keypair = build_keys(&mut prng);
+
+    for _ in 0..3 {
+        let asset_body = asset_creation_body(
+            &AssetTypeCode::gen_random(),
+            keypair.get_pk_ref(),
+            AssetRules::default(),
+            None,
+            None,
+        );
+        let asset_create = asset_creation_operation(&asset_body, &keypair);
+        let tx = Transaction::from_operation(
+            Operation::DefineAsset(asset_create),
+            state.get_block_commit_count(),
+        );
+        apply_transaction(&mut state, tx);
+    }
+
+    let count = state.get_block_commit_count();
+    let hist = state.get_state_commitment_history(0, count + 10);
+    assert_eq!(hist.len() as u64, count);
+
+    // each commitment is the hash of its data, and links to the previous one
+    let mut prev = None;
+    for (h, commitment, data) in hist {
+        assert_eq!(commitment, data.compute_commitment());
+        assert_eq!(
+            Some(commitment.clone()),
+            state.get_state_commitment_at_block_height(h)
+        );
+        if let Some(prev) = prev {
+            assert_eq!(data.previous_state_commitment, prev);
+        }
+        prev = Some(commitment);
+    }
+    assert_eq!(prev, Some(state.get_state_commitment().0));
+}
+
+#[test]
+fn test_utxo_checksum_history() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    let keypair = build_keys(&mut prng);
+
+    for _ in 0..3 {
+        let asset_body = asset_creation_body(
+            &AssetTypeCode::gen_random(),
+            keypair.get_pk_ref(),
+            AssetRules::default(),
+            None,
+            None,
+        );
+        let asset_create = asset_creation_operation(&asset_body, &keypair);
+        let tx = Transaction::from_operation(
+            Operation::DefineAsset(asset_create),
+            state.get_block_commit_count(),
+        );
+        apply_transaction(&mut state, tx);
+    }
+
+    // the bitmap checksum of each version is the one in its commitment data
+    let count = state.get_block_commit_count();
+    for (h, _, data) in state.get_state_commitment_history(0, count) {
+        assert_eq!(state.get_utxo_checksum(h), Some(data.bitmap));
+    }
+    assert!(state.get_utxo_checksum(0).is_none());
+    assert!(state.get_utxo_checksum(count + 1).is_none());
+}
+
+#[test]
+fn test_verify_and_replay() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    let keypair = build_keys(&mut prng);
+
+    for _ in 0..3 {
+        let asset_body = asset_creation_body(
+            &AssetTypeCode::gen_random(),
+            keypair.get_pk_ref(),
+            AssetRules::default(),
+            None,
+            None,
+        );
+        let asset_create = asset_creation_operation(&asset_body, &keypair);
+        let tx = Transaction::from_operation(
+            Operation::DefineAsset(asset_create),
+            state.get_block_commit_count(),
+        );
+        apply_transaction(&mut state, tx);
+    }
+    assert!(state.check_stores().is_empty());
+    assert!(state.check_invariants(10).is_empty());
+
+    let tmp = globutils::fresh_tmp_dir().to_string_lossy().into_owned();
+    let path = format!("{tmp}/blocks");
+    assert_eq!(state.dump_blocks(&path).unwrap(), 3);
+    drop(state);
+
+    let mut replayed = LedgerState::tmp_ledger();
+    let (n, res) = replayed.replay_blocks(&path).unwrap();
+    assert_eq!(n, 3);
+    assert!(res.is_empty(), "{res:?}");
+
+    // a tampered block is reported, and the replay stops there
+    let mut lines = fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str::<FinalizedBlock>(l).unwrap())
+        .collect::<Vec<_>>();
+    lines[1].state.txo_count += 1;
+    let tampered = lines
+        .iter()
+        .map(|b| serde_json::to_string(b).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+    fs::write(&path, tampered).unwrap();
+
+    let mut replayed = LedgerState::tmp_ledger();
+    let (n, res) = replayed.replay_blocks(&path).unwrap();
+    assert_eq!(n, 2);
+    assert!(res.iter().all(|d| 2 == d.height));
+    assert!(res.iter().any(|d| "txo count" == d.what));
+}
+
+#[test]
+fn test_replay_traced_diff() {
+    let mut prng = ChaChaRng::from_entropy();
+    let mut state = LedgerState::tmp_ledger();
+    let keypair = build_keys(&mut prng);
+
+    for _ in 0..2 {
+        let asset_body = asset_creation_body(
+            &AssetTypeCode::gen_random(),
+            keypair.get_pk_ref(),
+            AssetRules::default(),
+            None,
+            None,
+        );
+        let asset_create = asset_creation_operation(&asset_body, &keypair);
+        let tx = Transaction::from_operation(
+            Operation::DefineAsset(asset_create),
+            state.get_block_commit_count(),
+        );
+        apply_transaction(&mut state, tx);
+    }
+    let tmp = globutils::fresh_tmp_dir().to_string_lossy().into_owned();
+    let path = format!("{tmp}/blocks");
+    assert_eq!(state.dump_blocks(&path).unwrap(), 2);
+    drop(state);
+
+    let blocks = fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str::<FinalizedBlock>(l).unwrap())
+        .collect::<Vec<_>>();
+
+    // the two sides run one after the other, the storage is global
+    let replay = |blocks: Vec<FinalizedBlock>| {
+        let mut replayed = LedgerState::tmp_ledger();
+        let traces = blocks
+            .into_iter()
+            .map(|b| replayed.replay_traced(b).unwrap())
+            .collect::<Vec<_>>();
+        (replayed, traces)
+    };
+    let (a, ta) = replay(blocks.clone());
+    let scope = ta[1].scope.clone();
+    assert_eq!(1, scope.codes.len());
+    let da = a.dump_state(&scope);
+    drop(a);
+
+    // the same blocks trace the same
+    let (_, tb) = replay(blocks.clone());
+    assert!(diff_traces(&ta[0], &tb[0]).is_empty());
+    assert!(diff_traces(&ta[1], &tb[1]).is_empty());
+
+    // the other side misses the definition of the second block
+    let mut other = blocks;
+    other[1].txns.clear();
+    let (b, tb) = replay(other);
+    assert!(diff_traces(&ta[0], &tb[0]).is_empty());
+    assert!(!diff_traces(&ta[1], &tb[1]).is_empty());
+    let db = b.dump_state(&scope);
+
+    let res = diff_dumps(&da, &db);
+    assert!(res.iter().any(|d| d.what.starts_with("asset type")));
+    assert!(res.iter().all(|d| 2 == d.height));
+}
+
+#[test]
+fn test_op_kind() {
+    let mut prng = ChaChaRng::from_entropy();
+    let keypair = build_keys(&mut prng);
+
+    let define = || {
+        let body = asset_creation_body(
+            &AssetTypeCode::gen_random(),
+            keypair.get_pk_ref(),
+            AssetRules::default(),
+            None,
+            None,
+        );
+        Operation::DefineAsset(asset_creation_operation(&body, &keypair))
+    };
+
+    let mut tx = Transaction::from_operation(define(), 0);
+    tx.add_operation(define());
+    assert_eq!(
+        api_cache::OpKind::of_txn(&tx),
+        vec![api_cache::OpKind::DefineAsset]
+    );
+
+    assert_eq!(
+        serde_json::to_string(&api_cache::OpKind::UnDelegation).unwrap(),
+        "\"undelegation\""
+    );
+    assert_eq!(
+        serde_json::to_string(&api_cache::OpKind::DefineAsset).unwrap(),
+        "\"define_asset\""
+    );
+
+    let kinds = api_cache::OpKind::parse_list("define_asset, transfer").unwrap();
+    assert_eq!(
+        kinds,
+        vec![api_cache::OpKind::DefineAsset, api_cache::OpKind::Transfer]
+    );
+    assert!(api_cache::OpKind::parse_list("staking")
+        .unwrap()
+        .contains(&api_cache::OpKind::UnDelegation));
+    assert!(api_cache::OpKind::parse_list("define_asset,nope").is_err());
+    assert!(api_cache::OpKind::parse_list(",").is_err());
+}
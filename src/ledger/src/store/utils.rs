@@ -74,6 +74,8 @@ pub fn fra_gen_initial_tx(fra_owner_kp: &XfrKeyPair) -> Transaction {
                     id: None,
                     record: BlindAssetRecord::from_noah(&ba),
                     lien: None,
+                    cosig_rules: None,
+                    lock_until: 0,
                 },
                 None,
             )
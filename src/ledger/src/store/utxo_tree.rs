@@ -0,0 +1,257 @@
+//!
+//! # Authenticated UTXO Set
+//!
+//! A sparse merkle tree of depth 64 indexed by `TxoSID`, the leaf of an
+//! unspent TXO is the hash of its record, the others are empty.
+//!
+//! A proof against the root shows that a TXO is unspent, or that it is not
+//! (spent or never created), without the whole UTXO set.
+//!
+//! The changes are staged in memory and written to the store by `commit`,
+//! along with the other state trees at the end of a block.
+//!
+
+use {
+    crate::data_model::{TxOutput, TxoSID},
+    cryptohash::sha256::{self, Digest},
+    fbnc::{new_mapx, Mapx},
+    lazy_static::lazy_static,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::collections::BTreeMap,
+};
+
+/// The depth of the tree, one level per bit of a `TxoSID`
+pub const UTXO_TREE_DEPTH: usize = 64;
+
+const EMPTY_LEAF: Digest = Digest([0; 32]);
+
+lazy_static! {
+    // the roots of the empty subtrees of each height
+    static ref EMPTY_ROOTS: Vec<Digest> = {
+        let mut roots = vec![EMPTY_LEAF];
+        for h in 0..UTXO_TREE_DEPTH {
+            roots.push(node_hash(&roots[h], &roots[h]));
+        }
+        roots
+    };
+}
+
+/// The leaf of an unspent TXO, the hash of its bincode encoding
+pub fn leaf_hash(utxo: &TxOutput) -> Digest {
+    let mut bytes = vec![0];
+    bytes.extend(pnk!(bincode::serialize(utxo)));
+    sha256::hash(&bytes)
+}
+
+#[inline(always)]
+fn node_hash(left: &Digest, right: &Digest) -> Digest {
+    let mut bytes = Vec::with_capacity(65);
+    bytes.push(1);
+    bytes.extend_from_slice(&left.0);
+    bytes.extend_from_slice(&right.0);
+    sha256::hash(&bytes)
+}
+
+// parent of `cur` at the index `idx` of its level
+#[inline(always)]
+fn parent(idx: u64, cur: &Digest, sibling: &Digest) -> Digest {
+    if 0 == idx & 1 {
+        node_hash(cur, sibling)
+    } else {
+        node_hash(sibling, cur)
+    }
+}
+
+/// The sparse merkle tree of the unspent TXOs
+#[derive(Clone)]
+pub struct UtxoTree {
+    // (height, index in the level) => root of the subtree,
+    // the empty subtrees are not stored
+    nodes: Mapx<(u8, u64), Digest>,
+    // changes not committed yet, `None` for an emptied subtree
+    staged: BTreeMap<(u8, u64), Option<Digest>>,
+}
+
+impl UtxoTree {
+    #[allow(missing_docs)]
+    pub fn new(path: &str) -> Self {
+        UtxoTree {
+            nodes: new_mapx!(path),
+            staged: BTreeMap::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn node(&self, h: usize, idx: u64) -> Digest {
+        let k = (h as u8, idx);
+        match self.staged.get(&k) {
+            Some(d) => d.unwrap_or(EMPTY_ROOTS[h]),
+            None => self.nodes.get(&k).unwrap_or(EMPTY_ROOTS[h]),
+        }
+    }
+
+    #[inline(always)]
+    fn put(&mut self, h: usize, idx: u64, d: Digest) {
+        let d = alt!(d == EMPTY_ROOTS[h], None, Some(d));
+        self.staged.insert((h as u8, idx), d);
+    }
+
+    /// Write the staged changes to the store
+    pub fn commit(&mut self) {
+        for (k, d) in std::mem::take(&mut self.staged) {
+            if let Some(d) = d {
+                self.nodes.insert(k, d);
+            } else {
+                self.nodes.remove(&k);
+            }
+        }
+    }
+
+    /// Set the leaf of `sid`, `None` for a TXO which is not unspent
+    pub fn set(&mut self, sid: TxoSID, leaf: Option<Digest>) {
+        let mut idx = sid.0;
+        let mut cur = leaf.unwrap_or(EMPTY_LEAF);
+        for h in 0..UTXO_TREE_DEPTH {
+            self.put(h, idx, cur);
+            cur = parent(idx, &cur, &self.node(h, idx ^ 1));
+            idx >>= 1;
+        }
+        self.put(UTXO_TREE_DEPTH, 0, cur);
+    }
+
+    #[allow(missing_docs)]
+    pub fn root(&self) -> Digest {
+        self.node(UTXO_TREE_DEPTH, 0)
+    }
+
+    /// The proof of the leaf of `sid` against the current root
+    pub fn prove(&self, sid: TxoSID) -> SparseMerkleProof {
+        let mut idx = sid.0;
+        let mut bitmap = 0;
+        let mut siblings = vec![];
+        for h in 0..UTXO_TREE_DEPTH {
+            let sibling = self.node(h, idx ^ 1);
+            if sibling != EMPTY_ROOTS[h] {
+                bitmap |= 1 << h;
+                siblings.push(sibling);
+            }
+            idx >>= 1;
+        }
+        SparseMerkleProof {
+            sid,
+            bitmap,
+            siblings,
+        }
+    }
+}
+
+/// The siblings on the path from a leaf up to the root
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SparseMerkleProof {
+    #[allow(missing_docs)]
+    pub sid: TxoSID,
+    /// bit `h` is set if the sibling at height `h` is not empty,
+    /// only the non-empty ones are in `siblings`
+    pub bitmap: u64,
+    /// from the bottom up
+    pub siblings: Vec<Digest>,
+}
+
+impl SparseMerkleProof {
+    /// The root computed from `leaf`, `None` if the proof is malformed
+    pub fn root(&self, leaf: Option<Digest>) -> Option<Digest> {
+        let mut siblings = self.siblings.iter();
+        let mut idx = self.sid.0;
+        let mut cur = leaf.unwrap_or(EMPTY_LEAF);
+        for h in 0..UTXO_TREE_DEPTH {
+            let sibling = if 0 == self.bitmap & (1 << h) {
+                EMPTY_ROOTS[h]
+            } else {
+                *siblings.next()?
+            };
+            cur = parent(idx, &cur, &sibling);
+            idx >>= 1;
+        }
+        alt!(siblings.next().is_none(), Some(cur), None)
+    }
+
+    /// Check that `utxo` is unspent under `root`,
+    /// or with `None`, that the TXO of `sid` is not
+    pub fn verify(&self, root: &Digest, utxo: Option<&TxOutput>) -> bool {
+        Some(*root) == self.root(utxo.map(leaf_hash))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::data_model::ASSET_TYPE_FRA,
+        rand_chacha::ChaChaRng,
+        rand_core::SeedableRng,
+        zei::{
+            noah_api::xfr::structs::{XfrAmount, XfrAssetType},
+            BlindAssetRecord, XfrKeyPair,
+        },
+    };
+
+    #[test]
+    fn utxo_tree() {
+        let dir = globutils::fresh_tmp_dir().to_string_lossy().into_owned();
+        let mut tree = UtxoTree::new(&format!("{dir}/utxo_tree"));
+        let empty = tree.root();
+        assert_eq!(empty, EMPTY_ROOTS[UTXO_TREE_DEPTH]);
+
+        let utxo = TxOutput {
+            id: None,
+            record: BlindAssetRecord {
+                amount: XfrAmount::NonConfidential(1),
+                asset_type: XfrAssetType::NonConfidential(ASSET_TYPE_FRA),
+                public_key: XfrKeyPair::generate(&mut ChaChaRng::from_entropy())
+                    .get_pk(),
+            },
+            lien: None,
+            cosig_rules: None,
+            lock_until: 0,
+        };
+        let leaf = leaf_hash(&utxo);
+        tree.set(TxoSID(5), Some(leaf));
+        tree.set(TxoSID(u64::MAX), Some(leaf));
+        let root = tree.root();
+        assert_ne!(root, empty);
+
+        let proof = tree.prove(TxoSID(5));
+        assert!(proof.verify(&root, Some(&utxo)));
+        assert!(!proof.verify(&root, None));
+        assert_eq!(proof.siblings.len(), 1);
+
+        // non-membership of a TXO next to an unspent one
+        let proof = tree.prove(TxoSID(4));
+        assert_eq!(proof.siblings.len(), 2);
+        assert!(proof.verify(&root, None));
+        assert!(!proof.verify(&root, Some(&utxo)));
+
+        let mut forged = tree.prove(TxoSID(5));
+        forged.siblings.push(leaf);
+        assert!(forged.root(Some(leaf)).is_none());
+
+        // nothing is written before the commit
+        let reopened = UtxoTree {
+            nodes: tree.nodes.clone(),
+            staged: BTreeMap::new(),
+        };
+        assert_eq!(reopened.root(), empty);
+        tree.commit();
+        assert!(tree.staged.is_empty());
+        assert_eq!(tree.root(), root);
+
+        // spent, the empty subtrees are removed
+        tree.set(TxoSID(5), None);
+        tree.set(TxoSID(u64::MAX), None);
+        assert_eq!(tree.root(), empty);
+        assert!(tree.prove(TxoSID(5)).verify(&empty, None));
+        tree.commit();
+        assert_eq!(0, tree.nodes.len());
+    }
+}
@@ -0,0 +1,511 @@
+//!
+//! # Verification of a ledger
+//!
+//! `check_invariants` is cheap enough to run on a live node,
+//! the others are for the offline verification.
+//!
+//! The fbnc storage is global to a process, so a ledger can not be replayed
+//! into another one in the same process, the verification takes two steps:
+//! 1. `check_stores` and `dump_blocks` on the ledger being verified
+//! 2. `replay_blocks` on an empty ledger, in another process
+//!
+//! Two binaries are compared by replaying the same blocks in lockstep,
+//! each in its own process, see `replay_traced`. Once the `BlockTrace`s of
+//! a block differ, the entries touched by the block are dumped on both
+//! sides by `dump_state` and compared by `diff_dumps`.
+//!
+//! NOTE: the staking state is not replayed, it is driven by tendermint
+//! rather than by the transactions, so the `staking` field of the state
+//! commitments before `remove_fake_staking_hash` and the staking hash of
+//! the state domains can not be reproduced.
+//!
+
+use {
+    super::LedgerState,
+    crate::data_model::{
+        AssetType, AssetTypeCode, FinalizedBlock, StateCommitmentData,
+        StateDomainHashes, TxnEffect, TxoSID, Utxo,
+    },
+    bitmap::SparseMap,
+    globutils::HashOf,
+    ruc::*,
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeSet,
+        fmt::{Debug, Display},
+        fs::File,
+        io::{BufRead, BufReader, BufWriter, Write},
+    },
+};
+
+/// A difference found by the verification
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Divergence {
+    /// block commit count, 0 for the whole ledger
+    pub height: u64,
+    /// what is compared
+    pub what: String,
+    /// the recorded value
+    pub expected: String,
+    /// the recomputed value
+    pub found: String,
+}
+
+impl Divergence {
+    fn new(height: u64, what: &str, expected: impl Debug, found: impl Debug) -> Self {
+        Divergence {
+            height,
+            what: what.to_owned(),
+            expected: format!("{expected:?}"),
+            found: format!("{found:?}"),
+        }
+    }
+
+    fn text(height: u64, what: String, expected: &str, found: impl Display) -> Self {
+        Divergence {
+            height,
+            what,
+            expected: expected.to_owned(),
+            found: found.to_string(),
+        }
+    }
+}
+
+/// The entries of the state touched by some blocks
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DumpScope {
+    pub txos: BTreeSet<TxoSID>,
+    pub codes: BTreeSet<AssetTypeCode>,
+}
+
+impl DumpScope {
+    /// Add the entries of `other`
+    pub fn extend(&mut self, other: &DumpScope) {
+        self.txos.extend(other.txos.iter().copied());
+        self.codes.extend(other.codes.iter().copied());
+    }
+}
+
+/// The state after a replayed block, compared between two replays
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BlockTrace {
+    /// block commit count
+    pub height: u64,
+    /// the global state commitment
+    pub commitment: Option<HashOf<Option<StateCommitmentData>>>,
+    #[allow(missing_docs)]
+    pub state: Option<StateCommitmentData>,
+    /// differences from the state recorded in the block
+    pub recorded: Vec<Divergence>,
+    /// what the block touched, the inputs, the outputs and the assets
+    pub scope: DumpScope,
+}
+
+/// Entries of the state, `None` if absent
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct StateDump {
+    pub height: u64,
+    pub utxos: Vec<(TxoSID, Option<Utxo>)>,
+    pub spent_utxos: Vec<(TxoSID, Option<Utxo>)>,
+    pub asset_types: Vec<(AssetTypeCode, Option<AssetType>)>,
+    pub issuance_nums: Vec<(AssetTypeCode, Option<u64>)>,
+}
+
+fn compare<T: Debug + PartialEq>(
+    res: &mut Vec<Divergence>,
+    height: u64,
+    what: &str,
+    expected: T,
+    found: T,
+) {
+    if expected != found {
+        res.push(Divergence::new(height, what, expected, found));
+    }
+}
+
+impl LedgerState {
+    /// Compare the UTXO bitmap, the merkle trees and the history of blocks
+    /// with the commitments recorded in the ledger status
+    pub fn check_stores(&self) -> Vec<Divergence> {
+        let mut res = vec![];
+        let count = self.get_block_commit_count();
+        compare(&mut res, 0, "block count", count, self.blocks.len() as u64);
+
+        if let Some(data) = self.status.state_commitment_data.as_ref() {
+            let bitmap = self.utxo_map.write().compute_checksum();
+            compare(&mut res, count, "utxo bitmap", data.bitmap, bitmap);
+            let txn_root = self.txn_merkle.read().get_root_hash();
+            let block_root = self.block_merkle.read().get_root_hash();
+            compare(
+                &mut res,
+                count,
+                "txn merkle root",
+                data.transaction_merkle_commitment,
+                txn_root,
+            );
+            compare(
+                &mut res,
+                count,
+                "block merkle root",
+                data.block_merkle,
+                block_root,
+            );
+            if let Some(d) = data.domains.as_ref() {
+                let found = self.state_trees.read().domains(self.get_staking());
+                let roots = |d: &StateDomainHashes| {
+                    (d.version, d.asset_types, d.issuance_nums, d.utxos)
+                };
+                compare(&mut res, count, "state domains", roots(d), roots(&found));
+            }
+            compare(
+                &mut res,
+                count,
+                "global state commitment",
+                self.status.state_commitment_versions.last(),
+                Some(data.compute_commitment()),
+            );
+        }
+
+        // every block links to the previous one
+        let mut prev = None;
+        for idx in 0..self.blocks.len() {
+            let h = idx as u64 + 1;
+            let b = match self.blocks.get(idx) {
+                Some(b) => b,
+                None => {
+                    res.push(Divergence::new(h, "block", "present", "missing"));
+                    continue;
+                }
+            };
+            if let Some(prev) = prev {
+                let what = "previous state commitment";
+                compare(&mut res, h, what, b.state.previous_state_commitment, prev);
+            }
+            let commitment = b.state.compute_commitment();
+            compare(
+                &mut res,
+                h,
+                "state commitment",
+                self.status.state_commitment_versions.get(idx),
+                Some(commitment.clone()),
+            );
+            prev = Some(commitment);
+        }
+
+        res
+    }
+
+    /// Check the consistency among the UTXO set, the UTXO bitmap,
+    /// the owners of UTXOs and the transactions, stop after `max` violations
+    pub fn check_invariants(&self, max: usize) -> Vec<Divergence> {
+        let mut res = vec![];
+        let h = self.get_block_commit_count();
+        let status = &self.status;
+
+        if let Err(e) = status.fast_invariant_check() {
+            let what = "state commitment versions".to_owned();
+            res.push(Divergence::text(h, what, "consistent", e));
+        }
+        compare(&mut res, h, "block count", h, self.blocks.len() as u64);
+        compare(
+            &mut res,
+            h,
+            "txn count",
+            status.next_txn.0 as u64,
+            self.tx_to_block_location.len() as u64,
+        );
+
+        let bytes = self.utxo_map.read().serialize(0);
+        let bitmap = match SparseMap::new(&bytes) {
+            Ok(b) => b,
+            Err(e) => {
+                let what = "utxo bitmap".to_owned();
+                res.push(Divergence::text(h, what, "readable", e));
+                return res;
+            }
+        };
+        let is_set = |sid: u64| bitmap.query(sid).unwrap_or(false);
+
+        let mut unspent = 0;
+        for (sid, utxo) in status.utxos.iter() {
+            if max <= res.len() {
+                return res;
+            }
+            unspent += 1;
+            let what = || format!("utxo {}", sid.0);
+            if status.next_txo.0 <= sid.0 {
+                res.push(Divergence::text(h, what(), "below next txo", "above"));
+            }
+            if !is_set(sid.0) {
+                res.push(Divergence::text(h, what(), "set in bitmap", "unset"));
+            }
+            if status.spent_utxos.contains_key(&sid) {
+                res.push(Divergence::text(h, what(), "unspent", "spent"));
+            }
+            let owned = status
+                .owned_utxos
+                .get(&utxo.0.record.public_key)
+                .map_or(false, |s| s.contains(&sid));
+            if !owned {
+                res.push(Divergence::text(h, what(), "owned by its key", "not"));
+            }
+        }
+        let set = (0..status.next_txo.0).filter(|sid| is_set(*sid)).count() as u64;
+        compare(&mut res, h, "unspent count in bitmap", unspent, set);
+
+        res.truncate(max);
+        res
+    }
+
+    /// Write all blocks to `path`, one json object per line
+    pub fn dump_blocks(&self, path: &str) -> Result<u64> {
+        let mut w = BufWriter::new(File::create(path).c(d!(path))?);
+        let mut n = 0;
+        for idx in 0..self.blocks.len() {
+            let b = self
+                .blocks
+                .get(idx)
+                .c(d!(format!("block {idx} is missing")))?;
+            serde_json::to_writer(&mut w, &b).c(d!())?;
+            w.write_all(b"\n").c(d!())?;
+            n += 1;
+        }
+        w.flush().c(d!())?;
+        Ok(n)
+    }
+
+    /// Replay the blocks dumped by `dump_blocks` on this ledger,
+    /// which is expected to be empty.
+    ///
+    /// Stops at the first block which diverges,
+    /// all the following ones would diverge too.
+    pub fn replay_blocks(&mut self, path: &str) -> Result<(u64, Vec<Divergence>)> {
+        let r = BufReader::new(File::open(path).c(d!(path))?);
+        let mut res = vec![];
+        let mut replayed = 0;
+        for line in r.lines() {
+            let b: FinalizedBlock = serde_json::from_str(&line.c(d!())?).c(d!())?;
+            self.replay_block(b, &mut res).c(d!())?;
+            replayed += 1;
+            if !res.is_empty() {
+                break;
+            }
+        }
+        Ok((replayed, res))
+    }
+
+    /// Replay one dumped block on this ledger, the differences from
+    /// the dumped state are pushed to `res`
+    pub fn replay_block(
+        &mut self,
+        expected: FinalizedBlock,
+        res: &mut Vec<Divergence>,
+    ) -> Result<()> {
+        let h = self.get_block_commit_count() + 1;
+
+        // the reverse of the `pulse_count` in `checkpoint`
+        let td_height = expected.state.pulse_count + h;
+        self.set_tendermint_height(td_height);
+        self.get_staking_mut().set_custom_block_height(td_height);
+
+        let mut block = self.start_block().c(d!())?;
+        let features = self.get_staking().active_features();
+        let mut applied = vec![];
+        for ft in expected.txns.iter() {
+            match TxnEffect::compute_effect(ft.txn.clone(), &features)
+                .c(d!())
+                .and_then(|te| self.apply_transaction(&mut block, te).c(d!()))
+            {
+                Ok(tmp_sid) => applied.push((tmp_sid, ft)),
+                Err(e) => res.push(Divergence {
+                    height: h,
+                    what: format!("txn {}", ft.tx_id.0),
+                    expected: "accepted".to_owned(),
+                    found: format!("rejected: {e}"),
+                }),
+            }
+        }
+
+        let tsm = self.finish_block(block).c(d!())?;
+        for (tmp_sid, ft) in applied {
+            if let Some((txn_sid, txo_sids)) = tsm.get(&tmp_sid) {
+                let what = format!("sids of txn {}", ft.tx_id.0);
+                compare(res, h, &what, (ft.tx_id, &ft.txo_ids), (*txn_sid, txo_sids));
+            }
+        }
+
+        if let Some(found) = self.status.state_commitment_data.clone() {
+            compare_state(res, h, &expected.state, &found);
+        }
+
+        Ok(())
+    }
+
+    /// Replay one dumped block like `replay_block`,
+    /// and trace the state it results in
+    pub fn replay_traced(&mut self, block: FinalizedBlock) -> Result<BlockTrace> {
+        let mut scope = DumpScope::default();
+        let features = self.get_staking().active_features();
+        for ft in block.txns.iter() {
+            // a rejected transaction is traced too, it may be accepted by the other side
+            if let Ok(te) = TxnEffect::compute_effect(ft.txn.clone(), &features) {
+                scope.txos.extend(te.input_txos.keys().copied());
+                scope.codes.extend(te.new_asset_codes.keys().copied());
+                scope.codes.extend(te.new_issuance_nums.keys().copied());
+                scope.codes.extend(te.asset_types_involved.iter().copied());
+            }
+        }
+
+        let first_txo = self.get_next_txo().0;
+        let mut recorded = vec![];
+        self.replay_block(block, &mut recorded).c(d!())?;
+        scope
+            .txos
+            .extend((first_txo..self.get_next_txo().0).map(TxoSID));
+
+        Ok(BlockTrace {
+            height: self.get_block_commit_count(),
+            commitment: self.status.state_commitment_versions.last(),
+            state: self.status.state_commitment_data.clone(),
+            recorded,
+            scope,
+        })
+    }
+
+    /// The entries of the state in `scope`
+    pub fn dump_state(&self, scope: &DumpScope) -> StateDump {
+        let status = &self.status;
+        StateDump {
+            height: self.get_block_commit_count(),
+            utxos: scope
+                .txos
+                .iter()
+                .map(|sid| (*sid, status.get_utxo(*sid)))
+                .collect(),
+            spent_utxos: scope
+                .txos
+                .iter()
+                .map(|sid| (*sid, status.get_spent_utxo(*sid)))
+                .collect(),
+            asset_types: scope
+                .codes
+                .iter()
+                .map(|code| (*code, status.get_asset_type(code)))
+                .collect(),
+            issuance_nums: scope
+                .codes
+                .iter()
+                .map(|code| (*code, status.get_issuance_num(code)))
+                .collect(),
+        }
+    }
+}
+
+/// Compare the traces of the same block by two replays,
+/// `a` is taken as the expected one
+pub fn diff_traces(a: &BlockTrace, b: &BlockTrace) -> Vec<Divergence> {
+    let mut res = vec![];
+    let h = a.height;
+    compare(&mut res, h, "block count", a.height, b.height);
+    compare(
+        &mut res,
+        h,
+        "global state commitment",
+        &a.commitment,
+        &b.commitment,
+    );
+    if let (Some(sa), Some(sb)) = (a.state.as_ref(), b.state.as_ref()) {
+        compare_state(&mut res, h, sa, sb);
+    }
+    let rejected = |t: &BlockTrace| {
+        t.recorded
+            .iter()
+            .filter(|d| d.expected == "accepted")
+            .map(|d| d.what.clone())
+            .collect::<Vec<_>>()
+    };
+    compare(&mut res, h, "rejected txns", rejected(a), rejected(b));
+    res
+}
+
+/// Compare the states dumped by two replays with the same scope,
+/// `a` is taken as the expected one
+pub fn diff_dumps(a: &StateDump, b: &StateDump) -> Vec<Divergence> {
+    fn entries<K: Debug + PartialEq, V: Debug + PartialEq>(
+        res: &mut Vec<Divergence>,
+        h: u64,
+        what: &str,
+        a: &[(K, V)],
+        b: &[(K, V)],
+    ) {
+        for ((ka, va), (kb, vb)) in a.iter().zip(b.iter()) {
+            compare(res, h, &format!("{what} {ka:?}"), (ka, va), (kb, vb));
+        }
+        compare(res, h, &format!("{what} count"), a.len(), b.len());
+    }
+
+    let mut res = vec![];
+    let h = a.height;
+    entries(&mut res, h, "utxo", &a.utxos, &b.utxos);
+    entries(&mut res, h, "spent utxo", &a.spent_utxos, &b.spent_utxos);
+    entries(&mut res, h, "asset type", &a.asset_types, &b.asset_types);
+    entries(
+        &mut res,
+        h,
+        "issuance num",
+        &a.issuance_nums,
+        &b.issuance_nums,
+    );
+    res
+}
+
+fn compare_state(
+    res: &mut Vec<Divergence>,
+    h: u64,
+    expected: &StateCommitmentData,
+    found: &StateCommitmentData,
+) {
+    compare(res, h, "utxo bitmap", expected.bitmap, found.bitmap);
+    compare(
+        res,
+        h,
+        "txn merkle root",
+        expected.transaction_merkle_commitment,
+        found.transaction_merkle_commitment,
+    );
+    compare(
+        res,
+        h,
+        "block merkle root",
+        expected.block_merkle,
+        found.block_merkle,
+    );
+    compare(
+        res,
+        h,
+        "txns in block hash",
+        &expected.txns_in_block_hash,
+        &found.txns_in_block_hash,
+    );
+    compare(res, h, "txo count", expected.txo_count, found.txo_count);
+
+    let roots = |d: &StateCommitmentData| {
+        d.domains
+            .as_ref()
+            .map(|d| (d.version, d.asset_types, d.issuance_nums, d.utxos))
+    };
+    compare(res, h, "state domains", roots(expected), roots(found));
+
+    // the staking hashes can not be reproduced, see the module doc
+    if expected.staking.is_none() && expected.domains.is_none() {
+        compare(
+            res,
+            h,
+            "state commitment",
+            expected.compute_commitment(),
+            found.compute_commitment(),
+        );
+    }
+}